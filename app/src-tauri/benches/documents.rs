@@ -0,0 +1,87 @@
+//! Baselines for the operations that slow down on very large documents.
+//! Run with `cargo bench`; each benchmark runs at 1k, 10k and 100k nodes.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+use tempfile::TempDir;
+use uuid::Uuid;
+
+use app_lib::data::{generate_test_nodes, update_op, Document, DocumentState, NodeChanges};
+use app_lib::import_export::{generate_markdown, MarkdownOptions};
+use app_lib::search::SearchIndex;
+
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+const DEPTH: usize = 6;
+
+fn bench_load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("document_load");
+    group.sample_size(10);
+    for size in SIZES {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join(Uuid::now_v7().to_string());
+        let mut doc = Document::create(dir.clone()).unwrap();
+        doc.state.nodes = generate_test_nodes(size, DEPTH);
+        doc.save_state().unwrap();
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &dir, |b, dir| {
+            b.iter(|| Document::load(dir.clone()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_apply(c: &mut Criterion) {
+    let mut group = c.benchmark_group("operation_apply");
+    for size in SIZES {
+        let mut state = DocumentState {
+            nodes: generate_test_nodes(size, DEPTH),
+        };
+        // The last node is the worst case for lookups by id
+        let target = state.nodes[size - 1].id;
+
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| {
+                let op = update_op(
+                    target,
+                    NodeChanges {
+                        content: Some("Edited".to_string()),
+                        ..Default::default()
+                    },
+                );
+                op.apply(black_box(&mut state));
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_index(c: &mut Criterion) {
+    let mut group = c.benchmark_group("index_document");
+    group.sample_size(10);
+    for size in SIZES {
+        let nodes = generate_test_nodes(size, DEPTH);
+        let index = SearchIndex::open_in_memory().unwrap();
+        let doc_id = Uuid::now_v7();
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &nodes, |b, nodes| {
+            b.iter(|| index.index_document(&doc_id, nodes).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_markdown(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate_markdown");
+    group.sample_size(10);
+    let options = MarkdownOptions::default();
+    for size in SIZES {
+        let nodes = generate_test_nodes(size, DEPTH);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &nodes, |b, nodes| {
+            b.iter(|| generate_markdown(nodes, &options))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_load, bench_apply, bench_index, bench_markdown);
+criterion_main!(benches);
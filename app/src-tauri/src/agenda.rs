@@ -0,0 +1,31 @@
+//! Daily agenda job: applies the overdue rules once a day on this machine
+//! (at startup, then whenever the local date changes) and emits
+//! `overdue-rules-applied` with the change report when anything changed.
+
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::commands::{apply_overdue_rules, AppState};
+use crate::data::load_config;
+
+const POLL: Duration = Duration::from_secs(10 * 60);
+
+pub fn start_scheduler(app: AppHandle) {
+    thread::spawn(move || loop {
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        if load_config().overdue_rules_run.as_deref() != Some(today.as_str()) {
+            match apply_overdue_rules(&app.state::<AppState>()) {
+                Ok(report) if !report.changes.is_empty() => {
+                    log::info!("Overdue rules changed {} tasks", report.changes.len());
+                    if let Err(e) = app.emit("overdue-rules-applied", &report) {
+                        log::error!("Failed to emit overdue-rules-applied event: {}", e);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("Failed to apply overdue rules: {}", e),
+            }
+        }
+        thread::sleep(POLL);
+    });
+}
@@ -0,0 +1,807 @@
+//! Live collaboration between running instances over a WebSocket.
+//!
+//! One instance hosts (listens on a port) and the others connect to it. Every
+//! op appended locally is sent to the host, which applies it and relays it to
+//! the other connected instances. Peers are announced with a `hello` message
+//! and what each one is editing is taken from its latest op, or from the
+//! presence it reports (see `presence`).
+//!
+//! A session shares one document: the host names it in its hello, and ops
+//! for any other document are dropped on both sides. Peers must present the
+//! host's token (one is generated when none is given).
+//!
+//! Remote ops are only applied in memory. The device that made an op keeps
+//! it in its own pending file, which reaches everyone else through file sync,
+//! so writing it again here would store it twice.
+
+mod presence;
+
+pub use presence::Presence;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tungstenite::protocol::WebSocketConfig;
+use tungstenite::{Message, WebSocket};
+use uuid::Uuid;
+
+use crate::data::OpRecord;
+use presence::PresenceBoard;
+
+/// How often the listener checks whether it was stopped
+const ACCEPT_POLL: Duration = Duration::from_millis(100);
+/// A peer that stops reading is dropped rather than stalling everyone else
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a reader holds the socket before letting writers in
+const READ_POLL: Duration = Duration::from_millis(50);
+/// A peer that doesn't finish the opening handshake in time is dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Largest message accepted, so a bad peer can't exhaust memory
+const MAX_MESSAGE_LEN: usize = 16 * 1024 * 1024;
+/// Path the host serves the WebSocket on
+const WS_PATH: &str = "/collab";
+
+/// Messages exchanged between instances, one per WebSocket text message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum CollabMessage {
+    /// Sent by each side when a connection opens. The host also replays the
+    /// hellos of peers that were already connected.
+    Hello {
+        device: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+        /// The document the session shares (sent by the host)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        document_id: Option<Uuid>,
+    },
+    /// An op appended to a document
+    Op { document_id: Uuid, record: Box<OpRecord> },
+    /// A device opened a document or moved focus
+    Presence(Presence),
+    /// A peer disconnected from the host
+    Left { device: String },
+}
+
+/// Another instance taking part in the session
+#[derive(Debug, Clone, Serialize)]
+pub struct CollabPeer {
+    pub device: String,
+    pub connected_at: DateTime<Utc>,
+    /// Document and node the peer last changed
+    pub document_id: Option<Uuid>,
+    pub node_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollabRole {
+    Host,
+    Client,
+}
+
+/// Collaboration status returned to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct CollabStatus {
+    /// None when no session is running
+    pub role: Option<CollabRole>,
+    /// Address being listened on (host) or connected to (client)
+    pub address: Option<String>,
+    /// Token peers must present (host only)
+    pub token: Option<String>,
+    /// The document the session shares (a client learns it from the host)
+    pub document_id: Option<Uuid>,
+    /// Whether a client is still connected to its host
+    pub connected: bool,
+    pub peers: Vec<CollabPeer>,
+}
+
+impl CollabStatus {
+    fn stopped() -> Self {
+        Self {
+            role: None,
+            address: None,
+            token: None,
+            document_id: None,
+            connected: false,
+            peers: Vec::new(),
+        }
+    }
+}
+
+/// Things the session reports to the app, from its network threads
+#[derive(Debug)]
+pub enum CollabEvent {
+    /// An op made on another instance
+    RemoteOp { document_id: Uuid, record: Box<OpRecord> },
+    /// Peers joined or left, or moved to another node
+    PeersChanged(Vec<CollabPeer>),
+    /// The presence of other devices changed (all of them are listed)
+    PresenceChanged(Vec<Presence>),
+}
+
+pub type CollabHandler = Arc<dyn Fn(CollabEvent) + Send + Sync>;
+
+struct Connection {
+    id: u64,
+    /// Shared by the reader thread, which holds it for at most `READ_POLL`
+    /// at a time, and broadcasts
+    socket: Mutex<WebSocket<TcpStream>>,
+    /// Set once the peer's hello has been accepted (host only)
+    device: Mutex<Option<String>>,
+}
+
+impl Connection {
+    fn send(&self, message: &CollabMessage) -> Result<(), String> {
+        let text = serde_json::to_string(message).map_err(|e| format!("Serialize message: {}", e))?;
+        self.socket
+            .lock()
+            .unwrap()
+            .send(Message::Text(text))
+            .map_err(|e| format!("Send message: {}", e))
+    }
+
+    /// Wait up to `READ_POLL` for the next message (None if none came)
+    fn read(&self) -> Result<Option<Message>, String> {
+        match self.socket.lock().unwrap().read() {
+            Ok(message) => Ok(Some(message)),
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn close(&self) {
+        let mut socket = self.socket.lock().unwrap();
+        let _ = socket.close(None);
+        let _ = socket.flush();
+        let _ = socket.get_ref().shutdown(Shutdown::Both);
+    }
+}
+
+fn socket_config() -> WebSocketConfig {
+    WebSocketConfig {
+        max_message_size: Some(MAX_MESSAGE_LEN),
+        max_frame_size: Some(MAX_MESSAGE_LEN),
+        ..Default::default()
+    }
+}
+
+struct Hub {
+    role: CollabRole,
+    address: String,
+    device: String,
+    /// Shared secret peers must send in their hello (host only)
+    token: Option<String>,
+    /// The document the session shares; a client learns it from the host
+    document_id: Mutex<Option<Uuid>>,
+    connections: Mutex<Vec<Arc<Connection>>>,
+    peers: Mutex<HashMap<String, CollabPeer>>,
+    /// Presence of the other devices
+    presence: Mutex<PresenceBoard>,
+    /// Presence of this device, replayed to peers that join later
+    local_presence: Mutex<Option<Presence>>,
+    next_connection_id: AtomicU64,
+    stopped: AtomicBool,
+    handler: CollabHandler,
+}
+
+impl Hub {
+    fn new(
+        role: CollabRole,
+        address: String,
+        device: String,
+        token: Option<String>,
+        document_id: Option<Uuid>,
+        handler: CollabHandler,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            role,
+            address,
+            device,
+            token,
+            document_id: Mutex::new(document_id),
+            connections: Mutex::new(Vec::new()),
+            peers: Mutex::new(HashMap::new()),
+            presence: Mutex::new(PresenceBoard::default()),
+            local_presence: Mutex::new(None),
+            next_connection_id: AtomicU64::new(0),
+            stopped: AtomicBool::new(false),
+            handler,
+        })
+    }
+
+    fn add_connection(&self, socket: WebSocket<TcpStream>) -> Result<Arc<Connection>, String> {
+        let stream = socket.get_ref();
+        stream
+            .set_write_timeout(Some(WRITE_TIMEOUT))
+            .map_err(|e| format!("Set write timeout: {}", e))?;
+        stream
+            .set_read_timeout(Some(READ_POLL))
+            .map_err(|e| format!("Set read timeout: {}", e))?;
+        let connection = Arc::new(Connection {
+            id: self.next_connection_id.fetch_add(1, Ordering::Relaxed),
+            socket: Mutex::new(socket),
+            device: Mutex::new(None),
+        });
+        self.connections.lock().unwrap().push(connection.clone());
+        Ok(connection)
+    }
+
+    /// Send a message to every connection except `except`
+    fn send_all(&self, message: &CollabMessage, except: Option<u64>) {
+        let connections = self.connections.lock().unwrap().clone();
+        for connection in connections.iter().filter(|c| Some(c.id) != except) {
+            if let Err(e) = connection.send(message) {
+                log::warn!("Collab: failed to send to connection {}: {}", connection.id, e);
+            }
+        }
+    }
+
+    /// Whether an op for `document_id` belongs to this session
+    fn shares(&self, document_id: Uuid) -> bool {
+        *self.document_id.lock().unwrap() == Some(document_id)
+    }
+
+    fn peers_changed(&self) {
+        (self.handler)(CollabEvent::PeersChanged(self.peer_list()));
+    }
+
+    fn peer_list(&self) -> Vec<CollabPeer> {
+        let mut peers: Vec<CollabPeer> = self.peers.lock().unwrap().values().cloned().collect();
+        peers.sort_by(|a, b| a.device.cmp(&b.device));
+        peers
+    }
+
+    fn presence_changed(&self) {
+        (self.handler)(CollabEvent::PresenceChanged(self.presence.lock().unwrap().list(None)));
+    }
+
+    /// Forget a device that left, telling the app
+    fn remove_peer(&self, device: &str) {
+        let was_peer = self.peers.lock().unwrap().remove(device).is_some();
+        let was_present = self.presence.lock().unwrap().remove(device);
+        if was_peer {
+            self.peers_changed();
+        }
+        if was_present {
+            self.presence_changed();
+        }
+    }
+
+    fn add_peer(&self, device: &str) {
+        let peer = CollabPeer {
+            device: device.to_string(),
+            connected_at: Utc::now(),
+            document_id: None,
+            node_id: None,
+        };
+        self.peers.lock().unwrap().insert(device.to_string(), peer);
+        self.peers_changed();
+    }
+
+    /// Handle one message from a connection. Returns false to drop it.
+    fn handle_message(&self, connection: &Connection, message: CollabMessage) -> bool {
+        let known_device = connection.device.lock().unwrap().clone();
+        match message {
+            CollabMessage::Hello {
+                device,
+                token,
+                document_id,
+            } => {
+                if self.role == CollabRole::Client {
+                    // The host introduces itself (naming the document) and
+                    // everyone already connected
+                    if document_id.is_some() {
+                        *self.document_id.lock().unwrap() = document_id;
+                    }
+                    self.add_peer(&device);
+                    return true;
+                }
+                if known_device.is_some() {
+                    return true;
+                }
+                if token != self.token {
+                    log::warn!("Collab: rejected {} (bad token)", device);
+                    return false;
+                }
+                log::info!("Collab: {} joined", device);
+                let mut greetings = vec![CollabMessage::Hello {
+                    device: self.device.clone(),
+                    token: None,
+                    document_id: *self.document_id.lock().unwrap(),
+                }];
+                greetings.extend(self.peer_list().into_iter().map(|p| CollabMessage::Hello {
+                    device: p.device,
+                    token: None,
+                    document_id: None,
+                }));
+                greetings.extend(self.local_presence.lock().unwrap().clone().map(CollabMessage::Presence));
+                greetings.extend(self.presence.lock().unwrap().list(None).into_iter().map(CollabMessage::Presence));
+                for greeting in &greetings {
+                    if connection.send(greeting).is_err() {
+                        return false;
+                    }
+                }
+                *connection.device.lock().unwrap() = Some(device.clone());
+                self.add_peer(&device);
+                self.send_all(
+                    &CollabMessage::Hello {
+                        device,
+                        token: None,
+                        document_id: None,
+                    },
+                    Some(connection.id),
+                );
+                true
+            }
+            _ if self.role == CollabRole::Host && known_device.is_none() => {
+                log::warn!("Collab: message before hello, dropping connection");
+                false
+            }
+            CollabMessage::Op { document_id, record } => {
+                if !self.shares(document_id) {
+                    log::warn!("Collab: dropping op for document {} outside the session", document_id);
+                    return true;
+                }
+                if self.role == CollabRole::Host {
+                    let relay = CollabMessage::Op {
+                        document_id,
+                        record: record.clone(),
+                    };
+                    self.send_all(&relay, Some(connection.id));
+                }
+                if let Some(device) = &record.device {
+                    if let Some(peer) = self.peers.lock().unwrap().get_mut(device) {
+                        peer.document_id = Some(document_id);
+                        peer.node_id = Some(record.op.node_id());
+                    }
+                }
+                (self.handler)(CollabEvent::RemoteOp { document_id, record });
+                self.peers_changed();
+                true
+            }
+            CollabMessage::Presence(presence) => {
+                if presence.device == self.device {
+                    return true;
+                }
+                if self.role == CollabRole::Host {
+                    self.send_all(&CollabMessage::Presence(presence.clone()), Some(connection.id));
+                }
+                if self.presence.lock().unwrap().update(presence) {
+                    self.presence_changed();
+                }
+                true
+            }
+            CollabMessage::Left { device } => {
+                // Only the host reports departures
+                if self.role == CollabRole::Client {
+                    self.remove_peer(&device);
+                }
+                true
+            }
+        }
+    }
+
+    /// Read messages from a connection until it closes, then forget it
+    fn run_connection(self: Arc<Self>, connection: Arc<Connection>) {
+        loop {
+            // Pings are answered by the socket itself
+            let message = match connection.read() {
+                Ok(Some(Message::Text(text))) => text,
+                Ok(Some(Message::Close(_))) => break,
+                Ok(_) => continue,
+                Err(e) => {
+                    if !self.stopped.load(Ordering::Relaxed) {
+                        log::info!("Collab: connection {} closed: {}", connection.id, e);
+                    }
+                    break;
+                }
+            };
+            let message = match serde_json::from_str(&message) {
+                Ok(message) => message,
+                Err(e) => {
+                    log::warn!("Collab: ignoring bad message: {}", e);
+                    continue;
+                }
+            };
+            if !self.handle_message(&connection, message) {
+                break;
+            }
+        }
+
+        connection.close();
+        self.connections.lock().unwrap().retain(|c| c.id != connection.id);
+        if self.stopped.load(Ordering::Relaxed) {
+            return;
+        }
+        match self.role {
+            CollabRole::Host => {
+                let device = connection.device.lock().unwrap().take();
+                if let Some(device) = device {
+                    log::info!("Collab: {} left", device);
+                    self.remove_peer(&device);
+                    self.send_all(&CollabMessage::Left { device }, None);
+                }
+            }
+            CollabRole::Client => {
+                log::warn!("Collab: lost connection to host {}", self.address);
+                self.peers.lock().unwrap().clear();
+                self.presence.lock().unwrap().clear();
+                self.peers_changed();
+                self.presence_changed();
+            }
+        }
+    }
+}
+
+/// A running collaboration session (hosting or connected)
+pub struct CollabSession {
+    hub: Arc<Hub>,
+    /// Local ops waiting to be sent, so appending an op never waits on the network
+    outbox: Mutex<Sender<CollabMessage>>,
+}
+
+impl CollabSession {
+    /// Listen for other instances on `port` (0 picks a free port), sharing
+    /// `document_id`. Peers must present `token` in their hello; one is
+    /// generated if none is given (see `status`).
+    pub fn host(
+        port: u16,
+        token: Option<String>,
+        document_id: Uuid,
+        device: String,
+        handler: CollabHandler,
+    ) -> Result<Self, String> {
+        let token = token
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+        let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|e| format!("Listen on port {}: {}", port, e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Configure listener: {}", e))?;
+        let address = listener
+            .local_addr()
+            .map_err(|e| format!("Listener address: {}", e))?
+            .to_string();
+        let hub = Hub::new(CollabRole::Host, address, device, Some(token), Some(document_id), handler);
+
+        let accept_hub = hub.clone();
+        thread::spawn(move || accept_loop(listener, accept_hub));
+        log::info!("Collab: hosting on {}", hub.address);
+        Ok(Self::start(hub))
+    }
+
+    /// Connect to a host at `url` (`ws://host:port[/path]`, or just `host:port`)
+    pub fn connect(url: &str, token: Option<String>, device: String, handler: CollabHandler) -> Result<Self, String> {
+        let rest = url.strip_prefix("ws://").unwrap_or(url);
+        if rest.contains("://") {
+            return Err(format!("Unsupported collaboration URL: {}", url));
+        }
+        let (host, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, WS_PATH),
+        };
+
+        let stream = TcpStream::connect(host).map_err(|e| format!("Connect to {}: {}", host, e))?;
+        stream
+            .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+            .map_err(|e| format!("Set read timeout: {}", e))?;
+        let request = format!("ws://{}{}", host, path);
+        let (socket, _) = tungstenite::client::client_with_config(request.as_str(), stream, Some(socket_config()))
+            .map_err(|e| format!("WebSocket handshake with {}: {}", host, e))?;
+        let hub = Hub::new(CollabRole::Client, host.to_string(), device, None, None, handler);
+        let connection = hub.add_connection(socket)?;
+        connection.send(&CollabMessage::Hello {
+            device: hub.device.clone(),
+            token,
+            document_id: None,
+        })?;
+
+        let reader_hub = hub.clone();
+        thread::spawn(move || reader_hub.run_connection(connection));
+        log::info!("Collab: connected to {}", host);
+        Ok(Self::start(hub))
+    }
+
+    /// Start the thread that sends queued local ops; it ends with the session
+    fn start(hub: Arc<Hub>) -> Self {
+        let (outbox, queued) = mpsc::channel();
+        let sender_hub = hub.clone();
+        thread::spawn(move || {
+            for message in queued {
+                sender_hub.send_all(&message, None);
+            }
+        });
+        Self {
+            hub,
+            outbox: Mutex::new(outbox),
+        }
+    }
+
+    /// Send a locally appended op to the other instances, if it belongs to
+    /// the shared document
+    pub fn broadcast_op(&self, document_id: Uuid, record: &OpRecord) {
+        if !self.hub.shares(document_id) {
+            return;
+        }
+        let message = CollabMessage::Op {
+            document_id,
+            record: Box::new(record.clone()),
+        };
+        let _ = self.outbox.lock().unwrap().send(message);
+    }
+
+    /// Share where this device is in the outline with the other instances
+    pub fn update_presence(&self, presence: Presence) {
+        *self.hub.local_presence.lock().unwrap() = Some(presence.clone());
+        let _ = self.outbox.lock().unwrap().send(CollabMessage::Presence(presence));
+    }
+
+    /// Presence of the other devices, optionally only those in `document_id`
+    pub fn presence(&self, document_id: Option<Uuid>) -> Vec<Presence> {
+        self.hub.presence.lock().unwrap().list(document_id)
+    }
+
+    pub fn status(&self) -> CollabStatus {
+        CollabStatus {
+            role: Some(self.hub.role),
+            address: Some(self.hub.address.clone()),
+            token: self.hub.token.clone(),
+            document_id: *self.hub.document_id.lock().unwrap(),
+            connected: self.hub.role == CollabRole::Host || !self.hub.connections.lock().unwrap().is_empty(),
+            peers: self.hub.peer_list(),
+        }
+    }
+
+    /// Close every connection and stop listening
+    pub fn stop(&self) {
+        self.hub.stopped.store(true, Ordering::Relaxed);
+        let connections = std::mem::take(&mut *self.hub.connections.lock().unwrap());
+        for connection in connections {
+            connection.close();
+        }
+        self.hub.peers.lock().unwrap().clear();
+        self.hub.presence.lock().unwrap().clear();
+    }
+}
+
+fn accept_loop(listener: TcpListener, hub: Arc<Hub>) {
+    while !hub.stopped.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                let hub = hub.clone();
+                thread::spawn(move || {
+                    if let Err(e) = accept_connection(&hub, stream) {
+                        log::warn!("Collab: rejected connection from {}: {}", addr, e);
+                    }
+                });
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL),
+            Err(e) => {
+                log::error!("Collab: accept failed: {}", e);
+                thread::sleep(ACCEPT_POLL);
+            }
+        }
+    }
+    log::info!("Collab: stopped hosting on {}", hub.address);
+}
+
+fn accept_connection(hub: &Arc<Hub>, stream: TcpStream) -> Result<(), String> {
+    stream
+        .set_nonblocking(false)
+        .map_err(|e| format!("Configure stream: {}", e))?;
+    stream
+        .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+        .map_err(|e| format!("Set read timeout: {}", e))?;
+    let socket = tungstenite::accept_with_config(stream, Some(socket_config()))
+        .map_err(|e| format!("WebSocket handshake: {}", e))?;
+    if hub.stopped.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let connection = hub.add_connection(socket)?;
+    hub.clone().run_connection(connection);
+    Ok(())
+}
+
+/// The app's collaboration session, if any
+pub struct CollabState {
+    session: Mutex<Option<CollabSession>>,
+    /// This device's presence, kept across sessions
+    local_presence: Mutex<Option<Presence>>,
+}
+
+impl CollabState {
+    pub fn new() -> Self {
+        Self {
+            session: Mutex::new(None),
+            local_presence: Mutex::new(None),
+        }
+    }
+
+    /// Replace the running session (stopping the old one)
+    pub fn set_session(&self, session: CollabSession) {
+        if let Some(presence) = self.local_presence.lock().unwrap().clone() {
+            session.update_presence(presence);
+        }
+        let mut guard = self.session.lock().unwrap();
+        if let Some(old) = guard.take() {
+            old.stop();
+        }
+        *guard = Some(session);
+    }
+
+    /// Record this device's presence and share it if a session is running
+    pub fn update_presence(&self, presence: Presence) {
+        *self.local_presence.lock().unwrap() = Some(presence.clone());
+        if let Some(session) = self.session.lock().unwrap().as_ref() {
+            session.update_presence(presence);
+        }
+    }
+
+    /// Presence of the other devices, optionally only those in `document_id`
+    pub fn presence(&self, document_id: Option<Uuid>) -> Vec<Presence> {
+        self.session
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|session| session.presence(document_id))
+            .unwrap_or_default()
+    }
+
+    pub fn stop(&self) {
+        if let Some(session) = self.session.lock().unwrap().take() {
+            session.stop();
+        }
+    }
+
+    pub fn status(&self) -> CollabStatus {
+        self.session
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or_else(CollabStatus::stopped, CollabSession::status)
+    }
+
+    pub fn broadcast_op(&self, document_id: Uuid, record: &OpRecord) {
+        if let Some(session) = self.session.lock().unwrap().as_ref() {
+            session.broadcast_op(document_id, record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::update_op;
+    use std::time::Instant;
+
+    fn channel_handler() -> (CollabHandler, mpsc::Receiver<CollabEvent>) {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        (Arc::new(move |event| drop(tx.lock().unwrap().send(event))), rx)
+    }
+
+    /// Wait for the first event matching `pred`
+    fn wait_for(rx: &mpsc::Receiver<CollabEvent>, pred: impl Fn(&CollabEvent) -> bool) -> CollabEvent {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            let event = rx.recv_timeout(left).expect("timed out waiting for collab event");
+            if pred(&event) {
+                return event;
+            }
+        }
+    }
+
+    fn peers_are<'a>(devices: &'a [&'a str]) -> impl Fn(&CollabEvent) -> bool + 'a {
+        move |event| match event {
+            CollabEvent::PeersChanged(peers) => peers.iter().map(|p| p.device.as_str()).eq(devices.iter().copied()),
+            _ => false,
+        }
+    }
+
+    fn presence(device: &str, document_id: Uuid, node_id: Uuid) -> Presence {
+        Presence {
+            device: device.to_string(),
+            document_id: Some(document_id),
+            node_id: Some(node_id),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn present_are<'a>(devices: &'a [&'a str]) -> impl Fn(&CollabEvent) -> bool + 'a {
+        move |event| match event {
+            CollabEvent::PresenceChanged(list) => list.iter().map(|p| p.device.as_str()).eq(devices.iter().copied()),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_presence_is_shared_and_replayed() {
+        let (host_handler, host_rx) = channel_handler();
+        let doc_id = Uuid::now_v7();
+        let node_id = Uuid::now_v7();
+        let host = CollabSession::host(0, None, doc_id, "host".to_string(), host_handler).unwrap();
+        let status = host.status();
+        let url = status.address.unwrap().replace("0.0.0.0", "127.0.0.1");
+        // A token is generated when none is given
+        let token = status.token;
+        assert!(token.as_ref().is_some_and(|t| !t.is_empty()));
+        host.update_presence(presence("host", doc_id, node_id));
+
+        let (a_handler, a_rx) = channel_handler();
+        let a = CollabSession::connect(&url, token.clone(), "alice".to_string(), a_handler).unwrap();
+        wait_for(&a_rx, present_are(&["host"]));
+        a.update_presence(presence("alice", doc_id, node_id));
+        wait_for(&host_rx, present_are(&["alice"]));
+
+        // A later peer learns where everyone already is
+        let (b_handler, b_rx) = channel_handler();
+        let b = CollabSession::connect(&url, token, "bob".to_string(), b_handler).unwrap();
+        wait_for(&b_rx, present_are(&["alice", "host"]));
+        assert_eq!(b.presence(Some(doc_id)).len(), 2);
+        assert!(b.presence(Some(Uuid::now_v7())).is_empty());
+
+        a.stop();
+        wait_for(&b_rx, present_are(&["host"]));
+        host.stop();
+    }
+
+    #[test]
+    fn test_ops_are_relayed_between_instances() {
+        let (host_handler, host_rx) = channel_handler();
+        let doc_id = Uuid::now_v7();
+        let host = CollabSession::host(0, Some("secret".to_string()), doc_id, "host".to_string(), host_handler).unwrap();
+        let port = host.status().address.unwrap().rsplit(':').next().unwrap().to_string();
+        let url = format!("ws://127.0.0.1:{}", port);
+
+        assert!(CollabSession::connect(&url, None, "intruder".to_string(), channel_handler().0).is_ok());
+        let (a_handler, a_rx) = channel_handler();
+        let a = CollabSession::connect(&url, Some("secret".to_string()), "alice".to_string(), a_handler).unwrap();
+        wait_for(&a_rx, peers_are(&["host"]));
+        wait_for(&host_rx, peers_are(&["alice"]));
+
+        let (b_handler, b_rx) = channel_handler();
+        let b = CollabSession::connect(&url, Some("secret".to_string()), "bob".to_string(), b_handler).unwrap();
+        wait_for(&b_rx, peers_are(&["alice", "host"]));
+        wait_for(&a_rx, peers_are(&["bob", "host"]));
+        assert_eq!(a.status().document_id, Some(doc_id));
+
+        // Ops for documents outside the session aren't sent
+        let other = OpRecord {
+            op: update_op(Uuid::now_v7(), Default::default()),
+            device: Some("alice".to_string()),
+        };
+        a.broadcast_op(Uuid::now_v7(), &other);
+        let node_id = Uuid::now_v7();
+        let record = OpRecord {
+            op: update_op(node_id, Default::default()),
+            device: Some("alice".to_string()),
+        };
+        a.broadcast_op(doc_id, &record);
+        for rx in [&host_rx, &b_rx] {
+            match wait_for(rx, |e| matches!(e, CollabEvent::RemoteOp { .. })) {
+                CollabEvent::RemoteOp { document_id, record } => {
+                    assert_eq!(document_id, doc_id);
+                    assert_eq!(record.op.node_id(), node_id);
+                }
+                _ => unreachable!(),
+            }
+        }
+        let alice = host.status().peers.into_iter().find(|p| p.device == "alice").unwrap();
+        assert_eq!(alice.node_id, Some(node_id));
+
+        b.stop();
+        wait_for(&a_rx, peers_are(&["host"]));
+        host.stop();
+        wait_for(&a_rx, peers_are(&[]));
+        assert!(!a.status().connected);
+    }
+}
@@ -0,0 +1,88 @@
+//! Presence: which document each device has open and which node it is
+//! focused on, shared over the collaboration link.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Where one device is in the outline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Presence {
+    pub device: String,
+    /// Open document, if any
+    pub document_id: Option<Uuid>,
+    /// Focused node in that document
+    pub node_id: Option<Uuid>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The latest presence of each device
+#[derive(Debug, Default)]
+pub struct PresenceBoard {
+    by_device: HashMap<String, Presence>,
+}
+
+impl PresenceBoard {
+    /// Record a device's presence. Returns false if it is older than what
+    /// is already known (messages can arrive by more than one route).
+    pub fn update(&mut self, presence: Presence) -> bool {
+        if let Some(known) = self.by_device.get(&presence.device) {
+            if known.updated_at > presence.updated_at {
+                return false;
+            }
+        }
+        self.by_device.insert(presence.device.clone(), presence);
+        true
+    }
+
+    /// Forget a device. Returns whether it was known.
+    pub fn remove(&mut self, device: &str) -> bool {
+        self.by_device.remove(device).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.by_device.clear();
+    }
+
+    /// Presence of every device, optionally only those in `document_id`,
+    /// sorted by device
+    pub fn list(&self, document_id: Option<Uuid>) -> Vec<Presence> {
+        let mut list: Vec<Presence> = self
+            .by_device
+            .values()
+            .filter(|p| document_id.is_none() || p.document_id == document_id)
+            .cloned()
+            .collect();
+        list.sort_by(|a, b| a.device.cmp(&b.device));
+        list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_board_keeps_latest_presence_per_device() {
+        let doc = Uuid::now_v7();
+        let now = Utc::now();
+        let presence = |device: &str, document_id: Option<Uuid>, updated_at| Presence {
+            device: device.to_string(),
+            document_id,
+            node_id: None,
+            updated_at,
+        };
+
+        let mut board = PresenceBoard::default();
+        assert!(board.update(presence("laptop", Some(doc), now)));
+        assert!(board.update(presence("phone", None, now)));
+        assert!(!board.update(presence("laptop", None, now - Duration::seconds(1))));
+
+        assert_eq!(board.list(Some(doc)).len(), 1);
+        assert_eq!(board.list(None).len(), 2);
+        assert!(board.remove("laptop"));
+        assert!(board.list(Some(doc)).is_empty());
+    }
+}
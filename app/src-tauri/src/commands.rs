@@ -1,44 +1,154 @@
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use tauri::State;
 use uuid::Uuid;
 
 use crate::data::{
-    create_op, create_op_with_id, data_dir, default_data_dir, delete_op, documents_dir, ensure_dirs,
-    move_op, save_config, set_data_dir, update_op, Document, DocumentState, InboxConfig, InboxItem,
+    create_op, create_op_with_id, data_dir, default_data_dir, delete_op, device_name, documents_dir, ensure_dirs,
+    move_op, update_config, set_checked_recursive_ops, set_data_dir, data_dir_override_source, update_op, Document, DocumentState, InboxConfig, InboxItem,
     get_inbox_config, set_inbox_config as set_inbox_config_impl, clear_inbox_config as clear_inbox_config_impl,
-    Node, NodeChanges, NodeType, Operation, read_inbox, remove_inbox_items,
+    Node, NodeChanges, NodeType, OpRecord, Operation, position_between, read_inbox, remove_inbox_items, Divergence, ReloadDelta,
+    DocumentSettings, move_to_bottom_op, check_data_dir, DataDirHealth, SkippedOp, FsyncPolicy, MqttConfig, TranscriptionConfig, append_inbox_item,
+    NodeSecret, encrypt_node_op, decrypt_node_op, unlock_node as unlock_node_impl,
+    Comment, add_comment_op, resolve_comment_op, restore_comment_ops, set_op_listener, DocumentRenamed, RenameListener, search_cache_config, set_search_cache_config,
+    SearchCacheMode, complete_recurring_ops, load_overdue_rules, save_overdue_rules, overdue_rule_ops,
+    OverdueReport, OverdueRule, Share, ShareRefresher, load_shares,
+    create_share as create_share_impl, revoke_share as revoke_share_impl,
     // Folder management
     Folder, FolderState, load_folders,
     create_folder as create_folder_impl,
     update_folder as update_folder_impl,
     delete_folder as delete_folder_impl,
     move_document_to_folder as move_doc_to_folder_impl,
+    toggle_favorite as toggle_favorite_impl,
+    bookmark_node as bookmark_node_impl,
+    remove_bookmarks,
     reorder_folders as reorder_folders_impl,
+    // Snapshots
+    SnapshotDiff, SnapshotInfo, diff_states, load_snapshot, redact_snapshots,
+    create_snapshot as create_snapshot_impl,
+    list_snapshots as list_snapshots_impl,
+    // Operation log
+    OpLogEntry, op_log,
+    // Sorting
+    SortDirection, SortKey, sort_children_ops,
+    // Templates
+    TemplateInfo, extract_subtree, instantiate_nodes, load_template, find_template_by_name,
+    save_template as save_template_impl,
+    list_templates as list_templates_impl,
+    delete_template as delete_template_impl,
+    // Journal
+    JournalConfig, daily_note_ops, daily_title, find_titled_child, get_journal_config, has_title,
+    journal_date, set_journal_config,
+    // Task rollups
+    TaskRollup, task_rollup, task_rollups,
+    // Statistics
+    Statistics,
+    // Reviews
+    Completion, Review, completion_history, completed_changelog, completed_tasks, ChangelogFormat,
+    // Time tracking
+    RunningTimer, TimeEntry, TimeGroupBy, TimeReport, TimeReportRow, read_time_entries, running_timer,
+    start_timer as start_timer_impl,
+    stop_timer as stop_timer_impl,
+    // Pomodoro
+    PomodoroStats, read_pomodoros,
+    // Boards
+    Board, BoardGroupBy, build_board, move_card_op,
+    // Search and replace
+    ReplaceMatch, ReplaceMode, ReplaceScope, Replacer,
+    // Wiki links
+    get_link_target_folder, set_link_target_folder,
+    // Content
+    render_markdown, sanitize_html,
+    // Snippets
+    Snippet, load_snippets, save_snippets,
+    expand_snippet as expand_snippet_impl,
 };
-use crate::search::{BacklinkResult, SearchIndex, SearchResult};
-use crate::watcher::WatcherState;
+use crate::import_export::{
+    append_linked_references_html, build_document_archive, linked_references_markdown, ArchiveDocument, ArchiveFormat,
+    ExportScope, MarkdownOptions, generate_markdown, markdown_edit_ops, generate_workspace_backup,
+    parse_workspace_backup, WorkspaceDocument,
+};
+use crate::search::{
+    encryption_supported, extract_wiki_links, LinkTarget, UnlinkedMention, SwitchCandidate, group_search_results, LinkedReference, new_cache_key, BacklinkResult, CacheMaintenanceReport, CacheMode, IndexQueue, IndexStatus,
+    NodeFilter, NodeQueryResult, SearchError, SearchIndex, SearchResult, SearchResultGroup,
+    SharedSearchIndex, strip_html, Suggestion,
+};
+use crate::collab::{CollabEvent, CollabHandler, CollabSession, CollabState, CollabStatus, Presence};
+use crate::link_metadata::LinkMetadata;
+use crate::mount::{DocumentSource, MountState};
+use crate::pomodoro::{PomodoroState, PomodoroStatus};
+use crate::spellcheck::Misspelling;
+use crate::transcribe::{store_audio, TranscriptHandler, TranscriptTarget, TranscriptionJob, TranscriptionQueue};
+use crate::watcher::{WatcherState, WatcherStatus};
 
 /// Parse a UUID string, returning a descriptive error
 fn parse_uuid(id: &str) -> Result<Uuid, String> {
     Uuid::parse_str(id).map_err(|e| format!("Invalid UUID: {}", e))
 }
 
+/// Run `f` against a document: the in-memory current document if it matches,
+/// otherwise a copy loaded from disk
+fn with_document<T>(
+    state: &AppState,
+    doc_uuid: &Uuid,
+    f: impl FnOnce(&mut Document) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut current = state.current_document.lock().unwrap();
+    if let Some(ref mut doc) = *current {
+        if doc.id == *doc_uuid {
+            return f(doc);
+        }
+    }
+    drop(current);
+
+    let doc_dir = documents_dir().join(doc_uuid.to_string());
+    if !doc_dir.exists() {
+        return Err(format!("Document not found: {}", doc_uuid));
+    }
+    let mut doc = Document::load(doc_dir)?;
+    f(&mut doc)
+}
+
+/// Get a document's state, preferring the in-memory copy if it is the current document
+fn document_state_for(state: &AppState, doc_uuid: &Uuid) -> Result<DocumentState, String> {
+    with_document(state, doc_uuid, |doc| Ok(doc.state.clone()))
+}
+
 /// State managed by Tauri for the current document
 pub struct AppState {
     pub current_document: Mutex<Option<Document>>,
-    pub search_index: Mutex<Option<SearchIndex>>,
+    pub search_index: SharedSearchIndex,
+    /// Background indexing of loaded documents and rebuilds
+    pub indexer: IndexQueue,
+    /// Background rewriting of share bundles after edits
+    pub shares: ShareRefresher,
 }
 
 impl AppState {
     pub fn new() -> Self {
-        // Initialize search index
-        let search_index = SearchIndex::open()
-            .map_err(|e| log::error!("Failed to open search index: {}", e))
-            .ok();
+        // Initialize search index, rebuilding it if the cache was corrupted
+        let (search_index, recreated) = match SearchIndex::open_or_recreate(&CacheMode::configured()) {
+            Ok((index, recreated)) => (Some(index), recreated),
+            Err(e) => {
+                log::error!("Failed to open search index: {}", e);
+                (None, false)
+            }
+        };
+        let search_index = Arc::new(Mutex::new(search_index));
+
+        let indexer = IndexQueue::start(search_index.clone());
+        if recreated {
+            indexer.rebuild();
+        } else {
+            // Documents deleted while the app wasn't running
+            indexer.reconcile();
+        }
 
         Self {
             current_document: Mutex::new(None),
-            search_index: Mutex::new(search_index),
+            search_index,
+            indexer,
+            shares: ShareRefresher::start(),
         }
     }
 }
@@ -60,7 +170,7 @@ pub fn load_document(
 
     let doc_dir = documents_dir().join(doc_uuid.to_string());
 
-    let doc = if doc_dir.exists() {
+    let mut doc = if doc_dir.exists() {
         Document::load(doc_dir)?
     } else {
         // Create new document with sample data
@@ -68,23 +178,16 @@ pub fn load_document(
         create_sample_data(&mut doc)?;
         doc
     };
+    sync_document_title(&mut doc);
 
     let doc_state = doc.state.clone();
 
     // Index document for search in background (don't block loading)
-    let nodes_for_index = doc_state.nodes.clone();
-    std::thread::spawn(move || {
-        // Re-open search index in this thread
-        if let Ok(index) = SearchIndex::open() {
-            if let Err(e) = index.index_document(&doc_uuid, &nodes_for_index) {
-                log::warn!("Failed to index document: {}", e);
-            }
-            if let Err(e) = index.update_document_links(&doc_uuid, &nodes_for_index) {
-                log::warn!("Failed to update document links: {}", e);
-            }
-            log::info!("Background indexing complete for {} nodes", nodes_for_index.len());
-        }
-    });
+    state.indexer.index_document(doc_uuid, doc_state.nodes.clone());
+
+    if let Err(e) = crate::data::record_document_opened(&doc_uuid.to_string()) {
+        log::warn!("Failed to record recent document: {}", e);
+    }
 
     // Store current document
     let mut current = state.current_document.lock().unwrap();
@@ -96,33 +199,59 @@ pub fn load_document(
 /// Save an operation to the current document
 #[tauri::command]
 pub fn save_op(state: State<AppState>, op: Operation) -> Result<DocumentState, String> {
+    save_ops(&state, &[op])
+}
+
+/// Append and apply a batch of operations to the current document
+fn save_ops(state: &AppState, ops: &[Operation]) -> Result<DocumentState, String> {
     let mut current = state.current_document.lock().unwrap();
     let doc = current.as_mut().ok_or("No document loaded")?;
 
-    // Append operation to pending file
-    doc.append_op(&op)?;
+    let mut deleted = Vec::new();
+    for op in ops {
+        if let Operation::Delete { id, .. } = op {
+            deleted.extend(doc.state.subtree_ids(*id).iter().map(Uuid::to_string));
+        }
+        // Append operation to pending file, then apply to in-memory state
+        doc.append_op(op)?;
+        op.apply(&mut doc.state);
+    }
+    sync_document_title(doc);
+
+    // Deleted nodes take their bookmarks with them
+    if !deleted.is_empty() {
+        if let Err(e) = remove_bookmarks(&doc.id.to_string(), &deleted) {
+            log::warn!("Failed to remove bookmarks of deleted nodes: {}", e);
+        }
+    }
 
-    // Apply operation to in-memory state
-    op.apply(&mut doc.state);
+    state.shares.refresh(doc.id, &doc.state, doc.meta.settings.markdown_notes);
 
     // Auto-compact if threshold reached (1000 ops or 1MB)
     if doc.should_auto_compact() {
         log::info!("Auto-compacting document...");
         if let Err(e) = doc.compact() {
             log::error!("Auto-compact failed: {}", e);
-            // Don't fail the save_op, just log the error
+            // Don't fail the save, just log the error
         }
     }
 
     Ok(doc.state.clone())
 }
 
+/// Keep a document's title and meta.json in line after it changed
+fn sync_document_title(doc: &mut Document) {
+    if let Err(e) = doc.sync_title() {
+        log::warn!("Failed to sync title of document {}: {}", doc.id, e);
+    }
+}
+
 /// Create a new node (convenience command that wraps save_op)
 #[tauri::command]
 pub fn create_node(
     state: State<AppState>,
     parent_id: Option<String>,
-    position: i32,
+    position: f64,
     content: String,
 ) -> Result<(Uuid, DocumentState), String> {
     let parent_uuid = if let Some(id_str) = parent_id {
@@ -131,7 +260,15 @@ pub fn create_node(
         None
     };
 
-    let op = create_op(parent_uuid, position, content);
+    let default_node_type = {
+        let current = state.current_document.lock().unwrap();
+        current.as_ref().and_then(|doc| doc.meta.settings.default_node_type.clone())
+    };
+    let content = sanitize_html(&content);
+    let op = match default_node_type {
+        Some(node_type) => create_op_with_id(Uuid::now_v7(), parent_uuid, position, content, node_type),
+        None => create_op(parent_uuid, position, content),
+    };
     let new_id = match &op {
         Operation::Create { id, .. } => *id,
         _ => unreachable!(),
@@ -147,7 +284,7 @@ pub fn create_node_with_id(
     state: State<AppState>,
     id: String,
     parent_id: Option<String>,
-    position: i32,
+    position: f64,
     content: String,
     node_type: NodeType,
 ) -> Result<(Uuid, DocumentState), String> {
@@ -158,22 +295,96 @@ pub fn create_node_with_id(
         None
     };
 
-    let op = create_op_with_id(node_id, parent_uuid, position, content, node_type);
+    let op = create_op_with_id(node_id, parent_uuid, position, sanitize_html(&content), node_type);
 
     let new_state = save_op(state, op)?;
     Ok((node_id, new_state))
 }
 
-/// Update a node (convenience command that wraps save_op)
+/// Update a node (convenience command that wraps save_op). New content is
+/// sanitized first.
 #[tauri::command]
 pub fn update_node(
     state: State<AppState>,
     id: String,
-    changes: NodeChanges,
+    mut changes: NodeChanges,
 ) -> Result<DocumentState, String> {
     let node_id = parse_uuid(&id)?;
-    let op = update_op(node_id, changes);
-    save_op(state, op)
+    changes.content = changes.content.map(|c| sanitize_html(&c));
+    let sink = changes.is_checked == Some(true);
+    let mut ops = vec![update_op(node_id, changes)];
+    if sink {
+        ops.extend(completed_to_bottom_op(&state, node_id));
+    }
+    save_ops(&state, &ops)
+}
+
+/// Set (or with None, clear) the color label of several nodes in the
+/// current document at once
+#[tauri::command]
+pub fn bulk_recolor(
+    state: State<AppState>,
+    node_ids: Vec<String>,
+    color: Option<String>,
+) -> Result<DocumentState, String> {
+    let color = color
+        .map(|c| c.trim().to_lowercase())
+        .unwrap_or_default();
+    let ops = node_ids
+        .iter()
+        .map(|id| {
+            let changes = NodeChanges {
+                color: Some(color.clone()),
+                ..Default::default()
+            };
+            Ok(update_op(parse_uuid(id)?, changes))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    save_ops(&state, &ops)
+}
+
+/// A node's note as HTML: rendered Markdown if the document's notes are
+/// Markdown, escaped text with line breaks otherwise (empty if no note)
+#[tauri::command]
+pub fn render_note(state: State<AppState>, node_id: String) -> Result<String, String> {
+    let node_uuid = parse_uuid(&node_id)?;
+    let current = state.current_document.lock().unwrap();
+    let doc = current.as_ref().ok_or("No document loaded")?;
+    let node = doc
+        .state
+        .nodes
+        .iter()
+        .find(|n| n.id == node_uuid)
+        .ok_or_else(|| format!("Node not found: {}", node_id))?;
+    let Some(ref note) = node.note else {
+        return Ok(String::new());
+    };
+    if doc.meta.settings.markdown_notes {
+        Ok(render_markdown(note))
+    } else {
+        Ok(note
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('\n', "<br>"))
+    }
+}
+
+/// Clean pasted HTML down to the markup the editor uses
+#[tauri::command]
+pub fn sanitize_content(content: String) -> String {
+    sanitize_html(&content)
+}
+
+/// A Move op sinking a just-checked node below its siblings, if the current
+/// document's settings ask for it
+fn completed_to_bottom_op(state: &AppState, node_id: Uuid) -> Option<Operation> {
+    let current = state.current_document.lock().unwrap();
+    let doc = current.as_ref()?;
+    if !doc.meta.settings.completed_to_bottom {
+        return None;
+    }
+    move_to_bottom_op(&doc.state, node_id)
 }
 
 /// Move a node (convenience command that wraps save_op)
@@ -182,7 +393,7 @@ pub fn move_node(
     state: State<AppState>,
     id: String,
     parent_id: Option<String>,
-    position: i32,
+    position: f64,
 ) -> Result<DocumentState, String> {
     let node_id = parse_uuid(&id)?;
     let parent_uuid = if let Some(id_str) = parent_id {
@@ -203,6 +414,209 @@ pub fn delete_node(state: State<AppState>, id: String) -> Result<DocumentState,
     save_op(state, op)
 }
 
+/// Add a comment to a node, authored by this device
+#[tauri::command]
+pub fn add_comment(state: State<AppState>, node_id: String, text: String) -> Result<DocumentState, String> {
+    let node_uuid = parse_uuid(&node_id)?;
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return Err("Comment is empty".to_string());
+    }
+    save_op(state, add_comment_op(node_uuid, text))
+}
+
+/// List a node's comments, oldest first. Resolved comments are left out
+/// unless `include_resolved` is set.
+#[tauri::command]
+pub fn list_comments(
+    state: State<AppState>,
+    node_id: String,
+    include_resolved: Option<bool>,
+) -> Result<Vec<Comment>, String> {
+    let node_uuid = parse_uuid(&node_id)?;
+    let current = state.current_document.lock().unwrap();
+    let doc = current.as_ref().ok_or("No document loaded")?;
+    let node = doc
+        .state
+        .nodes
+        .iter()
+        .find(|n| n.id == node_uuid)
+        .ok_or_else(|| format!("Node not found: {}", node_id))?;
+    let include_resolved = include_resolved.unwrap_or(false);
+    Ok(node
+        .comments
+        .iter()
+        .filter(|c| include_resolved || c.resolved_at.is_none())
+        .cloned()
+        .collect())
+}
+
+/// Resolve a comment, or reopen it with `resolved: false`
+#[tauri::command]
+pub fn resolve_comment(
+    state: State<AppState>,
+    comment_id: String,
+    resolved: Option<bool>,
+) -> Result<DocumentState, String> {
+    let comment_uuid = parse_uuid(&comment_id)?;
+    let node_id = {
+        let current = state.current_document.lock().unwrap();
+        let doc = current.as_ref().ok_or("No document loaded")?;
+        doc.state
+            .nodes
+            .iter()
+            .find(|n| n.comments.iter().any(|c| c.id == comment_uuid))
+            .map(|n| n.id)
+            .ok_or_else(|| format!("Comment not found: {}", comment_id))?
+    };
+    save_op(state, resolve_comment_op(node_id, comment_uuid, resolved.unwrap_or(true)))
+}
+
+/// A node of the current document
+fn current_node(state: &AppState, node_id: Uuid) -> Result<Node, String> {
+    let current = state.current_document.lock().unwrap();
+    let doc = current.as_ref().ok_or("No document loaded")?;
+    doc.state
+        .nodes
+        .iter()
+        .find(|n| n.id == node_id)
+        .cloned()
+        .ok_or_else(|| format!("Node not found: {}", node_id))
+}
+
+/// Encrypt a node's content and note with a passphrase. Only ciphertext is
+/// stored from then on, and the node drops out of search. The document is
+/// compacted and its op history and snapshots rewritten, so the plain text
+/// isn't left on disk.
+#[tauri::command]
+pub fn encrypt_node(state: State<AppState>, node_id: String, passphrase: String) -> Result<DocumentState, String> {
+    let node = current_node(&state, parse_uuid(&node_id)?)?;
+    if node.encrypted {
+        return Err("Node is already encrypted".to_string());
+    }
+    let secret = NodeSecret {
+        content: node.content.clone(),
+        note: node.note.clone().filter(|n| !n.is_empty()),
+    };
+    let doc_state = save_ops(&state, &[encrypt_node_op(&node, &secret, &passphrase)?])?;
+
+    let mut current = state.current_document.lock().unwrap();
+    let doc = current.as_mut().ok_or("No document loaded")?;
+    // state.json still has the plain text until the next compaction
+    doc.compact()?;
+    doc.redact_node_ops(node.id)?;
+    if let Some(encrypted) = doc.state.nodes.iter().find(|n| n.id == node.id) {
+        redact_snapshots(&doc.id, encrypted)?;
+    }
+    Ok(doc_state)
+}
+
+/// Decrypt an encrypted node for display. Nothing is written; a wrong
+/// passphrase is an error.
+#[tauri::command]
+pub fn unlock_node(state: State<AppState>, node_id: String, passphrase: String) -> Result<NodeSecret, String> {
+    let node = current_node(&state, parse_uuid(&node_id)?)?;
+    unlock_node_impl(&node, &passphrase)
+}
+
+/// Save edits to an encrypted node, encrypting them with the passphrase it
+/// was unlocked with
+#[tauri::command]
+pub fn update_encrypted_node(
+    state: State<AppState>,
+    node_id: String,
+    passphrase: String,
+    content: String,
+    note: Option<String>,
+) -> Result<DocumentState, String> {
+    let node = current_node(&state, parse_uuid(&node_id)?)?;
+    // Checks the passphrase, so edits can't end up under a different one
+    unlock_node_impl(&node, &passphrase)?;
+    let secret = NodeSecret {
+        content: sanitize_html(&content),
+        note: note.filter(|n| !n.is_empty()),
+    };
+    save_op(state, encrypt_node_op(&node, &secret, &passphrase)?)
+}
+
+/// Remove a node's encryption, storing its content and note as plain text
+/// again
+#[tauri::command]
+pub fn decrypt_node(state: State<AppState>, node_id: String, passphrase: String) -> Result<DocumentState, String> {
+    let node = current_node(&state, parse_uuid(&node_id)?)?;
+    save_op(state, decrypt_node_op(&node, &passphrase)?)
+}
+
+/// Sort a node's children (or the roots) by a key, as one batch of Move ops
+#[tauri::command]
+pub fn sort_children(
+    state: State<AppState>,
+    parent_id: Option<String>,
+    key: SortKey,
+    direction: Option<SortDirection>,
+) -> Result<DocumentState, String> {
+    let parent_uuid = if let Some(id_str) = parent_id {
+        Some(parse_uuid(&id_str)?)
+    } else {
+        None
+    };
+
+    let ops = {
+        let current = state.current_document.lock().unwrap();
+        let doc = current.as_ref().ok_or("No document loaded")?;
+        sort_children_ops(&doc.state, parent_uuid, key, direction.unwrap_or_default())
+    };
+    save_ops(&state, &ops)
+}
+
+/// Check or uncheck a node and all its descendants in one batch
+#[tauri::command]
+pub fn set_checked_recursive(
+    state: State<AppState>,
+    node_id: String,
+    checked: bool,
+    skip_non_checkbox: Option<bool>,
+) -> Result<DocumentState, String> {
+    let node_uuid = parse_uuid(&node_id)?;
+
+    let ops = {
+        let current = state.current_document.lock().unwrap();
+        let doc = current.as_ref().ok_or("No document loaded")?;
+        if !doc.state.nodes.iter().any(|n| n.id == node_uuid) {
+            return Err(format!("Node not found: {}", node_uuid));
+        }
+        set_checked_recursive_ops(&doc.state, node_uuid, checked, skip_non_checkbox.unwrap_or(false))
+    };
+    let mut ops = ops;
+    if checked {
+        ops.extend(completed_to_bottom_op(&state, node_uuid));
+    }
+    save_ops(&state, &ops)
+}
+
+/// Count checked/total descendant tasks and the nearest open due date under a node
+#[tauri::command]
+pub fn get_task_rollup(state: State<AppState>, node_id: String) -> Result<TaskRollup, String> {
+    let node_uuid = parse_uuid(&node_id)?;
+    let current = state.current_document.lock().unwrap();
+    let doc = current.as_ref().ok_or("No document loaded")?;
+    if !doc.state.nodes.iter().any(|n| n.id == node_uuid) {
+        return Err(format!("Node not found: {}", node_uuid));
+    }
+    Ok(task_rollup(&doc.state, node_uuid))
+}
+
+/// Task rollups for every node in the current document that has tasks or due
+/// dates below it, computed in a single pass
+#[tauri::command]
+pub fn get_task_rollups(
+    state: State<AppState>,
+) -> Result<std::collections::HashMap<Uuid, TaskRollup>, String> {
+    let current = state.current_document.lock().unwrap();
+    let doc = current.as_ref().ok_or("No document loaded")?;
+    Ok(task_rollups(&doc.state))
+}
+
 /// Compact the current document (merge pending into state.json)
 #[tauri::command]
 pub fn compact_document(state: State<AppState>) -> Result<(), String> {
@@ -211,6 +625,17 @@ pub fn compact_document(state: State<AppState>) -> Result<(), String> {
     doc.compact()
 }
 
+/// Rewrite a document's sibling positions into a clean 0..n sequence via Move ops
+#[tauri::command]
+pub fn normalize_positions(state: State<AppState>, doc_id: String) -> Result<DocumentState, String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    with_document(&state, &doc_uuid, |doc| {
+        let moved = doc.normalize_positions(true)?;
+        log::info!("normalize_positions: moved {} nodes in {}", moved, doc_uuid);
+        Ok(doc.state.clone())
+    })
+}
+
 /// Check if document has external changes (from sync)
 #[tauri::command]
 pub fn check_for_changes(state: State<AppState>) -> Result<bool, String> {
@@ -219,23 +644,82 @@ pub fn check_for_changes(state: State<AppState>) -> Result<bool, String> {
     Ok(doc.has_external_changes())
 }
 
-/// Reload document if there are external changes
+/// Pick up external changes to the current document (ops synced from other
+/// machines). Only new ops are applied and only the affected nodes are
+/// re-indexed; the delta is emitted as a `document-delta` event. Returns the
+/// new state if anything changed.
 #[tauri::command]
-pub fn reload_if_changed(state: State<AppState>) -> Result<Option<DocumentState>, String> {
+pub fn reload_if_changed(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+) -> Result<Option<DocumentState>, String> {
+    use tauri::Emitter;
+
     let mut current = state.current_document.lock().unwrap();
     let doc = current.as_mut().ok_or("No document loaded")?;
 
-    if doc.has_external_changes() {
-        doc.reload()?;
+    let Some(mut delta) = doc.sync_external_changes()? else {
+        return Ok(None);
+    };
+    // A title taken from meta.json changes the first root node
+    let title = doc.state.title_node().map(|n| n.content.clone());
+    sync_document_title(doc);
+    if let Some(node) = doc.state.title_node().filter(|n| title.as_ref() != Some(&n.content)) {
+        delta.changed.retain(|n| n.id != node.id);
+        delta.changed.push(node.clone());
+    }
+    log::info!(
+        "External changes: {} changed, {} deleted (full reload: {})",
+        delta.changed.len(),
+        delta.deleted.len(),
+        delta.full_reload
+    );
+
+    if let Some(ref index) = *state.search_index.lock().unwrap() {
+        if let Err(e) = reindex_delta(index, doc, &delta) {
+            log::warn!("Failed to re-index external changes: {}", e);
+        }
+    }
 
-        // Re-index after reload
-        // Note: We'd need access to search_index here, but for simplicity
-        // the frontend can call load_document again if needed
+    if let Err(e) = app.emit("document-delta", &delta) {
+        log::error!("Failed to emit document-delta event: {}", e);
+    }
 
-        Ok(Some(doc.state.clone()))
-    } else {
-        Ok(None)
+    Ok(Some(doc.state.clone()))
+}
+
+/// Remote changes to a document that haven't been applied yet (empty unless
+/// it is the current document and other machines have synced ops since it
+/// was loaded), for a banner shown before reloading
+#[tauri::command]
+pub fn get_divergence(state: State<AppState>, doc_id: String) -> Result<Divergence, String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    with_document(&state, &doc_uuid, |doc| doc.divergence())
+}
+
+/// Update the search index for a reload delta. Whole-document reloads and
+/// top-level changes (which can change the title) re-index the document.
+fn reindex_delta(index: &SearchIndex, doc: &Document, delta: &ReloadDelta) -> rusqlite::Result<()> {
+    // Private documents stay out of the index
+    if doc.meta.settings.excluded {
+        return Ok(());
+    }
+    let doc_state = &doc.state;
+    let doc_uuid = Uuid::parse_str(&delta.document_id).unwrap_or_default();
+    if delta.full_reload || delta.changed.iter().any(|n| n.parent_id.is_none()) {
+        index.index_document(&doc_uuid, &doc_state.nodes)?;
+        return index.update_document_links(&doc_uuid, &doc_state.nodes);
+    }
+
+    for node in &delta.changed {
+        index.update_node(&doc_uuid, node, doc_state.ancestors(node.id).len())?;
+        index.update_links(&doc_uuid, node)?;
     }
+    // Deleting a node removes its whole subtree, and every removed id is listed
+    for node_id in &delta.deleted {
+        index.delete_node(node_id)?;
+    }
+    Ok(())
 }
 
 /// Create sample data for a new document
@@ -244,23 +728,23 @@ fn create_sample_data(doc: &mut Document) -> Result<(), String> {
     let root2 = Node::new("Getting Started".to_string());
     let root3 = Node::new("Features".to_string());
 
-    let child1 = Node::new_child(root2.id, 0, "Press Enter to create a new item".to_string());
-    let child2 = Node::new_child(root2.id, 1, "Press Tab to indent".to_string());
-    let child3 = Node::new_child(root2.id, 2, "Press Shift+Tab to outdent".to_string());
+    let child1 = Node::new_child(root2.id, 0.0, "Press Enter to create a new item".to_string());
+    let child2 = Node::new_child(root2.id, 1.0, "Press Tab to indent".to_string());
+    let child3 = Node::new_child(root2.id, 2.0, "Press Shift+Tab to outdent".to_string());
 
-    let feature1 = Node::new_child(root3.id, 0, "Hierarchical notes".to_string());
-    let feature2 = Node::new_child(root3.id, 1, "Rich text editing".to_string());
-    let feature3 = Node::new_child(root3.id, 2, "Cross-device sync (coming soon)".to_string());
+    let feature1 = Node::new_child(root3.id, 0.0, "Hierarchical notes".to_string());
+    let feature2 = Node::new_child(root3.id, 1.0, "Rich text editing".to_string());
+    let feature3 = Node::new_child(root3.id, 2.0, "Cross-device sync (coming soon)".to_string());
 
     // Create root nodes first
     let mut root1_mut = root1;
-    root1_mut.position = 0;
+    root1_mut.position = 0.0;
 
     let mut root2_mut = root2;
-    root2_mut.position = 1;
+    root2_mut.position = 1.0;
 
     let mut root3_mut = root3;
-    root3_mut.position = 2;
+    root3_mut.position = 2.0;
 
     doc.state.nodes = vec![
         root1_mut,
@@ -286,7 +770,7 @@ pub fn search(
     query: String,
     doc_id: Option<String>,
     limit: Option<usize>,
-) -> Result<Vec<SearchResult>, String> {
+) -> Result<Vec<SearchResult>, SearchError> {
     let doc_uuid = if let Some(id_str) = doc_id {
         Some(parse_uuid(&id_str)?)
     } else {
@@ -298,103 +782,504 @@ pub fn search(
         .as_ref()
         .ok_or("Search index not initialized")?;
 
-    index
-        .search(&query, doc_uuid.as_ref(), limit.unwrap_or(50))
-        .map_err(|e| format!("Search error: {}", e))
+    let mut results = index.search(&query, doc_uuid.as_ref(), limit.unwrap_or(50))?;
+
+    // Folder assignments live in folders.json rather than the cache
+    let folders = load_folders()?;
+    for result in &mut results {
+        result.folder_name = folders
+            .document_folders
+            .get(&result.document_id)
+            .and_then(|fid| folders.folders.iter().find(|f| f.id == *fid))
+            .map(|f| f.name.clone());
+    }
+
+    Ok(results)
 }
 
-/// Document info for listing
-#[derive(Clone, serde::Serialize)]
-pub struct DocumentInfo {
-    pub id: String,
-    pub title: String,
-    pub node_count: usize,
-    pub title_node_id: Option<String>,  // ID of the first root node (for renaming)
+/// Search across documents, grouping results per document (ordered by each
+/// document's best match)
+#[tauri::command]
+pub fn search_grouped(
+    state: State<AppState>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<SearchResultGroup>, SearchError> {
+    search(state, query, None, limit).map(group_search_results)
 }
 
-/// List all available documents
+/// Completions for the search box: matching tags, document titles and
+/// recent searches
 #[tauri::command]
-pub fn list_documents() -> Result<Vec<DocumentInfo>, String> {
-    use crate::data::list_documents as list_doc_ids;
-    ensure_dirs()?;
+pub fn suggest(state: State<AppState>, prefix: String) -> Result<Vec<Suggestion>, String> {
+    let search_index = state.search_index.lock().unwrap();
+    let index = search_index
+        .as_ref()
+        .ok_or("Search index not initialized")?;
 
-    let doc_ids = list_doc_ids()?;
-    let mut documents = Vec::new();
+    index
+        .suggest(&prefix)
+        .map_err(|e| format!("Suggest error: {}", e))
+}
 
-    for doc_id in doc_ids {
-        let doc_dir = documents_dir().join(doc_id.to_string());
-        if let Ok(doc) = Document::load(doc_dir) {
-            // Get first root node (for title and renaming)
-            let first_root = doc
-                .state
-                .nodes
-                .iter()
-                .filter(|n| n.parent_id.is_none())
-                .min_by_key(|n| n.position);
-
-            let title = first_root
-                .map(|n| strip_html_for_title(&n.content))
-                .unwrap_or_else(|| "Untitled".to_string());
+/// Remember a search the user committed to (e.g. opened a result), for suggestions
+#[tauri::command]
+pub fn record_search(state: State<AppState>, term: String) -> Result<(), String> {
+    let search_index = state.search_index.lock().unwrap();
+    let index = search_index
+        .as_ref()
+        .ok_or("Search index not initialized")?;
 
-            let title_node_id = first_root.map(|n| n.id.to_string());
+    index
+        .record_search(&term)
+        .map_err(|e| format!("Search history error: {}", e))
+}
 
-            documents.push(DocumentInfo {
-                id: doc_id.to_string(),
-                title,
-                node_count: doc.state.nodes.len(),
-                title_node_id,
-            });
-        }
-    }
+/// Find nodes across documents matching a structured filter (tags, dates,
+/// checked state, documents, text). Backs smart views, agenda and boards.
+#[tauri::command]
+pub fn query_nodes(state: State<AppState>, filter: NodeFilter) -> Result<Vec<NodeQueryResult>, String> {
+    let search_index = state.search_index.lock().unwrap();
+    let index = search_index
+        .as_ref()
+        .ok_or("Search index not initialized")?;
 
-    Ok(documents)
+    index
+        .query_nodes(&filter)
+        .map_err(|e| format!("Query error: {}", e))
 }
 
-/// Strip HTML tags for display (simple version)
-fn strip_html_for_title(html: &str) -> String {
-    let mut result = String::with_capacity(html.len());
-    let mut in_tag = false;
+/// Get a single node from any document (e.g. for backlink or mirror previews)
+#[tauri::command]
+pub fn get_node(state: State<AppState>, doc_id: String, node_id: String) -> Result<Node, String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    let node_uuid = parse_uuid(&node_id)?;
+    with_document(&state, &doc_uuid, |doc| {
+        doc.state
+            .nodes
+            .iter()
+            .find(|n| n.id == node_uuid)
+            .cloned()
+            .ok_or_else(|| format!("Node not found: {}", node_id))
+    })
+}
 
-    for c in html.chars() {
-        match c {
-            '<' => in_tag = true,
-            '>' => in_tag = false,
-            _ if !in_tag => result.push(c),
-            _ => {}
+/// Get a node and its descendants from any document, optionally only
+/// `depth` levels deep (0 = just the node)
+#[tauri::command]
+pub fn get_subtree(
+    state: State<AppState>,
+    doc_id: String,
+    node_id: String,
+    depth: Option<usize>,
+) -> Result<Vec<Node>, String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    let node_uuid = parse_uuid(&node_id)?;
+    with_document(&state, &doc_uuid, |doc| {
+        let nodes: Vec<Node> = doc.state.subtree(node_uuid, depth).into_iter().cloned().collect();
+        if nodes.is_empty() {
+            return Err(format!("Node not found: {}", node_id));
         }
-    }
+        Ok(nodes)
+    })
+}
 
-    result
-        .replace("&nbsp;", " ")
-        .replace("&amp;", "&")
-        .replace("&lt;", "<")
-        .replace("&gt;", ">")
-        .replace("&quot;", "\"")
-        .trim()
-        .to_string()
+/// A document scoped to one branch, for hoist ("zoom into bullet") mode
+#[derive(Clone, serde::Serialize)]
+pub struct HoistedState {
+    pub root_id: String,
+    /// The hoisted node and its descendants
+    pub state: DocumentState,
+    /// Ancestors of the hoisted node, top level first
+    pub breadcrumbs: Vec<AncestorInfo>,
 }
 
-/// Get backlinks for a node (items that link to this node)
+/// Make a document current and return only the branch under `node_id`, with
+/// breadcrumbs back to the top level. Edits go through save_op as usual.
 #[tauri::command]
-pub fn get_backlinks(
+pub fn load_subtree_as_root(
     state: State<AppState>,
+    doc_id: String,
     node_id: String,
-) -> Result<Vec<BacklinkResult>, String> {
+) -> Result<HoistedState, String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
     let node_uuid = parse_uuid(&node_id)?;
 
-    let search_index = state.search_index.lock().unwrap();
-    let index = search_index
+    let is_current = state
+        .current_document
+        .lock()
+        .unwrap()
         .as_ref()
-        .ok_or("Search index not initialized")?;
-
-    index
-        .get_backlinks(&node_uuid)
-        .map_err(|e| format!("Failed to get backlinks: {}", e))
-}
+        .is_some_and(|doc| doc.id == doc_uuid);
+    if !is_current {
+        load_document(state.clone(), Some(doc_id))?;
+    }
 
-/// Generate iCalendar feed for all dated items in a document
-#[tauri::command]
-pub fn generate_ical_feed(state: State<AppState>) -> Result<String, String> {
+    let current = state.current_document.lock().unwrap();
+    let doc = current.as_ref().ok_or("No document loaded")?;
+    let nodes: Vec<Node> = doc.state.subtree(node_uuid, None).into_iter().cloned().collect();
+    if nodes.is_empty() {
+        return Err(format!("Node not found: {}", node_id));
+    }
+
+    Ok(HoistedState {
+        root_id: node_id,
+        state: DocumentState { nodes },
+        breadcrumbs: ancestor_infos(&doc.state, node_uuid),
+    })
+}
+
+/// An ancestor on the path to a located node
+#[derive(Clone, serde::Serialize)]
+pub struct AncestorInfo {
+    pub id: String,
+    pub content: String,
+    pub collapsed: bool,
+}
+
+fn ancestor_infos(doc_state: &DocumentState, node_id: Uuid) -> Vec<AncestorInfo> {
+    doc_state
+        .ancestors(node_id)
+        .into_iter()
+        .map(|n| AncestorInfo {
+            id: n.id.to_string(),
+            content: strip_html_for_title(&n.content),
+            collapsed: n.collapsed,
+        })
+        .collect()
+}
+
+/// Where a node lives: its document and the branches to expand to show it
+#[derive(Clone, serde::Serialize)]
+pub struct NodeLocation {
+    pub document_id: String,
+    /// Ancestors from the top level down to the node's parent
+    pub ancestors: Vec<AncestorInfo>,
+    /// Collapsed ancestors that must be expanded for the node to be visible
+    pub collapsed_ancestor_ids: Vec<String>,
+}
+
+/// Find the document owning a node (current document, then the search
+/// cache, then every document) and its ancestor chain, for jumping to a
+/// search result or backlink
+#[tauri::command]
+pub fn locate_node(state: State<AppState>, node_id: String) -> Result<NodeLocation, String> {
+    let node_uuid = parse_uuid(&node_id)?;
+
+    let current_id = {
+        let current = state.current_document.lock().unwrap();
+        current
+            .as_ref()
+            .filter(|doc| doc.state.nodes.iter().any(|n| n.id == node_uuid))
+            .map(|doc| doc.id)
+    };
+    let cached_id = || -> Option<Uuid> {
+        let search_index = state.search_index.lock().unwrap();
+        let doc_id = search_index.as_ref()?.document_for_node(&node_uuid).ok()??;
+        Uuid::parse_str(&doc_id).ok()
+    };
+
+    let mut candidates: Vec<Uuid> = current_id.into_iter().chain(cached_id()).collect();
+    // The cache can be stale, so fall back to scanning every document
+    candidates.extend(crate::data::list_documents()?);
+
+    let mut checked = std::collections::HashSet::new();
+    for doc_uuid in candidates {
+        if !checked.insert(doc_uuid) {
+            continue;
+        }
+        let doc_state = document_state_for(&state, &doc_uuid)?;
+        if !doc_state.nodes.iter().any(|n| n.id == node_uuid) {
+            continue;
+        }
+
+        let ancestors = ancestor_infos(&doc_state, node_uuid);
+        let collapsed_ancestor_ids = ancestors
+            .iter()
+            .filter(|a| a.collapsed)
+            .map(|a| a.id.clone())
+            .collect();
+
+        return Ok(NodeLocation {
+            document_id: doc_uuid.to_string(),
+            ancestors,
+            collapsed_ancestor_ids,
+        });
+    }
+
+    Err(format!("Node not found: {}", node_id))
+}
+
+/// Document info for listing
+#[derive(Clone, serde::Serialize)]
+pub struct DocumentInfo {
+    pub id: String,
+    pub title: String,
+    pub node_count: usize,
+    pub title_node_id: Option<String>,  // ID of the first root node (for renaming)
+    pub locked: bool,
+}
+
+/// List all available documents
+#[tauri::command]
+pub fn list_documents() -> Result<Vec<DocumentInfo>, String> {
+    use crate::data::list_documents as list_doc_ids;
+    ensure_dirs()?;
+
+    let doc_ids = list_doc_ids()?;
+    let mut documents = Vec::new();
+
+    for doc_id in doc_ids {
+        let doc_dir = documents_dir().join(doc_id.to_string());
+        if let Ok(doc) = Document::load(doc_dir) {
+            // First root node (for renaming)
+            let title_node_id = doc.state.title_node().map(|n| n.id.to_string());
+
+            documents.push(DocumentInfo {
+                id: doc_id.to_string(),
+                title: doc.title(),
+                node_count: doc.state.nodes.len(),
+                title_node_id,
+                locked: doc.meta.locked,
+            });
+        }
+    }
+
+    Ok(documents)
+}
+
+/// Rename a document: its first root node and meta.json's title. Works on
+/// any document, not only the loaded one.
+#[tauri::command]
+pub fn rename_document(app: tauri::AppHandle, doc_id: String, title: String) -> Result<(), String> {
+    let document_id = parse_uuid(&doc_id)?;
+    edit_document(&app, document_id, |doc| doc.rename(&title).map(|_| ()))
+}
+
+/// Rename listener that updates the document's title in the search index
+/// and emits a `document-renamed` event for the sidebar
+pub fn rename_listener(app: tauri::AppHandle) -> RenameListener {
+    use tauri::{Emitter, Manager};
+
+    Box::new(move |renamed: &DocumentRenamed| {
+        let state = app.state::<AppState>();
+        if let (Some(ref index), Ok(id)) = (&*state.search_index.lock().unwrap(), Uuid::parse_str(&renamed.document_id)) {
+            if let Err(e) = index.set_document_title(&id, &renamed.title) {
+                log::warn!("Failed to update indexed title of {}: {}", renamed.document_id, e);
+            }
+        }
+        if let Err(e) = app.emit("document-renamed", renamed) {
+            log::error!("Failed to emit document-renamed event: {}", e);
+        }
+    })
+}
+
+/// Plain text of a node's content, for titles
+fn strip_html_for_title(html: &str) -> String {
+    strip_html(html).trim().to_string()
+}
+
+/// Get backlinks for a node (items that link to this node). Links in the
+/// loaded document are read from it rather than the index, so ones made
+/// since the last index update show up. Each backlink carries the linking
+/// node's current text, its breadcrumbs and its document's title; links
+/// whose node has been deleted are dropped.
+#[tauri::command]
+pub fn get_backlinks(
+    state: State<AppState>,
+    node_id: String,
+) -> Result<Vec<BacklinkResult>, String> {
+    let node_uuid = parse_uuid(&node_id)?;
+
+    let mut cached = {
+        let search_index = state.search_index.lock().unwrap();
+        let index = search_index
+            .as_ref()
+            .ok_or("Search index not initialized")?;
+        index
+            .get_backlinks(&node_uuid)
+            .map_err(|e| format!("Failed to get backlinks: {}", e))?
+    };
+
+    let mut backlinks = Vec::new();
+    {
+        let current = state.current_document.lock().unwrap();
+        if let Some(doc) = current.as_ref() {
+            let target = node_uuid.to_string();
+            for node in &doc.state.nodes {
+                if extract_wiki_links(&node.content).contains(&target) {
+                    backlinks.push(live_backlink(&doc.state, doc.id, node));
+                }
+            }
+            cached.retain(|b| b.source_document_id != doc.id.to_string());
+        }
+    }
+
+    let mut states: std::collections::HashMap<String, Option<DocumentState>> =
+        std::collections::HashMap::new();
+    for backlink in cached {
+        let doc_state = states.entry(backlink.source_document_id.clone()).or_insert_with(|| {
+            parse_uuid(&backlink.source_document_id)
+                .and_then(|uuid| document_state_for(&state, &uuid))
+                .ok()
+        });
+        let Some(doc_state) = doc_state.as_ref() else {
+            // Couldn't load the document: fall back to the index
+            backlinks.push(backlink);
+            continue;
+        };
+        if let Some(node) = doc_state.nodes.iter().find(|n| n.id.to_string() == backlink.source_node_id) {
+            let doc_uuid = Uuid::parse_str(&backlink.source_document_id).unwrap_or_default();
+            backlinks.push(live_backlink(doc_state, doc_uuid, node));
+        }
+    }
+    Ok(backlinks)
+}
+
+/// Resolve a `[[title]]` wiki link to a node by its text or an alias
+#[tauri::command]
+pub fn resolve_wiki_link(state: State<AppState>, title: String) -> Result<Option<LinkTarget>, String> {
+    let search_index = state.search_index.lock().unwrap();
+    let index = search_index
+        .as_ref()
+        .ok_or("Search index not initialized")?;
+    index
+        .resolve_wiki_link(&title)
+        .map_err(|e| format!("Failed to resolve link: {}", e))
+}
+
+/// Get nodes that mention a node by name or alias without linking to it
+#[tauri::command]
+pub fn get_unlinked_mentions(
+    state: State<AppState>,
+    node_id: String,
+) -> Result<Vec<UnlinkedMention>, String> {
+    let node_uuid = parse_uuid(&node_id)?;
+    let search_index = state.search_index.lock().unwrap();
+    let index = search_index
+        .as_ref()
+        .ok_or("Search index not initialized")?;
+    index
+        .unlinked_mentions(&node_uuid)
+        .map_err(|e| format!("Failed to find unlinked mentions: {}", e))
+}
+
+/// Quick switcher results at most
+const QUICK_SWITCH_LIMIT: usize = 50;
+
+/// Documents, headings and bookmarked nodes fuzzily matching a query, best
+/// first, for the Ctrl+P switcher
+#[tauri::command]
+pub fn quick_switch_candidates(
+    state: State<AppState>,
+    query: String,
+) -> Result<Vec<SwitchCandidate>, String> {
+    let bookmarks: Vec<String> = load_folders()?
+        .bookmarks
+        .into_iter()
+        .map(|b| b.node_id)
+        .collect();
+    let search_index = state.search_index.lock().unwrap();
+    let index = search_index
+        .as_ref()
+        .ok_or("Search index not initialized")?;
+    index
+        .quick_switch_candidates(&query, &bookmarks, QUICK_SWITCH_LIMIT)
+        .map_err(|e| format!("Failed to find switcher candidates: {}", e))
+}
+
+/// A document created for a wiki link
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LinkedDocument {
+    pub doc_id: String,
+    /// The title node, for the link to point at
+    pub node_id: String,
+    pub title: String,
+    /// False when the title already resolved to an existing node
+    pub created: bool,
+}
+
+/// Create the target of a dangling `[[title]]` link: a new document whose
+/// root node is the title, filed in the configured folder. If `source_node_id`
+/// (in the loaded document) is given, the link is recorded so it shows as a
+/// backlink right away. A title that already resolves returns that node.
+#[tauri::command]
+pub fn create_from_link(
+    state: State<AppState>,
+    title: String,
+    source_node_id: Option<String>,
+) -> Result<LinkedDocument, String> {
+    let title = title.trim().to_string();
+    if title.is_empty() {
+        return Err("Link title is empty".to_string());
+    }
+    let source_uuid = source_node_id.as_deref().map(parse_uuid).transpose()?;
+    let source_doc = state.current_document.lock().unwrap().as_ref().map(|d| d.id);
+
+    let search_index = state.search_index.lock().unwrap();
+    let existing = search_index
+        .as_ref()
+        .and_then(|index| index.resolve_wiki_link(&title).ok().flatten());
+    let (doc_id, node_id, created) = match existing {
+        Some(target) => (target.document_id, target.node_id, false),
+        None => {
+            ensure_dirs()?;
+            let root = Node::new(title.clone());
+            let node_id = root.id.to_string();
+            let folder = get_link_target_folder();
+            let result = create_imported_document(search_index.as_ref(), title.clone(), vec![root], folder.as_deref())?;
+            (result.doc_id, node_id, true)
+        }
+    };
+
+    if let (Some(index), Some(source), Some(source_doc)) = (search_index.as_ref(), source_uuid, source_doc) {
+        let target = parse_uuid(&node_id)?;
+        if let Err(e) = index.add_link(&source, &target, &source_doc) {
+            log::warn!("Failed to record link: {}", e);
+        }
+    }
+
+    Ok(LinkedDocument {
+        doc_id,
+        node_id,
+        title,
+        created,
+    })
+}
+
+/// Get the folder documents created from wiki links go in
+#[tauri::command]
+pub fn get_link_folder_setting() -> Option<String> {
+    get_link_target_folder()
+}
+
+/// Set the folder documents created from wiki links go in (None for the root)
+#[tauri::command]
+pub fn set_link_folder_setting(folder_id: Option<String>) -> Result<(), String> {
+    set_link_target_folder(folder_id)
+}
+
+/// A backlink read from the linking node's document
+fn live_backlink(doc_state: &DocumentState, document_id: Uuid, node: &Node) -> BacklinkResult {
+    BacklinkResult {
+        source_node_id: node.id.to_string(),
+        source_document_id: document_id.to_string(),
+        content: strip_html(&node.content),
+        document_title: doc_state
+            .sorted_children(None)
+            .first()
+            .map(|n| strip_html_for_title(&n.content)),
+        breadcrumbs: doc_state
+            .ancestors(node.id)
+            .into_iter()
+            .map(|n| strip_html_for_title(&n.content))
+            .collect(),
+    }
+}
+
+/// Generate iCalendar feed for all dated items in a document
+#[tauri::command]
+pub fn generate_ical_feed(state: State<AppState>) -> Result<String, String> {
     let current = state.current_document.lock().unwrap();
     let doc = current.as_ref().ok_or("No document loaded")?;
 
@@ -407,49 +1292,64 @@ pub fn generate_ical_feed(state: State<AppState>) -> Result<String, String> {
     ical.push_str("METHOD:PUBLISH\r\n");
     ical.push_str("X-WR-CALNAME:Outline Tasks\r\n");
 
-    for node in &doc.state.nodes {
-        if let Some(ref date) = node.date {
-            // Create VEVENT for each dated item
-            ical.push_str("BEGIN:VEVENT\r\n");
-
-            // UID - unique identifier
-            ical.push_str(&format!("UID:{}@outline.local\r\n", node.id));
-
-            // DTSTAMP - creation timestamp
-            ical.push_str(&format!(
-                "DTSTAMP:{}\r\n",
-                node.created_at.format("%Y%m%dT%H%M%SZ")
-            ));
-
-            // DTSTART - all-day event format
-            let date_compact = date.replace("-", "");
-            ical.push_str(&format!("DTSTART;VALUE=DATE:{}\r\n", date_compact));
-
-            // SUMMARY - strip HTML from content
-            let summary = strip_html_for_title(&node.content);
-            let escaped_summary = escape_ical_text(&summary);
-            ical.push_str(&format!("SUMMARY:{}\r\n", escaped_summary));
-
-            // STATUS - based on is_checked
-            if node.is_checked {
-                ical.push_str("STATUS:COMPLETED\r\n");
-            } else {
-                ical.push_str("STATUS:CONFIRMED\r\n");
-            }
+    // Private documents publish an empty calendar
+    let nodes: &[Node] = if doc.meta.settings.excluded { &[] } else { &doc.state.nodes };
+    for node in nodes {
+        let reminder = node.reminder_time();
+        // Nodes with only a reminder become an event at the reminder time
+        let dtstart = match (&node.date, reminder) {
+            (Some(date), _) => format!("DTSTART;VALUE=DATE:{}", date.replace("-", "")),
+            (None, Some(at)) => format!("DTSTART:{}", at.format("%Y%m%dT%H%M%SZ")),
+            (None, None) => continue,
+        };
 
-            // RRULE - if recurring
-            if let Some(ref rrule) = node.date_recurrence {
-                ical.push_str(&format!("RRULE:{}\r\n", rrule));
-            }
+        ical.push_str("BEGIN:VEVENT\r\n");
 
-            // DESCRIPTION - note field if present
-            if let Some(ref note) = node.note {
-                let escaped_note = escape_ical_text(note);
-                ical.push_str(&format!("DESCRIPTION:{}\r\n", escaped_note));
-            }
+        // UID - unique identifier
+        ical.push_str(&format!("UID:{}@outline.local\r\n", node.id));
+
+        // DTSTAMP - creation timestamp
+        ical.push_str(&format!(
+            "DTSTAMP:{}\r\n",
+            node.created_at.format("%Y%m%dT%H%M%SZ")
+        ));
+
+        // DTSTART - all-day event for dated items
+        ical.push_str(&format!("{}\r\n", dtstart));
+
+        // SUMMARY - strip HTML from content
+        let summary = strip_html_for_title(&node.content);
+        let escaped_summary = escape_ical_text(&summary);
+        ical.push_str(&format!("SUMMARY:{}\r\n", escaped_summary));
+
+        // STATUS - based on is_checked
+        if node.is_checked {
+            ical.push_str("STATUS:COMPLETED\r\n");
+        } else {
+            ical.push_str("STATUS:CONFIRMED\r\n");
+        }
+
+        // RRULE - if recurring
+        if let (Some(_), Some(ref rrule)) = (&node.date, &node.date_recurrence) {
+            ical.push_str(&format!("RRULE:{}\r\n", rrule));
+        }
+
+        // DESCRIPTION - note field if present
+        if let Some(ref note) = node.note {
+            let escaped_note = escape_ical_text(note);
+            ical.push_str(&format!("DESCRIPTION:{}\r\n", escaped_note));
+        }
 
-            ical.push_str("END:VEVENT\r\n");
+        // VALARM - the reminder, at its own time rather than the due date
+        if let (Some(at), false) = (reminder, node.is_checked) {
+            ical.push_str("BEGIN:VALARM\r\n");
+            ical.push_str("ACTION:DISPLAY\r\n");
+            ical.push_str(&format!("DESCRIPTION:{}\r\n", escaped_summary));
+            ical.push_str(&format!("TRIGGER;VALUE=DATE-TIME:{}\r\n", at.format("%Y%m%dT%H%M%SZ")));
+            ical.push_str("END:VALARM\r\n");
         }
+
+        ical.push_str("END:VEVENT\r\n");
     }
 
     ical.push_str("END:VCALENDAR\r\n");
@@ -507,6 +1407,26 @@ pub fn get_next_occurrence(
     }
 }
 
+/// Complete a recurring task in the current document: either move it to its
+/// next date, or (with the document's `recurring_instances` setting) check it
+/// off and add a new task for the next occurrence below it
+#[tauri::command]
+pub fn complete_recurring(state: State<AppState>, node_id: String) -> Result<DocumentState, String> {
+    let node_uuid = parse_uuid(&node_id)?;
+    let today = chrono::Local::now().date_naive();
+    let (spawn_instance, mut ops) = {
+        let current = state.current_document.lock().unwrap();
+        let doc = current.as_ref().ok_or("No document loaded")?;
+        let spawn_instance = doc.meta.settings.recurring_instances;
+        let (_, ops) = complete_recurring_ops(&doc.state, node_uuid, spawn_instance, today)?;
+        (spawn_instance, ops)
+    };
+    if spawn_instance {
+        ops.extend(completed_to_bottom_op(&state, node_uuid));
+    }
+    save_ops(&state, &ops)
+}
+
 /// Get all inbox items
 #[tauri::command]
 pub fn get_inbox() -> Result<Vec<InboxItem>, String> {
@@ -545,36 +1465,58 @@ fn import_nodes_to_document(doc: &mut Document, nodes: Vec<Node>) -> Result<(),
         let changes = NodeChanges {
             note: node.note,
             heading_level: node.heading_level,
+            language: node.language,
             is_checked: if node.is_checked { Some(true) } else { None },
+            priority: node.priority,
             color: node.color,
             tags: if node.tags.is_empty() {
                 None
             } else {
                 Some(node.tags)
             },
+            properties: if node.properties.is_empty() {
+                None
+            } else {
+                Some(node.properties)
+            },
             date: node.date,
             date_recurrence: node.date_recurrence,
+            remind_at: node.remind_at,
             collapsed: if node.collapsed { Some(true) } else { None },
             mirror_source_id: node.mirror_source_id,
+            encrypted: node.encrypted.then_some(true),
             ..Default::default()
         };
 
         // Only create update operation if there's something to update
         let has_changes = changes.note.is_some()
             || changes.heading_level.is_some()
+            || changes.language.is_some()
             || changes.is_checked.is_some()
+            || changes.priority.is_some()
             || changes.color.is_some()
             || changes.tags.is_some()
+            || changes.properties.is_some()
             || changes.date.is_some()
             || changes.date_recurrence.is_some()
+            || changes.remind_at.is_some()
             || changes.collapsed.is_some()
-            || changes.mirror_source_id.is_some();
+            || changes.mirror_source_id.is_some()
+            || changes.encrypted.is_some();
 
         if has_changes {
             let update = update_op(node.id, changes);
             doc.append_op(&update)?;
             update.apply(&mut doc.state);
         }
+
+        // Comments keep their ids, authors and resolved state
+        for comment in &node.comments {
+            for op in restore_comment_ops(node.id, comment) {
+                doc.append_op(&op)?;
+                op.apply(&mut doc.state);
+            }
+        }
     }
     Ok(())
 }
@@ -594,6 +1536,21 @@ pub fn import_opml(
     Ok(doc.state.clone())
 }
 
+/// Import Markdown content (e.g. an Obsidian note) into the current document
+#[tauri::command]
+pub fn import_markdown(
+    state: State<AppState>,
+    content: String,
+) -> Result<DocumentState, String> {
+    let mut current = state.current_document.lock().unwrap();
+    let doc = current.as_mut().ok_or("No document loaded")?;
+
+    let nodes = crate::import_export::parse_markdown(&content);
+    import_nodes_to_document(doc, nodes)?;
+
+    Ok(doc.state.clone())
+}
+
 /// Import result for OPML as new document
 #[derive(Clone, serde::Serialize)]
 pub struct ImportResult {
@@ -708,75 +1665,345 @@ pub fn import_dynalist_backup(
                     .to_string()
             });
 
-        // Create new document
-        let doc_uuid = Uuid::now_v7();
-        let doc_dir = documents_dir().join(doc_uuid.to_string());
-
-        let mut doc = match Document::create(doc_dir) {
-            Ok(d) => d,
-            Err(e) => {
-                log::warn!("Failed to create document for {}: {}", name, e);
-                continue;
-            }
-        };
-
-        if let Err(e) = import_nodes_to_document(&mut doc, nodes.clone()) {
-            log::warn!("Failed to import nodes for {}: {}", name, e);
-            continue;
+        let index = search_index.as_ref().and_then(|guard| guard.as_ref());
+        match create_imported_document(index, title, nodes, folder_id.as_deref()) {
+            Ok(result) => results.push(result),
+            Err(e) => log::warn!("Failed to import {}: {}", name, e),
         }
+    }
 
-        let node_count = doc.state.nodes.len();
-
-        // Index for search
-        if let Some(ref index_guard) = search_index {
-            if let Some(ref index) = **index_guard {
-                let _ = index.index_document(&doc_uuid, &doc.state.nodes);
-                let _ = index.update_document_links(&doc_uuid, &doc.state.nodes);
-            }
-        }
+    Ok(results)
+}
 
-        // Move to folder if specified
-        if let Some(ref fid) = folder_id {
-            let _ = crate::data::move_document_to_folder(&doc_uuid.to_string(), Some(fid), None);
-        }
+/// An OPML file passed to a batch import by content
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct OpmlFile {
+    pub name: String,
+    pub content: String,
+}
 
-        results.push(ImportResult {
-            doc_id: doc_uuid.to_string(),
-            title,
-            node_count,
-        });
-    }
+/// What happened to one file of a batch import
+#[derive(Clone, serde::Serialize)]
+pub struct BatchImportEntry {
+    /// File name or path
+    pub name: String,
+    pub result: Option<ImportResult>,
+    pub error: Option<String>,
+}
 
-    Ok(results)
+/// Result of a batch import
+#[derive(Clone, serde::Serialize)]
+pub struct BatchImportReport {
+    pub folder_id: String,
+    pub folder_name: String,
+    pub entries: Vec<BatchImportEntry>,
 }
 
-/// Find and import the latest Dynalist OPML backup from Dropbox
+/// Import many OPML files at once, given as paths and/or contents, creating
+/// one indexed document each in a folder named after the batch (by default
+/// "Import <date time>"). A file that fails is reported and the rest go on.
 #[tauri::command]
-pub fn import_latest_dynalist_backup(
+pub fn import_opml_batch(
     state: State<AppState>,
+    paths: Option<Vec<String>>,
+    contents: Option<Vec<OpmlFile>>,
     folder_name: Option<String>,
-) -> Result<Vec<ImportResult>, String> {
-    // Find Dropbox Dynalist backup directory
-    let home = dirs::home_dir().ok_or("Could not find home directory")?;
-    let backup_dir = home.join("Dropbox/Apps/Dynalist/backups");
+) -> Result<BatchImportReport, String> {
+    ensure_dirs()?;
 
-    if !backup_dir.exists() {
-        return Err(format!("Dynalist backup directory not found: {:?}", backup_dir));
+    let mut files: Vec<(String, Result<String, String>)> = paths
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| {
+            let content = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read file: {}", e));
+            (path, content)
+        })
+        .collect();
+    files.extend(contents.unwrap_or_default().into_iter().map(|f| (f.name, Ok(f.content))));
+    if files.is_empty() {
+        return Err("No files to import".to_string());
     }
 
-    // Find the latest OPML backup
-    let mut backups: Vec<_> = std::fs::read_dir(&backup_dir)
-        .map_err(|e| format!("Failed to read backup directory: {}", e))?
-        .filter_map(|e| e.ok())
-        .filter(|e| {
-            let name = e.file_name().to_string_lossy().to_string();
-            name.starts_with("dynalist-backup-opml-") && name.ends_with(".zip")
+    let folder_name = folder_name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| format!("Import {}", chrono::Local::now().format("%Y-%m-%d %H:%M")));
+    let folder = crate::data::get_or_create_folder(&folder_name)?;
+
+    let search_index = state.search_index.lock().unwrap();
+    let entries = files
+        .into_iter()
+        .map(|(name, content)| {
+            let imported = content.and_then(|content| {
+                let nodes = crate::import_export::parse_opml(&content)?;
+                let title = crate::import_export::get_opml_title(&content).unwrap_or_else(|| {
+                    std::path::Path::new(&name)
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().to_string())
+                        .unwrap_or_else(|| name.clone())
+                });
+                create_imported_document(search_index.as_ref(), title, nodes, Some(&folder.id))
+            });
+            if let Err(ref e) = imported {
+                log::warn!("Failed to import {}: {}", name, e);
+            }
+            BatchImportEntry {
+                name,
+                error: imported.as_ref().err().cloned(),
+                result: imported.ok(),
+            }
         })
         .collect();
 
-    if backups.is_empty() {
-        return Err("No Dynalist OPML backups found".to_string());
-    }
+    Ok(BatchImportReport {
+        folder_id: folder.id,
+        folder_name,
+        entries,
+    })
+}
+
+/// Import an Evernote ENEX export (Apple Notes can write these too), given as
+/// a path or content, creating one document per note in a folder named after
+/// the file (by default). Notes keep their creation dates and tags.
+#[tauri::command]
+pub fn import_enex(
+    state: State<AppState>,
+    path: Option<String>,
+    content: Option<String>,
+    folder_name: Option<String>,
+) -> Result<BatchImportReport, String> {
+    ensure_dirs()?;
+
+    let content = match (content, &path) {
+        (Some(content), _) => content,
+        (None, Some(path)) => std::fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?,
+        (None, None) => return Err("No file to import".to_string()),
+    };
+    let notes = crate::import_export::parse_enex(&content)?;
+    if notes.is_empty() {
+        return Err("No notes found in ENEX file".to_string());
+    }
+
+    let default_folder = path.as_deref().and_then(|p| {
+        std::path::Path::new(p)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+    });
+    let notes = notes.into_iter().map(|note| (note.title.clone(), Ok(note))).collect();
+    import_notes(&state, notes, folder_name.or(default_folder))
+}
+
+/// Import a folder of plain text files (.txt, .text, .md), one document per
+/// file titled after the file name, in a folder named after the directory
+/// (by default). Files keep their creation dates.
+#[tauri::command]
+pub fn import_text_folder(
+    state: State<AppState>,
+    path: String,
+    folder_name: Option<String>,
+) -> Result<BatchImportReport, String> {
+    ensure_dirs()?;
+
+    let dir = std::path::Path::new(&path);
+    let mut files: Vec<std::path::PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read folder: {}", e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.is_file()
+                && p.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| matches!(ext.to_lowercase().as_str(), "txt" | "text" | "md"))
+        })
+        .collect();
+    if files.is_empty() {
+        return Err("No text files to import".to_string());
+    }
+    files.sort();
+
+    let notes = files
+        .into_iter()
+        .map(|file| {
+            let name = file
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let note = std::fs::read_to_string(&file)
+                .map_err(|e| format!("Failed to read file: {}", e))
+                .map(|text| {
+                    let title = file
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    let created_at = std::fs::metadata(&file)
+                        .and_then(|m| m.created().or_else(|_| m.modified()))
+                        .ok()
+                        .map(chrono::DateTime::<chrono::Utc>::from);
+                    crate::import_export::note_from_text(&title, &text, created_at, Vec::new())
+                });
+            (name, note)
+        })
+        .collect();
+    let default_folder = dir.file_name().map(|n| n.to_string_lossy().to_string());
+    import_notes(&state, notes, folder_name.or(default_folder))
+}
+
+/// Create a document for each imported note in one folder, reporting notes
+/// that fail rather than stopping
+fn import_notes(
+    state: &AppState,
+    notes: Vec<(String, Result<crate::import_export::ImportedNote, String>)>,
+    folder_name: Option<String>,
+) -> Result<BatchImportReport, String> {
+    let folder_name = folder_name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| format!("Import {}", chrono::Local::now().format("%Y-%m-%d %H:%M")));
+    let folder = crate::data::get_or_create_folder(&folder_name)?;
+
+    let search_index = state.search_index.lock().unwrap();
+    let entries = notes
+        .into_iter()
+        .map(|(name, note)| {
+            let imported = note.and_then(|note| {
+                create_imported_document(search_index.as_ref(), note.title, note.nodes, Some(&folder.id))
+            });
+            if let Err(ref e) = imported {
+                log::warn!("Failed to import {}: {}", name, e);
+            }
+            BatchImportEntry {
+                name,
+                error: imported.as_ref().err().cloned(),
+                result: imported.ok(),
+            }
+        })
+        .collect();
+
+    Ok(BatchImportReport {
+        folder_id: folder.id,
+        folder_name,
+        entries,
+    })
+}
+
+/// Create a document of `node_count` synthetic nodes nested `depth` levels
+/// deep, for performance testing. The nodes are written straight to
+/// state.json rather than as ops.
+#[tauri::command]
+pub fn generate_test_document(
+    state: State<AppState>,
+    node_count: usize,
+    depth: usize,
+) -> Result<ImportResult, String> {
+    ensure_dirs()?;
+    let doc_uuid = Uuid::now_v7();
+    let mut doc = Document::create(documents_dir().join(doc_uuid.to_string()))?;
+    doc.state.nodes = crate::data::generate_test_nodes(node_count, depth);
+    doc.save_state()?;
+
+    if let Some(ref index) = *state.search_index.lock().unwrap() {
+        if let Err(e) = index.index_document(&doc_uuid, &doc.state.nodes) {
+            log::warn!("Failed to index test document: {}", e);
+        }
+    }
+
+    Ok(ImportResult {
+        doc_id: doc_uuid.to_string(),
+        title: format!("Test document ({} nodes)", node_count),
+        node_count: doc.state.nodes.len(),
+    })
+}
+
+/// Create a new document from imported nodes, index it, and optionally file
+/// it in a folder
+fn create_imported_document(
+    search_index: Option<&SearchIndex>,
+    title: String,
+    nodes: Vec<Node>,
+    folder_id: Option<&str>,
+) -> Result<ImportResult, String> {
+    let doc_uuid = Uuid::now_v7();
+    let doc_dir = documents_dir().join(doc_uuid.to_string());
+
+    let mut doc = Document::create(doc_dir)?;
+    import_nodes_to_document(&mut doc, nodes)?;
+
+    let node_count = doc.state.nodes.len();
+
+    // Index for search
+    if let Some(index) = search_index {
+        let _ = index.index_document(&doc_uuid, &doc.state.nodes);
+        let _ = index.update_document_links(&doc_uuid, &doc.state.nodes);
+    }
+
+    // Move to folder if specified
+    if let Some(fid) = folder_id {
+        let _ = crate::data::move_document_to_folder(&doc_uuid.to_string(), Some(fid), None);
+    }
+
+    Ok(ImportResult {
+        doc_id: doc_uuid.to_string(),
+        title,
+        node_count,
+    })
+}
+
+/// Import every document from a Dynalist account via the Dynalist API,
+/// recreating its folder structure (nested folders become "Parent / Child")
+/// and turning links between its documents into wiki links
+#[tauri::command]
+pub async fn import_dynalist_api(
+    state: State<'_, AppState>,
+    token: String,
+) -> Result<Vec<ImportResult>, String> {
+    ensure_dirs()?;
+
+    let documents = crate::import_export::fetch_dynalist_documents(&token).await?;
+
+    let search_index = state.search_index.lock().unwrap();
+    let mut results = Vec::new();
+    for document in documents {
+        let folder_id = match document.folder {
+            Some(ref name) => Some(crate::data::get_or_create_folder(name)?.id),
+            None => None,
+        };
+        results.push(create_imported_document(
+            search_index.as_ref(),
+            document.title,
+            document.nodes,
+            folder_id.as_deref(),
+        )?);
+    }
+
+    Ok(results)
+}
+
+/// Find and import the latest Dynalist OPML backup from Dropbox
+#[tauri::command]
+pub fn import_latest_dynalist_backup(
+    state: State<AppState>,
+    folder_name: Option<String>,
+) -> Result<Vec<ImportResult>, String> {
+    // Find Dropbox Dynalist backup directory
+    let home = dirs::home_dir().ok_or("Could not find home directory")?;
+    let backup_dir = home.join("Dropbox/Apps/Dynalist/backups");
+
+    if !backup_dir.exists() {
+        return Err(format!("Dynalist backup directory not found: {:?}", backup_dir));
+    }
+
+    // Find the latest OPML backup
+    let mut backups: Vec<_> = std::fs::read_dir(&backup_dir)
+        .map_err(|e| format!("Failed to read backup directory: {}", e))?
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            name.starts_with("dynalist-backup-opml-") && name.ends_with(".zip")
+        })
+        .collect();
+
+    if backups.is_empty() {
+        return Err("No Dynalist OPML backups found".to_string());
+    }
 
     // Sort by modification time (newest first)
     backups.sort_by(|a, b| {
@@ -794,22 +2021,95 @@ pub fn import_latest_dynalist_backup(
     import_dynalist_backup(state, zip_path, folder_name)
 }
 
-/// Export current document to OPML format
+/// Export current document to OPML format (node colors included unless
+/// `colors` is false)
+#[tauri::command]
+pub fn export_opml(
+    state: State<AppState>,
+    title: String,
+    scope: Option<ExportScope>,
+    colors: Option<bool>,
+) -> Result<String, String> {
+    let _timer = crate::perf::timer("export.opml");
+    let current = state.current_document.lock().unwrap();
+    let doc = current.as_ref().ok_or("No document loaded")?;
+
+    let nodes = scope.unwrap_or_default().apply(&doc.state.nodes);
+    crate::import_export::generate_opml(&nodes, &title, colors.unwrap_or(true))
+}
+
+/// Nodes in other documents linking into a document, for export
+fn linked_references_for(state: &AppState, doc_uuid: &Uuid) -> Result<Vec<LinkedReference>, String> {
+    let search_index = state.search_index.lock().unwrap();
+    let index = search_index.as_ref().ok_or("Search index not initialized")?;
+    index
+        .linked_references(doc_uuid)
+        .map_err(|e| format!("Failed to get linked references: {}", e))
+}
+
+/// Export current document to Markdown format, with a "Linked references"
+/// section when `linked_references` is set
+#[tauri::command]
+pub fn export_markdown(
+    state: State<AppState>,
+    options: Option<MarkdownOptions>,
+    scope: Option<ExportScope>,
+    linked_references: Option<bool>,
+) -> Result<String, String> {
+    let _timer = crate::perf::timer("export.markdown");
+    let current = state.current_document.lock().unwrap();
+    let doc = current.as_ref().ok_or("No document loaded")?;
+
+    let nodes = scope.unwrap_or_default().apply(&doc.state.nodes);
+    let mut markdown = crate::import_export::generate_markdown(&nodes, &options.unwrap_or_default());
+    if linked_references.unwrap_or(false) {
+        markdown.push_str(&linked_references_markdown(&linked_references_for(&state, &doc.id)?));
+    }
+    Ok(markdown)
+}
+
+/// Export current document to a standalone HTML page (node colors included
+/// unless `colors` is false, linked references when `linked_references` is set)
 #[tauri::command]
-pub fn export_opml(state: State<AppState>, title: String) -> Result<String, String> {
+pub fn export_html(
+    state: State<AppState>,
+    title: String,
+    scope: Option<ExportScope>,
+    colors: Option<bool>,
+    linked_references: Option<bool>,
+) -> Result<String, String> {
+    let _timer = crate::perf::timer("export.html");
     let current = state.current_document.lock().unwrap();
     let doc = current.as_ref().ok_or("No document loaded")?;
 
-    crate::import_export::generate_opml(&doc.state.nodes, &title)
+    let nodes = scope.unwrap_or_default().apply(&doc.state.nodes);
+    let markdown_notes = doc.meta.settings.markdown_notes;
+    let mut page = crate::import_export::generate_html(&nodes, &title, colors.unwrap_or(true), markdown_notes);
+    if linked_references.unwrap_or(false) {
+        append_linked_references_html(&mut page, &linked_references_for(&state, &doc.id)?);
+    }
+    Ok(page)
 }
 
-/// Export current document to Markdown format
+/// Export current document to LaTeX: an article with headings as sections,
+/// or a Beamer deck with a frame per top-level node
 #[tauri::command]
-pub fn export_markdown(state: State<AppState>) -> Result<String, String> {
+pub fn export_latex(
+    state: State<AppState>,
+    title: String,
+    beamer: Option<bool>,
+    scope: Option<ExportScope>,
+) -> Result<String, String> {
+    let _timer = crate::perf::timer("export.latex");
     let current = state.current_document.lock().unwrap();
     let doc = current.as_ref().ok_or("No document loaded")?;
 
-    Ok(crate::import_export::generate_markdown(&doc.state.nodes))
+    let nodes = scope.unwrap_or_default().apply(&doc.state.nodes);
+    Ok(crate::import_export::generate_latex(
+        &nodes,
+        &title,
+        beamer.unwrap_or(false),
+    ))
 }
 
 /// Export selected nodes and their children to markdown
@@ -818,7 +2118,10 @@ pub fn export_selection_markdown(
     state: State<AppState>,
     node_ids: Vec<String>,
     include_completed_children: bool,
+    options: Option<MarkdownOptions>,
+    scope: Option<ExportScope>,
 ) -> Result<String, String> {
+    let _timer = crate::perf::timer("export.selection_markdown");
     let current = state.current_document.lock().unwrap();
     let doc = current.as_ref().ok_or("No document loaded")?;
 
@@ -884,12 +2187,17 @@ pub fn export_selection_markdown(
         export_nodes.push(node);
     }
 
-    Ok(crate::import_export::generate_markdown(&export_nodes))
+    let export_nodes = scope.unwrap_or_default().apply(&export_nodes);
+    Ok(crate::import_export::generate_markdown(
+        &export_nodes,
+        &options.unwrap_or_default(),
+    ))
 }
 
 /// Export current document to JSON backup format
 #[tauri::command]
 pub fn export_json(state: State<AppState>) -> Result<String, String> {
+    let _timer = crate::perf::timer("export.json");
     let current = state.current_document.lock().unwrap();
     let doc = current.as_ref().ok_or("No document loaded")?;
 
@@ -911,6 +2219,86 @@ pub fn import_json(
     Ok(doc.state.clone())
 }
 
+/// Export every document with the folder structure (folders, which document
+/// is in which, and their order) as one JSON backup
+#[tauri::command]
+pub fn export_workspace(state: State<AppState>) -> Result<String, String> {
+    let _timer = crate::perf::timer("export.workspace");
+    ensure_dirs()?;
+    let mut documents = Vec::new();
+    for doc_id in crate::data::list_documents()? {
+        let doc_state = document_state_for(&state, &doc_id)?;
+        let title = doc_state
+            .sorted_children(None)
+            .first()
+            .map(|n| strip_html_for_title(&n.content))
+            .unwrap_or_else(|| "Untitled".to_string());
+        documents.push(WorkspaceDocument {
+            id: doc_id.to_string(),
+            title,
+            nodes: doc_state.nodes,
+        });
+    }
+    generate_workspace_backup(documents, &load_folders()?)
+}
+
+/// What a workspace import created
+#[derive(Clone, serde::Serialize)]
+pub struct WorkspaceImportReport {
+    pub documents: Vec<ImportResult>,
+    /// Folders created; folders whose name already existed are reused
+    pub folders_created: usize,
+}
+
+/// Import a workspace backup: each document becomes a new document (with a
+/// new id) and goes back into its folder, in its old order. Folders are
+/// matched to existing ones by name, else recreated with their color and
+/// icon.
+#[tauri::command]
+pub fn import_workspace(state: State<AppState>, content: String) -> Result<WorkspaceImportReport, String> {
+    ensure_dirs()?;
+    let backup = parse_workspace_backup(&content)?;
+
+    let existing = load_folders()?;
+    let mut folder_ids: std::collections::HashMap<&str, String> = std::collections::HashMap::new();
+    let mut folders_created = 0;
+    for folder in &backup.folders {
+        let id = match existing.folders.iter().find(|f| f.name == folder.name) {
+            Some(existing) => existing.id.clone(),
+            None => {
+                let created = crate::data::create_folder(&folder.name)?;
+                crate::data::update_folder(
+                    &created.id,
+                    None,
+                    Some(folder.collapsed),
+                    folder.color.as_deref(),
+                    folder.icon.as_deref(),
+                )?;
+                folders_created += 1;
+                created.id
+            }
+        };
+        folder_ids.insert(folder.id.as_str(), id);
+    }
+
+    let search_index = state.search_index.lock().unwrap();
+    let mut documents = Vec::new();
+    for (document, folder_id) in backup.placements() {
+        let folder_id = folder_id.and_then(|id| folder_ids.get(id)).map(String::as_str);
+        documents.push(create_imported_document(
+            search_index.as_ref(),
+            document.title.clone(),
+            document.nodes.clone(),
+            folder_id,
+        )?);
+    }
+
+    Ok(WorkspaceImportReport {
+        documents,
+        folders_created,
+    })
+}
+
 /// Data directory info returned to frontend
 #[derive(Clone, serde::Serialize)]
 pub struct DataDirectoryInfo {
@@ -933,9 +2321,15 @@ pub fn get_data_directory() -> DataDirectoryInfo {
     }
 }
 
-/// Set the data directory (requires app restart to take full effect)
+/// Set the data directory (requires app restart to take full effect). A running
+/// documents watcher is moved to the new directory.
 #[tauri::command]
-pub fn set_data_directory(path: Option<String>) -> Result<DataDirectoryInfo, String> {
+pub fn set_data_directory(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    watcher_state: State<WatcherState>,
+    path: Option<String>,
+) -> Result<DataDirectoryInfo, String> {
     // Validate path if provided
     if let Some(ref path_str) = path {
         let path_buf = std::path::PathBuf::from(path_str);
@@ -959,13 +2353,215 @@ pub fn set_data_directory(path: Option<String>) -> Result<DataDirectoryInfo, Str
     }
 
     // Save to config file (preserve existing inbox setting)
-    let mut config = crate::data::load_config();
-    config.data_directory = path;
-    save_config(&config)?;
+    update_config(|config| config.data_directory = path)?;
+
+    if let Err(e) = crate::data::run_migrations() {
+        log::error!("Failed to migrate data directory: {}", e);
+    }
+    reopen_search_index(&state);
+    watcher_state.restart_if_moved(app)?;
+
+    Ok(get_data_directory())
+}
+
+/// Point the search index at the current data directory's cache, then
+/// rebuild it from every document there in the background
+fn reopen_search_index(state: &AppState) {
+    *state.search_index.lock().unwrap() = SearchIndex::open_or_recreate(&CacheMode::configured())
+        .map(|(index, _)| index)
+        .map_err(|e| log::error!("Failed to open search index: {}", e))
+        .ok();
+    state.indexer.rebuild();
+}
+
+/// Get the background indexer's progress
+#[tauri::command]
+pub fn get_index_status(state: State<AppState>) -> IndexStatus {
+    state.indexer.status()
+}
+
+/// Check the search cache's integrity, rebuilding it from the documents if
+/// it is damaged, then optimize the full-text index and vacuum the database
+#[tauri::command]
+pub fn maintain_search_cache(state: State<AppState>) -> Result<CacheMaintenanceReport, String> {
+    let mut search_index = state.search_index.lock().unwrap();
+    let size_before = search_index.as_ref().and_then(|index| index.size().ok()).unwrap_or(0);
+    let integrity_errors = match *search_index {
+        Some(ref index) => index.integrity_check().unwrap_or_else(|e| vec![e.to_string()]),
+        None => vec!["Search index is not open".to_string()],
+    };
+
+    let rebuilt = !integrity_errors.is_empty();
+    if rebuilt {
+        *search_index = None;
+        let index = SearchIndex::recreate(&CacheMode::configured()).map_err(|e| format!("Failed to recreate search index: {}", e))?;
+        index.rebuild()?;
+        *search_index = Some(index);
+    }
+
+    let index = search_index.as_ref().ok_or("Search index not available")?;
+    index.optimize().map_err(|e| format!("Failed to optimize search index: {}", e))?;
+    let size_after = index.size().map_err(|e| e.to_string())?;
+
+    Ok(CacheMaintenanceReport {
+        integrity_errors,
+        rebuilt,
+        size_before,
+        size_after,
+    })
+}
+
+/// Search cache settings for the current data directory
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SearchCacheInfo {
+    pub mode: SearchCacheMode,
+    /// Whether this build can encrypt the cache
+    pub encryption_supported: bool,
+}
+
+/// Get how the current data directory's search cache is stored
+#[tauri::command]
+pub fn get_search_cache_mode() -> SearchCacheInfo {
+    SearchCacheInfo {
+        mode: search_cache_config().mode,
+        encryption_supported: encryption_supported(),
+    }
+}
+
+/// Change how the current data directory's search cache is stored. The old
+/// cache is deleted and the index rebuilt from the documents.
+#[tauri::command]
+pub fn set_search_cache_mode(state: State<AppState>, mode: SearchCacheMode) -> Result<SearchCacheInfo, String> {
+    if mode == SearchCacheMode::Encrypted && !encryption_supported() {
+        return Err("This build can't encrypt the search cache (it needs SQLCipher)".to_string());
+    }
+    let mut config = search_cache_config();
+    if config.mode == mode {
+        return Ok(get_search_cache_mode());
+    }
+    config.key = match mode {
+        SearchCacheMode::Encrypted => Some(new_cache_key()),
+        _ => None,
+    };
+    config.mode = mode;
+    set_search_cache_config(config)?;
+
+    // The old cache can't be opened in the new mode, so it is replaced
+    reopen_search_index(&state);
+    Ok(get_search_cache_mode())
+}
+
+/// Re-encrypt the search cache with a new random key
+#[tauri::command]
+pub fn rekey_search_cache(state: State<AppState>) -> Result<(), String> {
+    let mut config = search_cache_config();
+    if config.mode != SearchCacheMode::Encrypted {
+        return Err("Search cache is not encrypted".to_string());
+    }
+    let search_index = state.search_index.lock().unwrap();
+    let index = search_index.as_ref().ok_or("Search index not available")?;
+    let key = new_cache_key();
+    index.rekey(&key).map_err(|e| format!("Failed to rekey search cache: {}", e))?;
+    config.key = Some(key);
+    set_search_cache_config(config)
+}
+
+/// Copy (or move) everything in the data directory but its cache to
+/// `new_path` and switch to it, emitting `data-migration-progress` events
+/// along the way. Nothing changes if a copy or saving the new location
+/// fails. Edits to the current document wait, and the watcher and indexer
+/// are paused, until the copy is done; then the current document is
+/// re-opened from the new location and the search index rebuilt. Refused while `--data-dir` or
+/// $OUTLINE_DATA_DIR picks the data directory, since config.json would be
+/// ignored at the next launch.
+#[tauri::command]
+pub async fn migrate_data_directory(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    watcher_state: State<'_, WatcherState>,
+    new_path: String,
+    move_files: bool,
+) -> Result<DataDirectoryInfo, String> {
+    use tauri::Emitter;
+
+    if let Some(source) = data_dir_override_source() {
+        return Err(format!(
+            "The data directory is set by {}, so it can't be moved from here",
+            source
+        ));
+    }
+    let new_dir = std::path::PathBuf::from(&new_path);
+    ensure_dirs()?;
+
+    let watching = watcher_state.is_running();
+    let migrated = {
+        let paused = state.indexer.pause();
+        let mut current = state.current_document.lock().unwrap();
+        watcher_state.stop();
+
+        // The new location is saved before any original is removed, so a
+        // failed save can't leave the next launch on an emptied directory
+        let migrated = crate::data::migrate_data(
+            &data_dir(),
+            &new_dir,
+            move_files,
+            |progress| {
+                let _ = app.emit("data-migration-progress", progress);
+            },
+            || update_config(|config| config.data_directory = Some(new_path)),
+        );
+        if let Ok(count) = migrated {
+            log::info!("Migrated {} files to {:?}", count, new_dir);
+            set_data_dir(Some(new_dir));
+            if let Some(doc_id) = current.as_ref().map(|doc| doc.id) {
+                *current = Document::load(documents_dir().join(doc_id.to_string())).ok();
+            }
+        }
+        drop(current);
+        drop(paused);
+        migrated
+    };
+    if watching {
+        watcher_state.set_handle(crate::watcher::start_watcher(app)?);
+    }
+    migrated?;
+
+    reopen_search_index(&state);
 
     Ok(get_data_directory())
 }
 
+/// Get when op and state files are fsynced to disk
+#[tauri::command]
+pub fn get_fsync_policy() -> FsyncPolicy {
+    crate::data::fsync_policy()
+}
+
+/// Change when op and state files are fsynced to disk, saving it to config
+#[tauri::command]
+pub fn set_fsync_policy(policy: FsyncPolicy) -> Result<(), String> {
+    crate::data::set_fsync_policy(policy);
+    update_config(|config| config.fsync = Some(policy))
+}
+
+/// Get the MQTT broker events are published to, if any
+#[tauri::command]
+pub fn get_mqtt_config() -> Option<MqttConfig> {
+    crate::data::load_config().mqtt
+}
+
+/// Change (or, with None, stop) MQTT publishing, saving it to config
+#[tauri::command]
+pub fn set_mqtt_config(
+    app: tauri::AppHandle,
+    mqtt_state: State<crate::mqtt::MqttState>,
+    config: Option<MqttConfig>,
+) -> Result<(), String> {
+    update_config(|app_config| app_config.mqtt = config.clone())?;
+    mqtt_state.restart(app, config);
+    Ok(())
+}
+
 /// Open a directory picker dialog and return the selected path
 #[tauri::command]
 pub async fn pick_directory(window: tauri::Window) -> Result<Option<String>, String> {
@@ -1012,10 +2608,16 @@ pub fn create_folder(name: String) -> Result<Folder, String> {
     create_folder_impl(&name)
 }
 
-/// Update a folder's name or collapsed state
+/// Update a folder's name, collapsed state, color or icon (empty color/icon clears it)
 #[tauri::command]
-pub fn update_folder(id: String, name: Option<String>, collapsed: Option<bool>) -> Result<Folder, String> {
-    update_folder_impl(&id, name.as_deref(), collapsed)
+pub fn update_folder(
+    id: String,
+    name: Option<String>,
+    collapsed: Option<bool>,
+    color: Option<String>,
+    icon: Option<String>,
+) -> Result<Folder, String> {
+    update_folder_impl(&id, name.as_deref(), collapsed, color.as_deref(), icon.as_deref())
 }
 
 /// Delete a folder (documents move to root level)
@@ -1036,21 +2638,267 @@ pub fn reorder_folders(folder_ids: Vec<String>) -> Result<(), String> {
     reorder_folders_impl(folder_ids)
 }
 
-/// Save content to a file using the native save dialog
+/// Sidebar badge counts for a folder
+#[derive(Clone, serde::Serialize)]
+pub struct FolderSummary {
+    pub folder_id: String,
+    pub document_count: usize,
+    pub node_count: usize,
+    pub overdue_count: usize,
+}
+
+/// Document, node and overdue-task counts per folder, from the search cache
+/// (no documents are loaded)
 #[tauri::command]
-pub async fn save_to_file_with_dialog(
+pub fn get_folder_summaries(state: State<AppState>) -> Result<Vec<FolderSummary>, String> {
+    let folders = load_folders()?;
+    let existing: std::collections::HashSet<String> = crate::data::list_documents()?
+        .iter()
+        .map(|id| id.to_string())
+        .collect();
+
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let stats = {
+        let search_index = state.search_index.lock().unwrap();
+        let index = search_index
+            .as_ref()
+            .ok_or("Search index not initialized")?;
+        index
+            .document_stats(&today)
+            .map_err(|e| format!("Search error: {}", e))?
+    };
+
+    let mut summaries: Vec<FolderSummary> = folders
+        .folders
+        .iter()
+        .map(|f| FolderSummary {
+            folder_id: f.id.clone(),
+            document_count: 0,
+            node_count: 0,
+            overdue_count: 0,
+        })
+        .collect();
+
+    for (doc_id, folder_id) in &folders.document_folders {
+        if !existing.contains(doc_id) {
+            continue;
+        }
+        if let Some(summary) = summaries.iter_mut().find(|s| s.folder_id == *folder_id) {
+            let doc_stats = stats.get(doc_id).cloned().unwrap_or_default();
+            summary.document_count += 1;
+            summary.node_count += doc_stats.node_count;
+            summary.overdue_count += doc_stats.overdue_count;
+        }
+    }
+
+    Ok(summaries)
+}
+
+/// Export every document in a folder into a zip of Markdown or OPML files
+/// named by document title, saved via the native save dialog. Returns the
+/// saved path, or None if the dialog was cancelled. Markdown files end with
+/// their linked references when `linked_references` is set.
+#[tauri::command]
+pub async fn export_folder(
     app: tauri::AppHandle,
-    content: String,
-    suggested_filename: String,
-    extension: String,
+    state: State<'_, AppState>,
+    folder_id: String,
+    format: ArchiveFormat,
+    linked_references: Option<bool>,
 ) -> Result<Option<String>, String> {
     use tauri_plugin_dialog::DialogExt;
 
-    // Build the save dialog
-    let file_path = app
+    let folders = load_folders()?;
+    let folder = folders
+        .folders
+        .iter()
+        .find(|f| f.id == folder_id)
+        .ok_or_else(|| format!("Folder not found: {}", folder_id))?;
+
+    // Sidebar order first, then any assigned documents missing from it
+    let mut doc_ids: Vec<String> = folders.document_order.get(&folder_id).cloned().unwrap_or_default();
+    for (doc_id, fid) in &folders.document_folders {
+        if *fid == folder_id && !doc_ids.contains(doc_id) {
+            doc_ids.push(doc_id.clone());
+        }
+    }
+
+    let mut documents = Vec::new();
+    for doc_id in doc_ids.iter().filter(|id| folders.document_folders.get(*id) == Some(&folder_id)) {
+        let doc_uuid = parse_uuid(doc_id)?;
+        let doc_state = document_state_for(&state, &doc_uuid)?;
+        let title = doc_state
+            .sorted_children(None)
+            .first()
+            .map(|n| strip_html_for_title(&n.content))
+            .unwrap_or_else(|| "Untitled".to_string());
+        let references = if linked_references.unwrap_or(false) {
+            linked_references_for(&state, &doc_uuid)?
+        } else {
+            Vec::new()
+        };
+        documents.push(ArchiveDocument {
+            title,
+            nodes: doc_state.nodes,
+            references,
+        });
+    }
+
+    let bytes = build_document_archive(&documents, format)?;
+
+    let file_path = app
+        .dialog()
+        .file()
+        .add_filter("Zip Archives", &["zip"])
+        .set_file_name(format!("{}.zip", folder.name))
+        .blocking_save_file();
+
+    match file_path {
+        Some(file_path) => {
+            let path = file_path
+                .into_path()
+                .map_err(|e| format!("Failed to get file path: {}", e))?;
+            std::fs::write(&path, bytes).map_err(|e| format!("Failed to write file: {}", e))?;
+            Ok(Some(path.to_string_lossy().to_string()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// A pinned document or node with its display title
+#[derive(Clone, serde::Serialize)]
+pub struct FavoriteInfo {
+    pub document_id: String,
+    pub node_id: Option<String>,
+    pub title: String,
+}
+
+/// Pin or unpin a document (or a node within it); returns whether it is now pinned
+#[tauri::command]
+pub fn toggle_favorite(doc_id: String, node_id: Option<String>) -> Result<bool, String> {
+    toggle_favorite_impl(&doc_id, node_id.as_deref())
+}
+
+/// List pinned documents and nodes for the sidebar, skipping any that no longer exist
+#[tauri::command]
+pub fn list_favorites(state: State<AppState>) -> Result<Vec<FavoriteInfo>, String> {
+    let favorites = load_folders()?.favorites;
+    let mut states: std::collections::HashMap<String, Option<DocumentState>> =
+        std::collections::HashMap::new();
+    let mut result = Vec::new();
+
+    for favorite in favorites {
+        let doc_state = states.entry(favorite.document_id.clone()).or_insert_with(|| {
+            parse_uuid(&favorite.document_id)
+                .and_then(|uuid| document_state_for(&state, &uuid))
+                .ok()
+        });
+        let Some(doc_state) = doc_state else {
+            continue;
+        };
+
+        let node = match favorite.node_id {
+            Some(ref node_id) => doc_state.nodes.iter().find(|n| n.id.to_string() == *node_id),
+            // A document's title is its first root node
+            None => doc_state.sorted_children(None).into_iter().next(),
+        };
+        if favorite.node_id.is_some() && node.is_none() {
+            continue;
+        }
+
+        result.push(FavoriteInfo {
+            title: node
+                .map(|n| strip_html_for_title(&n.content))
+                .unwrap_or_else(|| "Untitled".to_string()),
+            document_id: favorite.document_id,
+            node_id: favorite.node_id,
+        });
+    }
+
+    Ok(result)
+}
+
+/// A bookmarked node with where it lives, for the bookmarks panel
+#[derive(Clone, serde::Serialize)]
+pub struct BookmarkInfo {
+    pub document_id: String,
+    pub node_id: String,
+    pub content: String,
+    /// Ancestors from the top level down to the node's parent
+    pub breadcrumbs: Vec<AncestorInfo>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Bookmark a node (or remove its bookmark); returns whether it is now bookmarked
+#[tauri::command]
+pub fn bookmark_node(doc_id: String, node_id: String, bookmarked: bool) -> Result<bool, String> {
+    parse_uuid(&doc_id)?;
+    parse_uuid(&node_id)?;
+    bookmark_node_impl(&doc_id, &node_id, bookmarked)
+}
+
+/// List bookmarked nodes across all documents. Bookmarks whose node or
+/// document was deleted (e.g. on another machine) are cleaned up.
+#[tauri::command]
+pub fn list_bookmarks(state: State<AppState>) -> Result<Vec<BookmarkInfo>, String> {
+    let bookmarks = load_folders()?.bookmarks;
+    let mut states: std::collections::HashMap<String, Option<DocumentState>> =
+        std::collections::HashMap::new();
+    let mut missing: std::collections::HashMap<String, Vec<String>> = std::collections::HashMap::new();
+    let mut result = Vec::new();
+
+    for bookmark in bookmarks {
+        let doc_state = states.entry(bookmark.document_id.clone()).or_insert_with(|| {
+            parse_uuid(&bookmark.document_id)
+                .and_then(|uuid| document_state_for(&state, &uuid))
+                .ok()
+        });
+        let node = doc_state
+            .as_ref()
+            .and_then(|s| s.nodes.iter().find(|n| n.id.to_string() == bookmark.node_id));
+        let (Some(doc_state), Some(node)) = (doc_state.as_ref(), node) else {
+            // Only forget bookmarks once the document is known to be gone or
+            // loaded without the node, not when it merely failed to load
+            let doc_gone = !documents_dir().join(&bookmark.document_id).exists();
+            if doc_gone || doc_state.is_some() {
+                missing.entry(bookmark.document_id).or_default().push(bookmark.node_id);
+            }
+            continue;
+        };
+
+        result.push(BookmarkInfo {
+            content: node.content.clone(),
+            breadcrumbs: ancestor_infos(doc_state, node.id),
+            document_id: bookmark.document_id,
+            node_id: bookmark.node_id,
+            created_at: bookmark.created_at,
+        });
+    }
+
+    for (doc_id, node_ids) in missing {
+        if let Err(e) = remove_bookmarks(&doc_id, &node_ids) {
+            log::warn!("Failed to clean up bookmarks in {}: {}", doc_id, e);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Save content to a file using the native save dialog
+#[tauri::command]
+pub async fn save_to_file_with_dialog(
+    app: tauri::AppHandle,
+    content: String,
+    suggested_filename: String,
+    extension: String,
+) -> Result<Option<String>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    // Build the save dialog
+    let file_path = app
         .dialog()
         .file()
-        .add_filter(&format!("{} Files", extension.to_uppercase()), &[&extension])
+        .add_filter(format!("{} Files", extension.to_uppercase()), &[&extension])
         .set_file_name(&suggested_filename)
         .blocking_save_file();
 
@@ -1141,19 +2989,19 @@ pub fn import_inbox_items(state: State<AppState>) -> Result<u32, String> {
         return Err("Inbox node not found in document".to_string());
     }
 
-    // Get current max position among inbox node's children
-    let max_position = doc.state.nodes.iter()
-        .filter(|n| n.parent_id == Some(inbox_node_id))
-        .map(|n| n.position)
-        .max()
-        .unwrap_or(-1);
+    // Append after the inbox node's current last child
+    let mut last_position = doc.state.sorted_children(Some(inbox_node_id))
+        .last()
+        .map(|n| n.position);
 
     // Import each item as a child of the inbox node
     let mut imported = 0;
     let mut item_ids = Vec::new();
 
-    for (i, item) in items.iter().enumerate() {
-        let position = max_position + 1 + (i as i32);
+    // Voice captures are imported once their transcript is in
+    for item in items.iter().filter(|item| !item.transcribing) {
+        let position = position_between(last_position, None);
+        last_position = Some(position);
 
         // Create the node
         let op = create_op(Some(inbox_node_id), position, item.content.clone());
@@ -1188,6 +3036,127 @@ pub fn import_inbox_items(state: State<AppState>) -> Result<u32, String> {
     Ok(imported)
 }
 
+// ============================================================================
+// Voice Capture Commands
+// ============================================================================
+
+/// Payload of the `transcription-finished` event
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TranscriptionFinished {
+    pub audio: String,
+    /// Inbox item the transcript went to, if it was a voice capture
+    pub inbox_item_id: Option<String>,
+    pub document_id: Option<String>,
+    pub node_id: Option<String>,
+    pub transcript: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Put finished transcripts where they belong: a node's is appended to its
+/// note (an inbox item's note was already filled in), then
+/// `transcription-finished` is emitted
+pub fn transcription_handler(app: tauri::AppHandle) -> TranscriptHandler {
+    use tauri::Emitter;
+
+    Arc::new(move |job, result| {
+        let mut finished = TranscriptionFinished {
+            audio: job.audio.clone(),
+            inbox_item_id: None,
+            document_id: None,
+            node_id: None,
+            transcript: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().cloned(),
+        };
+        match job.target {
+            TranscriptTarget::Inbox(ref id) => finished.inbox_item_id = Some(id.clone()),
+            TranscriptTarget::Node { document_id, node_id } => {
+                finished.document_id = Some(document_id.to_string());
+                finished.node_id = Some(node_id.to_string());
+                if let Some(transcript) = result.as_ref().ok().filter(|t| !t.is_empty()) {
+                    let applied = apply_generated_ops(&app, document_id, |state| {
+                        let node = state.nodes.iter().find(|n| n.id == node_id).ok_or("Node was deleted")?;
+                        let note = match node.note.as_deref().filter(|n| !n.trim().is_empty()) {
+                            Some(note) => format!("{}\n\n{}", note, transcript),
+                            None => transcript.clone(),
+                        };
+                        Ok(vec![update_op(node_id, NodeChanges { note: Some(note), ..Default::default() })])
+                    });
+                    if let Err(e) = applied {
+                        log::warn!("Failed to add transcript to node {}: {}", node_id, e);
+                        finished.error = Some(e);
+                    }
+                }
+            }
+        }
+        if let Err(e) = app.emit("transcription-finished", &finished) {
+            log::error!("Failed to emit transcription-finished event: {}", e);
+        }
+    })
+}
+
+/// Capture a voice recording to the inbox. With a transcription engine
+/// configured, the item waits in the inbox until its transcript (which
+/// becomes its note) is in.
+#[tauri::command]
+pub fn capture_audio(
+    queue: State<TranscriptionQueue>,
+    data: Vec<u8>,
+    extension: String,
+) -> Result<InboxItem, String> {
+    ensure_dirs()?;
+    let audio = store_audio(&data, &extension)?;
+    let mut item = InboxItem::new(
+        format!("Voice note {}", chrono::Local::now().format("%H:%M")),
+        None,
+        Some("voice".to_string()),
+    );
+    item.audio = Some(audio.clone());
+    item.transcribing = crate::data::load_config().transcription.is_some();
+    append_inbox_item(&item)?;
+
+    if item.transcribing {
+        queue.push(TranscriptionJob { audio, target: TranscriptTarget::Inbox(item.id.clone()) })?;
+    }
+    Ok(item)
+}
+
+/// Attach a recording (an audio file on disk) to a node and transcribe it
+/// into the node's note. Returns the recording's name in the audio
+/// directory.
+#[tauri::command]
+pub fn transcribe_node_audio(
+    queue: State<TranscriptionQueue>,
+    document_id: String,
+    node_id: String,
+    path: String,
+) -> Result<String, String> {
+    let target = TranscriptTarget::Node {
+        document_id: parse_uuid(&document_id)?,
+        node_id: parse_uuid(&node_id)?,
+    };
+    if crate::data::load_config().transcription.is_none() {
+        return Err("No transcription engine is configured".to_string());
+    }
+    let path = std::path::Path::new(&path);
+    let data = std::fs::read(path).map_err(|e| format!("Read {}: {}", path.display(), e))?;
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let audio = store_audio(&data, extension)?;
+    queue.push(TranscriptionJob { audio: audio.clone(), target })?;
+    Ok(audio)
+}
+
+/// Get the transcription engine (None if voice captures aren't transcribed)
+#[tauri::command]
+pub fn get_transcription_config() -> Option<TranscriptionConfig> {
+    crate::data::load_config().transcription
+}
+
+/// Change (or, with None, turn off) the transcription engine
+#[tauri::command]
+pub fn set_transcription_config(config: Option<TranscriptionConfig>) -> Result<(), String> {
+    update_config(|app_config| app_config.transcription = config)
+}
+
 // ============================================================================
 // Documents Watcher Commands
 // ============================================================================
@@ -1219,3 +3188,1292 @@ pub fn stop_documents_watcher(watcher_state: State<WatcherState>) -> Result<(),
 pub fn is_documents_watcher_running(watcher_state: State<WatcherState>) -> bool {
     watcher_state.is_running()
 }
+
+/// Get whether the documents watcher is running and what it watches
+#[tauri::command]
+pub fn watcher_status(watcher_state: State<WatcherState>) -> WatcherStatus {
+    watcher_state.status()
+}
+
+// ============================================================================
+// Collaboration Commands
+// ============================================================================
+
+/// Route collaboration events into the app: remote ops are applied, peer
+/// and presence changes are emitted as `collab-peers` / `presence-changed`
+/// events
+fn collab_handler(app: tauri::AppHandle) -> CollabHandler {
+    use tauri::Emitter;
+
+    Arc::new(move |event| match event {
+        CollabEvent::RemoteOp { document_id, record } => {
+            if let Err(e) = apply_remote_op(&app, document_id, &record) {
+                log::warn!("Failed to apply op from {:?}: {}", record.device, e);
+            }
+        }
+        CollabEvent::PeersChanged(peers) => {
+            if let Err(e) = app.emit("collab-peers", &peers) {
+                log::error!("Failed to emit collab-peers event: {}", e);
+            }
+        }
+        CollabEvent::PresenceChanged(presence) => {
+            if let Err(e) = app.emit("presence-changed", &presence) {
+                log::error!("Failed to emit presence-changed event: {}", e);
+            }
+        }
+    })
+}
+
+/// Show an op made on another instance. It is applied to the current
+/// document in memory only and emitted as a `document-delta` event; the
+/// device that made it saves it to its own pending file, which arrives here
+/// through file sync (so a document that isn't open picks it up then).
+fn apply_remote_op(app: &tauri::AppHandle, document_id: Uuid, record: &OpRecord) -> Result<(), String> {
+    use tauri::{Emitter, Manager};
+
+    let state = app.state::<AppState>();
+    let mut current = state.current_document.lock().unwrap();
+    let Some(doc) = current.as_mut().filter(|doc| doc.id == document_id) else {
+        return Ok(());
+    };
+
+    let before = doc.state.clone();
+    record.op.apply(&mut doc.state);
+    sync_document_title(doc);
+    let delta = ReloadDelta::between(document_id, false, &before, &doc.state);
+    if delta.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(ref index) = *state.search_index.lock().unwrap() {
+        if let Err(e) = reindex_delta(index, doc, &delta) {
+            log::warn!("Failed to re-index remote op: {}", e);
+        }
+    }
+    if let Err(e) = app.emit("document-delta", &delta) {
+        log::error!("Failed to emit document-delta event: {}", e);
+    }
+    Ok(())
+}
+
+/// Make `session` the running collaboration session and start forwarding
+/// local ops to it
+fn start_collab_session(app: tauri::AppHandle, collab_state: &CollabState, session: CollabSession) -> CollabStatus {
+    use tauri::Manager;
+
+    let status = session.status();
+    collab_state.set_session(session);
+    set_op_listener("collab", Some(Box::new(move |document_id, record| {
+        app.state::<CollabState>().broadcast_op(document_id, record);
+    })));
+    status
+}
+
+/// Host a collaboration session on `port` sharing `doc_id` with other
+/// instances. Peers must present `token` to join; one is generated (and
+/// returned in the status) if none is given.
+#[tauri::command]
+pub fn start_collab_server(
+    app: tauri::AppHandle,
+    collab_state: State<CollabState>,
+    doc_id: String,
+    port: u16,
+    token: Option<String>,
+) -> Result<CollabStatus, String> {
+    let document_id = parse_uuid(&doc_id)?;
+    let session = CollabSession::host(port, token, document_id, device_name(), collab_handler(app.clone()))?;
+    Ok(start_collab_session(app, &collab_state, session))
+}
+
+/// Join a collaboration session hosted by another instance
+/// (`ws://host:port`)
+#[tauri::command]
+pub fn connect_collab(
+    app: tauri::AppHandle,
+    collab_state: State<CollabState>,
+    url: String,
+    token: Option<String>,
+) -> Result<CollabStatus, String> {
+    let session = CollabSession::connect(&url, token, device_name(), collab_handler(app.clone()))?;
+    Ok(start_collab_session(app, &collab_state, session))
+}
+
+/// Leave (or stop hosting) the collaboration session
+#[tauri::command]
+pub fn stop_collab(collab_state: State<CollabState>) {
+    set_op_listener("collab", None);
+    collab_state.stop();
+}
+
+/// Get the collaboration role, address and connected peers
+#[tauri::command]
+pub fn get_collab_status(collab_state: State<CollabState>) -> CollabStatus {
+    collab_state.status()
+}
+
+/// Tell other instances which document this device has open and which node
+/// is focused (both None when no document is open)
+#[tauri::command]
+pub fn update_presence(
+    collab_state: State<CollabState>,
+    document_id: Option<String>,
+    node_id: Option<String>,
+) -> Result<(), String> {
+    let presence = Presence {
+        device: device_name(),
+        document_id: document_id.as_deref().map(parse_uuid).transpose()?,
+        node_id: node_id.as_deref().map(parse_uuid).transpose()?,
+        updated_at: chrono::Utc::now(),
+    };
+    collab_state.update_presence(presence);
+    Ok(())
+}
+
+/// Where the other devices are, optionally only those in one document
+#[tauri::command]
+pub fn get_presence(collab_state: State<CollabState>, document_id: Option<String>) -> Result<Vec<Presence>, String> {
+    let document_id = document_id.as_deref().map(parse_uuid).transpose()?;
+    Ok(collab_state.presence(document_id))
+}
+
+// ============================================================================
+// Mount Commands
+// ============================================================================
+
+/// The app's documents as seen through the FUSE mount
+struct MountedDocuments(tauri::AppHandle);
+
+impl DocumentSource for MountedDocuments {
+    fn documents(&self) -> Result<Vec<(Uuid, String)>, String> {
+        list_documents()?
+            .into_iter()
+            .map(|info| Ok((parse_uuid(&info.id)?, info.title)))
+            .collect()
+    }
+
+    fn folders(&self) -> Result<FolderState, String> {
+        load_folders()
+    }
+
+    fn read(&self, id: Uuid) -> Result<String, String> {
+        use tauri::Manager;
+
+        let doc_state = document_state_for(&self.0.state::<AppState>(), &id)?;
+        Ok(generate_markdown(&doc_state.nodes, &MarkdownOptions::default()))
+    }
+
+    fn write(&self, id: Uuid, markdown: &str) -> Result<(), String> {
+        apply_generated_ops(&self.0, id, |state| markdown_edit_ops(state, markdown))
+    }
+}
+
+/// Apply ops made off the editor (a Markdown edit, a transcript) to any
+/// document. `ops_for` is given the document's state. Changes to the current
+/// document are applied in memory and emitted as a `document-delta` event.
+fn apply_generated_ops(
+    app: &tauri::AppHandle,
+    document_id: Uuid,
+    ops_for: impl FnOnce(&DocumentState) -> Result<Vec<Operation>, String>,
+) -> Result<(), String> {
+    edit_document(app, document_id, |doc| {
+        for op in ops_for(&doc.state)? {
+            doc.append_op(&op)?;
+            op.apply(&mut doc.state);
+        }
+        Ok(())
+    })
+}
+
+/// Run an edit that appends and applies ops to any document, then keep its
+/// title in sync and re-index it. Changes to the current document are
+/// emitted as a `document-delta` event.
+fn edit_document(
+    app: &tauri::AppHandle,
+    document_id: Uuid,
+    edit: impl FnOnce(&mut Document) -> Result<(), String>,
+) -> Result<(), String> {
+    use tauri::{Emitter, Manager};
+
+    let state = app.state::<AppState>();
+    let mut current = state.current_document.lock().unwrap();
+    let Some(doc) = current.as_mut().filter(|doc| doc.id == document_id) else {
+        drop(current);
+        let doc_dir = documents_dir().join(document_id.to_string());
+        if !doc_dir.exists() {
+            return Err(format!("Document not found: {}", document_id));
+        }
+        let mut doc = Document::load(doc_dir)?;
+        edit(&mut doc)?;
+        sync_document_title(&mut doc);
+        state.indexer.index_document(document_id, doc.state.nodes);
+        return Ok(());
+    };
+
+    let before = doc.state.clone();
+    edit(doc)?;
+    sync_document_title(doc);
+    let delta = ReloadDelta::between(document_id, false, &before, &doc.state);
+    if delta.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(ref index) = *state.search_index.lock().unwrap() {
+        if let Err(e) = reindex_delta(index, doc, &delta) {
+            log::warn!("Failed to re-index generated ops: {}", e);
+        }
+    }
+    if let Err(e) = app.emit("document-delta", &delta) {
+        log::error!("Failed to emit document-delta event: {}", e);
+    }
+    Ok(())
+}
+
+/// Mount the documents as Markdown files at `path` (an empty directory),
+/// folders as directories. Needs a build with the `fuse` feature.
+#[tauri::command]
+pub fn mount_documents(app: tauri::AppHandle, mount_state: State<MountState>, path: String) -> Result<(), String> {
+    ensure_dirs()?;
+    mount_state.mount(&path, MountedDocuments(app))
+}
+
+/// Unmount the documents
+#[tauri::command]
+pub fn unmount_documents(mount_state: State<MountState>) {
+    mount_state.unmount();
+}
+
+/// Where the documents are mounted, if they are
+#[tauri::command]
+pub fn get_mount_point(mount_state: State<MountState>) -> Option<String> {
+    mount_state.mount_point()
+}
+
+/// Search index part of a health check
+#[derive(Clone, serde::Serialize)]
+pub struct SearchIndexHealth {
+    pub available: bool,
+    /// Problems found by SQLite's quick check
+    pub errors: Vec<String>,
+}
+
+/// Result of health_check
+#[derive(Clone, serde::Serialize)]
+pub struct HealthReport {
+    pub data_dir: DataDirHealth,
+    pub search_index: SearchIndexHealth,
+    pub watcher: WatcherStatus,
+}
+
+/// Check the data directory (writable, free space, documents that fail to
+/// load), the search index and the watcher, so the UI can warn before
+/// changes are lost
+#[tauri::command]
+pub fn health_check(state: State<AppState>, watcher_state: State<WatcherState>) -> HealthReport {
+    let search_index = match *state.search_index.lock().unwrap() {
+        Some(ref index) => SearchIndexHealth {
+            available: true,
+            errors: index.quick_check().unwrap_or_else(|e| vec![e.to_string()]),
+        },
+        None => SearchIndexHealth {
+            available: false,
+            errors: Vec::new(),
+        },
+    };
+
+    HealthReport {
+        data_dir: check_data_dir(&data_dir()),
+        search_index,
+        watcher: watcher_state.status(),
+    }
+}
+
+/// Get p50/p95 timings of recent document loads, indexing, compactions and
+/// exports, by operation
+#[tauri::command]
+pub fn get_perf_stats() -> Vec<crate::perf::PerfStat> {
+    crate::perf::perf_stats()
+}
+
+/// Get the most recent log lines (default 500), oldest first, for the
+/// diagnostics panel
+#[tauri::command]
+pub fn get_recent_logs(lines: Option<usize>) -> Result<Vec<String>, String> {
+    crate::logging::read_recent_logs(&crate::logging::logs_dir(), lines.unwrap_or(500))
+}
+
+/// Load a document that fails to load because of malformed pending op lines,
+/// moving the bad lines into `.corrupt` files next to them. Returns what was
+/// skipped; the document becomes current if it already was.
+#[tauri::command]
+pub fn repair_document(state: State<AppState>, doc_id: String) -> Result<Vec<SkippedOp>, String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    let doc_dir = documents_dir().join(doc_uuid.to_string());
+    if !doc_dir.exists() {
+        return Err(format!("Document not found: {}", doc_id));
+    }
+
+    let (doc, skipped) = Document::load_lenient(doc_dir)?;
+    if !skipped.is_empty() {
+        log::warn!("Quarantined {} bad op lines in document {}", skipped.len(), doc_uuid);
+    }
+
+    // Queued, so a private document stays out of the index
+    state.indexer.index_document(doc_uuid, doc.state.nodes.clone());
+
+    let mut current = state.current_document.lock().unwrap();
+    if current.as_ref().is_some_and(|current| current.id == doc_uuid) {
+        *current = Some(doc);
+    }
+    Ok(skipped)
+}
+
+// ============================================================================
+// Snapshot Commands
+// ============================================================================
+
+/// Freeze the current state of a document under a name
+#[tauri::command]
+pub fn create_snapshot(
+    state: State<AppState>,
+    doc_id: String,
+    name: String,
+) -> Result<SnapshotInfo, String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    let doc_state = document_state_for(&state, &doc_uuid)?;
+    create_snapshot_impl(&doc_uuid, &name, &doc_state)
+}
+
+/// List a document's snapshots, newest first
+#[tauri::command]
+pub fn list_snapshots(doc_id: String) -> Result<Vec<SnapshotInfo>, String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    list_snapshots_impl(&doc_uuid)
+}
+
+/// Compare a snapshot against the document's current state
+#[tauri::command]
+pub fn diff_snapshot(
+    state: State<AppState>,
+    doc_id: String,
+    snapshot: String,
+) -> Result<SnapshotDiff, String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    let frozen = load_snapshot(&doc_uuid, &snapshot)?;
+    let doc_state = document_state_for(&state, &doc_uuid)?;
+    Ok(diff_states(&frozen.state, &doc_state))
+}
+
+// ============================================================================
+// Operation Log Commands
+// ============================================================================
+
+/// Time range for operation log queries (RFC 3339 timestamps or YYYY-MM-DD dates)
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct OpLogRange {
+    pub start: Option<String>,
+    pub end: Option<String>,
+}
+
+/// Parse a range bound; bare dates cover the whole day (start of day for the
+/// lower bound, end of day for the upper bound)
+fn parse_range_bound(
+    value: &str,
+    end_of_day: bool,
+) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid date format: {}", e))?;
+    let time = if end_of_day {
+        date.and_hms_opt(23, 59, 59).unwrap()
+    } else {
+        date.and_hms_opt(0, 0, 0).unwrap()
+    };
+    Ok(Utc.from_utc_datetime(&time))
+}
+
+/// Get applied operations for a document with timestamps and originating device
+#[tauri::command]
+pub fn get_op_log(doc_id: String, range: Option<OpLogRange>) -> Result<Vec<OpLogEntry>, String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    let range = range.unwrap_or_default();
+    let start = range.start.as_deref().map(|s| parse_range_bound(s, false)).transpose()?;
+    let end = range.end.as_deref().map(|s| parse_range_bound(s, true)).transpose()?;
+
+    let doc_dir = documents_dir().join(doc_uuid.to_string());
+    if !doc_dir.exists() {
+        return Err(format!("Document not found: {}", doc_uuid));
+    }
+    op_log(&doc_dir, start, end)
+}
+
+// ============================================================================
+// Template Commands
+// ============================================================================
+
+/// Save a node and its descendants from the current document as a named template
+#[tauri::command]
+pub fn save_template(
+    state: State<AppState>,
+    name: String,
+    node_id: String,
+) -> Result<TemplateInfo, String> {
+    let node_uuid = parse_uuid(&node_id)?;
+    let nodes = {
+        let current = state.current_document.lock().unwrap();
+        let doc = current.as_ref().ok_or("No document loaded")?;
+        extract_subtree(&doc.state, node_uuid)?
+    };
+    save_template_impl(&name, nodes, false)
+}
+
+/// Save a whole document as a named document template
+#[tauri::command]
+pub fn save_document_as_template(
+    state: State<AppState>,
+    doc_id: String,
+    name: String,
+) -> Result<TemplateInfo, String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    let doc_state = document_state_for(&state, &doc_uuid)?;
+    save_template_impl(&name, doc_state.nodes, true)
+}
+
+/// Create a new document from a template (looked up by name), with fresh IDs
+/// and date placeholders like {{date}} expanded, e.g. a new "Sprint {{week}}"
+#[tauri::command]
+pub fn create_document_from_template(
+    state: State<AppState>,
+    template_name: String,
+) -> Result<ImportResult, String> {
+    ensure_dirs()?;
+    let template = find_template_by_name(&template_name)?;
+    let nodes = instantiate_nodes(&template.nodes, None, 0.0, chrono::Local::now());
+
+    let title = nodes
+        .iter()
+        .filter(|n| n.parent_id.is_none())
+        .min_by(|a, b| a.position.total_cmp(&b.position))
+        .map(|n| strip_html_for_title(&n.content))
+        .unwrap_or_else(|| template.name.clone());
+
+    let search_index = state.search_index.lock().unwrap();
+    create_imported_document(search_index.as_ref(), title, nodes, None)
+}
+
+/// List saved templates
+#[tauri::command]
+pub fn list_templates() -> Result<Vec<TemplateInfo>, String> {
+    list_templates_impl()
+}
+
+/// Delete a saved template
+#[tauri::command]
+pub fn delete_template(template_id: String) -> Result<(), String> {
+    delete_template_impl(&template_id)
+}
+
+/// Clone a template under a parent in the current document (appended after the
+/// last child), with fresh IDs and date placeholders like {{date}} expanded.
+/// Returns the IDs of the new top-level nodes.
+#[tauri::command]
+pub fn instantiate_template(
+    state: State<AppState>,
+    template_id: String,
+    parent_id: Option<String>,
+) -> Result<(Vec<Uuid>, DocumentState), String> {
+    let parent_uuid = if let Some(id_str) = parent_id {
+        Some(parse_uuid(&id_str)?)
+    } else {
+        None
+    };
+    let template = load_template(&template_id)?;
+
+    let mut current = state.current_document.lock().unwrap();
+    let doc = current.as_mut().ok_or("No document loaded")?;
+    if let Some(pid) = parent_uuid {
+        if !doc.state.nodes.iter().any(|n| n.id == pid) {
+            return Err(format!("Node not found: {}", pid));
+        }
+    }
+
+    let last = doc.state.sorted_children(parent_uuid).last().map(|n| n.position);
+    let position = position_between(last, None);
+    let nodes = instantiate_nodes(&template.nodes, parent_uuid, position, chrono::Local::now());
+    let root_ids = nodes
+        .iter()
+        .filter(|n| n.parent_id == parent_uuid)
+        .map(|n| n.id)
+        .collect();
+
+    import_nodes_to_document(doc, nodes)?;
+    Ok((root_ids, doc.state.clone()))
+}
+
+// ============================================================================
+// Journal Commands
+// ============================================================================
+
+/// Get the journal configuration
+#[tauri::command]
+pub fn get_journal_setting() -> JournalConfig {
+    get_journal_config()
+}
+
+/// Configure daily notes: a journal document to hold dated nodes (None for one
+/// document per day), the title format and the folder each day's document
+/// goes in
+#[tauri::command]
+pub fn set_journal_setting(
+    document_id: Option<String>,
+    title_format: Option<String>,
+    folder_id: Option<String>,
+) -> Result<JournalConfig, String> {
+    if let Some(ref id) = document_id {
+        parse_uuid(id)?;
+    }
+    let mut journal = JournalConfig {
+        document_id,
+        folder_id,
+        ..Default::default()
+    };
+    if let Some(format) = title_format {
+        // Validate before saving
+        daily_title(chrono::Local::now().date_naive(), &format)?;
+        journal.title_format = format;
+    }
+    set_journal_config(journal.clone())?;
+    Ok(journal)
+}
+
+/// A daily note located or created by open_daily_note
+#[derive(Clone, serde::Serialize)]
+pub struct DailyNote {
+    pub doc_id: String,
+    /// The dated node (journal document mode) or the document's title node
+    pub node_id: String,
+    pub title: String,
+    pub created: bool,
+}
+
+/// Find or create the daily note for a date (YYYY-MM-DD, default today)
+#[tauri::command]
+pub fn open_daily_note(state: State<AppState>, date: Option<String>) -> Result<DailyNote, String> {
+    let config = get_journal_config();
+    let date = journal_date(date.as_deref(), chrono::Local::now().date_naive())?;
+    let title = daily_title(date, &config.title_format)?;
+
+    // Journal document mode: one dated root node per day
+    if let Some(ref journal_id) = config.document_id {
+        let doc_uuid = parse_uuid(journal_id)?;
+        return with_document(&state, &doc_uuid, |doc| {
+            let title = match doc.meta.settings.journal_date_format {
+                Some(ref format) => daily_title(date, format)?,
+                None => title,
+            };
+            if let Some(node_id) = find_titled_child(&doc.state, None, &title) {
+                return Ok((
+                    DailyNote {
+                        doc_id: doc_uuid.to_string(),
+                        node_id: node_id.to_string(),
+                        title: title.clone(),
+                        created: false,
+                    },
+                    None,
+                ));
+            }
+            let (node_id, ops) = daily_note_ops(&doc.state, None, &title, date);
+            for op in &ops {
+                doc.append_op(op)?;
+                op.apply(&mut doc.state);
+            }
+            sync_document_title(doc);
+            state.shares.refresh(doc.id, &doc.state, doc.meta.settings.markdown_notes);
+            Ok((
+                DailyNote {
+                    doc_id: doc_uuid.to_string(),
+                    node_id: node_id.to_string(),
+                    title: title.clone(),
+                    created: true,
+                },
+                Some(doc.state.nodes.clone()),
+            ))
+        })
+        .map(|(note, nodes)| {
+            // Queued, so a private journal stays out of the index
+            if let Some(nodes) = nodes {
+                state.indexer.index_document(doc_uuid, nodes);
+            }
+            note
+        });
+    }
+
+    // Document-per-day mode: find a document titled for the day by the
+    // titles in the search cache, then in meta.json (documents not indexed
+    // yet, or private), loading only the documents that match
+    ensure_dirs()?;
+    let indexed = state
+        .search_index
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|index| index.documents_titled(&title).ok())
+        .unwrap_or_default();
+    let titled_in_meta = || -> Result<Vec<Uuid>, String> {
+        Ok(crate::data::list_documents()?
+            .into_iter()
+            .filter(|id| {
+                crate::data::DocumentMeta::load(&documents_dir().join(id.to_string()))
+                    .is_ok_and(|meta| meta.title.as_deref() == Some(title.as_str()))
+            })
+            .collect())
+    };
+    let daily_note = |doc_id: &Uuid| {
+        let doc_state = document_state_for(&state, doc_id).ok()?;
+        has_title(&doc_state, &title).then(|| (*doc_id, doc_state.sorted_children(None)[0].id))
+    };
+    let mut found = indexed.iter().find_map(daily_note);
+    if found.is_none() {
+        found = titled_in_meta()?.iter().find_map(daily_note);
+    }
+    if let Some((doc_id, title_node)) = found {
+        return Ok(DailyNote {
+            doc_id: doc_id.to_string(),
+            node_id: title_node.to_string(),
+            title,
+            created: false,
+        });
+    }
+
+    let doc_uuid = Uuid::now_v7();
+    let mut doc = Document::create(documents_dir().join(doc_uuid.to_string()))?;
+    let (node_id, ops) = daily_note_ops(&doc.state, None, &title, date);
+    for op in &ops {
+        doc.append_op(op)?;
+        op.apply(&mut doc.state);
+    }
+    sync_document_title(&mut doc);
+    state.indexer.index_document(doc_uuid, doc.state.nodes.clone());
+    if let Err(e) = crate::data::move_document_to_folder(&doc_uuid.to_string(), config.folder_id.as_deref(), None) {
+        log::warn!("Failed to file daily note document: {}", e);
+    }
+    Ok(DailyNote {
+        doc_id: doc_uuid.to_string(),
+        node_id: node_id.to_string(),
+        title,
+        created: true,
+    })
+}
+
+// ============================================================================
+// Statistics Commands
+// ============================================================================
+
+/// Node, word and task statistics for one document, or the whole workspace
+/// when no document is given
+#[tauri::command]
+pub fn get_statistics(state: State<AppState>, doc_id: Option<String>) -> Result<Statistics, String> {
+    let mut stats = Statistics::default();
+
+    if let Some(id) = doc_id {
+        let doc_uuid = parse_uuid(&id)?;
+        stats.add_document(&document_state_for(&state, &doc_uuid)?);
+        return Ok(stats);
+    }
+
+    ensure_dirs()?;
+    for doc_uuid in crate::data::list_documents()? {
+        match document_state_for(&state, &doc_uuid) {
+            Ok(doc_state) => stats.add_document(&doc_state),
+            Err(e) => log::warn!("Skip document {} in statistics: {}", doc_uuid, e),
+        }
+    }
+    Ok(stats)
+}
+
+/// Tasks checked off in a document over a period, from its op log
+#[tauri::command]
+pub fn get_completion_history(doc_id: String, range: Option<OpLogRange>) -> Result<Vec<Completion>, String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    let range = range.unwrap_or_default();
+    let start = range.start.as_deref().map(|s| parse_range_bound(s, false)).transpose()?;
+    let end = range.end.as_deref().map(|s| parse_range_bound(s, true)).transpose()?;
+
+    let doc_dir = documents_dir().join(doc_uuid.to_string());
+    if !doc_dir.exists() {
+        return Err(format!("Document not found: {}", doc_uuid));
+    }
+    completion_history(&doc_dir, start, end)
+}
+
+/// Markdown report of tasks completed, tasks created, overdue tasks and the
+/// most-edited documents over a period (the last 7 days by default)
+#[tauri::command]
+pub fn generate_review(state: State<AppState>, range: Option<OpLogRange>) -> Result<String, String> {
+    let range = range.unwrap_or_default();
+    let end = match range.end.as_deref() {
+        Some(end) => parse_range_bound(end, true)?,
+        None => chrono::Utc::now(),
+    };
+    let start = match range.start.as_deref() {
+        Some(start) => parse_range_bound(start, false)?,
+        None => end - chrono::Duration::days(7),
+    };
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+
+    let mut review = Review::new(start, end, &today);
+    ensure_dirs()?;
+    for doc_uuid in crate::data::list_documents()? {
+        let doc_state = match document_state_for(&state, &doc_uuid) {
+            Ok(doc_state) => doc_state,
+            Err(e) => {
+                log::warn!("Skip document {} in review: {}", doc_uuid, e);
+                continue;
+            }
+        };
+        let ops = op_log(&documents_dir().join(doc_uuid.to_string()), Some(start), Some(end))?;
+        let title = doc_state
+            .sorted_children(None)
+            .first()
+            .map(|n| strip_html_for_title(&n.content))
+            .unwrap_or_else(|| "Untitled".to_string());
+        review.add_document(&title, &doc_state, &ops);
+    }
+    Ok(review.to_markdown())
+}
+
+/// Changelog of tasks completed in a period across all documents (the last
+/// 7 days by default), grouped by date
+#[tauri::command]
+pub fn export_completed(
+    state: State<AppState>,
+    range: Option<OpLogRange>,
+    format: Option<ChangelogFormat>,
+) -> Result<String, String> {
+    let range = range.unwrap_or_default();
+    let end = match range.end.as_deref() {
+        Some(end) => parse_range_bound(end, true)?,
+        None => chrono::Utc::now(),
+    };
+    let start = match range.start.as_deref() {
+        Some(start) => parse_range_bound(start, false)?,
+        None => end - chrono::Duration::days(7),
+    };
+
+    let mut tasks = Vec::new();
+    ensure_dirs()?;
+    for doc_uuid in crate::data::list_documents()? {
+        let doc_state = match document_state_for(&state, &doc_uuid) {
+            Ok(doc_state) => doc_state,
+            Err(e) => {
+                log::warn!("Skip document {} in completed export: {}", doc_uuid, e);
+                continue;
+            }
+        };
+        // The whole log up to the end, so earlier completions aren't
+        // mistaken for ones without a record
+        let ops = op_log(&documents_dir().join(doc_uuid.to_string()), None, Some(end))?;
+        let title = doc_state
+            .sorted_children(None)
+            .first()
+            .map(|n| strip_html_for_title(&n.content))
+            .unwrap_or_else(|| "Untitled".to_string());
+        tasks.extend(completed_tasks(&title, &doc_state, &ops, start, end));
+    }
+    Ok(completed_changelog(&tasks, start, end, format.unwrap_or_default()))
+}
+
+// ============================================================================
+// Time Tracking Commands
+// ============================================================================
+
+/// Start timing a node (in the current document unless `doc_id` is given).
+/// A timer already running is stopped and recorded first.
+#[tauri::command]
+pub fn start_timer(state: State<AppState>, node_id: String, doc_id: Option<String>) -> Result<RunningTimer, String> {
+    let node_uuid = parse_uuid(&node_id)?;
+    let doc_uuid = match doc_id {
+        Some(id) => parse_uuid(&id)?,
+        None => state
+            .current_document
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|doc| doc.id)
+            .ok_or("No document loaded")?,
+    };
+    if !document_state_for(&state, &doc_uuid)?.nodes.iter().any(|n| n.id == node_uuid) {
+        return Err(format!("Node not found: {}", node_id));
+    }
+    start_timer_impl(doc_uuid, node_uuid)
+}
+
+/// Stop the running timer and record the time; returns the new entry, or
+/// None if no timer was running
+#[tauri::command]
+pub fn stop_timer() -> Result<Option<TimeEntry>, String> {
+    stop_timer_impl()
+}
+
+/// Get the timer running on this machine, if any
+#[tauri::command]
+pub fn get_running_timer() -> Option<RunningTimer> {
+    running_timer()
+}
+
+/// Hours spent over a period, summed by node (default), tag or document.
+/// The running timer counts up to now.
+#[tauri::command]
+pub fn get_time_report(
+    state: State<AppState>,
+    range: Option<OpLogRange>,
+    group_by: Option<TimeGroupBy>,
+) -> Result<Vec<TimeReportRow>, String> {
+    let range = range.unwrap_or_default();
+    let start = range.start.as_deref().map(|s| parse_range_bound(s, false)).transpose()?;
+    let end = range.end.as_deref().map(|s| parse_range_bound(s, true)).transpose()?;
+    let running = running_timer();
+
+    let mut report = TimeReport::new(group_by.unwrap_or_default(), start, end);
+    ensure_dirs()?;
+    for doc_uuid in crate::data::list_documents()? {
+        let mut entries = read_time_entries(&documents_dir().join(doc_uuid.to_string()))?;
+        if let Some(timer) = running.as_ref().filter(|t| t.document_id == doc_uuid) {
+            entries.push(timer.entry(chrono::Utc::now()));
+        }
+        if entries.is_empty() {
+            continue;
+        }
+        let doc_state = match document_state_for(&state, &doc_uuid) {
+            Ok(doc_state) => doc_state,
+            Err(e) => {
+                log::warn!("Skip document {} in time report: {}", doc_uuid, e);
+                continue;
+            }
+        };
+        let title = doc_state
+            .sorted_children(None)
+            .first()
+            .map(|n| strip_html_for_title(&n.content))
+            .unwrap_or_else(|| "Untitled".to_string());
+        report.add_document(doc_uuid, &title, &doc_state, &entries);
+    }
+    Ok(report.rows())
+}
+
+// ============================================================================
+// Pomodoro Commands
+// ============================================================================
+
+/// Start a pomodoro on a node (in the current document unless `doc_id` is
+/// given), replacing any running one. Lasts `minutes` (default 25).
+#[tauri::command]
+pub fn start_pomodoro(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    node_id: String,
+    minutes: Option<u32>,
+    doc_id: Option<String>,
+) -> Result<PomodoroStatus, String> {
+    let node_uuid = parse_uuid(&node_id)?;
+    let minutes = minutes.unwrap_or(crate::pomodoro::DEFAULT_MINUTES);
+    if minutes == 0 {
+        return Err("A pomodoro must last at least a minute".to_string());
+    }
+    let doc_uuid = match doc_id {
+        Some(id) => parse_uuid(&id)?,
+        None => state
+            .current_document
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|doc| doc.id)
+            .ok_or("No document loaded")?,
+    };
+    if !document_state_for(&state, &doc_uuid)?.nodes.iter().any(|n| n.id == node_uuid) {
+        return Err(format!("Node not found: {}", node_id));
+    }
+    Ok(crate::pomodoro::start_pomodoro(app, doc_uuid, node_uuid, minutes))
+}
+
+/// Cancel the running pomodoro (it isn't logged); returns whether one was running
+#[tauri::command]
+pub fn stop_pomodoro(pomodoro_state: State<PomodoroState>) -> bool {
+    pomodoro_state.cancel()
+}
+
+/// Get the running pomodoro, if any
+#[tauri::command]
+pub fn get_pomodoro_status(pomodoro_state: State<PomodoroState>) -> Option<PomodoroStatus> {
+    pomodoro_state.status()
+}
+
+/// Daily counts of completed pomodoros over a period, across the workspace
+/// or for one document or node
+#[tauri::command]
+pub fn get_pomodoro_stats(
+    range: Option<OpLogRange>,
+    doc_id: Option<String>,
+    node_id: Option<String>,
+) -> Result<PomodoroStats, String> {
+    let range = range.unwrap_or_default();
+    let start = range.start.as_deref().map(|s| parse_range_bound(s, false)).transpose()?;
+    let end = range.end.as_deref().map(|s| parse_range_bound(s, true)).transpose()?;
+    let node_uuid = node_id.as_deref().map(parse_uuid).transpose()?;
+    let doc_uuids = match doc_id {
+        Some(id) => vec![parse_uuid(&id)?],
+        None => {
+            ensure_dirs()?;
+            crate::data::list_documents()?
+        }
+    };
+
+    let mut sessions = Vec::new();
+    for doc_uuid in doc_uuids {
+        sessions.extend(read_pomodoros(&documents_dir().join(doc_uuid.to_string()))?);
+    }
+    let sessions = sessions.iter().filter(|s| node_uuid.map_or(true, |id| s.node_id == id));
+    Ok(PomodoroStats::from_sessions(sessions, start, end))
+}
+
+// ============================================================================
+// Overdue Rules Commands
+// ============================================================================
+
+/// Get the overdue rules
+#[tauri::command]
+pub fn get_overdue_rules() -> Result<Vec<OverdueRule>, String> {
+    load_overdue_rules()
+}
+
+/// Replace the overdue rules (rules without an id get one)
+#[tauri::command]
+pub fn set_overdue_rules(rules: Vec<OverdueRule>) -> Result<Vec<OverdueRule>, String> {
+    ensure_dirs()?;
+    save_overdue_rules(rules)
+}
+
+/// Apply the overdue rules to every document now
+#[tauri::command]
+pub fn run_overdue_rules(state: State<AppState>) -> Result<OverdueReport, String> {
+    apply_overdue_rules(&state)
+}
+
+/// Apply the overdue rules across the workspace and record today as this
+/// machine's last run. Changed documents are re-indexed; the current one is
+/// changed in memory too, so the frontend should reload it.
+pub(crate) fn apply_overdue_rules(state: &AppState) -> Result<OverdueReport, String> {
+    ensure_dirs()?;
+    let rules = load_overdue_rules()?;
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let mut report = OverdueReport {
+        date: today.clone(),
+        changes: Vec::new(),
+    };
+
+    if rules.iter().any(|r| r.enabled) {
+        for doc_uuid in crate::data::list_documents()? {
+            let result = with_document(state, &doc_uuid, |doc| {
+                let (ops, changes) = overdue_rule_ops(&doc.state, doc_uuid, &rules, &today);
+                for op in &ops {
+                    doc.append_op(op)?;
+                    op.apply(&mut doc.state);
+                }
+                Ok((changes, doc.state.nodes.clone()))
+            });
+            match result {
+                Ok((changes, nodes)) if !changes.is_empty() => {
+                    state.indexer.index_document(doc_uuid, nodes);
+                    report.changes.extend(changes);
+                }
+                Ok(_) => {}
+                // Locked documents are skipped
+                Err(e) => log::warn!("Overdue rules skipped document {}: {}", doc_uuid, e),
+            }
+        }
+    }
+
+    update_config(|config| config.overdue_rules_run = Some(today))?;
+    Ok(report)
+}
+
+// ============================================================================
+// Share Commands
+// ============================================================================
+
+/// Publish a document, or one node's subtree, as a read-only page under a
+/// new secret token (served by the thin server at /share/<token>)
+#[tauri::command]
+pub fn create_share(state: State<AppState>, doc_id: String, node_id: Option<String>) -> Result<Share, String> {
+    ensure_dirs()?;
+    let doc_uuid = parse_uuid(&doc_id)?;
+    let node_uuid = node_id.as_deref().map(parse_uuid).transpose()?;
+    with_document(&state, &doc_uuid, |doc| {
+        create_share_impl(doc_uuid, node_uuid, &doc.state, doc.meta.settings.markdown_notes)
+    })
+}
+
+/// Revoke a share, deleting its published page
+#[tauri::command]
+pub fn revoke_share(token: String) -> Result<(), String> {
+    revoke_share_impl(&token)
+}
+
+/// List the shares, optionally only those of one document
+#[tauri::command]
+pub fn list_shares(doc_id: Option<String>) -> Result<Vec<Share>, String> {
+    let mut shares = load_shares()?;
+    if let Some(doc_id) = doc_id {
+        shares.retain(|s| s.document_id == doc_id);
+    }
+    Ok(shares)
+}
+
+// ============================================================================
+// Snippet Commands
+// ============================================================================
+
+/// List the text snippets
+#[tauri::command]
+pub fn list_snippets() -> Result<Vec<Snippet>, String> {
+    load_snippets()
+}
+
+/// Replace the text snippets
+#[tauri::command]
+pub fn set_snippets(snippets: Vec<Snippet>) -> Result<Vec<Snippet>, String> {
+    ensure_dirs()?;
+    save_snippets(snippets)
+}
+
+/// Expand a snippet trigger (e.g. ";today"), or None if no snippet has it
+#[tauri::command]
+pub fn expand_snippet(trigger: String) -> Result<Option<String>, String> {
+    Ok(expand_snippet_impl(&load_snippets()?, &trigger, chrono::Local::now()))
+}
+
+// ============================================================================
+// Spellcheck Commands
+// ============================================================================
+
+/// Find misspelled words in a text, with suggestions
+#[tauri::command]
+pub fn check_text(text: String) -> Result<Vec<Misspelling>, String> {
+    let dictionary = crate::spellcheck::dictionary()?;
+    Ok(dictionary.check(&text, &crate::spellcheck::personal_words()))
+}
+
+/// Add a word to the personal dictionary (kept in the data directory)
+#[tauri::command]
+pub fn add_to_dictionary(word: String) -> Result<(), String> {
+    ensure_dirs()?;
+    crate::spellcheck::add_to_dictionary(&word)
+}
+
+/// List the words in the personal dictionary
+#[tauri::command]
+pub fn get_personal_dictionary() -> Vec<String> {
+    let mut words: Vec<String> = crate::spellcheck::personal_words().into_iter().collect();
+    words.sort_by_key(|w| w.to_lowercase());
+    words
+}
+
+// ============================================================================
+// Link Commands
+// ============================================================================
+
+/// Title and favicon of the page at `url`, with the link as Markdown and
+/// HTML, for turning a pasted bare URL into a titled link
+#[tauri::command]
+pub async fn fetch_link_metadata(url: String) -> Result<LinkMetadata, String> {
+    crate::link_metadata::fetch_link_metadata(&url).await
+}
+
+// ============================================================================
+// Board Commands
+// ============================================================================
+
+/// Group a document's nodes into board columns by tag, color, or a property
+#[tauri::command]
+pub fn get_board(
+    state: State<AppState>,
+    doc_id: String,
+    group_by: BoardGroupBy,
+) -> Result<Board, String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    let doc_state = document_state_for(&state, &doc_uuid)?;
+    Ok(build_board(&doc_state, group_by))
+}
+
+/// Move a card between board columns by rewriting its grouping field in the
+/// current document (`to` of None takes it off the board)
+#[tauri::command]
+pub fn move_card(
+    state: State<AppState>,
+    node_id: String,
+    group_by: BoardGroupBy,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<DocumentState, String> {
+    let node_uuid = parse_uuid(&node_id)?;
+    let op = {
+        let current = state.current_document.lock().unwrap();
+        let doc = current.as_ref().ok_or("No document loaded")?;
+        move_card_op(&doc.state, node_uuid, &group_by, from.as_deref(), to.as_deref())?
+    };
+    save_ops(&state, &[op])
+}
+
+// ============================================================================
+// Search and Replace Commands
+// ============================================================================
+
+/// Replace text in node content and notes across a document or the whole
+/// workspace. Without `apply` this only previews the changes; with it, the
+/// changes are written as Update ops and the affected documents re-indexed.
+/// Locked documents are skipped rather than failing the replace partway
+/// through; their matches come back marked `locked`.
+#[tauri::command]
+pub fn replace_all(
+    state: State<AppState>,
+    query: String,
+    replacement: String,
+    scope: ReplaceScope,
+    mode: Option<ReplaceMode>,
+    case_sensitive: Option<bool>,
+    apply: Option<bool>,
+) -> Result<Vec<ReplaceMatch>, String> {
+    let replacer = Replacer::new(&query, &replacement, mode.unwrap_or_default(), case_sensitive.unwrap_or(true))?;
+    let apply = apply.unwrap_or(false);
+
+    let doc_ids = match scope {
+        ReplaceScope::Document(id) => vec![parse_uuid(&id)?],
+        ReplaceScope::Workspace => {
+            ensure_dirs()?;
+            crate::data::list_documents()?
+        }
+    };
+
+    let mut all_matches = Vec::new();
+    for doc_uuid in doc_ids {
+        let (matches, nodes) = with_document(&state, &doc_uuid, |doc| {
+            let (mut matches, ops) = replacer.plan(&doc_uuid, &doc.state);
+            if doc.meta.locked {
+                matches.iter_mut().for_each(|m| m.locked = true);
+                return Ok((matches, None));
+            }
+            if !apply || ops.is_empty() {
+                return Ok((matches, None));
+            }
+            for op in &ops {
+                doc.append_op(op)?;
+                op.apply(&mut doc.state);
+            }
+            Ok((matches, Some(doc.state.nodes.clone())))
+        })?;
+
+        // Queued, so private documents stay out of the index
+        if let Some(nodes) = nodes {
+            state.indexer.index_document(doc_uuid, nodes);
+        }
+        all_matches.extend(matches);
+    }
+
+    Ok(all_matches)
+}
+
+// ============================================================================
+// Locked documents
+// ============================================================================
+
+/// Make a document read-only: any operation saved against it returns an error
+#[tauri::command]
+pub fn lock_document(state: State<AppState>, doc_id: String) -> Result<(), String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    with_document(&state, &doc_uuid, |doc| doc.set_locked(true))
+}
+
+/// Allow edits to a locked document again
+#[tauri::command]
+pub fn unlock_document(state: State<AppState>, doc_id: String) -> Result<(), String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    with_document(&state, &doc_uuid, |doc| doc.set_locked(false))
+}
+
+/// Get a document's settings
+#[tauri::command]
+pub fn get_document_settings(state: State<AppState>, doc_id: String) -> Result<DocumentSettings, String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    with_document(&state, &doc_uuid, |doc| Ok(doc.meta.settings.clone()))
+}
+
+/// Replace a document's settings
+#[tauri::command]
+pub fn set_document_settings(
+    state: State<AppState>,
+    doc_id: String,
+    settings: DocumentSettings,
+) -> Result<(), String> {
+    let doc_uuid = parse_uuid(&doc_id)?;
+    let reindex = with_document(&state, &doc_uuid, |doc| {
+        let changed = doc.meta.settings.excluded != settings.excluded;
+        doc.set_settings(settings)?;
+        // A new sync direction can change the title
+        let renamed = doc.sync_title()?.is_some();
+        Ok((changed || renamed).then(|| doc.state.nodes.clone()))
+    })?;
+    // Drop a document that became private from the index, or add it back
+    if let Some(nodes) = reindex {
+        state.indexer.index_document(doc_uuid, nodes);
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Recent documents
+// ============================================================================
+
+/// A recently opened document
+#[derive(Clone, serde::Serialize)]
+pub struct RecentDocument {
+    pub id: String,
+    pub title: String,
+    pub opened_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Documents ordered by when they were last opened on this machine, most
+/// recent first (for a "Recent" list or quick switcher)
+#[tauri::command]
+pub fn get_recent_documents(
+    state: State<AppState>,
+    limit: Option<usize>,
+) -> Result<Vec<RecentDocument>, String> {
+    let limit = limit.unwrap_or(10);
+    let mut result = Vec::new();
+
+    for (doc_id, opened_at) in crate::data::recent_documents() {
+        if result.len() >= limit {
+            break;
+        }
+        // Skip documents that were deleted or live in another data directory
+        let Ok(doc_state) = parse_uuid(&doc_id).and_then(|uuid| document_state_for(&state, &uuid)) else {
+            continue;
+        };
+        let title = doc_state
+            .sorted_children(None)
+            .first()
+            .map(|n| strip_html_for_title(&n.content))
+            .unwrap_or_else(|| "Untitled".to_string());
+
+        result.push(RecentDocument {
+            id: doc_id,
+            title,
+            opened_at,
+        });
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+use super::document::DocumentState;
+use super::node::Node;
+use super::operations::{update_op, NodeChanges, Operation};
+
+/// Which node field a board groups cards by
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BoardGroupBy {
+    /// One column per tag (a card with several tags appears in each)
+    Tag,
+    /// One column per color label
+    Color,
+    /// One column per value of a property, e.g. {"property": "status"}
+    Property(String),
+}
+
+/// A board column: every card carrying one grouping value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardColumn {
+    pub key: String,
+    pub cards: Vec<Node>,
+}
+
+/// Nodes grouped into columns, ordered by column key. Cards keep document order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Board {
+    pub group_by: BoardGroupBy,
+    pub columns: Vec<BoardColumn>,
+}
+
+/// The grouping values a node carries
+fn group_keys(node: &Node, group_by: &BoardGroupBy) -> Vec<String> {
+    match group_by {
+        BoardGroupBy::Tag => node.tags.clone(),
+        BoardGroupBy::Color => node.color.iter().filter(|c| !c.is_empty()).cloned().collect(),
+        BoardGroupBy::Property(key) => node.properties.get(key).cloned().into_iter().collect(),
+    }
+}
+
+/// Depth-first document order, so cards read top to bottom as in the outline
+fn document_order(state: &DocumentState) -> Vec<&Node> {
+    let mut order = Vec::with_capacity(state.nodes.len());
+    let mut stack: Vec<&Node> = state.sorted_children(None).into_iter().rev().collect();
+    let mut children_of: HashMap<Uuid, Vec<&Node>> = HashMap::new();
+    for node in &state.nodes {
+        if let Some(parent_id) = node.parent_id {
+            children_of.entry(parent_id).or_default().push(node);
+        }
+    }
+    for children in children_of.values_mut() {
+        children.sort_by(|a, b| a.position.total_cmp(&b.position));
+    }
+
+    while let Some(node) = stack.pop() {
+        order.push(node);
+        if let Some(children) = children_of.get(&node.id) {
+            stack.extend(children.iter().rev().copied());
+        }
+    }
+    order
+}
+
+/// Group a document's nodes into board columns. Nodes without a grouping
+/// value are left off the board.
+pub fn build_board(state: &DocumentState, group_by: BoardGroupBy) -> Board {
+    let mut columns: BTreeMap<String, Vec<Node>> = BTreeMap::new();
+    for node in document_order(state) {
+        for key in group_keys(node, &group_by) {
+            columns.entry(key).or_default().push(node.clone());
+        }
+    }
+
+    Board {
+        group_by,
+        columns: columns
+            .into_iter()
+            .map(|(key, cards)| BoardColumn { key, cards })
+            .collect(),
+    }
+}
+
+/// Build the Update op that moves a card from one column to another by
+/// rewriting its grouping field. `from` is only needed for tags (a card can
+/// sit in several tag columns); `to` of None takes the card off the board.
+pub fn move_card_op(
+    state: &DocumentState,
+    node_id: Uuid,
+    group_by: &BoardGroupBy,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<Operation, String> {
+    let node = state
+        .nodes
+        .iter()
+        .find(|n| n.id == node_id)
+        .ok_or_else(|| format!("Node not found: {}", node_id))?;
+
+    let changes = match group_by {
+        BoardGroupBy::Tag => {
+            let mut tags: Vec<String> = node
+                .tags
+                .iter()
+                .filter(|t| Some(t.as_str()) != from)
+                .cloned()
+                .collect();
+            if let Some(to) = to {
+                if !tags.iter().any(|t| t == to) {
+                    tags.push(to.to_string());
+                }
+            }
+            NodeChanges {
+                tags: Some(tags),
+                ..Default::default()
+            }
+        }
+        BoardGroupBy::Color => NodeChanges {
+            // Empty string clears the color
+            color: Some(to.unwrap_or_default().to_string()),
+            ..Default::default()
+        },
+        BoardGroupBy::Property(key) => NodeChanges {
+            // Empty value removes the property
+            properties: Some(HashMap::from([(key.clone(), to.unwrap_or_default().to_string())])),
+            ..Default::default()
+        },
+    };
+
+    Ok(update_op(node_id, changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(parent: Option<&Node>, content: &str, position: f64, value: &str) -> Node {
+        let mut node = match parent {
+            Some(p) => Node::new_child(p.id, position, content.to_string()),
+            None => Node::new(content.to_string()),
+        };
+        node.position = position;
+        node.properties.insert("status".to_string(), value.to_string());
+        node
+    }
+
+    #[test]
+    fn test_build_board_by_property() {
+        let first = status(None, "First", 0.0, "todo");
+        let nested = status(Some(&first), "Nested", 0.0, "doing");
+        let second = status(None, "Second", 1.0, "todo");
+        let plain = Node::new("Plain".to_string());
+        let state = DocumentState {
+            nodes: vec![second, plain, nested, first],
+        };
+
+        let board = build_board(&state, BoardGroupBy::Property("status".to_string()));
+        let keys: Vec<&str> = board.columns.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(keys, vec!["doing", "todo"]);
+        let todo: Vec<&str> = board.columns[1].cards.iter().map(|n| n.content.as_str()).collect();
+        assert_eq!(todo, vec!["First", "Second"]);
+    }
+
+    #[test]
+    fn test_move_card() {
+        let mut card = Node::new("Card".to_string());
+        card.tags = vec!["backlog".to_string(), "ui".to_string()];
+        card.updated_at = chrono::Utc::now() - chrono::Duration::seconds(1);
+        let card_id = card.id;
+        let mut state = DocumentState { nodes: vec![card] };
+
+        move_card_op(&state, card_id, &BoardGroupBy::Tag, Some("backlog"), Some("done"))
+            .unwrap()
+            .apply(&mut state);
+        assert_eq!(state.nodes[0].tags, vec!["ui", "done"]);
+
+        let board = build_board(&state, BoardGroupBy::Tag);
+        assert_eq!(board.columns.len(), 2);
+
+        let op = move_card_op(&state, card_id, &BoardGroupBy::Property("status".to_string()), None, Some("waiting"))
+            .unwrap();
+        op.apply(&mut state);
+        assert_eq!(state.nodes[0].properties["status"], "waiting");
+
+        assert!(move_card_op(&state, Uuid::now_v7(), &BoardGroupBy::Color, None, None).is_err());
+    }
+}
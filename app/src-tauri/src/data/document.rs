@@ -1,17 +1,41 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
-use std::path::PathBuf;
-use std::sync::RwLock;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
 use uuid::Uuid;
 
-use super::node::Node;
-use super::operations::Operation;
+use super::node::{position_between, Node, NodeType, MIN_POSITION_GAP};
+use super::operations::{move_op, OpRecord, Operation};
+use super::timelog::RunningTimer;
+use super::title::TitleSync;
+use super::encryption::redact_plaintext;
+use super::wal::{
+    recover_compaction_wal, remove_compaction_wal, sync_op_file, write_atomic, write_compaction_wal,
+    FsyncPolicy,
+};
 
 /// Global config for data directory (can be changed at runtime)
 static DATA_DIR_OVERRIDE: RwLock<Option<PathBuf>> = RwLock::new(None);
 
+/// Called with the document id and record of every op appended locally
+pub type OpListener = Box<dyn Fn(Uuid, &OpRecord) + Send + Sync>;
+
+static OP_LISTENERS: RwLock<Vec<(&'static str, OpListener)>> = RwLock::new(Vec::new());
+
+/// Set (or clear) the listener registered under `name` that is told about
+/// each locally appended op, e.g. to forward it to collaborating instances
+pub fn set_op_listener(name: &'static str, listener: Option<OpListener>) {
+    let mut listeners = OP_LISTENERS.write().unwrap();
+    listeners.retain(|(n, _)| *n != name);
+    if let Some(listener) = listener {
+        listeners.push((name, listener));
+    }
+}
+
 /// Document state stored in state.json
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentState {
@@ -34,6 +58,132 @@ impl DocumentState {
     pub fn nodes_by_id_mut(&mut self) -> HashMap<Uuid, &mut Node> {
         self.nodes.iter_mut().map(|n| (n.id, n)).collect()
     }
+
+    /// Get the children of a parent (None for roots), sorted by position
+    pub fn sorted_children(&self, parent_id: Option<Uuid>) -> Vec<&Node> {
+        let mut children: Vec<_> = self.nodes.iter().filter(|n| n.parent_id == parent_id).collect();
+        children.sort_by(|a, b| sibling_order(a, b));
+        children
+    }
+
+    /// Get a node and all its descendants (breadth-first, starting with the node)
+    pub fn subtree_ids(&self, root_id: Uuid) -> Vec<Uuid> {
+        let mut children_of: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+        for node in &self.nodes {
+            if let Some(parent_id) = node.parent_id {
+                children_of.entry(parent_id).or_default().push(node.id);
+            }
+        }
+
+        let mut ids = vec![root_id];
+        let mut i = 0;
+        while i < ids.len() {
+            if let Some(children) = children_of.get(&ids[i]) {
+                ids.extend(children.iter().copied());
+            }
+            i += 1;
+        }
+        ids
+    }
+
+    /// Get a node and its descendants down to `max_depth` levels below it
+    /// (all of them when None), parents before children in sibling order
+    pub fn subtree(&self, root_id: Uuid, max_depth: Option<usize>) -> Vec<&Node> {
+        let Some(root) = self.nodes.iter().find(|n| n.id == root_id) else {
+            return Vec::new();
+        };
+        let mut children_of: HashMap<Uuid, Vec<&Node>> = HashMap::new();
+        for node in &self.nodes {
+            if let Some(parent_id) = node.parent_id {
+                children_of.entry(parent_id).or_default().push(node);
+            }
+        }
+
+        let mut result = Vec::new();
+        let mut stack = vec![(root, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            result.push(node);
+            if max_depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+            if let Some(children) = children_of.get_mut(&node.id) {
+                children.sort_by(|a, b| sibling_order(a, b));
+                stack.extend(children.iter().rev().map(|c| (*c, depth + 1)));
+            }
+        }
+        result
+    }
+
+    /// Get a node's ancestors, root first (empty for a top-level or unknown node).
+    /// Stops at a missing parent or a cycle.
+    pub fn ancestors(&self, node_id: Uuid) -> Vec<&Node> {
+        let by_id = self.nodes_by_id();
+        let mut ancestors = Vec::new();
+        let mut seen = HashSet::from([node_id]);
+        let mut parent_id = by_id.get(&node_id).and_then(|n| n.parent_id);
+        while let Some(node) = parent_id.and_then(|id| by_id.get(&id)) {
+            if !seen.insert(node.id) {
+                break;
+            }
+            ancestors.push(*node);
+            parent_id = node.parent_id;
+        }
+        ancestors.reverse();
+        ancestors
+    }
+
+    /// Get a position that places a node at `index` among a parent's children,
+    /// without renumbering them. `moving` is excluded from the siblings so a node
+    /// can be repositioned within its own parent.
+    #[allow(dead_code)]
+    pub fn position_for_index(&self, parent_id: Option<Uuid>, index: usize, moving: Option<Uuid>) -> f64 {
+        let siblings: Vec<f64> = self
+            .sorted_children(parent_id)
+            .into_iter()
+            .filter(|n| Some(n.id) != moving)
+            .map(|n| n.position)
+            .collect();
+        let index = index.min(siblings.len());
+        let before = index.checked_sub(1).map(|i| siblings[i]);
+        let after = siblings.get(index).copied();
+        position_between(before, after)
+    }
+
+    /// Build Move ops that renumber sibling groups into a clean 0..n sequence.
+    /// Groups with non-finite, duplicate or cramped positions (gaps below
+    /// MIN_POSITION_GAP) are always renumbered; with `force`, any group that is
+    /// not already 0..n is too. Healthy groups, including negative positions
+    /// from inserts at the top, are otherwise left alone: a Move bumps the
+    /// node's `updated_at`, so it would win over an edit from a machine that
+    /// has not synced yet.
+    pub fn position_normalization_ops(&self, force: bool) -> Vec<Operation> {
+        let mut groups: HashMap<Option<Uuid>, Vec<&Node>> = HashMap::new();
+        for node in &self.nodes {
+            groups.entry(node.parent_id).or_default().push(node);
+        }
+
+        let mut ops = Vec::new();
+        for (parent_id, mut siblings) in groups {
+            siblings.sort_by(|a, b| sibling_order(a, b));
+            let unhealthy = siblings.iter().any(|n| !n.position.is_finite())
+                || siblings
+                    .windows(2)
+                    .any(|w| w[1].position - w[0].position < MIN_POSITION_GAP);
+            let clean = siblings
+                .iter()
+                .enumerate()
+                .all(|(i, n)| n.position == i as f64);
+            if !unhealthy && (clean || !force) {
+                continue;
+            }
+            for (i, node) in siblings.iter().enumerate() {
+                if node.position != i as f64 {
+                    ops.push(move_op(node.id, parent_id, i as f64));
+                }
+            }
+        }
+        ops
+    }
 }
 
 impl Default for DocumentState {
@@ -42,7 +192,68 @@ impl Default for DocumentState {
     }
 }
 
-/// Manages a single document's files (state.json + pending.*.jsonl)
+/// Per-document settings stored in meta.json (synced with the document)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocumentMeta {
+    /// Read-only: edits are rejected until the document is unlocked
+    pub locked: bool,
+    /// Plain-text title, kept in line with the first root node (see
+    /// `TitleSync`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub settings: DocumentSettings,
+}
+
+/// User-editable behavior settings for one document
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocumentSettings {
+    /// Type for new nodes created in this document (bullet if unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_node_type: Option<NodeType>,
+    /// Move a node below its siblings when it is checked
+    pub completed_to_bottom: bool,
+    /// Daily-note title format when this is the journal document (overrides
+    /// the journal config)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub journal_date_format: Option<String>,
+    /// Completing a recurring task checks it off and adds a new task for the
+    /// next occurrence, instead of moving the task's date forward
+    pub recurring_instances: bool,
+    /// Private: kept out of the search index (global search, backlinks,
+    /// agenda queries) and the iCal feed
+    pub excluded: bool,
+    /// Notes are Markdown: shown rendered (see `render_note`) and exported
+    /// as HTML in HTML exports
+    pub markdown_notes: bool,
+    /// Which of meta.json's title and the first root node follows the other
+    pub title_sync: TitleSync,
+}
+
+impl DocumentMeta {
+    fn path(dir: &Path) -> PathBuf {
+        dir.join("meta.json")
+    }
+
+    /// Load a document's meta.json (defaults if missing)
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let path = Self::path(dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&path).map_err(|e| format!("Read meta.json: {}", e))?;
+        serde_json::from_str(&contents).map_err(|e| format!("Parse meta.json: {}", e))
+    }
+
+    /// Write a document's meta.json
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("Serialize: {}", e))?;
+        fs::write(Self::path(dir), json).map_err(|e| format!("Write meta.json: {}", e))
+    }
+}
+
+/// Manages a single document's files (state.json + pending.*.jsonl + meta.json)
 pub struct Document {
     /// Document UUID
     #[allow(dead_code)]
@@ -55,6 +266,14 @@ pub struct Document {
     pub last_load_time: std::time::SystemTime,
     /// Count of pending operations since last compact (for auto-compact threshold)
     pub pending_op_count: usize,
+    /// Settings from meta.json
+    pub meta: DocumentMeta,
+    /// Ops already applied from each pending file (by file name), so a
+    /// refresh only applies what was appended since
+    pub(super) applied_ops: HashMap<String, usize>,
+    /// Ops written by this instance since the files were last read in full,
+    /// re-applied by `reload` if they have gone missing on disk
+    local_ops: Vec<OpRecord>,
 }
 
 impl Document {
@@ -63,51 +282,79 @@ impl Document {
         self.dir.join("state.json")
     }
 
+    /// Get the pending.{hostname}.jsonl file name
+    fn pending_file_name() -> String {
+        format!("pending.{}.jsonl", device_name())
+    }
+
     /// Get the pending.{hostname}.jsonl path
     fn pending_path(&self) -> PathBuf {
-        let hostname = hostname::get()
-            .map(|h| h.to_string_lossy().into_owned())
-            .unwrap_or_else(|_| "unknown".to_string());
-        self.dir.join(format!("pending.{}.jsonl", hostname))
+        self.dir.join(Self::pending_file_name())
+    }
+
+    /// Get the history.{hostname}.jsonl path (ops archived by compaction on this machine)
+    fn history_path(&self) -> PathBuf {
+        self.dir.join(format!("history.{}.jsonl", device_name()))
     }
 
     /// Load document from directory, applying any pending operations
     pub fn load(dir: PathBuf) -> Result<Self, String> {
+        Self::load_with(dir, false).map(|(doc, _)| doc)
+    }
+
+    /// Load a document even if some pending op lines can't be parsed. Bad
+    /// lines are moved out of their pending file into `<file>.corrupt` and
+    /// reported; the rest of the document loads as usual.
+    pub fn load_lenient(dir: PathBuf) -> Result<(Self, Vec<SkippedOp>), String> {
+        Self::load_with(dir, true)
+    }
+
+    fn load_with(dir: PathBuf, lenient: bool) -> Result<(Self, Vec<SkippedOp>), String> {
+        let _timer = crate::perf::timer("document.load");
         let id = dir
             .file_name()
             .and_then(|n| n.to_str())
             .and_then(|s| Uuid::parse_str(s).ok())
             .ok_or_else(|| format!("Invalid document directory name: {:?}", dir))?;
 
+        recover_compaction_wal(&dir)?;
+
         // Load base state
         let state_path = dir.join("state.json");
         let mut state = if state_path.exists() {
-            let contents =
-                fs::read_to_string(&state_path).map_err(|e| format!("Read state.json: {}", e))?;
-            serde_json::from_str(&contents).map_err(|e| format!("Parse state.json: {}", e))?
+            let file = File::open(&state_path).map_err(|e| format!("Read state.json: {}", e))?;
+            serde_json::from_reader(BufReader::new(file))
+                .map_err(|e| format!("Parse state.json: {}", e))?
         } else {
             DocumentState::new()
         };
 
         // Collect all pending.*.jsonl files
         let mut ops: Vec<Operation> = Vec::new();
+        let mut applied_ops = HashMap::new();
+        let mut skipped = Vec::new();
         if dir.exists() {
             for entry in fs::read_dir(&dir).map_err(|e| format!("Read dir: {}", e))? {
                 let entry = entry.map_err(|e| format!("Read dir entry: {}", e))?;
                 let path = entry.path();
                 if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
                     if name.starts_with("pending.") && name.ends_with(".jsonl") {
-                        let file =
-                            File::open(&path).map_err(|e| format!("Open {}: {}", name, e))?;
-                        let reader = BufReader::new(file);
-                        for line in reader.lines() {
-                            let line = line.map_err(|e| format!("Read line: {}", e))?;
-                            if !line.trim().is_empty() {
-                                let op: Operation = serde_json::from_str(&line)
-                                    .map_err(|e| format!("Parse op: {} in {}", e, line))?;
-                                ops.push(op);
+                        let records = if lenient {
+                            let (records, bad_lines) = read_op_records_lenient(&path)?;
+                            if !bad_lines.is_empty() {
+                                quarantine_lines(&path, &bad_lines)?;
+                                skipped.extend(bad_lines.into_iter().map(|bad| SkippedOp {
+                                    file: name.to_string(),
+                                    line: bad.number,
+                                    error: bad.error,
+                                }));
                             }
-                        }
+                            records
+                        } else {
+                            read_op_records(&path)?
+                        };
+                        applied_ops.insert(name.to_string(), records.len());
+                        ops.extend(records.into_iter().map(|r| r.op));
                     }
                 }
             }
@@ -120,13 +367,19 @@ impl Document {
             op.apply(&mut state);
         }
 
-        Ok(Self {
+        let meta = DocumentMeta::load(&dir)?;
+
+        let doc = Self {
             id,
             dir,
             state,
             last_load_time: std::time::SystemTime::now(),
             pending_op_count,
-        })
+            meta,
+            applied_ops,
+            local_ops: Vec::new(),
+        };
+        Ok((doc, skipped))
     }
 
     /// Create a new empty document
@@ -147,14 +400,55 @@ impl Document {
             state,
             last_load_time: std::time::SystemTime::now(),
             pending_op_count: 0,
+            meta: DocumentMeta::default(),
+            applied_ops: HashMap::new(),
+            local_ops: Vec::new(),
         };
         doc.save_state()?;
 
         Ok(doc)
     }
 
-    /// Append an operation to the pending file
+    /// Lock or unlock the document, persisting the flag to meta.json
+    pub fn set_locked(&mut self, locked: bool) -> Result<(), String> {
+        self.meta.locked = locked;
+        self.meta.save(&self.dir)
+    }
+
+    /// Replace the document's settings and save them to meta.json
+    pub fn set_settings(&mut self, settings: DocumentSettings) -> Result<(), String> {
+        self.meta.settings = settings;
+        self.meta.save(&self.dir)
+    }
+
+    /// Append an operation to the pending file (rejected if the document is
+    /// locked, or if it would write plain text into an encrypted node)
     pub fn append_op(&mut self, op: &Operation) -> Result<(), String> {
+        if let Operation::Update { id, changes, .. } = op {
+            let edits_text = changes.content.is_some() || changes.note.is_some();
+            if edits_text && changes.encrypted.is_none() && self.state.nodes.iter().any(|n| n.id == *id && n.encrypted) {
+                return Err(format!(
+                    "Node {} is encrypted: save changes to it with update_encrypted_node",
+                    id
+                ));
+            }
+        }
+        let record = OpRecord {
+            op: op.clone(),
+            device: Some(device_name()),
+        };
+        self.append_record(&record)?;
+        for (_, listener) in OP_LISTENERS.read().unwrap().iter() {
+            listener(self.id, &record);
+        }
+        Ok(())
+    }
+
+    fn append_record(&mut self, record: &OpRecord) -> Result<(), String> {
+        if self.meta.locked {
+            return Err("Document is locked".to_string());
+        }
+
         let pending_path = self.pending_path();
         log::info!("append_op: writing to {:?}", pending_path);
 
@@ -164,15 +458,29 @@ impl Document {
             .open(&pending_path)
             .map_err(|e| format!("Open pending file {:?}: {}", pending_path, e))?;
 
-        let json = serde_json::to_string(op).map_err(|e| format!("Serialize op: {}", e))?;
+        let json = op_line(record)?;
         writeln!(file, "{}", json).map_err(|e| format!("Write op: {}", e))?;
         file.flush().map_err(|e| format!("Flush pending file: {}", e))?;
+        sync_op_file(&file)?;
 
         self.pending_op_count += 1;
+        *self.applied_ops.entry(Self::pending_file_name()).or_default() += 1;
+        self.local_ops.push(record.clone());
         log::info!("append_op: wrote {} bytes (pending ops: {})", json.len(), self.pending_op_count);
         Ok(())
     }
 
+    /// Renumber sibling positions via Move ops (see
+    /// `DocumentState::position_normalization_ops`). Returns the number of nodes moved.
+    pub fn normalize_positions(&mut self, force: bool) -> Result<usize, String> {
+        let ops = self.state.position_normalization_ops(force);
+        for op in &ops {
+            self.append_op(op)?;
+            op.apply(&mut self.state);
+        }
+        Ok(ops.len())
+    }
+
     /// Check if auto-compaction should be triggered
     /// Threshold: 1000 operations or 1MB pending file size
     pub fn should_auto_compact(&self) -> bool {
@@ -199,9 +507,11 @@ impl Document {
     pub fn save_state(&self) -> Result<(), String> {
         let state_path = self.state_path();
 
-        let json =
-            serde_json::to_string_pretty(&self.state).map_err(|e| format!("Serialize: {}", e))?;
-        fs::write(&state_path, json).map_err(|e| format!("Write state.json: {}", e))?;
+        // Streamed and compact: pretty-printing a large document roughly
+        // doubles the file and building it as a String doubles the memory
+        write_atomic(&state_path, |writer| {
+            serde_json::to_writer(writer, &self.state).map_err(|e| format!("Write state.json: {}", e))
+        })?;
 
         Ok(())
     }
@@ -225,12 +535,120 @@ impl Document {
         Ok(())
     }
 
+    /// Append every pending op (from all machines) to this machine's history file,
+    /// so the operation log survives compaction
+    fn archive_pending(&self, records: &[OpRecord]) -> Result<(), String> {
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let history_path = self.history_path();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&history_path)
+            .map_err(|e| format!("Open history file {:?}: {}", history_path, e))?;
+        for record in records {
+            writeln!(file, "{}", op_line(record)?).map_err(|e| format!("Write history: {}", e))?;
+        }
+        file.flush().map_err(|e| format!("Flush history file: {}", e))?;
+        Ok(())
+    }
+
+    /// Once this machine's history file reaches `max_bytes`, rename it to
+    /// history.{device}.{stamp}.jsonl (the stamp being its newest op, so
+    /// `op_log` can skip it) and delete all but the newest `keep` of these
+    fn rotate_history(&self, max_bytes: u64, keep: usize) -> Result<(), String> {
+        let path = self.history_path();
+        if fs::metadata(&path).map_or(true, |m| m.len() < max_bytes) {
+            return Ok(());
+        }
+        let mut newest = read_op_records(&path)?
+            .iter()
+            .map(|r| r.op.updated_at())
+            .max()
+            .unwrap_or_else(Utc::now);
+        let device = device_name();
+        let mut rotated = self.dir.join(rotated_history_name(&device, newest));
+        while rotated.exists() {
+            newest += chrono::Duration::milliseconds(1);
+            rotated = self.dir.join(rotated_history_name(&device, newest));
+        }
+        fs::rename(&path, &rotated).map_err(|e| format!("Rotate {:?}: {}", path, e))?;
+
+        // Stamps sort by time, so the oldest come first
+        let mine: Vec<PathBuf> = op_files(&self.dir, "history.")?
+            .into_iter()
+            .filter(|p| split_history_stamp(p).is_some_and(|(d, _)| d == device))
+            .collect();
+        for old in &mine[..mine.len().saturating_sub(keep)] {
+            fs::remove_file(old).map_err(|e| format!("Remove {:?}: {}", old, e))?;
+        }
+        Ok(())
+    }
+
+    /// Strip a node's text from every op file (pending and history, from
+    /// all devices), so what it held before it was encrypted doesn't stay on
+    /// disk. Creates and updates of the node lose their content and note,
+    /// except the updates that store it encrypted. Returns the number of ops
+    /// changed.
+    pub fn redact_node_ops(&mut self, node_id: Uuid) -> Result<usize, String> {
+        let mut paths = op_files(&self.dir, "pending.")?;
+        paths.extend(op_files(&self.dir, "history.")?);
+        let mut redacted = 0;
+        for path in paths {
+            let mut records = read_op_records(&path)?;
+            let mut changed = 0;
+            for record in &mut records {
+                changed += usize::from(redact_plaintext(&mut record.op, node_id));
+            }
+            if changed == 0 {
+                continue;
+            }
+            write_atomic(&path, |writer| {
+                for record in &records {
+                    writeln!(writer, "{}", op_line(record)?).map_err(|e| format!("Write {:?}: {}", path, e))?;
+                }
+                Ok(())
+            })?;
+            redacted += changed;
+        }
+        for record in &mut self.local_ops {
+            redact_plaintext(&mut record.op, node_id);
+        }
+        Ok(redacted)
+    }
+
     /// Compact: merge all pending into state.json, clear pending files
     pub fn compact(&mut self) -> Result<(), String> {
-        // State is already up-to-date from load(), just save and clear
+        let _timer = crate::perf::timer("document.compact");
+        // State is already up-to-date from load(); repair broken positions, save and clear.
+        // Locked documents are compacted as-is.
+        if !self.meta.locked {
+            let moved = self.normalize_positions(false)?;
+            if moved > 0 {
+                log::info!("Normalized positions of {} nodes", moved);
+            }
+        }
+        // Journal the pending ops first, so a crash before state.json is
+        // safely on disk can't lose them once the pending files are gone
+        let mut records = Vec::new();
+        for path in op_files(&self.dir, "pending.")? {
+            records.extend(read_op_records(&path)?);
+        }
+        if !records.is_empty() {
+            write_compaction_wal(&self.dir, &records)?;
+        }
         self.save_state()?;
+        self.archive_pending(&records)?;
+        if let Err(e) = self.rotate_history(HISTORY_ROTATE_BYTES, HISTORY_KEEP_ROTATED) {
+            log::warn!("Failed to rotate history of document {}: {}", self.id, e);
+        }
         self.clear_pending()?;
+        remove_compaction_wal(&self.dir)?;
         self.pending_op_count = 0;
+        self.applied_ops.clear();
+        self.local_ops.clear();
         self.last_load_time = std::time::SystemTime::now();
         log::info!("Compacted document, reset pending op count to 0");
         Ok(())
@@ -269,16 +687,286 @@ impl Document {
         false
     }
 
-    /// Reload document from disk, updating state
+    /// Reload document from disk, updating state. Ops this instance wrote
+    /// that are no longer in its pending file (another machine compacted
+    /// before they synced, or a sync tool restored an older copy) are written
+    /// again and re-applied on top, so edits made during a sync aren't lost.
+    /// Ops that did make it into a compacted state.json re-apply as no-ops.
     pub fn reload(&mut self) -> Result<(), String> {
         let new_doc = Document::load(self.dir.clone())?;
+
+        // Compared as JSON values, which ignore field order
+        let op_value = |record: &OpRecord| serde_json::to_value(&record.op).ok();
+        let pending_path = self.pending_path();
+        let on_disk: Vec<_> = if pending_path.exists() {
+            read_op_records(&pending_path)?.iter().map(op_value).collect()
+        } else {
+            Vec::new()
+        };
+        let missing: Vec<OpRecord> = std::mem::take(&mut self.local_ops)
+            .into_iter()
+            .filter(|record| !on_disk.contains(&op_value(record)))
+            .collect();
+
         self.state = new_doc.state;
         self.pending_op_count = new_doc.pending_op_count;
+        self.meta = new_doc.meta;
+        self.applied_ops = new_doc.applied_ops;
         self.last_load_time = std::time::SystemTime::now();
+
+        if !missing.is_empty() {
+            log::info!("reload: re-applying {} local ops missing on disk", missing.len());
+        }
+        for record in &missing {
+            self.append_record(record)?;
+            record.op.apply(&mut self.state);
+        }
+        self.local_ops.clear();
         Ok(())
     }
 }
 
+/// An applied operation with its originating device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpLogEntry {
+    pub device: String,
+    pub updated_at: DateTime<Utc>,
+    pub op: Operation,
+}
+
+/// Deterministic sibling order: by position, ties broken by creation time then id
+fn sibling_order(a: &Node, b: &Node) -> std::cmp::Ordering {
+    a.position
+        .total_cmp(&b.position)
+        .then_with(|| a.created_at.cmp(&b.created_at))
+        .then_with(|| a.id.cmp(&b.id))
+}
+
+/// Get this machine's device name (hostname), used to name pending files and tag ops
+pub fn device_name() -> String {
+    hostname::get()
+        .map(|h| h.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// List the {prefix}*.jsonl op files in a document directory
+pub(super) fn op_files(dir: &Path, prefix: &str) -> Result<Vec<PathBuf>, String> {
+    let mut paths = Vec::new();
+    if !dir.exists() {
+        return Ok(paths);
+    }
+    for entry in fs::read_dir(dir).map_err(|e| format!("Read dir: {}", e))? {
+        let entry = entry.map_err(|e| format!("Read dir entry: {}", e))?;
+        let path = entry.path();
+        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+            if name.starts_with(prefix) && name.ends_with(".jsonl") {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Read op records from a pending/history file. Records written before devices were
+/// tracked take the device from the file name ({prefix}.{device}.jsonl).
+pub(super) fn read_op_records(path: &Path) -> Result<Vec<OpRecord>, String> {
+    let (records, bad_lines) = read_op_records_lenient(path)?;
+    match bad_lines.into_iter().next() {
+        Some(bad) => Err(format!("Parse op: {} in {}", bad.error, bad.text)),
+        None => Ok(records),
+    }
+}
+
+/// Key of the checksum field that ends each op line
+const CHECKSUM_FIELD: &str = ",\"checksum\":\"";
+
+/// SHA-256 of an op record's JSON, truncated to 16 hex digits
+fn op_checksum(json: &str) -> String {
+    Sha256::digest(json.as_bytes())[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Serialize an op record as a line of an op file. A checksum of the JSON is
+/// added as the last field, so lines damaged by a partial or mangled sync
+/// are caught on load instead of being applied.
+pub(super) fn op_line(record: &OpRecord) -> Result<String, String> {
+    let json = serde_json::to_string(record).map_err(|e| format!("Serialize op: {}", e))?;
+    let body = json.strip_suffix('}').ok_or("Op record is not a JSON object")?;
+    Ok(format!("{}{}{}\"}}", body, CHECKSUM_FIELD, op_checksum(&json)))
+}
+
+/// Check the checksum at the end of an op line, returning whether it had
+/// one. Lines written before ops had checksums have none and pass, unless
+/// `required` (a line after a checksummed one in the same file: only
+/// damage strips it there).
+fn verify_op_line(line: &str, required: bool) -> Result<bool, String> {
+    let checksum = line
+        .trim_end()
+        .strip_suffix("\"}")
+        .and_then(|rest| rest.rfind(CHECKSUM_FIELD).map(|start| (rest, start)));
+    let Some((rest, start)) = checksum else {
+        return if required { Err("missing checksum".to_string()) } else { Ok(false) };
+    };
+    let expected = &rest[start + CHECKSUM_FIELD.len()..];
+    let actual = op_checksum(&format!("{}}}", &rest[..start]));
+    if expected != actual {
+        return Err(format!("checksum mismatch (expected {}, got {})", expected, actual));
+    }
+    Ok(true)
+}
+
+/// A line of an op file that isn't a valid op record
+#[derive(Debug, Clone)]
+struct BadLine {
+    /// 1-based line number
+    number: usize,
+    text: String,
+    error: String,
+}
+
+/// An op line skipped by a lenient load
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedOp {
+    pub file: String,
+    pub line: usize,
+    pub error: String,
+}
+
+/// Read an op file, returning the records that parse and the lines that don't
+fn read_op_records_lenient(path: &Path) -> Result<(Vec<OpRecord>, Vec<BadLine>), String> {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    let file_device = match split_history_stamp(path) {
+        Some((device, _)) => Some(device.to_string()),
+        None => name
+            .strip_suffix(".jsonl")
+            .and_then(|n| n.split_once('.'))
+            .map(|(_, device)| device.to_string()),
+    };
+
+    let file = File::open(path).map_err(|e| format!("Open {}: {}", name, e))?;
+    let reader = BufReader::new(file);
+    let mut records = Vec::new();
+    let mut bad_lines = Vec::new();
+    // Once a file has checksummed lines, every later line must have one
+    let mut checksummed = false;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| format!("Read line: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parsed = verify_op_line(&line, checksummed)
+            .inspect(|had_checksum| checksummed |= had_checksum)
+            .and_then(|_| serde_json::from_str::<OpRecord>(&line).map_err(|e| e.to_string()));
+        match parsed {
+            Ok(mut record) => {
+                if record.device.is_none() {
+                    record.device = file_device.clone();
+                }
+                records.push(record);
+            }
+            Err(error) => bad_lines.push(BadLine {
+                number: i + 1,
+                text: line,
+                error,
+            }),
+        }
+    }
+    Ok((records, bad_lines))
+}
+
+/// Append bad lines to `<file>.corrupt` and rewrite the file without them
+fn quarantine_lines(path: &Path, bad_lines: &[BadLine]) -> Result<(), String> {
+    let mut corrupt_path = path.as_os_str().to_owned();
+    corrupt_path.push(".corrupt");
+    let mut corrupt = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&corrupt_path)
+        .map_err(|e| format!("Open {:?}: {}", corrupt_path, e))?;
+    for bad in bad_lines {
+        writeln!(corrupt, "{}", bad.text).map_err(|e| format!("Write {:?}: {}", corrupt_path, e))?;
+    }
+
+    let contents = fs::read_to_string(path).map_err(|e| format!("Read {:?}: {}", path, e))?;
+    let bad_numbers: HashSet<usize> = bad_lines.iter().map(|bad| bad.number).collect();
+    let mut kept = String::with_capacity(contents.len());
+    for (i, line) in contents.lines().enumerate() {
+        if !bad_numbers.contains(&(i + 1)) {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+    fs::write(path, kept).map_err(|e| format!("Write {:?}: {}", path, e))
+}
+
+/// This machine's history file is rotated once compaction leaves it this big...
+const HISTORY_ROTATE_BYTES: u64 = 4 * 1024 * 1024;
+/// ...and only this many rotated files are kept, so history stays bounded
+const HISTORY_KEEP_ROTATED: usize = 8;
+/// Stamp in rotated history file names (no dots, which separate the device)
+const HISTORY_STAMP_FORMAT: &str = "%Y%m%dT%H%M%S%3fZ";
+
+fn rotated_history_name(device: &str, newest: DateTime<Utc>) -> String {
+    format!("history.{}.{}.jsonl", device, newest.format(HISTORY_STAMP_FORMAT))
+}
+
+/// Device and newest op time of a rotated history file (None for other files)
+fn split_history_stamp(path: &Path) -> Option<(&str, DateTime<Utc>)> {
+    let name = path.file_name()?.to_str()?;
+    let stem = name.strip_prefix("history.")?.strip_suffix(".jsonl")?;
+    let (device, stamp) = stem.rsplit_once('.')?;
+    let newest = NaiveDateTime::parse_from_str(stamp, HISTORY_STAMP_FORMAT).ok()?;
+    Some((device, newest.and_utc()))
+}
+
+/// Get applied operations (archived and pending) with their originating device,
+/// optionally limited to ops whose timestamp falls within [start, end].
+/// Rotated history files holding only ops before `start` aren't read.
+pub fn op_log(
+    dir: &Path,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> Result<Vec<OpLogEntry>, String> {
+    let mut paths: Vec<PathBuf> = op_files(dir, "history.")?
+        .into_iter()
+        .filter(|p| match (start, split_history_stamp(p)) {
+            (Some(start), Some((_, newest))) => newest >= start,
+            _ => true,
+        })
+        .collect();
+    paths.extend(op_files(dir, "pending.")?);
+
+    // The same op can be archived by two machines that compacted concurrently
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for path in paths {
+        for record in read_op_records(&path)? {
+            let updated_at = record.op.updated_at();
+            if start.is_some_and(|s| updated_at < s) || end.is_some_and(|e| updated_at > e) {
+                continue;
+            }
+            let key = serde_json::to_string(&record.op).map_err(|e| format!("Serialize op: {}", e))?;
+            if !seen.insert(key) {
+                continue;
+            }
+            entries.push(OpLogEntry {
+                device: record.device.unwrap_or_else(|| "unknown".to_string()),
+                updated_at,
+                op: record.op,
+            });
+        }
+    }
+
+    entries.sort_by_key(|e| e.updated_at);
+    Ok(entries)
+}
+
 /// Get the default data directory path
 pub fn default_data_dir() -> PathBuf {
     dirs::home_dir()
@@ -286,14 +974,48 @@ pub fn default_data_dir() -> PathBuf {
         .join(".outline-data")
 }
 
-/// Get the data directory path (uses override if set, otherwise default)
+/// Environment variable that overrides the configured data directory
+pub const DATA_DIR_ENV: &str = "OUTLINE_DATA_DIR";
+
+/// Get the data directory path: the runtime override if set, then
+/// $OUTLINE_DATA_DIR, then the default
 pub fn data_dir() -> PathBuf {
     if let Ok(guard) = DATA_DIR_OVERRIDE.read() {
         if let Some(ref path) = *guard {
             return path.clone();
         }
     }
-    default_data_dir()
+    env_data_dir().unwrap_or_else(default_data_dir)
+}
+
+fn env_data_dir() -> Option<PathBuf> {
+    std::env::var_os(DATA_DIR_ENV)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Get the `--data-dir <path>` (or `--data-dir=<path>`) launch argument
+fn data_dir_arg(args: impl IntoIterator<Item = String>) -> Option<PathBuf> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--data-dir" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--data-dir=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+/// What picks the data directory ahead of config.json (the `--data-dir`
+/// launch argument or $OUTLINE_DATA_DIR), if anything
+pub fn data_dir_override_source() -> Option<String> {
+    if data_dir_arg(std::env::args().skip(1)).is_some() {
+        Some("--data-dir".to_string())
+    } else {
+        env_data_dir().map(|_| format!("${}", DATA_DIR_ENV))
+    }
 }
 
 /// Set the data directory override
@@ -304,7 +1026,7 @@ pub fn set_data_dir(path: Option<PathBuf>) {
 }
 
 /// Get the config file path (stored in user's config directory)
-fn config_path() -> PathBuf {
+pub fn config_path() -> PathBuf {
     dirs::config_dir()
         .unwrap_or_else(|| dirs::home_dir().unwrap_or_else(|| PathBuf::from(".")))
         .join("outline")
@@ -318,6 +1040,105 @@ pub struct InboxConfig {
     pub node_id: String,
 }
 
+/// Journal configuration - where daily notes live and how they are titled
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalConfig {
+    /// Document that holds one dated root node per day; if unset, each day
+    /// gets its own document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_id: Option<String>,
+    /// strftime-style title format (e.g. "%Y-%m-%d" or "%A, %B %-d")
+    #[serde(default = "default_journal_title_format")]
+    pub title_format: String,
+    /// Folder each day's document is filed in (None for the root)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder_id: Option<String>,
+}
+
+fn default_journal_title_format() -> String {
+    "%Y-%m-%d".to_string()
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        Self {
+            document_id: None,
+            title_format: default_journal_title_format(),
+            folder_id: None,
+        }
+    }
+}
+
+/// MQTT publishing configuration - where events go and which tags to announce
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MqttConfig {
+    /// Broker address, `host`, `host:port` or `mqtt://host:port`
+    pub broker: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Prefix of every topic, e.g. "outline" for "outline/task/checked"
+    #[serde(default = "default_mqtt_topic_prefix")]
+    pub topic_prefix: String,
+    /// Tags announced when a new node gets them (without the leading #)
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Whether to publish today's agenda as a retained message
+    #[serde(default = "default_true")]
+    pub agenda: bool,
+}
+
+fn default_mqtt_topic_prefix() -> String {
+    "outline".to_string()
+}
+
+/// Speech-to-text engine for voice captures (see transcribe.rs)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "engine", rename_all = "snake_case")]
+pub enum TranscriptionConfig {
+    /// A local whisper.cpp build; recordings that aren't WAV are converted
+    /// with ffmpeg first
+    WhisperCpp {
+        /// The whisper.cpp command line program
+        #[serde(default = "default_whisper_binary")]
+        binary: String,
+        /// Path of a ggml model file
+        model: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        language: Option<String>,
+    },
+    /// An OpenAI-compatible `/v1/audio/transcriptions` endpoint
+    Api {
+        #[serde(default = "default_transcription_url")]
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        api_key: Option<String>,
+        #[serde(default = "default_transcription_model")]
+        model: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        language: Option<String>,
+    },
+}
+
+fn default_whisper_binary() -> String {
+    "whisper-cli".to_string()
+}
+
+fn default_transcription_url() -> String {
+    "https://api.openai.com/v1/audio/transcriptions".to_string()
+}
+
+fn default_transcription_model() -> String {
+    "whisper-1".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
 /// App configuration
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AppConfig {
@@ -325,35 +1146,139 @@ pub struct AppConfig {
     pub data_directory: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub inbox: Option<InboxConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub journal: Option<JournalConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fsync: Option<FsyncPolicy>,
+    /// When each document was last opened, from before this moved to
+    /// recent.json; read once to seed it
+    #[serde(default, skip_serializing)]
+    pub recent_documents: HashMap<String, DateTime<Utc>>,
+    /// Migration level reached by each data directory (see migrations.rs)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub data_migrations: HashMap<String, usize>,
+    /// Time tracking timer running on this machine (see timelog.rs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub running_timer: Option<RunningTimer>,
+    /// How the search cache of each data directory is stored on this machine
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub search_cache: HashMap<String, SearchCacheConfig>,
+    /// Local date overdue rules last ran on this machine (see overdue.rs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overdue_rules_run: Option<String>,
+    /// Folder for documents created from dangling wiki links (None for the root)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_target_folder: Option<String>,
+    /// MQTT broker to publish events to (see mqtt.rs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mqtt: Option<MqttConfig>,
+    /// How voice captures are transcribed (None leaves them as audio only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcription: Option<TranscriptionConfig>,
 }
 
-/// Load app configuration from disk
-pub fn load_config() -> AppConfig {
+/// How the search cache is stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchCacheMode {
+    /// Plain SQLite file in the cache directory
+    #[default]
+    Plain,
+    /// SQLCipher database (needs a build with the `sqlcipher` feature)
+    Encrypted,
+    /// Nothing written to disk: the index is kept in memory and rebuilt at startup
+    Disabled,
+}
+
+/// Search cache settings for one data directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchCacheConfig {
+    #[serde(default)]
+    pub mode: SearchCacheMode,
+    /// Key of an encrypted cache. It stays on this machine, like the cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+/// Search cache settings for the current data directory
+pub fn search_cache_config() -> SearchCacheConfig {
+    let key = data_dir().to_string_lossy().to_string();
+    load_config().search_cache.remove(&key).unwrap_or_default()
+}
+
+/// Save the search cache settings for the current data directory
+pub fn set_search_cache_config(cache: SearchCacheConfig) -> Result<(), String> {
+    let key = data_dir().to_string_lossy().to_string();
+    update_config(|config| {
+        config.search_cache.insert(key, cache);
+    })
+}
+
+/// How many documents the recent list remembers
+const MAX_RECENT_DOCUMENTS: usize = 50;
+
+/// Held while config.json is read, changed and written back, so two changes
+/// can't overwrite each other
+static CONFIG_LOCK: Mutex<()> = Mutex::new(());
+
+/// Read config.json (defaults if there is none). Unlike `load_config`, a
+/// file that can't be read or parsed is an error.
+fn read_config() -> Result<AppConfig, String> {
     let path = config_path();
-    if path.exists() {
-        if let Ok(content) = fs::read_to_string(&path) {
-            if let Ok(config) = serde_json::from_str(&content) {
-                return config;
-            }
-        }
+    if !path.exists() {
+        return Ok(AppConfig::default());
     }
-    AppConfig::default()
+    let content = fs::read_to_string(&path).map_err(|e| format!("Read config: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Parse config: {}", e))
 }
 
-/// Save app configuration to disk
-pub fn save_config(config: &AppConfig) -> Result<(), String> {
+/// Load app configuration from disk (defaults if missing or unreadable)
+pub fn load_config() -> AppConfig {
+    read_config().unwrap_or_else(|e| {
+        log::warn!("{}", e);
+        AppConfig::default()
+    })
+}
+
+/// Change app configuration on disk, returning what `change` returns. The
+/// file is re-read under a lock and replaced atomically; one that can't be
+/// parsed is left alone rather than overwritten with defaults.
+pub fn update_config<T>(change: impl FnOnce(&mut AppConfig) -> T) -> Result<T, String> {
+    let _guard = CONFIG_LOCK.lock().unwrap();
+    let mut config = read_config()?;
+    let result = change(&mut config);
+
     let path = config_path();
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).map_err(|e| format!("Create config dir: {}", e))?;
     }
-    let content = serde_json::to_string_pretty(config)
-        .map_err(|e| format!("Serialize config: {}", e))?;
-    fs::write(&path, content).map_err(|e| format!("Write config: {}", e))?;
-    Ok(())
+    write_atomic(&path, |writer| {
+        serde_json::to_writer_pretty(writer, &config).map_err(|e| format!("Write config: {}", e))
+    })?;
+    Ok(result)
 }
 
-/// Initialize data directory from config (call at startup)
+/// Initialize data directory (call at startup). Precedence: the `--data-dir`
+/// launch argument, then $OUTLINE_DATA_DIR, then config.json, then the default.
 pub fn init_data_dir_from_config() {
+    select_data_dir();
+    if let Err(e) = super::migrations::run_migrations() {
+        log::error!("Failed to migrate data directory: {}", e);
+    }
+}
+
+fn select_data_dir() {
+    if let Some(path) = data_dir_arg(std::env::args().skip(1)) {
+        log::info!("Using data directory from --data-dir: {:?}", path);
+        set_data_dir(Some(path));
+        return;
+    }
+    if let Some(path) = env_data_dir() {
+        log::info!("Using data directory from {}: {:?}", DATA_DIR_ENV, path);
+        set_data_dir(Some(path));
+        return;
+    }
+
     let config = load_config();
     if let Some(ref path_str) = config.data_directory {
         let path = PathBuf::from(path_str);
@@ -370,19 +1295,82 @@ pub fn get_inbox_config() -> Option<InboxConfig> {
 
 /// Set the inbox configuration
 pub fn set_inbox_config(document_id: String, node_id: String) -> Result<(), String> {
-    let mut config = load_config();
-    config.inbox = Some(InboxConfig {
-        document_id,
-        node_id,
-    });
-    save_config(&config)
+    update_config(|config| {
+        config.inbox = Some(InboxConfig {
+            document_id,
+            node_id,
+        });
+    })
 }
 
 /// Clear the inbox configuration
 pub fn clear_inbox_config() -> Result<(), String> {
-    let mut config = load_config();
-    config.inbox = None;
-    save_config(&config)
+    update_config(|config| config.inbox = None)
+}
+
+/// When each document was last opened on this machine, kept next to
+/// config.json but apart from it, since it changes on every open
+fn recent_path() -> PathBuf {
+    config_path().with_file_name("recent.json")
+}
+
+static RECENT_LOCK: Mutex<()> = Mutex::new(());
+
+fn load_recent() -> HashMap<String, DateTime<Utc>> {
+    match fs::read_to_string(recent_path()) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => load_config().recent_documents,
+    }
+}
+
+/// Record that a document was just opened
+pub fn record_document_opened(document_id: &str) -> Result<(), String> {
+    let _guard = RECENT_LOCK.lock().unwrap();
+    let mut recent = load_recent();
+    recent.insert(document_id.to_string(), Utc::now());
+
+    // Forget the oldest entries beyond the limit
+    if recent.len() > MAX_RECENT_DOCUMENTS {
+        let mut opened: Vec<DateTime<Utc>> = recent.values().copied().collect();
+        opened.sort_unstable_by_key(|at| std::cmp::Reverse(*at));
+        let cutoff = opened[MAX_RECENT_DOCUMENTS - 1];
+        recent.retain(|_, at| *at >= cutoff);
+    }
+
+    let path = recent_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Create config dir: {}", e))?;
+    }
+    write_atomic(&path, |writer| {
+        serde_json::to_writer(writer, &recent).map_err(|e| format!("Write recent documents: {}", e))
+    })
+}
+
+/// Recently opened documents, most recent first
+pub fn recent_documents() -> Vec<(String, DateTime<Utc>)> {
+    let mut recent: Vec<_> = load_recent().into_iter().collect();
+    recent.sort_by_key(|(_, at)| std::cmp::Reverse(*at));
+    recent
+}
+
+/// Get the journal configuration (defaults if unset)
+pub fn get_journal_config() -> JournalConfig {
+    load_config().journal.unwrap_or_default()
+}
+
+/// Set the journal configuration
+pub fn set_journal_config(journal: JournalConfig) -> Result<(), String> {
+    update_config(|config| config.journal = Some(journal))
+}
+
+/// Folder that documents created from wiki links are filed in
+pub fn get_link_target_folder() -> Option<String> {
+    load_config().link_target_folder
+}
+
+/// Set the folder for documents created from wiki links (None for the root)
+pub fn set_link_target_folder(folder_id: Option<String>) -> Result<(), String> {
+    update_config(|config| config.link_target_folder = folder_id)
 }
 
 /// Get the documents directory path
@@ -422,6 +1410,26 @@ pub fn list_documents() -> Result<Vec<Uuid>, String> {
     Ok(ids)
 }
 
+/// Whether another device's operations for a document were written after
+/// `since` (this device's own saves don't count)
+fn synced_since(doc_dir: &Path, since: std::time::SystemTime) -> bool {
+    let own = Document::pending_file_name();
+    op_files(doc_dir, "pending.")
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|path| path.file_name().and_then(|n| n.to_str()) != Some(own.as_str()))
+        .filter_map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .any(|modified| modified > since)
+}
+
+/// Documents that received operations from other devices after `since`
+pub fn documents_synced_since(since: std::time::SystemTime) -> Result<Vec<Uuid>, String> {
+    Ok(list_documents()?
+        .into_iter()
+        .filter(|id| synced_since(&documents_dir().join(id.to_string()), since))
+        .collect())
+}
+
 /// An inbox item captured from mobile/web
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InboxItem {
@@ -433,6 +1441,30 @@ pub struct InboxItem {
     pub captured_at: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub source: Option<String>,
+    /// Recording of a voice capture, a file name in the audio directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<String>,
+    /// Whether the recording is still being transcribed (the transcript
+    /// becomes the note); such items aren't imported until it's done
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub transcribing: bool,
+}
+
+impl InboxItem {
+    /// A new item captured now
+    pub fn new(content: String, note: Option<String>, source: Option<String>) -> Self {
+        let now = chrono::Local::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            content,
+            note,
+            capture_date: now.format("%Y-%m-%d").to_string(),
+            captured_at: now.to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+            source,
+            audio: None,
+            transcribing: false,
+        }
+    }
 }
 
 /// Get the inbox file path
@@ -466,6 +1498,39 @@ pub fn read_inbox() -> Result<Vec<InboxItem>, String> {
     Ok(items)
 }
 
+/// Append an item to the inbox
+pub fn append_inbox_item(item: &InboxItem) -> Result<(), String> {
+    let json = serde_json::to_string(item).map_err(|e| format!("Serialize item: {}", e))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(inbox_path())
+        .map_err(|e| format!("Open inbox.jsonl: {}", e))?;
+    writeln!(file, "{}", json).map_err(|e| format!("Write item: {}", e))
+}
+
+/// Change an inbox item in place. Returns false if it's no longer in the
+/// inbox.
+pub fn update_inbox_item(id: &str, update: impl FnOnce(&mut InboxItem)) -> Result<bool, String> {
+    let mut items = read_inbox()?;
+    let Some(item) = items.iter_mut().find(|item| item.id == id) else {
+        return Ok(false);
+    };
+    update(item);
+
+    let mut file = File::create(inbox_path()).map_err(|e| format!("Create inbox.jsonl: {}", e))?;
+    for item in items {
+        let json = serde_json::to_string(&item).map_err(|e| format!("Serialize item: {}", e))?;
+        writeln!(file, "{}", json).map_err(|e| format!("Write item: {}", e))?;
+    }
+    Ok(true)
+}
+
+/// Directory for voice recordings (inbox captures and node attachments)
+pub fn audio_dir() -> PathBuf {
+    data_dir().join("audio")
+}
+
 /// Remove processed inbox items by their IDs
 pub fn remove_inbox_items(ids: &[String]) -> Result<(), String> {
     let path = inbox_path();
@@ -510,6 +1575,18 @@ mod tests {
         (tmp, doc_dir)
     }
 
+    #[test]
+    fn test_synced_since() {
+        let (_tmp, doc_dir) = test_doc_dir();
+        let before = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+        fs::write(doc_dir.join(Document::pending_file_name()), "").unwrap();
+        assert!(!synced_since(&doc_dir, before), "own saves aren't synced changes");
+
+        fs::write(doc_dir.join("pending.other-device.jsonl"), "").unwrap();
+        assert!(synced_since(&doc_dir, before));
+        assert!(!synced_since(&doc_dir, std::time::SystemTime::now() + std::time::Duration::from_secs(60)));
+    }
+
     #[test]
     fn test_create_and_load_document() {
         let (_tmp, doc_dir) = test_doc_dir();
@@ -523,6 +1600,34 @@ mod tests {
         assert!(doc2.state.nodes.is_empty());
     }
 
+    #[test]
+    fn test_reload_keeps_local_ops_missing_on_disk() {
+        let (_tmp, doc_dir) = test_doc_dir();
+        let mut doc = Document::create(doc_dir.clone()).unwrap();
+        let saved = create_op(None, 0.0, "Saved".to_string());
+        doc.append_op(&saved).unwrap();
+        saved.apply(&mut doc.state);
+
+        // Another machine compacts with a copy of our pending file that
+        // lacks the next op, then its deletion of our file syncs back
+        let mut other = Document::load(doc_dir.clone()).unwrap();
+        let typed = create_op(None, 1.0, "Typed during sync".to_string());
+        doc.append_op(&typed).unwrap();
+        typed.apply(&mut doc.state);
+        other.compact().unwrap();
+
+        doc.reload().unwrap();
+        let mut contents: Vec<&str> = doc.state.nodes.iter().map(|n| n.content.as_str()).collect();
+        contents.sort();
+        assert_eq!(contents, vec!["Saved", "Typed during sync"]);
+
+        // The re-applied op was written again, so it survives a fresh load
+        assert_eq!(Document::load(doc_dir.clone()).unwrap().state.nodes.len(), 2);
+        let pending = doc.pending_op_count;
+        doc.reload().unwrap();
+        assert_eq!(doc.pending_op_count, pending, "ops on disk aren't written twice");
+    }
+
     #[test]
     fn test_operations_and_reload() {
         let (_tmp, doc_dir) = test_doc_dir();
@@ -531,7 +1636,7 @@ mod tests {
         let mut doc = Document::create(doc_dir.clone()).unwrap();
 
         // Add a node via operation
-        let op1 = create_op(None, 0, "First node".to_string());
+        let op1 = create_op(None, 0.0, "First node".to_string());
         let node_id = match &op1 {
             crate::data::Operation::Create { id, .. } => *id,
             _ => unreachable!(),
@@ -576,8 +1681,8 @@ mod tests {
         doc.save_state().unwrap();
 
         // Simulate operations from two machines by writing pending files directly
-        let op1 = create_op(None, 0, "From machine A".to_string());
-        let op2 = create_op(None, 1, "From machine B".to_string());
+        let op1 = create_op(None, 0.0, "From machine A".to_string());
+        let op2 = create_op(None, 1.0, "From machine B".to_string());
 
         // Write to different pending files
         let pending_a = doc_dir.join("pending.machine-a.jsonl");
@@ -590,4 +1695,305 @@ mod tests {
         let doc2 = Document::load(doc_dir).unwrap();
         assert_eq!(doc2.state.nodes.len(), 2);
     }
+
+    #[test]
+    fn test_op_log_survives_compaction() {
+        let (_tmp, doc_dir) = test_doc_dir();
+        let mut doc = Document::create(doc_dir.clone()).unwrap();
+
+        // Legacy line from another machine, written without a device field
+        let remote = create_op(None, 0.0, "From laptop".to_string());
+        fs::write(
+            doc_dir.join("pending.laptop.jsonl"),
+            serde_json::to_string(&remote).unwrap() + "\n",
+        )
+        .unwrap();
+
+        let local = create_op(None, 1.0, "From here".to_string());
+        doc.append_op(&local).unwrap();
+
+        let log = op_log(&doc.dir, None, None).unwrap();
+        assert_eq!(log.len(), 2);
+        assert!(log.iter().any(|e| e.device == "laptop"));
+        assert!(log.iter().any(|e| e.device == device_name()));
+
+        // Compaction clears pending files but keeps the log
+        doc.compact().unwrap();
+        let log = op_log(&doc.dir, None, None).unwrap();
+        assert_eq!(log.len(), 2);
+        assert!(log[0].updated_at <= log[1].updated_at);
+
+        // Range filtering
+        let after_all = Utc::now() + chrono::Duration::seconds(1);
+        assert!(op_log(&doc.dir, Some(after_all), None).unwrap().is_empty());
+        assert_eq!(op_log(&doc.dir, None, Some(after_all)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_history_is_rotated_and_capped() {
+        let (_tmp, doc_dir) = test_doc_dir();
+        let mut doc = Document::create(doc_dir.clone()).unwrap();
+        let rotated = |doc: &Document| {
+            op_files(&doc.dir, "history.")
+                .unwrap()
+                .into_iter()
+                .filter_map(|p| split_history_stamp(&p).map(|(_, newest)| newest))
+                .collect::<Vec<_>>()
+        };
+
+        let mut first_op = None;
+        for i in 0..4 {
+            let op = create_op(None, i as f64, format!("Node {}", i));
+            first_op.get_or_insert(op.updated_at());
+            doc.append_op(&op).unwrap();
+            doc.compact().unwrap();
+            doc.rotate_history(1, 2).unwrap();
+        }
+        // Each compaction rotated one file; only the newest two are kept
+        let stamps = rotated(&doc);
+        assert_eq!(stamps.len(), 2);
+        assert!(!doc.history_path().exists());
+        assert!(stamps[0] < stamps[1]);
+
+        // Rotated files still make up the log, with their device
+        let log = op_log(&doc.dir, None, None).unwrap();
+        assert_eq!(log.len(), 2);
+        assert!(log.iter().all(|e| e.device == device_name()));
+        assert!(log.iter().all(|e| e.updated_at > first_op.unwrap()));
+        // Files older than the range are skipped
+        let after_all = stamps[1] + chrono::Duration::milliseconds(1);
+        assert!(op_log(&doc.dir, Some(after_all), None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_locked_document_rejects_ops() {
+        let (_temp, doc_dir) = test_doc_dir();
+        let mut doc = Document::create(doc_dir.clone()).unwrap();
+        doc.append_op(&create_op(None, 0.0, "Before".to_string())).unwrap();
+
+        doc.set_locked(true).unwrap();
+        let err = doc.append_op(&create_op(None, 1.0, "After".to_string())).unwrap_err();
+        assert_eq!(err, "Document is locked");
+
+        // The flag persists and compaction still works
+        let mut reloaded = Document::load(doc_dir.clone()).unwrap();
+        assert!(reloaded.meta.locked);
+        reloaded.compact().unwrap();
+        assert_eq!(reloaded.state.nodes.len(), 1);
+
+        reloaded.set_locked(false).unwrap();
+        reloaded.append_op(&create_op(None, 1.0, "After".to_string())).unwrap();
+    }
+
+    #[test]
+    fn test_subtree() {
+        let root = Node::new("Root".to_string());
+        let second = Node::new_child(root.id, 1.0, "Second".to_string());
+        let first = Node::new_child(root.id, 0.0, "First".to_string());
+        let grandchild = Node::new_child(first.id, 0.0, "Grandchild".to_string());
+        let other = Node::new("Other".to_string());
+        let state = DocumentState {
+            nodes: vec![second.clone(), root.clone(), grandchild.clone(), first.clone(), other],
+        };
+
+        let contents = |nodes: Vec<&Node>| nodes.iter().map(|n| n.content.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            contents(state.subtree(root.id, None)),
+            vec!["Root", "First", "Grandchild", "Second"]
+        );
+        assert_eq!(contents(state.subtree(root.id, Some(1))), vec!["Root", "First", "Second"]);
+        assert_eq!(contents(state.subtree(root.id, Some(0))), vec!["Root"]);
+        assert!(state.subtree(Uuid::now_v7(), None).is_empty());
+    }
+
+    #[test]
+    fn test_load_lenient_quarantines_bad_lines() {
+        let (_temp, doc_dir) = test_doc_dir();
+        let mut doc = Document::create(doc_dir.clone()).unwrap();
+        let op = create_op(None, 0.0, "Survivor".to_string());
+        doc.append_op(&op).unwrap();
+
+        let pending = doc.dir.join(Document::pending_file_name());
+        let mut file = OpenOptions::new().append(true).open(&pending).unwrap();
+        writeln!(file, r#"{{"op":"update","id":"trunc"#).unwrap();
+        drop(file);
+
+        assert!(Document::load(doc_dir.clone()).is_err());
+
+        let (doc, skipped) = Document::load_lenient(doc_dir.clone()).unwrap();
+        assert_eq!(doc.state.nodes.len(), 1);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].line, 2);
+
+        let mut corrupt = pending.into_os_string();
+        corrupt.push(".corrupt");
+        assert!(fs::read_to_string(corrupt).unwrap().contains("trunc"));
+        // The pending file is clean again
+        assert_eq!(Document::load(doc_dir).unwrap().state.nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_op_checksum_catches_altered_lines() {
+        let (_temp, doc_dir) = test_doc_dir();
+        let mut doc = Document::create(doc_dir.clone()).unwrap();
+        doc.append_op(&create_op(None, 0.0, "Original".to_string())).unwrap();
+        doc.append_op(&create_op(None, 1.0, "Second".to_string())).unwrap();
+
+        // Still valid JSON, so only the checksum can tell
+        let pending = doc.dir.join(Document::pending_file_name());
+        let contents = fs::read_to_string(&pending).unwrap();
+        assert!(contents.lines().all(|line| verify_op_line(line, true).is_ok()));
+        fs::write(&pending, contents.replace("Original", "Altered")).unwrap();
+
+        let err = Document::load(doc_dir.clone()).err().unwrap();
+        assert!(err.contains("checksum mismatch"), "{}", err);
+
+        let (doc, skipped) = Document::load_lenient(doc_dir.clone()).unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(doc.state.nodes.len(), 1);
+        assert_eq!(doc.state.nodes[0].content, "Second");
+
+        // Lines written before checksums still load
+        let legacy = serde_json::to_string(&OpRecord {
+            op: create_op(None, 2.0, "Legacy".to_string()),
+            device: None,
+        })
+        .unwrap();
+        assert_eq!(verify_op_line(&legacy, false), Ok(false));
+
+        // ...but not after checksummed lines, where the checksum was cut off
+        let (_temp, doc_dir) = test_doc_dir();
+        let mut doc = Document::create(doc_dir.clone()).unwrap();
+        doc.append_op(&create_op(None, 0.0, "Checked".to_string())).unwrap();
+        let pending = doc.dir.join(Document::pending_file_name());
+        let mut file = OpenOptions::new().append(true).open(&pending).unwrap();
+        writeln!(file, "{}", legacy).unwrap();
+        let err = Document::load(doc_dir).err().unwrap();
+        assert!(err.contains("missing checksum"), "{}", err);
+    }
+
+    #[test]
+    fn test_document_settings_persist() {
+        let (_temp, doc_dir) = test_doc_dir();
+        let mut doc = Document::create(doc_dir.clone()).unwrap();
+        // meta.json written before settings existed
+        fs::write(doc_dir.join("meta.json"), r#"{"locked": false}"#).unwrap();
+        assert!(!Document::load(doc_dir.clone()).unwrap().meta.settings.completed_to_bottom);
+
+        doc.set_settings(DocumentSettings {
+            default_node_type: Some(NodeType::Checkbox),
+            completed_to_bottom: true,
+            journal_date_format: None,
+            recurring_instances: false,
+            excluded: true,
+            markdown_notes: false,
+            title_sync: TitleSync::FirstNode,
+        })
+        .unwrap();
+        let settings = Document::load(doc_dir).unwrap().meta.settings;
+        assert_eq!(settings.default_node_type, Some(NodeType::Checkbox));
+        assert!(settings.completed_to_bottom);
+        assert!(settings.excluded);
+    }
+
+    #[test]
+    fn test_data_dir_arg() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(data_dir_arg(args(&["--data-dir", "/tmp/notes"])), Some(PathBuf::from("/tmp/notes")));
+        assert_eq!(data_dir_arg(args(&["-v", "--data-dir=/tmp/notes"])), Some(PathBuf::from("/tmp/notes")));
+        assert_eq!(data_dir_arg(args(&["--data-dir"])), None);
+        assert_eq!(data_dir_arg(args(&["--verbose"])), None);
+    }
+
+    #[test]
+    fn test_ancestors() {
+        let root = Node::new("Root".to_string());
+        let child = Node::new_child(root.id, 0.0, "Child".to_string());
+        let leaf = Node::new_child(child.id, 0.0, "Leaf".to_string());
+        let state = DocumentState {
+            nodes: vec![leaf.clone(), root.clone(), child.clone()],
+        };
+
+        let ids: Vec<Uuid> = state.ancestors(leaf.id).iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![root.id, child.id]);
+        assert!(state.ancestors(root.id).is_empty());
+        assert!(state.ancestors(Uuid::now_v7()).is_empty());
+    }
+
+    #[test]
+    fn test_position_for_index() {
+        let parent = Node::new("Parent".to_string());
+        let a = Node::new_child(parent.id, 0.0, "A".to_string());
+        let b = Node::new_child(parent.id, 1.0, "B".to_string());
+        let state = DocumentState {
+            nodes: vec![parent.clone(), a.clone(), b.clone()],
+        };
+
+        assert_eq!(state.position_for_index(Some(parent.id), 0, None), -1.0);
+        assert_eq!(state.position_for_index(Some(parent.id), 1, None), 0.5);
+        assert_eq!(state.position_for_index(Some(parent.id), 2, None), 2.0);
+        assert_eq!(state.position_for_index(Some(parent.id), 99, None), 2.0);
+        // Moving A after B ignores A's own position
+        assert_eq!(state.position_for_index(Some(parent.id), 1, Some(a.id)), 2.0);
+        // Empty parent
+        assert_eq!(state.position_for_index(Some(a.id), 0, None), 0.0);
+    }
+
+    #[test]
+    fn test_normalize_only_broken_groups() {
+        let parent = Node::new("Parent".to_string());
+        let parent_id = parent.id;
+        let mut roomy = Node::new("Roomy".to_string());
+        roomy.position = 0.5;
+        let a = Node::new_child(parent.id, -3.0, "A".to_string());
+        let mut b = Node::new_child(parent.id, 1.0, "B".to_string());
+        let mut c = Node::new_child(parent.id, 1.0, "C".to_string());
+        b.created_at = a.created_at + chrono::Duration::seconds(1);
+        c.created_at = a.created_at + chrono::Duration::seconds(2);
+        let mut state = DocumentState {
+            nodes: vec![parent, roomy, c, a, b],
+        };
+
+        // Duplicate and negative positions are repaired, tie broken by creation time
+        let ops = state.position_normalization_ops(false);
+        assert_eq!(ops.len(), 2); // B is already at index 1
+        for op in &ops {
+            op.apply(&mut state);
+        }
+        let order: Vec<_> = state
+            .sorted_children(Some(parent_id))
+            .iter()
+            .map(|n| (n.content.clone(), n.position))
+            .collect();
+        assert_eq!(
+            order,
+            vec![("A".to_string(), 0.0), ("B".to_string(), 1.0), ("C".to_string(), 2.0)]
+        );
+
+        // Negative positions from inserts at the top are fine
+        let mut top = Node::new("Top".to_string());
+        top.position = position_between(None, Some(0.0));
+        assert_eq!(top.position, -1.0);
+        state.nodes.push(top);
+
+        // The healthy fractional root group is only renumbered when forced
+        assert!(state.position_normalization_ops(false).is_empty());
+        assert_eq!(state.position_normalization_ops(true).len(), 3);
+    }
+
+    #[test]
+    fn test_compact_normalizes_positions() {
+        let (_tmp, doc_dir) = test_doc_dir();
+        let mut doc = Document::create(doc_dir.clone()).unwrap();
+        for content in ["One", "Two"] {
+            let op = create_op(None, 0.0, content.to_string());
+            doc.append_op(&op).unwrap();
+            op.apply(&mut doc.state);
+        }
+
+        doc.compact().unwrap();
+        let doc2 = Document::load(doc_dir).unwrap();
+        let positions: Vec<_> = doc2.state.sorted_children(None).iter().map(|n| n.position).collect();
+        assert_eq!(positions, vec![0.0, 1.0]);
+    }
 }
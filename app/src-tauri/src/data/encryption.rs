@@ -0,0 +1,212 @@
+//! Encryption of single nodes, for passwords and personal details living in
+//! otherwise shareable documents. An encrypted node's content is the
+//! ciphertext of its content and note together (the note is emptied), so
+//! state.json, new ops, exports and shares only carry ciphertext, and the
+//! search index leaves the node out. The plaintext is only returned by
+//! unlocking the node with its passphrase. Encrypting a node also rewrites
+//! the document's op files and snapshots so the text it held before is gone
+//! (see `Document::redact_node_ops`); copies elsewhere (exports, backups, ops
+//! another device hasn't synced yet) are out of reach.
+//!
+//! Keys are derived from the passphrase with Argon2id, with a new random
+//! salt each time a node is (re)encrypted, and the text is sealed with
+//! XChaCha20-Poly1305.
+
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::node::Node;
+use super::operations::{update_op, NodeChanges, Operation};
+
+/// Start of every encrypted content, with the format version
+const CIPHERTEXT_PREFIX: &str = "outline-enc:v1:";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// What an encrypted node hides
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeSecret {
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Derive key: {}", e))?;
+    Ok(key)
+}
+
+/// Encrypt a node's content and note with a passphrase
+pub fn encrypt_secret(secret: &NodeSecret, passphrase: &str) -> Result<String, String> {
+    if passphrase.is_empty() {
+        return Err("The passphrase can't be empty".to_string());
+    }
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = XChaCha20Poly1305::new(&derive_key(passphrase, &salt)?.into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let plaintext = serde_json::to_vec(secret).map_err(|e| format!("Serialize node: {}", e))?;
+    let sealed = cipher
+        .encrypt(&nonce, plaintext.as_slice())
+        .map_err(|_| "Encryption failed".to_string())?;
+
+    let mut bytes = Vec::with_capacity(SALT_LEN + NONCE_LEN + sealed.len());
+    bytes.extend_from_slice(&salt);
+    bytes.extend_from_slice(&nonce);
+    bytes.extend_from_slice(&sealed);
+    Ok(format!("{}{}", CIPHERTEXT_PREFIX, BASE64.encode(bytes)))
+}
+
+/// Decrypt what `encrypt_secret` made. A wrong passphrase is an error.
+pub fn decrypt_secret(ciphertext: &str, passphrase: &str) -> Result<NodeSecret, String> {
+    let bytes = ciphertext
+        .strip_prefix(CIPHERTEXT_PREFIX)
+        .and_then(|data| BASE64.decode(data).ok())
+        .filter(|bytes| bytes.len() > SALT_LEN + NONCE_LEN)
+        .ok_or("Not an encrypted node")?;
+    let (salt, rest) = bytes.split_at(SALT_LEN);
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+    let cipher = XChaCha20Poly1305::new(&derive_key(passphrase, salt)?.into());
+    let plaintext = cipher
+        .decrypt(XNonce::from_slice(nonce), sealed)
+        .map_err(|_| "Wrong passphrase".to_string())?;
+    serde_json::from_slice(&plaintext).map_err(|e| format!("Parse decrypted node: {}", e))
+}
+
+/// The plaintext of an encrypted node
+pub fn unlock_node(node: &Node, passphrase: &str) -> Result<NodeSecret, String> {
+    if !node.encrypted {
+        return Err(format!("Node {} isn't encrypted", node.id));
+    }
+    decrypt_secret(&node.content, passphrase)
+}
+
+/// Op storing `secret` as the node's encrypted content. Used to encrypt a
+/// node (with its current text) and to save edits to an encrypted one.
+pub fn encrypt_node_op(node: &Node, secret: &NodeSecret, passphrase: &str) -> Result<Operation, String> {
+    Ok(update_op(
+        node.id,
+        NodeChanges {
+            content: Some(encrypt_secret(secret, passphrase)?),
+            note: Some(String::new()),
+            encrypted: Some(true),
+            ..Default::default()
+        },
+    ))
+}
+
+/// Remove the plain text an op would give a node: a create's content, an
+/// update's content and note (unless the update stores them encrypted).
+/// Returns whether the op changed.
+pub(super) fn redact_plaintext(op: &mut Operation, node_id: Uuid) -> bool {
+    match op {
+        Operation::Create { id, content, .. } if *id == node_id && !content.is_empty() => {
+            content.clear();
+            true
+        }
+        Operation::Update { id, changes, .. }
+            if *id == node_id
+                && changes.encrypted != Some(true)
+                && (changes.content.is_some() || changes.note.is_some()) =>
+        {
+            changes.content = None;
+            changes.note = None;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Op turning an encrypted node back into plain text
+pub fn decrypt_node_op(node: &Node, passphrase: &str) -> Result<Operation, String> {
+    let secret = unlock_node(node, passphrase)?;
+    Ok(update_op(
+        node.id,
+        NodeChanges {
+            content: Some(secret.content),
+            note: Some(secret.note.unwrap_or_default()),
+            encrypted: Some(false),
+            ..Default::default()
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{create_op, Document, DocumentState};
+
+    #[test]
+    fn test_encrypt_node() {
+        let mut node = Node::new("Bank login".to_string());
+        node.note = Some("hunter2".to_string());
+        let mut state = DocumentState { nodes: vec![node.clone()] };
+        let secret = NodeSecret {
+            content: node.content.clone(),
+            note: node.note.clone(),
+        };
+
+        encrypt_node_op(&node, &secret, "correct horse").unwrap().apply(&mut state);
+        let encrypted = state.nodes[0].clone();
+        assert!(encrypted.encrypted);
+        assert!(encrypted.content.starts_with(CIPHERTEXT_PREFIX));
+        assert!(!encrypted.content.contains("hunter2"));
+        assert_eq!(encrypted.note.as_deref(), Some(""));
+
+        assert_eq!(unlock_node(&encrypted, "correct horse").unwrap(), secret);
+        assert_eq!(unlock_node(&encrypted, "wrong").unwrap_err(), "Wrong passphrase");
+        assert!(unlock_node(&node, "correct horse").is_err());
+
+        decrypt_node_op(&encrypted, "correct horse").unwrap().apply(&mut state);
+        assert!(!state.nodes[0].encrypted);
+        assert_eq!(state.nodes[0].content, "Bank login");
+        assert_eq!(state.nodes[0].note.as_deref(), Some("hunter2"));
+    }
+
+    #[test]
+    fn test_encrypted_node_keeps_no_plaintext() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut doc = Document::create(tmp.path().join(Uuid::now_v7().to_string())).unwrap();
+        let create = create_op(None, 0.0, "PIN 4321".to_string());
+        let id = create.node_id();
+        for op in [
+            create,
+            update_op(id, NodeChanges { note: Some("card ending 99".to_string()), ..Default::default() }),
+        ] {
+            doc.append_op(&op).unwrap();
+            op.apply(&mut doc.state);
+        }
+        doc.compact().unwrap();
+
+        let node = doc.state.nodes[0].clone();
+        let secret = NodeSecret {
+            content: node.content.clone(),
+            note: node.note.clone(),
+        };
+        let op = encrypt_node_op(&node, &secret, "pw").unwrap();
+        doc.append_op(&op).unwrap();
+        op.apply(&mut doc.state);
+
+        // A plain edit would put the text back on disk next to the flag
+        let edit = update_op(id, NodeChanges { content: Some("PIN 0000".to_string()), ..Default::default() });
+        assert!(doc.append_op(&edit).unwrap_err().contains("update_encrypted_node"));
+
+        doc.compact().unwrap();
+        assert_eq!(doc.redact_node_ops(id).unwrap(), 2);
+        for entry in std::fs::read_dir(&doc.dir).unwrap() {
+            let text = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            assert!(!text.contains("4321") && !text.contains("card ending"));
+        }
+        let reloaded = Document::load(doc.dir.clone()).unwrap();
+        assert_eq!(unlock_node(&reloaded.state.nodes[0], "pw").unwrap(), secret);
+    }
+}
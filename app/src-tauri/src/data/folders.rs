@@ -1,23 +1,51 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
-use std::path::PathBuf;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use uuid::Uuid;
 
-use super::document::data_dir;
+use super::document::{data_dir, device_name, op_files};
+
+/// Folder state as this app last read or wrote it, so the watcher can tell
+/// the app's own writes from changes synced in from other machines
+static KNOWN_FOLDERS: Mutex<Option<FolderState>> = Mutex::new(None);
 
 /// Folder structure for organizing documents
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Folder {
     pub id: String,
     pub name: String,
     pub position: i32,
     pub collapsed: bool,
+    /// Color label for the sidebar (same names as node colors)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Icon (an emoji or icon name) shown before the folder name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+}
+
+/// A pinned document, or a pinned node within a document
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Favorite {
+    pub document_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<String>,
+}
+
+/// A bookmarked node, kept for the whole workspace rather than per document
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Bookmark {
+    pub document_id: String,
+    pub node_id: String,
+    pub created_at: DateTime<Utc>,
 }
 
 /// Folder assignment: maps document_id -> folder_id (or null for root level)
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct FolderState {
     /// Ordered list of folders
     pub folders: Vec<Folder>,
@@ -26,6 +54,17 @@ pub struct FolderState {
     /// Order of documents within each folder (folder_id -> [doc_id, ...])
     /// Special key "__root__" for documents at root level
     pub document_order: HashMap<String, Vec<String>>,
+    /// Pinned documents and nodes, in the order they were pinned
+    #[serde(default)]
+    pub favorites: Vec<Favorite>,
+    /// Bookmarked nodes, in the order they were bookmarked
+    #[serde(default)]
+    pub bookmarks: Vec<Bookmark>,
+    /// Timestamp of the latest op applied from each machine. Ops at or before
+    /// it are already in this state, e.g. an op file that sync brought back
+    /// after it was folded into folders.json, and are skipped.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub applied_through: HashMap<String, DateTime<Utc>>,
 }
 
 impl FolderState {
@@ -34,6 +73,25 @@ impl FolderState {
             folders: Vec::new(),
             document_folders: HashMap::new(),
             document_order: HashMap::new(),
+            favorites: Vec::new(),
+            bookmarks: Vec::new(),
+            applied_through: HashMap::new(),
+        }
+    }
+
+    /// Pin a document (or node) if it isn't pinned, otherwise unpin it.
+    /// Returns whether it is now a favorite.
+    pub fn toggle_favorite(&mut self, document_id: &str, node_id: Option<&str>) -> bool {
+        let favorite = Favorite {
+            document_id: document_id.to_string(),
+            node_id: node_id.map(String::from),
+        };
+        if let Some(index) = self.favorites.iter().position(|f| *f == favorite) {
+            self.favorites.remove(index);
+            false
+        } else {
+            self.favorites.push(favorite);
+            true
         }
     }
 
@@ -44,21 +102,253 @@ impl FolderState {
     }
 }
 
+/// A change to the folder structure. Folder state is folders.json plus these
+/// ops, appended per machine to folders.{hostname}.jsonl so that two machines
+/// editing folders through file sync merge instead of overwriting each other.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum FolderOp {
+    CreateFolder {
+        folder: Folder,
+    },
+    /// Set the given fields; an empty color or icon clears it
+    UpdateFolder {
+        id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        collapsed: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        icon: Option<String>,
+    },
+    DeleteFolder {
+        id: String,
+    },
+    MoveDocument {
+        doc_id: String,
+        folder_id: Option<String>,
+        position: Option<i32>,
+    },
+    ReorderFolders {
+        folder_ids: Vec<String>,
+    },
+    SetFavorite {
+        document_id: String,
+        node_id: Option<String>,
+        pinned: bool,
+    },
+    AddBookmark {
+        bookmark: Bookmark,
+    },
+    /// Remove bookmarks from nodes of a document (unbookmarked or deleted)
+    RemoveBookmarks {
+        document_id: String,
+        node_ids: Vec<String>,
+    },
+}
+
+/// A folder op as written to a folders.{hostname}.jsonl file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderOpRecord {
+    pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub device: String,
+    #[serde(flatten)]
+    pub op: FolderOp,
+}
+
+/// Fold op files into folders.json once they hold this many ops
+const FOLDER_COMPACT_THRESHOLD: usize = 500;
+
+impl FolderState {
+    /// Apply a folder op. Ops are applied in timestamp order across machines,
+    /// so each one tolerates state another machine changed in the meantime
+    /// (e.g. a move into a folder that has since been deleted lands at root).
+    pub fn apply(&mut self, op: &FolderOp) {
+        match op {
+            FolderOp::CreateFolder { folder } => {
+                if !self.folders.iter().any(|f| f.id == folder.id) {
+                    self.folders.push(folder.clone());
+                    self.folders.sort_by_key(|f| f.position);
+                }
+            }
+            FolderOp::UpdateFolder { id, name, collapsed, color, icon } => {
+                if let Some(folder) = self.folders.iter_mut().find(|f| f.id == *id) {
+                    if let Some(n) = name {
+                        folder.name = n.clone();
+                    }
+                    if let Some(c) = collapsed {
+                        folder.collapsed = *c;
+                    }
+                    if let Some(c) = color {
+                        folder.color = Some(c.clone()).filter(|c| !c.is_empty());
+                    }
+                    if let Some(i) = icon {
+                        folder.icon = Some(i.clone()).filter(|i| !i.is_empty());
+                    }
+                }
+            }
+            FolderOp::DeleteFolder { id } => {
+                // Remove folder from list
+                self.folders.retain(|f| f.id != *id);
+
+                // Move documents from this folder to root level
+                let mut docs_in_folder: Vec<String> = self.document_order.remove(id).unwrap_or_default();
+                for (doc_id, folder_id) in &self.document_folders {
+                    if folder_id == id && !docs_in_folder.contains(doc_id) {
+                        docs_in_folder.push(doc_id.clone());
+                    }
+                }
+                self.document_folders.retain(|_, folder_id| folder_id != id);
+
+                let root_order = self.document_order.entry("__root__".to_string()).or_default();
+                for doc_id in docs_in_folder {
+                    if !root_order.contains(&doc_id) {
+                        root_order.push(doc_id);
+                    }
+                }
+            }
+            FolderOp::MoveDocument { doc_id, folder_id, position } => {
+                let folder_id = folder_id
+                    .as_deref()
+                    .filter(|fid| self.folders.iter().any(|f| f.id == *fid));
+
+                // Remove document from old location
+                self.document_folders.remove(doc_id);
+                for order in self.document_order.values_mut() {
+                    order.retain(|d| d != doc_id);
+                }
+
+                // Add document to new location
+                if let Some(fid) = folder_id {
+                    self.document_folders.insert(doc_id.clone(), fid.to_string());
+                }
+                let order = self
+                    .document_order
+                    .entry(folder_id.unwrap_or("__root__").to_string())
+                    .or_default();
+                match position {
+                    Some(pos) => order.insert((*pos.max(&0) as usize).min(order.len()), doc_id.clone()),
+                    None => order.push(doc_id.clone()),
+                }
+            }
+            FolderOp::ReorderFolders { folder_ids } => {
+                for (i, folder_id) in folder_ids.iter().enumerate() {
+                    if let Some(folder) = self.folders.iter_mut().find(|f| f.id == *folder_id) {
+                        folder.position = i as i32;
+                    }
+                }
+                self.folders.sort_by_key(|f| f.position);
+            }
+            FolderOp::SetFavorite { document_id, node_id, pinned } => {
+                let favorite = Favorite {
+                    document_id: document_id.clone(),
+                    node_id: node_id.clone(),
+                };
+                let index = self.favorites.iter().position(|f| *f == favorite);
+                match (index, pinned) {
+                    (None, true) => self.favorites.push(favorite),
+                    (Some(i), false) => {
+                        self.favorites.remove(i);
+                    }
+                    _ => {}
+                }
+            }
+            FolderOp::AddBookmark { bookmark } => {
+                let exists = self
+                    .bookmarks
+                    .iter()
+                    .any(|b| b.document_id == bookmark.document_id && b.node_id == bookmark.node_id);
+                if !exists {
+                    self.bookmarks.push(bookmark.clone());
+                }
+            }
+            FolderOp::RemoveBookmarks { document_id, node_ids } => {
+                self.bookmarks
+                    .retain(|b| b.document_id != *document_id || !node_ids.contains(&b.node_id));
+            }
+        }
+    }
+}
+
 /// Get the folders.json path
 pub fn folders_path() -> PathBuf {
     data_dir().join("folders.json")
 }
 
-/// Load folder state from disk
-pub fn load_folders() -> Result<FolderState, String> {
-    let path = folders_path();
-    if !path.exists() {
-        return Ok(FolderState::new());
+/// Load folders.json from a data directory and apply every machine's op file
+/// (skipping ops folders.json already holds), returning the merged state and
+/// the number of ops applied
+fn read_folder_state(dir: &Path) -> Result<(FolderState, usize), String> {
+    let path = dir.join("folders.json");
+    let mut state = if path.exists() {
+        let file = File::open(&path).map_err(|e| format!("Open folders.json: {}", e))?;
+        let reader = BufReader::new(file);
+        serde_json::from_reader(reader).map_err(|e| format!("Parse folders.json: {}", e))?
+    } else {
+        FolderState::new()
+    };
+
+    let mut records = Vec::new();
+    for path in op_files(dir, "folders.")? {
+        let file = File::open(&path).map_err(|e| format!("Open {:?}: {}", path, e))?;
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("Read line: {}", e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<FolderOpRecord>(&line) {
+                Ok(record) => records.push(record),
+                // A partially synced last line shouldn't hide every folder
+                Err(e) => log::warn!("Skipping folder op in {:?}: {}", path, e),
+            }
+        }
     }
 
-    let file = File::open(&path).map_err(|e| format!("Open folders.json: {}", e))?;
-    let reader = BufReader::new(file);
-    serde_json::from_reader(reader).map_err(|e| format!("Parse folders.json: {}", e))
+    records.retain(|r| {
+        state
+            .applied_through
+            .get(&r.device)
+            .map_or(true, |through| r.timestamp > *through)
+    });
+    records.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.device.cmp(&b.device)));
+    for record in &records {
+        state.apply(&record.op);
+        state.applied_through.insert(record.device.clone(), record.timestamp);
+    }
+    Ok((state, records.len()))
+}
+
+fn remember_folders(state: &FolderState) {
+    *KNOWN_FOLDERS.lock().unwrap() = Some(state.clone());
+}
+
+/// Load folder state from disk (folders.json plus all pending folder ops)
+pub fn load_folders() -> Result<FolderState, String> {
+    let (state, _) = read_folder_state(&data_dir())?;
+    remember_folders(&state);
+    Ok(state)
+}
+
+/// Whether the folder files now hold a different state from the one this app
+/// last read or wrote, i.e. another machine changed them. The new state is
+/// remembered, so each change is reported once.
+pub fn folders_changed_on_disk() -> bool {
+    let state = match read_folder_state(&data_dir()) {
+        Ok((state, _)) => state,
+        Err(e) => {
+            log::warn!("Failed to read folders: {}", e);
+            return false;
+        }
+    };
+    let mut known = KNOWN_FOLDERS.lock().unwrap();
+    if known.as_ref() == Some(&state) {
+        return false;
+    }
+    *known = Some(state);
+    true
 }
 
 /// Save folder state to disk
@@ -66,12 +356,44 @@ pub fn save_folders(state: &FolderState) -> Result<(), String> {
     let path = folders_path();
     let file = File::create(&path).map_err(|e| format!("Create folders.json: {}", e))?;
     let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, state).map_err(|e| format!("Write folders.json: {}", e))
+    serde_json::to_writer_pretty(writer, state).map_err(|e| format!("Write folders.json: {}", e))?;
+    remember_folders(state);
+    Ok(())
+}
+
+/// Append a folder op to this machine's op file, returning the merged state.
+/// Once enough ops pile up they are folded into folders.json.
+fn append_folder_op(op: FolderOp) -> Result<FolderState, String> {
+    let dir = data_dir();
+    let record = FolderOpRecord {
+        timestamp: Utc::now(),
+        device: device_name(),
+        op,
+    };
+    let json = serde_json::to_string(&record).map_err(|e| format!("Serialize folder op: {}", e))?;
+
+    let path = dir.join(format!("folders.{}.jsonl", record.device));
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Open {:?}: {}", path, e))?;
+    writeln!(file, "{}", json).map_err(|e| format!("Write folder op: {}", e))?;
+
+    let (state, op_count) = read_folder_state(&dir)?;
+    if op_count >= FOLDER_COMPACT_THRESHOLD {
+        save_folders(&state)?;
+        for path in op_files(&dir, "folders.")? {
+            fs::remove_file(&path).map_err(|e| format!("Remove {:?}: {}", path, e))?;
+        }
+    }
+    remember_folders(&state);
+    Ok(state)
 }
 
 /// Create a new folder
 pub fn create_folder(name: &str) -> Result<Folder, String> {
-    let mut state = load_folders()?;
+    let state = load_folders()?;
 
     // Calculate next position
     let position = state.folders.iter().map(|f| f.position).max().unwrap_or(-1) + 1;
@@ -81,11 +403,11 @@ pub fn create_folder(name: &str) -> Result<Folder, String> {
         name: name.to_string(),
         position,
         collapsed: false,
+        color: None,
+        icon: None,
     };
 
-    state.folders.push(folder.clone());
-    save_folders(&state)?;
-
+    append_folder_op(FolderOp::CreateFolder { folder: folder.clone() })?;
     Ok(folder)
 }
 
@@ -102,99 +424,103 @@ pub fn get_or_create_folder(name: &str) -> Result<Folder, String> {
     create_folder(name)
 }
 
-/// Update a folder's properties
-pub fn update_folder(id: &str, name: Option<&str>, collapsed: Option<bool>) -> Result<Folder, String> {
-    let mut state = load_folders()?;
-
-    let folder = state.folders.iter_mut().find(|f| f.id == id)
-        .ok_or_else(|| format!("Folder not found: {}", id))?;
-
-    if let Some(n) = name {
-        folder.name = n.to_string();
+/// Update a folder's properties. An empty color or icon clears it.
+pub fn update_folder(
+    id: &str,
+    name: Option<&str>,
+    collapsed: Option<bool>,
+    color: Option<&str>,
+    icon: Option<&str>,
+) -> Result<Folder, String> {
+    if !load_folders()?.folders.iter().any(|f| f.id == id) {
+        return Err(format!("Folder not found: {}", id));
     }
-    if let Some(c) = collapsed {
-        folder.collapsed = c;
-    }
-
-    let result = folder.clone();
-    save_folders(&state)?;
 
-    Ok(result)
+    let state = append_folder_op(FolderOp::UpdateFolder {
+        id: id.to_string(),
+        name: name.map(String::from),
+        collapsed,
+        color: color.map(String::from),
+        icon: icon.map(String::from),
+    })?;
+    state
+        .folders
+        .into_iter()
+        .find(|f| f.id == id)
+        .ok_or_else(|| format!("Folder not found: {}", id))
 }
 
 /// Delete a folder (moves documents to root level)
 pub fn delete_folder(id: &str) -> Result<(), String> {
-    let mut state = load_folders()?;
-
-    // Remove folder from list
-    state.folders.retain(|f| f.id != id);
-
-    // Move documents from this folder to root level
-    let docs_in_folder: Vec<String> = state.document_folders.iter()
-        .filter(|(_, folder_id)| *folder_id == id)
-        .map(|(doc_id, _)| doc_id.clone())
-        .collect();
-
-    for doc_id in &docs_in_folder {
-        state.document_folders.remove(doc_id);
-    }
-
-    // Clean up document_order
-    state.document_order.remove(id);
-
-    // Add documents to root level order
-    let root_order = state.document_order.entry("__root__".to_string()).or_default();
-    root_order.extend(docs_in_folder);
-
-    save_folders(&state)?;
+    append_folder_op(FolderOp::DeleteFolder { id: id.to_string() })?;
     Ok(())
 }
 
 /// Move a document to a folder (or root level if folder_id is None)
 pub fn move_document_to_folder(doc_id: &str, folder_id: Option<&str>, position: Option<i32>) -> Result<(), String> {
-    let mut state = load_folders()?;
-
-    // Remove document from old location
-    let old_folder = state.document_folders.remove(doc_id);
-    let old_folder_key = old_folder.as_deref().unwrap_or("__root__");
-    if let Some(order) = state.document_order.get_mut(old_folder_key) {
-        order.retain(|d| d != doc_id);
-    }
-
-    // Add document to new location
-    let new_folder_key = folder_id.unwrap_or("__root__");
-    if let Some(fid) = folder_id {
-        state.document_folders.insert(doc_id.to_string(), fid.to_string());
-    }
-
-    // Update document order
-    let order = state.document_order.entry(new_folder_key.to_string()).or_default();
-    if let Some(pos) = position {
-        let idx = (pos as usize).min(order.len());
-        order.insert(idx, doc_id.to_string());
-    } else {
-        order.push(doc_id.to_string());
-    }
-
-    save_folders(&state)?;
+    append_folder_op(FolderOp::MoveDocument {
+        doc_id: doc_id.to_string(),
+        folder_id: folder_id.map(String::from),
+        position,
+    })?;
     Ok(())
 }
 
-/// Reorder folders
-pub fn reorder_folders(folder_ids: Vec<String>) -> Result<(), String> {
+/// Toggle a document or node favorite, returning whether it is now pinned
+pub fn toggle_favorite(document_id: &str, node_id: Option<&str>) -> Result<bool, String> {
     let mut state = load_folders()?;
+    let pinned = state.toggle_favorite(document_id, node_id);
+    append_folder_op(FolderOp::SetFavorite {
+        document_id: document_id.to_string(),
+        node_id: node_id.map(String::from),
+        pinned,
+    })?;
+    Ok(pinned)
+}
 
-    // Update positions based on new order
-    for (i, folder_id) in folder_ids.iter().enumerate() {
-        if let Some(folder) = state.folders.iter_mut().find(|f| f.id == *folder_id) {
-            folder.position = i as i32;
+/// Bookmark a node, or remove its bookmark. Returns whether it is now bookmarked.
+pub fn bookmark_node(document_id: &str, node_id: &str, bookmarked: bool) -> Result<bool, String> {
+    let op = if bookmarked {
+        FolderOp::AddBookmark {
+            bookmark: Bookmark {
+                document_id: document_id.to_string(),
+                node_id: node_id.to_string(),
+                created_at: Utc::now(),
+            },
         }
-    }
+    } else {
+        FolderOp::RemoveBookmarks {
+            document_id: document_id.to_string(),
+            node_ids: vec![node_id.to_string()],
+        }
+    };
+    append_folder_op(op)?;
+    Ok(bookmarked)
+}
 
-    // Sort folders by new position
-    state.folders.sort_by_key(|f| f.position);
+/// Drop the bookmarks of nodes that were deleted from a document. Returns
+/// how many were removed; nothing is written if none were bookmarked.
+pub fn remove_bookmarks(document_id: &str, node_ids: &[String]) -> Result<usize, String> {
+    let bookmarked: Vec<String> = load_folders()?
+        .bookmarks
+        .into_iter()
+        .filter(|b| b.document_id == document_id && node_ids.contains(&b.node_id))
+        .map(|b| b.node_id)
+        .collect();
+    if bookmarked.is_empty() {
+        return Ok(0);
+    }
+    let count = bookmarked.len();
+    append_folder_op(FolderOp::RemoveBookmarks {
+        document_id: document_id.to_string(),
+        node_ids: bookmarked,
+    })?;
+    Ok(count)
+}
 
-    save_folders(&state)?;
+/// Reorder folders
+pub fn reorder_folders(folder_ids: Vec<String>) -> Result<(), String> {
+    append_folder_op(FolderOp::ReorderFolders { folder_ids })?;
     Ok(())
 }
 
@@ -202,7 +528,6 @@ pub fn reorder_folders(folder_ids: Vec<String>) -> Result<(), String> {
 mod tests {
     use super::*;
     use std::env;
-    use std::sync::Mutex;
     use tempfile::TempDir;
 
     // Mutex to ensure tests run serially (they share global state via env var)
@@ -215,6 +540,147 @@ mod tests {
         tmp
     }
 
+    #[test]
+    fn test_toggle_favorite() {
+        let mut state = FolderState::new();
+        assert!(state.toggle_favorite("doc", None));
+        assert!(state.toggle_favorite("doc", Some("node")));
+        assert_eq!(state.favorites.len(), 2);
+
+        // Unpinning the document leaves the node pinned
+        assert!(!state.toggle_favorite("doc", None));
+        assert_eq!(
+            state.favorites,
+            vec![Favorite {
+                document_id: "doc".to_string(),
+                node_id: Some("node".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_bookmark_ops() {
+        let bookmark = |node_id: &str| Bookmark {
+            document_id: "doc".to_string(),
+            node_id: node_id.to_string(),
+            created_at: Utc::now(),
+        };
+        let mut state = FolderState::new();
+        state.apply(&FolderOp::AddBookmark { bookmark: bookmark("a") });
+        state.apply(&FolderOp::AddBookmark { bookmark: bookmark("b") });
+        state.apply(&FolderOp::AddBookmark { bookmark: bookmark("a") });
+        assert_eq!(state.bookmarks.len(), 2);
+
+        state.apply(&FolderOp::RemoveBookmarks {
+            document_id: "other".to_string(),
+            node_ids: vec!["a".to_string()],
+        });
+        state.apply(&FolderOp::RemoveBookmarks {
+            document_id: "doc".to_string(),
+            node_ids: vec!["a".to_string(), "c".to_string()],
+        });
+        let remaining: Vec<&str> = state.bookmarks.iter().map(|b| b.node_id.as_str()).collect();
+        assert_eq!(remaining, vec!["b"]);
+    }
+
+    fn write_ops(dir: &Path, device: &str, ops: &[(&str, FolderOp)]) {
+        let lines: Vec<String> = ops
+            .iter()
+            .map(|(timestamp, op)| {
+                serde_json::to_string(&FolderOpRecord {
+                    timestamp: timestamp.parse().unwrap(),
+                    device: device.to_string(),
+                    op: op.clone(),
+                })
+                .unwrap()
+            })
+            .collect();
+        fs::write(dir.join(format!("folders.{}.jsonl", device)), lines.join("\n")).unwrap();
+    }
+
+    #[test]
+    fn test_folder_ops_merge_across_machines() {
+        let tmp = TempDir::new().unwrap();
+        let folder = |id: &str, name: &str, position: i32| Folder {
+            id: id.to_string(),
+            name: name.to_string(),
+            position,
+            collapsed: false,
+            color: None,
+            icon: None,
+        };
+
+        // Existing folders.json is the base state
+        let mut base = FolderState::new();
+        base.folders.push(folder("work", "Work", 0));
+        fs::write(tmp.path().join("folders.json"), serde_json::to_string(&base).unwrap()).unwrap();
+
+        write_ops(tmp.path(), "laptop", &[
+            ("2024-01-01T10:00:00Z", FolderOp::CreateFolder { folder: folder("home", "Home", 1) }),
+            ("2024-01-01T10:02:00Z", FolderOp::MoveDocument {
+                doc_id: "doc1".to_string(),
+                folder_id: Some("home".to_string()),
+                position: None,
+            }),
+        ]);
+        write_ops(tmp.path(), "desktop", &[
+            ("2024-01-01T10:01:00Z", FolderOp::UpdateFolder {
+                id: "work".to_string(),
+                name: Some("Job".to_string()),
+                collapsed: None,
+                color: None,
+                icon: None,
+            }),
+            ("2024-01-01T10:03:00Z", FolderOp::DeleteFolder { id: "home".to_string() }),
+        ]);
+
+        let (state, op_count) = read_folder_state(tmp.path()).unwrap();
+        assert_eq!(op_count, 4);
+        let names: Vec<&str> = state.folders.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["Job"]);
+        // The folder was deleted after the move, so the document is back at root
+        assert!(state.document_folders.is_empty());
+        assert_eq!(state.document_order["__root__"], vec!["doc1"]);
+    }
+
+    #[test]
+    fn test_compacted_ops_are_not_replayed() {
+        let tmp = TempDir::new().unwrap();
+        let folder = Folder {
+            id: "old".to_string(),
+            name: "Old".to_string(),
+            position: 0,
+            collapsed: false,
+            color: None,
+            icon: None,
+        };
+        let created = ("2024-01-01T10:00:00Z", FolderOp::CreateFolder { folder });
+        let deleted = ("2024-01-01T10:01:00Z", FolderOp::DeleteFolder { id: "old".to_string() });
+        write_ops(tmp.path(), "laptop", std::slice::from_ref(&created));
+        write_ops(tmp.path(), "desktop", &[deleted]);
+
+        // Compact the way append_folder_op does
+        let (state, _) = read_folder_state(tmp.path()).unwrap();
+        assert!(state.folders.is_empty());
+        fs::write(tmp.path().join("folders.json"), serde_json::to_string(&state).unwrap()).unwrap();
+        for path in op_files(tmp.path(), "folders.").unwrap() {
+            fs::remove_file(path).unwrap();
+        }
+
+        // The laptop hadn't seen the deletion yet and appended to its old file
+        let moved = FolderOp::MoveDocument {
+            doc_id: "doc1".to_string(),
+            folder_id: None,
+            position: None,
+        };
+        write_ops(tmp.path(), "laptop", &[created, ("2024-01-01T10:02:00Z", moved)]);
+
+        let (state, op_count) = read_folder_state(tmp.path()).unwrap();
+        assert_eq!(op_count, 1);
+        assert!(state.folders.is_empty());
+        assert_eq!(state.document_order["__root__"], vec!["doc1"]);
+    }
+
     #[test]
     fn test_folder_collapsed_state_persistence() {
         let _lock = TEST_MUTEX.lock().unwrap();
@@ -225,7 +691,7 @@ mod tests {
         assert!(!folder.collapsed, "New folder should not be collapsed");
 
         // Collapse the folder
-        let updated = update_folder(&folder.id, None, Some(true)).expect("Should update folder");
+        let updated = update_folder(&folder.id, None, Some(true), None, None).expect("Should update folder");
         assert!(updated.collapsed, "Folder should be collapsed after update");
 
         // Reload folders from disk
@@ -235,6 +701,30 @@ mod tests {
         assert!(loaded_folder.collapsed, "Collapsed state should be persisted");
     }
 
+    #[test]
+    fn test_only_outside_folder_changes_are_reported() {
+        let _lock = TEST_MUTEX.lock().unwrap();
+        let tmp = setup_test_data_dir();
+
+        // The app's own writes
+        let folder = create_folder("Mine").unwrap();
+        update_folder(&folder.id, None, Some(true), None, None).unwrap();
+        assert!(!folders_changed_on_disk());
+
+        // Another machine's ops arrive through sync
+        let folder = Folder {
+            id: "synced".to_string(),
+            name: "Synced".to_string(),
+            position: 99,
+            collapsed: false,
+            color: None,
+            icon: None,
+        };
+        write_ops(tmp.path(), "elsewhere", &[("2024-01-01T10:00:00Z", FolderOp::CreateFolder { folder })]);
+        assert!(folders_changed_on_disk());
+        assert!(!folders_changed_on_disk());
+    }
+
     #[test]
     fn test_folder_collapsed_state_toggle() {
         let _lock = TEST_MUTEX.lock().unwrap();
@@ -242,7 +732,7 @@ mod tests {
 
         // Create and collapse a folder
         let folder = create_folder("Toggle Test").expect("Should create folder");
-        update_folder(&folder.id, None, Some(true)).expect("Should collapse folder");
+        update_folder(&folder.id, None, Some(true), None, None).expect("Should collapse folder");
 
         // Verify collapsed
         let state1 = load_folders().expect("Should load folders");
@@ -251,7 +741,7 @@ mod tests {
         assert!(folder1.collapsed, "Should be collapsed");
 
         // Expand the folder
-        update_folder(&folder.id, None, Some(false)).expect("Should expand folder");
+        update_folder(&folder.id, None, Some(false), None, None).expect("Should expand folder");
 
         // Verify expanded
         let state2 = load_folders().expect("Should load folders");
@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+use uuid::Uuid;
+
+use super::node::{Node, NodeType};
+
+/// Build a synthetic outline of `node_count` nodes nested at most `depth`
+/// levels deep, for performance testing. Every parent gets the same number
+/// of children, and content cycles through plain text, inline formatting,
+/// tags, dates, wiki-links and checkboxes so every code path sees some work.
+pub fn generate_test_nodes(node_count: usize, depth: usize) -> Vec<Node> {
+    let depth = depth.max(1);
+    let fanout = ((node_count as f64).powf(1.0 / depth as f64).ceil() as usize).max(2);
+
+    let mut nodes: Vec<Node> = Vec::with_capacity(node_count);
+    // (parent, level of its children)
+    let mut parents: VecDeque<(Option<Uuid>, usize)> = VecDeque::new();
+    while nodes.len() < node_count {
+        let (parent_id, level) = parents.pop_front().unwrap_or((None, 1));
+        for position in 0..fanout {
+            if nodes.len() == node_count {
+                break;
+            }
+            let node = test_node(nodes.len(), parent_id, position as f64, nodes.last());
+            if level < depth {
+                parents.push_back((Some(node.id), level + 1));
+            }
+            nodes.push(node);
+        }
+    }
+    nodes
+}
+
+fn test_node(i: usize, parent_id: Option<Uuid>, position: f64, previous: Option<&Node>) -> Node {
+    let content = match i % 8 {
+        0 => format!("Item {} with some ordinary text to index", i),
+        1 => format!("<strong>Bold</strong> and <em>italic</em> item {}", i),
+        2 => format!("Tagged item {} #project{} #area{}", i, i % 20, i % 7),
+        3 => format!("Call about invoice {} <code>INV-{}</code>", i, i),
+        4 => match previous {
+            Some(prev) => format!(
+                r#"See <span class="wiki-link" data-node-id="{}">item {}</span>"#,
+                prev.id,
+                i - 1
+            ),
+            None => format!("Item {}", i),
+        },
+        5 => format!("Task {} to get done", i),
+        6 => format!("Quarterly review {} @due", i),
+        _ => format!("Note-worthy item {}", i),
+    };
+
+    let mut node = match parent_id {
+        Some(parent_id) => Node::new_child(parent_id, position, content),
+        None => Node {
+            position,
+            ..Node::new(content)
+        },
+    };
+    match i % 8 {
+        2 => node.tags = vec![format!("project{}", i % 20), format!("area{}", i % 7)],
+        5 => {
+            node.node_type = NodeType::Checkbox;
+            node.is_checked = i % 3 == 0;
+        }
+        6 => node.date = Some(format!("2024-{:02}-{:02}", i % 12 + 1, i % 28 + 1)),
+        7 => node.note = Some(format!("A note on item {}", i)),
+        _ => {}
+    }
+    node
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_generate_test_nodes() {
+        let nodes = generate_test_nodes(1000, 4);
+        assert_eq!(nodes.len(), 1000);
+
+        let parents: HashMap<Uuid, Option<Uuid>> = nodes.iter().map(|n| (n.id, n.parent_id)).collect();
+        let max_depth = nodes
+            .iter()
+            .map(|n| {
+                let mut depth = 1;
+                let mut parent = n.parent_id;
+                while let Some(id) = parent {
+                    depth += 1;
+                    parent = parents[&id];
+                }
+                depth
+            })
+            .max();
+        assert_eq!(max_depth, Some(4));
+        assert!(nodes.iter().any(|n| n.node_type == NodeType::Checkbox));
+    }
+}
@@ -0,0 +1,94 @@
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use super::Document;
+
+/// Free space below which the data directory is reported as low on space
+pub const LOW_DISK_SPACE_BYTES: u64 = 100 * 1024 * 1024;
+
+/// A document that failed to load
+#[derive(Debug, Clone, Serialize)]
+pub struct BrokenDocument {
+    pub document_id: String,
+    pub error: String,
+}
+
+/// Health of the data directory and the documents in it
+#[derive(Debug, Clone, Serialize)]
+pub struct DataDirHealth {
+    pub path: String,
+    pub writable: bool,
+    /// Bytes available to the user on the data directory's disk, if known
+    pub free_bytes: Option<u64>,
+    pub low_disk_space: bool,
+    pub document_count: usize,
+    pub broken_documents: Vec<BrokenDocument>,
+}
+
+/// Check that `dir` can be written to, how much space is left on its disk,
+/// and which documents under it fail to load
+pub fn check_data_dir(dir: &Path) -> DataDirHealth {
+    let free_bytes = fs2::available_space(dir).ok();
+
+    let mut document_count = 0;
+    let mut broken_documents = Vec::new();
+    if let Ok(entries) = fs::read_dir(dir.join("documents")) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            document_count += 1;
+            if let Err(error) = Document::load(path.clone()) {
+                broken_documents.push(BrokenDocument {
+                    document_id: entry.file_name().to_string_lossy().to_string(),
+                    error,
+                });
+            }
+        }
+    }
+
+    DataDirHealth {
+        path: dir.to_string_lossy().to_string(),
+        writable: is_writable(dir),
+        free_bytes,
+        low_disk_space: free_bytes.is_some_and(|free| free < LOW_DISK_SPACE_BYTES),
+        document_count,
+        broken_documents,
+    }
+}
+
+/// Try creating and removing a file, since permissions alone don't reveal
+/// read-only mounts or sync folders
+fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(".outline-write-test");
+    let writable = fs::write(&probe, b"ok").is_ok();
+    let _ = fs::remove_file(&probe);
+    writable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_check_data_dir() {
+        let tmp = TempDir::new().unwrap();
+        let docs = tmp.path().join("documents");
+        let bad_id = Uuid::now_v7().to_string();
+        Document::create(docs.join(Uuid::now_v7().to_string())).unwrap();
+        fs::create_dir_all(docs.join(&bad_id)).unwrap();
+        fs::write(docs.join(&bad_id).join("state.json"), "{ not json").unwrap();
+
+        let health = check_data_dir(tmp.path());
+        assert!(health.writable);
+        assert!(health.free_bytes.is_some());
+        assert_eq!(health.document_count, 2);
+        assert_eq!(health.broken_documents.len(), 1);
+        assert_eq!(health.broken_documents[0].document_id, bad_id);
+        assert!(!tmp.path().join(".outline-write-test").exists());
+    }
+}
@@ -0,0 +1,303 @@
+//! Node content is a small HTML fragment. Text pasted from browsers brings
+//! styles, classes and scripts along; `sanitize_html` keeps only the markup
+//! the editor produces before content is saved and indexed. `strip_html`
+//! turns content into plain text for search, OPML and titles. Notes may be
+//! Markdown, rendered with `render_markdown`.
+
+use ammonia::Builder;
+use pulldown_cmark::{html, Event, Options, Parser, TagEnd};
+use regex::Regex;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+/// Markup the editor produces (its getHTML() wraps content in a paragraph)
+const ALLOWED_TAGS: &[&str] = &[
+    "a", "b", "br", "code", "del", "em", "i", "mark", "p", "s", "span", "strike", "strong", "sub", "sup", "u",
+];
+
+fn sanitizer() -> &'static Builder<'static> {
+    static SANITIZER: OnceLock<Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = Builder::empty();
+        builder
+            .tags(ALLOWED_TAGS.iter().copied().collect())
+            .clean_content_tags(HashSet::from(["script", "style", "template"]))
+            // Wiki links, hashtags, mentions and dates are data-* spans
+            .generic_attribute_prefixes(HashSet::from(["data-"]))
+            .generic_attributes(HashSet::from(["class"]))
+            // Auto and Markdown links open in a new window
+            .add_tag_attributes("a", ["href", "title", "target", "rel"])
+            .link_rel(None);
+        builder
+    })
+}
+
+/// Clean pasted HTML down to the editor's markup: unknown tags are unwrapped,
+/// scripts and styles dropped, attributes other than data-*, class and link
+/// attributes removed, and `<span>`s left with no attributes unwrapped. Plain
+/// text passes through unchanged.
+pub fn sanitize_html(content: &str) -> String {
+    if !content.contains('<') {
+        return content.to_string();
+    }
+    unwrap_bare_spans(&sanitizer().clean(content).to_string())
+}
+
+/// Drop `<span>` tags without attributes, keeping their contents. The input
+/// is ammonia output, so spans are balanced.
+fn unwrap_bare_spans(html: &str) -> String {
+    static SPAN_TAG: OnceLock<Regex> = OnceLock::new();
+    let span_tag = SPAN_TAG.get_or_init(|| Regex::new(r"<(/?)span(\s[^>]*)?>").unwrap());
+
+    let mut result = String::with_capacity(html.len());
+    let mut open_bare = Vec::new();
+    let mut last = 0;
+    for caps in span_tag.captures_iter(html) {
+        let tag = caps.get(0).unwrap();
+        result.push_str(&html[last..tag.start()]);
+        last = tag.end();
+        let keep = if caps[1].is_empty() {
+            let bare = caps.get(2).map_or(true, |attrs| attrs.as_str().trim().is_empty());
+            open_bare.push(bare);
+            !bare
+        } else {
+            !open_bare.pop().unwrap_or(false)
+        };
+        if keep {
+            result.push_str(tag.as_str());
+        }
+    }
+    result.push_str(&html[last..]);
+    result
+}
+
+/// Named entities decoded besides the numeric forms
+const NAMED_ENTITIES: &[(&str, &str)] = &[
+    ("amp", "&"),
+    ("lt", "<"),
+    ("gt", ">"),
+    ("quot", "\""),
+    ("apos", "'"),
+    ("nbsp", " "),
+    ("ndash", "\u{2013}"),
+    ("mdash", "\u{2014}"),
+    ("hellip", "\u{2026}"),
+    ("lsquo", "\u{2018}"),
+    ("rsquo", "\u{2019}"),
+    ("ldquo", "\u{201C}"),
+    ("rdquo", "\u{201D}"),
+    ("bull", "\u{2022}"),
+    ("middot", "\u{00B7}"),
+    ("copy", "\u{00A9}"),
+    ("reg", "\u{00AE}"),
+    ("trade", "\u{2122}"),
+    ("deg", "\u{00B0}"),
+    ("times", "\u{00D7}"),
+    ("euro", "\u{20AC}"),
+];
+
+/// Longest entity name looked for after `&`
+const MAX_ENTITY_LEN: usize = 10;
+
+/// Decode HTML entities in text in one pass, so `&amp;lt;` becomes `&lt;`.
+/// Handles decimal and hex numeric references and common named ones;
+/// anything else is left as written.
+pub fn decode_entities(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        result.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..]
+            .char_indices()
+            .take(MAX_ENTITY_LEN + 1)
+            .find(|(_, c)| *c == ';')
+            .and_then(|(end, _)| decode_entity(&rest[1..1 + end]).map(|c| (c, end + 2)));
+        match decoded {
+            Some((decoded, len)) => {
+                result.push_str(&decoded);
+                rest = &rest[len..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+fn decode_entity(name: &str) -> Option<String> {
+    if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code).filter(|c| *c != '\0').map(String::from);
+    }
+    NAMED_ENTITIES
+        .iter()
+        .find(|(entity, _)| *entity == name)
+        .map(|(_, text)| text.to_string())
+}
+
+/// Escape text for use between HTML tags
+pub fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Convert HTML content to plain text: tags (including ones with `<` or `>`
+/// inside quoted attributes) and comments are removed, script and style
+/// contents dropped, and entities decoded. A `<` that can't start a tag is
+/// kept as text. Whitespace is left as it is.
+pub fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(lt) = rest.find('<') {
+        text.push_str(&rest[..lt]);
+        rest = &rest[lt..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let starts_tag = rest[1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!' || c == '?');
+        if !starts_tag {
+            text.push('<');
+            rest = &rest[1..];
+            continue;
+        }
+
+        let tag_len = tag_length(rest);
+        let tag = &rest[..tag_len];
+        rest = &rest[tag_len..];
+
+        let name: String = tag[1..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if (name == "script" || name == "style") && !tag.ends_with("/>") {
+            let close = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&close) {
+                Some(start) => {
+                    let after = &rest[start..];
+                    &after[tag_length(after)..]
+                }
+                None => "",
+            };
+        }
+    }
+    text.push_str(rest);
+    decode_entities(&text)
+}
+
+/// Length of the tag at the start of `html` up to and including its `>`,
+/// skipping quoted attribute values (the rest of the input if unclosed)
+fn tag_length(html: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in html.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return i + 1,
+            _ => {}
+        }
+    }
+    html.len()
+}
+
+fn markdown_options() -> Options {
+    Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES | Options::ENABLE_TASKLISTS
+}
+
+/// Render a Markdown note to HTML. Raw HTML in the note is sanitized.
+pub fn render_markdown(markdown: &str) -> String {
+    let mut rendered = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut rendered, Parser::new_ext(markdown, markdown_options()));
+    ammonia::clean(&rendered)
+}
+
+/// Plain text of a Markdown note (for indexing): markup is dropped and
+/// blocks are separated by line breaks
+pub fn markdown_to_text(markdown: &str) -> String {
+    let mut text = String::with_capacity(markdown.len());
+    for event in Parser::new_ext(markdown, markdown_options()) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::Html(h) | Event::InlineHtml(h) => text.push_str(&strip_html(&h)),
+            Event::SoftBreak | Event::HardBreak => text.push('\n'),
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item | TagEnd::CodeBlock)
+                if !text.is_empty() && !text.ends_with('\n') =>
+            {
+                text.push('\n')
+            }
+            _ => {}
+        }
+    }
+    text.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_html() {
+        let pasted = concat!(
+            r#"<meta charset="utf-8"><span style="color: red; font-family: Arial"><b style="font-weight:700">Bold</b> "#,
+            r#"<span><span>plain</span></span></span><script>alert(1)</script>"#,
+            r#"<a href="https://example.com" onclick="x()" target="_blank">link</a>"#,
+            r#"<div class="para">block</div>"#,
+        );
+        assert_eq!(
+            sanitize_html(pasted),
+            r#"<b>Bold</b> plain<a href="https://example.com" target="_blank">link</a>block"#
+        );
+
+        // The editor's own markup survives
+        let wiki = r#"<span data-wiki-link="" data-node-id="abc" class="wiki-link">Target</span> and <mark>hi</mark>"#;
+        assert_eq!(sanitize_html(wiki), wiki);
+        let edited = concat!(
+            r#"<p>Ask <span data-mention="" class="mention">@sam</span> about "#,
+            r#"<a target="_blank" rel="noopener noreferrer" href="https://example.com" data-auto-link="" class="auto-link">"#,
+            r#"https://example.com</a> <strong>today</strong></p>"#,
+        );
+        assert_eq!(sanitize_html(edited), edited);
+        assert_eq!(sanitize_html("<a href=\"javascript:alert(1)\">x</a>"), "<a>x</a>");
+        assert_eq!(sanitize_html("a &amp; b"), "a &amp; b");
+    }
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(strip_html("<b>Bold</b> &amp; <i>italic</i>"), "Bold & italic");
+        // Quoted > and < inside attributes don't end or start tags
+        assert_eq!(
+            strip_html(r#"<a href="x" title="a > b">link</a> and <span data-x='<y>'>more</span>"#),
+            "link and more"
+        );
+        assert_eq!(strip_html("It&#8217;s &#x263A; &hellip;&nbsp;ok"), "It\u{2019}s \u{263A} \u{2026} ok");
+        assert_eq!(strip_html("&amp;lt; stays escaped, &bogus; too"), "&lt; stays escaped, &bogus; too");
+        assert_eq!(strip_html("before<script>if (a < b) alert('x')</script> <STYLE>p{}</STYLE>after"), "before after");
+        assert_eq!(strip_html("a < b <!-- note --> c"), "a < b  c");
+        assert_eq!(strip_html("1 &lt; 2 &#0; &"), "1 < 2 &#0; &");
+    }
+
+    #[test]
+    fn test_markdown_notes() {
+        let note = "Call **Ann** about [the lease](https://example.com)\n\n- one\n- `two`\n\n<script>x()</script>";
+        let rendered = render_markdown(note);
+        assert!(rendered.contains("<strong>Ann</strong>"));
+        assert!(rendered.contains(r#"<a href="https://example.com" rel="noopener noreferrer">the lease</a>"#));
+        assert!(rendered.contains("<li><code>two</code></li>"));
+        assert!(!rendered.contains("script"));
+
+        assert_eq!(markdown_to_text(note), "Call Ann about the lease\none\ntwo");
+        assert_eq!(markdown_to_text("plain\nlines"), "plain\nlines");
+    }
+}
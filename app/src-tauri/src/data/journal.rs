@@ -0,0 +1,114 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::NaiveDate;
+use uuid::Uuid;
+
+use super::document::DocumentState;
+use super::node::position_between;
+use super::operations::{create_op, update_op, NodeChanges, Operation};
+use crate::search::strip_html;
+
+/// Parse a YYYY-MM-DD date, defaulting to `today` when none is given
+pub fn journal_date(date: Option<&str>, today: NaiveDate) -> Result<NaiveDate, String> {
+    match date {
+        Some(s) => NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|e| format!("Invalid date format: {}", e)),
+        None => Ok(today),
+    }
+}
+
+/// Render a daily note title, rejecting malformed format strings
+pub fn daily_title(date: NaiveDate, format: &str) -> Result<String, String> {
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(format!("Invalid title format: {}", format));
+    }
+    Ok(date.format_with_items(items.into_iter()).to_string())
+}
+
+/// Find a child of `parent_id` (None for roots) whose plain-text content is `title`
+pub fn find_titled_child(state: &DocumentState, parent_id: Option<Uuid>, title: &str) -> Option<Uuid> {
+    state
+        .sorted_children(parent_id)
+        .into_iter()
+        .find(|n| strip_html(&n.content).trim() == title)
+        .map(|n| n.id)
+}
+
+/// Whether a document's first root node carries the given title
+pub fn has_title(state: &DocumentState, title: &str) -> bool {
+    state
+        .sorted_children(None)
+        .first()
+        .is_some_and(|n| strip_html(&n.content).trim() == title)
+}
+
+/// Build the ops that append a dated daily note under `parent_id`.
+/// Returns the new node id and the ops.
+pub fn daily_note_ops(
+    state: &DocumentState,
+    parent_id: Option<Uuid>,
+    title: &str,
+    date: NaiveDate,
+) -> (Uuid, Vec<Operation>) {
+    let last = state.sorted_children(parent_id).last().map(|n| n.position);
+    let create = create_op(parent_id, position_between(last, None), title.to_string());
+    let id = match &create {
+        Operation::Create { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let dated = update_op(
+        id,
+        NodeChanges {
+            date: Some(date.format("%Y-%m-%d").to_string()),
+            ..Default::default()
+        },
+    );
+    (id, vec![create, dated])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Node;
+
+    #[test]
+    fn test_daily_title() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        assert_eq!(daily_title(date, "%Y-%m-%d").unwrap(), "2024-03-05");
+        assert_eq!(daily_title(date, "%A, %B %-d").unwrap(), "Tuesday, March 5");
+        assert!(daily_title(date, "%Q").is_err());
+    }
+
+    #[test]
+    fn test_journal_date() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        assert_eq!(journal_date(None, today).unwrap(), today);
+        assert_eq!(
+            journal_date(Some("2024-01-02"), today).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()
+        );
+        assert!(journal_date(Some("tomorrow"), today).is_err());
+    }
+
+    #[test]
+    fn test_find_or_append_daily_note() {
+        let journal = Node::new("Journal".to_string());
+        let existing = Node::new_child(journal.id, 0.0, "<b>2024-03-04</b>".to_string());
+        let mut state = DocumentState {
+            nodes: vec![journal.clone(), existing.clone()],
+        };
+        assert!(has_title(&state, "Journal"));
+        assert_eq!(find_titled_child(&state, Some(journal.id), "2024-03-04"), Some(existing.id));
+        assert_eq!(find_titled_child(&state, Some(journal.id), "2024-03-05"), None);
+
+        let date = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        let (id, ops) = daily_note_ops(&state, Some(journal.id), "2024-03-05", date);
+        for op in &ops {
+            op.apply(&mut state);
+        }
+        let node = state.nodes.iter().find(|n| n.id == id).unwrap();
+        assert_eq!(node.parent_id, Some(journal.id));
+        assert!(node.position > existing.position);
+        assert_eq!(node.date, Some("2024-03-05".to_string()));
+        assert_eq!(find_titled_child(&state, Some(journal.id), "2024-03-05"), Some(id));
+    }
+}
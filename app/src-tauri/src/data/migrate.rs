@@ -0,0 +1,194 @@
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Progress of a data directory migration, reported after each file
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationProgress {
+    pub copied: usize,
+    pub total: usize,
+    /// Path of the file just copied, relative to the data directory
+    pub current: String,
+}
+
+/// Holds this machine's logs and other rebuildable files, which stay behind
+const CACHE_DIR: &str = ".cache";
+
+/// Collect every file under `dir`, relative to it, except the cache
+fn collect_files(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir.join(relative))
+        .map_err(|e| format!("Read {:?}: {}", dir.join(relative), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Read dir entry: {}", e))?;
+        let path = relative.join(entry.file_name());
+        if path == Path::new(CACHE_DIR) {
+            continue;
+        }
+        let file_type = entry.file_type().map_err(|e| format!("Read {:?}: {}", path, e))?;
+        if file_type.is_dir() {
+            collect_files(dir, &path, files)?;
+        } else if file_type.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Copy everything in the data directory `from` (documents, folders, inbox,
+/// snapshots, templates...) but the cache into `to`, calling `progress` after
+/// each file.
+/// Existing files in `to` are never overwritten. Once every file is copied,
+/// `switch` records `to` as the data directory; if a copy or `switch` fails,
+/// everything copied so far is removed again. With `move_files`, the
+/// originals are deleted only after a successful switch. Returns the number
+/// of files.
+pub fn migrate_data(
+    from: &Path,
+    to: &Path,
+    move_files: bool,
+    mut progress: impl FnMut(MigrationProgress),
+    switch: impl FnOnce() -> Result<(), String>,
+) -> Result<usize, String> {
+    let from = from.canonicalize().map_err(|e| format!("Data directory {:?}: {}", from, e))?;
+    let created = !to.exists();
+    fs::create_dir_all(to).map_err(|e| format!("Create {:?}: {}", to, e))?;
+    let to = to.canonicalize().map_err(|e| format!("Data directory {:?}: {}", to, e))?;
+    let invalid = if to == from {
+        Some("The new data directory is the current one")
+    } else if to.starts_with(&from) || from.starts_with(&to) {
+        Some("The data directories can't be inside one another")
+    } else {
+        None
+    };
+    if let Some(message) = invalid {
+        if created {
+            let _ = fs::remove_dir(&to);
+        }
+        return Err(message.to_string());
+    }
+
+    let mut files = Vec::new();
+    collect_files(&from, Path::new(""), &mut files)?;
+    if let Some(existing) = files.iter().find(|f| to.join(f).exists()) {
+        return Err(format!("{:?} already exists in the new data directory", existing));
+    }
+
+    let mut copied: Vec<PathBuf> = Vec::new();
+    for file in &files {
+        let target = to.join(file);
+        let result = target
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::copy(from.join(file), &target).map(|_| ()));
+        if let Err(e) = result {
+            rollback(&to, &copied);
+            return Err(format!("Copy {:?}: {} (migration rolled back)", file, e));
+        }
+        copied.push(file.clone());
+        progress(MigrationProgress {
+            copied: copied.len(),
+            total: files.len(),
+            current: file.to_string_lossy().to_string(),
+        });
+    }
+
+    if let Err(e) = switch() {
+        rollback(&to, &copied);
+        return Err(format!("{} (migration rolled back)", e));
+    }
+
+    if move_files {
+        for file in &files {
+            if let Err(e) = fs::remove_file(from.join(file)) {
+                log::warn!("Failed to remove {:?} after migration: {}", file, e);
+            }
+        }
+        remove_empty_dirs(&from);
+    }
+
+    Ok(files.len())
+}
+
+/// Remove copied files and any directories left empty by that
+fn rollback(to: &Path, copied: &[PathBuf]) {
+    for file in copied {
+        let _ = fs::remove_file(to.join(file));
+    }
+    remove_empty_dirs(to);
+}
+
+/// Remove empty subdirectories (the directory itself is kept)
+fn remove_empty_dirs(dir: &Path) {
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                remove_empty_dirs(&path);
+                let _ = fs::remove_dir(&path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_data_moves_everything() {
+        let from = TempDir::new().unwrap();
+        let to = TempDir::new().unwrap();
+        write(&from.path().join("documents/abc/state.json"), "{}");
+        write(&from.path().join("folders.json"), "{}");
+        write(&from.path().join("inbox.jsonl"), "");
+        write(&from.path().join(".cache/logs/outline.log"), "log");
+
+        let mut reports = Vec::new();
+        let count = migrate_data(from.path(), to.path(), true, |p| reports.push(p), || Ok(())).unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(reports.last().map(|p| (p.copied, p.total)), Some((3, 3)));
+        assert!(to.path().join("documents/abc/state.json").exists());
+        assert!(to.path().join("folders.json").exists());
+        assert!(!from.path().join("documents").exists());
+        assert!(!from.path().join("folders.json").exists());
+        // The cache stays where it was
+        assert!(!to.path().join(".cache").exists());
+        assert!(from.path().join(".cache/logs/outline.log").exists());
+    }
+
+    #[test]
+    fn test_migrate_data_refuses_to_overwrite() {
+        let from = TempDir::new().unwrap();
+        let to = TempDir::new().unwrap();
+        write(&from.path().join("folders.json"), "{}");
+        write(&from.path().join("inbox.jsonl"), "");
+        write(&to.path().join("folders.json"), "theirs");
+
+        assert!(migrate_data(from.path(), to.path(), true, |_| {}, || Ok(())).is_err());
+        assert_eq!(fs::read_to_string(to.path().join("folders.json")).unwrap(), "theirs");
+        assert!(!to.path().join("inbox.jsonl").exists());
+        assert!(from.path().join("inbox.jsonl").exists());
+
+        let nested = from.path().join("nested");
+        assert!(migrate_data(from.path(), &nested, false, |_| {}, || Ok(())).is_err());
+        assert!(!nested.exists());
+    }
+
+    #[test]
+    fn test_migrate_data_keeps_originals_if_switch_fails() {
+        let from = TempDir::new().unwrap();
+        let to = TempDir::new().unwrap();
+        write(&from.path().join("documents/abc/state.json"), "{}");
+
+        let result = migrate_data(from.path(), to.path(), true, |_| {}, || Err("Write config: denied".to_string()));
+        assert!(result.unwrap_err().starts_with("Write config: denied"));
+        assert!(from.path().join("documents/abc/state.json").exists());
+        assert!(!to.path().join("documents").exists());
+    }
+}
@@ -0,0 +1,258 @@
+//! One-time upgrades of data directories written by older versions of the
+//! app (or by the Dynalist import script), run at startup. Each migration
+//! works on the raw JSON so fields it doesn't know about are kept. The level
+//! reached is recorded per data directory in config.json, so each migration
+//! runs once.
+
+use serde_json::{Map, Value};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use uuid::Uuid;
+
+use super::document::{data_dir, load_config, update_config};
+use super::wal::write_atomic;
+use crate::import_export::{dynalist_color, process_dynalist_content};
+
+struct Migration {
+    name: &'static str,
+    /// Upgrade the data directory, returning the number of files changed
+    run: fn(&Path) -> Result<usize, String>,
+}
+
+/// Migrations in the order they were added; a data directory's level is the
+/// number of them it has been through
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        name: "dynalist node fields",
+        run: migrate_dynalist_nodes,
+    },
+    Migration {
+        name: "folder defaults",
+        run: migrate_folders,
+    },
+];
+
+/// Bring the current data directory up to date, recording the new level
+pub fn run_migrations() -> Result<(), String> {
+    let dir = data_dir();
+    if !dir.exists() {
+        return Ok(());
+    }
+    let key = dir.to_string_lossy().to_string();
+    let level = load_config().data_migrations.get(&key).copied().unwrap_or(0);
+    if level >= MIGRATIONS.len() {
+        return Ok(());
+    }
+
+    let reached = migrate_data_dir(&dir, level)?;
+    update_config(|config| {
+        config.data_migrations.insert(key, reached);
+    })
+}
+
+/// Run every migration past `level` on a data directory, returning the level
+/// reached
+fn migrate_data_dir(dir: &Path, level: usize) -> Result<usize, String> {
+    for migration in MIGRATIONS.iter().skip(level) {
+        let changed = (migration.run)(dir).map_err(|e| format!("Migration {:?}: {}", migration.name, e))?;
+        log::info!("Migration {:?} updated {} files in {:?}", migration.name, changed, dir);
+    }
+    Ok(MIGRATIONS.len())
+}
+
+fn read_json(path: &Path) -> Result<Value, String> {
+    let file = File::open(path).map_err(|e| format!("Open {:?}: {}", path, e))?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|e| format!("Parse {:?}: {}", path, e))
+}
+
+/// Convert what the Dynalist import left in state.json: numeric color
+/// labels, and `!(date)` / `==highlight==` syntax still in the content
+fn migrate_dynalist_nodes(dir: &Path) -> Result<usize, String> {
+    let docs_dir = dir.join("documents");
+    let Ok(entries) = fs::read_dir(&docs_dir) else {
+        return Ok(0);
+    };
+
+    let mut changed_files = 0;
+    for entry in entries.flatten() {
+        let state_path = entry.path().join("state.json");
+        if !state_path.exists() {
+            continue;
+        }
+        let mut state = read_json(&state_path)?;
+        let Some(nodes) = state.get_mut("nodes").and_then(Value::as_array_mut) else {
+            continue;
+        };
+        let mut changed = false;
+        for node in nodes.iter_mut().filter_map(Value::as_object_mut) {
+            changed |= upgrade_dynalist_node(node);
+        }
+        if changed {
+            write_atomic(&state_path, |writer| {
+                serde_json::to_writer(writer, &state).map_err(|e| format!("Write state.json: {}", e))
+            })?;
+            changed_files += 1;
+        }
+    }
+    Ok(changed_files)
+}
+
+/// Returns whether the node was changed
+fn upgrade_dynalist_node(node: &mut Map<String, Value>) -> bool {
+    let mut changed = false;
+
+    let label = node.get("color").and_then(Value::as_str).and_then(|c| c.parse::<u8>().ok());
+    if let Some(label) = label {
+        match dynalist_color(label) {
+            Some(name) => node.insert("color".to_string(), Value::String(name)),
+            None => node.remove("color"),
+        };
+        changed = true;
+    }
+
+    // Only content that still has a Dynalist date came from Dynalist, so
+    // `==` in newer notes is left alone
+    let has_date = node.get("date").is_some_and(|d| !d.is_null());
+    let content = node.get("content").and_then(Value::as_str).unwrap_or_default();
+    let (converted, date, recurrence) = process_dynalist_content(content);
+    if let (false, Some(date)) = (has_date, date) {
+        node.insert("content".to_string(), Value::String(converted));
+        node.insert("date".to_string(), Value::String(date));
+        if let Some(recurrence) = recurrence {
+            node.insert("date_recurrence".to_string(), Value::String(recurrence));
+        }
+        changed = true;
+    }
+
+    changed
+}
+
+/// Fill in what older folders.json files lack: the document maps, and the
+/// position and collapsed state of each folder (or whole folders, when they
+/// were stored as bare names)
+fn migrate_folders(dir: &Path) -> Result<usize, String> {
+    let path = dir.join("folders.json");
+    if !path.exists() {
+        return Ok(0);
+    }
+    let mut state = read_json(&path)?;
+    let Some(state_obj) = state.as_object_mut() else {
+        return Err("folders.json is not an object".to_string());
+    };
+
+    let mut changed = false;
+    if !state_obj.get("folders").is_some_and(Value::is_array) {
+        state_obj.insert("folders".to_string(), Value::Array(Vec::new()));
+        changed = true;
+    }
+    for key in ["document_folders", "document_order"] {
+        if !state_obj.get(key).is_some_and(Value::is_object) {
+            state_obj.insert(key.to_string(), Value::Object(Map::new()));
+            changed = true;
+        }
+    }
+
+    if let Some(folders) = state_obj.get_mut("folders").and_then(Value::as_array_mut) {
+        for (position, folder) in folders.iter_mut().enumerate() {
+            if let Some(name) = folder.as_str() {
+                let mut upgraded = Map::new();
+                upgraded.insert("id".to_string(), Value::String(Uuid::new_v4().to_string()));
+                upgraded.insert("name".to_string(), Value::String(name.to_string()));
+                *folder = Value::Object(upgraded);
+                changed = true;
+            }
+            let Some(folder) = folder.as_object_mut() else {
+                continue;
+            };
+            if !folder.contains_key("id") {
+                folder.insert("id".to_string(), Value::String(Uuid::new_v4().to_string()));
+                changed = true;
+            }
+            if !folder.contains_key("name") {
+                folder.insert("name".to_string(), Value::String("Untitled".to_string()));
+                changed = true;
+            }
+            if !folder.get("position").is_some_and(Value::is_i64) {
+                folder.insert("position".to_string(), Value::from(position as i64));
+                changed = true;
+            }
+            if !folder.get("collapsed").is_some_and(Value::is_boolean) {
+                folder.insert("collapsed".to_string(), Value::Bool(false));
+                changed = true;
+            }
+        }
+    }
+
+    if !changed {
+        return Ok(0);
+    }
+    write_atomic(&path, |writer| {
+        serde_json::to_writer_pretty(writer, &state).map_err(|e| format!("Write folders.json: {}", e))
+    })?;
+    Ok(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Document, FolderState};
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_dynalist_nodes_are_upgraded() {
+        let tmp = TempDir::new().unwrap();
+        let doc_id = Uuid::now_v7();
+        let doc_dir = tmp.path().join("documents").join(doc_id.to_string());
+        fs::create_dir_all(&doc_dir).unwrap();
+        let node = |id: u128, content: &str, color: Option<&str>| {
+            json!({
+                "id": Uuid::from_u128(id),
+                "position": 0.0,
+                "content": content,
+                "color": color,
+                "created_at": "2024-01-01T00:00:00Z",
+                "updated_at": "2024-01-01T00:00:00Z",
+                "legacy_field": 1
+            })
+        };
+        let state = json!({ "nodes": [
+            node(1, "<p>Renew !(2024-09-01 | 1y) ==soon==</p>", Some("3")),
+            node(2, "<p>a == b == c</p>", Some("blue")),
+        ]});
+        fs::write(doc_dir.join("state.json"), state.to_string()).unwrap();
+
+        assert_eq!(migrate_data_dir(tmp.path(), 0).unwrap(), MIGRATIONS.len());
+
+        let doc = Document::load(doc_dir.clone()).unwrap();
+        let renew = &doc.state.nodes[0];
+        assert_eq!(renew.content, "<p>Renew <mark>soon</mark></p>");
+        assert_eq!(renew.date.as_deref(), Some("2024-09-01"));
+        assert_eq!(renew.date_recurrence.as_deref(), Some("FREQ=YEARLY"));
+        assert_eq!(renew.color.as_deref(), Some("yellow"));
+        assert_eq!(doc.state.nodes[1].content, "<p>a == b == c</p>");
+
+        let raw = read_json(&doc_dir.join("state.json")).unwrap();
+        assert_eq!(raw["nodes"][0]["legacy_field"], 1);
+    }
+
+    #[test]
+    fn test_old_folders_json_is_upgraded() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(
+            tmp.path().join("folders.json"),
+            json!({ "folders": ["Work", { "id": "f2", "name": "Home" }] }).to_string(),
+        )
+        .unwrap();
+
+        migrate_folders(tmp.path()).unwrap();
+
+        let state: FolderState = serde_json::from_value(read_json(&tmp.path().join("folders.json")).unwrap()).unwrap();
+        assert_eq!(state.folders.len(), 2);
+        assert_eq!(state.folders[0].name, "Work");
+        assert_eq!(state.folders[1].id, "f2");
+        assert_eq!(state.folders[1].position, 1);
+        assert_eq!(migrate_folders(tmp.path()).unwrap(), 0);
+    }
+}
@@ -2,8 +2,56 @@ mod node;
 mod document;
 mod operations;
 mod folders;
+mod snapshots;
+mod sorting;
+mod templates;
+mod journal;
+mod rollup;
+mod statistics;
+mod board;
+mod replace;
+mod reload;
+mod migrate;
+mod health;
+mod wal;
+mod generate;
+mod migrations;
+mod review;
+mod timelog;
+mod pomodoro;
+mod recurrence;
+mod overdue;
+mod html;
+mod snippets;
+mod shares;
+mod encryption;
+mod title;
 
 pub use node::*;
 pub use document::*;
 pub use operations::*;
 pub use folders::*;
+pub use snapshots::*;
+pub use sorting::*;
+pub use templates::*;
+pub use journal::*;
+pub use rollup::*;
+pub use statistics::*;
+pub use board::*;
+pub use replace::*;
+pub use reload::*;
+pub use migrate::*;
+pub use health::*;
+pub use wal::*;
+pub use generate::*;
+pub use migrations::*;
+pub use review::*;
+pub use timelog::*;
+pub use pomodoro::*;
+pub use recurrence::*;
+pub use overdue::*;
+pub use html::*;
+pub use snippets::*;
+pub use shares::*;
+pub use encryption::*;
+pub use title::*;
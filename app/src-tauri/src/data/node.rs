@@ -1,5 +1,6 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
 /// Node type determines display and behavior
@@ -10,6 +11,27 @@ pub enum NodeType {
     Bullet,
     Checkbox,
     Heading,
+    /// Ordered list item, numbered among consecutive numbered siblings
+    Numbered,
+    /// Block quotation
+    Quote,
+    /// Horizontal rule; carries no text
+    Divider,
+    /// Preformatted code, with syntax language in `language`
+    CodeBlock,
+}
+
+/// A comment on a node, for discussing an outline shared between people
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: Uuid,
+    /// Device (hostname) the comment was written on
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+    pub text: String,
+    /// When the comment was resolved (None while open)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_at: Option<DateTime<Utc>>,
 }
 
 /// A single node in the outline tree
@@ -22,8 +44,9 @@ pub struct Node {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parent_id: Option<Uuid>,
 
-    /// Position among siblings (0-indexed)
-    pub position: i32,
+    /// Fractional sort key among siblings (lower sorts first). New positions are
+    /// chosen between neighbours so inserts and moves never renumber siblings.
+    pub position: f64,
 
     /// Primary text content (may contain rich text HTML from TipTap)
     pub content: String,
@@ -40,10 +63,18 @@ pub struct Node {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub heading_level: Option<u8>,
 
+    /// Syntax language (only meaningful when node_type is CodeBlock)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+
     /// Checkbox state (only valid when node_type is Checkbox)
     #[serde(default)]
     pub is_checked: bool,
 
+    /// Task priority from 1 (highest) to 4 (low)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u8>,
+
     /// Color label
     #[serde(skip_serializing_if = "Option::is_none")]
     pub color: Option<String>,
@@ -52,6 +83,10 @@ pub struct Node {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub tags: Vec<String>,
 
+    /// Arbitrary key-value properties (e.g. status=waiting)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub properties: HashMap<String, String>,
+
     /// Due date for tasks
     #[serde(skip_serializing_if = "Option::is_none")]
     pub date: Option<String>,
@@ -60,6 +95,11 @@ pub struct Node {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub date_recurrence: Option<String>,
 
+    /// When to remind about this node (RFC 3339, or a local YYYY-MM-DDTHH:MM),
+    /// independent of the due date
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remind_at: Option<String>,
+
     /// Whether children are hidden
     #[serde(default)]
     pub collapsed: bool,
@@ -68,6 +108,15 @@ pub struct Node {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mirror_source_id: Option<Uuid>,
 
+    /// Comments left on this node, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<Comment>,
+
+    /// Whether content holds the ciphertext of the content and note (see
+    /// encryption.rs)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
+
     /// Creation timestamp
     pub created_at: DateTime<Utc>,
 
@@ -75,6 +124,34 @@ pub struct Node {
     pub updated_at: DateTime<Utc>,
 }
 
+/// Parse a reminder time: RFC 3339, or a date and time without an offset,
+/// taken as local time
+pub fn parse_remind_at(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Some(at.with_timezone(&Utc));
+    }
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .map(|at| at.with_timezone(&Utc))
+}
+
+/// Smallest gap allowed between adjacent sibling positions before compaction
+/// rebalances them (keeps repeated midpoint inserts clear of f64 precision limits)
+pub const MIN_POSITION_GAP: f64 = 1e-9;
+
+/// Get a position that sorts strictly between two sibling positions.
+/// `None` means there is no sibling on that side.
+pub fn position_between(before: Option<f64>, after: Option<f64>) -> f64 {
+    match (before, after) {
+        (Some(b), Some(a)) => b + (a - b) / 2.0,
+        (Some(b), None) => b.floor() + 1.0,
+        (None, Some(a)) => a.ceil() - 1.0,
+        (None, None) => 0.0,
+    }
+}
+
 impl Node {
     /// Create a new node with default values
     pub fn new(content: String) -> Self {
@@ -82,28 +159,55 @@ impl Node {
         Self {
             id: Uuid::now_v7(),
             parent_id: None,
-            position: 0,
+            position: 0.0,
             content,
             note: None,
             node_type: NodeType::default(),
             heading_level: None,
+            language: None,
             is_checked: false,
+            priority: None,
             color: None,
             tags: Vec::new(),
+            properties: HashMap::new(),
             date: None,
             date_recurrence: None,
+            remind_at: None,
             collapsed: false,
             mirror_source_id: None,
+            comments: Vec::new(),
+            encrypted: false,
             created_at: now,
             updated_at: now,
         }
     }
 
+    /// The reminder time, if set and readable
+    pub fn reminder_time(&self) -> Option<DateTime<Utc>> {
+        self.remind_at.as_deref().and_then(parse_remind_at)
+    }
+
     /// Create a new child node under this parent
-    pub fn new_child(parent_id: Uuid, position: i32, content: String) -> Self {
+    pub fn new_child(parent_id: Uuid, position: f64, content: String) -> Self {
         let mut node = Self::new(content);
         node.parent_id = Some(parent_id);
         node.position = position;
         node
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remind_at() {
+        let utc = Utc.with_ymd_and_hms(2024, 5, 1, 13, 30, 0).unwrap();
+        assert_eq!(parse_remind_at("2024-05-01T09:30:00-04:00"), Some(utc));
+        assert_eq!(parse_remind_at("2024-05-01T13:30:00Z"), Some(utc));
+
+        let local = Local.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(parse_remind_at("2024-05-01T09:30"), Some(local));
+        assert_eq!(parse_remind_at("next wednesday"), None);
+    }
+}
@@ -1,9 +1,10 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
-use super::document::DocumentState;
-use super::node::{Node, NodeType};
+use super::document::{device_name, DocumentState};
+use super::node::{Comment, Node, NodeType};
 
 /// Operations that can be applied to a document
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,7 +14,7 @@ pub enum Operation {
     Create {
         id: Uuid,
         parent_id: Option<Uuid>,
-        position: i32,
+        position: f64,
         content: String,
         #[serde(default)]
         node_type: NodeType,
@@ -32,7 +33,7 @@ pub enum Operation {
     Move {
         id: Uuid,
         parent_id: Option<Uuid>,
-        position: i32,
+        position: f64,
         updated_at: DateTime<Utc>,
     },
 
@@ -41,6 +42,32 @@ pub enum Operation {
         id: Uuid,
         updated_at: DateTime<Utc>,
     },
+
+    /// Add a comment to a node
+    AddComment {
+        node_id: Uuid,
+        comment: Comment,
+        updated_at: DateTime<Utc>,
+    },
+
+    /// Resolve a comment, or reopen it
+    ResolveComment {
+        node_id: Uuid,
+        comment_id: Uuid,
+        resolved: bool,
+        updated_at: DateTime<Utc>,
+    },
+}
+
+/// A line in a pending or history file: an operation plus the device that wrote it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpRecord {
+    #[serde(flatten)]
+    pub op: Operation,
+
+    /// Originating device (hostname); missing in files written before this was tracked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
 }
 
 /// Fields that can be changed in an Update operation
@@ -58,26 +85,45 @@ pub struct NodeChanges {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub heading_level: Option<u8>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_checked: Option<bool>,
 
+    /// 1-4; 0 clears the priority
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u8>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub color: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tags: Option<Vec<String>>,
 
+    /// Properties to set, merged into the existing ones; an empty value removes the key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub properties: Option<HashMap<String, String>>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub date: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub date_recurrence: Option<String>,
 
+    /// Empty string clears the reminder
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remind_at: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub collapsed: Option<bool>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mirror_source_id: Option<Uuid>,
+
+    /// Set along with content by encryption.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted: Option<bool>,
 }
 
 impl Operation {
@@ -88,6 +134,20 @@ impl Operation {
             Operation::Update { updated_at, .. } => *updated_at,
             Operation::Move { updated_at, .. } => *updated_at,
             Operation::Delete { updated_at, .. } => *updated_at,
+            Operation::AddComment { updated_at, .. } => *updated_at,
+            Operation::ResolveComment { updated_at, .. } => *updated_at,
+        }
+    }
+
+    /// Get the node this operation targets
+    pub fn node_id(&self) -> Uuid {
+        match self {
+            Operation::Create { id, .. } => *id,
+            Operation::Update { id, .. } => *id,
+            Operation::Move { id, .. } => *id,
+            Operation::Delete { id, .. } => *id,
+            Operation::AddComment { node_id, .. } => *node_id,
+            Operation::ResolveComment { node_id, .. } => *node_id,
         }
     }
 
@@ -107,21 +167,34 @@ impl Operation {
                     return;
                 }
 
+                // Dividers carry no text
+                let content = if *node_type == NodeType::Divider {
+                    String::new()
+                } else {
+                    content.clone()
+                };
+
                 let node = Node {
                     id: *id,
                     parent_id: *parent_id,
                     position: *position,
-                    content: content.clone(),
+                    content,
                     note: None,
                     node_type: node_type.clone(),
                     heading_level: None,
+                    language: None,
                     is_checked: false,
+                    priority: None,
                     color: None,
                     tags: Vec::new(),
+                    properties: HashMap::new(),
                     date: None,
                     date_recurrence: None,
+                    remind_at: None,
                     collapsed: false,
                     mirror_source_id: None,
+                    comments: Vec::new(),
+                    encrypted: false,
                     created_at: *updated_at,
                     updated_at: *updated_at,
                 };
@@ -149,15 +222,33 @@ impl Operation {
                         if let Some(heading_level) = changes.heading_level {
                             node.heading_level = Some(heading_level);
                         }
+                        if let Some(ref language) = changes.language {
+                            // Empty string means clear the language
+                            node.language = if language.is_empty() { None } else { Some(language.clone()) };
+                        }
                         if let Some(is_checked) = changes.is_checked {
                             node.is_checked = is_checked;
                         }
+                        if let Some(priority) = changes.priority {
+                            // 0 (or anything out of range) clears the priority
+                            node.priority = Some(priority).filter(|p| (1..=4).contains(p));
+                        }
                         if let Some(ref color) = changes.color {
-                            node.color = Some(color.clone());
+                            // Empty string means clear the color
+                            node.color = if color.is_empty() { None } else { Some(color.clone()) };
                         }
                         if let Some(ref tags) = changes.tags {
                             node.tags = tags.clone();
                         }
+                        if let Some(ref properties) = changes.properties {
+                            for (key, value) in properties {
+                                if value.is_empty() {
+                                    node.properties.remove(key);
+                                } else {
+                                    node.properties.insert(key.clone(), value.clone());
+                                }
+                            }
+                        }
                         if let Some(ref date) = changes.date {
                             // Empty string means clear the date
                             node.date = if date.is_empty() { None } else { Some(date.clone()) };
@@ -166,12 +257,23 @@ impl Operation {
                             // Empty string means clear the recurrence
                             node.date_recurrence = if date_recurrence.is_empty() { None } else { Some(date_recurrence.clone()) };
                         }
+                        if let Some(ref remind_at) = changes.remind_at {
+                            // Empty string means clear the reminder
+                            node.remind_at = if remind_at.is_empty() { None } else { Some(remind_at.clone()) };
+                        }
                         if let Some(collapsed) = changes.collapsed {
                             node.collapsed = collapsed;
                         }
                         if let Some(mirror_source_id) = changes.mirror_source_id {
                             node.mirror_source_id = Some(mirror_source_id);
                         }
+                        if let Some(encrypted) = changes.encrypted {
+                            node.encrypted = encrypted;
+                        }
+                        if node.node_type == NodeType::Divider {
+                            node.content.clear();
+                            node.is_checked = false;
+                        }
                         node.updated_at = *updated_at;
                     }
                 }
@@ -209,12 +311,38 @@ impl Operation {
 
                 state.nodes.retain(|n| !to_delete.contains(&n.id));
             }
+
+            // Comments don't touch the node's updated_at, so they never
+            // win or lose against concurrent edits to its fields
+            Operation::AddComment { node_id, comment, .. } => {
+                if let Some(node) = state.nodes.iter_mut().find(|n| n.id == *node_id) {
+                    if !node.comments.iter().any(|c| c.id == comment.id) {
+                        node.comments.push(comment.clone());
+                    }
+                }
+            }
+
+            Operation::ResolveComment {
+                node_id,
+                comment_id,
+                resolved,
+                updated_at,
+            } => {
+                let comment = state
+                    .nodes
+                    .iter_mut()
+                    .find(|n| n.id == *node_id)
+                    .and_then(|n| n.comments.iter_mut().find(|c| c.id == *comment_id));
+                if let Some(comment) = comment {
+                    comment.resolved_at = resolved.then_some(*updated_at);
+                }
+            }
         }
     }
 }
 
 /// Helper to create a Create operation
-pub fn create_op(parent_id: Option<Uuid>, position: i32, content: String) -> Operation {
+pub fn create_op(parent_id: Option<Uuid>, position: f64, content: String) -> Operation {
     Operation::Create {
         id: Uuid::now_v7(),
         parent_id,
@@ -229,7 +357,7 @@ pub fn create_op(parent_id: Option<Uuid>, position: i32, content: String) -> Ope
 pub fn create_op_with_id(
     id: Uuid,
     parent_id: Option<Uuid>,
-    position: i32,
+    position: f64,
     content: String,
     node_type: NodeType,
 ) -> Operation {
@@ -253,7 +381,7 @@ pub fn update_op(id: Uuid, changes: NodeChanges) -> Operation {
 }
 
 /// Helper to create a Move operation
-pub fn move_op(id: Uuid, parent_id: Option<Uuid>, position: i32) -> Operation {
+pub fn move_op(id: Uuid, parent_id: Option<Uuid>, position: f64) -> Operation {
     Operation::Move {
         id,
         parent_id,
@@ -269,3 +397,203 @@ pub fn delete_op(id: Uuid) -> Operation {
         updated_at: Utc::now(),
     }
 }
+
+/// Helper to create an AddComment operation, authored by this device
+pub fn add_comment_op(node_id: Uuid, text: String) -> Operation {
+    let now = Utc::now();
+    Operation::AddComment {
+        node_id,
+        comment: Comment {
+            id: Uuid::now_v7(),
+            author: device_name(),
+            created_at: now,
+            text,
+            resolved_at: None,
+        },
+        updated_at: now,
+    }
+}
+
+/// Helper to create a ResolveComment operation
+pub fn resolve_comment_op(node_id: Uuid, comment_id: Uuid, resolved: bool) -> Operation {
+    Operation::ResolveComment {
+        node_id,
+        comment_id,
+        resolved,
+        updated_at: Utc::now(),
+    }
+}
+
+/// Helper to build the ops that bring back an existing comment (e.g. from a
+/// backup) with its id, author and time: an AddComment, then a
+/// ResolveComment if it was resolved
+pub fn restore_comment_ops(node_id: Uuid, comment: &Comment) -> Vec<Operation> {
+    let mut ops = vec![Operation::AddComment {
+        node_id,
+        comment: Comment {
+            resolved_at: None,
+            ..comment.clone()
+        },
+        updated_at: comment.created_at,
+    }];
+    if let Some(resolved_at) = comment.resolved_at {
+        ops.push(Operation::ResolveComment {
+            node_id,
+            comment_id: comment.id,
+            resolved: true,
+            updated_at: resolved_at,
+        });
+    }
+    ops
+}
+
+/// Helper to build Update ops that check or uncheck a node and its descendants.
+/// With `skip_non_checkbox`, descendants that are not checkboxes are left alone
+/// (the node itself is always updated). Nodes already in the target state are skipped.
+pub fn set_checked_recursive_ops(
+    state: &DocumentState,
+    node_id: Uuid,
+    checked: bool,
+    skip_non_checkbox: bool,
+) -> Vec<Operation> {
+    let nodes = state.nodes_by_id();
+    state
+        .subtree_ids(node_id)
+        .into_iter()
+        .filter_map(|id| nodes.get(&id).copied())
+        .filter(|n| n.id == node_id || !skip_non_checkbox || n.node_type == NodeType::Checkbox)
+        .filter(|n| n.is_checked != checked)
+        .map(|n| {
+            update_op(
+                n.id,
+                NodeChanges {
+                    is_checked: Some(checked),
+                    ..Default::default()
+                },
+            )
+        })
+        .collect()
+}
+
+/// Helper to build a Move op that puts a node after its last sibling (None if
+/// it is already last), e.g. to sink completed items
+pub fn move_to_bottom_op(state: &DocumentState, node_id: Uuid) -> Option<Operation> {
+    let node = state.nodes.iter().find(|n| n.id == node_id)?;
+    let siblings = state.sorted_children(node.parent_id);
+    if siblings.last().map(|n| n.id) == Some(node_id) {
+        return None;
+    }
+    let position = state.position_for_index(node.parent_id, siblings.len(), Some(node_id));
+    Some(move_op(node_id, node.parent_id, position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_checked_recursive_ops() {
+        let project = Node::new("Project".to_string());
+        let mut task = Node::new_child(project.id, 0.0, "Task".to_string());
+        task.node_type = NodeType::Checkbox;
+        let note = Node::new_child(project.id, 1.0, "Reference".to_string());
+        let mut subtask = Node::new_child(task.id, 0.0, "Subtask".to_string());
+        subtask.node_type = NodeType::Checkbox;
+        subtask.is_checked = true;
+        let mut state = DocumentState {
+            nodes: vec![project.clone(), task.clone(), note.clone(), subtask.clone()],
+        };
+
+        // Skipping non-checkboxes leaves the plain bullet alone; the already-checked
+        // subtask needs no op
+        let ops = set_checked_recursive_ops(&state, project.id, true, true);
+        assert_eq!(ops.len(), 2);
+        for op in &ops {
+            op.apply(&mut state);
+        }
+        let by_id = state.nodes_by_id();
+        assert!(by_id[&project.id].is_checked);
+        assert!(by_id[&task.id].is_checked);
+        assert!(!by_id[&note.id].is_checked);
+
+        // Without skipping, everything under the node is included
+        let ops = set_checked_recursive_ops(&state, project.id, true, false);
+        assert_eq!(ops.len(), 1);
+    }
+
+    #[test]
+    fn test_move_to_bottom_op() {
+        let parent = Node::new("Parent".to_string());
+        let done = Node::new_child(parent.id, 0.0, "Done".to_string());
+        let open = Node::new_child(parent.id, 1.0, "Open".to_string());
+        let mut state = DocumentState {
+            nodes: vec![parent.clone(), done.clone(), open.clone()],
+        };
+
+        let op = move_to_bottom_op(&state, done.id).unwrap();
+        op.apply(&mut state);
+        let order: Vec<Uuid> = state.sorted_children(Some(parent.id)).iter().map(|n| n.id).collect();
+        assert_eq!(order, vec![open.id, done.id]);
+        assert!(move_to_bottom_op(&state, done.id).is_none());
+    }
+
+    #[test]
+    fn test_divider_carries_no_text() {
+        let mut state = DocumentState::new();
+        let op = create_op_with_id(Uuid::now_v7(), None, 0.0, "ignored".to_string(), NodeType::Divider);
+        op.apply(&mut state);
+        assert_eq!(state.nodes[0].content, "");
+
+        let mut quote = Node::new("Quoted".to_string());
+        quote.updated_at = Utc::now() - chrono::Duration::seconds(1);
+        let quote_id = quote.id;
+        state.nodes.push(quote);
+        update_op(
+            quote_id,
+            NodeChanges {
+                node_type: Some(NodeType::Divider),
+                ..Default::default()
+            },
+        )
+        .apply(&mut state);
+        let node = state.nodes.iter().find(|n| n.id == quote_id).unwrap();
+        assert_eq!(node.node_type, NodeType::Divider);
+        assert_eq!(node.content, "");
+    }
+
+    #[test]
+    fn test_comment_ops() {
+        let mut state = DocumentState::new();
+        let node = Node::new("Discuss".to_string());
+        let node_id = node.id;
+        let updated_at = node.updated_at;
+        state.nodes.push(node);
+
+        let add = add_comment_op(node_id, "Is this still needed?".to_string());
+        add.apply(&mut state);
+        add.apply(&mut state);
+        let comment_id = state.nodes[0].comments[0].id;
+        assert_eq!(state.nodes[0].comments.len(), 1);
+        assert_eq!(state.nodes[0].comments[0].author, device_name());
+        // Commenting isn't an edit, so it can't beat a concurrent update
+        assert_eq!(state.nodes[0].updated_at, updated_at);
+
+        resolve_comment_op(node_id, comment_id, true).apply(&mut state);
+        assert!(state.nodes[0].comments[0].resolved_at.is_some());
+        resolve_comment_op(node_id, comment_id, false).apply(&mut state);
+        assert!(state.nodes[0].comments[0].resolved_at.is_none());
+
+        // A resolved comment from a backup comes back resolved at the same time
+        let mut resolved = state.nodes[0].comments[0].clone();
+        resolved.resolved_at = Some(resolved.created_at + chrono::Duration::minutes(5));
+        let mut restored = DocumentState {
+            nodes: vec![Node { comments: Vec::new(), ..state.nodes[0].clone() }],
+        };
+        let ops = restore_comment_ops(node_id, &resolved);
+        assert_eq!(ops.len(), 2);
+        for op in &ops {
+            op.apply(&mut restored);
+        }
+        assert_eq!(restored.nodes[0].comments, vec![resolved]);
+    }
+}
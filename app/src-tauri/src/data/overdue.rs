@@ -0,0 +1,232 @@
+//! Overdue rules: what to do with tasks still open after their date, stored
+//! in overdue_rules.json in the data directory so every machine applies the
+//! same ones. Each machine runs them once a day (see AppConfig) and the ops
+//! they produce are ordinary updates, so runs on several machines agree.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use super::document::{data_dir, DocumentState};
+use super::node::NodeType;
+use super::operations::{update_op, NodeChanges, Operation};
+use crate::search::strip_html;
+
+/// Which overdue tasks a rule applies to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum OverdueScope {
+    All,
+    /// Tasks carrying this tag (with or without the leading #)
+    Tag(String),
+    /// Tasks in this document
+    Document(String),
+}
+
+/// What a rule does to an overdue task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OverdueAction {
+    /// Move the date to today. Recurring tasks are left to their rule.
+    RollToToday,
+    /// Tag the task, e.g. #overdue
+    AddTag { tag: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverdueRule {
+    pub id: String,
+    pub name: String,
+    pub scope: OverdueScope,
+    pub action: OverdueAction,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// A task changed by a rule run
+#[derive(Debug, Clone, Serialize)]
+pub struct OverdueChange {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub document_id: String,
+    pub node_id: String,
+    pub content: String,
+    /// The task's date before the run (YYYY-MM-DD)
+    pub date: String,
+}
+
+/// Everything a rule run changed, sent with `overdue-rules-applied`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OverdueReport {
+    /// Local date of the run (YYYY-MM-DD)
+    pub date: String,
+    pub changes: Vec<OverdueChange>,
+}
+
+fn rules_path() -> PathBuf {
+    data_dir().join("overdue_rules.json")
+}
+
+/// Load the rules (none if the file is missing)
+pub fn load_overdue_rules() -> Result<Vec<OverdueRule>, String> {
+    let path = rules_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = fs::read_to_string(&path).map_err(|e| format!("Read overdue rules: {}", e))?;
+    serde_json::from_str(&contents).map_err(|e| format!("Parse overdue rules: {}", e))
+}
+
+/// Replace the rules, giving new ones an id
+pub fn save_overdue_rules(mut rules: Vec<OverdueRule>) -> Result<Vec<OverdueRule>, String> {
+    for rule in rules.iter_mut().filter(|r| r.id.is_empty()) {
+        rule.id = Uuid::new_v4().to_string();
+    }
+    let json = serde_json::to_string_pretty(&rules).map_err(|e| format!("Serialize overdue rules: {}", e))?;
+    fs::write(rules_path(), json).map_err(|e| format!("Write overdue rules: {}", e))?;
+    Ok(rules)
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_string()
+}
+
+/// Apply the enabled rules to a document's overdue tasks (open checkboxes
+/// dated before `today`). Rules run in order; a task gets at most one update.
+pub fn overdue_rule_ops(
+    state: &DocumentState,
+    document_id: Uuid,
+    rules: &[OverdueRule],
+    today: &str,
+) -> (Vec<Operation>, Vec<OverdueChange>) {
+    let mut ops = Vec::new();
+    let mut report = Vec::new();
+    for node in &state.nodes {
+        if node.node_type != NodeType::Checkbox || node.is_checked {
+            continue;
+        }
+        let Some(date) = node.date.as_deref().map(|d| d.get(..10).unwrap_or(d)) else {
+            continue;
+        };
+        if date.is_empty() || date >= today {
+            continue;
+        }
+
+        let mut changes = NodeChanges::default();
+        let mut tags = node.tags.clone();
+        for rule in rules.iter().filter(|r| r.enabled) {
+            let applies = match &rule.scope {
+                OverdueScope::All => true,
+                OverdueScope::Tag(tag) => tags.contains(&normalize_tag(tag)),
+                OverdueScope::Document(id) => *id == document_id.to_string(),
+            };
+            if !applies {
+                continue;
+            }
+            let changed = match &rule.action {
+                OverdueAction::RollToToday => {
+                    let recurring = node.date_recurrence.as_deref().is_some_and(|r| !r.is_empty());
+                    let roll = !recurring && changes.date.is_none();
+                    if roll {
+                        changes.date = Some(today.to_string());
+                    }
+                    roll
+                }
+                OverdueAction::AddTag { tag } => {
+                    let tag = normalize_tag(tag);
+                    let add = !tag.is_empty() && !tags.contains(&tag);
+                    if add {
+                        tags.push(tag);
+                        changes.tags = Some(tags.clone());
+                    }
+                    add
+                }
+            };
+            if changed {
+                report.push(OverdueChange {
+                    rule_id: rule.id.clone(),
+                    rule_name: rule.name.clone(),
+                    document_id: document_id.to_string(),
+                    node_id: node.id.to_string(),
+                    content: strip_html(&node.content),
+                    date: date.to_string(),
+                });
+            }
+        }
+        if changes.date.is_some() || changes.tags.is_some() {
+            ops.push(update_op(node.id, changes));
+        }
+    }
+    (ops, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Node;
+
+    fn task(content: &str, date: &str, tags: &[&str]) -> Node {
+        let mut node = Node::new(content.to_string());
+        node.node_type = NodeType::Checkbox;
+        node.date = Some(date.to_string());
+        node.tags = tags.iter().map(|t| t.to_string()).collect();
+        node
+    }
+
+    fn rule(name: &str, scope: OverdueScope, action: OverdueAction) -> OverdueRule {
+        OverdueRule {
+            id: name.to_string(),
+            name: name.to_string(),
+            scope,
+            action,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_overdue_rules() {
+        let doc_id = Uuid::now_v7();
+        let errand = task("Post letter", "2024-03-01", &["errand"]);
+        let mut weekly = task("Review inbox", "2024-03-01", &["errand"]);
+        weekly.date_recurrence = Some("FREQ=WEEKLY".to_string());
+        let report_task = task("Write report", "2024-03-01", &["work"]);
+        let future = task("Renew passport", "2024-04-01", &["errand"]);
+        let mut state = DocumentState {
+            nodes: vec![errand.clone(), weekly.clone(), report_task.clone(), future],
+        };
+        let rules = vec![
+            rule("roll errands", OverdueScope::Tag("#errand".to_string()), OverdueAction::RollToToday),
+            rule(
+                "flag",
+                OverdueScope::All,
+                OverdueAction::AddTag {
+                    tag: "#overdue".to_string(),
+                },
+            ),
+        ];
+
+        let (ops, changes) = overdue_rule_ops(&state, doc_id, &rules, "2024-03-05");
+        assert_eq!(ops.len(), 3);
+        let by_rule = |id: &str| changes.iter().filter(|c| c.rule_id == id).count();
+        assert_eq!(by_rule("roll errands"), 1);
+        assert_eq!(by_rule("flag"), 3);
+
+        for op in &ops {
+            op.apply(&mut state);
+        }
+        let find = |id| state.nodes.iter().find(|n| n.id == id).unwrap();
+        assert_eq!(find(errand.id).date.as_deref(), Some("2024-03-05"));
+        assert_eq!(find(weekly.id).date.as_deref(), Some("2024-03-01"));
+        assert_eq!(find(report_task.id).tags, vec!["work", "overdue"]);
+
+        // A second run the same day only finds the recurring task's date
+        // still overdue, and it is already tagged
+        let (ops, _) = overdue_rule_ops(&state, doc_id, &rules, "2024-03-05");
+        assert!(ops.is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+//! Completed pomodoro sessions, appended per machine to
+//! pomodoro.{hostname}.jsonl in the document directory.
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use uuid::Uuid;
+
+use super::document::{device_name, op_files};
+
+/// A pomodoro that ran to the end
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PomodoroSession {
+    pub node_id: Uuid,
+    pub started_at: DateTime<Utc>,
+    pub minutes: u32,
+    pub completed_at: DateTime<Utc>,
+    #[serde(default)]
+    pub device: String,
+}
+
+/// Record a completed session in a document directory
+pub fn append_pomodoro(dir: &Path, session: &PomodoroSession) -> Result<(), String> {
+    let path = dir.join(format!("pomodoro.{}.jsonl", device_name()));
+    let json = serde_json::to_string(session).map_err(|e| format!("Serialize pomodoro: {}", e))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Open {:?}: {}", path, e))?;
+    writeln!(file, "{}", json).map_err(|e| format!("Write pomodoro: {}", e))
+}
+
+/// Read the completed sessions of every machine for a document
+pub fn read_pomodoros(dir: &Path) -> Result<Vec<PomodoroSession>, String> {
+    let mut sessions: Vec<PomodoroSession> = Vec::new();
+    for path in op_files(dir, "pomodoro.")? {
+        let file = File::open(&path).map_err(|e| format!("Open {:?}: {}", path, e))?;
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("Read {:?}: {}", path, e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(session) => sessions.push(session),
+                Err(e) => log::warn!("Skip pomodoro in {:?}: {}", path, e),
+            }
+        }
+    }
+    sessions.sort_by_key(|s| s.completed_at);
+    Ok(sessions)
+}
+
+/// Pomodoros completed on one day
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PomodoroDay {
+    /// Local date (YYYY-MM-DD)
+    pub date: String,
+    pub count: usize,
+    pub minutes: u32,
+}
+
+/// Daily pomodoro counts, plus counts per node
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PomodoroStats {
+    pub total: usize,
+    /// Days with at least one pomodoro, oldest first
+    pub days: Vec<PomodoroDay>,
+    /// Completed pomodoros keyed by node id
+    pub by_node: BTreeMap<String, usize>,
+}
+
+impl PomodoroStats {
+    /// Count the sessions completed within [start, end]
+    pub fn from_sessions<'a>(
+        sessions: impl IntoIterator<Item = &'a PomodoroSession>,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Self {
+        let mut stats = Self::default();
+        let mut days: BTreeMap<String, PomodoroDay> = BTreeMap::new();
+        for session in sessions {
+            if start.is_some_and(|s| session.completed_at < s) || end.is_some_and(|e| session.completed_at > e) {
+                continue;
+            }
+            let date = session.completed_at.with_timezone(&Local).format("%Y-%m-%d").to_string();
+            let day = days.entry(date.clone()).or_insert_with(|| PomodoroDay {
+                date,
+                ..Default::default()
+            });
+            day.count += 1;
+            day.minutes += session.minutes;
+            *stats.by_node.entry(session.node_id.to_string()).or_default() += 1;
+            stats.total += 1;
+        }
+        stats.days = days.into_values().collect();
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_pomodoro_log_and_stats() {
+        let tmp = TempDir::new().unwrap();
+        let node_a = Uuid::now_v7();
+        let node_b = Uuid::now_v7();
+        let noon = Local.with_ymd_and_hms(2024, 5, 6, 12, 0, 0).unwrap().with_timezone(&Utc);
+        let session = |node_id, completed_at: DateTime<Utc>| PomodoroSession {
+            node_id,
+            started_at: completed_at - Duration::minutes(25),
+            minutes: 25,
+            completed_at,
+            device: "laptop".to_string(),
+        };
+        for s in [
+            session(node_a, noon + Duration::days(1)),
+            session(node_a, noon),
+            session(node_b, noon + Duration::hours(1)),
+            session(node_b, noon - Duration::days(10)),
+        ] {
+            append_pomodoro(tmp.path(), &s).unwrap();
+        }
+
+        let sessions = read_pomodoros(tmp.path()).unwrap();
+        assert_eq!(sessions.len(), 4);
+        assert!(sessions.windows(2).all(|w| w[0].completed_at <= w[1].completed_at));
+
+        let stats = PomodoroStats::from_sessions(&sessions, Some(noon - Duration::days(1)), None);
+        assert_eq!(stats.total, 3);
+        assert_eq!(
+            stats.days,
+            vec![
+                PomodoroDay {
+                    date: "2024-05-06".to_string(),
+                    count: 2,
+                    minutes: 50
+                },
+                PomodoroDay {
+                    date: "2024-05-07".to_string(),
+                    count: 1,
+                    minutes: 25
+                },
+            ]
+        );
+        assert_eq!(stats.by_node[&node_a.to_string()], 2);
+    }
+}
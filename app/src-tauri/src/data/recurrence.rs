@@ -0,0 +1,199 @@
+use chrono::NaiveDate;
+use rrule::RRuleSet;
+use uuid::Uuid;
+
+use super::document::DocumentState;
+use super::node::position_between;
+use super::operations::{create_op_with_id, update_op, NodeChanges, Operation};
+
+/// The occurrence of an RRULE anchored on `due` that follows it (YYYY-MM-DD
+/// dates), or None once the rule has ended
+pub fn next_occurrence(rrule: &str, due: NaiveDate) -> Result<Option<NaiveDate>, String> {
+    let rrule_set: RRuleSet = format!("DTSTART:{}\nRRULE:{}", due.format("%Y%m%dT000000Z"), rrule)
+        .parse()
+        .map_err(|e| format!("Invalid RRULE: {}", e))?;
+    // The first occurrence is usually `due` itself
+    Ok(rrule_set
+        .all(2)
+        .dates
+        .iter()
+        .map(|dt| dt.date_naive())
+        .find(|date| *date > due))
+}
+
+/// Ops that complete a recurring node, returning the id of the node holding
+/// the next occurrence.
+///
+/// By default the node itself moves on to its next date and stays unchecked.
+/// With `spawn_instance`, the node is checked off as it is (keeping its note
+/// and its completion in the op log) and a new sibling below it takes the
+/// next date, the recurrence, and a reminder shifted by the same interval.
+/// A node whose rule has ended is simply checked.
+pub fn complete_recurring_ops(
+    state: &DocumentState,
+    node_id: Uuid,
+    spawn_instance: bool,
+    today: NaiveDate,
+) -> Result<(Option<Uuid>, Vec<Operation>), String> {
+    let node = state
+        .nodes
+        .iter()
+        .find(|n| n.id == node_id)
+        .ok_or_else(|| format!("Node not found: {}", node_id))?;
+    let rrule = node
+        .date_recurrence
+        .as_deref()
+        .filter(|r| !r.is_empty())
+        .ok_or("Node is not recurring")?;
+    let due = node
+        .date
+        .as_deref()
+        .and_then(|d| NaiveDate::parse_from_str(d.get(..10).unwrap_or(d), "%Y-%m-%d").ok())
+        .unwrap_or(today);
+
+    let checked = || {
+        update_op(
+            node_id,
+            NodeChanges {
+                is_checked: Some(true),
+                ..Default::default()
+            },
+        )
+    };
+    let Some(next) = next_occurrence(rrule, due)? else {
+        return Ok((None, vec![checked()]));
+    };
+    let next_date = next.format("%Y-%m-%d").to_string();
+    let remind_at = node
+        .reminder_time()
+        .map(|at| (at + (next - due)).to_rfc3339())
+        .unwrap_or_default();
+
+    if !spawn_instance {
+        let advance = update_op(
+            node_id,
+            NodeChanges {
+                date: Some(next_date),
+                remind_at: node.remind_at.as_ref().map(|_| remind_at),
+                ..Default::default()
+            },
+        );
+        return Ok((Some(node_id), vec![advance]));
+    }
+
+    let after = state
+        .sorted_children(node.parent_id)
+        .into_iter()
+        .map(|n| n.position)
+        .find(|p| *p > node.position);
+    let instance_id = Uuid::now_v7();
+    let create = create_op_with_id(
+        instance_id,
+        node.parent_id,
+        position_between(Some(node.position), after),
+        node.content.clone(),
+        node.node_type.clone(),
+    );
+    let fields = update_op(
+        instance_id,
+        NodeChanges {
+            heading_level: node.heading_level,
+            priority: node.priority,
+            color: node.color.clone(),
+            tags: Some(node.tags.clone()).filter(|t| !t.is_empty()),
+            properties: Some(node.properties.clone()).filter(|p| !p.is_empty()),
+            date: Some(next_date),
+            date_recurrence: Some(rrule.to_string()),
+            remind_at: Some(remind_at).filter(|r| !r.is_empty()),
+            ..Default::default()
+        },
+    );
+    // The finished instance keeps its date but no longer recurs
+    let finished = update_op(
+        node_id,
+        NodeChanges {
+            is_checked: Some(true),
+            date_recurrence: Some(String::new()),
+            ..Default::default()
+        },
+    );
+    Ok((Some(instance_id), vec![create, fields, finished]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Node, NodeType};
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn weekly_task() -> (DocumentState, Node) {
+        let mut task = Node::new("Water plants".to_string());
+        task.node_type = NodeType::Checkbox;
+        task.note = Some("Used the blue can".to_string());
+        task.tags = vec!["home".to_string()];
+        task.date = Some("2024-03-04".to_string());
+        task.date_recurrence = Some("FREQ=WEEKLY".to_string());
+        task.remind_at = Some("2024-03-04T08:00:00+00:00".to_string());
+        let mut next = Node::new("Next".to_string());
+        next.position = 1.0;
+        let state = DocumentState {
+            nodes: vec![task.clone(), next],
+        };
+        (state, task)
+    }
+
+    #[test]
+    fn test_next_occurrence() {
+        assert_eq!(next_occurrence("FREQ=DAILY", date("2024-02-28")).unwrap(), Some(date("2024-02-29")));
+        assert_eq!(next_occurrence("FREQ=WEEKLY", date("2024-03-04")).unwrap(), Some(date("2024-03-11")));
+        // A due date off the rule's days moves to the next one
+        assert_eq!(
+            next_occurrence("FREQ=WEEKLY;BYDAY=FR", date("2024-03-04")).unwrap(),
+            Some(date("2024-03-08"))
+        );
+        assert_eq!(next_occurrence("FREQ=DAILY;COUNT=1", date("2024-03-04")).unwrap(), None);
+        assert!(next_occurrence("FREQ=SOMETIMES", date("2024-03-04")).is_err());
+    }
+
+    #[test]
+    fn test_complete_recurring_in_place() {
+        let (mut state, task) = weekly_task();
+        let (next_id, ops) = complete_recurring_ops(&state, task.id, false, date("2024-03-05")).unwrap();
+        assert_eq!(next_id, Some(task.id));
+        for op in &ops {
+            op.apply(&mut state);
+        }
+        let node = &state.nodes[0];
+        assert!(!node.is_checked);
+        assert_eq!(node.date.as_deref(), Some("2024-03-11"));
+        assert_eq!(node.remind_at.as_deref(), Some("2024-03-11T08:00:00+00:00"));
+    }
+
+    #[test]
+    fn test_complete_recurring_spawns_instance() {
+        let (mut state, task) = weekly_task();
+        let (next_id, ops) = complete_recurring_ops(&state, task.id, true, date("2024-03-05")).unwrap();
+        for op in &ops {
+            op.apply(&mut state);
+        }
+
+        let done = state.nodes.iter().find(|n| n.id == task.id).unwrap();
+        assert!(done.is_checked);
+        assert_eq!(done.date.as_deref(), Some("2024-03-04"));
+        assert_eq!(done.date_recurrence, None);
+        assert_eq!(done.note.as_deref(), Some("Used the blue can"));
+
+        let instance = state.nodes.iter().find(|n| Some(n.id) == next_id).unwrap();
+        assert!(!instance.is_checked);
+        assert_eq!(instance.node_type, NodeType::Checkbox);
+        assert_eq!(instance.note, None);
+        assert_eq!(instance.tags, vec!["home"]);
+        assert_eq!(instance.date.as_deref(), Some("2024-03-11"));
+        assert_eq!(instance.date_recurrence.as_deref(), Some("FREQ=WEEKLY"));
+        assert_eq!(instance.remind_at.as_deref(), Some("2024-03-11T08:00:00+00:00"));
+        assert!(instance.position > done.position && instance.position < 1.0);
+    }
+}
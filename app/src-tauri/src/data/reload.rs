@@ -0,0 +1,249 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use uuid::Uuid;
+
+use super::document::{op_files, read_op_records, Document, DocumentMeta, DocumentState};
+use super::node::Node;
+use super::operations::{OpRecord, Operation};
+use super::snapshots::{diff_states, SnapshotDiff};
+
+/// What changed in a document when external changes (ops or a compacted
+/// state.json from another machine) were picked up
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadDelta {
+    pub document_id: String,
+    /// state.json was replaced, so the whole document was reloaded
+    pub full_reload: bool,
+    /// Nodes that were added or changed
+    pub changed: Vec<Node>,
+    /// Nodes that no longer exist
+    pub deleted: Vec<Uuid>,
+}
+
+impl ReloadDelta {
+    /// The nodes that differ between two states of a document
+    pub fn between(document_id: Uuid, full_reload: bool, before: &DocumentState, after: &DocumentState) -> Self {
+        let diff = diff_states(before, after);
+        Self {
+            document_id: document_id.to_string(),
+            full_reload,
+            changed: diff.added.into_iter().chain(diff.changed.into_iter().map(|c| c.after)).collect(),
+            deleted: diff.removed.iter().map(|n| n.id).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.deleted.is_empty()
+    }
+}
+
+/// Remote changes on disk that haven't been applied to a loaded document,
+/// for a "remote changes pending" banner
+#[derive(Debug, Clone, Serialize)]
+pub struct Divergence {
+    pub document_id: String,
+    /// Another machine compacted, so reloading replaces the whole state
+    pub full_reload: bool,
+    /// Devices the unapplied ops came from (unknown after a compaction)
+    pub devices: Vec<String>,
+    /// Ops waiting to be applied
+    pub pending_ops: usize,
+    /// What reloading would change
+    pub diff: SnapshotDiff,
+}
+
+impl Divergence {
+    pub fn is_empty(&self) -> bool {
+        self.diff.added.is_empty() && self.diff.removed.is_empty() && self.diff.changed.is_empty()
+    }
+}
+
+/// What appeared on disk since a document was last read
+enum ExternalChanges {
+    /// state.json was replaced or a pending file shrank or vanished
+    Replaced,
+    /// Ops appended to pending files, with the new per-file op counts
+    Appended {
+        records: Vec<OpRecord>,
+        applied_ops: HashMap<String, usize>,
+    },
+}
+
+impl Document {
+    fn external_changes(&self) -> Result<ExternalChanges, String> {
+        let state_replaced = fs::metadata(self.dir.join("state.json"))
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| modified > self.last_load_time);
+        if state_replaced {
+            return Ok(ExternalChanges::Replaced);
+        }
+
+        let mut records = Vec::new();
+        let mut applied_ops = HashMap::new();
+        for path in op_files(&self.dir, "pending.")? {
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default()
+                .to_string();
+            let file_records = read_op_records(&path)?;
+            let applied = self.applied_ops.get(&name).copied().unwrap_or(0);
+            if file_records.len() < applied {
+                return Ok(ExternalChanges::Replaced);
+            }
+            applied_ops.insert(name, file_records.len());
+            records.extend(file_records.into_iter().skip(applied));
+        }
+        if self.applied_ops.keys().any(|name| !applied_ops.contains_key(name)) {
+            return Ok(ExternalChanges::Replaced);
+        }
+        Ok(ExternalChanges::Appended { records, applied_ops })
+    }
+
+    /// Pick up changes made on disk by other machines. Only ops appended to
+    /// pending files since they were last read are applied; a replaced
+    /// state.json (or a pending file that shrank or vanished, i.e. another
+    /// machine compacted) falls back to a full reload. Returns None when
+    /// nothing changed.
+    pub fn sync_external_changes(&mut self) -> Result<Option<ReloadDelta>, String> {
+        let before = self.state.clone();
+
+        let full_reload = match self.external_changes()? {
+            ExternalChanges::Replaced => {
+                self.reload()?;
+                true
+            }
+            ExternalChanges::Appended { records, applied_ops } => {
+                let mut new_ops: Vec<Operation> = records.into_iter().map(|r| r.op).collect();
+                self.pending_op_count += new_ops.len();
+                new_ops.sort_by_key(|op| op.updated_at());
+                for op in &new_ops {
+                    op.apply(&mut self.state);
+                }
+                self.applied_ops = applied_ops;
+                self.meta = DocumentMeta::load(&self.dir)?;
+                self.last_load_time = std::time::SystemTime::now();
+                false
+            }
+        };
+
+        let delta = ReloadDelta::between(self.id, full_reload, &before, &self.state);
+        Ok(if delta.is_empty() { None } else { Some(delta) })
+    }
+
+    /// What `sync_external_changes` would change, without applying anything
+    pub fn divergence(&self) -> Result<Divergence, String> {
+        let (full_reload, devices, pending_ops, after) = match self.external_changes()? {
+            ExternalChanges::Replaced => (true, Vec::new(), 0, Document::load(self.dir.clone())?.state),
+            ExternalChanges::Appended { mut records, .. } => {
+                let mut devices: Vec<String> = records.iter().filter_map(|r| r.device.clone()).collect();
+                devices.sort();
+                devices.dedup();
+                records.sort_by_key(|r| r.op.updated_at());
+                let mut after = self.state.clone();
+                for record in &records {
+                    record.op.apply(&mut after);
+                }
+                (false, devices, records.len(), after)
+            }
+        };
+        Ok(Divergence {
+            document_id: self.id.to_string(),
+            full_reload,
+            devices,
+            pending_ops,
+            diff: diff_states(&self.state, &after),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{create_op, delete_op, update_op, NodeChanges};
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn append_external(doc: &Document, device: &str, op: &Operation) {
+        let record = OpRecord {
+            op: op.clone(),
+            device: Some(device.to_string()),
+        };
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(doc.dir.join(format!("pending.{}.jsonl", device)))
+            .unwrap();
+        writeln!(file, "{}", serde_json::to_string(&record).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_sync_external_changes_applies_only_new_ops() {
+        let tmp = TempDir::new().unwrap();
+        let mut doc = Document::create(tmp.path().join(Uuid::now_v7().to_string())).unwrap();
+
+        let local = create_op(None, 0.0, "Local".to_string());
+        doc.append_op(&local).unwrap();
+        local.apply(&mut doc.state);
+        let local_id = match local {
+            Operation::Create { id, .. } => id,
+            _ => unreachable!(),
+        };
+
+        // Our own ops are not external changes
+        assert!(doc.sync_external_changes().unwrap().is_none());
+
+        let remote = create_op(None, 1.0, "Remote".to_string());
+        append_external(&doc, "other-machine", &remote);
+        append_external(
+            &doc,
+            "other-machine",
+            &update_op(
+                local_id,
+                NodeChanges {
+                    content: Some("Edited remotely".to_string()),
+                    ..Default::default()
+                },
+            ),
+        );
+
+        // The divergence previews the same changes without applying them
+        let divergence = doc.divergence().unwrap();
+        assert_eq!(divergence.devices, vec!["other-machine"]);
+        assert_eq!(divergence.pending_ops, 2);
+        assert_eq!(divergence.diff.added.len(), 1);
+        assert_eq!(divergence.diff.changed[0].fields, vec!["content"]);
+        assert_eq!(doc.state.nodes.len(), 1);
+
+        let delta = doc.sync_external_changes().unwrap().unwrap();
+        assert!(!delta.full_reload);
+        let mut contents: Vec<&str> = delta.changed.iter().map(|n| n.content.as_str()).collect();
+        contents.sort();
+        assert_eq!(contents, vec!["Edited remotely", "Remote"]);
+        assert!(delta.deleted.is_empty());
+        assert!(doc.sync_external_changes().unwrap().is_none());
+
+        append_external(&doc, "other-machine", &delete_op(local_id));
+        let delta = doc.sync_external_changes().unwrap().unwrap();
+        assert_eq!(delta.deleted, vec![local_id]);
+        assert_eq!(doc.state.nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_sync_external_changes_reloads_after_remote_compaction() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join(Uuid::now_v7().to_string());
+        let mut doc = Document::create(dir.clone()).unwrap();
+        append_external(&doc, "other-machine", &create_op(None, 0.0, "Remote".to_string()));
+        assert!(doc.sync_external_changes().unwrap().is_some());
+
+        // Another machine compacts: its pending file is folded into state.json
+        let mut other = Document::load(dir).unwrap();
+        other.compact().unwrap();
+
+        // Nothing changed in content, so no delta, but the op counts are reset
+        assert!(doc.sync_external_changes().unwrap().is_none());
+        assert!(doc.applied_ops.is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::document::DocumentState;
+use super::html::{decode_entities, escape_text};
+use super::operations::{update_op, NodeChanges, Operation};
+
+/// How the search text is interpreted
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplaceMode {
+    /// Match the text exactly
+    #[default]
+    Literal,
+    /// Match a regular expression; the replacement may use $1, ${name}
+    Regex,
+}
+
+/// Where to replace: one document or every document
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplaceScope {
+    Document(String),
+    Workspace,
+}
+
+/// One node whose content and/or note would change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplaceMatch {
+    pub doc_id: String,
+    pub node_id: String,
+    /// Number of matches replaced in this node
+    pub count: usize,
+    pub content_before: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note_before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note_after: Option<String>,
+    /// The document is locked, so this change is only previewed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub locked: bool,
+}
+
+/// Compiled search-and-replace
+pub struct Replacer {
+    regex: Regex,
+    replacement: String,
+}
+
+impl Replacer {
+    pub fn new(query: &str, replacement: &str, mode: ReplaceMode, case_sensitive: bool) -> Result<Self, String> {
+        if query.is_empty() {
+            return Err("Search text is empty".to_string());
+        }
+        let (pattern, replacement) = match mode {
+            ReplaceMode::Literal => (regex::escape(query), replacement.replace('$', "$$")),
+            ReplaceMode::Regex => (query.to_string(), replacement.to_string()),
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!case_sensitive)
+            .build()
+            .map_err(|e| format!("Invalid pattern: {}", e))?;
+        Ok(Self { regex, replacement })
+    }
+
+    /// Replace in plain text, returning the new text and the number of matches
+    fn replace_text(&self, text: &str) -> (String, usize) {
+        let count = self.regex.find_iter(text).count();
+        if count == 0 {
+            return (text.to_string(), 0);
+        }
+        (self.regex.replace_all(text, self.replacement.as_str()).into_owned(), count)
+    }
+
+    /// Replace in rich-text HTML, touching only the text between tags so markup
+    /// and attributes (links, mentions) are left intact. Text is matched with
+    /// its entities decoded (so `a & b` finds `a &amp; b`), and re-escaped
+    /// where it changed
+    fn replace_html(&self, html: &str) -> (String, usize) {
+        let mut result = String::with_capacity(html.len());
+        let mut total = 0;
+        let mut rest = html;
+
+        while !rest.is_empty() {
+            let text_end = rest.find('<').unwrap_or(rest.len());
+            let text = &rest[..text_end];
+            let (replaced, count) = self.replace_text(&decode_entities(text));
+            if count > 0 {
+                result.push_str(&escape_text(&replaced));
+                total += count;
+            } else {
+                result.push_str(text);
+            }
+            rest = &rest[text_end..];
+
+            if !rest.is_empty() {
+                let tag_end = rest.find('>').map(|i| i + 1).unwrap_or(rest.len());
+                result.push_str(&rest[..tag_end]);
+                rest = &rest[tag_end..];
+            }
+        }
+
+        (result, total)
+    }
+
+    /// Find every node in a document that would change, with the Update ops
+    /// that make the change. Encrypted nodes are left alone: their content is
+    /// ciphertext, and changing it would make them impossible to unlock.
+    pub fn plan(&self, doc_id: &Uuid, state: &DocumentState) -> (Vec<ReplaceMatch>, Vec<Operation>) {
+        let mut matches = Vec::new();
+        let mut ops = Vec::new();
+
+        for node in state.nodes.iter().filter(|n| !n.encrypted) {
+            let (content, content_count) = self.replace_html(&node.content);
+            let (note, note_count) = match node.note {
+                Some(ref note) => {
+                    let (replaced, count) = self.replace_text(note);
+                    (Some(replaced), count)
+                }
+                None => (None, 0),
+            };
+            if content_count + note_count == 0 {
+                continue;
+            }
+
+            let content_after = (content_count > 0).then_some(content);
+            let note_after = if note_count > 0 { note } else { None };
+            ops.push(update_op(
+                node.id,
+                NodeChanges {
+                    content: content_after.clone(),
+                    note: note_after.clone(),
+                    ..Default::default()
+                },
+            ));
+            matches.push(ReplaceMatch {
+                doc_id: doc_id.to_string(),
+                node_id: node.id.to_string(),
+                count: content_count + note_count,
+                content_before: node.content.clone(),
+                content_after,
+                note_before: note_after.as_ref().and(node.note.clone()),
+                note_after,
+                locked: false,
+            });
+        }
+
+        (matches, ops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{encrypt_node_op, unlock_node, Node, NodeSecret};
+
+    #[test]
+    fn test_replace_skips_markup() {
+        let replacer = Replacer::new("link", "ref", ReplaceMode::Literal, true).unwrap();
+        let (html, count) = replacer.replace_html(r#"a <a href="link">link</a> link"#);
+        assert_eq!(html, r#"a <a href="link">ref</a> ref"#);
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_replace_html_entities() {
+        let replacer = Replacer::new("a & b", "x < y", ReplaceMode::Literal, true).unwrap();
+        let (html, count) = replacer.replace_html("<b>a &amp; b</b> and a &amp; b &gt; c");
+        assert_eq!(html, "<b>x &lt; y</b> and x &lt; y &gt; c");
+        assert_eq!(count, 2);
+
+        // Text without a match keeps its entities as written
+        let (html, count) = replacer.replace_html("<i>&lt;tag&gt;</i>");
+        assert_eq!(html, "<i>&lt;tag&gt;</i>");
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_literal_and_regex_modes() {
+        let literal = Replacer::new("$5.00", "$6.00", ReplaceMode::Literal, true).unwrap();
+        assert_eq!(literal.replace_text("costs $5.00"), ("costs $6.00".to_string(), 1));
+
+        let regex = Replacer::new(r"(\d{4})-(\d{2})", "$2/$1", ReplaceMode::Regex, true).unwrap();
+        assert_eq!(regex.replace_text("due 2024-03"), ("due 03/2024".to_string(), 1));
+
+        let insensitive = Replacer::new("todo", "DONE", ReplaceMode::Literal, false).unwrap();
+        assert_eq!(insensitive.replace_text("Todo todo"), ("DONE DONE".to_string(), 2));
+
+        assert!(Replacer::new("(", "", ReplaceMode::Regex, true).is_err());
+        assert!(Replacer::new("", "x", ReplaceMode::Literal, true).is_err());
+    }
+
+    #[test]
+    fn test_plan_and_apply() {
+        let doc_id = Uuid::now_v7();
+        let mut hit = Node::new("Call Bob".to_string());
+        hit.note = Some("Bob's number".to_string());
+        hit.updated_at = chrono::Utc::now() - chrono::Duration::seconds(1);
+        let mut note_only = Node::new("Lunch".to_string());
+        note_only.note = Some("with Bob".to_string());
+        note_only.updated_at = hit.updated_at;
+        let mut state = DocumentState {
+            nodes: vec![hit, note_only, Node::new("Nothing".to_string())],
+        };
+
+        let replacer = Replacer::new("Bob", "Rob", ReplaceMode::Literal, true).unwrap();
+        let (matches, ops) = replacer.plan(&doc_id, &state);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].count, 2);
+        assert_eq!(matches[0].content_after.as_deref(), Some("Call Rob"));
+        assert_eq!(matches[1].content_after, None);
+        assert_eq!(matches[1].note_before.as_deref(), Some("with Bob"));
+
+        for op in &ops {
+            op.apply(&mut state);
+        }
+        assert_eq!(state.nodes[0].content, "Call Rob");
+        assert_eq!(state.nodes[0].note.as_deref(), Some("Rob's number"));
+        assert_eq!(state.nodes[1].content, "Lunch");
+        assert_eq!(state.nodes[1].note.as_deref(), Some("with Rob"));
+        assert!(replacer.plan(&doc_id, &state).0.is_empty());
+    }
+
+    #[test]
+    fn test_plan_skips_encrypted_nodes() {
+        let doc_id = Uuid::now_v7();
+        let node = Node::new("PIN 1234".to_string());
+        let secret = NodeSecret {
+            content: node.content.clone(),
+            note: None,
+        };
+        let mut state = DocumentState { nodes: vec![node.clone()] };
+        encrypt_node_op(&node, &secret, "passphrase").unwrap().apply(&mut state);
+
+        // Any character at all would match the ciphertext
+        let replacer = Replacer::new(".", "x", ReplaceMode::Regex, true).unwrap();
+        let (matches, ops) = replacer.plan(&doc_id, &state);
+        assert!(matches.is_empty());
+        for op in &ops {
+            op.apply(&mut state);
+        }
+        assert_eq!(unlock_node(&state.nodes[0], "passphrase").unwrap(), secret);
+    }
+}
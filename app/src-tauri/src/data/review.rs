@@ -0,0 +1,372 @@
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use uuid::Uuid;
+
+use super::document::{op_log, DocumentState, OpLogEntry};
+use super::node::NodeType;
+use super::operations::Operation;
+use crate::search::strip_html;
+
+/// How many documents the "most edited" section lists
+const MOST_EDITED_LIMIT: usize = 5;
+
+/// A task checked off, as recorded in a document's op log
+#[derive(Debug, Clone, Serialize)]
+pub struct Completion {
+    pub node_id: Uuid,
+    pub completed_at: DateTime<Utc>,
+    pub device: String,
+}
+
+/// Tasks checked off in a document within [start, end], oldest first. Taken
+/// from the op log (pending and archived ops), so it survives compaction.
+pub fn completion_history(
+    dir: &Path,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> Result<Vec<Completion>, String> {
+    Ok(completions(&op_log(dir, start, end)?))
+}
+
+fn completions(entries: &[OpLogEntry]) -> Vec<Completion> {
+    entries
+        .iter()
+        .filter_map(|entry| match &entry.op {
+            Operation::Update { id, changes, .. } if changes.is_checked == Some(true) => Some(Completion {
+                node_id: *id,
+                completed_at: entry.updated_at,
+                device: entry.device.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// When each task in an op log was last checked off
+fn latest_completions(ops: &[OpLogEntry]) -> HashMap<Uuid, DateTime<Utc>> {
+    let mut completed_at = HashMap::new();
+    for completion in completions(ops) {
+        completed_at.insert(completion.node_id, completion.completed_at);
+    }
+    completed_at
+}
+
+/// A task completed in a period, for the completed-items changelog
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletedTask {
+    pub document: String,
+    pub content: String,
+    pub completed_at: DateTime<Utc>,
+}
+
+/// Checked tasks in a document completed within [start, end]. `ops` is the
+/// document's op log up to `end`; tasks with no completion in it (imported
+/// already checked, or from before ops were archived) count at their last
+/// update.
+pub fn completed_tasks(
+    title: &str,
+    state: &DocumentState,
+    ops: &[OpLogEntry],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<CompletedTask> {
+    let completed_at = latest_completions(ops);
+    state
+        .nodes
+        .iter()
+        .filter(|node| node.is_checked)
+        .filter_map(|node| {
+            let at = completed_at.get(&node.id).copied().unwrap_or(node.updated_at);
+            (at >= start && at <= end).then(|| CompletedTask {
+                document: title.to_string(),
+                content: strip_html(&node.content).trim().to_string(),
+                completed_at: at,
+            })
+        })
+        .collect()
+}
+
+/// Output format of the completed-items changelog
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangelogFormat {
+    #[default]
+    Markdown,
+    Text,
+    Csv,
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// A flat list of completed tasks grouped by (local) completion date, oldest
+/// first, e.g. for standups or invoicing
+pub fn completed_changelog(
+    tasks: &[CompletedTask],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    format: ChangelogFormat,
+) -> String {
+    let mut tasks: Vec<&CompletedTask> = tasks.iter().collect();
+    tasks.sort_by(|a, b| a.completed_at.cmp(&b.completed_at).then_with(|| a.content.cmp(&b.content)));
+
+    let mut out = match format {
+        ChangelogFormat::Markdown => format!("# Completed: {} – {}\n", local_date(&start), local_date(&end)),
+        ChangelogFormat::Text => format!("Completed {} – {}\n", local_date(&start), local_date(&end)),
+        ChangelogFormat::Csv => "date,document,task,completed_at\n".to_string(),
+    };
+    let mut current_date = None;
+    for task in tasks {
+        let date = local_date(&task.completed_at);
+        if format == ChangelogFormat::Csv {
+            out.push_str(&format!(
+                "{},{},{},{}\n",
+                date,
+                csv_field(&task.document),
+                csv_field(&task.content),
+                task.completed_at.to_rfc3339()
+            ));
+            continue;
+        }
+        if current_date.as_ref() != Some(&date) {
+            match format {
+                ChangelogFormat::Markdown => out.push_str(&format!("\n## {}\n\n", date)),
+                _ => out.push_str(&format!("\n{}\n", date)),
+            }
+            current_date = Some(date);
+        }
+        match format {
+            ChangelogFormat::Markdown => out.push_str(&format!("- {} — *{}*\n", task.content, task.document)),
+            _ => out.push_str(&format!("  {} ({})\n", task.content, task.document)),
+        }
+    }
+    out
+}
+
+/// A line in a review section
+#[derive(Debug, Clone)]
+struct ReviewItem {
+    document: String,
+    content: String,
+    /// Completion, creation or due date (YYYY-MM-DD)
+    date: String,
+}
+
+/// A GTD-style review of a period across documents, rendered as Markdown
+#[derive(Debug, Clone)]
+pub struct Review {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    /// Tasks dated before this (YYYY-MM-DD) and still open are overdue
+    today: String,
+    completed: Vec<ReviewItem>,
+    created: Vec<ReviewItem>,
+    overdue: Vec<ReviewItem>,
+    /// Document title and number of ops in the period
+    edits: Vec<(String, usize)>,
+}
+
+fn local_date(at: &DateTime<Utc>) -> String {
+    at.with_timezone(&Local).format("%Y-%m-%d").to_string()
+}
+
+impl Review {
+    pub fn new(start: DateTime<Utc>, end: DateTime<Utc>, today: &str) -> Self {
+        Self {
+            start,
+            end,
+            today: today.to_string(),
+            completed: Vec::new(),
+            created: Vec::new(),
+            overdue: Vec::new(),
+            edits: Vec::new(),
+        }
+    }
+
+    /// Add a document, given its op log for the review period
+    pub fn add_document(&mut self, title: &str, state: &DocumentState, ops: &[OpLogEntry]) {
+        let nodes: HashMap<Uuid, _> = state.nodes.iter().map(|n| (n.id, n)).collect();
+        let item = |content: &str, date: String| ReviewItem {
+            document: title.to_string(),
+            content: strip_html(content),
+            date,
+        };
+
+        // A task checked more than once counts once, at its latest completion;
+        // tasks unchecked since don't count
+        for (node_id, at) in latest_completions(ops) {
+            if let Some(node) = nodes.get(&node_id).filter(|n| n.is_checked) {
+                self.completed.push(item(&node.content, local_date(&at)));
+            }
+        }
+
+        for node in &state.nodes {
+            if node.node_type == NodeType::Checkbox && node.created_at >= self.start && node.created_at <= self.end {
+                self.created.push(item(&node.content, local_date(&node.created_at)));
+            }
+            let due = node.date.as_deref().filter(|d| !d.is_empty()).map(|d| d.get(..10).unwrap_or(d));
+            if let (false, Some(due)) = (node.is_checked, due) {
+                if due < self.today.as_str() {
+                    self.overdue.push(item(&node.content, due.to_string()));
+                }
+            }
+        }
+
+        if !ops.is_empty() {
+            self.edits.push((title.to_string(), ops.len()));
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "# Weekly review: {} – {}\n",
+            local_date(&self.start),
+            local_date(&self.end)
+        );
+
+        let sections = [
+            ("Completed", &self.completed, ""),
+            ("Created", &self.created, ""),
+            ("Overdue", &self.overdue, "due "),
+        ];
+        for (heading, items, date_prefix) in sections {
+            let mut items = items.clone();
+            items.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.content.cmp(&b.content)));
+            out.push_str(&format!("\n## {} ({})\n\n", heading, items.len()));
+            if items.is_empty() {
+                out.push_str("_None_\n");
+            }
+            for item in items {
+                out.push_str(&format!(
+                    "- {} — *{}* ({}{})\n",
+                    item.content, item.document, date_prefix, item.date
+                ));
+            }
+        }
+
+        let mut edits = self.edits.clone();
+        edits.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        out.push_str("\n## Most edited\n\n");
+        if edits.is_empty() {
+            out.push_str("_None_\n");
+        }
+        for (i, (title, count)) in edits.iter().take(MOST_EDITED_LIMIT).enumerate() {
+            let noun = if *count == 1 { "change" } else { "changes" };
+            out.push_str(&format!("{}. {} — {} {}\n", i + 1, title, count, noun));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{update_op, Node, NodeChanges};
+    use chrono::TimeZone;
+
+    fn entry(op: Operation, at: DateTime<Utc>) -> OpLogEntry {
+        OpLogEntry {
+            device: "laptop".to_string(),
+            updated_at: at,
+            op,
+        }
+    }
+
+    fn check(id: Uuid, checked: bool) -> Operation {
+        update_op(
+            id,
+            NodeChanges {
+                is_checked: Some(checked),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_completed_changelog() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2024, 1, 21, 12, 0, 0).unwrap();
+        let day = |d| Utc.with_ymd_and_hms(2024, 1, d, 12, 0, 0).unwrap();
+
+        let task = |content: &str, updated_at| {
+            let mut node = Node::new(content.to_string());
+            node.is_checked = true;
+            node.updated_at = updated_at;
+            node
+        };
+        // Completed in range but edited after: the completion counts
+        let invoice = task("Send <i>invoice</i>, v2", day(25));
+        let review = task("Review PR", day(10));
+        // No completion in the log: its last update counts
+        let imported = task("Imported done item", day(16));
+        let old = task("Done long ago", day(1));
+        let ops = vec![
+            entry(check(invoice.id, true), day(17)),
+            entry(check(review.id, true), day(16)),
+            entry(check(old.id, true), day(1)),
+        ];
+        let state = DocumentState {
+            nodes: vec![invoice, review, imported, old, Node::new("Not a task".to_string())],
+        };
+        let tasks = completed_tasks("Client", &state, &ops, start, end);
+        assert_eq!(tasks.len(), 3);
+
+        let markdown = completed_changelog(&tasks, start, end, ChangelogFormat::Markdown);
+        let date = |d| local_date(&day(d));
+        assert!(markdown.contains(&format!(
+            "## {}\n\n- Imported done item — *Client*\n- Review PR — *Client*\n",
+            date(16)
+        )));
+        assert!(markdown.contains(&format!("## {}\n\n- Send invoice, v2 — *Client*\n", date(17))));
+
+        let csv = completed_changelog(&tasks, start, end, ChangelogFormat::Csv);
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.contains(&format!("{},Client,\"Send invoice, v2\",", date(17))));
+    }
+
+    #[test]
+    fn test_review_report() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2024, 1, 21, 12, 0, 0).unwrap();
+        let mid = Utc.with_ymd_and_hms(2024, 1, 17, 12, 0, 0).unwrap();
+
+        let task = |content: &str, created_at| {
+            let mut node = Node::new(content.to_string());
+            node.node_type = NodeType::Checkbox;
+            node.created_at = created_at;
+            node
+        };
+        let mut done = task("<b>Ship</b> release", start - chrono::Duration::days(30));
+        done.is_checked = true;
+        let mut reopened = task("Reopened", mid);
+        reopened.is_checked = false;
+        let mut late = task("Pay rent", start - chrono::Duration::days(30));
+        late.date = Some("2024-01-10".to_string());
+        let ops = vec![
+            entry(check(done.id, true), mid),
+            entry(check(reopened.id, true), mid),
+            entry(check(reopened.id, false), mid),
+        ];
+        let state = DocumentState {
+            nodes: vec![done, reopened, late],
+        };
+
+        let mut review = Review::new(start, end, "2024-01-21");
+        review.add_document("Work", &state, &ops);
+        review.add_document("Quiet", &DocumentState::new(), &[]);
+        let report = review.to_markdown();
+
+        assert!(report.contains("## Completed (1)\n\n- Ship release — *Work*"));
+        assert!(report.contains("## Created (1)\n\n- Reopened — *Work*"));
+        assert!(report.contains("## Overdue (1)\n\n- Pay rent — *Work* (due 2024-01-10)"));
+        assert!(report.contains("1. Work — 3 changes"));
+        assert!(!report.contains("Quiet"));
+    }
+}
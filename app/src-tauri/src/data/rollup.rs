@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::document::DocumentState;
+use super::node::{Node, NodeType};
+
+/// Task counts over a node's descendants (the node itself is not included)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskRollup {
+    /// Descendant checkboxes
+    pub total: usize,
+    /// Descendant checkboxes that are checked
+    pub checked: usize,
+    /// Earliest due date among unchecked descendants
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_due: Option<String>,
+}
+
+impl TaskRollup {
+    /// Fold a descendant's own task state into this rollup
+    fn add_node(&mut self, node: &Node) {
+        if node.node_type == NodeType::Checkbox || node.is_checked {
+            self.total += 1;
+            if node.is_checked {
+                self.checked += 1;
+            }
+        }
+        if !node.is_checked {
+            if let Some(ref date) = node.date {
+                self.add_due(date);
+            }
+        }
+    }
+
+    /// Fold a child's rollup into this one
+    fn merge(&mut self, other: &TaskRollup) {
+        self.total += other.total;
+        self.checked += other.checked;
+        if let Some(ref date) = other.next_due {
+            self.add_due(date);
+        }
+    }
+
+    fn add_due(&mut self, date: &str) {
+        if self.next_due.as_deref().map_or(true, |d| date < d) {
+            self.next_due = Some(date.to_string());
+        }
+    }
+}
+
+/// Compute rollups for every node in one bottom-up pass. Nodes with no tasks
+/// or due dates below them are omitted.
+pub fn task_rollups(state: &DocumentState) -> HashMap<Uuid, TaskRollup> {
+    let by_id = state.nodes_by_id();
+    let mut children_of: HashMap<Uuid, Vec<&Node>> = HashMap::new();
+    let mut order: Vec<&Node> = Vec::with_capacity(state.nodes.len());
+    for node in &state.nodes {
+        match node.parent_id.filter(|p| by_id.contains_key(p)) {
+            Some(parent_id) => children_of.entry(parent_id).or_default().push(node),
+            None => order.push(node),
+        }
+    }
+
+    // Breadth-first from the roots, then walk backwards so children come first
+    let mut i = 0;
+    while i < order.len() {
+        if let Some(children) = children_of.get(&order[i].id) {
+            order.extend(children.iter().copied());
+        }
+        i += 1;
+    }
+
+    let mut rollups: HashMap<Uuid, TaskRollup> = HashMap::new();
+    for node in order.iter().rev() {
+        let Some(parent_id) = node.parent_id.filter(|p| by_id.contains_key(p)) else {
+            continue;
+        };
+        let own = rollups.get(&node.id).cloned();
+        let parent = rollups.entry(parent_id).or_default();
+        parent.add_node(node);
+        if let Some(own) = own {
+            parent.merge(&own);
+        }
+    }
+
+    rollups.retain(|_, r| r.total > 0 || r.next_due.is_some());
+    rollups
+}
+
+/// Compute the rollup for a single node's subtree
+pub fn task_rollup(state: &DocumentState, node_id: Uuid) -> TaskRollup {
+    let by_id = state.nodes_by_id();
+    let mut rollup = TaskRollup::default();
+    for id in state.subtree_ids(node_id).into_iter().skip(1) {
+        if let Some(node) = by_id.get(&id) {
+            rollup.add_node(node);
+        }
+    }
+    rollup
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(parent: &Node, content: &str, checked: bool, date: Option<&str>) -> Node {
+        let mut node = Node::new_child(parent.id, 0.0, content.to_string());
+        node.node_type = NodeType::Checkbox;
+        node.is_checked = checked;
+        node.date = date.map(String::from);
+        node
+    }
+
+    #[test]
+    fn test_task_rollups() {
+        let project = Node::new("Project".to_string());
+        let phase = Node::new_child(project.id, 0.0, "Phase 1".to_string());
+        let a = task(&phase, "A", true, Some("2024-01-01"));
+        let b = task(&phase, "B", false, Some("2024-03-01"));
+        let c = task(&project, "C", false, Some("2024-02-01"));
+        let d = task(&c, "D", false, None);
+        let state = DocumentState {
+            nodes: vec![project.clone(), phase.clone(), a, b, c.clone(), d],
+        };
+
+        let rollups = task_rollups(&state);
+        let expected = TaskRollup {
+            total: 4,
+            checked: 1,
+            // A is done, so its earlier date does not count
+            next_due: Some("2024-02-01".to_string()),
+        };
+        assert_eq!(rollups[&project.id], expected);
+        assert_eq!(task_rollup(&state, project.id), expected);
+
+        assert_eq!(rollups[&phase.id].total, 2);
+        assert_eq!(rollups[&phase.id].next_due, Some("2024-03-01".to_string()));
+        assert_eq!(rollups[&c.id].total, 1);
+        assert_eq!(rollups[&c.id].next_due, None);
+        assert_eq!(task_rollup(&state, c.id), rollups[&c.id]);
+
+        // Leaves have nothing below them
+        assert_eq!(rollups.len(), 3);
+    }
+}
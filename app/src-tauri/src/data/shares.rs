@@ -0,0 +1,249 @@
+//! Read-only share links. A share gives a document, or one node's subtree, a
+//! secret token, and the app writes it as a static bundle (index.html and
+//! data.json) to shares/<token>/ in the data directory, which the thin server
+//! serves at /share/<token>. Bundles are rewritten in the background once
+//! edits to the shared document pause (see `ShareRefresher`), and deleted
+//! when the share is revoked. The list of shares is kept in shares.json.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::document::{data_dir, DocumentState};
+use super::html::strip_html;
+use super::node::Node;
+use crate::import_export::{generate_html, generate_json_backup};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Share {
+    pub token: String,
+    pub document_id: String,
+    /// Root of the shared subtree; None shares the whole document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<String>,
+    pub title: String,
+    pub created_at: DateTime<Utc>,
+}
+
+fn shares_path() -> PathBuf {
+    data_dir().join("shares.json")
+}
+
+/// Directory holding a share's bundle
+pub fn share_dir(token: &str) -> PathBuf {
+    data_dir().join("shares").join(token)
+}
+
+/// Load the shares (none if none were made)
+pub fn load_shares() -> Result<Vec<Share>, String> {
+    let path = shares_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = fs::read_to_string(&path).map_err(|e| format!("Read shares: {}", e))?;
+    serde_json::from_str(&contents).map_err(|e| format!("Parse shares: {}", e))
+}
+
+fn save_shares(shares: &[Share]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(shares).map_err(|e| format!("Serialize shares: {}", e))?;
+    fs::write(shares_path(), json).map_err(|e| format!("Write shares: {}", e))
+}
+
+/// The nodes a share publishes: the whole document, or the subtree of
+/// `node_id` with that node moved to the top level
+pub fn shared_nodes(state: &DocumentState, node_id: Option<Uuid>) -> Result<Vec<Node>, String> {
+    let Some(root_id) = node_id else {
+        return Ok(state.nodes.clone());
+    };
+    if !state.nodes.iter().any(|n| n.id == root_id) {
+        return Err(format!("Node not found: {}", root_id));
+    }
+    let ids: std::collections::HashSet<Uuid> = state.subtree_ids(root_id).into_iter().collect();
+    Ok(state
+        .nodes
+        .iter()
+        .filter(|n| ids.contains(&n.id))
+        .map(|n| {
+            let mut node = n.clone();
+            if node.id == root_id {
+                node.parent_id = None;
+            }
+            node
+        })
+        .collect())
+}
+
+/// Plain-text title of the first top-level node
+fn shared_title(nodes: &[Node]) -> String {
+    nodes
+        .iter()
+        .filter(|n| n.parent_id.is_none())
+        .min_by(|a, b| a.position.total_cmp(&b.position))
+        .map(|n| strip_html(&n.content).trim().to_string())
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| "Untitled".to_string())
+}
+
+/// (Re)write a share's bundle from the document's current state
+fn write_bundle(share: &Share, state: &DocumentState, markdown_notes: bool) -> Result<(), String> {
+    let node_id = share.node_id.as_deref().map(Uuid::parse_str).transpose().map_err(|e| e.to_string())?;
+    let nodes = shared_nodes(state, node_id)?;
+    let dir = share_dir(&share.token);
+    fs::create_dir_all(&dir).map_err(|e| format!("Create share dir: {}", e))?;
+    fs::write(dir.join("index.html"), generate_html(&nodes, &share.title, true, markdown_notes))
+        .map_err(|e| format!("Write share page: {}", e))?;
+    fs::write(dir.join("data.json"), generate_json_backup(&nodes)?).map_err(|e| format!("Write share data: {}", e))
+}
+
+/// Share a document, or the subtree of one of its nodes, under a new token
+pub fn create_share(
+    document_id: Uuid,
+    node_id: Option<Uuid>,
+    state: &DocumentState,
+    markdown_notes: bool,
+) -> Result<Share, String> {
+    let share = Share {
+        token: Uuid::new_v4().simple().to_string(),
+        document_id: document_id.to_string(),
+        node_id: node_id.map(|id| id.to_string()),
+        title: shared_title(&shared_nodes(state, node_id)?),
+        created_at: Utc::now(),
+    };
+    write_bundle(&share, state, markdown_notes)?;
+
+    let mut shares = load_shares()?;
+    shares.push(share.clone());
+    save_shares(&shares)?;
+    Ok(share)
+}
+
+/// Remove a share and delete its bundle
+pub fn revoke_share(token: &str) -> Result<(), String> {
+    let mut shares = load_shares()?;
+    let count = shares.len();
+    shares.retain(|s| s.token != token);
+    if shares.len() == count {
+        return Err(format!("Share not found: {}", token));
+    }
+    save_shares(&shares)?;
+
+    let dir = share_dir(token);
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| format!("Remove share dir: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Rewrite the bundles of a document's shares. A share whose node was
+/// deleted keeps its last bundle.
+pub fn refresh_shares(document_id: Uuid, state: &DocumentState, markdown_notes: bool) -> Result<usize, String> {
+    let id = document_id.to_string();
+    let mut refreshed = 0;
+    for share in load_shares()?.iter().filter(|s| s.document_id == id) {
+        match write_bundle(share, state, markdown_notes) {
+            Ok(()) => refreshed += 1,
+            Err(e) => log::warn!("Failed to refresh share {}: {}", share.token, e),
+        }
+    }
+    Ok(refreshed)
+}
+
+/// Bundles are rewritten once a document goes this long without edits...
+const REFRESH_DELAY: Duration = Duration::from_secs(2);
+/// ...or after this long of continuous editing
+const MAX_REFRESH_DELAY: Duration = Duration::from_secs(30);
+
+struct RefreshJob {
+    document_id: Uuid,
+    state: DocumentState,
+    markdown_notes: bool,
+}
+
+/// Rewrites share bundles on a background thread, so saving an op never
+/// waits on it. Jobs for the same document are merged, keeping the latest
+/// state.
+pub struct ShareRefresher {
+    sender: Sender<RefreshJob>,
+}
+
+impl ShareRefresher {
+    pub fn start() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || run_refresher(receiver));
+        Self { sender }
+    }
+
+    /// Queue a rewrite of a document's bundles from its current state
+    pub fn refresh(&self, document_id: Uuid, state: &DocumentState, markdown_notes: bool) {
+        let job = RefreshJob {
+            document_id,
+            state: state.clone(),
+            markdown_notes,
+        };
+        if self.sender.send(job).is_err() {
+            log::error!("Share refresh thread has stopped");
+        }
+    }
+}
+
+fn run_refresher(receiver: Receiver<RefreshJob>) {
+    let mut waiting: HashMap<Uuid, RefreshJob> = HashMap::new();
+    let mut first_queued = Instant::now();
+    loop {
+        let received = if waiting.is_empty() {
+            receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            let left = MAX_REFRESH_DELAY.saturating_sub(first_queued.elapsed());
+            receiver.recv_timeout(REFRESH_DELAY.min(left))
+        };
+        match received {
+            Ok(job) => {
+                if waiting.is_empty() {
+                    first_queued = Instant::now();
+                }
+                waiting.insert(job.document_id, job);
+                if first_queued.elapsed() < MAX_REFRESH_DELAY {
+                    continue;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) if waiting.is_empty() => return,
+            Err(RecvTimeoutError::Disconnected) => {}
+        }
+        for (_, job) in waiting.drain() {
+            if let Err(e) = refresh_shares(job.document_id, &job.state, job.markdown_notes) {
+                log::warn!("Failed to refresh shares of document {}: {}", job.document_id, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_nodes() {
+        let outside = Node::new("Work".to_string());
+        let list = Node::new("<b>Groceries</b>".to_string());
+        let item = Node::new_child(list.id, 0.0, "Milk".to_string());
+        let state = DocumentState {
+            nodes: vec![outside.clone(), list.clone(), item.clone()],
+        };
+
+        let nodes = shared_nodes(&state, Some(list.id)).unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].parent_id, None);
+        assert_eq!(nodes[1].parent_id, Some(list.id));
+        assert_eq!(shared_title(&nodes), "Groceries");
+
+        assert_eq!(shared_nodes(&state, None).unwrap().len(), 3);
+        assert!(shared_nodes(&state, Some(Uuid::now_v7())).is_err());
+    }
+}
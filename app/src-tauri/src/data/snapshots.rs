@@ -0,0 +1,329 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use super::document::{data_dir, DocumentState};
+use super::node::Node;
+
+/// A frozen copy of a document's state, stored in snapshots/{doc_id}/{id}.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub state: DocumentState,
+}
+
+/// Snapshot summary for listing (without the node payload)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub node_count: usize,
+}
+
+/// A node present in both states whose fields differ
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangedNode {
+    pub before: Node,
+    pub after: Node,
+    /// Names of the fields that differ (e.g. "content", "is_checked")
+    pub fields: Vec<String>,
+}
+
+/// Differences between a snapshot (before) and the current state (after)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    pub added: Vec<Node>,
+    pub removed: Vec<Node>,
+    pub changed: Vec<ChangedNode>,
+}
+
+/// Get the snapshots directory for a document
+pub fn snapshots_dir(doc_id: &Uuid) -> PathBuf {
+    data_dir().join("snapshots").join(doc_id.to_string())
+}
+
+/// Turn a snapshot name into a filesystem-safe slug
+fn slugify(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
+
+/// Reject snapshot ids that could escape the snapshots directory
+fn validate_snapshot_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || id.contains('/') || id.contains('\\') || id.contains("..") {
+        return Err(format!("Invalid snapshot id: {}", id));
+    }
+    Ok(())
+}
+
+/// Save a named snapshot of a document state
+pub fn create_snapshot(doc_id: &Uuid, name: &str, state: &DocumentState) -> Result<SnapshotInfo, String> {
+    let dir = snapshots_dir(doc_id);
+    fs::create_dir_all(&dir).map_err(|e| format!("Create snapshots dir: {}", e))?;
+
+    let created_at = Utc::now();
+    let slug = slugify(name);
+    let base_id = if slug.is_empty() {
+        created_at.format("%Y%m%dT%H%M%S").to_string()
+    } else {
+        format!("{}-{}", created_at.format("%Y%m%dT%H%M%S"), slug)
+    };
+    // Avoid clobbering a snapshot taken in the same second with the same name
+    let mut id = base_id.clone();
+    let mut suffix = 2;
+    while dir.join(format!("{}.json", id)).exists() {
+        id = format!("{}-{}", base_id, suffix);
+        suffix += 1;
+    }
+
+    let snapshot = Snapshot {
+        name: name.to_string(),
+        created_at,
+        state: state.clone(),
+    };
+
+    let path = dir.join(format!("{}.json", id));
+    let file = File::create(&path).map_err(|e| format!("Create snapshot: {}", e))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &snapshot)
+        .map_err(|e| format!("Write snapshot: {}", e))?;
+
+    Ok(SnapshotInfo {
+        id,
+        name: snapshot.name,
+        created_at,
+        node_count: snapshot.state.nodes.len(),
+    })
+}
+
+/// Load a snapshot by id
+pub fn load_snapshot(doc_id: &Uuid, snapshot_id: &str) -> Result<Snapshot, String> {
+    validate_snapshot_id(snapshot_id)?;
+    let path = snapshots_dir(doc_id).join(format!("{}.json", snapshot_id));
+    if !path.exists() {
+        return Err(format!("Snapshot not found: {}", snapshot_id));
+    }
+    let file = File::open(&path).map_err(|e| format!("Open snapshot: {}", e))?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|e| format!("Parse snapshot: {}", e))
+}
+
+/// List all snapshots of a document, newest first
+pub fn list_snapshots(doc_id: &Uuid) -> Result<Vec<SnapshotInfo>, String> {
+    let dir = snapshots_dir(doc_id);
+    let mut snapshots = Vec::new();
+
+    if !dir.exists() {
+        return Ok(snapshots);
+    }
+
+    for entry in fs::read_dir(&dir).map_err(|e| format!("Read snapshots dir: {}", e))? {
+        let entry = entry.map_err(|e| format!("Read entry: {}", e))?;
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        match load_snapshot(doc_id, id) {
+            Ok(snapshot) => snapshots.push(SnapshotInfo {
+                id: id.to_string(),
+                name: snapshot.name,
+                created_at: snapshot.created_at,
+                node_count: snapshot.state.nodes.len(),
+            }),
+            Err(e) => log::warn!("Skip unreadable snapshot {}: {}", id, e),
+        }
+    }
+
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+    Ok(snapshots)
+}
+
+/// Give `node`'s copy in every snapshot of a document the node's current
+/// content and note (used once it is encrypted, so no snapshot keeps its
+/// plain text). Returns the number of snapshots rewritten.
+pub fn redact_snapshots(doc_id: &Uuid, node: &Node) -> Result<usize, String> {
+    let mut rewritten = 0;
+    for info in list_snapshots(doc_id)? {
+        let mut snapshot = load_snapshot(doc_id, &info.id)?;
+        let Some(copy) = snapshot.state.nodes.iter_mut().find(|n| n.id == node.id) else {
+            continue;
+        };
+        if copy.content == node.content && copy.note == node.note && copy.encrypted == node.encrypted {
+            continue;
+        }
+        copy.content = node.content.clone();
+        copy.note = node.note.clone();
+        copy.encrypted = node.encrypted;
+
+        let path = snapshots_dir(doc_id).join(format!("{}.json", info.id));
+        let file = File::create(&path).map_err(|e| format!("Create snapshot: {}", e))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &snapshot)
+            .map_err(|e| format!("Write snapshot: {}", e))?;
+        rewritten += 1;
+    }
+    Ok(rewritten)
+}
+
+/// List the fields that differ between two versions of a node
+fn changed_fields(before: &Node, after: &Node) -> Vec<String> {
+    let mut fields = Vec::new();
+    if before.parent_id != after.parent_id {
+        fields.push("parent_id");
+    }
+    if before.position != after.position {
+        fields.push("position");
+    }
+    if before.content != after.content {
+        fields.push("content");
+    }
+    if before.note != after.note {
+        fields.push("note");
+    }
+    if before.node_type != after.node_type {
+        fields.push("node_type");
+    }
+    if before.heading_level != after.heading_level {
+        fields.push("heading_level");
+    }
+    if before.language != after.language {
+        fields.push("language");
+    }
+    if before.is_checked != after.is_checked {
+        fields.push("is_checked");
+    }
+    if before.priority != after.priority {
+        fields.push("priority");
+    }
+    if before.color != after.color {
+        fields.push("color");
+    }
+    if before.tags != after.tags {
+        fields.push("tags");
+    }
+    if before.properties != after.properties {
+        fields.push("properties");
+    }
+    if before.date != after.date {
+        fields.push("date");
+    }
+    if before.date_recurrence != after.date_recurrence {
+        fields.push("date_recurrence");
+    }
+    if before.remind_at != after.remind_at {
+        fields.push("remind_at");
+    }
+    if before.collapsed != after.collapsed {
+        fields.push("collapsed");
+    }
+    if before.mirror_source_id != after.mirror_source_id {
+        fields.push("mirror_source_id");
+    }
+    if before.comments != after.comments {
+        fields.push("comments");
+    }
+    if before.encrypted != after.encrypted {
+        fields.push("encrypted");
+    }
+    fields.into_iter().map(String::from).collect()
+}
+
+/// Compare two document states node-by-node
+pub fn diff_states(before: &DocumentState, after: &DocumentState) -> SnapshotDiff {
+    let before_by_id: HashMap<Uuid, &Node> = before.nodes.iter().map(|n| (n.id, n)).collect();
+    let after_by_id: HashMap<Uuid, &Node> = after.nodes.iter().map(|n| (n.id, n)).collect();
+
+    let mut diff = SnapshotDiff::default();
+
+    for node in &after.nodes {
+        match before_by_id.get(&node.id) {
+            None => diff.added.push(node.clone()),
+            Some(old) => {
+                let fields = changed_fields(old, node);
+                if !fields.is_empty() {
+                    diff.changed.push(ChangedNode {
+                        before: (*old).clone(),
+                        after: node.clone(),
+                        fields,
+                    });
+                }
+            }
+        }
+    }
+
+    for node in &before.nodes {
+        if !after_by_id.contains_key(&node.id) {
+            diff.removed.push(node.clone());
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Project Plan (March)"), "project-plan-march");
+        assert_eq!(slugify("  --  "), "");
+        assert_eq!(slugify("v1.2"), "v1-2");
+    }
+
+    #[test]
+    fn test_validate_snapshot_id() {
+        assert!(validate_snapshot_id("20240101T000000-plan").is_ok());
+        assert!(validate_snapshot_id("../state").is_err());
+        assert!(validate_snapshot_id("a/b").is_err());
+        assert!(validate_snapshot_id("").is_err());
+    }
+
+    #[test]
+    fn test_diff_states() {
+        let kept = Node::new("Kept".to_string());
+        let edited = Node::new("Before".to_string());
+        let removed = Node::new("Removed".to_string());
+        let before = DocumentState {
+            nodes: vec![kept.clone(), edited.clone(), removed.clone()],
+        };
+
+        let mut edited_after = edited.clone();
+        edited_after.content = "After".to_string();
+        edited_after.is_checked = true;
+        let added = Node::new("Added".to_string());
+        let after = DocumentState {
+            nodes: vec![kept, edited_after, added.clone()],
+        };
+
+        let diff = diff_states(&before, &after);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].id, added.id);
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].id, removed.id);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].before.content, "Before");
+        assert_eq!(diff.changed[0].after.content, "After");
+        assert_eq!(diff.changed[0].fields, vec!["content", "is_checked"]);
+
+        // A reminder alone is a change
+        let mut reminded = after.clone();
+        reminded.nodes[0].remind_at = Some("2024-05-01T09:00:00Z".to_string());
+        let diff = diff_states(&after, &reminded);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].fields, vec!["remind_at"]);
+    }
+}
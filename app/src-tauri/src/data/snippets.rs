@@ -0,0 +1,99 @@
+//! Text snippets: a trigger such as `;today` typed in the editor expands to
+//! its text, with template date placeholders like {{date}} filled in. Kept in
+//! snippets.json in the data directory so every machine expands the same way.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use super::document::data_dir;
+use super::templates::expand_placeholders;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snippet {
+    /// What is typed, e.g. ";meet"
+    pub trigger: String,
+    /// What it becomes; may contain placeholders like {{date}}
+    pub expansion: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+fn snippets_path() -> PathBuf {
+    data_dir().join("snippets.json")
+}
+
+/// Snippets used until the user saves their own
+fn default_snippets() -> Vec<Snippet> {
+    vec![
+        Snippet {
+            trigger: ";today".to_string(),
+            expansion: "{{date}}".to_string(),
+            description: Some("Today's date".to_string()),
+        },
+        Snippet {
+            trigger: ";now".to_string(),
+            expansion: "{{date}} {{time}}".to_string(),
+            description: Some("Date and time".to_string()),
+        },
+    ]
+}
+
+/// Load the snippets (the defaults if none were saved)
+pub fn load_snippets() -> Result<Vec<Snippet>, String> {
+    let path = snippets_path();
+    if !path.exists() {
+        return Ok(default_snippets());
+    }
+    let contents = fs::read_to_string(&path).map_err(|e| format!("Read snippets: {}", e))?;
+    serde_json::from_str(&contents).map_err(|e| format!("Parse snippets: {}", e))
+}
+
+/// Replace the snippets. Triggers must be single words and unique.
+pub fn save_snippets(snippets: Vec<Snippet>) -> Result<Vec<Snippet>, String> {
+    let mut triggers = HashSet::new();
+    for snippet in &snippets {
+        let trigger = snippet.trigger.as_str();
+        if trigger.is_empty() || trigger.contains(char::is_whitespace) {
+            return Err(format!("Invalid snippet trigger: {:?}", trigger));
+        }
+        if !triggers.insert(trigger) {
+            return Err(format!("Duplicate snippet trigger: {}", trigger));
+        }
+    }
+    let json = serde_json::to_string_pretty(&snippets).map_err(|e| format!("Serialize snippets: {}", e))?;
+    fs::write(snippets_path(), json).map_err(|e| format!("Write snippets: {}", e))?;
+    Ok(snippets)
+}
+
+/// The expansion of a trigger with its placeholders filled in, if there is
+/// a snippet for it
+pub fn expand_snippet(snippets: &[Snippet], trigger: &str, now: DateTime<Local>) -> Option<String> {
+    snippets
+        .iter()
+        .find(|s| s.trigger == trigger)
+        .map(|s| expand_placeholders(&s.expansion, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_expand_snippet() {
+        let now = Local.with_ymd_and_hms(2024, 1, 15, 9, 5, 0).unwrap();
+        let mut snippets = default_snippets();
+        snippets.push(Snippet {
+            trigger: ";meet".to_string(),
+            expansion: "Meeting {{weekday}} {{date}}: ".to_string(),
+            description: None,
+        });
+
+        assert_eq!(expand_snippet(&snippets, ";today", now).as_deref(), Some("2024-01-15"));
+        assert_eq!(expand_snippet(&snippets, ";meet", now).as_deref(), Some("Meeting Monday 2024-01-15: "));
+        assert_eq!(expand_snippet(&snippets, ";missing", now), None);
+    }
+}
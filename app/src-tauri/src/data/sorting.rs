@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use uuid::Uuid;
+
+use super::document::DocumentState;
+use super::node::Node;
+use super::operations::{move_op, Operation};
+use crate::search::strip_html;
+
+/// What to sort children by
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    /// Plain-text content, case-insensitive
+    Alphabetical,
+    /// Due date (undated items always go last)
+    Date,
+    /// Unchecked before checked
+    Checked,
+    /// Creation time
+    Created,
+    /// Priority, highest (1) first in ascending order (unprioritized items always go last)
+    Priority,
+}
+
+/// Sort direction
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+fn compare_by_key(a: &Node, b: &Node, key: SortKey) -> Ordering {
+    match key {
+        SortKey::Alphabetical => strip_html(&a.content)
+            .to_lowercase()
+            .cmp(&strip_html(&b.content).to_lowercase()),
+        SortKey::Date => a.date.cmp(&b.date),
+        SortKey::Checked => a.is_checked.cmp(&b.is_checked),
+        SortKey::Created => a.created_at.cmp(&b.created_at),
+        SortKey::Priority => a.priority.cmp(&b.priority),
+    }
+}
+
+/// Build the Move ops that reorder a parent's children (None for roots).
+/// The sort is stable, so equal items keep their current relative order.
+pub fn sort_children_ops(
+    state: &DocumentState,
+    parent_id: Option<Uuid>,
+    key: SortKey,
+    direction: SortDirection,
+) -> Vec<Operation> {
+    let mut children = state.sorted_children(parent_id);
+    children.sort_by(|a, b| {
+        // Undated items stay at the end in both directions
+        if key == SortKey::Date && a.date.is_none() != b.date.is_none() {
+            return a.date.is_none().cmp(&b.date.is_none());
+        }
+        if key == SortKey::Priority && a.priority.is_none() != b.priority.is_none() {
+            return a.priority.is_none().cmp(&b.priority.is_none());
+        }
+        let ord = compare_by_key(a, b, key);
+        match direction {
+            SortDirection::Asc => ord,
+            SortDirection::Desc => ord.reverse(),
+        }
+    });
+
+    children
+        .iter()
+        .enumerate()
+        .filter(|(i, node)| node.position != *i as f64)
+        .map(|(i, node)| move_op(node.id, parent_id, i as f64))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted_contents(state: &DocumentState, parent_id: Option<Uuid>) -> Vec<String> {
+        state
+            .sorted_children(parent_id)
+            .iter()
+            .map(|n| n.content.clone())
+            .collect()
+    }
+
+    fn apply_all(state: &mut DocumentState, ops: &[Operation]) {
+        for op in ops {
+            op.apply(state);
+        }
+    }
+
+    fn list(items: &[(&str, Option<&str>, bool)]) -> (DocumentState, Uuid) {
+        let parent = Node::new("List".to_string());
+        let parent_id = parent.id;
+        let mut nodes = vec![parent];
+        for (i, (content, date, checked)) in items.iter().enumerate() {
+            let mut node = Node::new_child(parent_id, i as f64, content.to_string());
+            node.date = date.map(String::from);
+            node.is_checked = *checked;
+            nodes.push(node);
+        }
+        (DocumentState { nodes }, parent_id)
+    }
+
+    #[test]
+    fn test_sort_alphabetical() {
+        let (mut state, parent_id) = list(&[
+            ("<b>pears</b>", None, false),
+            ("Apples", None, false),
+            ("bananas", None, false),
+        ]);
+
+        let ops = sort_children_ops(&state, Some(parent_id), SortKey::Alphabetical, SortDirection::Asc);
+        apply_all(&mut state, &ops);
+        assert_eq!(sorted_contents(&state, Some(parent_id)), vec!["Apples", "bananas", "<b>pears</b>"]);
+
+        let ops = sort_children_ops(&state, Some(parent_id), SortKey::Alphabetical, SortDirection::Desc);
+        apply_all(&mut state, &ops);
+        assert_eq!(sorted_contents(&state, Some(parent_id)), vec!["<b>pears</b>", "bananas", "Apples"]);
+    }
+
+    #[test]
+    fn test_sort_by_date_keeps_undated_last() {
+        let (mut state, parent_id) = list(&[
+            ("Undated", None, false),
+            ("Later", Some("2025-03-01"), false),
+            ("Sooner", Some("2025-01-01"), false),
+        ]);
+
+        let ops = sort_children_ops(&state, Some(parent_id), SortKey::Date, SortDirection::Desc);
+        apply_all(&mut state, &ops);
+        assert_eq!(sorted_contents(&state, Some(parent_id)), vec!["Later", "Sooner", "Undated"]);
+    }
+
+    #[test]
+    fn test_sort_by_checked_is_stable() {
+        let (mut state, parent_id) = list(&[
+            ("Done 1", None, true),
+            ("Open 1", None, false),
+            ("Done 2", None, true),
+            ("Open 2", None, false),
+        ]);
+
+        let ops = sort_children_ops(&state, Some(parent_id), SortKey::Checked, SortDirection::Asc);
+        apply_all(&mut state, &ops);
+        assert_eq!(
+            sorted_contents(&state, Some(parent_id)),
+            vec!["Open 1", "Open 2", "Done 1", "Done 2"]
+        );
+
+        // Already sorted: no ops needed
+        assert!(sort_children_ops(&state, Some(parent_id), SortKey::Checked, SortDirection::Asc).is_empty());
+    }
+
+    #[test]
+    fn test_sort_by_priority_keeps_unprioritized_last() {
+        let (mut state, parent_id) = list(&[("None", None, false), ("Low", None, false), ("High", None, false)]);
+        for node in state.nodes.iter_mut() {
+            node.priority = match node.content.as_str() {
+                "Low" => Some(4),
+                "High" => Some(1),
+                _ => None,
+            };
+        }
+
+        let ops = sort_children_ops(&state, Some(parent_id), SortKey::Priority, SortDirection::Asc);
+        apply_all(&mut state, &ops);
+        assert_eq!(sorted_contents(&state, Some(parent_id)), vec!["High", "Low", "None"]);
+    }
+}
@@ -0,0 +1,108 @@
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::document::DocumentState;
+use super::node::NodeType;
+use crate::search::strip_html;
+
+/// Counts for a stats/review dashboard, over one document or the workspace
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Statistics {
+    pub document_count: usize,
+    pub node_count: usize,
+    /// Node counts keyed by node type (e.g. "bullet", "checkbox")
+    pub nodes_by_type: BTreeMap<String, usize>,
+    /// Words in content and notes (HTML stripped)
+    pub word_count: usize,
+    pub task_count: usize,
+    pub completed_task_count: usize,
+    /// Checked tasks per ISO week (e.g. "2024-W03"), dated by last modification
+    pub completed_per_week: BTreeMap<String, usize>,
+    /// Nodes created per ISO week
+    pub created_per_week: BTreeMap<String, usize>,
+}
+
+/// ISO week label for a timestamp
+fn week_key(at: &DateTime<Utc>) -> String {
+    let week = at.iso_week();
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+/// Serialized name of a node type (matches the frontend's NodeType)
+fn type_key(node_type: &NodeType) -> String {
+    serde_json::to_value(node_type)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_else(|| format!("{:?}", node_type))
+}
+
+impl Statistics {
+    /// Add one document's nodes to the totals
+    pub fn add_document(&mut self, state: &DocumentState) {
+        self.document_count += 1;
+        self.node_count += state.nodes.len();
+
+        for node in &state.nodes {
+            *self.nodes_by_type.entry(type_key(&node.node_type)).or_default() += 1;
+
+            self.word_count += strip_html(&node.content).split_whitespace().count();
+            if let Some(ref note) = node.note {
+                self.word_count += note.split_whitespace().count();
+            }
+
+            if node.node_type == NodeType::Checkbox || node.is_checked {
+                self.task_count += 1;
+            }
+            if node.is_checked {
+                self.completed_task_count += 1;
+                *self.completed_per_week.entry(week_key(&node.updated_at)).or_default() += 1;
+            }
+
+            *self.created_per_week.entry(week_key(&node.created_at)).or_default() += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Node;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_statistics() {
+        let jan_15 = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+        let jan_22 = Utc.with_ymd_and_hms(2024, 1, 22, 12, 0, 0).unwrap();
+
+        let mut heading = Node::new("<b>Weekly</b> plan".to_string());
+        heading.node_type = NodeType::Heading;
+        heading.created_at = jan_15;
+        let mut done = Node::new("Write report".to_string());
+        done.node_type = NodeType::Checkbox;
+        done.is_checked = true;
+        done.note = Some("three more words".to_string());
+        done.created_at = jan_15;
+        done.updated_at = jan_22;
+        let mut open = Node::new("Review".to_string());
+        open.node_type = NodeType::Checkbox;
+        open.created_at = jan_22;
+
+        let mut stats = Statistics::default();
+        stats.add_document(&DocumentState {
+            nodes: vec![heading, done, open],
+        });
+        stats.add_document(&DocumentState::new());
+
+        assert_eq!(stats.document_count, 2);
+        assert_eq!(stats.node_count, 3);
+        assert_eq!(stats.nodes_by_type["checkbox"], 2);
+        assert_eq!(stats.nodes_by_type["heading"], 1);
+        assert_eq!(stats.word_count, 8);
+        assert_eq!(stats.task_count, 2);
+        assert_eq!(stats.completed_task_count, 1);
+        assert_eq!(stats.completed_per_week["2024-W04"], 1);
+        assert_eq!(stats.created_per_week["2024-W03"], 2);
+        assert_eq!(stats.created_per_week["2024-W04"], 1);
+    }
+}
@@ -0,0 +1,270 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use super::document::{data_dir, DocumentState};
+use super::node::Node;
+
+/// A named subtree that can be cloned into documents, stored in templates/{id}.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    /// Whole-document template (instantiated as a new document) rather than a subtree
+    #[serde(default)]
+    pub document: bool,
+    /// Template nodes; top-level nodes have no parent_id
+    pub nodes: Vec<Node>,
+}
+
+/// Template summary for listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateInfo {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub document: bool,
+    pub node_count: usize,
+}
+
+impl From<&Template> for TemplateInfo {
+    fn from(t: &Template) -> Self {
+        Self {
+            id: t.id.clone(),
+            name: t.name.clone(),
+            created_at: t.created_at,
+            document: t.document,
+            node_count: t.nodes.len(),
+        }
+    }
+}
+
+/// Get the templates directory path
+pub fn templates_dir() -> PathBuf {
+    data_dir().join("templates")
+}
+
+fn template_path(id: &str) -> Result<PathBuf, String> {
+    Uuid::parse_str(id).map_err(|e| format!("Invalid template id: {}", e))?;
+    Ok(templates_dir().join(format!("{}.json", id)))
+}
+
+/// Save nodes as a new named template; `document` marks a whole-document template
+pub fn save_template(name: &str, nodes: Vec<Node>, document: bool) -> Result<TemplateInfo, String> {
+    let dir = templates_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("Create templates dir: {}", e))?;
+
+    let template = Template {
+        id: Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        created_at: Utc::now(),
+        document,
+        nodes,
+    };
+
+    let file = File::create(template_path(&template.id)?)
+        .map_err(|e| format!("Create template: {}", e))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &template)
+        .map_err(|e| format!("Write template: {}", e))?;
+
+    Ok(TemplateInfo::from(&template))
+}
+
+/// Load a template by id
+pub fn load_template(id: &str) -> Result<Template, String> {
+    let path = template_path(id)?;
+    if !path.exists() {
+        return Err(format!("Template not found: {}", id));
+    }
+    let file = File::open(&path).map_err(|e| format!("Open template: {}", e))?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|e| format!("Parse template: {}", e))
+}
+
+/// List all templates, sorted by name
+pub fn list_templates() -> Result<Vec<TemplateInfo>, String> {
+    let dir = templates_dir();
+    let mut templates = Vec::new();
+
+    if !dir.exists() {
+        return Ok(templates);
+    }
+
+    for entry in fs::read_dir(&dir).map_err(|e| format!("Read templates dir: {}", e))? {
+        let entry = entry.map_err(|e| format!("Read entry: {}", e))?;
+        let path = entry.path();
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        match load_template(id) {
+            Ok(template) => templates.push(TemplateInfo::from(&template)),
+            Err(e) => log::warn!("Skip unreadable template {}: {}", id, e),
+        }
+    }
+
+    templates.sort_by_key(|t| t.name.to_lowercase());
+    Ok(templates)
+}
+
+/// Load a template by name (case-insensitive)
+pub fn find_template_by_name(name: &str) -> Result<Template, String> {
+    let wanted = name.to_lowercase();
+    let info = list_templates()?
+        .into_iter()
+        .find(|t| t.name.to_lowercase() == wanted)
+        .ok_or_else(|| format!("Template not found: {}", name))?;
+    load_template(&info.id)
+}
+
+/// Delete a template
+pub fn delete_template(id: &str) -> Result<(), String> {
+    let path = template_path(id)?;
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| format!("Remove template: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Copy a node and its descendants out of a document, with the node as the
+/// single top-level node (parent_id None, position 0)
+pub fn extract_subtree(state: &DocumentState, root_id: Uuid) -> Result<Vec<Node>, String> {
+    let by_id = state.nodes_by_id();
+    if !by_id.contains_key(&root_id) {
+        return Err(format!("Node not found: {}", root_id));
+    }
+
+    Ok(state
+        .subtree_ids(root_id)
+        .into_iter()
+        .filter_map(|id| by_id.get(&id).map(|n| (*n).clone()))
+        .map(|mut node| {
+            if node.id == root_id {
+                node.parent_id = None;
+                node.position = 0.0;
+            }
+            node
+        })
+        .collect())
+}
+
+/// Expand date placeholders relative to `today`:
+/// `{{date}}` (2024-01-15), `{{time}}` (14:30), `{{weekday}}` (Monday),
+/// `{{week}}` (2024-W03), `{{month}}` (2024-01), `{{year}}` (2024)
+pub fn expand_placeholders(text: &str, now: DateTime<Local>) -> String {
+    if !text.contains("{{") {
+        return text.to_string();
+    }
+    let today: NaiveDate = now.date_naive();
+    let iso_week = today.iso_week();
+    text.replace("{{date}}", &today.format("%Y-%m-%d").to_string())
+        .replace("{{time}}", &now.format("%H:%M").to_string())
+        .replace("{{weekday}}", &today.format("%A").to_string())
+        .replace("{{week}}", &format!("{}-W{:02}", iso_week.year(), iso_week.week()))
+        .replace("{{month}}", &today.format("%Y-%m").to_string())
+        .replace("{{year}}", &today.format("%Y").to_string())
+}
+
+/// Clone template nodes with fresh ids and expanded placeholders. Top-level
+/// nodes are placed under `parent_id` starting at `position`.
+pub fn instantiate_nodes(
+    template_nodes: &[Node],
+    parent_id: Option<Uuid>,
+    position: f64,
+    now: DateTime<Local>,
+) -> Vec<Node> {
+    let id_map: HashMap<Uuid, Uuid> = template_nodes
+        .iter()
+        .map(|n| (n.id, Uuid::now_v7()))
+        .collect();
+
+    let mut top_level: Vec<&Node> = template_nodes
+        .iter()
+        .filter(|n| n.parent_id.map_or(true, |p| !id_map.contains_key(&p)))
+        .collect();
+    top_level.sort_by(|a, b| a.position.total_cmp(&b.position));
+    let top_positions: HashMap<Uuid, f64> = top_level
+        .iter()
+        .enumerate()
+        .map(|(i, n)| (n.id, position + i as f64))
+        .collect();
+
+    let created = now.with_timezone(&Utc);
+    template_nodes
+        .iter()
+        .map(|n| {
+            let mut node = n.clone();
+            node.id = id_map[&n.id];
+            match top_positions.get(&n.id) {
+                Some(&pos) => {
+                    node.parent_id = parent_id;
+                    node.position = pos;
+                }
+                None => node.parent_id = n.parent_id.map(|p| id_map[&p]),
+            }
+            node.content = expand_placeholders(&n.content, now);
+            node.note = n.note.as_ref().map(|note| expand_placeholders(note, now));
+            node.date = n.date.as_ref().map(|date| expand_placeholders(date, now));
+            node.mirror_source_id = None;
+            node.created_at = created;
+            node.updated_at = created;
+            node
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn fixed_now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 1, 15, 9, 5, 0).unwrap()
+    }
+
+    #[test]
+    fn test_expand_placeholders() {
+        assert_eq!(
+            expand_placeholders("Review {{week}} ({{date}}, {{weekday}})", fixed_now()),
+            "Review 2024-W03 (2024-01-15, Monday)"
+        );
+        assert_eq!(expand_placeholders("{{month}} / {{year}} {{time}}", fixed_now()), "2024-01 / 2024 09:05");
+        assert_eq!(expand_placeholders("No placeholders", fixed_now()), "No placeholders");
+    }
+
+    #[test]
+    fn test_extract_and_instantiate() {
+        let outer = Node::new("Outer".to_string());
+        let root = Node::new_child(outer.id, 3.0, "1:1 {{date}}".to_string());
+        let mut item = Node::new_child(root.id, 0.0, "Agenda".to_string());
+        item.note = Some("Week {{week}}".to_string());
+        let state = DocumentState {
+            nodes: vec![outer.clone(), root.clone(), item.clone()],
+        };
+
+        let template_nodes = extract_subtree(&state, root.id).unwrap();
+        assert_eq!(template_nodes.len(), 2);
+        assert!(template_nodes[0].parent_id.is_none());
+
+        let target = Uuid::now_v7();
+        let nodes = instantiate_nodes(&template_nodes, Some(target), 5.0, fixed_now());
+        assert_eq!(nodes.len(), 2);
+
+        let new_root = nodes.iter().find(|n| n.parent_id == Some(target)).unwrap();
+        assert_ne!(new_root.id, root.id);
+        assert_eq!(new_root.position, 5.0);
+        assert_eq!(new_root.content, "1:1 2024-01-15");
+
+        let new_item = nodes.iter().find(|n| n.parent_id == Some(new_root.id)).unwrap();
+        assert_ne!(new_item.id, item.id);
+        assert_eq!(new_item.note, Some("Week 2024-W03".to_string()));
+
+        assert!(extract_subtree(&state, Uuid::now_v7()).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! Time tracking: entries of time spent on nodes, appended per machine to
+//! timelog.{hostname}.jsonl in the document directory (so file sync merges
+//! them like pending ops). The running timer is machine-local and kept in
+//! config.json until it is stopped.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use uuid::Uuid;
+
+use super::document::{device_name, documents_dir, load_config, op_files, update_config, DocumentState};
+use crate::search::strip_html;
+
+/// Time spent on a node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeEntry {
+    pub node_id: Uuid,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    #[serde(default)]
+    pub device: String,
+}
+
+impl TimeEntry {
+    /// Seconds of the entry that fall within [start, end]
+    fn seconds_within(&self, start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> i64 {
+        let from = start.map_or(self.started_at, |s| s.max(self.started_at));
+        let to = end.map_or(self.ended_at, |e| e.min(self.ended_at));
+        (to - from).num_seconds().max(0)
+    }
+}
+
+/// The timer running on this machine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunningTimer {
+    pub document_id: Uuid,
+    pub node_id: Uuid,
+    pub started_at: DateTime<Utc>,
+}
+
+impl RunningTimer {
+    /// The entry for this timer if it were stopped at `ended_at`
+    pub fn entry(&self, ended_at: DateTime<Utc>) -> TimeEntry {
+        TimeEntry {
+            node_id: self.node_id,
+            started_at: self.started_at,
+            ended_at,
+            device: device_name(),
+        }
+    }
+}
+
+/// Append a time entry to this machine's timelog in a document directory
+pub fn append_time_entry(dir: &Path, entry: &TimeEntry) -> Result<(), String> {
+    let path = dir.join(format!("timelog.{}.jsonl", device_name()));
+    let json = serde_json::to_string(entry).map_err(|e| format!("Serialize time entry: {}", e))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Open {:?}: {}", path, e))?;
+    writeln!(file, "{}", json).map_err(|e| format!("Write time entry: {}", e))
+}
+
+/// The timer running on this machine, if any
+pub fn running_timer() -> Option<RunningTimer> {
+    load_config().running_timer
+}
+
+/// Start timing a node, stopping (and recording) any timer already running
+pub fn start_timer(document_id: Uuid, node_id: Uuid) -> Result<RunningTimer, String> {
+    stop_timer()?;
+    let timer = RunningTimer {
+        document_id,
+        node_id,
+        started_at: Utc::now(),
+    };
+    update_config(|config| config.running_timer = Some(timer.clone()))?;
+    Ok(timer)
+}
+
+/// Stop the running timer, recording its entry in the document's timelog.
+/// Returns None if no timer was running.
+pub fn stop_timer() -> Result<Option<TimeEntry>, String> {
+    let Some(timer) = load_config().running_timer else {
+        return Ok(None);
+    };
+    let entry = timer.entry(Utc::now());
+    let doc_dir = documents_dir().join(timer.document_id.to_string());
+    if doc_dir.exists() {
+        append_time_entry(&doc_dir, &entry)?;
+    } else {
+        log::warn!("Timer stopped for deleted document {}", timer.document_id);
+    }
+    update_config(|config| config.running_timer = None)?;
+    Ok(Some(entry))
+}
+
+/// Read the time entries of every machine for a document. Unreadable lines
+/// (e.g. cut short by sync) are skipped with a warning.
+pub fn read_time_entries(dir: &Path) -> Result<Vec<TimeEntry>, String> {
+    let mut entries: Vec<TimeEntry> = Vec::new();
+    for path in op_files(dir, "timelog.")? {
+        let file = File::open(&path).map_err(|e| format!("Open {:?}: {}", path, e))?;
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("Read {:?}: {}", path, e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => log::warn!("Skip time entry in {:?}: {}", path, e),
+            }
+        }
+    }
+    entries.sort_by_key(|e| e.started_at);
+    Ok(entries)
+}
+
+/// What a time report adds up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeGroupBy {
+    #[default]
+    Node,
+    Tag,
+    Document,
+}
+
+/// Time spent on one node, tag or document
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeReportRow {
+    /// Node or document id, or the tag
+    pub key: String,
+    /// Node text, tag or document title
+    pub label: String,
+    /// Document of the node (None when grouping by tag)
+    pub document_id: Option<String>,
+    pub seconds: i64,
+    pub hours: f64,
+}
+
+/// Sums time entries over a period, grouped by node, tag or document
+pub struct TimeReport {
+    group_by: TimeGroupBy,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    rows: HashMap<String, TimeReportRow>,
+}
+
+impl TimeReport {
+    pub fn new(group_by: TimeGroupBy, start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> Self {
+        Self {
+            group_by,
+            start,
+            end,
+            rows: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, key: String, label: String, document_id: Option<String>, seconds: i64) {
+        self.rows
+            .entry(key.clone())
+            .or_insert(TimeReportRow {
+                key,
+                label,
+                document_id,
+                seconds: 0,
+                hours: 0.0,
+            })
+            .seconds += seconds;
+    }
+
+    /// Add a document's entries. Entries for nodes that were deleted still
+    /// count toward the document.
+    pub fn add_document(&mut self, document_id: Uuid, title: &str, state: &DocumentState, entries: &[TimeEntry]) {
+        for entry in entries {
+            let seconds = entry.seconds_within(self.start, self.end);
+            if seconds == 0 {
+                continue;
+            }
+            let node = state.nodes.iter().find(|n| n.id == entry.node_id);
+            match self.group_by {
+                TimeGroupBy::Document => {
+                    self.add(document_id.to_string(), title.to_string(), Some(document_id.to_string()), seconds)
+                }
+                TimeGroupBy::Node => {
+                    let label = node.map_or_else(|| "(deleted)".to_string(), |n| strip_html(&n.content));
+                    self.add(entry.node_id.to_string(), label, Some(document_id.to_string()), seconds)
+                }
+                TimeGroupBy::Tag => {
+                    let tags = node.map(|n| n.tags.as_slice()).unwrap_or_default();
+                    if tags.is_empty() {
+                        self.add(String::new(), "(untagged)".to_string(), None, seconds);
+                    }
+                    // Time on a node with several tags counts toward each
+                    for tag in tags {
+                        self.add(tag.clone(), tag.clone(), None, seconds);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The rows, most time first
+    pub fn rows(self) -> Vec<TimeReportRow> {
+        let mut rows: Vec<TimeReportRow> = self
+            .rows
+            .into_values()
+            .map(|mut row| {
+                row.hours = (row.seconds as f64 / 36.0).round() / 100.0;
+                row
+            })
+            .collect();
+        rows.sort_by(|a, b| b.seconds.cmp(&a.seconds).then_with(|| a.label.cmp(&b.label)));
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Node;
+    use chrono::{Duration, TimeZone};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_time_entries_roundtrip() {
+        let tmp = TempDir::new().unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let timer = |started_at| RunningTimer {
+            document_id: Uuid::now_v7(),
+            node_id: Uuid::now_v7(),
+            started_at,
+        };
+        let later = timer(start + Duration::hours(2)).entry(start + Duration::hours(3));
+        let earlier = timer(start).entry(start + Duration::hours(1));
+        append_time_entry(tmp.path(), &later).unwrap();
+        append_time_entry(tmp.path(), &earlier).unwrap();
+
+        assert_eq!(read_time_entries(tmp.path()).unwrap(), vec![earlier, later]);
+    }
+
+    #[test]
+    fn test_time_report_groups_and_clips_to_range() {
+        let doc_id = Uuid::now_v7();
+        let day = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let mut design = Node::new("<b>Design</b>".to_string());
+        design.tags = vec!["work".to_string(), "client".to_string()];
+        let mut review = Node::new("Review".to_string());
+        review.tags = vec!["work".to_string()];
+        let state = DocumentState {
+            nodes: vec![design.clone(), review.clone()],
+        };
+        let entry = |node_id, from_hour, to_hour| TimeEntry {
+            node_id,
+            started_at: day + Duration::hours(from_hour),
+            ended_at: day + Duration::hours(to_hour),
+            device: "laptop".to_string(),
+        };
+        // The first entry starts before the range, so only 1 of its 2 hours counts
+        let entries = vec![entry(design.id, 8, 10), entry(review.id, 10, 11), entry(design.id, 20, 23)];
+        let range = (Some(day + Duration::hours(9)), Some(day + Duration::hours(12)));
+
+        let mut by_node = TimeReport::new(TimeGroupBy::Node, range.0, range.1);
+        by_node.add_document(doc_id, "Project", &state, &entries);
+        let rows = by_node.rows();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].label.as_str(), rows[0].hours), ("Design", 1.0));
+
+        let mut by_tag = TimeReport::new(TimeGroupBy::Tag, range.0, range.1);
+        by_tag.add_document(doc_id, "Project", &state, &entries);
+        let rows = by_tag.rows();
+        assert_eq!((rows[0].label.as_str(), rows[0].seconds), ("work", 7200));
+        assert_eq!((rows[1].label.as_str(), rows[1].seconds), ("client", 3600));
+
+        let mut by_document = TimeReport::new(TimeGroupBy::Document, None, None);
+        by_document.add_document(doc_id, "Project", &state, &entries);
+        assert_eq!(by_document.rows()[0].hours, 6.0);
+    }
+}
@@ -0,0 +1,227 @@
+//! A document's title lives in two places: the first root node (what the
+//! outline shows) and `title` in meta.json (what tools reading the data
+//! directory see without replaying ops). `TitleSync` in the document's
+//! settings says which one follows the other. Whenever syncing or a rename
+//! changes the title, the rename listener is told, so the search index and
+//! the sidebar can follow.
+
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use uuid::Uuid;
+
+use super::document::{Document, DocumentState};
+use super::html::strip_html;
+use super::node::Node;
+use super::operations::{create_op, update_op, NodeChanges, Operation};
+
+/// Which of meta.json's title and the first root node leads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TitleSync {
+    /// meta.json's title follows the first root node
+    #[default]
+    FirstNode,
+    /// The first root node follows meta.json's title (e.g. one set by a
+    /// script)
+    Meta,
+    /// The two are independent; the first root node is the title
+    Off,
+}
+
+/// A document's title changed (sent as the `document-renamed` event)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DocumentRenamed {
+    pub document_id: String,
+    pub title: String,
+}
+
+pub type RenameListener = Box<dyn Fn(&DocumentRenamed) + Send + Sync>;
+
+static RENAME_LISTENER: RwLock<Option<RenameListener>> = RwLock::new(None);
+
+/// Set (or clear) the listener told about each title change
+pub fn set_rename_listener(listener: Option<RenameListener>) {
+    *RENAME_LISTENER.write().unwrap() = listener;
+}
+
+fn notify_renamed(document_id: Uuid, title: &str) {
+    if let Some(ref listener) = *RENAME_LISTENER.read().unwrap() {
+        listener(&DocumentRenamed {
+            document_id: document_id.to_string(),
+            title: title.to_string(),
+        });
+    }
+}
+
+/// Plain-text title a node holds (none if it is encrypted or blank)
+fn node_title(node: &Node) -> Option<String> {
+    if node.encrypted {
+        return None;
+    }
+    let title = strip_html(&node.content).trim().to_string();
+    (!title.is_empty()).then_some(title)
+}
+
+/// Node content showing `title` as plain text
+fn title_content(title: &str) -> String {
+    title.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+impl DocumentState {
+    /// The first root node, which holds the document's title
+    pub fn title_node(&self) -> Option<&Node> {
+        self.nodes
+            .iter()
+            .filter(|n| n.parent_id.is_none())
+            .min_by(|a, b| a.position.total_cmp(&b.position))
+    }
+}
+
+impl Document {
+    /// The document's title: meta.json's if it leads, else the first root
+    /// node's
+    pub fn title(&self) -> String {
+        let meta_title = match self.meta.settings.title_sync {
+            TitleSync::Meta => self.meta.title.clone(),
+            _ => None,
+        };
+        meta_title
+            .or_else(|| self.state.title_node().and_then(node_title))
+            .unwrap_or_else(|| "Untitled".to_string())
+    }
+
+    /// Bring meta.json's title and the first root node back in line, the
+    /// way the settings say. In `Meta` mode this appends an op changing the
+    /// node (not in a locked document). Returns the new title if it changed.
+    pub fn sync_title(&mut self) -> Result<Option<String>, String> {
+        let node = self.state.title_node().cloned();
+        let renamed = match self.meta.settings.title_sync {
+            TitleSync::Off => None,
+            TitleSync::FirstNode => {
+                let title = node.as_ref().and_then(node_title);
+                if title == self.meta.title {
+                    None
+                } else {
+                    self.meta.title = title;
+                    self.meta.save(&self.dir)?;
+                    Some(self.title())
+                }
+            }
+            TitleSync::Meta => match (self.meta.title.clone(), node) {
+                (Some(title), Some(node))
+                    if !node.encrypted && !self.meta.locked && node_title(&node).as_ref() != Some(&title) =>
+                {
+                    let op = update_op(
+                        node.id,
+                        NodeChanges {
+                            content: Some(title_content(&title)),
+                            ..Default::default()
+                        },
+                    );
+                    self.append_op(&op)?;
+                    op.apply(&mut self.state);
+                    Some(title)
+                }
+                _ => None,
+            },
+        };
+        if let Some(ref title) = renamed {
+            notify_renamed(self.id, title);
+        }
+        Ok(renamed)
+    }
+
+    /// Rename the document: the first root node (created if the document
+    /// is empty) and, unless syncing is off, meta.json's title. Returns the
+    /// op that changed the node.
+    pub fn rename(&mut self, title: &str) -> Result<Operation, String> {
+        let title = title.trim();
+        if title.is_empty() {
+            return Err("The title can't be empty".to_string());
+        }
+        let op = match self.state.title_node() {
+            Some(node) if node.encrypted => return Err("The title node is encrypted".to_string()),
+            Some(node) => update_op(
+                node.id,
+                NodeChanges {
+                    content: Some(title_content(title)),
+                    ..Default::default()
+                },
+            ),
+            None => create_op(None, 0.0, title_content(title)),
+        };
+        self.append_op(&op)?;
+        op.apply(&mut self.state);
+
+        if self.meta.settings.title_sync != TitleSync::Off {
+            self.meta.title = Some(title.to_string());
+            self.meta.save(&self.dir)?;
+        }
+        notify_renamed(self.id, title);
+        Ok(op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::DocumentMeta;
+    use tempfile::TempDir;
+
+    fn document_with_title(tmp: &TempDir, title: &str) -> Document {
+        let mut doc = Document::create(tmp.path().join(Uuid::now_v7().to_string())).unwrap();
+        let op = create_op(None, 0.0, title.to_string());
+        doc.append_op(&op).unwrap();
+        op.apply(&mut doc.state);
+        doc
+    }
+
+    #[test]
+    fn test_sync_title_from_first_node() {
+        let tmp = TempDir::new().unwrap();
+        let mut doc = document_with_title(&tmp, "<b>Plans</b> &amp; ideas");
+
+        assert_eq!(doc.sync_title().unwrap().as_deref(), Some("Plans & ideas"));
+        assert_eq!(DocumentMeta::load(&doc.dir).unwrap().title.as_deref(), Some("Plans & ideas"));
+        // Already in line
+        assert_eq!(doc.sync_title().unwrap(), None);
+
+        doc.rename("Roadmap").unwrap();
+        assert_eq!(doc.title(), "Roadmap");
+        assert_eq!(doc.meta.title.as_deref(), Some("Roadmap"));
+        assert_eq!(doc.sync_title().unwrap(), None);
+    }
+
+    #[test]
+    fn test_sync_title_from_meta() {
+        let tmp = TempDir::new().unwrap();
+        let mut doc = document_with_title(&tmp, "Old");
+        let mut settings = doc.meta.settings.clone();
+        settings.title_sync = TitleSync::Meta;
+        doc.set_settings(settings).unwrap();
+        doc.meta.title = Some("Q&A <draft>".to_string());
+
+        assert_eq!(doc.sync_title().unwrap().as_deref(), Some("Q&A <draft>"));
+        assert_eq!(doc.state.title_node().unwrap().content, "Q&amp;A &lt;draft&gt;");
+        assert_eq!(doc.title(), "Q&A <draft>");
+        assert_eq!(doc.sync_title().unwrap(), None);
+
+        // The op was saved, so the node keeps the title after a reload
+        let reloaded = Document::load(doc.dir.clone()).unwrap();
+        assert_eq!(reloaded.state.title_node().unwrap().content, "Q&amp;A &lt;draft&gt;");
+    }
+
+    #[test]
+    fn test_sync_title_off() {
+        let tmp = TempDir::new().unwrap();
+        let mut doc = document_with_title(&tmp, "Node title");
+        let mut settings = doc.meta.settings.clone();
+        settings.title_sync = TitleSync::Off;
+        doc.set_settings(settings).unwrap();
+        doc.meta.title = Some("Meta title".to_string());
+
+        assert_eq!(doc.sync_title().unwrap(), None);
+        assert_eq!(doc.title(), "Node title");
+        assert_eq!(doc.state.title_node().unwrap().content, "Node title");
+    }
+}
@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use super::document::{device_name, load_config, op_files, op_line, read_op_records};
+use super::operations::OpRecord;
+
+/// When op and state files are fsynced to disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    /// After every op, before it is applied (safest)
+    #[default]
+    Always,
+    /// Only when compacting, leaving recent ops to the OS
+    OnCompact,
+    /// Never; fastest, but a power loss can drop recent edits
+    Never,
+}
+
+impl FsyncPolicy {
+    fn syncs_ops(self) -> bool {
+        self == FsyncPolicy::Always
+    }
+
+    fn syncs_compaction(self) -> bool {
+        self != FsyncPolicy::Never
+    }
+}
+
+static FSYNC_POLICY: RwLock<FsyncPolicy> = RwLock::new(FsyncPolicy::Always);
+
+/// Get the fsync policy in effect
+pub fn fsync_policy() -> FsyncPolicy {
+    FSYNC_POLICY.read().map(|p| *p).unwrap_or_default()
+}
+
+/// Set the fsync policy for this session (config.json keeps it across restarts)
+pub fn set_fsync_policy(policy: FsyncPolicy) {
+    if let Ok(mut guard) = FSYNC_POLICY.write() {
+        *guard = policy;
+    }
+}
+
+/// Apply the fsync policy saved in config.json
+pub fn init_fsync_policy_from_config() {
+    if let Some(policy) = load_config().fsync {
+        set_fsync_policy(policy);
+    }
+}
+
+/// Flush an appended op to disk if the policy asks for it
+pub(super) fn sync_op_file(file: &File) -> Result<(), String> {
+    if fsync_policy().syncs_ops() {
+        file.sync_data().map_err(|e| format!("Sync pending file: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Replace a file's contents without ever leaving it half-written: stream
+/// them into a temporary file next to it, sync it, then rename it into place
+pub(super) fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let file = File::create(&tmp_path).map_err(|e| format!("Create {:?}: {}", tmp_path, e))?;
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)?;
+    let file = writer
+        .into_inner()
+        .map_err(|e| format!("Write {:?}: {}", tmp_path, e.error()))?;
+    if fsync_policy().syncs_compaction() {
+        file.sync_all().map_err(|e| format!("Sync {:?}: {}", tmp_path, e))?;
+    }
+    fs::rename(&tmp_path, path).map_err(|e| format!("Replace {:?}: {}", path, e))
+}
+
+/// This machine's compaction journal in a document directory
+fn wal_path(dir: &Path) -> PathBuf {
+    dir.join(format!("wal.{}.jsonl", device_name()))
+}
+
+/// Record the ops a compaction is about to fold into state.json, so they
+/// can be replayed if the machine crashes before state.json reaches disk
+pub(super) fn write_compaction_wal(dir: &Path, records: &[OpRecord]) -> Result<(), String> {
+    write_atomic(&wal_path(dir), |writer| {
+        for record in records {
+            writeln!(writer, "{}", op_line(record)?).map_err(|e| format!("Write op: {}", e))?;
+        }
+        Ok(())
+    })
+}
+
+/// Remove the compaction journal once the compaction is complete
+pub(super) fn remove_compaction_wal(dir: &Path) -> Result<(), String> {
+    let path = wal_path(dir);
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| format!("Remove {:?}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+/// Finish recovering from a compaction interrupted by a crash: ops in the
+/// journal that are no longer in any pending file go back into this
+/// machine's pending file, so loading applies them again. Ops are
+/// idempotent, so replaying ones state.json already has is harmless.
+/// Returns the number of ops restored.
+pub(super) fn recover_compaction_wal(dir: &Path) -> Result<usize, String> {
+    let path = wal_path(dir);
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let mut pending = HashSet::new();
+    for pending_path in op_files(dir, "pending.")? {
+        for record in read_op_records(&pending_path)? {
+            pending.insert(serde_json::to_string(&record.op).map_err(|e| format!("Serialize op: {}", e))?);
+        }
+    }
+
+    let mut lines = Vec::new();
+    for record in read_op_records(&path)? {
+        let key = serde_json::to_string(&record.op).map_err(|e| format!("Serialize op: {}", e))?;
+        if !pending.contains(&key) {
+            lines.push(op_line(&record)?);
+        }
+    }
+
+    if !lines.is_empty() {
+        let pending_path = dir.join(format!("pending.{}.jsonl", device_name()));
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&pending_path)
+            .map_err(|e| format!("Open pending file {:?}: {}", pending_path, e))?;
+        for line in &lines {
+            writeln!(file, "{}", line).map_err(|e| format!("Write op: {}", e))?;
+        }
+        if fsync_policy().syncs_compaction() {
+            file.sync_data().map_err(|e| format!("Sync pending file: {}", e))?;
+        }
+        log::warn!("Replayed {} ops from an interrupted compaction in {:?}", lines.len(), dir);
+    }
+
+    fs::remove_file(&path).map_err(|e| format!("Remove {:?}: {}", path, e))?;
+    Ok(lines.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{create_op, Document};
+    use tempfile::TempDir;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_interrupted_compaction_is_replayed() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join(Uuid::now_v7().to_string());
+        let mut doc = Document::create(dir.clone()).unwrap();
+        let op = create_op(None, 0.0, "Journaled".to_string());
+        doc.append_op(&op).unwrap();
+
+        // Crash after the pending files were cleared, but before the new
+        // state.json made it to disk
+        let records = read_op_records(&dir.join(format!("pending.{}.jsonl", device_name()))).unwrap();
+        write_compaction_wal(&dir, &records).unwrap();
+        doc.clear_pending().unwrap();
+
+        let doc = Document::load(dir.clone()).unwrap();
+        assert_eq!(doc.state.nodes.len(), 1);
+        assert_eq!(doc.state.nodes[0].content, "Journaled");
+        assert!(!wal_path(&dir).exists());
+
+        // Replaying twice doesn't duplicate ops
+        write_compaction_wal(&dir, &records).unwrap();
+        assert_eq!(recover_compaction_wal(&dir).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_compact_leaves_no_journal() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join(Uuid::now_v7().to_string());
+        let mut doc = Document::create(dir.clone()).unwrap();
+        let op = create_op(None, 0.0, "Compacted".to_string());
+        doc.append_op(&op).unwrap();
+        op.apply(&mut doc.state);
+
+        doc.compact().unwrap();
+        assert!(!wal_path(&dir).exists());
+        assert!(!dir.join("state.json.tmp").exists());
+        assert_eq!(Document::load(dir).unwrap().state.nodes.len(), 1);
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{Cursor, Write};
+
+use super::{generate_markdown, generate_opml, linked_references_markdown, MarkdownOptions};
+use crate::data::Node;
+use crate::search::LinkedReference;
+
+/// File format for documents in an exported archive
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveFormat {
+    Markdown,
+    Opml,
+}
+
+impl ArchiveFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Markdown => "md",
+            ArchiveFormat::Opml => "opml",
+        }
+    }
+}
+
+/// Make a document title safe to use as a file name
+fn file_stem(title: &str) -> String {
+    let stem: String = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    let stem = stem.trim().trim_matches('.').trim();
+    if stem.is_empty() {
+        "Untitled".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+/// A document to put in an archive
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveDocument {
+    pub title: String,
+    pub nodes: Vec<Node>,
+    /// Appended to Markdown files as a "Linked references" section
+    pub references: Vec<LinkedReference>,
+}
+
+/// Build a zip with one file per document, named by title. Duplicate titles
+/// get a " (2)", " (3)", ... suffix.
+pub fn build_document_archive(documents: &[ArchiveDocument], format: ArchiveFormat) -> Result<Vec<u8>, String> {
+    let _timer = crate::perf::timer("export.archive");
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default();
+    let mut used_names = HashSet::new();
+
+    for document in documents {
+        let content = match format {
+            ArchiveFormat::Markdown => {
+                generate_markdown(&document.nodes, &MarkdownOptions::default())
+                    + &linked_references_markdown(&document.references)
+            }
+            ArchiveFormat::Opml => generate_opml(&document.nodes, &document.title, true)?,
+        };
+
+        let stem = file_stem(&document.title);
+        let mut name = format!("{}.{}", stem, format.extension());
+        let mut n = 2;
+        while !used_names.insert(name.to_lowercase()) {
+            name = format!("{} ({}).{}", stem, n, format.extension());
+            n += 1;
+        }
+
+        zip.start_file(name.as_str(), options)
+            .map_err(|e| format!("Add {} to zip: {}", name, e))?;
+        zip.write_all(content.as_bytes())
+            .map_err(|e| format!("Write {} to zip: {}", name, e))?;
+    }
+
+    let cursor = zip.finish().map_err(|e| format!("Finish zip: {}", e))?;
+    Ok(cursor.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_build_document_archive() {
+        let document = |title: &str, nodes| ArchiveDocument {
+            title: title.to_string(),
+            nodes,
+            ..Default::default()
+        };
+        let documents = vec![
+            document("Plans: 2024", vec![Node::new("Ship it".to_string())]),
+            document("Plans: 2024", vec![Node::new("Again".to_string())]),
+            document("", vec![]),
+        ];
+
+        let bytes = build_document_archive(&documents, ArchiveFormat::Markdown).unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let names: Vec<&str> = archive.file_names().collect();
+        let mut names: Vec<String> = names.into_iter().map(String::from).collect();
+        names.sort();
+        assert_eq!(names, vec!["Plans- 2024 (2).md", "Plans- 2024.md", "Untitled.md"]);
+
+        let mut content = String::new();
+        archive
+            .by_name("Plans- 2024.md")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "- Ship it\n");
+    }
+}
@@ -0,0 +1,448 @@
+use chrono::{DateTime, TimeZone, Utc};
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::opml::{convert_dynalist_syntax, dynalist_color, process_dynalist_content};
+use crate::data::{Node, NodeType};
+use crate::search::strip_html;
+
+const DYNALIST_API: &str = "https://dynalist.io/api/v1";
+
+/// A document or folder from Dynalist's file/list endpoint
+#[derive(Debug, Clone, Deserialize)]
+pub struct DynalistFile {
+    pub id: String,
+    pub title: String,
+    /// "document" or "folder"
+    #[serde(rename = "type")]
+    pub file_type: String,
+    #[serde(default)]
+    pub children: Vec<String>,
+}
+
+/// A node from Dynalist's doc/read endpoint
+#[derive(Debug, Clone, Deserialize)]
+pub struct DynalistNode {
+    pub id: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub note: String,
+    #[serde(default)]
+    pub checked: bool,
+    #[serde(default)]
+    pub checkbox: bool,
+    /// Heading level 1-3, 0 for none
+    #[serde(default)]
+    pub heading: u8,
+    /// Color label 1-6, 0 for none
+    #[serde(default)]
+    pub color: u8,
+    #[serde(default)]
+    pub collapsed: bool,
+    /// Milliseconds since the epoch
+    #[serde(default)]
+    pub created: i64,
+    #[serde(default)]
+    pub modified: i64,
+    #[serde(default)]
+    pub children: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct FileListResponse {
+    _code: String,
+    _msg: Option<String>,
+    #[serde(default)]
+    root_file_id: String,
+    #[serde(default)]
+    files: Vec<DynalistFile>,
+}
+
+#[derive(Deserialize)]
+struct DocReadResponse {
+    _code: String,
+    _msg: Option<String>,
+    #[serde(default)]
+    nodes: Vec<DynalistNode>,
+}
+
+/// A Dynalist document ready to be created locally
+#[derive(Debug, Clone)]
+pub struct DynalistDocument {
+    /// Dynalist file id
+    pub file_id: String,
+    pub title: String,
+    /// Folder path, e.g. "Work / Projects" (None for the top level)
+    pub folder: Option<String>,
+    pub nodes: Vec<Node>,
+    /// Our id for each Dynalist node id
+    pub node_ids: HashMap<String, Uuid>,
+}
+
+/// Turn a non-"Ok" API response into an error
+fn check_code(code: &str, msg: Option<String>) -> Result<(), String> {
+    match code {
+        "Ok" => Ok(()),
+        "TooManyRequests" => Err("Dynalist rate limit reached; try again in a minute".to_string()),
+        "InvalidToken" => Err("Invalid Dynalist API token".to_string()),
+        _ => Err(format!("Dynalist API error {}: {}", code, msg.unwrap_or_default())),
+    }
+}
+
+async fn post<T: for<'de> Deserialize<'de>>(
+    client: &reqwest::Client,
+    endpoint: &str,
+    body: serde_json::Value,
+) -> Result<T, String> {
+    client
+        .post(format!("{}/{}", DYNALIST_API, endpoint))
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("Dynalist request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid Dynalist response: {}", e))
+}
+
+/// Fetch every document in a Dynalist account, with its folder path
+pub async fn fetch_dynalist_documents(token: &str) -> Result<Vec<DynalistDocument>, String> {
+    let client = reqwest::Client::new();
+
+    let list: FileListResponse = post(&client, "file/list", json!({ "token": token })).await?;
+    check_code(&list._code, list._msg)?;
+
+    let mut documents = Vec::new();
+    for (file, folder) in documents_in_order(&list.files, &list.root_file_id) {
+        let doc: DocReadResponse =
+            post(&client, "doc/read", json!({ "token": token, "file_id": file.id })).await?;
+        check_code(&doc._code, doc._msg)?;
+
+        let (nodes, node_ids) = convert_nodes_with_ids(&doc.nodes);
+        documents.push(DynalistDocument {
+            file_id: file.id.clone(),
+            title: file.title.clone(),
+            folder,
+            nodes,
+            node_ids,
+        });
+    }
+
+    link_dynalist_documents(&mut documents);
+    Ok(documents)
+}
+
+/// Walk the file tree from the root folder, returning documents in Dynalist's
+/// order with their folder path. Our folders are flat, so nested folder names
+/// are joined with " / ".
+pub fn documents_in_order<'a>(
+    files: &'a [DynalistFile],
+    root_file_id: &str,
+) -> Vec<(&'a DynalistFile, Option<String>)> {
+    let by_id: HashMap<&str, &DynalistFile> = files.iter().map(|f| (f.id.as_str(), f)).collect();
+    let mut documents = Vec::new();
+
+    fn walk<'a>(
+        file: &'a DynalistFile,
+        path: Option<String>,
+        by_id: &HashMap<&str, &'a DynalistFile>,
+        documents: &mut Vec<(&'a DynalistFile, Option<String>)>,
+    ) {
+        for child_id in &file.children {
+            let Some(child) = by_id.get(child_id.as_str()) else {
+                continue;
+            };
+            if child.file_type == "folder" {
+                let child_path = match path {
+                    Some(ref p) => format!("{} / {}", p, child.title),
+                    None => child.title.clone(),
+                };
+                walk(child, Some(child_path), by_id, documents);
+            } else {
+                documents.push((*child, path.clone()));
+            }
+        }
+    }
+
+    if let Some(root) = by_id.get(root_file_id) {
+        walk(root, None, &by_id, &mut documents);
+    }
+    documents
+}
+
+fn timestamp(millis: i64) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(millis).single().unwrap_or_else(Utc::now)
+}
+
+/// Convert a Dynalist document's nodes into our nodes. The "root" node holds
+/// the document title and is dropped; its children become top-level nodes.
+pub fn convert_dynalist_nodes(dynalist_nodes: &[DynalistNode]) -> Vec<Node> {
+    convert_nodes_with_ids(dynalist_nodes).0
+}
+
+/// Convert nodes as `convert_dynalist_nodes` does, also mapping each Dynalist
+/// node id to the id of the node made from it
+fn convert_nodes_with_ids(dynalist_nodes: &[DynalistNode]) -> (Vec<Node>, HashMap<String, Uuid>) {
+    let by_id: HashMap<&str, &DynalistNode> =
+        dynalist_nodes.iter().map(|n| (n.id.as_str(), n)).collect();
+    let mut nodes = Vec::with_capacity(dynalist_nodes.len());
+    let mut node_ids = HashMap::new();
+
+    let Some(root) = by_id.get("root") else {
+        return (nodes, node_ids);
+    };
+
+    // (dynalist node, our parent id, position)
+    let mut stack: Vec<(&DynalistNode, Option<Uuid>, f64)> = root
+        .children
+        .iter()
+        .enumerate()
+        .rev()
+        .filter_map(|(i, id)| by_id.get(id.as_str()).map(|n| (*n, None, i as f64)))
+        .collect();
+
+    while let Some((item, parent_id, position)) = stack.pop() {
+        let (content, date, date_recurrence) = process_dynalist_content(&item.content);
+        let heading_level = Some(item.heading).filter(|h| (1..=6).contains(h));
+        let node_type = if heading_level.is_some() {
+            NodeType::Heading
+        } else if item.checkbox || item.checked {
+            NodeType::Checkbox
+        } else {
+            NodeType::Bullet
+        };
+
+        let node = Node {
+            id: Uuid::now_v7(),
+            parent_id,
+            position,
+            content,
+            note: Some(item.note.as_str())
+                .filter(|n| !n.is_empty())
+                .map(convert_dynalist_syntax),
+            node_type,
+            heading_level,
+            language: None,
+            is_checked: item.checked,
+            priority: None,
+            color: dynalist_color(item.color),
+            tags: Vec::new(),
+            properties: HashMap::new(),
+            date,
+            date_recurrence,
+            remind_at: None,
+            collapsed: item.collapsed,
+            mirror_source_id: None,
+            comments: Vec::new(),
+            encrypted: false,
+            created_at: timestamp(item.created),
+            updated_at: timestamp(item.modified),
+        };
+
+        for (i, child_id) in item.children.iter().enumerate().rev() {
+            if let Some(child) = by_id.get(child_id.as_str()) {
+                stack.push((child, Some(node.id), i as f64));
+            }
+        }
+        node_ids.insert(item.id.clone(), node.id);
+        nodes.push(node);
+    }
+
+    (nodes, node_ids)
+}
+
+/// Rewrite links between imported documents (`https://dynalist.io/d/<file>`
+/// with an optional `#z=<node>`) into wiki links to the imported nodes. A
+/// link to a whole document points at its first top-level node. Links to
+/// documents that weren't imported are left alone.
+pub fn link_dynalist_documents(documents: &mut [DynalistDocument]) {
+    // (file id, node id or "" for the document) -> (our id, link text)
+    let mut targets: HashMap<(String, String), (Uuid, String)> = HashMap::new();
+    for document in documents.iter() {
+        let text_of = |id: Uuid| {
+            document
+                .nodes
+                .iter()
+                .find(|n| n.id == id)
+                .map(|n| strip_html(&n.content).trim().to_string())
+                .unwrap_or_default()
+        };
+        for (dynalist_id, id) in &document.node_ids {
+            targets.insert((document.file_id.clone(), dynalist_id.clone()), (*id, text_of(*id)));
+        }
+        let first = document
+            .nodes
+            .iter()
+            .filter(|n| n.parent_id.is_none())
+            .min_by(|a, b| a.position.total_cmp(&b.position));
+        if let Some(first) = first {
+            targets.insert((document.file_id.clone(), String::new()), (first.id, document.title.clone()));
+        }
+    }
+
+    let link_re = Regex::new(
+        r"\[([^\]]*)\]\(https?://dynalist\.io/d/([\w-]+)(?:#z=([\w-]+))?\)|https?://dynalist\.io/d/([\w-]+)(?:#z=([\w-]+))?",
+    )
+    .unwrap();
+    let rewrite = |text: &str| -> String {
+        link_re
+            .replace_all(text, |caps: &Captures| {
+                let (label, file, node) = match caps.get(2) {
+                    Some(file) => (caps.get(1).map(|m| m.as_str()), file, caps.get(3)),
+                    None => (None, caps.get(4).unwrap(), caps.get(5)),
+                };
+                let key = (file.as_str().to_string(), node.map(|m| m.as_str().to_string()).unwrap_or_default());
+                match targets.get(&key) {
+                    Some((id, title)) => {
+                        let label = label.filter(|l| !l.trim().is_empty()).unwrap_or(title);
+                        format!(
+                            "<span data-wiki-link=\"\" data-node-id=\"{}\" class=\"wiki-link\">{}</span>",
+                            id, label
+                        )
+                    }
+                    None => caps[0].to_string(),
+                }
+            })
+            .to_string()
+    };
+
+    for document in documents.iter_mut() {
+        for node in document.nodes.iter_mut() {
+            if node.content.contains("dynalist.io/d/") {
+                node.content = rewrite(&node.content);
+            }
+            if let Some(note) = node.note.as_mut().filter(|n| n.contains("dynalist.io/d/")) {
+                *note = rewrite(note);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(id: &str, title: &str, file_type: &str, children: &[&str]) -> DynalistFile {
+        DynalistFile {
+            id: id.to_string(),
+            title: title.to_string(),
+            file_type: file_type.to_string(),
+            children: children.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_documents_in_order() {
+        let files = vec![
+            file("root", "Root", "folder", &["inbox", "work"]),
+            file("inbox", "Inbox", "document", &[]),
+            file("work", "Work", "folder", &["plan", "archive"]),
+            file("plan", "Plan", "document", &[]),
+            file("archive", "Archive", "folder", &["old"]),
+            file("old", "Old", "document", &[]),
+        ];
+
+        let docs: Vec<(&str, Option<String>)> = documents_in_order(&files, "root")
+            .into_iter()
+            .map(|(f, folder)| (f.title.as_str(), folder))
+            .collect();
+        assert_eq!(
+            docs,
+            vec![
+                ("Inbox", None),
+                ("Plan", Some("Work".to_string())),
+                ("Old", Some("Work / Archive".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_convert_dynalist_nodes() {
+        let json = r#"[
+            {"id": "root", "content": "Doc title", "children": ["a", "b"]},
+            {"id": "a", "content": "Pay rent !(2024-02-01 | 1m)", "checkbox": true, "color": 1,
+             "created": 1704067200000, "modified": 1704153600000, "children": ["c"]},
+            {"id": "b", "content": "Heading", "heading": 2, "note": "Some note"},
+            {"id": "c", "content": "Done", "checked": true}
+        ]"#;
+        let dynalist_nodes: Vec<DynalistNode> = serde_json::from_str(json).unwrap();
+        let nodes = convert_dynalist_nodes(&dynalist_nodes);
+
+        assert_eq!(nodes.len(), 3);
+        let rent = &nodes[0];
+        assert_eq!(rent.content.trim(), "Pay rent");
+        assert_eq!(rent.parent_id, None);
+        assert_eq!(rent.node_type, NodeType::Checkbox);
+        assert_eq!(rent.date.as_deref(), Some("2024-02-01"));
+        assert_eq!(rent.date_recurrence.as_deref(), Some("FREQ=MONTHLY"));
+        assert_eq!(rent.color.as_deref(), Some("red"));
+        assert_eq!(rent.created_at.to_rfc3339(), "2024-01-01T00:00:00+00:00");
+
+        let done = &nodes[1];
+        assert_eq!(done.parent_id, Some(rent.id));
+        assert!(done.is_checked);
+
+        let heading = &nodes[2];
+        assert_eq!(heading.position, 1.0);
+        assert_eq!(heading.heading_level, Some(2));
+        assert_eq!(heading.note.as_deref(), Some("Some note"));
+    }
+
+    fn document(file_id: &str, title: &str, json: &str) -> DynalistDocument {
+        let dynalist_nodes: Vec<DynalistNode> = serde_json::from_str(json).unwrap();
+        let (nodes, node_ids) = convert_nodes_with_ids(&dynalist_nodes);
+        DynalistDocument {
+            file_id: file_id.to_string(),
+            title: title.to_string(),
+            folder: None,
+            nodes,
+            node_ids,
+        }
+    }
+
+    #[test]
+    fn test_link_dynalist_documents() {
+        let mut documents = vec![
+            document(
+                "abc",
+                "Projects",
+                r#"[
+                    {"id": "root", "content": "Projects", "children": ["p1", "p2"]},
+                    {"id": "p1", "content": "Garden"},
+                    {"id": "p2", "content": "Kitchen"}
+                ]"#,
+            ),
+            document(
+                "xyz",
+                "Journal",
+                r#"[
+                    {"id": "root", "content": "Journal", "children": ["j1"]},
+                    {"id": "j1", "content": "Planted [beans](https://dynalist.io/d/abc#z=p1), see https://dynalist.io/d/abc and https://dynalist.io/d/other#z=q",
+                     "note": "Also https://dynalist.io/d/abc#z=p2"}
+                ]"#,
+            ),
+        ];
+        link_dynalist_documents(&mut documents);
+
+        let garden = documents[0].node_ids["p1"];
+        let kitchen = documents[0].node_ids["p2"];
+        let entry = &documents[1].nodes[0];
+        assert_eq!(
+            entry.content,
+            format!(
+                "Planted <span data-wiki-link=\"\" data-node-id=\"{0}\" class=\"wiki-link\">beans</span>, \
+                 see <span data-wiki-link=\"\" data-node-id=\"{0}\" class=\"wiki-link\">Projects</span> \
+                 and https://dynalist.io/d/other#z=q",
+                garden
+            )
+        );
+        assert_eq!(
+            entry.note.as_deref().unwrap(),
+            format!("Also <span data-wiki-link=\"\" data-node-id=\"{}\" class=\"wiki-link\">Kitchen</span>", kitchen)
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use uuid::Uuid;
+
+use super::markdown::code_block_text;
+use crate::data::{render_markdown, Node, NodeType};
+
+/// Generate a standalone HTML page from nodes. Node content is already HTML
+/// (from TipTap) and is embedded as-is; titles and notes are escaped, or
+/// notes rendered when they are Markdown (`markdown_notes`). With `colors`,
+/// colored nodes are wrapped in a `color-<name>` span styled inline.
+pub fn generate_html(nodes: &[Node], title: &str, colors: bool, markdown_notes: bool) -> String {
+    let mut output = String::new();
+    output.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    output.push_str(&format!("<title>{}</title>\n", escape_html(title)));
+    output.push_str("</head>\n<body>\n");
+    write_html_nodes(&mut output, nodes, None, colors, markdown_notes);
+    output.push_str("</body>\n</html>\n");
+    output
+}
+
+fn write_html_nodes(
+    output: &mut String,
+    nodes: &[Node],
+    parent_id: Option<Uuid>,
+    colors: bool,
+    markdown_notes: bool,
+) {
+    // Get children of this parent, sorted by position
+    let mut children: Vec<_> = nodes.iter().filter(|n| n.parent_id == parent_id).collect();
+    if children.is_empty() {
+        return;
+    }
+    children.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+    // Consecutive numbered siblings share an <ol>; everything else goes in a <ul>
+    let mut open_list: Option<&str> = None;
+    for node in children {
+        let list = if node.node_type == NodeType::Numbered { "ol" } else { "ul" };
+        if open_list != Some(list) {
+            if let Some(prev) = open_list {
+                output.push_str(&format!("</{}>\n", prev));
+            }
+            output.push_str(&format!("<{}>\n", list));
+            open_list = Some(list);
+        }
+
+        output.push_str("<li>");
+        let color = node.color.as_deref().filter(|_| colors);
+        if let Some(color) = color {
+            output.push_str(&color_span(color));
+        }
+        match node.node_type {
+            NodeType::Divider => output.push_str("<hr>"),
+            NodeType::CodeBlock => {
+                let class = node
+                    .language
+                    .as_ref()
+                    .map(|lang| format!(" class=\"language-{}\"", escape_html(lang)))
+                    .unwrap_or_default();
+                output.push_str(&format!(
+                    "<pre><code{}>{}</code></pre>",
+                    class,
+                    escape_html(&code_block_text(&node.content))
+                ));
+            }
+            NodeType::Quote => output.push_str(&format!("<blockquote>{}</blockquote>", node.content)),
+            NodeType::Heading => {
+                let level = node.heading_level.unwrap_or(1).clamp(1, 6);
+                output.push_str(&format!("<h{0}>{1}</h{0}>", level, node.content));
+            }
+            NodeType::Checkbox => {
+                let checked = if node.is_checked { " checked" } else { "" };
+                output.push_str(&format!(
+                    "<input type=\"checkbox\" disabled{}> {}",
+                    checked, node.content
+                ));
+            }
+            _ => output.push_str(&node.content),
+        }
+        if color.is_some() {
+            output.push_str("</span>");
+        }
+
+        if let Some(ref note) = node.note {
+            if markdown_notes {
+                output.push_str(&format!("<div class=\"note\">{}</div>", render_markdown(note)));
+            } else {
+                output.push_str(&format!("<p class=\"note\">{}</p>", escape_html(note)));
+            }
+        }
+
+        let has_children = nodes.iter().any(|n| n.parent_id == Some(node.id));
+        if has_children {
+            output.push('\n');
+            write_html_nodes(output, nodes, Some(node.id), colors, markdown_notes);
+        }
+        output.push_str("</li>\n");
+    }
+
+    if let Some(list) = open_list {
+        output.push_str(&format!("</{}>\n", list));
+    }
+}
+
+/// Opening span for a node color. The named colors get a readable text
+/// color; any other name only gets the class.
+fn color_span(color: &str) -> String {
+    let css = match color {
+        "red" => Some("#d32f2f"),
+        "orange" => Some("#ef6c00"),
+        "yellow" => Some("#f9a825"),
+        "green" => Some("#388e3c"),
+        "blue" => Some("#1976d2"),
+        "purple" => Some("#7b1fa2"),
+        _ => None,
+    };
+    let class = escape_html(color);
+    match css {
+        Some(css) => format!("<span class=\"color-{}\" style=\"color: {}\">", class, css),
+        None => format!("<span class=\"color-{}\">", class),
+    }
+}
+
+/// Escape text for inclusion in HTML
+pub(super) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(content: &str, position: f64, node_type: NodeType) -> Node {
+        let mut node = Node::new(content.to_string());
+        node.position = position;
+        node.node_type = node_type;
+        node
+    }
+
+    #[test]
+    fn test_generate_html_lists() {
+        let nodes = vec![
+            typed("Intro", 0.0, NodeType::Bullet),
+            typed("<b>First</b>", 1.0, NodeType::Numbered),
+            typed("Second", 2.0, NodeType::Numbered),
+            typed("", 3.0, NodeType::Divider),
+            typed("Wise words", 4.0, NodeType::Quote),
+        ];
+
+        let html = generate_html(&nodes, "Notes & Plans", true, false);
+        assert!(html.contains("<title>Notes &amp; Plans</title>"));
+        assert!(html.contains("<ul>\n<li>Intro</li>\n</ul>"));
+        assert!(html.contains("<ol>\n<li><b>First</b></li>\n<li>Second</li>\n</ol>"));
+        assert!(html.contains("<li><hr></li>"));
+        assert!(html.contains("<li><blockquote>Wise words</blockquote></li>"));
+    }
+
+    #[test]
+    fn test_generate_html_nested_with_note() {
+        let mut parent = Node::new("Parent".to_string());
+        parent.note = Some("a < b".to_string());
+        let mut child = Node::new_child(parent.id, 0.0, "Task".to_string());
+        child.node_type = NodeType::Checkbox;
+        child.is_checked = true;
+
+        let html = generate_html(&[parent, child], "Doc", true, false);
+        assert!(html.contains("<li>Parent<p class=\"note\">a &lt; b</p>\n<ul>\n"));
+        assert!(html.contains("<input type=\"checkbox\" disabled checked> Task"));
+
+        let mut parent = Node::new("Parent".to_string());
+        parent.note = Some("Read **this**".to_string());
+        let html = generate_html(&[parent], "Doc", true, true);
+        assert!(html.contains("<div class=\"note\"><p>Read <strong>this</strong></p>\n</div>"));
+    }
+
+    #[test]
+    fn test_generate_html_code_block() {
+        let mut code = typed("if a &lt; b {<br>}", 0.0, NodeType::CodeBlock);
+        code.language = Some("rust".to_string());
+
+        let html = generate_html(&[code], "Doc", true, false);
+        assert!(html.contains("<li><pre><code class=\"language-rust\">if a &lt; b {\n}</code></pre></li>"));
+    }
+
+    #[test]
+    fn test_generate_html_colors() {
+        let mut node = Node::new("Urgent".to_string());
+        node.color = Some("red".to_string());
+
+        let html = generate_html(std::slice::from_ref(&node), "Doc", true, false);
+        assert!(html.contains("<li><span class=\"color-red\" style=\"color: #d32f2f\">Urgent</span></li>"));
+        assert!(generate_html(&[node], "Doc", false, false).contains("<li>Urgent</li>"));
+    }
+}
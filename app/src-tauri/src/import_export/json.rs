@@ -1,7 +1,16 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
 
 use crate::data::{DocumentState, Node};
 
+/// Upgrades from each older backup version to the next, indexed by the
+/// version they upgrade from. The current version is the number of upgrades.
+const BACKUP_UPGRADES: &[fn(&mut Value)] = &[upgrade_v0];
+
+/// Version written into new backups
+pub const BACKUP_VERSION: u32 = BACKUP_UPGRADES.len() as u32;
+
 /// JSON backup format - preserves all node data
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonBackup {
@@ -16,7 +25,7 @@ pub struct JsonBackup {
 impl JsonBackup {
     pub fn new(nodes: Vec<Node>) -> Self {
         Self {
-            version: 1,
+            version: BACKUP_VERSION,
             exported_at: chrono::Utc::now().to_rfc3339(),
             nodes,
         }
@@ -33,11 +42,77 @@ pub fn generate_json_backup(nodes: &[Node]) -> Result<String, String> {
     serde_json::to_string_pretty(&backup).map_err(|e| format!("JSON serialization error: {}", e))
 }
 
-/// Parse JSON backup and return nodes
+/// Parse JSON backup and return nodes. Backups from older versions are
+/// upgraded first, and fields this version doesn't know are ignored.
 pub fn parse_json_backup(content: &str) -> Result<Vec<Node>, String> {
-    let backup: JsonBackup =
-        serde_json::from_str(content).map_err(|e| format!("JSON parse error: {}", e))?;
-    Ok(backup.nodes)
+    let backup: Value = serde_json::from_str(content).map_err(|e| format!("JSON parse error: {}", e))?;
+    nodes_from_backup(backup)
+}
+
+/// The nodes of a parsed backup (or anything shaped like one), upgraded to
+/// the current version
+pub(super) fn nodes_from_backup(mut backup: Value) -> Result<Vec<Node>, String> {
+    let version = match backup.get("version") {
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| format!("Invalid backup version: {}", version))? as u32,
+        // Bare node lists and backups from before versioning
+        None => 0,
+    };
+    if version > BACKUP_VERSION {
+        return Err(format!(
+            "Backup version {} is newer than this app supports ({}); please update",
+            version, BACKUP_VERSION
+        ));
+    }
+    for upgrade in &BACKUP_UPGRADES[version as usize..] {
+        upgrade(&mut backup);
+    }
+
+    let nodes = match backup.get_mut("nodes").map(Value::take) {
+        Some(Value::Array(nodes)) => nodes,
+        _ => return Err("Backup has no nodes".to_string()),
+    };
+    nodes
+        .into_iter()
+        .enumerate()
+        .map(|(i, node)| serde_json::from_value(node).map_err(|e| format!("Node {} in backup: {}", i + 1, e)))
+        .collect()
+}
+
+/// Version 0: a bare list of nodes or an unversioned object, from before
+/// backups had a format version. Nodes might lack ids, positions and
+/// timestamps, and name the checked state `checked`.
+fn upgrade_v0(backup: &mut Value) {
+    if backup.is_array() {
+        let nodes = backup.take();
+        let mut wrapped = Map::new();
+        wrapped.insert("nodes".to_string(), nodes);
+        *backup = Value::Object(wrapped);
+    }
+    let exported_at = backup
+        .get("exported_at")
+        .and_then(Value::as_str)
+        .map(String::from)
+        .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+
+    let Some(nodes) = backup.get_mut("nodes").and_then(Value::as_array_mut) else {
+        return;
+    };
+    for (i, node) in nodes.iter_mut().enumerate() {
+        let Some(node) = node.as_object_mut() else {
+            continue;
+        };
+        node.entry("id").or_insert_with(|| Value::String(Uuid::now_v7().to_string()));
+        node.entry("position").or_insert_with(|| Value::from(i as f64));
+        node.entry("content").or_insert_with(|| Value::String(String::new()));
+        if let Some(checked) = node.remove("checked") {
+            node.entry("is_checked").or_insert(checked);
+        }
+        node.entry("created_at").or_insert_with(|| Value::String(exported_at.clone()));
+        let created_at = node["created_at"].clone();
+        node.entry("updated_at").or_insert(created_at);
+    }
 }
 
 #[cfg(test)]
@@ -66,6 +141,8 @@ mod tests {
         node.date = Some("2024-01-15".to_string());
         node.is_checked = true;
         node.tags = vec!["important".to_string(), "work".to_string()];
+        node.priority = Some(2);
+        node.properties.insert("status".to_string(), "waiting".to_string());
 
         let nodes = vec![node];
         let json = generate_json_backup(&nodes).unwrap();
@@ -76,5 +153,53 @@ mod tests {
         assert_eq!(restored.date, Some("2024-01-15".to_string()));
         assert!(restored.is_checked);
         assert_eq!(restored.tags, vec!["important".to_string(), "work".to_string()]);
+        assert_eq!(restored.priority, Some(2));
+        assert_eq!(restored.properties.get("status").map(String::as_str), Some("waiting"));
+    }
+
+    #[test]
+    fn test_json_backup_includes_comments() {
+        let mut state = DocumentState::new();
+        let node = Node::new("Shared".to_string());
+        let node_id = node.id;
+        state.nodes.push(node);
+        crate::data::add_comment_op(node_id, "Looks good".to_string()).apply(&mut state);
+
+        let json = serde_json::to_string(&JsonBackup::from_state(&state)).unwrap();
+        let parsed = parse_json_backup(&json).unwrap();
+        assert_eq!(parsed[0].comments, state.nodes[0].comments);
+    }
+
+    #[test]
+    fn test_unversioned_backup_is_upgraded() {
+        let json = r#"[
+            {"content": "Old task", "checked": true, "node_type": "checkbox"},
+            {"id": "0190a000-0000-7000-8000-000000000001", "content": "Kept id", "position": 5}
+        ]"#;
+        let parsed = parse_json_backup(json).unwrap();
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].content, "Old task");
+        assert!(parsed[0].is_checked);
+        assert_eq!(parsed[0].position, 0.0);
+        assert_eq!(parsed[1].id.to_string(), "0190a000-0000-7000-8000-000000000001");
+        assert_eq!(parsed[1].position, 5.0);
+    }
+
+    #[test]
+    fn test_backup_with_unknown_fields_restores() {
+        let mut backup = serde_json::to_value(JsonBackup::new(vec![Node::new("Item".to_string())])).unwrap();
+        backup["app_name"] = Value::from("outline");
+        backup["nodes"][0]["future_field"] = Value::from(42);
+
+        let parsed = parse_json_backup(&backup.to_string()).unwrap();
+        assert_eq!(parsed[0].content, "Item");
+    }
+
+    #[test]
+    fn test_newer_backup_version_is_rejected() {
+        let json = format!(r#"{{"version": {}, "nodes": []}}"#, BACKUP_VERSION + 1);
+        let err = parse_json_backup(&json).unwrap_err();
+        assert!(err.contains("newer"), "{}", err);
     }
 }
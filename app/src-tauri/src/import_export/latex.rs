@@ -0,0 +1,244 @@
+use uuid::Uuid;
+
+use super::markdown::code_block_text;
+use crate::data::{decode_entities, Node, NodeType};
+
+/// Generate a LaTeX document from nodes. Top-level heading nodes become
+/// sections (by heading level) and everything else becomes itemize/enumerate
+/// lists. With `beamer`, each top-level node becomes a slide instead.
+pub fn generate_latex(nodes: &[Node], title: &str, beamer: bool) -> String {
+    let mut output = String::new();
+    let class = if beamer { "beamer" } else { "article" };
+    output.push_str(&format!("\\documentclass{{{}}}\n", class));
+    output.push_str("\\usepackage[utf8]{inputenc}\n");
+    output.push_str("\\usepackage{amssymb}\n");
+    output.push_str("\\usepackage{hyperref}\n");
+    output.push_str(&format!("\\title{{{}}}\n", escape_latex(title)));
+    output.push_str("\\begin{document}\n");
+
+    if beamer {
+        output.push_str("\\frame{\\titlepage}\n");
+        for node in sorted_children(nodes, None) {
+            output.push_str(&format!("\\begin{{frame}}{{{}}}\n", html_to_latex(&node.content)));
+            write_note(&mut output, node);
+            write_list(&mut output, nodes, node.id);
+            output.push_str("\\end{frame}\n");
+        }
+    } else {
+        output.push_str("\\maketitle\n");
+        write_sections(&mut output, nodes, None);
+    }
+
+    output.push_str("\\end{document}\n");
+    output
+}
+
+fn sorted_children(nodes: &[Node], parent_id: Option<Uuid>) -> Vec<&Node> {
+    let mut children: Vec<_> = nodes.iter().filter(|n| n.parent_id == parent_id).collect();
+    children.sort_by(|a, b| a.position.total_cmp(&b.position));
+    children
+}
+
+/// Write headings as sections, nesting while headings stay inside headings.
+/// Runs of other nodes are written as lists.
+fn write_sections(output: &mut String, nodes: &[Node], parent_id: Option<Uuid>) {
+    let mut run: Vec<&Node> = Vec::new();
+    for node in sorted_children(nodes, parent_id) {
+        if node.node_type != NodeType::Heading {
+            run.push(node);
+            continue;
+        }
+        write_items(output, nodes, &run);
+        run.clear();
+
+        let command = match node.heading_level.unwrap_or(1) {
+            1 => "section",
+            2 => "subsection",
+            3 => "subsubsection",
+            _ => "paragraph",
+        };
+        output.push_str(&format!("\\{}{{{}}}\n", command, html_to_latex(&node.content)));
+        write_note(output, node);
+        write_sections(output, nodes, Some(node.id));
+    }
+    write_items(output, nodes, &run);
+}
+
+/// Write a node's children as a list
+fn write_list(output: &mut String, nodes: &[Node], parent_id: Uuid) {
+    let children = sorted_children(nodes, Some(parent_id));
+    write_items(output, nodes, &children);
+}
+
+/// Write sibling nodes as list items. Consecutive numbered nodes share an
+/// enumerate; everything else goes in an itemize.
+fn write_items(output: &mut String, nodes: &[Node], items: &[&Node]) {
+    let mut open_list: Option<&str> = None;
+    for node in items {
+        let list = if node.node_type == NodeType::Numbered { "enumerate" } else { "itemize" };
+        if open_list != Some(list) {
+            if let Some(prev) = open_list {
+                output.push_str(&format!("\\end{{{}}}\n", prev));
+            }
+            output.push_str(&format!("\\begin{{{}}}\n", list));
+            open_list = Some(list);
+        }
+
+        let content = html_to_latex(&node.content);
+        match node.node_type {
+            NodeType::Divider => output.push_str("\\item[] \\hrulefill\n"),
+            NodeType::CodeBlock => {
+                output.push_str("\\item[]\n\\begin{verbatim}\n");
+                output.push_str(&code_block_text(&node.content));
+                output.push_str("\n\\end{verbatim}\n");
+            }
+            NodeType::Quote => {
+                output.push_str(&format!("\\item[] \\begin{{quote}}{}\\end{{quote}}\n", content));
+            }
+            NodeType::Heading => output.push_str(&format!("\\item \\textbf{{{}}}\n", content)),
+            NodeType::Checkbox => {
+                let mark = if node.is_checked { "$\\boxtimes$" } else { "$\\square$" };
+                output.push_str(&format!("\\item[{}] {}\n", mark, content));
+            }
+            _ => output.push_str(&format!("\\item {}\n", content)),
+        }
+
+        write_note(output, node);
+        write_list(output, nodes, node.id);
+    }
+
+    if let Some(list) = open_list {
+        output.push_str(&format!("\\end{{{}}}\n", list));
+    }
+}
+
+fn write_note(output: &mut String, node: &Node) {
+    if let Some(ref note) = node.note {
+        output.push_str(&format!("{{\\small {}}}\n", escape_latex(note)));
+    }
+}
+
+/// Convert TipTap HTML (bold, italic, code, links) to LaTeX markup
+fn html_to_latex(html: &str) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let text_end = rest.find('<').unwrap_or(rest.len());
+        result.push_str(&escape_latex(&decode_entities(&rest[..text_end])));
+        rest = &rest[text_end..];
+        if rest.is_empty() {
+            break;
+        }
+
+        let tag_end = rest.find('>').map(|i| i + 1).unwrap_or(rest.len());
+        let tag = &rest[1..tag_end.saturating_sub(1).max(1)];
+        rest = &rest[tag_end..];
+
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+        match (name.as_str(), closing) {
+            ("strong" | "b", false) => result.push_str("\\textbf{"),
+            ("em" | "i", false) => result.push_str("\\emph{"),
+            ("code", false) => result.push_str("\\texttt{"),
+            ("mark", false) => result.push_str("\\textbf{"),
+            ("a", false) => {
+                let href = tag
+                    .split("href=\"")
+                    .nth(1)
+                    .and_then(|h| h.split('"').next())
+                    .unwrap_or("");
+                result.push_str(&format!("\\href{{{}}}{{", escape_url(&decode_entities(href))));
+            }
+            ("strong" | "b" | "em" | "i" | "code" | "mark" | "a", true) => result.push('}'),
+            ("br", _) => result.push_str("\\\\ "),
+            _ => {}
+        }
+    }
+
+    result
+}
+
+/// Escape LaTeX special characters in plain text
+fn escape_latex(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => result.push_str("\\textbackslash{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                result.push('\\');
+                result.push(c);
+            }
+            '~' => result.push_str("\\textasciitilde{}"),
+            '^' => result.push_str("\\textasciicircum{}"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Escape the characters hyperref can't take literally in a URL
+fn escape_url(url: &str) -> String {
+    url.replace('\\', "").replace('%', "\\%").replace('#', "\\#")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heading(content: &str, level: u8, position: f64) -> Node {
+        let mut node = Node::new(content.to_string());
+        node.node_type = NodeType::Heading;
+        node.heading_level = Some(level);
+        node.position = position;
+        node
+    }
+
+    #[test]
+    fn test_html_to_latex() {
+        assert_eq!(
+            html_to_latex("<strong>Save</strong> 50% &amp; <em>more</em>"),
+            "\\textbf{Save} 50\\% \\& \\emph{more}"
+        );
+        assert_eq!(
+            html_to_latex(r#"see <a href="https://x.org/a#b">docs</a>"#),
+            "see \\href{https://x.org/a\\#b}{docs}"
+        );
+    }
+
+    #[test]
+    fn test_generate_latex_article() {
+        let intro = heading("Intro", 1, 0.0);
+        let details = Node::new_child(intro.id, 0.0, "Point".to_string());
+        let mut step = Node::new_child(intro.id, 1.0, "Step".to_string());
+        step.node_type = NodeType::Numbered;
+        let sub = heading("Background", 2, 0.0);
+        let sub = Node { parent_id: Some(intro.id), position: 2.0, ..sub };
+
+        let latex = generate_latex(&[intro, details, step, sub], "Paper", false);
+        assert!(latex.starts_with("\\documentclass{article}\n"));
+        assert!(latex.contains("\\title{Paper}\n"));
+        assert!(latex.contains(
+            "\\section{Intro}\n\\begin{itemize}\n\\item Point\n\\end{itemize}\n\\begin{enumerate}\n\\item Step\n\\end{enumerate}\n\\subsection{Background}\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_latex_beamer() {
+        let slide = Node::new("First slide".to_string());
+        let mut task = Node::new_child(slide.id, 0.0, "Done".to_string());
+        task.node_type = NodeType::Checkbox;
+        task.is_checked = true;
+
+        let latex = generate_latex(&[slide, task], "Talk", true);
+        assert!(latex.starts_with("\\documentclass{beamer}\n"));
+        assert!(latex.contains(
+            "\\begin{frame}{First slide}\n\\begin{itemize}\n\\item[$\\boxtimes$] Done\n\\end{itemize}\n\\end{frame}\n"
+        ));
+    }
+}
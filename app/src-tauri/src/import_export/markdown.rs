@@ -1,67 +1,206 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
-use crate::data::Node;
+use crate::data::{decode_entities, Node, NodeType};
 
-/// Generate Markdown content from nodes (Obsidian Tasks compatible)
-pub fn generate_markdown(nodes: &[Node]) -> String {
+/// Property holding a task's completion date (the Obsidian Tasks ✅ date)
+pub const COMPLETED_PROPERTY: &str = "completed";
+
+/// How notes are written under their item
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteStyle {
+    /// Indented paragraph lines
+    #[default]
+    Plain,
+    /// Indented "> " blockquote lines
+    Blockquote,
+}
+
+/// Markdown flavor options, since different target tools want different output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MarkdownOptions {
+    /// Write top-level heading nodes as `#` headings instead of bullets
+    pub headings: bool,
+    /// Emit Obsidian Tasks emoji (priority, 📅, 🔁, ✅)
+    pub task_emoji: bool,
+    /// Spaces per nesting level
+    pub indent_width: usize,
+    pub note_style: NoteStyle,
+    /// Tag colored items with `#color/<name>`
+    pub color_tags: bool,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        Self {
+            headings: false,
+            task_emoji: true,
+            indent_width: 2,
+            note_style: NoteStyle::Plain,
+            color_tags: false,
+        }
+    }
+}
+
+/// Generate Markdown content from nodes (Obsidian Tasks compatible by default)
+pub fn generate_markdown(nodes: &[Node], options: &MarkdownOptions) -> String {
     let mut output = String::new();
-    write_markdown_nodes(&mut output, nodes, None, 0);
+    write_markdown_nodes(&mut output, nodes, None, 0, options);
     output
 }
 
-fn write_markdown_nodes(output: &mut String, nodes: &[Node], parent_id: Option<Uuid>, depth: usize) {
+fn write_markdown_nodes(
+    output: &mut String,
+    nodes: &[Node],
+    parent_id: Option<Uuid>,
+    depth: usize,
+    options: &MarkdownOptions,
+) {
     // Get children of this parent, sorted by position
     let mut children: Vec<_> = nodes.iter().filter(|n| n.parent_id == parent_id).collect();
-    children.sort_by_key(|n| n.position);
+    children.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+    // Numbering restarts after any non-numbered sibling
+    let mut number = 0;
 
     for node in children {
-        let indent = "  ".repeat(depth);
+        let indent = " ".repeat(depth * options.indent_width);
         let content = html_to_markdown(&node.content);
 
-        // Determine bullet type
-        let bullet = if node.is_checked {
-            "- [x]"
-        } else if matches!(node.node_type, crate::data::NodeType::Checkbox) {
-            "- [ ]"
+        if node.node_type == NodeType::Numbered {
+            number += 1;
         } else {
-            "-"
-        };
+            number = 0;
+        }
 
-        // Build the line with Obsidian Tasks metadata
-        let mut line = format!("{}{} {}", indent, bullet, content);
+        if node.node_type == NodeType::Divider {
+            output.push_str(&format!("{}---\n", indent));
+            write_markdown_nodes(output, nodes, Some(node.id), depth + 1, options);
+            continue;
+        }
 
-        // Add due date emoji (📅)
-        if let Some(ref date) = node.date {
-            line.push_str(&format!(" 📅 {}", date));
+        // Headings can't be nested in a list, so only top-level ones become
+        // `#` headings; their children start a fresh list below them
+        if options.headings && node.node_type == NodeType::Heading && depth == 0 {
+            let level = node.heading_level.unwrap_or(1).clamp(1, 6) as usize;
+            let mut line = format!("{} {}", "#".repeat(level), content);
+            push_color_tag(&mut line, node, options);
+            output.push_str(&line);
+            output.push('\n');
+            write_markdown_note(output, node, "", options);
+            write_markdown_nodes(output, nodes, Some(node.id), depth, options);
+            continue;
         }
 
-        // Add recurrence emoji (🔁) - convert RRULE to human-readable
-        if let Some(ref rrule) = node.date_recurrence {
-            if let Some(human_readable) = rrule_to_human_readable(rrule) {
-                line.push_str(&format!(" 🔁 {}", human_readable));
+        // Code blocks become fenced blocks, keeping line breaks and the language
+        if node.node_type == NodeType::CodeBlock {
+            let code = code_block_text(&node.content);
+            let fence = if code.contains("```") { "~~~" } else { "```" };
+            output.push_str(&format!("{}{}{}\n", indent, fence, node.language.as_deref().unwrap_or("")));
+            for line in code.lines() {
+                output.push_str(&format!("{}{}\n", indent, line));
             }
+            output.push_str(&format!("{}{}\n", indent, fence));
+            write_markdown_nodes(output, nodes, Some(node.id), depth + 1, options);
+            continue;
         }
 
-        // Add completion date emoji (✅) for checked items
-        if node.is_checked {
-            // Use the updated_at date as completion date
-            let completion_date = node.updated_at.format("%Y-%m-%d").to_string();
-            line.push_str(&format!(" ✅ {}", completion_date));
+        // Determine bullet type
+        let bullet = if node.is_checked {
+            "- [x]".to_string()
+        } else {
+            match node.node_type {
+                NodeType::Checkbox => "- [ ]".to_string(),
+                NodeType::Numbered => format!("{}.", number),
+                NodeType::Quote => ">".to_string(),
+                _ => "-".to_string(),
+            }
+        };
+
+        // Build the line with Obsidian Tasks metadata
+        let mut line = format!("{}{} {}", indent, bullet, content);
+        push_color_tag(&mut line, node, options);
+        if options.task_emoji {
+            push_task_emoji(&mut line, node);
         }
 
         output.push_str(&line);
         output.push('\n');
 
         // Add note if present (as indented paragraph)
-        if let Some(ref note) = node.note {
-            let note_indent = "  ".repeat(depth + 1);
-            for line in note.lines() {
-                output.push_str(&format!("{}{}\n", note_indent, line));
-            }
-        }
+        let note_indent = " ".repeat((depth + 1) * options.indent_width);
+        write_markdown_note(output, node, &note_indent, options);
 
         // Recurse to children
-        write_markdown_nodes(output, nodes, Some(node.id), depth + 1);
+        write_markdown_nodes(output, nodes, Some(node.id), depth + 1, options);
+    }
+}
+
+/// Write a node's note lines in the configured style
+fn write_markdown_note(output: &mut String, node: &Node, indent: &str, options: &MarkdownOptions) {
+    let Some(ref note) = node.note else {
+        return;
+    };
+    let prefix = match options.note_style {
+        NoteStyle::Plain => "",
+        NoteStyle::Blockquote => "> ",
+    };
+    for line in note.lines() {
+        output.push_str(&format!("{}{}{}\n", indent, prefix, line));
+    }
+}
+
+/// Append a `#color/<name>` tag for a colored item, if enabled
+fn push_color_tag(line: &mut String, node: &Node, options: &MarkdownOptions) {
+    if let Some(color) = node.color.as_deref().filter(|_| options.color_tags) {
+        line.push_str(&format!(" #color/{}", color));
+    }
+}
+
+/// Append Obsidian Tasks metadata emoji to an item's line
+fn push_task_emoji(line: &mut String, node: &Node) {
+    // Add priority emoji (🔺 highest, ⏫ high, 🔼 medium, 🔽 low)
+    if let Some(emoji) = node.priority.and_then(priority_emoji) {
+        line.push(' ');
+        line.push_str(emoji);
+    }
+
+    // Add due date emoji (📅)
+    if let Some(ref date) = node.date {
+        line.push_str(&format!(" 📅 {}", date));
+    }
+
+    // Add recurrence emoji (🔁) - convert RRULE to human-readable
+    if let Some(ref rrule) = node.date_recurrence {
+        if let Some(human_readable) = rrule_to_human_readable(rrule) {
+            line.push_str(&format!(" 🔁 {}", human_readable));
+        }
+    }
+
+    // Add completion date emoji (✅) for checked items
+    if node.is_checked {
+        // Use the imported completion date if there is one, else updated_at
+        let completion_date = node
+            .properties
+            .get(COMPLETED_PROPERTY)
+            .cloned()
+            .unwrap_or_else(|| node.updated_at.format("%Y-%m-%d").to_string());
+        line.push_str(&format!(" ✅ {}", completion_date));
+    }
+}
+
+/// Obsidian Tasks priority emoji for a 1-4 priority
+fn priority_emoji(priority: u8) -> Option<&'static str> {
+    match priority {
+        1 => Some("🔺"),
+        2 => Some("⏫"),
+        3 => Some("🔼"),
+        4 => Some("🔽"),
+        _ => None,
     }
 }
 
@@ -124,6 +263,288 @@ fn rrule_to_human_readable(rrule: &str) -> Option<String> {
     Some(result)
 }
 
+/// Convert an Obsidian Tasks recurrence ("every 2 weeks on Monday, Friday")
+/// back to an RRULE. The inverse of rrule_to_human_readable.
+fn human_readable_to_rrule(text: &str) -> Option<String> {
+    let re = Regex::new(r"^every(?: (\d+))? (day|week|month|year)s?(?: on (.+))?$").unwrap();
+    let text = text.trim().trim_end_matches(" when done").to_lowercase();
+    let caps = re.captures(&text)?;
+
+    let freq = match &caps[2] {
+        "day" => "DAILY",
+        "week" => "WEEKLY",
+        "month" => "MONTHLY",
+        _ => "YEARLY",
+    };
+    let mut rrule = format!("FREQ={}", freq);
+
+    let interval: u32 = caps.get(1).and_then(|m| m.as_str().parse().ok()).unwrap_or(1);
+    if interval > 1 {
+        rrule.push_str(&format!(";INTERVAL={}", interval));
+    }
+
+    if let Some(days) = caps.get(3).filter(|_| freq == "WEEKLY") {
+        let codes: Vec<&str> = days
+            .as_str()
+            .split(',')
+            .filter_map(|day| match day.trim() {
+                "monday" => Some("MO"),
+                "tuesday" => Some("TU"),
+                "wednesday" => Some("WE"),
+                "thursday" => Some("TH"),
+                "friday" => Some("FR"),
+                "saturday" => Some("SA"),
+                "sunday" => Some("SU"),
+                _ => None,
+            })
+            .collect();
+        if !codes.is_empty() {
+            rrule.push_str(&format!(";BYDAY={}", codes.join(",")));
+        }
+    }
+
+    Some(rrule)
+}
+
+/// Obsidian Tasks metadata pulled off the end of a list item
+#[derive(Debug, Default, PartialEq)]
+struct TaskMetadata {
+    date: Option<String>,
+    recurrence: Option<String>,
+    completed: Option<String>,
+    priority: Option<u8>,
+}
+
+/// Split Obsidian Tasks emoji metadata (📅 due, 🔁 recurrence, ✅ done,
+/// priority) out of a list item's text
+fn parse_task_metadata(text: &str) -> (String, TaskMetadata) {
+    let mut meta = TaskMetadata::default();
+
+    let date_re = Regex::new(r"📅\s*(\d{4}-\d{2}-\d{2})").unwrap();
+    let done_re = Regex::new(r"✅\s*(\d{4}-\d{2}-\d{2})").unwrap();
+    // Recurrence text runs until the next emoji field
+    let recur_re = Regex::new(r"🔁\s*([^📅✅⏳🛫➕🔺⏫🔼🔽⏬]+)").unwrap();
+    let priority_re = Regex::new(r"[🔺⏫🔼🔽⏬]\u{FE0F}?").unwrap();
+
+    meta.date = date_re.captures(text).map(|c| c[1].to_string());
+    meta.completed = done_re.captures(text).map(|c| c[1].to_string());
+    meta.recurrence = recur_re
+        .captures(text)
+        .and_then(|c| human_readable_to_rrule(&c[1]));
+    meta.priority = priority_re.find(text).map(|m| match m.as_str().chars().next() {
+        Some('🔺') => 1,
+        Some('⏫') => 2,
+        Some('🔼') => 3,
+        // Low and lowest both map to our lowest priority
+        _ => 4,
+    });
+
+    let mut rest = date_re.replace_all(text, "").to_string();
+    rest = done_re.replace_all(&rest, "").to_string();
+    rest = recur_re.replace_all(&rest, "").to_string();
+    rest = priority_re.replace_all(&rest, "").to_string();
+
+    (rest.trim().to_string(), meta)
+}
+
+/// Convert inline Markdown (bold, italic, code, links) to HTML.
+/// The inverse of html_to_markdown.
+fn markdown_inline_to_html(text: &str) -> String {
+    let escaped = text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+
+    let code_re = Regex::new(r"`([^`]+)`").unwrap();
+    let bold_re = Regex::new(r"\*\*(.+?)\*\*").unwrap();
+    let italic_re = Regex::new(r"\*([^*]+)\*").unwrap();
+    let link_re = Regex::new(r#"\[([^\]]+)\]\(([^)\s"]+)\)"#).unwrap();
+
+    let html = code_re.replace_all(&escaped, "<code>$1</code>");
+    let html = bold_re.replace_all(&html, "<strong>$1</strong>");
+    let html = italic_re.replace_all(&html, "<em>$1</em>");
+    link_re.replace_all(&html, r#"<a href="$2">$1</a>"#).into_owned()
+}
+
+/// Count leading indentation in columns (a tab counts as four)
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// Parse Markdown into nodes (list items, headings, quotes, dividers and
+/// fenced code). Obsidian Tasks metadata is read back into date, recurrence,
+/// checked state and priority; the ✅ date is kept in the "completed" property.
+pub fn parse_markdown(content: &str) -> Vec<Node> {
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut positions: HashMap<Option<Uuid>, f64> = HashMap::new();
+    // Open list items as (indent, id), and headings as (level, id)
+    let mut list_stack: Vec<(usize, Uuid)> = Vec::new();
+    let mut heading_stack: Vec<(usize, Uuid)> = Vec::new();
+    // Last list item as (index into nodes, indent), for note lines
+    let mut last_item: Option<(usize, usize)> = None;
+    // Open code fence as (fence, indent, index into nodes, lines)
+    let mut code: Option<(String, usize, usize, Vec<String>)> = None;
+
+    let heading_re = Regex::new(r"^(#{1,6})\s+(.*)$").unwrap();
+    let checkbox_re = Regex::new(r"^[-*+]\s+\[([ xX])\]\s*(.*)$").unwrap();
+    let bullet_re = Regex::new(r"^[-*+]\s+(.*)$").unwrap();
+    let numbered_re = Regex::new(r"^\d+[.)]\s+(.*)$").unwrap();
+
+    let mut push_node = |nodes: &mut Vec<Node>, parent_id: Option<Uuid>, mut node: Node| {
+        let position = positions.entry(parent_id).or_insert(0.0);
+        node.parent_id = parent_id;
+        node.position = *position;
+        *position += 1.0;
+        nodes.push(node);
+        nodes.len() - 1
+    };
+
+    for line in content.lines() {
+        // Inside a fenced code block everything is literal until the closing fence
+        if let Some((ref fence, indent, index, ref mut lines)) = code {
+            if line.trim() == fence {
+                let text = lines.join("\n");
+                nodes[index].content = text
+                    .replace('&', "&amp;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;")
+                    .replace('\n', "<br>");
+                code = None;
+            } else {
+                let strip = indent_width(line).min(indent);
+                lines.push(line.chars().skip(strip).collect());
+            }
+            continue;
+        }
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let indent = indent_width(line);
+        let text = line.trim();
+
+        if let Some(caps) = heading_re.captures(text).filter(|_| indent == 0) {
+            let level = caps[1].len();
+            while heading_stack.last().is_some_and(|(l, _)| *l >= level) {
+                heading_stack.pop();
+            }
+            list_stack.clear();
+            let mut node = Node::new(markdown_inline_to_html(caps[2].trim()));
+            node.node_type = NodeType::Heading;
+            node.heading_level = Some(level as u8);
+            let id = node.id;
+            push_node(&mut nodes, heading_stack.last().map(|(_, id)| *id), node);
+            heading_stack.push((level, id));
+            last_item = None;
+            continue;
+        }
+
+        // Indented plain text under a list item is that item's note
+        let is_block = text.starts_with("```")
+            || text.starts_with("~~~")
+            || text.starts_with('>')
+            || bullet_re.is_match(text)
+            || numbered_re.is_match(text)
+            || matches!(text, "---" | "***" | "___");
+        if !is_block {
+            if let Some((index, _)) = last_item.filter(|(_, i)| indent > *i) {
+                let note = nodes[index].note.get_or_insert_with(String::new);
+                if !note.is_empty() {
+                    note.push('\n');
+                }
+                note.push_str(text);
+                continue;
+            }
+        }
+
+        while list_stack.last().is_some_and(|(i, _)| *i >= indent) {
+            list_stack.pop();
+        }
+        let parent_id = list_stack
+            .last()
+            .or(heading_stack.last())
+            .map(|(_, id)| *id);
+
+        let mut node = Node::new(String::new());
+        let mut item_text = None;
+        if text.starts_with("```") || text.starts_with("~~~") {
+            let fence = text[..3].to_string();
+            node.node_type = NodeType::CodeBlock;
+            node.language = Some(text[3..].trim().to_string()).filter(|l| !l.is_empty());
+            let index = push_node(&mut nodes, parent_id, node);
+            code = Some((fence, indent, index, Vec::new()));
+            list_stack.push((indent, nodes[index].id));
+            last_item = None;
+            continue;
+        } else if matches!(text, "---" | "***" | "___") {
+            node.node_type = NodeType::Divider;
+        } else if let Some(caps) = checkbox_re.captures(text) {
+            node.node_type = NodeType::Checkbox;
+            node.is_checked = &caps[1] != " ";
+            item_text = Some(caps[2].to_string());
+        } else if let Some(caps) = bullet_re.captures(text) {
+            item_text = Some(caps[1].to_string());
+        } else if let Some(caps) = numbered_re.captures(text) {
+            node.node_type = NodeType::Numbered;
+            item_text = Some(caps[1].to_string());
+        } else if let Some(quoted) = text.strip_prefix('>') {
+            node.node_type = NodeType::Quote;
+            item_text = Some(quoted.trim().to_string());
+        } else {
+            item_text = Some(text.to_string());
+        }
+
+        if let Some(item_text) = item_text {
+            let (rest, meta) = parse_task_metadata(&item_text);
+            node.content = markdown_inline_to_html(&rest);
+            node.date = meta.date;
+            node.date_recurrence = meta.recurrence;
+            node.priority = meta.priority;
+            if let Some(completed) = meta.completed {
+                node.is_checked = true;
+                node.properties.insert(COMPLETED_PROPERTY.to_string(), completed);
+            }
+        }
+
+        let id = node.id;
+        let index = push_node(&mut nodes, parent_id, node);
+        list_stack.push((indent, id));
+        last_item = Some((index, indent));
+    }
+
+    nodes
+}
+
+/// Extract the literal code from a code block's content: line breaks and
+/// paragraphs become newlines, other tags are dropped and entities decoded
+pub fn code_block_text(html: &str) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut chars = html.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '<' {
+            result.push(c);
+            continue;
+        }
+        let tag: String = chars.by_ref().take_while(|&c| c != '>').collect();
+        let tag_name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+        if tag_name == "br" || (tag_name == "p" && tag.starts_with('/')) {
+            result.push('\n');
+        }
+    }
+
+    decode_entities(&result).trim_end_matches('\n').to_string()
+}
+
 /// Convert HTML content to Markdown
 fn html_to_markdown(html: &str) -> String {
     let mut result = String::with_capacity(html.len());
@@ -223,21 +644,12 @@ fn html_to_markdown(html: &str) -> String {
         }
     }
 
-    // Decode HTML entities
-    result
-        .replace("&nbsp;", " ")
-        .replace("&amp;", "&")
-        .replace("&lt;", "<")
-        .replace("&gt;", ">")
-        .replace("&quot;", "\"")
-        .trim()
-        .to_string()
+    decode_entities(&result).trim().to_string()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::data::NodeType;
 
     #[test]
     fn test_generate_markdown_simple() {
@@ -245,10 +657,10 @@ mod tests {
             Node::new("First item".to_string()),
             Node::new("Second item".to_string()),
         ];
-        nodes[0].position = 0;
-        nodes[1].position = 1;
+        nodes[0].position = 0.0;
+        nodes[1].position = 1.0;
 
-        let md = generate_markdown(&nodes);
+        let md = generate_markdown(&nodes, &MarkdownOptions::default());
         assert!(md.contains("- First item"));
         assert!(md.contains("- Second item"));
     }
@@ -257,10 +669,10 @@ mod tests {
     fn test_generate_markdown_nested() {
         let parent = Node::new("Parent".to_string());
         let parent_id = parent.id;
-        let child = Node::new_child(parent_id, 0, "Child".to_string());
+        let child = Node::new_child(parent_id, 0.0, "Child".to_string());
 
         let nodes = vec![parent, child];
-        let md = generate_markdown(&nodes);
+        let md = generate_markdown(&nodes, &MarkdownOptions::default());
 
         assert!(md.contains("- Parent"));
         assert!(md.contains("  - Child"));
@@ -273,7 +685,7 @@ mod tests {
         node.is_checked = false;
 
         let nodes = vec![node];
-        let md = generate_markdown(&nodes);
+        let md = generate_markdown(&nodes, &MarkdownOptions::default());
         assert!(md.contains("- [ ] Task"));
     }
 
@@ -284,7 +696,7 @@ mod tests {
         node.is_checked = true;
 
         let nodes = vec![node];
-        let md = generate_markdown(&nodes);
+        let md = generate_markdown(&nodes, &MarkdownOptions::default());
         assert!(md.contains("- [x] Done task"));
         // Should also have completion date emoji
         assert!(md.contains("✅"));
@@ -296,7 +708,7 @@ mod tests {
         node.note = Some("This is a note".to_string());
 
         let nodes = vec![node];
-        let md = generate_markdown(&nodes);
+        let md = generate_markdown(&nodes, &MarkdownOptions::default());
         assert!(md.contains("- Item"));
         assert!(md.contains("  This is a note"));
     }
@@ -308,11 +720,22 @@ mod tests {
         node.date = Some("2025-01-15".to_string());
 
         let nodes = vec![node];
-        let md = generate_markdown(&nodes);
+        let md = generate_markdown(&nodes, &MarkdownOptions::default());
         assert!(md.contains("- [ ] Task with date"));
         assert!(md.contains("📅 2025-01-15"));
     }
 
+    #[test]
+    fn test_generate_markdown_with_priority() {
+        let mut node = Node::new("Urgent".to_string());
+        node.node_type = NodeType::Checkbox;
+        node.priority = Some(2);
+        node.date = Some("2024-01-15".to_string());
+
+        let md = generate_markdown(&[node], &MarkdownOptions::default());
+        assert!(md.contains("- [ ] Urgent ⏫ 📅 2024-01-15"));
+    }
+
     #[test]
     fn test_generate_markdown_with_recurrence() {
         let mut node = Node::new("Recurring task".to_string());
@@ -320,7 +743,7 @@ mod tests {
         node.date_recurrence = Some("FREQ=WEEKLY;INTERVAL=1".to_string());
 
         let nodes = vec![node];
-        let md = generate_markdown(&nodes);
+        let md = generate_markdown(&nodes, &MarkdownOptions::default());
         assert!(md.contains("- [ ] Recurring task"));
         assert!(md.contains("🔁 every week"));
     }
@@ -332,7 +755,7 @@ mod tests {
         node.date_recurrence = Some("FREQ=WEEKLY;INTERVAL=1;BYDAY=MO,WE,FR".to_string());
 
         let nodes = vec![node];
-        let md = generate_markdown(&nodes);
+        let md = generate_markdown(&nodes, &MarkdownOptions::default());
         assert!(md.contains("🔁 every week on Monday, Wednesday, Friday"));
     }
 
@@ -385,4 +808,172 @@ mod tests {
         assert_eq!(html_to_markdown("A &amp; B"), "A & B");
         assert_eq!(html_to_markdown("&lt;tag&gt;"), "<tag>");
     }
+
+    #[test]
+    fn test_generate_markdown_numbered_quote_divider() {
+        let types = [
+            NodeType::Numbered,
+            NodeType::Numbered,
+            NodeType::Divider,
+            NodeType::Numbered,
+            NodeType::Quote,
+        ];
+        let nodes: Vec<Node> = types
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let mut node = Node::new(format!("Item {}", i));
+                node.position = i as f64;
+                node.node_type = t.clone();
+                node
+            })
+            .collect();
+
+        let md = generate_markdown(&nodes, &MarkdownOptions::default());
+        assert_eq!(md, "1. Item 0\n2. Item 1\n---\n1. Item 3\n> Item 4\n");
+    }
+
+    #[test]
+    fn test_human_readable_to_rrule() {
+        assert_eq!(human_readable_to_rrule("every day"), Some("FREQ=DAILY".to_string()));
+        assert_eq!(human_readable_to_rrule("every 2 weeks"), Some("FREQ=WEEKLY;INTERVAL=2".to_string()));
+        assert_eq!(
+            human_readable_to_rrule("every week on Monday, Friday when done"),
+            Some("FREQ=WEEKLY;BYDAY=MO,FR".to_string())
+        );
+        assert_eq!(human_readable_to_rrule("every year"), Some("FREQ=YEARLY".to_string()));
+        assert_eq!(human_readable_to_rrule("sometimes"), None);
+
+        for rrule in ["FREQ=MONTHLY;INTERVAL=3", "FREQ=WEEKLY;BYDAY=TU,TH"] {
+            let text = rrule_to_human_readable(rrule).unwrap();
+            assert_eq!(human_readable_to_rrule(&text).as_deref(), Some(rrule));
+        }
+    }
+
+    #[test]
+    fn test_parse_markdown_structure() {
+        let md = "# Project\n- Parent\n  A note\n  - Child with **bold**\n1. First\n> Quote\n---\n```rust\nlet x = a < b;\n```\n";
+        let nodes = parse_markdown(md);
+        let contents: Vec<&str> = nodes.iter().map(|n| n.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            vec![
+                "Project",
+                "Parent",
+                "Child with <strong>bold</strong>",
+                "First",
+                "Quote",
+                "",
+                "let x = a &lt; b;"
+            ]
+        );
+
+        let project = &nodes[0];
+        assert_eq!(project.node_type, NodeType::Heading);
+        assert_eq!(nodes[1].parent_id, Some(project.id));
+        assert_eq!(nodes[1].note.as_deref(), Some("A note"));
+        assert_eq!(nodes[2].parent_id, Some(nodes[1].id));
+        assert_eq!(nodes[3].node_type, NodeType::Numbered);
+        assert_eq!(nodes[3].position, 1.0);
+        assert_eq!(nodes[4].node_type, NodeType::Quote);
+        assert_eq!(nodes[5].node_type, NodeType::Divider);
+        assert_eq!(nodes[6].node_type, NodeType::CodeBlock);
+        assert_eq!(nodes[6].language.as_deref(), Some("rust"));
+    }
+
+    #[test]
+    fn test_parse_markdown_obsidian_tasks() {
+        let md = "- [ ] Pay rent ⏫ 📅 2024-02-01 🔁 every month\n- [x] Call Bob ✅ 2024-01-10\n";
+        let nodes = parse_markdown(md);
+
+        assert_eq!(nodes[0].content, "Pay rent");
+        assert_eq!(nodes[0].node_type, NodeType::Checkbox);
+        assert!(!nodes[0].is_checked);
+        assert_eq!(nodes[0].priority, Some(2));
+        assert_eq!(nodes[0].date.as_deref(), Some("2024-02-01"));
+        assert_eq!(nodes[0].date_recurrence.as_deref(), Some("FREQ=MONTHLY"));
+
+        assert!(nodes[1].is_checked);
+        assert_eq!(nodes[1].properties[COMPLETED_PROPERTY], "2024-01-10");
+    }
+
+    #[test]
+    fn test_markdown_roundtrip() {
+        let mut task = Node::new("Review <em>draft</em>".to_string());
+        task.node_type = NodeType::Checkbox;
+        task.is_checked = true;
+        task.priority = Some(1);
+        task.date = Some("2024-03-01".to_string());
+        task.date_recurrence = Some("FREQ=WEEKLY;BYDAY=MO".to_string());
+        task.properties.insert(COMPLETED_PROPERTY.to_string(), "2024-02-28".to_string());
+        let child = Node::new_child(task.id, 0.0, "Details".to_string());
+
+        let md = generate_markdown(&[task.clone(), child], &MarkdownOptions::default());
+        let parsed = parse_markdown(&md);
+        assert_eq!(generate_markdown(&parsed, &MarkdownOptions::default()), md);
+
+        assert_eq!(parsed[0].content, task.content);
+        assert_eq!(parsed[0].date, task.date);
+        assert_eq!(parsed[0].date_recurrence, task.date_recurrence);
+        assert_eq!(parsed[0].priority, task.priority);
+        assert_eq!(parsed[1].parent_id, Some(parsed[0].id));
+    }
+
+    #[test]
+    fn test_markdown_options() {
+        let mut heading = Node::new("Plan".to_string());
+        heading.node_type = NodeType::Heading;
+        heading.heading_level = Some(2);
+        heading.note = Some("Why".to_string());
+        let mut task = Node::new_child(heading.id, 0.0, "Task".to_string());
+        task.node_type = NodeType::Checkbox;
+        task.date = Some("2024-01-15".to_string());
+        let child = Node::new_child(task.id, 0.0, "Detail".to_string());
+        let nodes = vec![heading, task, child];
+
+        let default = generate_markdown(&nodes, &MarkdownOptions::default());
+        assert_eq!(default, "- Plan\n  Why\n  - [ ] Task 📅 2024-01-15\n    - Detail\n");
+
+        let options = MarkdownOptions {
+            headings: true,
+            task_emoji: false,
+            indent_width: 4,
+            note_style: NoteStyle::Blockquote,
+            color_tags: false,
+        };
+        let md = generate_markdown(&nodes, &options);
+        assert_eq!(md, "## Plan\n> Why\n- [ ] Task\n    - Detail\n");
+    }
+
+    #[test]
+    fn test_code_block_text() {
+        assert_eq!(code_block_text("fn main() {<br>    x &lt; 1;<br/>}"), "fn main() {\n    x < 1;\n}");
+        assert_eq!(code_block_text("<p>a &amp;&amp; b</p><p>c</p>"), "a && b\nc");
+        assert_eq!(code_block_text("&amp;lt;"), "&lt;");
+    }
+
+    #[test]
+    fn test_generate_markdown_code_block() {
+        let parent = Node::new("Example".to_string());
+        let mut code = Node::new_child(parent.id, 0.0, "let x = 1;<br>let y = *x;".to_string());
+        code.node_type = NodeType::CodeBlock;
+        code.language = Some("rust".to_string());
+
+        let md = generate_markdown(&[parent, code], &MarkdownOptions::default());
+        assert_eq!(md, "- Example\n  ```rust\n  let x = 1;\n  let y = *x;\n  ```\n");
+    }
+
+    #[test]
+    fn test_markdown_color_tags() {
+        let mut node = Node::new("Urgent".to_string());
+        node.color = Some("red".to_string());
+        let nodes = vec![node];
+
+        assert_eq!(generate_markdown(&nodes, &MarkdownOptions::default()), "- Urgent\n");
+        let options = MarkdownOptions {
+            color_tags: true,
+            ..Default::default()
+        };
+        assert_eq!(generate_markdown(&nodes, &options), "- Urgent #color/red\n");
+    }
 }
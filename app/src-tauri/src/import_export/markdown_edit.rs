@@ -0,0 +1,339 @@
+//! Editing a document as Markdown: the ops that turn a document into an
+//! edited copy of its Markdown rendering. The document's own rendering and
+//! the edited text are both parsed and their lines lined up (common prefix
+//! and suffix, then a longest common subsequence), so unchanged lines keep
+//! their nodes and anything Markdown can't express (colors, tags, comments)
+//! survives the round trip.
+
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use super::markdown::{generate_markdown, parse_markdown, MarkdownOptions};
+use crate::data::{
+    create_op_with_id, delete_op, move_op, update_op, DocumentState, Node, NodeChanges, Operation,
+};
+
+/// Largest table the line alignment builds, to bound memory on huge rewrites
+const MAX_ALIGNMENT_CELLS: usize = 4_000_000;
+
+/// Nodes reachable from the top level in outline order, as rendered
+fn preorder(nodes: &[Node]) -> Vec<&Node> {
+    let mut children_of: HashMap<Option<Uuid>, Vec<&Node>> = HashMap::new();
+    for node in nodes {
+        children_of.entry(node.parent_id).or_default().push(node);
+    }
+    for children in children_of.values_mut() {
+        children.sort_by(|a, b| a.position.total_cmp(&b.position));
+    }
+
+    let mut order = Vec::new();
+    let mut stack: Vec<&Node> = children_of.get(&None).map(|c| c.iter().rev().copied().collect()).unwrap_or_default();
+    while let Some(node) = stack.pop() {
+        order.push(node);
+        if let Some(children) = children_of.get(&Some(node.id)) {
+            stack.extend(children.iter().rev().copied());
+        }
+    }
+    order
+}
+
+/// Depth of each parsed node (parse_markdown lists parents before children)
+fn depths(nodes: &[Node]) -> Vec<usize> {
+    let mut depth_of: HashMap<Uuid, usize> = HashMap::new();
+    nodes
+        .iter()
+        .map(|node| {
+            let depth = node.parent_id.and_then(|p| depth_of.get(&p)).map_or(0, |d| d + 1);
+            depth_of.insert(node.id, depth);
+            depth
+        })
+        .collect()
+}
+
+/// The fields of `new` that differ from `old`, among those Markdown carries
+fn markdown_changes(old: &Node, new: &Node) -> Option<NodeChanges> {
+    let mut changes = NodeChanges::default();
+    let mut changed = false;
+    let mut set = |differs: bool| {
+        changed |= differs;
+        differs
+    };
+    if set(old.content != new.content) {
+        changes.content = Some(new.content.clone());
+    }
+    if set(old.note != new.note) {
+        changes.note = Some(new.note.clone().unwrap_or_default());
+    }
+    if set(old.node_type != new.node_type) {
+        changes.node_type = Some(new.node_type.clone());
+    }
+    if set(old.heading_level != new.heading_level) {
+        changes.heading_level = new.heading_level;
+    }
+    if set(old.language != new.language) {
+        changes.language = Some(new.language.clone().unwrap_or_default());
+    }
+    if set(old.is_checked != new.is_checked) {
+        changes.is_checked = Some(new.is_checked);
+    }
+    if set(old.priority != new.priority) {
+        changes.priority = Some(new.priority.unwrap_or(0));
+    }
+    if set(old.date != new.date) {
+        changes.date = Some(new.date.clone().unwrap_or_default());
+    }
+    if set(old.date_recurrence != new.date_recurrence) {
+        changes.date_recurrence = Some(new.date_recurrence.clone().unwrap_or_default());
+    }
+    if set(old.properties != new.properties) {
+        let mut properties = new.properties.clone();
+        for key in old.properties.keys().filter(|k| !new.properties.contains_key(*k)) {
+            properties.insert(key.clone(), String::new());
+        }
+        changes.properties = Some(properties);
+    }
+    changed.then_some(changes)
+}
+
+/// Pairs (before, after) of lines that are unchanged
+fn align(before: &[Node], before_depths: &[usize], after: &[Node], after_depths: &[usize]) -> Result<Vec<(usize, usize)>, String> {
+    let same = |i: usize, j: usize| before_depths[i] == after_depths[j] && markdown_changes(&before[i], &after[j]).is_none();
+
+    let mut prefix = 0;
+    while prefix < before.len() && prefix < after.len() && same(prefix, prefix) {
+        prefix += 1;
+    }
+    let mut suffix = 0;
+    while suffix < before.len() - prefix
+        && suffix < after.len() - prefix
+        && same(before.len() - 1 - suffix, after.len() - 1 - suffix)
+    {
+        suffix += 1;
+    }
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    let (n, m) = (before.len() - prefix - suffix, after.len() - prefix - suffix);
+    if (n + 1) * (m + 1) > MAX_ALIGNMENT_CELLS {
+        return Err("Too many changes to apply at once".to_string());
+    }
+    // lcs[i][j]: longest common subsequence of the middles from i and j on
+    let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[at(i, j)] = if same(prefix + i, prefix + j) {
+                lcs[at(i + 1, j + 1)] + 1
+            } else {
+                lcs[at(i + 1, j)].max(lcs[at(i, j + 1)])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if same(prefix + i, prefix + j) {
+            pairs.push((prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if lcs[at(i + 1, j)] >= lcs[at(i, j + 1)] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs.extend((0..suffix).map(|k| (before.len() - suffix + k, after.len() - suffix + k)));
+    Ok(pairs)
+}
+
+/// Positions for a parent's children in their new order. Children already
+/// under this parent in increasing order keep their positions; the others
+/// are spread between them.
+fn child_positions(children: &[Uuid], current: &HashMap<Uuid, (Option<Uuid>, f64)>, parent: Option<Uuid>) -> Vec<f64> {
+    let mut kept: Vec<Option<f64>> = Vec::with_capacity(children.len());
+    let mut last: Option<f64> = None;
+    for id in children {
+        let position = current
+            .get(id)
+            .filter(|(p, pos)| *p == parent && last.map_or(true, |l| *pos > l))
+            .map(|(_, pos)| *pos);
+        if position.is_some() {
+            last = position;
+        }
+        kept.push(position);
+    }
+
+    let mut positions = Vec::with_capacity(children.len());
+    let mut start = 0;
+    while start < kept.len() {
+        if let Some(position) = kept[start] {
+            positions.push(position);
+            start += 1;
+            continue;
+        }
+        let end = (start..kept.len()).find(|&k| kept[k].is_some()).unwrap_or(kept.len());
+        let before = start.checked_sub(1).and_then(|k| kept[k]);
+        let after = kept.get(end).copied().flatten();
+        let count = (end - start) as f64;
+        for k in 1..=(end - start) {
+            let k = k as f64;
+            positions.push(match (before, after) {
+                (Some(b), Some(a)) => b + (a - b) * k / (count + 1.0),
+                (Some(b), None) => b.floor() + k,
+                (None, Some(a)) => a - count - 1.0 + k,
+                (None, None) => k - 1.0,
+            });
+        }
+        start = end;
+    }
+    positions
+}
+
+/// Ops that turn a document into `markdown`, an edited copy of its rendering
+/// with the default Markdown options
+pub fn markdown_edit_ops(state: &DocumentState, markdown: &str) -> Result<Vec<Operation>, String> {
+    let order = preorder(&state.nodes);
+    let before = parse_markdown(&generate_markdown(&state.nodes, &MarkdownOptions::default()));
+    if before.len() != order.len() {
+        return Err("This document can't be edited as Markdown".to_string());
+    }
+    let after = parse_markdown(markdown);
+    let (before_depths, after_depths) = (depths(&before), depths(&after));
+    let pairs = align(&before, &before_depths, &after, &after_depths)?;
+
+    // Lines between two unchanged ones are edits of each other: the same
+    // text (e.g. checked off or re-indented), else the next edited line.
+    // Whatever is left over was added or removed.
+    let mut matched: Vec<(usize, usize)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for &(pi, pj) in pairs.iter().chain(std::iter::once(&(before.len(), after.len()))) {
+        let mut free: Vec<usize> = (i..pi).collect();
+        let mut unpaired = Vec::new();
+        for (aj, new) in after.iter().enumerate().take(pj).skip(j) {
+            match free.iter().position(|&bi| before[bi].content == new.content) {
+                Some(k) => matched.push((free.remove(k), aj)),
+                None => unpaired.push(aj),
+            }
+        }
+        matched.extend(free.into_iter().zip(unpaired));
+        if pi < before.len() {
+            matched.push((pi, pj));
+        }
+        i = pi + 1;
+        j = pj + 1;
+    }
+
+    let mut final_id: HashMap<Uuid, Uuid> = HashMap::new();
+    let mut source: HashMap<usize, usize> = HashMap::new();
+    for &(bi, aj) in &matched {
+        final_id.insert(after[aj].id, order[bi].id);
+        source.insert(aj, bi);
+    }
+    let current: HashMap<Uuid, (Option<Uuid>, f64)> =
+        state.nodes.iter().map(|n| (n.id, (n.parent_id, n.position))).collect();
+
+    // New parent and position of every line
+    let id_of = |j: usize| final_id.get(&after[j].id).copied().unwrap_or(after[j].id);
+    let parent_of = |j: usize| after[j].parent_id.map(|p| final_id.get(&p).copied().unwrap_or(p));
+    let mut children_of: Vec<(Option<Uuid>, Vec<usize>)> = Vec::new();
+    for j in 0..after.len() {
+        let parent = parent_of(j);
+        match children_of.iter_mut().find(|(p, _)| *p == parent) {
+            Some((_, children)) => children.push(j),
+            None => children_of.push((parent, vec![j])),
+        }
+    }
+    let mut position_of: HashMap<usize, f64> = HashMap::new();
+    for (parent, children) in &children_of {
+        let ids: Vec<Uuid> = children.iter().map(|&j| id_of(j)).collect();
+        position_of.extend(children.iter().copied().zip(child_positions(&ids, &current, *parent)));
+    }
+
+    let mut ops = Vec::new();
+    for (j, new) in after.iter().enumerate() {
+        let (parent, position) = (parent_of(j), position_of[&j]);
+        match source.get(&j) {
+            Some(&bi) => {
+                let id = order[bi].id;
+                if current.get(&id) != Some(&(parent, position)) {
+                    ops.push(move_op(id, parent, position));
+                }
+                if let Some(changes) = markdown_changes(&before[bi], new) {
+                    ops.push(update_op(id, changes));
+                }
+            }
+            None => {
+                ops.push(create_op_with_id(new.id, parent, position, new.content.clone(), new.node_type.clone()));
+                let blank = Node {
+                    node_type: new.node_type.clone(),
+                    ..Node::new(new.content.clone())
+                };
+                if let Some(changes) = markdown_changes(&blank, new) {
+                    ops.push(update_op(new.id, changes));
+                }
+            }
+        }
+    }
+
+    // Removed lines go last, once their surviving children have moved out;
+    // a removed node's removed descendants go with it
+    let kept: HashSet<usize> = matched.iter().map(|&(bi, _)| bi).collect();
+    let removed: HashSet<Uuid> = (0..order.len()).filter(|i| !kept.contains(i)).map(|i| order[i].id).collect();
+    for node in &order {
+        if removed.contains(&node.id) && !node.parent_id.is_some_and(|p| removed.contains(&p)) {
+            ops.push(delete_op(node.id));
+        }
+    }
+    Ok(ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(state: &mut DocumentState, ops: &[Operation]) {
+        for op in ops {
+            op.apply(state);
+        }
+    }
+
+    fn outline(state: &DocumentState) -> String {
+        generate_markdown(&state.nodes, &MarkdownOptions::default())
+    }
+
+    #[test]
+    fn test_markdown_edit_ops() {
+        let mut groceries = Node::new("Groceries".to_string());
+        groceries.color = Some("green".to_string());
+        let milk = Node::new_child(groceries.id, 0.0, "Milk".to_string());
+        let mut eggs = Node::new_child(groceries.id, 1.0, "Eggs".to_string());
+        eggs.tags = vec!["dairy".to_string()];
+        let mut work = Node::new("Work".to_string());
+        work.position = 1.0;
+        let call = Node::new_child(work.id, 0.0, "Call Bob".to_string());
+        let mut state = DocumentState {
+            nodes: vec![groceries.clone(), milk.clone(), eggs.clone(), work.clone(), call],
+        };
+
+        // Unchanged text means no ops
+        assert!(markdown_edit_ops(&state, &outline(&state)).unwrap().is_empty());
+
+        // Edit a line, check one off and nest it, add one and drop one
+        let edited = "- Groceries\n  - Oat milk\n    - [x] Eggs\n  - Bread\n- Work\n";
+        let ops = markdown_edit_ops(&state, edited).unwrap();
+        apply(&mut state, &ops);
+        // Checking off stamps the completion date
+        let rendered: String = outline(&state).lines().map(|l| format!("{}\n", l.split(" ✅").next().unwrap())).collect();
+        assert_eq!(rendered, edited);
+
+        // Edited nodes keep their ids and what Markdown doesn't show
+        let find = |content: &str| state.nodes.iter().find(|n| n.content == content).unwrap().clone();
+        assert_eq!(find("Groceries").id, groceries.id);
+        assert_eq!(find("Groceries").color.as_deref(), Some("green"));
+        assert_eq!(find("Oat milk").id, milk.id);
+        assert_eq!(find("Eggs").id, eggs.id);
+        assert_eq!(find("Eggs").tags, vec!["dairy"]);
+        assert_eq!(find("Eggs").parent_id, Some(milk.id));
+        assert!(find("Eggs").is_checked);
+        assert_eq!(find("Work").id, work.id);
+        assert_eq!(state.nodes.len(), 5);
+    }
+}
@@ -1,7 +1,25 @@
 mod opml;
 mod markdown;
+mod markdown_edit;
 mod json;
+mod html;
+mod dynalist;
+mod scope;
+mod latex;
+mod archive;
+mod references;
+mod notes;
+mod workspace;
 
 pub use opml::*;
 pub use markdown::*;
+pub use markdown_edit::*;
 pub use json::*;
+pub use html::*;
+pub use dynalist::*;
+pub use scope::*;
+pub use latex::*;
+pub use archive::*;
+pub use references::*;
+pub use notes::*;
+pub use workspace::*;
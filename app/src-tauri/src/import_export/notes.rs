@@ -0,0 +1,190 @@
+//! Importers for note apps: Evernote ENEX exports (Apple Notes can export
+//! these too) and folders of plain text files. Each note becomes its own
+//! document: a title node carrying the note's tags, with one child per
+//! paragraph. Every node keeps the note's creation date.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use regex::Regex;
+use std::sync::OnceLock;
+
+use super::html::escape_html;
+use crate::data::{strip_html, Node};
+
+/// A note converted to a document's nodes
+#[derive(Debug, Clone)]
+pub struct ImportedNote {
+    pub title: String,
+    pub nodes: Vec<Node>,
+}
+
+/// Build a note's nodes from its plain text. Paragraphs are separated by
+/// blank lines; line breaks within a paragraph are kept.
+pub fn note_from_text(title: &str, text: &str, created_at: Option<DateTime<Utc>>, tags: Vec<String>) -> ImportedNote {
+    let title = match title.trim() {
+        "" => "Untitled",
+        t => t,
+    };
+    let stamp = |mut node: Node| {
+        if let Some(at) = created_at {
+            node.created_at = at;
+            node.updated_at = at;
+        }
+        node
+    };
+
+    let mut root = stamp(Node::new(escape_html(title)));
+    root.tags = tags;
+    let mut nodes = vec![root.clone()];
+    let mut lines: Vec<&str> = Vec::new();
+    let flush = |lines: &mut Vec<&str>, nodes: &mut Vec<Node>| {
+        if lines.is_empty() {
+            return;
+        }
+        let content = lines.iter().map(|l| escape_html(l)).collect::<Vec<_>>().join("<br>");
+        let position = (nodes.len() - 1) as f64;
+        nodes.push(stamp(Node::new_child(root.id, position, content)));
+        lines.clear();
+    };
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            flush(&mut lines, &mut nodes);
+        } else {
+            lines.push(line);
+        }
+    }
+    flush(&mut lines, &mut nodes);
+
+    ImportedNote {
+        title: title.to_string(),
+        nodes,
+    }
+}
+
+/// Plain text of an ENML note body, with a line break after each block
+/// element (Evernote writes one `<div>` per line)
+fn enml_to_text(enml: &str) -> String {
+    static BLOCK_END: OnceLock<Regex> = OnceLock::new();
+    let block_end = BLOCK_END.get_or_init(|| {
+        Regex::new(r"(?i)<br\s*/?>|</(div|p|li|h[1-6]|tr|blockquote|pre)\s*>").unwrap()
+    });
+    // Drop the XML declaration and doctype before stripping tags
+    let body = enml.find("<en-note").map_or(enml, |start| &enml[start..]);
+    strip_html(&block_end.replace_all(body, "\n"))
+}
+
+/// ENEX timestamps look like 20240115T093000Z
+fn parse_enex_date(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value.trim(), "%Y%m%dT%H%M%SZ")
+        .ok()
+        .map(|at| at.and_utc())
+}
+
+/// Parse an Evernote ENEX export into notes
+pub fn parse_enex(content: &str) -> Result<Vec<ImportedNote>, String> {
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().trim_text(true);
+
+    let mut notes = Vec::new();
+    let mut buf = Vec::new();
+    // Element whose text is being read, and the fields of the open note
+    let mut field: Option<String> = None;
+    let mut in_note = false;
+    let mut title = String::new();
+    let mut body = String::new();
+    let mut created = None;
+    let mut tags = Vec::new();
+
+    loop {
+        let text = match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                if name == "note" {
+                    in_note = true;
+                    title.clear();
+                    body.clear();
+                    created = None;
+                    tags.clear();
+                }
+                field = Some(name);
+                None
+            }
+            Ok(Event::End(ref e)) => {
+                if e.name().as_ref() == b"note" && in_note {
+                    in_note = false;
+                    notes.push(note_from_text(&title, &enml_to_text(&body), created, tags.clone()));
+                }
+                field = None;
+                None
+            }
+            Ok(Event::Text(ref e)) => Some(e.unescape().map_err(|e| format!("ENEX decode error: {}", e))?.to_string()),
+            Ok(Event::CData(e)) => Some(String::from_utf8_lossy(&e.into_inner()).to_string()),
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("XML parse error: {}", e)),
+            _ => None,
+        };
+        if let (Some(text), true) = (text, in_note) {
+            match field.as_deref() {
+                Some("title") => title.push_str(&text),
+                Some("content") => body.push_str(&text),
+                Some("created") => created = parse_enex_date(&text),
+                Some("tag") => tags.push(text.trim().replace(char::is_whitespace, "-")),
+                _ => {}
+            }
+        }
+        buf.clear();
+    }
+
+    Ok(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_note_from_text() {
+        let note = note_from_text("Ideas", "First line\nsame paragraph\n\n\n  <Second> & more  \n", None, Vec::new());
+        assert_eq!(note.nodes.len(), 3);
+        assert_eq!(note.nodes[1].content, "First line<br>same paragraph");
+        assert_eq!(note.nodes[2].content, "  &lt;Second&gt; &amp; more");
+        assert!(note.nodes[1..].iter().all(|n| n.parent_id == Some(note.nodes[0].id)));
+        assert!(note.nodes[1].position < note.nodes[2].position);
+    }
+
+    #[test]
+    fn test_parse_enex() {
+        let enex = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-export SYSTEM "http://xml.evernote.com/pub/evernote-export4.dtd">
+<en-export export-date="20240201T120000Z" application="Evernote">
+  <note>
+    <title>Trip &amp; packing</title>
+    <content><![CDATA[<?xml version="1.0" encoding="UTF-8"?><!DOCTYPE en-note SYSTEM "http://xml.evernote.com/pub/enml2.dtd"><en-note><div>Passport</div><div>Charger &amp; <b>cables</b></div><div><br/></div><div>Book hotel</div></en-note>]]></content>
+    <created>20240115T093000Z</created>
+    <updated>20240120T100000Z</updated>
+    <tag>travel</tag>
+    <tag>to do</tag>
+  </note>
+  <note>
+    <title>Empty</title>
+    <content><![CDATA[<en-note></en-note>]]></content>
+  </note>
+</en-export>"#;
+        let notes = parse_enex(enex).unwrap();
+        assert_eq!(notes.len(), 2);
+
+        let trip = &notes[0];
+        assert_eq!(trip.title, "Trip & packing");
+        assert_eq!(trip.nodes[0].content, "Trip &amp; packing");
+        assert_eq!(trip.nodes[0].tags, vec!["travel", "to-do"]);
+        let contents: Vec<&str> = trip.nodes[1..].iter().map(|n| n.content.as_str()).collect();
+        assert_eq!(contents, vec!["Passport<br>Charger &amp; cables", "Book hotel"]);
+        let created = Utc.with_ymd_and_hms(2024, 1, 15, 9, 30, 0).unwrap();
+        assert!(trip.nodes.iter().all(|n| n.created_at == created));
+
+        assert_eq!(notes[1].nodes.len(), 1);
+    }
+}
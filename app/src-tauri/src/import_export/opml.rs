@@ -2,10 +2,12 @@ use chrono::Utc;
 use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::{Reader, Writer};
 use regex::Regex;
+use std::collections::HashMap;
 use std::io::Cursor;
 use uuid::Uuid;
 
-use crate::data::Node;
+use super::markdown::code_block_text;
+use crate::data::{strip_html, Node, NodeType};
 
 /// Extract title from OPML content
 pub fn get_opml_title(content: &str) -> Option<String> {
@@ -56,7 +58,7 @@ pub fn parse_opml(content: &str) -> Result<Vec<Node>, String> {
 
     let mut nodes = Vec::new();
     // Stack of (parent_id, next_child_position)
-    let mut parent_stack: Vec<(Option<Uuid>, i32)> = vec![(None, 0)];
+    let mut parent_stack: Vec<(Option<Uuid>, f64)> = vec![(None, 0.0)];
     let mut in_body = false;
     let mut buf = Vec::new();
 
@@ -74,7 +76,7 @@ pub fn parse_opml(content: &str) -> Result<Vec<Node>, String> {
                     nodes.push(node);
 
                     // Push this node as parent for children
-                    parent_stack.push((Some(node_id), 0));
+                    parent_stack.push((Some(node_id), 0.0));
                 }
             }
             Ok(Event::Empty(ref e)) => {
@@ -108,13 +110,16 @@ pub fn parse_opml(content: &str) -> Result<Vec<Node>, String> {
 
 fn parse_outline_element(
     e: &BytesStart,
-    parent_stack: &mut Vec<(Option<Uuid>, i32)>,
+    parent_stack: &mut [(Option<Uuid>, f64)],
 ) -> Result<Node, String> {
     let mut text = String::new();
     let mut note: Option<String> = None;
     let mut is_checked = false;
     let mut color: Option<String> = None;
     let mut heading_level: Option<u8> = None;
+    let mut node_type_attr: Option<NodeType> = None;
+    let mut language: Option<String> = None;
+    let mut properties: HashMap<String, String> = HashMap::new();
 
     for attr in e.attributes().flatten() {
         let key = String::from_utf8_lossy(attr.key.as_ref());
@@ -129,20 +134,27 @@ fn parse_outline_element(
             // Dynalist uses "complete" attribute for checked items
             "complete" => is_checked = value == "true",
             // Dynalist color labels: 1=red, 2=orange, 3=yellow, 4=green, 5=blue, 6=purple
-            "colorLabel" => {
-                color = match value.as_str() {
-                    "1" => Some("red".to_string()),
-                    "2" => Some("orange".to_string()),
-                    "3" => Some("yellow".to_string()),
-                    "4" => Some("green".to_string()),
-                    "5" => Some("blue".to_string()),
-                    "6" => Some("purple".to_string()),
+            "colorLabel" => color = value.parse().ok().and_then(dynalist_color),
+            // Colors without a Dynalist label
+            "_color" => color = color.or(Some(value)),
+            // Dynalist headings (1-6)
+            "heading" => {
+                heading_level = value.parse::<u8>().ok().filter(|h| (1..=6).contains(h));
+            }
+            // Our own extension for types Dynalist lacks
+            "_type" => {
+                node_type_attr = match value.as_str() {
+                    "numbered" => Some(NodeType::Numbered),
+                    "quote" => Some(NodeType::Quote),
+                    "divider" => Some(NodeType::Divider),
+                    "code" => Some(NodeType::CodeBlock),
                     _ => None,
                 };
             }
-            // Dynalist headings (1-6)
-            "heading" => {
-                heading_level = value.parse::<u8>().ok().filter(|&h| h >= 1 && h <= 6);
+            "_language" => language = Some(value),
+            // Key-value properties as a JSON object
+            "_properties" => {
+                properties = serde_json::from_str(&value).unwrap_or_default();
             }
             _ => {}
         }
@@ -151,25 +163,32 @@ fn parse_outline_element(
     // Get parent_id and position from stack
     let (parent_id, position) = if let Some((pid, pos)) = parent_stack.last_mut() {
         let current_pos = *pos;
-        *pos += 1;
+        *pos += 1.0;
         (*pid, current_pos)
     } else {
-        (None, 0)
+        (None, 0.0)
     };
 
-    // Process text to extract dates and convert special syntax
-    let (processed_text, date, date_recurrence) = process_dynalist_content(&text);
+    // Process text to extract dates and convert special syntax (code is kept verbatim)
+    let (processed_text, date, date_recurrence) = if node_type_attr == Some(NodeType::CodeBlock) {
+        let escaped = text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        (escaped.replace('\n', "<br>"), None, None)
+    } else {
+        process_dynalist_content(&text)
+    };
 
     // Convert special syntax in notes too
     let processed_note = note.map(|n| convert_dynalist_syntax(&n));
 
     // Determine node type
-    let node_type = if heading_level.is_some() {
-        crate::data::NodeType::Heading
+    let node_type = if let Some(node_type) = node_type_attr {
+        node_type
+    } else if heading_level.is_some() {
+        NodeType::Heading
     } else if is_checked {
-        crate::data::NodeType::Checkbox
+        NodeType::Checkbox
     } else {
-        crate::data::NodeType::Bullet
+        NodeType::Bullet
     };
 
     let now = Utc::now();
@@ -181,20 +200,45 @@ fn parse_outline_element(
         note: processed_note,
         node_type,
         heading_level,
+        language,
         is_checked,
+        priority: None,
         color,
         tags: Vec::new(),
+        properties,
         date,
         date_recurrence,
+        remind_at: None,
         collapsed: false,
         mirror_source_id: None,
+        comments: Vec::new(),
+        encrypted: false,
         created_at: now,
         updated_at: now,
     })
 }
 
+/// Map a Dynalist color label number to our color name
+pub(crate) fn dynalist_color(label: u8) -> Option<String> {
+    let name = match label {
+        1 => "red",
+        2 => "orange",
+        3 => "yellow",
+        4 => "green",
+        5 => "blue",
+        6 => "purple",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Map our color name to its Dynalist color label number
+fn dynalist_color_label(color: &str) -> Option<u8> {
+    (1..=6).find(|&label| dynalist_color(label).as_deref() == Some(color))
+}
+
 /// Process Dynalist-specific content, extracting dates and converting syntax
-fn process_dynalist_content(text: &str) -> (String, Option<String>, Option<String>) {
+pub(crate) fn process_dynalist_content(text: &str) -> (String, Option<String>, Option<String>) {
     // Extract Dynalist dates: !(2024-09-01) or !(2024-09-01 | 1y)
     // Capture: date part, optional recurrence part
     let date_re = Regex::new(r"!\((\d{4}-\d{2}-\d{2})(?:\s*\|\s*([^)]+))?\)\s*").unwrap();
@@ -250,7 +294,7 @@ fn convert_dynalist_recurrence(rec: &str) -> Option<String> {
 }
 
 /// Convert Dynalist-specific syntax to our format
-fn convert_dynalist_syntax(text: &str) -> String {
+pub(super) fn convert_dynalist_syntax(text: &str) -> String {
     let mut result = text.to_string();
 
     // Convert Obsidian links: [@ob](obsidian://open?vault=...&file=...) -> [[page-name]]
@@ -274,8 +318,9 @@ fn convert_dynalist_syntax(text: &str) -> String {
 }
 
 
-/// Generate OPML content from nodes
-pub fn generate_opml(nodes: &[Node], title: &str) -> Result<String, String> {
+/// Generate OPML content from nodes. With `colors`, node colors are written
+/// as Dynalist color labels.
+pub fn generate_opml(nodes: &[Node], title: &str, colors: bool) -> Result<String, String> {
     let mut writer = Writer::new(Cursor::new(Vec::new()));
 
     // XML declaration
@@ -315,7 +360,7 @@ pub fn generate_opml(nodes: &[Node], title: &str) -> Result<String, String> {
         .map_err(|e| format!("Write error: {}", e))?;
 
     // Write nodes recursively
-    write_opml_nodes(&mut writer, nodes, None)?;
+    write_opml_nodes(&mut writer, nodes, None, colors)?;
 
     writer
         .write_event(Event::End(BytesEnd::new("body")))
@@ -333,16 +378,21 @@ fn write_opml_nodes<W: std::io::Write>(
     writer: &mut Writer<W>,
     nodes: &[Node],
     parent_id: Option<Uuid>,
+    colors: bool,
 ) -> Result<(), String> {
     // Get children of this parent, sorted by position
     let mut children: Vec<_> = nodes.iter().filter(|n| n.parent_id == parent_id).collect();
-    children.sort_by_key(|n| n.position);
+    children.sort_by(|a, b| a.position.total_cmp(&b.position));
 
     for node in children {
         let mut outline = BytesStart::new("outline");
 
-        // Strip HTML tags from content for OPML text
-        let text = strip_html(&node.content);
+        // Strip HTML tags from content for OPML text (code keeps its line breaks)
+        let text = if node.node_type == NodeType::CodeBlock {
+            code_block_text(&node.content)
+        } else {
+            strip_html(&node.content).trim().to_string()
+        };
         outline.push_attribute(("text", text.as_str()));
 
         // Add note if present
@@ -350,6 +400,32 @@ fn write_opml_nodes<W: std::io::Write>(
             outline.push_attribute(("_note", note.as_str()));
         }
 
+        // Node types without a Dynalist equivalent
+        let node_type = match node.node_type {
+            NodeType::Numbered => Some("numbered"),
+            NodeType::Quote => Some("quote"),
+            NodeType::Divider => Some("divider"),
+            NodeType::CodeBlock => Some("code"),
+            _ => None,
+        };
+        if let Some(node_type) = node_type {
+            outline.push_attribute(("_type", node_type));
+        }
+        if let Some(ref language) = node.language {
+            outline.push_attribute(("_language", language.as_str()));
+        }
+        if let Some(color) = node.color.as_deref().filter(|_| colors) {
+            match dynalist_color_label(color) {
+                Some(label) => outline.push_attribute(("colorLabel", label.to_string().as_str())),
+                None => outline.push_attribute(("_color", color)),
+            }
+        }
+        if !node.properties.is_empty() {
+            let properties = serde_json::to_string(&node.properties)
+                .map_err(|e| format!("Serialize properties: {}", e))?;
+            outline.push_attribute(("_properties", properties.as_str()));
+        }
+
         // Check if has children
         let has_children = nodes.iter().any(|n| n.parent_id == Some(node.id));
 
@@ -357,7 +433,7 @@ fn write_opml_nodes<W: std::io::Write>(
             writer
                 .write_event(Event::Start(outline))
                 .map_err(|e| format!("Write error: {}", e))?;
-            write_opml_nodes(writer, nodes, Some(node.id))?;
+            write_opml_nodes(writer, nodes, Some(node.id), colors)?;
             writer
                 .write_event(Event::End(BytesEnd::new("outline")))
                 .map_err(|e| format!("Write error: {}", e))?;
@@ -371,30 +447,6 @@ fn write_opml_nodes<W: std::io::Write>(
     Ok(())
 }
 
-/// Strip HTML tags from content
-fn strip_html(html: &str) -> String {
-    let mut result = String::with_capacity(html.len());
-    let mut in_tag = false;
-
-    for c in html.chars() {
-        match c {
-            '<' => in_tag = true,
-            '>' => in_tag = false,
-            _ if !in_tag => result.push(c),
-            _ => {}
-        }
-    }
-
-    result
-        .replace("&nbsp;", " ")
-        .replace("&amp;", "&")
-        .replace("&lt;", "<")
-        .replace("&gt;", ">")
-        .replace("&quot;", "\"")
-        .trim()
-        .to_string()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -419,17 +471,17 @@ mod tests {
         let first = &nodes[0];
         assert_eq!(strip_html(&first.content), "First item");
         assert!(first.parent_id.is_none());
-        assert_eq!(first.position, 0);
+        assert_eq!(first.position, 0.0);
 
         let second = &nodes[1];
         assert_eq!(strip_html(&second.content), "Second item");
         assert!(second.parent_id.is_none());
-        assert_eq!(second.position, 1);
+        assert_eq!(second.position, 1.0);
 
         let child = &nodes[2];
         assert_eq!(strip_html(&child.content), "Child item");
         assert_eq!(child.parent_id, Some(second.id));
-        assert_eq!(child.position, 0);
+        assert_eq!(child.position, 0.0);
     }
 
     #[test]
@@ -465,16 +517,81 @@ mod tests {
             Node::new("First item".to_string()),
             Node::new("Second item".to_string()),
         ];
-        nodes[0].position = 0;
-        nodes[1].position = 1;
+        nodes[0].position = 0.0;
+        nodes[1].position = 1.0;
 
-        let opml = generate_opml(&nodes, "Test Document").unwrap();
+        let opml = generate_opml(&nodes, "Test Document", true).unwrap();
         assert!(opml.contains("First item"));
         assert!(opml.contains("Second item"));
         assert!(opml.contains("opml version"));
         assert!(opml.contains("<title>Test Document</title>"));
     }
 
+    #[test]
+    fn test_node_type_roundtrip() {
+        let mut nodes = vec![
+            Node::new("Step".to_string()),
+            Node::new("Quoted".to_string()),
+            Node::new(String::new()),
+            Node::new("Plain".to_string()),
+        ];
+        let types = [NodeType::Numbered, NodeType::Quote, NodeType::Divider, NodeType::Bullet];
+        for (i, (node, t)) in nodes.iter_mut().zip(types.iter()).enumerate() {
+            node.position = i as f64;
+            node.node_type = t.clone();
+        }
+
+        let opml = generate_opml(&nodes, "Types", true).unwrap();
+        assert!(opml.contains(r#"_type="numbered""#));
+        let parsed = parse_opml(&opml).unwrap();
+        let parsed_types: Vec<NodeType> = parsed.iter().map(|n| n.node_type.clone()).collect();
+        assert_eq!(parsed_types, types);
+    }
+
+    #[test]
+    fn test_code_block_roundtrip() {
+        let mut code = Node::new("if a &lt; b {<br>    a == b<br>}".to_string());
+        code.node_type = NodeType::CodeBlock;
+        code.language = Some("rust".to_string());
+
+        let opml = generate_opml(&[code.clone()], "Code", true).unwrap();
+        assert!(opml.contains(r#"_language="rust""#));
+        let parsed = parse_opml(&opml).unwrap();
+        assert_eq!(parsed[0].node_type, NodeType::CodeBlock);
+        assert_eq!(parsed[0].language, Some("rust".to_string()));
+        // Not mangled by Dynalist syntax conversion (== highlights)
+        assert_eq!(parsed[0].content, code.content);
+    }
+
+    #[test]
+    fn test_properties_roundtrip() {
+        let mut node = Node::new("Order parts".to_string());
+        node.properties.insert("status".to_string(), "waiting".to_string());
+        node.properties.insert("vendor".to_string(), "Acme \"Widgets\"".to_string());
+
+        let opml = generate_opml(&[node.clone()], "Props", true).unwrap();
+        let parsed = parse_opml(&opml).unwrap();
+        assert_eq!(parsed[0].properties, node.properties);
+    }
+
+    #[test]
+    fn test_colors_roundtrip() {
+        let mut red = Node::new("Red".to_string());
+        red.color = Some("red".to_string());
+        let mut teal = Node::new("Teal".to_string());
+        teal.color = Some("teal".to_string());
+        teal.position = 1.0;
+
+        let opml = generate_opml(&[red.clone(), teal.clone()], "Colors", true).unwrap();
+        assert!(opml.contains(r#"colorLabel="1""#));
+        let parsed = parse_opml(&opml).unwrap();
+        assert_eq!(parsed[0].color.as_deref(), Some("red"));
+        assert_eq!(parsed[1].color.as_deref(), Some("teal"));
+
+        let plain = generate_opml(&[red, teal], "Colors", false).unwrap();
+        assert!(!plain.contains("colorLabel") && !plain.contains("_color"));
+    }
+
     #[test]
     fn test_roundtrip() {
         let original = r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -491,7 +608,7 @@ mod tests {
 </opml>"#;
 
         let nodes = parse_opml(original).unwrap();
-        let regenerated = generate_opml(&nodes, "Roundtrip Test").unwrap();
+        let regenerated = generate_opml(&nodes, "Roundtrip Test", true).unwrap();
 
         // Parse the regenerated OPML
         let nodes2 = parse_opml(&regenerated).unwrap();
@@ -526,7 +643,7 @@ mod tests {
         // Find completed task
         let completed = nodes.iter().find(|n| n.content == "Completed task").unwrap();
         assert!(completed.is_checked);
-        assert_eq!(completed.node_type, crate::data::NodeType::Checkbox);
+        assert_eq!(completed.node_type, NodeType::Checkbox);
 
         // Find colored items
         let red = nodes.iter().find(|n| n.content == "Important item").unwrap();
@@ -650,13 +767,13 @@ mod tests {
         let nodes = parse_opml(opml).unwrap();
         assert_eq!(nodes.len(), 3);
 
-        assert_eq!(nodes[0].node_type, crate::data::NodeType::Heading);
+        assert_eq!(nodes[0].node_type, NodeType::Heading);
         assert_eq!(nodes[0].heading_level, Some(1));
 
-        assert_eq!(nodes[1].node_type, crate::data::NodeType::Heading);
+        assert_eq!(nodes[1].node_type, NodeType::Heading);
         assert_eq!(nodes[1].heading_level, Some(3));
 
-        assert_eq!(nodes[2].node_type, crate::data::NodeType::Bullet);
+        assert_eq!(nodes[2].node_type, NodeType::Bullet);
         assert_eq!(nodes[2].heading_level, None);
     }
 
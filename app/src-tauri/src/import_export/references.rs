@@ -0,0 +1,86 @@
+use super::html::escape_html;
+use crate::search::LinkedReference;
+
+const HEADING: &str = "Linked references";
+
+/// Group references by source document, keeping their order
+fn by_document(refs: &[LinkedReference]) -> Vec<(&str, Vec<&LinkedReference>)> {
+    let mut groups: Vec<(&str, Vec<&LinkedReference>)> = Vec::new();
+    for r in refs {
+        match groups.last_mut() {
+            Some((_, group)) if group[0].source_document_id == r.source_document_id => group.push(r),
+            _ => groups.push((r.source_title.as_str(), vec![r])),
+        }
+    }
+    groups
+}
+
+/// A Markdown "Linked references" section listing the nodes of other
+/// documents that link here, under a heading per document. Empty when
+/// nothing links here.
+pub fn linked_references_markdown(refs: &[LinkedReference]) -> String {
+    if refs.is_empty() {
+        return String::new();
+    }
+    let mut output = format!("\n## {}\n", HEADING);
+    for (title, group) in by_document(refs) {
+        output.push_str(&format!("\n### {}\n\n", title));
+        for r in group {
+            output.push_str(&format!("- {}\n", r.content.trim()));
+        }
+    }
+    output
+}
+
+/// The same section for an HTML export, inserted before `</body>`
+pub fn append_linked_references_html(page: &mut String, refs: &[LinkedReference]) {
+    if refs.is_empty() {
+        return;
+    }
+    let mut section = format!("<section class=\"linked-references\">\n<h2>{}</h2>\n", HEADING);
+    for (title, group) in by_document(refs) {
+        section.push_str(&format!("<h3>{}</h3>\n<ul>\n", escape_html(title)));
+        for r in group {
+            section.push_str(&format!("<li>{}</li>\n", escape_html(r.content.trim())));
+        }
+        section.push_str("</ul>\n");
+    }
+    section.push_str("</section>\n");
+    let at = page.rfind("</body>").unwrap_or(page.len());
+    page.insert_str(at, &section);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Node;
+    use crate::import_export::generate_html;
+
+    fn reference(doc: &str, title: &str, content: &str) -> LinkedReference {
+        LinkedReference {
+            source_node_id: content.to_string(),
+            source_document_id: doc.to_string(),
+            source_title: title.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_linked_references_sections() {
+        let refs = vec![
+            reference("a", "Journal", "Met about the launch"),
+            reference("a", "Journal", "Launch slipped"),
+            reference("b", "Q&A", "Why <now>?"),
+        ];
+        assert_eq!(
+            linked_references_markdown(&refs),
+            "\n## Linked references\n\n### Journal\n\n- Met about the launch\n- Launch slipped\n\n### Q&A\n\n- Why <now>?\n"
+        );
+        assert_eq!(linked_references_markdown(&[]), "");
+
+        let mut page = generate_html(&[Node::new("Launch".to_string())], "Launch", false, false);
+        append_linked_references_html(&mut page, &refs);
+        assert!(page.contains("<h3>Q&amp;A</h3>\n<ul>\n<li>Why &lt;now&gt;?</li>"));
+        assert!(page.ends_with("</section>\n</body>\n</html>\n"));
+    }
+}
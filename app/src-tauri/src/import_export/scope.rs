@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::data::Node;
+
+/// Which part of an outline an export covers. Applied before any generator
+/// (OPML, Markdown, HTML) so all formats trim the tree the same way.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportScope {
+    /// Number of levels to include (1 = top-level nodes only)
+    pub max_depth: Option<usize>,
+    /// Leave out the descendants of collapsed nodes, matching what's on screen
+    pub respect_collapsed: bool,
+}
+
+impl ExportScope {
+    /// Filter nodes down to the scope. Nodes whose parent is missing from
+    /// `nodes` count as top-level.
+    pub fn apply(&self, nodes: &[Node]) -> Vec<Node> {
+        if self.max_depth.is_none() && !self.respect_collapsed {
+            return nodes.to_vec();
+        }
+
+        let ids: HashSet<Uuid> = nodes.iter().map(|n| n.id).collect();
+        let mut children_of: HashMap<Option<Uuid>, Vec<&Node>> = HashMap::new();
+        for node in nodes {
+            let parent_id = node.parent_id.filter(|p| ids.contains(p));
+            children_of.entry(parent_id).or_default().push(node);
+        }
+
+        let mut included = HashSet::new();
+        let mut stack: Vec<(&Node, usize)> = children_of
+            .get(&None)
+            .map(|roots| roots.iter().map(|n| (*n, 1)).collect())
+            .unwrap_or_default();
+
+        while let Some((node, depth)) = stack.pop() {
+            included.insert(node.id);
+
+            let below_limit = self.max_depth.map_or(true, |max| depth < max);
+            let expanded = !(self.respect_collapsed && node.collapsed);
+            if below_limit && expanded {
+                if let Some(children) = children_of.get(&Some(node.id)) {
+                    stack.extend(children.iter().map(|c| (*c, depth + 1)));
+                }
+            }
+        }
+
+        nodes
+            .iter()
+            .filter(|n| included.contains(&n.id))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_scope() {
+        let mut root = Node::new("Root".to_string());
+        let child = Node::new_child(root.id, 0.0, "Child".to_string());
+        let grandchild = Node::new_child(child.id, 0.0, "Grandchild".to_string());
+        let other = Node::new("Other".to_string());
+        let nodes = vec![root.clone(), child.clone(), grandchild, other.clone()];
+
+        let contents = |scope: &ExportScope, nodes: &[Node]| -> Vec<String> {
+            scope.apply(nodes).into_iter().map(|n| n.content).collect()
+        };
+
+        assert_eq!(contents(&ExportScope::default(), &nodes).len(), 4);
+
+        let two_levels = ExportScope {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(contents(&two_levels, &nodes), vec!["Root", "Child", "Other"]);
+
+        root.collapsed = true;
+        let visible = ExportScope {
+            respect_collapsed: true,
+            ..Default::default()
+        };
+        let nodes = vec![root, child, other];
+        assert_eq!(contents(&visible, &nodes), vec!["Root", "Other"]);
+    }
+}
@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::json::{nodes_from_backup, BACKUP_VERSION};
+use crate::data::{Folder, FolderState, Node};
+
+/// Whole-workspace backup: every document with its nodes, plus the folders
+/// and which document sits where. Nodes use the same versioning as a
+/// single-document JSON backup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceBackup {
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub exported_at: String,
+    pub documents: Vec<WorkspaceDocument>,
+    #[serde(default)]
+    pub folders: Vec<Folder>,
+    /// Document id -> folder id (documents not listed are at the root)
+    #[serde(default)]
+    pub document_folders: HashMap<String, String>,
+    /// Folder id (or "__root__") -> document ids in sidebar order
+    #[serde(default)]
+    pub document_order: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceDocument {
+    /// Id the document had in the exported workspace
+    pub id: String,
+    pub title: String,
+    pub nodes: Vec<Node>,
+}
+
+/// Generate a workspace backup from documents (id, title, nodes) and the
+/// folder state. Favorites and bookmarks aren't included.
+pub fn generate_workspace_backup(documents: Vec<WorkspaceDocument>, folders: &FolderState) -> Result<String, String> {
+    let ids: std::collections::HashSet<&str> = documents.iter().map(|d| d.id.as_str()).collect();
+    let backup = WorkspaceBackup {
+        version: BACKUP_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        folders: folders.folders.clone(),
+        document_folders: folders
+            .document_folders
+            .iter()
+            .filter(|(doc_id, _)| ids.contains(doc_id.as_str()))
+            .map(|(doc_id, folder_id)| (doc_id.clone(), folder_id.clone()))
+            .collect(),
+        document_order: folders
+            .document_order
+            .iter()
+            .map(|(key, order)| (key.clone(), order.iter().filter(|id| ids.contains(id.as_str())).cloned().collect()))
+            .collect(),
+        documents,
+    };
+    serde_json::to_string_pretty(&backup).map_err(|e| format!("JSON serialization error: {}", e))
+}
+
+/// Parse a workspace backup, upgrading each document's nodes from the
+/// backup's version
+pub fn parse_workspace_backup(content: &str) -> Result<WorkspaceBackup, String> {
+    let mut backup: Value = serde_json::from_str(content).map_err(|e| format!("JSON parse error: {}", e))?;
+    let version = backup.get("version").cloned().unwrap_or(Value::from(0));
+    let documents = match backup.get_mut("documents").map(Value::take) {
+        Some(Value::Array(documents)) => documents,
+        _ => return Err("Not a workspace backup (it has no documents)".to_string()),
+    };
+
+    let mut parsed = Vec::with_capacity(documents.len());
+    for (i, mut document) in documents.into_iter().enumerate() {
+        let nodes = document.get_mut("nodes").map(Value::take).unwrap_or(Value::Array(Vec::new()));
+        let nodes = nodes_from_backup(serde_json::json!({ "version": version, "nodes": nodes }))
+            .map_err(|e| format!("Document {} in backup: {}", i + 1, e))?;
+        let text = |key: &str| document.get(key).and_then(Value::as_str).map(String::from);
+        parsed.push(WorkspaceDocument {
+            id: text("id").unwrap_or_else(|| format!("document-{}", i + 1)),
+            title: text("title").unwrap_or_else(|| "Untitled".to_string()),
+            nodes,
+        });
+    }
+
+    backup["documents"] = Value::Array(Vec::new());
+    let mut workspace: WorkspaceBackup =
+        serde_json::from_value(backup).map_err(|e| format!("Invalid workspace backup: {}", e))?;
+    workspace.documents = parsed;
+    Ok(workspace)
+}
+
+impl WorkspaceBackup {
+    /// The documents in the order to restore them: folder by folder in
+    /// folder order, each in its sidebar order, then the root documents.
+    /// Each comes with the backup's id of its folder, if it's in one that
+    /// the backup has.
+    pub fn placements(&self) -> Vec<(&WorkspaceDocument, Option<&str>)> {
+        let mut folders: Vec<&Folder> = self.folders.iter().collect();
+        folders.sort_by_key(|f| f.position);
+        let folder_of = |doc: &WorkspaceDocument| {
+            self.document_folders
+                .get(&doc.id)
+                .map(String::as_str)
+                .filter(|id| self.folders.iter().any(|f| f.id == *id))
+        };
+        let rank = |doc: &WorkspaceDocument, key: &str| {
+            self.document_order
+                .get(key)
+                .and_then(|order| order.iter().position(|id| *id == doc.id))
+                .unwrap_or(usize::MAX)
+        };
+
+        let mut placements: Vec<(usize, usize, usize, &WorkspaceDocument, Option<&str>)> = self
+            .documents
+            .iter()
+            .enumerate()
+            .map(|(i, doc)| {
+                let folder = folder_of(doc);
+                let folder_rank = folder
+                    .and_then(|id| folders.iter().position(|f| f.id == id))
+                    .unwrap_or(folders.len());
+                (folder_rank, rank(doc, folder.unwrap_or("__root__")), i, doc, folder)
+            })
+            .collect();
+        placements.sort_by_key(|(folder_rank, rank, i, _, _)| (*folder_rank, *rank, *i));
+        placements.into_iter().map(|(_, _, _, doc, folder)| (doc, folder)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(id: &str, name: &str, position: i32) -> Folder {
+        Folder {
+            id: id.to_string(),
+            name: name.to_string(),
+            position,
+            collapsed: false,
+            color: None,
+            icon: None,
+        }
+    }
+
+    fn document(id: &str) -> WorkspaceDocument {
+        WorkspaceDocument {
+            id: id.to_string(),
+            title: id.to_string(),
+            nodes: vec![Node::new(id.to_string())],
+        }
+    }
+
+    #[test]
+    fn test_workspace_backup_roundtrip() {
+        let mut folders = FolderState::new();
+        folders.folders = vec![folder("f-work", "Work", 1), folder("f-home", "Home", 0)];
+        for (doc, f) in [("plan", "f-work"), ("notes", "f-work"), ("garden", "f-home")] {
+            folders.document_folders.insert(doc.to_string(), f.to_string());
+        }
+        folders
+            .document_order
+            .insert("f-work".to_string(), vec!["notes".to_string(), "gone".to_string(), "plan".to_string()]);
+        folders.document_folders.insert("gone".to_string(), "f-work".to_string());
+
+        let documents = ["inbox", "plan", "notes", "garden"].into_iter().map(document).collect();
+        let json = generate_workspace_backup(documents, &folders).unwrap();
+        let backup = parse_workspace_backup(&json).unwrap();
+
+        // Documents that weren't exported aren't referenced
+        assert!(!backup.document_folders.contains_key("gone"));
+        assert_eq!(backup.document_order["f-work"], vec!["notes", "plan"]);
+        assert_eq!(backup.documents[1].nodes[0].content, "plan");
+
+        let placements: Vec<(&str, Option<&str>)> =
+            backup.placements().into_iter().map(|(doc, folder)| (doc.id.as_str(), folder)).collect();
+        assert_eq!(
+            placements,
+            vec![
+                ("garden", Some("f-home")),
+                ("notes", Some("f-work")),
+                ("plan", Some("f-work")),
+                ("inbox", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_workspace_backup_upgrades_nodes() {
+        let json = r#"{"documents": [{"id": "a", "title": "Old", "nodes": [{"content": "Task", "checked": true}]}]}"#;
+        let backup = parse_workspace_backup(json).unwrap();
+        assert!(backup.documents[0].nodes[0].is_checked);
+        assert!(backup.folders.is_empty());
+
+        assert!(parse_workspace_backup(r#"{"version": 1, "nodes": []}"#).is_err());
+    }
+}
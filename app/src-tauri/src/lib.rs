@@ -1,10 +1,30 @@
+mod agenda;
+mod collab;
 mod commands;
-mod data;
-mod import_export;
-mod search;
+pub mod data;
+pub mod import_export;
+mod link_metadata;
+mod logging;
+mod mount;
+mod mqtt;
+mod perf;
+mod pomodoro;
+mod reminders;
+mod resume;
+pub mod search;
+// Share intake only runs on mobile
+#[cfg_attr(not(mobile), allow(dead_code))]
+mod share;
+mod spellcheck;
+mod transcribe;
 mod watcher;
 
+use collab::CollabState;
 use commands::AppState;
+use mount::MountState;
+use transcribe::TranscriptionQueue;
+use mqtt::MqttState;
+use pomodoro::PomodoroState;
 use tauri::Manager;
 use watcher::WatcherState;
 
@@ -12,22 +32,26 @@ use watcher::WatcherState;
 pub fn run() {
     // Initialize data directory from saved config before anything else
     data::init_data_dir_from_config();
+    data::init_fsync_policy_from_config();
 
-    tauri::Builder::default()
+    let builder = tauri::Builder::default()
+        .plugin(logging::plugin())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_window_state::Builder::new().build())
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_notification::init());
+    #[cfg(mobile)]
+    let builder = builder.plugin(tauri_plugin_deep_link::init());
+
+    builder
         .manage(AppState::new())
         .manage(WatcherState::new())
+        .manage(CollabState::new())
+        .manage(PomodoroState::new())
+        .manage(MqttState::new())
+        .manage(MountState::new())
+        .manage(TranscriptionQueue::new())
         .setup(|app| {
-            if cfg!(debug_assertions) {
-                app.handle().plugin(
-                    tauri_plugin_log::Builder::default()
-                        .level(log::LevelFilter::Info)
-                        .build(),
-                )?;
-            }
-
             // Start the documents watcher
             let app_handle = app.handle().clone();
             match watcher::start_watcher(app_handle) {
@@ -41,6 +65,18 @@ pub fn run() {
                 }
             }
 
+            data::set_rename_listener(Some(commands::rename_listener(app.handle().clone())));
+            reminders::start_scheduler(app.handle().clone());
+            agenda::start_scheduler(app.handle().clone());
+            app.state::<TranscriptionQueue>()
+                .start(commands::transcription_handler(app.handle().clone()));
+            if let Some(config) = data::load_config().mqtt {
+                app.state::<MqttState>().restart(app.handle().clone(), Some(config));
+            }
+
+            #[cfg(mobile)]
+            share::start_intake(app);
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -49,31 +85,95 @@ pub fn run() {
             commands::create_node,
             commands::create_node_with_id,
             commands::update_node,
+            commands::bulk_recolor,
+            commands::sanitize_content,
+            commands::render_note,
             commands::move_node,
             commands::delete_node,
+            commands::add_comment,
+            commands::list_comments,
+            commands::resolve_comment,
+            commands::encrypt_node,
+            commands::unlock_node,
+            commands::update_encrypted_node,
+            commands::decrypt_node,
+            commands::sort_children,
+            commands::set_checked_recursive,
+            commands::get_task_rollup,
+            commands::get_task_rollups,
             commands::compact_document,
+            commands::normalize_positions,
             commands::check_for_changes,
             commands::reload_if_changed,
+            commands::get_divergence,
             commands::search,
+            commands::search_grouped,
+            commands::maintain_search_cache,
+            commands::get_search_cache_mode,
+            commands::set_search_cache_mode,
+            commands::rekey_search_cache,
+            commands::get_index_status,
+            commands::suggest,
+            commands::record_search,
+            commands::locate_node,
+            commands::get_node,
+            commands::get_subtree,
+            commands::load_subtree_as_root,
+            commands::query_nodes,
             commands::list_documents,
+            commands::rename_document,
             commands::get_backlinks,
+            commands::resolve_wiki_link,
+            commands::get_unlinked_mentions,
+            commands::quick_switch_candidates,
+            commands::create_from_link,
+            commands::get_link_folder_setting,
+            commands::set_link_folder_setting,
             commands::get_next_occurrence,
+            commands::complete_recurring,
+            commands::get_overdue_rules,
+            commands::set_overdue_rules,
+            commands::run_overdue_rules,
+            commands::create_share,
+            commands::revoke_share,
+            commands::list_shares,
+            commands::list_snippets,
+            commands::set_snippets,
+            commands::expand_snippet,
+            commands::check_text,
+            commands::add_to_dictionary,
+            commands::get_personal_dictionary,
+            commands::fetch_link_metadata,
             commands::generate_ical_feed,
             commands::get_inbox,
             commands::get_inbox_count,
             commands::clear_inbox_items,
             commands::import_opml,
+            commands::import_markdown,
             commands::import_opml_as_document,
             commands::import_dynalist_backup,
+            commands::import_opml_batch,
+            commands::import_enex,
+            commands::import_text_folder,
             commands::import_latest_dynalist_backup,
+            commands::import_dynalist_api,
             commands::export_opml,
             commands::export_markdown,
+            commands::export_html,
+            commands::export_latex,
             commands::export_selection_markdown,
             commands::export_json,
             commands::save_to_file_with_dialog,
             commands::import_json,
+            commands::export_workspace,
+            commands::import_workspace,
             commands::get_data_directory,
             commands::set_data_directory,
+            commands::migrate_data_directory,
+            commands::get_fsync_policy,
+            commands::set_fsync_policy,
+            commands::get_mqtt_config,
+            commands::set_mqtt_config,
             commands::pick_directory,
             // Folder management
             commands::get_folders,
@@ -82,16 +182,84 @@ pub fn run() {
             commands::delete_folder,
             commands::move_document_to_folder,
             commands::reorder_folders,
+            commands::export_folder,
+            commands::get_folder_summaries,
+            commands::toggle_favorite,
+            commands::list_favorites,
+            commands::bookmark_node,
+            commands::list_bookmarks,
             // Inbox configuration
             commands::get_inbox_setting,
             commands::set_inbox_setting,
             commands::clear_inbox_setting,
             commands::import_inbox_items,
+            commands::capture_audio,
+            commands::transcribe_node_audio,
+            commands::get_transcription_config,
+            commands::set_transcription_config,
             // Watcher commands
             commands::start_documents_watcher,
             commands::stop_documents_watcher,
             commands::is_documents_watcher_running,
+            commands::watcher_status,
+            commands::start_collab_server,
+            commands::connect_collab,
+            commands::stop_collab,
+            commands::get_collab_status,
+            commands::update_presence,
+            commands::get_presence,
+            commands::mount_documents,
+            commands::unmount_documents,
+            commands::get_mount_point,
+            commands::health_check,
+            commands::repair_document,
+            commands::get_recent_logs,
+            commands::get_perf_stats,
+            commands::generate_test_document,
+            // Snapshots
+            commands::create_snapshot,
+            commands::list_snapshots,
+            commands::diff_snapshot,
+            // Operation log
+            commands::get_op_log,
+            // Templates
+            commands::save_template,
+            commands::list_templates,
+            commands::delete_template,
+            commands::instantiate_template,
+            commands::save_document_as_template,
+            commands::create_document_from_template,
+            // Journal
+            commands::get_journal_setting,
+            commands::set_journal_setting,
+            commands::open_daily_note,
+            // Statistics
+            commands::get_statistics,
+            commands::get_completion_history,
+            commands::generate_review,
+            commands::export_completed,
+            commands::start_timer,
+            commands::stop_timer,
+            commands::get_running_timer,
+            commands::get_time_report,
+            commands::start_pomodoro,
+            commands::stop_pomodoro,
+            commands::get_pomodoro_status,
+            commands::get_pomodoro_stats,
+            // Boards
+            commands::get_board,
+            commands::move_card,
+            // Search and replace
+            commands::replace_all,
+            // Locked documents
+            commands::lock_document,
+            commands::unlock_document,
+            commands::get_document_settings,
+            commands::set_document_settings,
+            // Recent documents
+            commands::get_recent_documents,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(resume::run_event_handler());
 }
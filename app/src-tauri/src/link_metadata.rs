@@ -0,0 +1,253 @@
+//! Titles and favicons of web pages, so a bare URL pasted into a node can be
+//! turned into a titled link. Pages are fetched here rather than in the
+//! webview (no CORS, no network code in the frontend). Only the start of a
+//! page is read, requests time out, and results are cached for the session.
+
+use regex::Regex;
+use reqwest::Url;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::data::decode_entities;
+
+/// How long a page may take to answer
+const FETCH_TIMEOUT: Duration = Duration::from_secs(8);
+/// How much of a page is read looking for its title and icons
+const MAX_HEAD_BYTES: usize = 256 * 1024;
+/// How long fetched metadata is reused
+const CACHE_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+/// Cached pages kept at most (the oldest go first)
+const MAX_CACHED: usize = 500;
+
+/// What a link is turned into
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LinkMetadata {
+    /// The URL the page was finally served from (after redirects)
+    pub url: String,
+    pub title: Option<String>,
+    /// Absolute URL of the page's icon, else of /favicon.ico
+    pub favicon: Option<String>,
+    /// `[title](url)`, or the bare URL when the page has no title
+    pub markdown: String,
+    /// `<a href="url">title</a>` for node content
+    pub html: String,
+}
+
+static CACHE: Mutex<Option<HashMap<String, (Instant, LinkMetadata)>>> = Mutex::new(None);
+
+fn cached(url: &str) -> Option<LinkMetadata> {
+    let cache = CACHE.lock().unwrap();
+    let (at, metadata) = cache.as_ref()?.get(url)?;
+    (at.elapsed() < CACHE_TTL).then(|| metadata.clone())
+}
+
+fn remember(url: &str, metadata: &LinkMetadata) {
+    let mut cache = CACHE.lock().unwrap();
+    let cache = cache.get_or_insert_with(HashMap::new);
+    cache.retain(|_, (at, _)| at.elapsed() < CACHE_TTL);
+    if cache.len() >= MAX_CACHED {
+        if let Some(oldest) = cache.iter().min_by_key(|(_, (at, _))| *at).map(|(url, _)| url.clone()) {
+            cache.remove(&oldest);
+        }
+    }
+    cache.insert(url.to_string(), (Instant::now(), metadata.clone()));
+}
+
+/// Value of an attribute in a tag's source (`<link rel="icon" ...>`)
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    static ATTRIBUTE: OnceLock<Regex> = OnceLock::new();
+    let re = ATTRIBUTE.get_or_init(|| {
+        Regex::new(r#"([A-Za-z_:][-A-Za-z0-9_:.]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap()
+    });
+    re.captures_iter(tag)
+        .find(|c| c[1].eq_ignore_ascii_case(name))
+        .and_then(|c| c.get(2).or(c.get(3)).or(c.get(4)))
+        .map(|m| decode_entities(m.as_str()))
+}
+
+fn clean_title(title: &str) -> Option<String> {
+    let title = decode_entities(title).split_whitespace().collect::<Vec<_>>().join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+/// Title and favicon of an HTML page served from `base`. The title comes
+/// from `<title>`, else from an `og:title` meta tag.
+pub fn parse_page(html: &str, base: &Url) -> (Option<String>, Option<String>) {
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let title_re = TITLE.get_or_init(|| Regex::new(r"(?is)<title\b[^>]*>(.*?)</title\s*>").unwrap());
+    let tag_re = TAG.get_or_init(|| Regex::new(r"(?is)<(meta|link)\b[^>]*>").unwrap());
+
+    let tags: Vec<(String, &str)> = tag_re
+        .captures_iter(html)
+        .map(|c| (c[1].to_lowercase(), c.get(0).unwrap().as_str()))
+        .collect();
+
+    let title = title_re.captures(html).and_then(|c| clean_title(&c[1])).or_else(|| {
+        tags.iter()
+            .filter(|(name, _)| name == "meta")
+            .find(|(_, tag)| attribute(tag, "property").or_else(|| attribute(tag, "name")).as_deref() == Some("og:title"))
+            .and_then(|(_, tag)| attribute(tag, "content"))
+            .and_then(|content| clean_title(&content))
+    });
+
+    // Plain "icon" links win over apple-touch-icon and the like
+    let icons: Vec<(bool, String)> = tags
+        .iter()
+        .filter(|(name, _)| name == "link")
+        .filter_map(|(_, tag)| {
+            let rel = attribute(tag, "rel")?.to_lowercase();
+            let rel: Vec<&str> = rel.split_whitespace().collect();
+            if !rel.iter().any(|r| r.contains("icon")) {
+                return None;
+            }
+            Some((rel.contains(&"icon"), attribute(tag, "href")?))
+        })
+        .collect();
+    let favicon = icons
+        .iter()
+        .find(|(plain, _)| *plain)
+        .or(icons.first())
+        .and_then(|(_, href)| base.join(href.trim()).ok())
+        .or_else(|| base.join("/favicon.ico").ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https" | "data"))
+        .map(String::from);
+
+    (title, favicon)
+}
+
+/// The Markdown and HTML forms of a link to `url`
+pub fn link_metadata(url: &str, title: Option<String>, favicon: Option<String>) -> LinkMetadata {
+    let (markdown, html) = match title {
+        Some(ref title) => {
+            let label = title.replace('\\', "\\\\").replace('[', "\\[").replace(']', "\\]");
+            let target = url.replace('(', "%28").replace(')', "%29").replace(' ', "%20");
+            (format!("[{}]({})", label, target), format!(r#"<a href="{}">{}</a>"#, escape(url), escape(title)))
+        }
+        None => (url.to_string(), format!(r#"<a href="{0}">{0}</a>"#, escape(url))),
+    };
+    LinkMetadata {
+        url: url.to_string(),
+        title,
+        favicon,
+        markdown,
+        html,
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Fetch a page's title and favicon. Pages that aren't HTML, or that can't
+/// be read, give a link without a title; only a malformed URL is an error.
+pub async fn fetch_link_metadata(url: &str) -> Result<LinkMetadata, String> {
+    let parsed = Url::parse(url.trim()).map_err(|e| format!("Invalid URL {}: {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("Not a web link: {}", url));
+    }
+    if let Some(metadata) = cached(parsed.as_str()) {
+        return Ok(metadata);
+    }
+
+    let metadata = match fetch_head(&parsed).await {
+        Ok((final_url, Some(html))) => {
+            let (title, favicon) = parse_page(&html, &final_url);
+            link_metadata(parsed.as_str(), title, favicon)
+        }
+        Ok((final_url, None)) => link_metadata(parsed.as_str(), None, final_url.join("/favicon.ico").ok().map(String::from)),
+        Err(e) => {
+            // Not cached, so a page that was down can be tried again
+            log::info!("Failed to fetch link metadata for {}: {}", parsed, e);
+            return Ok(link_metadata(parsed.as_str(), None, None));
+        }
+    };
+    remember(parsed.as_str(), &metadata);
+    Ok(metadata)
+}
+
+/// The final URL and, for HTML pages, the start of the page
+async fn fetch_head(url: &Url) -> Result<(Url, Option<String>), String> {
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .user_agent(concat!("Outline/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut response = client
+        .get(url.clone())
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?;
+
+    let final_url = response.url().clone();
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|t| t.to_str().ok())
+        .map_or(true, |t| t.contains("html"));
+    if !is_html {
+        return Ok((final_url, None));
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        body.extend_from_slice(&chunk);
+        if body.len() >= MAX_HEAD_BYTES || body.windows(7).any(|w| w.eq_ignore_ascii_case(b"</head>")) {
+            break;
+        }
+    }
+    Ok((final_url, Some(String::from_utf8_lossy(&body).into_owned())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_page() {
+        let base = Url::parse("https://example.com/blog/post").unwrap();
+        let html = r#"<html><head>
+            <TITLE>
+              Rust &amp; Outlines
+            </TITLE>
+            <link rel="apple-touch-icon" href="/touch.png">
+            <link href='icons/fav.png' rel='shortcut icon'>
+            </head><body></body></html>"#;
+        assert_eq!(
+            parse_page(html, &base),
+            (
+                Some("Rust & Outlines".to_string()),
+                Some("https://example.com/blog/icons/fav.png".to_string())
+            )
+        );
+
+        let html = r#"<meta property="og:title" content="From Open Graph"><title> </title>"#;
+        assert_eq!(
+            parse_page(html, &base),
+            (
+                Some("From Open Graph".to_string()),
+                Some("https://example.com/favicon.ico".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn test_link_metadata() {
+        let link = link_metadata("https://en.wikipedia.org/wiki/Rust_(language)", Some("[Rust] <lang>".to_string()), None);
+        assert_eq!(link.markdown, r"[\[Rust\] <lang>](https://en.wikipedia.org/wiki/Rust_%28language%29)");
+        assert_eq!(
+            link.html,
+            r#"<a href="https://en.wikipedia.org/wiki/Rust_(language)">[Rust] &lt;lang&gt;</a>"#
+        );
+
+        let bare = link_metadata("https://example.com/", None, None);
+        assert_eq!(bare.markdown, "https://example.com/");
+        assert_eq!(bare.html, r#"<a href="https://example.com/">https://example.com/</a>"#);
+    }
+}
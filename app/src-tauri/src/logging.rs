@@ -0,0 +1,95 @@
+//! Log files written to the data directory, so bug reports have something
+//! to go on. Files are rotated at startup once they pass `MAX_LOG_FILE_SIZE`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri_plugin_log::{RotationStrategy, Target, TargetKind};
+
+use crate::data::data_dir;
+
+/// Log file name, without the .log extension
+const LOG_FILE_NAME: &str = "outline";
+const MAX_LOG_FILE_SIZE: u128 = 1024 * 1024;
+/// Log files kept, including the current one
+const KEPT_LOG_FILES: usize = 5;
+
+/// Directory the log files are written to
+pub fn logs_dir() -> PathBuf {
+    data_dir().join(".cache").join("logs")
+}
+
+/// Build the log plugin: log files in every build, plus stdout in debug builds
+pub fn plugin<R: tauri::Runtime>() -> tauri::plugin::TauriPlugin<R> {
+    let mut builder = tauri_plugin_log::Builder::new()
+        .clear_targets()
+        .target(Target::new(TargetKind::Folder {
+            path: logs_dir(),
+            file_name: Some(LOG_FILE_NAME.to_string()),
+        }))
+        .max_file_size(MAX_LOG_FILE_SIZE)
+        .rotation_strategy(RotationStrategy::KeepSome(KEPT_LOG_FILES))
+        .level(log::LevelFilter::Info);
+    if cfg!(debug_assertions) {
+        builder = builder.target(Target::new(TargetKind::Stdout));
+    }
+    builder.build()
+}
+
+/// The last `max_lines` lines logged, oldest first, reading back through
+/// rotated files as needed
+pub fn read_recent_logs(dir: &Path, max_lines: usize) -> Result<Vec<String>, String> {
+    // Rotated files are named outline_<timestamp>.log, so sorting by name
+    // puts them in order; the current outline.log is the newest
+    let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with(&format!("{}_", LOG_FILE_NAME)) && n.ends_with(".log"))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort();
+    files.push(dir.join(format!("{}.log", LOG_FILE_NAME)));
+
+    let mut lines = Vec::new();
+    for path in files.iter().rev() {
+        if lines.len() >= max_lines {
+            break;
+        }
+        let Ok(contents) = fs::read_to_string(path) else {
+            continue;
+        };
+        let file_lines: Vec<&str> = contents.lines().collect();
+        let take = (max_lines - lines.len()).min(file_lines.len());
+        let mut older: Vec<String> = file_lines[file_lines.len() - take..]
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        older.append(&mut lines);
+        lines = older;
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_recent_logs_spans_rotated_files() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("outline_2024-01-01_10-00-00.log"), "a\nb\n").unwrap();
+        fs::write(tmp.path().join("outline_2024-01-02_10-00-00.log"), "c\nd\n").unwrap();
+        fs::write(tmp.path().join("outline.log"), "e\nf\n").unwrap();
+        fs::write(tmp.path().join("other.log"), "x\n").unwrap();
+
+        assert_eq!(read_recent_logs(tmp.path(), 3).unwrap(), vec!["d", "e", "f"]);
+        assert_eq!(read_recent_logs(tmp.path(), 10).unwrap(), vec!["a", "b", "c", "d", "e", "f"]);
+        assert!(read_recent_logs(&tmp.path().join("missing"), 10).unwrap().is_empty());
+    }
+}
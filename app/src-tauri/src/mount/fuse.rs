@@ -0,0 +1,466 @@
+//! The FUSE filesystem behind the mount. Inode numbers are handed out as
+//! entries are first seen and stay fixed while mounted; the directory tree
+//! is rebuilt from the source at most every few seconds. Each open file
+//! handle gets its own buffer of the document, written back to the source
+//! when that handle is flushed or released, so closing one reader never
+//! applies another writer's half-written file.
+
+use fuser::{
+    BackgroundSession, FileAttr, FileType, Filesystem, KernelConfig, MountOption, ReplyAttr, ReplyData,
+    ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, ReplyWrite, Request, TimeOrNow,
+};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::{mount_entries, DocumentSource, EntryKey, MountEntry};
+
+/// How long the kernel may cache attributes and names
+const TTL: Duration = Duration::from_secs(1);
+/// How long a listing of the documents is reused
+const TREE_TTL: Duration = Duration::from_secs(5);
+const ROOT_INO: u64 = 1;
+
+// errno values (the same on Linux and macOS)
+const ENOENT: i32 = 2;
+const EIO: i32 = 5;
+const EBADF: i32 = 9;
+const ENOTDIR: i32 = 20;
+const EISDIR: i32 = 21;
+
+struct Buffer {
+    ino: u64,
+    data: Vec<u8>,
+    dirty: bool,
+}
+
+struct OutlineFs<S: DocumentSource> {
+    source: S,
+    inodes: HashMap<EntryKey, u64>,
+    entries: HashMap<u64, MountEntry>,
+    next_ino: u64,
+    refreshed: Option<Instant>,
+    /// Open buffers by file handle
+    buffers: HashMap<u64, Buffer>,
+    next_fh: u64,
+    uid: u32,
+    gid: u32,
+}
+
+impl<S: DocumentSource> OutlineFs<S> {
+    fn refresh(&mut self) {
+        if self.refreshed.is_some_and(|at| at.elapsed() < TREE_TTL) {
+            return;
+        }
+        let listing = self
+            .source
+            .folders()
+            .and_then(|folders| Ok(mount_entries(&folders, &self.source.documents()?)));
+        match listing {
+            Ok(listing) => {
+                self.entries.clear();
+                for entry in listing {
+                    let ino = match self.inodes.get(&entry.key) {
+                        Some(ino) => *ino,
+                        None => {
+                            self.next_ino += 1;
+                            self.inodes.insert(entry.key.clone(), self.next_ino);
+                            self.next_ino
+                        }
+                    };
+                    self.entries.insert(ino, entry);
+                }
+                self.refreshed = Some(Instant::now());
+            }
+            Err(e) => log::warn!("Failed to list documents for the mount: {}", e),
+        }
+    }
+
+    fn parent_ino(&self, entry: &MountEntry) -> u64 {
+        entry
+            .parent
+            .as_ref()
+            .and_then(|key| self.inodes.get(key).copied())
+            .unwrap_or(ROOT_INO)
+    }
+
+    fn children(&self, ino: u64) -> Vec<(u64, &MountEntry)> {
+        let mut children: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| self.parent_ino(entry) == ino)
+            .map(|(ino, entry)| (*ino, entry))
+            .collect();
+        children.sort_by_key(|(ino, _)| *ino);
+        children
+    }
+
+    fn document(&self, ino: u64) -> Result<uuid::Uuid, i32> {
+        match self.entries.get(&ino).map(|e| &e.key) {
+            Some(EntryKey::Document(id)) => Ok(*id),
+            Some(EntryKey::Folder(_)) => Err(EISDIR),
+            None if ino == ROOT_INO => Err(EISDIR),
+            None => Err(ENOENT),
+        }
+    }
+
+    /// A fresh buffer of a document's Markdown
+    fn load(&self, ino: u64) -> Result<Buffer, i32> {
+        let id = self.document(ino)?;
+        let markdown = self.source.read(id).map_err(|e| {
+            log::warn!("Failed to read document {} for the mount: {}", id, e);
+            EIO
+        })?;
+        Ok(Buffer { ino, data: markdown.into_bytes(), dirty: false })
+    }
+
+    /// Open a document, returning the new file handle
+    fn open_handle(&mut self, ino: u64) -> Result<u64, i32> {
+        let buffer = self.load(ino)?;
+        self.next_fh += 1;
+        self.buffers.insert(self.next_fh, buffer);
+        Ok(self.next_fh)
+    }
+
+    /// The buffer behind a file handle
+    fn buffer(&mut self, ino: u64, fh: u64) -> Result<&mut Buffer, i32> {
+        self.buffers.get_mut(&fh).filter(|b| b.ino == ino).ok_or(EBADF)
+    }
+
+    /// Apply edited Markdown to a document
+    fn apply(&self, ino: u64, data: &[u8]) -> Result<(), i32> {
+        let Ok(id) = self.document(ino) else {
+            return Ok(());
+        };
+        let markdown = String::from_utf8_lossy(data).to_string();
+        self.source.write(id, &markdown).map_err(|e| {
+            log::warn!("Failed to apply edit of document {} from the mount: {}", id, e);
+            EIO
+        })
+    }
+
+    /// Apply a file handle's buffer if it was written to
+    fn write_back(&mut self, fh: u64) -> Result<(), i32> {
+        let Some(buffer) = self.buffers.get(&fh).filter(|b| b.dirty) else {
+            return Ok(());
+        };
+        self.apply(buffer.ino, &buffer.data)?;
+        if let Some(buffer) = self.buffers.get_mut(&fh) {
+            buffer.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Write back a file handle's buffer and drop it
+    fn release_handle(&mut self, fh: u64) -> Result<(), i32> {
+        let result = self.write_back(fh);
+        self.buffers.remove(&fh);
+        result
+    }
+
+    /// Truncate a document. Through a file handle only that handle's buffer
+    /// changes; without one the document is rewritten right away.
+    fn truncate(&mut self, ino: u64, fh: Option<u64>, size: u64) -> Result<(), i32> {
+        match fh {
+            Some(fh) => {
+                let buffer = self.buffer(ino, fh)?;
+                buffer.data.resize(size as usize, 0);
+                buffer.dirty = true;
+                Ok(())
+            }
+            None => {
+                let mut buffer = self.load(ino)?;
+                buffer.data.resize(size as usize, 0);
+                self.apply(ino, &buffer.data)
+            }
+        }
+    }
+
+    /// Length of a document's Markdown, from a buffer with unsaved writes if
+    /// there is one
+    fn size(&self, ino: u64, id: uuid::Uuid) -> Result<u64, i32> {
+        if let Some(buffer) = self.buffers.values().find(|b| b.ino == ino && b.dirty) {
+            return Ok(buffer.data.len() as u64);
+        }
+        match self.source.read(id) {
+            Ok(markdown) => Ok(markdown.len() as u64),
+            Err(e) => {
+                log::warn!("Failed to read document {} for the mount: {}", id, e);
+                Err(EIO)
+            }
+        }
+    }
+
+    fn attr(&self, ino: u64) -> Result<FileAttr, i32> {
+        let (kind, perm, size) = if ino == ROOT_INO {
+            (FileType::Directory, 0o755, 0)
+        } else {
+            match self.entries.get(&ino).map(|e| &e.key) {
+                Some(EntryKey::Folder(_)) => (FileType::Directory, 0o755, 0),
+                Some(EntryKey::Document(id)) => (FileType::RegularFile, 0o644, self.size(ino, *id)?),
+                None => return Err(ENOENT),
+            }
+        };
+        let now = SystemTime::now();
+        Ok(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: now,
+            mtime: now,
+            ctime: now,
+            crtime: now,
+            kind,
+            perm,
+            nlink: if kind == FileType::Directory { 2 } else { 1 },
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        })
+    }
+}
+
+impl<S: DocumentSource> Filesystem for OutlineFs<S> {
+    fn init(&mut self, req: &Request<'_>, _config: &mut KernelConfig) -> Result<(), i32> {
+        self.uid = req.uid();
+        self.gid = req.gid();
+        Ok(())
+    }
+
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        self.refresh();
+        let found = self
+            .children(parent)
+            .into_iter()
+            .find(|(_, entry)| OsStr::new(&entry.name) == name)
+            .map(|(ino, _)| ino);
+        match found.map(|ino| self.attr(ino)) {
+            Some(Ok(attr)) => reply.entry(&TTL, &attr, 0),
+            Some(Err(e)) => reply.error(e),
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        self.refresh();
+        match self.attr(ino) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn setattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        size: Option<u64>,
+        _atime: Option<TimeOrNow>,
+        _mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        // Truncation is the only change that means anything here
+        if let Some(size) = size {
+            if let Err(e) = self.truncate(ino, fh, size) {
+                return reply.error(e);
+            }
+        }
+        match self.attr(ino) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn readdir(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, mut reply: ReplyDirectory) {
+        self.refresh();
+        if ino != ROOT_INO && !matches!(self.entries.get(&ino).map(|e| &e.key), Some(EntryKey::Folder(_))) {
+            return reply.error(ENOTDIR);
+        }
+        let parent = self.entries.get(&ino).map_or(ROOT_INO, |entry| self.parent_ino(entry));
+        let mut listing = vec![(ino, FileType::Directory, ".".to_string()), (parent, FileType::Directory, "..".to_string())];
+        listing.extend(self.children(ino).into_iter().map(|(child, entry)| {
+            let kind = match entry.key {
+                EntryKey::Folder(_) => FileType::Directory,
+                EntryKey::Document(_) => FileType::RegularFile,
+            };
+            (child, kind, entry.name.clone())
+        }));
+        for (i, (child, kind, name)) in listing.into_iter().enumerate().skip(offset as usize) {
+            if reply.add(child, (i + 1) as i64, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, _flags: i32, reply: ReplyOpen) {
+        match self.open_handle(ino) {
+            Ok(fh) => reply.opened(fh, 0),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        match self.buffer(ino, fh) {
+            Ok(buffer) => {
+                let start = (offset.max(0) as usize).min(buffer.data.len());
+                let end = (start + size as usize).min(buffer.data.len());
+                reply.data(&buffer.data[start..end]);
+            }
+            Err(e) => reply.error(e),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        match self.buffer(ino, fh) {
+            Ok(buffer) => {
+                let start = offset.max(0) as usize;
+                if buffer.data.len() < start + data.len() {
+                    buffer.data.resize(start + data.len(), 0);
+                }
+                buffer.data[start..start + data.len()].copy_from_slice(data);
+                buffer.dirty = true;
+                reply.written(data.len() as u32);
+            }
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn flush(&mut self, _req: &Request<'_>, _ino: u64, fh: u64, _lock_owner: u64, reply: ReplyEmpty) {
+        match self.write_back(fh) {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        // The next open re-reads, so edits made in the app show up
+        match self.release_handle(fh) {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+}
+
+/// Mount the documents at `path` until the returned session is dropped
+pub fn mount(path: &str, source: impl DocumentSource) -> Result<BackgroundSession, String> {
+    let fs = OutlineFs {
+        source,
+        inodes: HashMap::new(),
+        entries: HashMap::new(),
+        next_ino: ROOT_INO,
+        refreshed: None,
+        buffers: HashMap::new(),
+        next_fh: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let options = [MountOption::FSName("outline".to_string()), MountOption::NoAtime];
+    fuser::spawn_mount2(fs, path, &options).map_err(|e| format!("Mount {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::FolderState;
+    use std::sync::{Arc, Mutex};
+    use uuid::Uuid;
+
+    struct FakeSource {
+        id: Uuid,
+        markdown: Arc<Mutex<String>>,
+    }
+
+    impl DocumentSource for FakeSource {
+        fn documents(&self) -> Result<Vec<(Uuid, String)>, String> {
+            Ok(vec![(self.id, "Notes".to_string())])
+        }
+        fn folders(&self) -> Result<FolderState, String> {
+            Ok(FolderState::default())
+        }
+        fn read(&self, _id: Uuid) -> Result<String, String> {
+            Ok(self.markdown.lock().unwrap().clone())
+        }
+        fn write(&self, _id: Uuid, markdown: &str) -> Result<(), String> {
+            *self.markdown.lock().unwrap() = markdown.to_string();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_release_only_writes_back_own_handle() {
+        let markdown = Arc::new(Mutex::new("- one\n- two\n".to_string()));
+        let mut fs = OutlineFs {
+            source: FakeSource { id: Uuid::now_v7(), markdown: markdown.clone() },
+            inodes: HashMap::new(),
+            entries: HashMap::new(),
+            next_ino: ROOT_INO,
+            refreshed: None,
+            buffers: HashMap::new(),
+            next_fh: 0,
+            uid: 0,
+            gid: 0,
+        };
+        fs.refresh();
+        let ino = *fs.entries.keys().next().unwrap();
+
+        // A writer truncates and starts rewriting the file...
+        let writer = fs.open_handle(ino).unwrap();
+        fs.truncate(ino, Some(writer), 0).unwrap();
+        fs.buffer(ino, writer).unwrap().data.extend_from_slice(b"- one");
+
+        // ...while a reader opens and closes it
+        let reader = fs.open_handle(ino).unwrap();
+        assert_eq!(fs.buffer(ino, reader).unwrap().data, b"- one\n- two\n");
+        fs.release_handle(reader).unwrap();
+        assert_eq!(*markdown.lock().unwrap(), "- one\n- two\n");
+
+        fs.buffer(ino, writer).unwrap().data.extend_from_slice(b"\n- three\n");
+        fs.release_handle(writer).unwrap();
+        assert_eq!(*markdown.lock().unwrap(), "- one\n- three\n");
+        assert!(fs.buffers.is_empty());
+    }
+}
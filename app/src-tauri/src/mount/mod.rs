@@ -0,0 +1,183 @@
+//! FUSE view of the documents (Linux and macOS, in builds with the `fuse`
+//! feature): folders become directories and each document a Markdown file,
+//! so grep, fzf or git can work on the outlines directly. Writing a file back
+//! turns the edit into ops on the document (see markdown_edit.rs). Files are
+//! edited in place; new files, renames and deletions are not supported.
+
+#[cfg(all(feature = "fuse", not(any(target_os = "linux", target_os = "macos"))))]
+compile_error!("The fuse feature needs Linux or macOS");
+
+#[cfg(feature = "fuse")]
+mod fuse;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::data::FolderState;
+
+/// What a path in the mount stands for
+#[cfg_attr(not(feature = "fuse"), allow(dead_code))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EntryKey {
+    Folder(String),
+    Document(Uuid),
+}
+
+/// A directory or file below the mount point
+#[cfg_attr(not(feature = "fuse"), allow(dead_code))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MountEntry {
+    pub key: EntryKey,
+    /// Containing folder; None for the mount root
+    pub parent: Option<EntryKey>,
+    pub name: String,
+}
+
+/// Where the mount gets documents from and sends edits to
+#[cfg_attr(not(feature = "fuse"), allow(dead_code))]
+pub trait DocumentSource: Send + 'static {
+    /// Every document as (id, title)
+    fn documents(&self) -> Result<Vec<(Uuid, String)>, String>;
+    fn folders(&self) -> Result<FolderState, String>;
+    /// A document rendered as Markdown
+    fn read(&self, id: Uuid) -> Result<String, String>;
+    /// Apply an edited Markdown rendering to a document
+    fn write(&self, id: Uuid, markdown: &str) -> Result<(), String>;
+}
+
+/// A title or folder name made safe as a file name
+fn file_name(name: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| if c == '/' || c == '\0' || c.is_control() { '-' } else { c })
+        .collect();
+    match name.as_str() {
+        "" | "." | ".." => "Untitled".to_string(),
+        _ => name,
+    }
+}
+
+/// The directories and files of the mount: a directory per folder and a
+/// `<title>.md` file per document. Names that would clash get " (2)", " (3)"…
+#[cfg_attr(not(feature = "fuse"), allow(dead_code))]
+pub fn mount_entries(folders: &FolderState, documents: &[(Uuid, String)]) -> Vec<MountEntry> {
+    let mut entries = Vec::new();
+    let mut taken: HashSet<(Option<EntryKey>, String)> = HashSet::new();
+    let mut add = |entries: &mut Vec<MountEntry>, key: EntryKey, parent: Option<EntryKey>, base: String, ext: &str| {
+        let mut name = format!("{}{}", base, ext);
+        let mut n = 2;
+        while !taken.insert((parent.clone(), name.to_lowercase())) {
+            name = format!("{} ({}){}", base, n, ext);
+            n += 1;
+        }
+        entries.push(MountEntry { key, parent, name });
+    };
+
+    let mut sorted: Vec<_> = folders.folders.iter().collect();
+    sorted.sort_by_key(|f| f.position);
+    for folder in sorted {
+        add(&mut entries, EntryKey::Folder(folder.id.clone()), None, file_name(&folder.name), "");
+    }
+
+    let folder_ids: HashSet<&str> = folders.folders.iter().map(|f| f.id.as_str()).collect();
+    let mut rank: HashMap<&str, usize> = HashMap::new();
+    for ids in folders.document_order.values() {
+        for (i, id) in ids.iter().enumerate() {
+            rank.insert(id.as_str(), i);
+        }
+    }
+    let mut documents: Vec<_> = documents.iter().collect();
+    documents.sort_by_key(|(id, title)| (rank.get(id.to_string().as_str()).copied().unwrap_or(usize::MAX), title.clone()));
+    for (id, title) in documents {
+        let parent = folders
+            .document_folders
+            .get(&id.to_string())
+            .filter(|f| folder_ids.contains(f.as_str()))
+            .map(|f| EntryKey::Folder(f.clone()));
+        add(&mut entries, EntryKey::Document(*id), parent, file_name(title), ".md");
+    }
+    entries
+}
+
+/// The mounted view, if any
+pub struct MountState {
+    #[cfg(feature = "fuse")]
+    session: Mutex<Option<(String, fuser::BackgroundSession)>>,
+    #[cfg(not(feature = "fuse"))]
+    session: Mutex<Option<(String, ())>>,
+}
+
+impl MountState {
+    pub fn new() -> Self {
+        Self {
+            session: Mutex::new(None),
+        }
+    }
+
+    /// Where the documents are mounted
+    pub fn mount_point(&self) -> Option<String> {
+        self.session.lock().unwrap().as_ref().map(|(path, _)| path.clone())
+    }
+
+    /// Mount the documents at `path` (an existing, empty directory),
+    /// replacing any earlier mount
+    #[cfg(feature = "fuse")]
+    pub fn mount(&self, path: &str, source: impl DocumentSource) -> Result<(), String> {
+        let mut session = self.session.lock().unwrap();
+        session.take();
+        *session = Some((path.to_string(), fuse::mount(path, source)?));
+        Ok(())
+    }
+
+    #[cfg(not(feature = "fuse"))]
+    pub fn mount(&self, _path: &str, _source: impl DocumentSource) -> Result<(), String> {
+        Err("This build can't mount documents (it needs the fuse feature)".to_string())
+    }
+
+    /// Unmount the documents (dropping the session unmounts them)
+    pub fn unmount(&self) {
+        self.session.lock().unwrap().take();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Folder;
+
+    #[test]
+    fn test_mount_entries() {
+        let folder = |id: &str, name: &str, position: i32| Folder {
+            id: id.to_string(),
+            name: name.to_string(),
+            position,
+            collapsed: false,
+            color: None,
+            icon: None,
+        };
+        let (a, b, c) = (Uuid::now_v7(), Uuid::now_v7(), Uuid::now_v7());
+        let mut folders = FolderState {
+            folders: vec![folder("f2", "Home", 1), folder("f1", "Work/Projects", 0)],
+            ..Default::default()
+        };
+        folders.document_folders.insert(c.to_string(), "f1".to_string());
+        let documents = vec![(a, "Notes".to_string()), (b, "notes".to_string()), (c, "".to_string())];
+
+        let entries = mount_entries(&folders, &documents);
+        let names: Vec<(&str, Option<&EntryKey>)> = entries.iter().map(|e| (e.name.as_str(), e.parent.as_ref())).collect();
+        let work = EntryKey::Folder("f1".to_string());
+        assert_eq!(
+            names,
+            vec![
+                ("Work-Projects", None),
+                ("Home", None),
+                ("Untitled.md", Some(&work)),
+                ("Notes.md", None),
+                ("notes (2).md", None),
+            ]
+        );
+        assert_eq!(entries[2].key, EntryKey::Document(c));
+    }
+}
@@ -0,0 +1,530 @@
+//! Optional MQTT publisher for home automation. A background thread keeps a
+//! plain-TCP MQTT 3.1.1 connection to the configured broker and publishes
+//! (QoS 0, so events while the broker is unreachable are dropped):
+//!
+//! - `<prefix>/task/checked` and `<prefix>/task/unchecked` when a checkbox
+//!   is toggled
+//! - `<prefix>/node/tagged/<tag>` when a node created this session first
+//!   gets one of the configured tags
+//! - `<prefix>/agenda/today`, retained: the unchecked items dated today,
+//!   republished whenever they change
+
+use chrono::Local;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use uuid::Uuid;
+
+use crate::commands::AppState;
+use crate::data::{device_name, set_op_listener, strip_html, MqttConfig, Operation};
+use crate::search::{NodeFilter, NodeQueryResult};
+
+const DEFAULT_PORT: u16 = 1883;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Keep-alive announced to the broker; a ping goes out after half of it idle
+const KEEP_ALIVE: Duration = Duration::from_secs(60);
+const RECONNECT_DELAY: Duration = Duration::from_secs(30);
+const AGENDA_INTERVAL: Duration = Duration::from_secs(60);
+const TICK: Duration = Duration::from_secs(5);
+/// New nodes remembered for tag announcements
+const MAX_TRACKED_NODES: usize = 1000;
+
+const PINGREQ: [u8; 2] = [0xC0, 0x00];
+const DISCONNECT: [u8; 2] = [0xE0, 0x00];
+
+/// Something worth publishing, found in a locally appended op
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    TaskChecked { document_id: Uuid, node_id: Uuid, checked: bool },
+    Tagged { document_id: Uuid, node_id: Uuid, tag: String, content: String },
+}
+
+/// Payload of task and tag events
+#[derive(Debug, Serialize)]
+struct NodeEvent<'a> {
+    document_id: String,
+    node_id: String,
+    content: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<&'a str>,
+}
+
+/// Payload of `agenda/today`
+#[derive(Debug, Serialize, PartialEq)]
+struct Agenda {
+    date: String,
+    items: Vec<AgendaItem>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct AgendaItem {
+    document_id: String,
+    node_id: String,
+    content: String,
+    priority: Option<u8>,
+}
+
+struct NewNode {
+    content: String,
+    announced: Vec<String>,
+}
+
+/// Turns ops into events, remembering nodes created this session so a tag
+/// is announced once, when a new node first gets it
+struct EventTracker {
+    /// Configured tags, lowercase without the #
+    tags: Vec<String>,
+    created: HashMap<Uuid, NewNode>,
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
+
+impl EventTracker {
+    fn new(tags: &[String]) -> Self {
+        Self {
+            tags: tags.iter().map(|t| normalize_tag(t)).filter(|t| !t.is_empty()).collect(),
+            created: HashMap::new(),
+        }
+    }
+
+    fn events(&mut self, document_id: Uuid, op: &Operation) -> Vec<Event> {
+        match op {
+            Operation::Create { id, content, .. } if !self.tags.is_empty() => {
+                if self.created.len() >= MAX_TRACKED_NODES {
+                    self.created.clear();
+                }
+                let content = strip_html(content).trim().to_string();
+                self.created.insert(*id, NewNode { content, announced: Vec::new() });
+                Vec::new()
+            }
+            Operation::Update { id, changes, .. } => {
+                let mut events = Vec::new();
+                if let Some(checked) = changes.is_checked {
+                    events.push(Event::TaskChecked { document_id, node_id: *id, checked });
+                }
+                if let Some(node) = self.created.get_mut(id) {
+                    if let Some(ref content) = changes.content {
+                        node.content = strip_html(content).trim().to_string();
+                    }
+                    for tag in changes.tags.iter().flatten().map(|t| normalize_tag(t)) {
+                        if self.tags.contains(&tag) && !node.announced.contains(&tag) {
+                            node.announced.push(tag.clone());
+                            events.push(Event::Tagged {
+                                document_id,
+                                node_id: *id,
+                                tag,
+                                content: node.content.clone(),
+                            });
+                        }
+                    }
+                }
+                events
+            }
+            Operation::Delete { id, .. } => {
+                self.created.remove(id);
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Socket address of a broker given as `host`, `host:port` or
+/// `mqtt://host:port`
+fn broker_address(broker: &str) -> Result<String, String> {
+    let broker = broker.trim().trim_end_matches('/');
+    if broker.starts_with("mqtts://") || broker.starts_with("ssl://") {
+        return Err("TLS brokers are not supported".to_string());
+    }
+    let host = broker
+        .strip_prefix("mqtt://")
+        .or_else(|| broker.strip_prefix("tcp://"))
+        .unwrap_or(broker);
+    if host.is_empty() {
+        return Err("No MQTT broker configured".to_string());
+    }
+    Ok(match host.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => host.to_string(),
+        _ => format!("{}:{}", host, DEFAULT_PORT),
+    })
+}
+
+/// Fixed header of a packet: its type byte and the remaining length
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![header];
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        bytes.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+    bytes.extend_from_slice(body);
+    bytes
+}
+
+/// Length-prefixed UTF-8 string
+fn push_string(body: &mut Vec<u8>, value: &str) {
+    body.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    body.extend_from_slice(value.as_bytes());
+}
+
+fn connect_packet(client_id: &str, username: Option<&str>, password: Option<&str>) -> Vec<u8> {
+    let mut body = Vec::new();
+    push_string(&mut body, "MQTT");
+    body.push(4); // protocol level 3.1.1
+    let mut flags = 0x02; // clean session
+    if username.is_some() {
+        flags |= 0x80;
+        if password.is_some() {
+            flags |= 0x40;
+        }
+    }
+    body.push(flags);
+    body.extend_from_slice(&(KEEP_ALIVE.as_secs() as u16).to_be_bytes());
+    push_string(&mut body, client_id);
+    if let Some(username) = username {
+        push_string(&mut body, username);
+        if let Some(password) = password {
+            push_string(&mut body, password);
+        }
+    }
+    packet(0x10, &body)
+}
+
+fn publish_packet(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    push_string(&mut body, topic);
+    body.extend_from_slice(payload);
+    packet(if retain { 0x31 } else { 0x30 }, &body)
+}
+
+/// Open a session with the broker
+fn connect(config: &MqttConfig) -> Result<TcpStream, String> {
+    let address = broker_address(&config.broker)?;
+    let socket = address
+        .to_socket_addrs()
+        .map_err(|e| format!("Resolve {}: {}", address, e))?
+        .next()
+        .ok_or_else(|| format!("Resolve {}: no address", address))?;
+    let mut stream =
+        TcpStream::connect_timeout(&socket, CONNECT_TIMEOUT).map_err(|e| format!("Connect to {}: {}", address, e))?;
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(CONNECT_TIMEOUT)).map_err(|e| e.to_string())?;
+
+    let client_id = config
+        .client_id
+        .clone()
+        .unwrap_or_else(|| format!("outline-{}", device_name()));
+    let password = config.username.as_ref().and(config.password.as_deref());
+    stream
+        .write_all(&connect_packet(&client_id, config.username.as_deref(), password))
+        .map_err(|e| format!("Send CONNECT: {}", e))?;
+
+    let mut connack = [0u8; 4];
+    stream.read_exact(&mut connack).map_err(|e| format!("Read CONNACK: {}", e))?;
+    match connack {
+        [0x20, 0x02, _, 0] => Ok(stream),
+        [0x20, 0x02, _, 4 | 5] => Err("Broker refused the credentials".to_string()),
+        [0x20, 0x02, _, code] => Err(format!("Broker refused the connection (code {})", code)),
+        _ => Err("Unexpected reply from broker".to_string()),
+    }
+}
+
+fn agenda_today(app: &AppHandle) -> Option<Agenda> {
+    let index = app.state::<AppState>().search_index.lock().unwrap().clone()?;
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    let filter = NodeFilter {
+        date_from: Some(today.clone()),
+        date_to: Some(today.clone()),
+        checked: Some(false),
+        ..Default::default()
+    };
+    match index.query_nodes(&filter) {
+        Ok(results) => Some(Agenda {
+            date: today,
+            items: results
+                .into_iter()
+                .map(|r| AgendaItem {
+                    document_id: r.document_id,
+                    node_id: r.node_id,
+                    content: strip_html(&r.content).trim().to_string(),
+                    priority: r.priority,
+                })
+                .collect(),
+        }),
+        Err(e) => {
+            log::warn!("Failed to look up today's agenda: {}", e);
+            None
+        }
+    }
+}
+
+/// The indexed node, for the content of a toggled task
+fn indexed_node(app: &AppHandle, node_id: Uuid) -> Option<NodeQueryResult> {
+    let index = app.state::<AppState>().search_index.lock().unwrap().clone()?;
+    let filter = NodeFilter {
+        node_ids: vec![node_id.to_string()],
+        limit: Some(1),
+        ..Default::default()
+    };
+    index.query_nodes(&filter).ok()?.pop()
+}
+
+enum Message {
+    Op(Uuid, Box<Operation>),
+    Stop,
+}
+
+/// The broker connection of a running publisher
+struct Connection {
+    config: MqttConfig,
+    stream: Option<TcpStream>,
+    last_write: Instant,
+    retry_at: Instant,
+}
+
+impl Connection {
+    fn ensure_connected(&mut self) -> bool {
+        if self.stream.is_none() && Instant::now() >= self.retry_at {
+            match connect(&self.config) {
+                Ok(stream) => {
+                    log::info!("Connected to MQTT broker {}", self.config.broker);
+                    self.stream = Some(stream);
+                    self.last_write = Instant::now();
+                }
+                Err(e) => {
+                    log::warn!("MQTT: {}", e);
+                    self.retry_at = Instant::now() + RECONNECT_DELAY;
+                }
+            }
+        }
+        self.stream.is_some()
+    }
+
+    /// Write a packet, dropping the connection if it fails
+    fn send(&mut self, bytes: &[u8]) -> bool {
+        let Some(stream) = self.stream.as_mut() else {
+            return false;
+        };
+        match stream.write_all(bytes) {
+            Ok(()) => {
+                self.last_write = Instant::now();
+                true
+            }
+            Err(e) => {
+                log::warn!("Lost connection to MQTT broker: {}", e);
+                self.stream = None;
+                false
+            }
+        }
+    }
+
+    fn publish(&mut self, topic: &str, payload: &impl Serialize, retain: bool) -> bool {
+        match serde_json::to_vec(payload) {
+            Ok(payload) => self.send(&publish_packet(topic, &payload, retain)),
+            Err(e) => {
+                log::error!("Failed to serialize MQTT payload: {}", e);
+                false
+            }
+        }
+    }
+}
+
+fn run(app: AppHandle, config: MqttConfig, messages: mpsc::Receiver<Message>) {
+    let prefix = config.topic_prefix.trim_end_matches('/').to_string();
+    let mut tracker = EventTracker::new(&config.tags);
+    let publish_agenda = config.agenda;
+    let mut connection = Connection {
+        config,
+        stream: None,
+        last_write: Instant::now(),
+        retry_at: Instant::now(),
+    };
+    let mut published_agenda: Option<Agenda> = None;
+    let mut agenda_due = Instant::now();
+
+    loop {
+        let was_connected = connection.stream.is_some();
+        if connection.ensure_connected() && !was_connected {
+            // The broker may have lost the retained agenda
+            published_agenda = None;
+            agenda_due = Instant::now();
+        }
+
+        match messages.recv_timeout(TICK) {
+            Ok(Message::Op(document_id, op)) => {
+                for event in tracker.events(document_id, &op) {
+                    match event {
+                        Event::TaskChecked { document_id, node_id, checked } => {
+                            let content = indexed_node(&app, node_id).map(|n| strip_html(&n.content));
+                            let topic = format!("{}/task/{}", prefix, if checked { "checked" } else { "unchecked" });
+                            let payload = NodeEvent {
+                                document_id: document_id.to_string(),
+                                node_id: node_id.to_string(),
+                                content: content.as_deref().unwrap_or_default().trim(),
+                                tag: None,
+                            };
+                            connection.publish(&topic, &payload, false);
+                            agenda_due = Instant::now();
+                        }
+                        Event::Tagged { document_id, node_id, tag, content } => {
+                            let payload = NodeEvent {
+                                document_id: document_id.to_string(),
+                                node_id: node_id.to_string(),
+                                content: &content,
+                                tag: Some(&tag),
+                            };
+                            connection.publish(&format!("{}/node/tagged/{}", prefix, tag), &payload, false);
+                        }
+                    }
+                }
+            }
+            Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => {
+                connection.send(&DISCONNECT);
+                return;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        if connection.stream.is_none() {
+            continue;
+        }
+        if publish_agenda && Instant::now() >= agenda_due {
+            agenda_due = Instant::now() + AGENDA_INTERVAL;
+            if let Some(agenda) = agenda_today(&app) {
+                if published_agenda.as_ref() != Some(&agenda)
+                    && connection.publish(&format!("{}/agenda/today", prefix), &agenda, true)
+                {
+                    published_agenda = Some(agenda);
+                }
+            }
+        }
+        if connection.last_write.elapsed() >= KEEP_ALIVE / 2 {
+            connection.send(&PINGREQ);
+        }
+    }
+}
+
+/// The running MQTT publisher, if one is configured
+pub struct MqttState {
+    sender: Mutex<Option<Sender<Message>>>,
+}
+
+impl MqttState {
+    pub fn new() -> Self {
+        Self {
+            sender: Mutex::new(None),
+        }
+    }
+
+    /// Stop the running publisher and, given a config, start a new one that
+    /// is told about every locally appended op
+    pub fn restart(&self, app: AppHandle, config: Option<MqttConfig>) {
+        set_op_listener("mqtt", None);
+        let mut sender = self.sender.lock().unwrap();
+        if let Some(old) = sender.take() {
+            let _ = old.send(Message::Stop);
+        }
+        let Some(config) = config else {
+            return;
+        };
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || run(app, config, rx));
+        let listener_tx = tx.clone();
+        set_op_listener(
+            "mqtt",
+            Some(Box::new(move |document_id, record| {
+                let _ = listener_tx.send(Message::Op(document_id, Box::new(record.op.clone())));
+            })),
+        );
+        *sender = Some(tx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::NodeChanges;
+    use chrono::Utc;
+
+    #[test]
+    fn test_packets() {
+        assert_eq!(broker_address("mqtt://broker.lan:1884/").unwrap(), "broker.lan:1884");
+        assert_eq!(broker_address("192.168.1.5").unwrap(), "192.168.1.5:1883");
+        assert!(broker_address("mqtts://broker.lan").is_err());
+
+        // Remaining length takes a second byte past 127
+        let publish = publish_packet("a/b", &[b'x'; 200], true);
+        assert_eq!(&publish[..5], &[0x31, 0xCD, 0x01, 0x00, 0x03]);
+        assert_eq!(publish.len(), 3 + 5 + 200);
+
+        let connect = connect_packet("outline", Some("me"), Some("pw"));
+        assert_eq!(connect[0], 0x10);
+        assert_eq!(connect[1] as usize, connect.len() - 2);
+        assert_eq!(&connect[2..9], b"\x00\x04MQTT\x04");
+        assert_eq!(connect[9], 0xC2);
+        assert!(connect.ends_with(b"\x00\x02me\x00\x02pw"));
+    }
+
+    #[test]
+    fn test_event_tracker() {
+        let doc = Uuid::now_v7();
+        let mut tracker = EventTracker::new(&["#Shopping".to_string()]);
+        let update = |id: Uuid, changes: NodeChanges| Operation::Update {
+            id,
+            changes,
+            updated_at: Utc::now(),
+        };
+        let tags = |tags: &[&str]| NodeChanges {
+            tags: Some(tags.iter().map(|t| t.to_string()).collect()),
+            ..Default::default()
+        };
+
+        let new_id = Uuid::now_v7();
+        let create = Operation::Create {
+            id: new_id,
+            parent_id: None,
+            position: 0.0,
+            content: "<b>Milk</b>".to_string(),
+            node_type: Default::default(),
+            updated_at: Utc::now(),
+        };
+        assert!(tracker.events(doc, &create).is_empty());
+        assert_eq!(
+            tracker.events(doc, &update(new_id, tags(&["shopping"]))),
+            vec![Event::Tagged {
+                document_id: doc,
+                node_id: new_id,
+                tag: "shopping".to_string(),
+                content: "Milk".to_string(),
+            }]
+        );
+        // Announced once, and only for nodes created this session
+        assert!(tracker.events(doc, &update(new_id, tags(&["shopping", "dairy"]))).is_empty());
+        assert!(tracker.events(doc, &update(Uuid::now_v7(), tags(&["shopping"]))).is_empty());
+
+        let checked = NodeChanges {
+            is_checked: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            tracker.events(doc, &update(new_id, checked)),
+            vec![Event::TaskChecked { document_id: doc, node_id: new_id, checked: true }]
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! In-process timing of the expensive operations (loading, indexing,
+//! compacting, exporting), kept in memory so slow documents can be
+//! diagnosed on users' machines.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Measurements kept per operation
+const MAX_SAMPLES: usize = 200;
+
+static SAMPLES: Mutex<BTreeMap<&'static str, VecDeque<Duration>>> = Mutex::new(BTreeMap::new());
+
+/// Record how long an operation took
+pub fn record(operation: &'static str, elapsed: Duration) {
+    let mut samples = SAMPLES.lock().unwrap();
+    let queue = samples.entry(operation).or_default();
+    if queue.len() == MAX_SAMPLES {
+        queue.pop_front();
+    }
+    queue.push_back(elapsed);
+}
+
+/// Records the time from its creation until it is dropped
+pub struct Timer {
+    operation: &'static str,
+    start: Instant,
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        record(self.operation, self.start.elapsed());
+    }
+}
+
+/// Start timing an operation; the measurement is recorded when the returned
+/// guard goes out of scope
+pub fn timer(operation: &'static str) -> Timer {
+    Timer {
+        operation,
+        start: Instant::now(),
+    }
+}
+
+/// Summary of the recent measurements of one operation
+#[derive(Debug, Clone, Serialize)]
+pub struct PerfStat {
+    pub operation: String,
+    pub count: usize,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    pub last_ms: f64,
+}
+
+/// Nearest-rank percentile of sorted durations, in milliseconds
+fn percentile(sorted: &[Duration], pct: f64) -> f64 {
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1].as_secs_f64() * 1000.0
+}
+
+/// Stats for every operation measured so far, by operation name
+pub fn perf_stats() -> Vec<PerfStat> {
+    let samples = SAMPLES.lock().unwrap();
+    samples
+        .iter()
+        .filter(|(_, queue)| !queue.is_empty())
+        .map(|(operation, queue)| {
+            let mut sorted: Vec<Duration> = queue.iter().copied().collect();
+            sorted.sort();
+            PerfStat {
+                operation: operation.to_string(),
+                count: sorted.len(),
+                p50_ms: percentile(&sorted, 50.0),
+                p95_ms: percentile(&sorted, 95.0),
+                max_ms: percentile(&sorted, 100.0),
+                last_ms: queue.back().map_or(0.0, |d| d.as_secs_f64() * 1000.0),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perf_stats() {
+        for ms in (1..=20).rev() {
+            record("test.op", Duration::from_millis(ms));
+        }
+        drop(timer("test.timer"));
+
+        let stats = perf_stats();
+        let op = stats.iter().find(|s| s.operation == "test.op").unwrap();
+        assert_eq!(op.count, 20);
+        assert_eq!(op.p50_ms, 10.0);
+        assert_eq!(op.p95_ms, 19.0);
+        assert_eq!(op.max_ms, 20.0);
+        assert_eq!(op.last_ms, 1.0);
+        assert!(stats.iter().any(|s| s.operation == "test.timer" && s.count == 1));
+    }
+}
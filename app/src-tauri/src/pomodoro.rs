@@ -0,0 +1,157 @@
+//! Pomodoro timer run by the backend, so it keeps time while the window is
+//! hidden. Emits `pomodoro-tick` every second and `pomodoro-finished` at the
+//! end, when the session is logged against its node and a notification is
+//! shown.
+
+use chrono::{DateTime, Utc};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+use uuid::Uuid;
+
+use crate::data::{append_pomodoro, device_name, documents_dir, PomodoroSession};
+
+/// Default session length
+pub const DEFAULT_MINUTES: u32 = 25;
+const TICK: Duration = Duration::from_secs(1);
+
+/// The running pomodoro, as reported to the frontend
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PomodoroStatus {
+    pub id: Uuid,
+    pub document_id: Uuid,
+    pub node_id: Uuid,
+    pub minutes: u32,
+    pub started_at: DateTime<Utc>,
+    pub remaining_seconds: i64,
+}
+
+struct ActivePomodoro {
+    id: Uuid,
+    document_id: Uuid,
+    node_id: Uuid,
+    minutes: u32,
+    started_at: DateTime<Utc>,
+    cancel: Sender<()>,
+}
+
+impl ActivePomodoro {
+    fn status(&self) -> PomodoroStatus {
+        let ends_at = self.started_at + chrono::Duration::minutes(self.minutes as i64);
+        PomodoroStatus {
+            id: self.id,
+            document_id: self.document_id,
+            node_id: self.node_id,
+            minutes: self.minutes,
+            started_at: self.started_at,
+            remaining_seconds: (ends_at - Utc::now()).num_seconds().max(0),
+        }
+    }
+}
+
+/// State for the pomodoro timer (at most one runs at a time)
+pub struct PomodoroState {
+    active: Mutex<Option<ActivePomodoro>>,
+}
+
+impl PomodoroState {
+    pub fn new() -> Self {
+        Self {
+            active: Mutex::new(None),
+        }
+    }
+
+    pub fn status(&self) -> Option<PomodoroStatus> {
+        self.active.lock().unwrap().as_ref().map(ActivePomodoro::status)
+    }
+
+    /// Cancel the running pomodoro without logging it. Returns whether one was running.
+    pub fn cancel(&self) -> bool {
+        match self.active.lock().unwrap().take() {
+            Some(active) => {
+                let _ = active.cancel.send(());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Clear the pomodoro if it is still `id`, returning it
+    fn finish(&self, id: Uuid) -> Option<ActivePomodoro> {
+        let mut active = self.active.lock().unwrap();
+        if active.as_ref().is_some_and(|a| a.id == id) {
+            active.take()
+        } else {
+            None
+        }
+    }
+}
+
+/// Start a pomodoro on a node, replacing (without logging) any running one
+pub fn start_pomodoro(app: AppHandle, document_id: Uuid, node_id: Uuid, minutes: u32) -> PomodoroStatus {
+    let (cancel, cancelled) = mpsc::channel();
+    let active = ActivePomodoro {
+        id: Uuid::new_v4(),
+        document_id,
+        node_id,
+        minutes,
+        started_at: Utc::now(),
+        cancel,
+    };
+    let status = active.status();
+
+    let state = app.state::<PomodoroState>();
+    state.cancel();
+    *state.active.lock().unwrap() = Some(active);
+
+    let id = status.id;
+    thread::spawn(move || loop {
+        match cancelled.recv_timeout(TICK) {
+            Err(RecvTimeoutError::Timeout) => {}
+            // Cancelled, or replaced by a new pomodoro
+            _ => return,
+        }
+        let Some(status) = app.state::<PomodoroState>().status().filter(|s| s.id == id) else {
+            return;
+        };
+        if status.remaining_seconds > 0 {
+            if let Err(e) = app.emit("pomodoro-tick", &status) {
+                log::error!("Failed to emit pomodoro-tick event: {}", e);
+            }
+            continue;
+        }
+        if let Some(active) = app.state::<PomodoroState>().finish(id) {
+            complete(&app, active);
+        }
+        return;
+    });
+
+    status
+}
+
+/// Log a finished pomodoro, tell the frontend and show a notification
+fn complete(app: &AppHandle, active: ActivePomodoro) {
+    let status = active.status();
+    let session = PomodoroSession {
+        node_id: active.node_id,
+        started_at: active.started_at,
+        minutes: active.minutes,
+        completed_at: Utc::now(),
+        device: device_name(),
+    };
+    let doc_dir = documents_dir().join(active.document_id.to_string());
+    if let Err(e) = append_pomodoro(&doc_dir, &session) {
+        log::error!("Failed to log pomodoro: {}", e);
+    }
+
+    if let Err(e) = app.emit("pomodoro-finished", &status) {
+        log::error!("Failed to emit pomodoro-finished event: {}", e);
+    }
+    let body = format!("{} minutes done. Time for a break.", active.minutes);
+    if let Err(e) = app.notification().builder().title("Pomodoro finished").body(body).show() {
+        log::warn!("Failed to show pomodoro notification: {}", e);
+    }
+}
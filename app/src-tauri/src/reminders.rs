@@ -0,0 +1,74 @@
+//! Reminder notifications. A background thread checks the search index every
+//! half minute for reminders that came due since its last check (including
+//! while the machine was asleep) and, for each unchecked node, shows a
+//! notification and emits `reminder-due`.
+
+use chrono::{DateTime, Duration as ChronoDuration, Local, Utc};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::commands::AppState;
+use crate::data::parse_remind_at;
+use crate::search::{NodeFilter, NodeQueryResult};
+
+const POLL: Duration = Duration::from_secs(30);
+
+/// Start the reminder thread. Reminders that were due before startup are
+/// not shown.
+pub fn start_scheduler(app: AppHandle) {
+    thread::spawn(move || {
+        let mut last_check = Utc::now();
+        loop {
+            thread::sleep(POLL);
+            let now = Utc::now();
+            for reminder in due_reminders(&app, last_check, now) {
+                notify(&app, &reminder);
+            }
+            last_check = now;
+        }
+    });
+}
+
+/// Unchecked nodes whose reminder falls in (after, until]
+fn due_reminders(app: &AppHandle, after: DateTime<Utc>, until: DateTime<Utc>) -> Vec<NodeQueryResult> {
+    let Some(index) = app.state::<AppState>().search_index.lock().unwrap().clone() else {
+        return Vec::new();
+    };
+    // Reminders are stored with their own offset, so the date bounds are
+    // widened by a day and the exact times compared here
+    let date = |at: DateTime<Utc>| at.with_timezone(&Local).format("%Y-%m-%d").to_string();
+    let filter = NodeFilter {
+        remind_from: Some(date(after - ChronoDuration::days(1))),
+        remind_to: Some(date(until + ChronoDuration::days(1))),
+        checked: Some(false),
+        ..Default::default()
+    };
+    match index.query_nodes(&filter) {
+        Ok(results) => results
+            .into_iter()
+            .filter(|r| {
+                r.remind_at
+                    .as_deref()
+                    .and_then(parse_remind_at)
+                    .is_some_and(|at| at > after && at <= until)
+            })
+            .collect(),
+        Err(e) => {
+            log::warn!("Failed to look up reminders: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn notify(app: &AppHandle, reminder: &NodeQueryResult) {
+    if let Err(e) = app.emit("reminder-due", reminder) {
+        log::error!("Failed to emit reminder-due event: {}", e);
+    }
+    let body = reminder.content.trim();
+    let body = if body.is_empty() { "(empty item)" } else { body };
+    if let Err(e) = app.notification().builder().title("Reminder").body(body).show() {
+        log::warn!("Failed to show reminder notification: {}", e);
+    }
+}
@@ -0,0 +1,97 @@
+//! Catch-up when the mobile app returns to the foreground. Rather than the
+//! frontend polling the inbox and every document, a background check on
+//! resume emits one `changes-while-away` event summarizing what arrived since
+//! the last check: new inbox items and documents synced from other devices.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::thread;
+use std::time::SystemTime;
+use tauri::{AppHandle, Emitter, RunEvent};
+
+use crate::data::{documents_synced_since, read_inbox, InboxItem};
+
+/// Payload of the `changes-while-away` event
+#[derive(Debug, Clone, Serialize)]
+pub struct AwaySummary {
+    /// Inbox items captured since the last check
+    pub new_inbox_items: usize,
+    /// Items waiting in the inbox in all
+    pub inbox_items: usize,
+    /// Documents changed by other devices since the last check
+    pub changed_documents: Vec<String>,
+}
+
+impl AwaySummary {
+    fn is_empty(&self) -> bool {
+        self.new_inbox_items == 0 && self.changed_documents.is_empty()
+    }
+}
+
+/// Items captured after `since` (items with unreadable times count as new)
+fn captured_since(items: &[InboxItem], since: DateTime<Utc>) -> usize {
+    items
+        .iter()
+        .filter(|item| {
+            DateTime::parse_from_rfc3339(&item.captured_at).map_or(true, |at| at.with_timezone(&Utc) > since)
+        })
+        .count()
+}
+
+/// What changed after `since`
+pub fn summarize(since: SystemTime) -> Result<AwaySummary, String> {
+    let inbox = read_inbox()?;
+    Ok(AwaySummary {
+        new_inbox_items: captured_since(&inbox, since.into()),
+        inbox_items: inbox.len(),
+        changed_documents: documents_synced_since(since)?
+            .into_iter()
+            .map(|id| id.to_string())
+            .collect(),
+    })
+}
+
+/// Run-event handler that, on mobile, checks for changes on each resume off
+/// the main thread and emits `changes-while-away` if there were any
+pub fn run_event_handler() -> impl FnMut(&AppHandle, RunEvent) {
+    let mut last_check = SystemTime::now();
+    move |app, event| {
+        if !cfg!(mobile) || !matches!(event, RunEvent::Resumed) {
+            return;
+        }
+        let since = last_check;
+        last_check = SystemTime::now();
+        let app = app.clone();
+        thread::spawn(move || match summarize(since) {
+            Ok(summary) if summary.is_empty() => {}
+            Ok(summary) => {
+                if let Err(e) = app.emit("changes-while-away", &summary) {
+                    log::warn!("Failed to emit changes-while-away: {}", e);
+                }
+            }
+            Err(e) => log::warn!("Failed to check for changes on resume: {}", e),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_captured_since() {
+        let since = Utc::now() - Duration::hours(1);
+        let item = |captured_at: String| InboxItem {
+            captured_at,
+            ..InboxItem::new("x".to_string(), None, None)
+        };
+        let items = [
+            item((since - Duration::minutes(5)).to_rfc3339()),
+            item((since + Duration::minutes(5)).to_rfc3339()),
+            InboxItem::new("just now".to_string(), None, Some("web".to_string())),
+            item("garbled".to_string()),
+        ];
+        assert_eq!(captured_since(&items, since), 3);
+    }
+}
@@ -0,0 +1,246 @@
+//! Wiki-link resolution by title. A node can list other names for itself in
+//! its `aliases` property (comma-separated); an alias on a document's title
+//! node names the whole document. `[[JS]]` then resolves to the same node as
+//! `[[JavaScript]]`, and mentions of either count as unlinked mentions.
+
+use rusqlite::{params, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use super::SearchIndex;
+
+/// Node property holding a node's alias names
+pub const ALIASES_PROPERTY: &str = "aliases";
+
+/// Mentions returned at most by `unlinked_mentions`
+const MAX_MENTIONS: usize = 100;
+
+/// The node a wiki link title resolves to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkTarget {
+    pub node_id: String,
+    pub document_id: String,
+    /// The node's own text, whichever name matched
+    pub content: String,
+    /// The alias that matched, if it wasn't the node's text
+    pub alias: Option<String>,
+}
+
+/// A node naming another node (by its text or an alias) without linking to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnlinkedMention {
+    pub node_id: String,
+    pub document_id: String,
+    pub content: String,
+    /// The name found in the content
+    pub matched: String,
+}
+
+/// Split an `aliases` property value into names
+pub fn parse_aliases(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty())
+        .collect()
+}
+
+fn aliases_from_json(properties: Option<&str>) -> Vec<String> {
+    properties
+        .and_then(|json| serde_json::from_str::<HashMap<String, String>>(json).ok())
+        .and_then(|props| props.get(ALIASES_PROPERTY).map(|v| parse_aliases(v)))
+        .unwrap_or_default()
+}
+
+/// Whether `name` occurs in `text` as whole words (case-insensitive)
+fn mentions(text: &str, name: &str) -> bool {
+    let text = text.to_lowercase();
+    let name = name.to_lowercase();
+    if name.is_empty() {
+        return false;
+    }
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric());
+    text.match_indices(&name).any(|(start, _)| {
+        !is_word(text[..start].chars().next_back()) && !is_word(text[start + name.len()..].chars().next())
+    })
+}
+
+impl SearchIndex {
+    /// Find the node a `[[title]]` link means: a node whose text is the title
+    /// (shallowest first, so document titles win), else one with the title
+    /// as an alias. Matching ignores case.
+    pub fn resolve_wiki_link(&self, title: &str) -> SqliteResult<Option<LinkTarget>> {
+        let title = title.trim();
+        if title.is_empty() {
+            return Ok(None);
+        }
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, document_id, content FROM nodes
+            WHERE lower(trim(content)) = lower(?)
+            ORDER BY depth ASC
+            LIMIT 1
+            "#,
+        )?;
+        let mut rows = stmt.query_map(params![title], |row| {
+            Ok(LinkTarget {
+                node_id: row.get(0)?,
+                document_id: row.get(1)?,
+                content: row.get(2)?,
+                alias: None,
+            })
+        })?;
+        if let Some(target) = rows.next() {
+            return target.map(Some);
+        }
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, document_id, content, properties FROM nodes
+            WHERE json_extract(properties, '$.aliases') IS NOT NULL
+            ORDER BY depth ASC
+            "#,
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })?;
+        let lower = title.to_lowercase();
+        for row in rows {
+            let (node_id, document_id, content, properties) = row?;
+            if let Some(alias) = aliases_from_json(properties.as_deref())
+                .into_iter()
+                .find(|a| a.to_lowercase() == lower)
+            {
+                return Ok(Some(LinkTarget {
+                    node_id,
+                    document_id,
+                    content,
+                    alias: Some(alias),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Nodes that mention a node by its text or one of its aliases (as whole
+    /// words) but don't link to it
+    pub fn unlinked_mentions(&self, target_node_id: &Uuid) -> SqliteResult<Vec<UnlinkedMention>> {
+        let conn = self.conn.lock().unwrap();
+        let target_id = target_node_id.to_string();
+
+        let target = conn
+            .query_row(
+                "SELECT content, properties FROM nodes WHERE id = ?",
+                params![target_id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+            )
+            .ok();
+        let Some((content, properties)) = target else {
+            return Ok(Vec::new());
+        };
+        let mut names = vec![content.trim().to_string()];
+        names.extend(aliases_from_json(properties.as_deref()));
+        names.retain(|n| !n.is_empty());
+        names.dedup_by(|a, b| a.to_lowercase() == b.to_lowercase());
+
+        let mut stmt = conn.prepare("SELECT source_node_id FROM links WHERE target_node_id = ?")?;
+        let linked: HashSet<String> = stmt
+            .query_map(params![target_id], |row| row.get(0))?
+            .collect::<SqliteResult<_>>()?;
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, document_id, content FROM nodes
+            WHERE id != ? AND instr(lower(content), lower(?)) > 0
+            ORDER BY document_id, depth
+            "#,
+        )?;
+        let mut seen = HashSet::new();
+        let mut results = Vec::new();
+        for name in &names {
+            let rows = stmt.query_map(params![target_id, name], |row| {
+                Ok(UnlinkedMention {
+                    node_id: row.get(0)?,
+                    document_id: row.get(1)?,
+                    content: row.get(2)?,
+                    matched: name.clone(),
+                })
+            })?;
+            for mention in rows {
+                let mention = mention?;
+                if linked.contains(&mention.node_id)
+                    || !mentions(&mention.content, name)
+                    || !seen.insert(mention.node_id.clone())
+                {
+                    continue;
+                }
+                results.push(mention);
+                if results.len() >= MAX_MENTIONS {
+                    return Ok(results);
+                }
+            }
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Node;
+    use rusqlite::Connection;
+
+    fn setup_index() -> SearchIndex {
+        let conn = Connection::open_in_memory().unwrap();
+        SearchIndex::init_schema(&conn).unwrap();
+        SearchIndex::from_connection(conn)
+    }
+
+    #[test]
+    fn test_aliases_resolve_and_mention() {
+        let index = setup_index();
+        let languages = Uuid::now_v7();
+        let notes = Uuid::now_v7();
+
+        let mut title = Node::new("JavaScript".to_string());
+        title
+            .properties
+            .insert(ALIASES_PROPERTY.to_string(), "JS, ECMAScript".to_string());
+        let child = Node::new_child(title.id, 0.0, "javascript".to_string());
+        index.index_document(&languages, &[title.clone(), child]).unwrap();
+
+        let day = Node::new("Monday".to_string());
+        let plain = Node::new_child(day.id, 0.0, "Fixed the JS build".to_string());
+        let inside_word = Node::new_child(day.id, 1.0, "Read about JSON".to_string());
+        let mut linked = Node::new_child(day.id, 2.0, String::new());
+        linked.content = format!("<span data-wiki-link data-node-id=\"{}\">JS</span> again", title.id);
+        let nodes = [day, plain.clone(), inside_word, linked];
+        index.index_document(&notes, &nodes).unwrap();
+        index.update_document_links(&notes, &nodes).unwrap();
+
+        // Titles and aliases resolve to the same node; text matches win and
+        // shallower nodes come first
+        for name in ["JavaScript", "js", " ecmascript "] {
+            let target = index.resolve_wiki_link(name).unwrap().unwrap();
+            assert_eq!(target.node_id, title.id.to_string());
+            assert_eq!(target.document_id, languages.to_string());
+        }
+        assert_eq!(index.resolve_wiki_link("js").unwrap().unwrap().alias.as_deref(), Some("JS"));
+        assert!(index.resolve_wiki_link("Java").unwrap().is_none());
+
+        // Only whole-word, not-yet-linked mentions are listed
+        let mentions = index.unlinked_mentions(&title.id).unwrap();
+        let ids: Vec<_> = mentions.iter().map(|m| m.node_id.as_str()).collect();
+        assert!(ids.contains(&plain.id.to_string().as_str()));
+        assert_eq!(mentions.iter().find(|m| m.node_id == plain.id.to_string()).unwrap().matched, "JS");
+        assert_eq!(mentions.len(), 2, "the lowercase child and the plain mention");
+    }
+}
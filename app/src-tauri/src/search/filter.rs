@@ -0,0 +1,338 @@
+use rusqlite::{params_from_iter, Result as SqliteResult, ToSql};
+use serde::{Deserialize, Serialize};
+
+use super::{escape_fts_query, SearchIndex};
+
+/// WHERE conditions with their bound values, built up filter by filter
+#[derive(Default)]
+pub(crate) struct SqlConditions {
+    conditions: Vec<String>,
+    pub(crate) values: Vec<Box<dyn ToSql>>,
+}
+
+impl SqlConditions {
+    pub(crate) fn push(&mut self, condition: &str, values: Vec<Box<dyn ToSql>>) {
+        self.conditions.push(condition.to_string());
+        self.values.extend(values);
+    }
+
+    pub(crate) fn priority(&mut self, priority: u8) {
+        self.push("n.priority = ?", vec![Box::new(priority)]);
+    }
+
+    /// Match a color label (case-insensitive)
+    pub(crate) fn color(&mut self, color: &str) {
+        self.push("lower(n.color) = ?", vec![Box::new(color.to_lowercase())]);
+    }
+
+    /// Match a property by key, and by value when one is given
+    pub(crate) fn property(&mut self, key: &str, value: Option<&str>) {
+        let path = format!("$.\"{}\"", key.replace('"', "\\\""));
+        match value {
+            Some(value) => self.push(
+                "json_extract(n.properties, ?) = ?",
+                vec![Box::new(path), Box::new(value.to_string())],
+            ),
+            None => self.push("json_extract(n.properties, ?) IS NOT NULL", vec![Box::new(path)]),
+        }
+    }
+
+    /// Match a tag exactly (tags are stored space-separated)
+    pub(crate) fn tag(&mut self, tag: &str) {
+        let tag = tag.trim_start_matches('#');
+        self.push(
+            "instr(' ' || IFNULL(n.tags, '') || ' ', ?) > 0",
+            vec![Box::new(format!(" {} ", tag))],
+        );
+    }
+
+    pub(crate) fn where_clause(&self) -> String {
+        if self.conditions.is_empty() {
+            "1 = 1".to_string()
+        } else {
+            self.conditions.join(" AND ")
+        }
+    }
+}
+
+/// A structured node filter, as saved by smart views, agenda and boards.
+/// Every field is optional; all given fields must match.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeFilter {
+    /// Full-text search over content and notes
+    pub text: Option<String>,
+    /// Tags the node must all carry (with or without the leading #)
+    pub tags: Vec<String>,
+    /// Earliest date, inclusive (YYYY-MM-DD)
+    pub date_from: Option<String>,
+    /// Latest date, inclusive (YYYY-MM-DD)
+    pub date_to: Option<String>,
+    /// Only nodes with (true) or without (false) a date
+    pub has_date: Option<bool>,
+    /// Earliest reminder date, inclusive (YYYY-MM-DD)
+    pub remind_from: Option<String>,
+    /// Latest reminder date, inclusive (YYYY-MM-DD)
+    pub remind_to: Option<String>,
+    /// Only nodes with (true) or without (false) a reminder
+    pub has_reminder: Option<bool>,
+    /// Only checked (true) or unchecked (false) nodes
+    pub checked: Option<bool>,
+    /// Restrict to these documents; empty means every document
+    pub document_ids: Vec<String>,
+    /// Restrict to these nodes; empty means every node
+    pub node_ids: Vec<String>,
+    pub priority: Option<u8>,
+    /// Property key and optional value, as in `prop:key=value`
+    pub properties: Vec<(String, Option<String>)>,
+    pub limit: Option<usize>,
+}
+
+/// A node matched by a NodeFilter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeQueryResult {
+    pub node_id: String,
+    pub document_id: String,
+    pub content: String,
+    pub note: Option<String>,
+    pub date: Option<String>,
+    pub remind_at: Option<String>,
+    pub is_checked: bool,
+    pub tags: Vec<String>,
+    pub priority: Option<u8>,
+}
+
+const DEFAULT_QUERY_LIMIT: usize = 500;
+
+impl SearchIndex {
+    /// Find nodes matching a structured filter. Results are ordered by date
+    /// (undated last), then priority, then outline depth.
+    pub fn query_nodes(&self, filter: &NodeFilter) -> SqliteResult<Vec<NodeQueryResult>> {
+        let conn = self.conn.lock().unwrap();
+        let mut conditions = SqlConditions::default();
+
+        // Text with no words (e.g. only punctuation) escapes to nothing, and
+        // an empty MATCH is an FTS5 syntax error
+        let escaped_text = escape_fts_query(filter.text.as_deref().unwrap_or_default());
+        if !escaped_text.is_empty() {
+            conditions.push(
+                "n.id IN (SELECT id FROM nodes_fts WHERE nodes_fts MATCH ?)",
+                vec![Box::new(escaped_text)],
+            );
+        }
+        for tag in &filter.tags {
+            conditions.tag(tag);
+        }
+        if let Some(ref from) = filter.date_from {
+            conditions.push("substr(n.date, 1, 10) >= ?", vec![Box::new(from.clone())]);
+        }
+        if let Some(ref to) = filter.date_to {
+            conditions.push("substr(n.date, 1, 10) <= ?", vec![Box::new(to.clone())]);
+        }
+        match filter.has_date {
+            Some(true) => conditions.push("n.date IS NOT NULL AND n.date != ''", vec![]),
+            Some(false) => conditions.push("(n.date IS NULL OR n.date = '')", vec![]),
+            None => {}
+        }
+        if let Some(ref from) = filter.remind_from {
+            conditions.push("substr(n.remind_at, 1, 10) >= ?", vec![Box::new(from.clone())]);
+        }
+        if let Some(ref to) = filter.remind_to {
+            conditions.push("substr(n.remind_at, 1, 10) <= ?", vec![Box::new(to.clone())]);
+        }
+        match filter.has_reminder {
+            Some(true) => conditions.push("n.remind_at IS NOT NULL AND n.remind_at != ''", vec![]),
+            Some(false) => conditions.push("(n.remind_at IS NULL OR n.remind_at = '')", vec![]),
+            None => {}
+        }
+        if let Some(checked) = filter.checked {
+            conditions.push("n.is_checked = ?", vec![Box::new(checked)]);
+        }
+        if !filter.document_ids.is_empty() {
+            let placeholders = vec!["?"; filter.document_ids.len()].join(", ");
+            conditions.push(
+                &format!("n.document_id IN ({})", placeholders),
+                filter
+                    .document_ids
+                    .iter()
+                    .map(|id| Box::new(id.clone()) as Box<dyn ToSql>)
+                    .collect(),
+            );
+        }
+        if !filter.node_ids.is_empty() {
+            let placeholders = vec!["?"; filter.node_ids.len()].join(", ");
+            conditions.push(
+                &format!("n.id IN ({})", placeholders),
+                filter
+                    .node_ids
+                    .iter()
+                    .map(|id| Box::new(id.clone()) as Box<dyn ToSql>)
+                    .collect(),
+            );
+        }
+        if let Some(priority) = filter.priority {
+            conditions.priority(priority);
+        }
+        for (key, value) in &filter.properties {
+            conditions.property(key, value.as_deref());
+        }
+
+        let sql = format!(
+            r#"
+            SELECT n.id, n.document_id, n.content, n.note, n.date, n.is_checked, n.tags, n.priority, n.remind_at
+            FROM nodes n
+            WHERE {}
+            ORDER BY n.date IS NULL, n.date ASC, n.priority IS NULL, n.priority ASC, n.depth ASC
+            LIMIT ?
+            "#,
+            conditions.where_clause()
+        );
+        conditions
+            .values
+            .push(Box::new(filter.limit.unwrap_or(DEFAULT_QUERY_LIMIT) as i64));
+
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(conditions.values.iter()), |row| {
+            let tags: Option<String> = row.get(6)?;
+            Ok(NodeQueryResult {
+                node_id: row.get(0)?,
+                document_id: row.get(1)?,
+                content: row.get(2)?,
+                note: row.get(3)?,
+                date: row.get(4)?,
+                is_checked: row.get(5)?,
+                tags: tags
+                    .map(|t| t.split_whitespace().map(String::from).collect())
+                    .unwrap_or_default(),
+                priority: row.get(7)?,
+                remind_at: row.get(8)?,
+            })
+        })?;
+
+        Ok(rows.flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Node;
+    use rusqlite::Connection;
+    use uuid::Uuid;
+
+    fn setup_index() -> SearchIndex {
+        let conn = Connection::open_in_memory().unwrap();
+        SearchIndex::init_schema(&conn).unwrap();
+        SearchIndex::from_connection(conn)
+    }
+
+    fn node(content: &str, date: Option<&str>, tags: &[&str], checked: bool) -> Node {
+        let mut node = Node::new(content.to_string());
+        node.date = date.map(String::from);
+        node.tags = tags.iter().map(|t| t.to_string()).collect();
+        node.is_checked = checked;
+        node
+    }
+
+    fn contents(results: &[NodeQueryResult]) -> Vec<&str> {
+        results.iter().map(|r| r.content.as_str()).collect()
+    }
+
+    #[test]
+    fn test_query_nodes() {
+        let index = setup_index();
+        let doc_a = Uuid::now_v7();
+        let doc_b = Uuid::now_v7();
+        index
+            .index_document(
+                &doc_a,
+                &[
+                    node("Pay rent", Some("2024-02-01"), &["home"], false),
+                    node("Call plumber", Some("2024-01-15"), &["home", "urgent"], false),
+                    node("Buy milk", None, &["home"], true),
+                ],
+            )
+            .unwrap();
+        index
+            .index_document(&doc_b, &[node("Ship release", Some("2024-01-20"), &["work"], false)])
+            .unwrap();
+
+        let home = NodeFilter {
+            tags: vec!["#home".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            contents(&index.query_nodes(&home).unwrap()),
+            vec!["Call plumber", "Pay rent", "Buy milk"]
+        );
+
+        let january_open = NodeFilter {
+            date_from: Some("2024-01-01".to_string()),
+            date_to: Some("2024-01-31".to_string()),
+            checked: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(
+            contents(&index.query_nodes(&january_open).unwrap()),
+            vec!["Call plumber", "Ship release"]
+        );
+
+        let scoped = NodeFilter {
+            text: Some("release".to_string()),
+            document_ids: vec![doc_a.to_string()],
+            ..Default::default()
+        };
+        assert!(index.query_nodes(&scoped).unwrap().is_empty());
+
+        // Text without words adds no text condition
+        for text in ["-", "(", " \" "] {
+            let punctuation = NodeFilter {
+                text: Some(text.to_string()),
+                tags: vec!["work".to_string()],
+                ..Default::default()
+            };
+            assert_eq!(contents(&index.query_nodes(&punctuation).unwrap()), vec!["Ship release"]);
+        }
+
+        let undated = NodeFilter {
+            has_date: Some(false),
+            ..Default::default()
+        };
+        let results = index.query_nodes(&undated).unwrap();
+        assert_eq!(contents(&results), vec!["Buy milk"]);
+        assert!(results[0].is_checked);
+        assert_eq!(results[0].tags, vec!["home"]);
+
+        // A tag only matches whole words
+        let partial = NodeFilter {
+            tags: vec!["hom".to_string()],
+            ..Default::default()
+        };
+        assert!(index.query_nodes(&partial).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_query_reminders_apart_from_dates() {
+        let index = setup_index();
+        let mut report = node("Write report", Some("2024-03-08"), &[], false);
+        report.remind_at = Some("2024-03-06T09:00:00-05:00".to_string());
+        index
+            .index_document(&Uuid::now_v7(), &[report, node("Due only", Some("2024-03-06"), &[], false)])
+            .unwrap();
+
+        let wednesday = NodeFilter {
+            remind_from: Some("2024-03-06".to_string()),
+            remind_to: Some("2024-03-06".to_string()),
+            ..Default::default()
+        };
+        let results = index.query_nodes(&wednesday).unwrap();
+        assert_eq!(contents(&results), vec!["Write report"]);
+        assert_eq!(results[0].date.as_deref(), Some("2024-03-08"));
+
+        let without = NodeFilter {
+            has_reminder: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(contents(&index.query_nodes(&without).unwrap()), vec!["Due only"]);
+    }
+}
@@ -0,0 +1,267 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, RwLock, RwLockWriteGuard};
+use uuid::Uuid;
+
+use super::SearchIndex;
+use crate::data::{documents_dir, list_documents, Document, DocumentMeta, Node};
+
+/// Work for the indexing thread
+enum IndexJob {
+    /// Re-index one document's nodes and links
+    Document { id: Uuid, nodes: Vec<Node> },
+    /// Replace the whole index with every document on disk
+    Rebuild,
+    /// Drop the entries of documents no longer on disk
+    Reconcile,
+}
+
+/// Progress of the indexing thread
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IndexStatus {
+    /// Jobs waiting to run (repeated requests for a document count once)
+    pub queued: usize,
+    /// Jobs finished since startup
+    pub completed: usize,
+    /// Document being indexed, or "all" during a rebuild
+    pub current: Option<String>,
+}
+
+/// The app's search index, shared between commands and the indexing thread.
+/// `None` while the database can't be opened.
+pub type SharedSearchIndex = Arc<Mutex<Option<SearchIndex>>>;
+
+/// Queue of search indexing jobs, run one at a time on a dedicated thread
+pub struct IndexQueue {
+    sender: Sender<IndexJob>,
+    status: Arc<Mutex<IndexStatus>>,
+    /// Held by the worker while it runs a batch, and by `pause`
+    running: Arc<RwLock<()>>,
+}
+
+impl IndexQueue {
+    /// Start the indexing thread, writing to whichever index is in `index`
+    /// when each batch of jobs starts
+    pub fn start(index: SharedSearchIndex) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let status = Arc::new(Mutex::new(IndexStatus::default()));
+        let running = Arc::new(RwLock::new(()));
+        let worker_status = status.clone();
+        let worker_running = running.clone();
+        std::thread::spawn(move || run_worker(receiver, index, worker_status, worker_running));
+        Self { sender, status, running }
+    }
+
+    /// Wait for the running batch to finish and hold off the next one until
+    /// the returned guard is dropped (jobs queued meanwhile still run then)
+    pub fn pause(&self) -> RwLockWriteGuard<'_, ()> {
+        self.running.write().unwrap()
+    }
+
+    /// Queue a document for re-indexing
+    pub fn index_document(&self, id: Uuid, nodes: Vec<Node>) {
+        self.send(IndexJob::Document { id, nodes });
+    }
+
+    /// Queue a rebuild of the whole index from the documents on disk
+    pub fn rebuild(&self) {
+        self.send(IndexJob::Rebuild);
+    }
+
+    /// Queue removal of documents that were deleted outside the app (by
+    /// sync, or by hand) from the index
+    pub fn reconcile(&self) {
+        self.send(IndexJob::Reconcile);
+    }
+
+    pub fn status(&self) -> IndexStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    fn send(&self, job: IndexJob) {
+        self.status.lock().unwrap().queued += 1;
+        if self.sender.send(job).is_err() {
+            log::error!("Search indexing thread has stopped");
+        }
+    }
+}
+
+fn run_worker(
+    receiver: Receiver<IndexJob>,
+    shared: SharedSearchIndex,
+    status: Arc<Mutex<IndexStatus>>,
+    running: Arc<RwLock<()>>,
+) {
+    while let Ok(job) = receiver.recv() {
+        let _running = running.read().unwrap();
+        let mut jobs = vec![job];
+        jobs.extend(receiver.try_iter());
+        let received = jobs.len();
+        let jobs = merge_jobs(jobs);
+        status.lock().unwrap().queued -= received - jobs.len();
+
+        // Clone the handle rather than holding the lock, so searches can
+        // run between jobs
+        let index = shared.lock().unwrap().clone();
+        if index.is_none() {
+            log::warn!("Search index not available; dropping {} indexing jobs", jobs.len());
+        }
+
+        for job in jobs {
+            status.lock().unwrap().current = Some(match job {
+                IndexJob::Document { id, .. } => id.to_string(),
+                IndexJob::Rebuild | IndexJob::Reconcile => "all".to_string(),
+            });
+            if let Some(ref index) = index {
+                run_job(index, &job);
+            }
+            let mut status = status.lock().unwrap();
+            status.queued -= 1;
+            status.completed += 1;
+            status.current = None;
+        }
+    }
+}
+
+/// Whether a document is marked private, so it must not be indexed
+fn is_excluded(id: &Uuid) -> bool {
+    DocumentMeta::load(&documents_dir().join(id.to_string())).is_ok_and(|meta| meta.settings.excluded)
+}
+
+fn run_job(index: &SearchIndex, job: &IndexJob) {
+    match job {
+        IndexJob::Document { id, nodes } => {
+            // Indexing no nodes removes an excluded document's entries
+            let nodes: &[Node] = if is_excluded(id) { &[] } else { nodes };
+            if let Err(e) = index.index_document(id, nodes) {
+                log::warn!("Failed to index document {}: {}", id, e);
+            }
+            if let Err(e) = index.update_document_links(id, nodes) {
+                log::warn!("Failed to update links for document {}: {}", id, e);
+            }
+        }
+        IndexJob::Rebuild => match index.rebuild() {
+            Ok(count) => log::info!("Rebuilt search index for {} documents", count),
+            Err(e) => log::warn!("Failed to rebuild search index: {}", e),
+        },
+        IndexJob::Reconcile => match index.remove_deleted_documents() {
+            Ok(removed) if !removed.is_empty() => {
+                log::info!("Removed {} deleted documents from the search index", removed.len())
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Failed to remove deleted documents from the search index: {}", e),
+        },
+    }
+}
+
+/// Collapse a batch of queued jobs: a rebuild makes every earlier job
+/// redundant, only the latest nodes queued for a document matter, and one
+/// reconcile (the last) is enough
+fn merge_jobs(jobs: Vec<IndexJob>) -> Vec<IndexJob> {
+    let last_rebuild = jobs.iter().rposition(|job| matches!(job, IndexJob::Rebuild));
+    let last_reconcile = jobs.iter().rposition(|job| matches!(job, IndexJob::Reconcile));
+    let mut merged = Vec::new();
+    let mut slots: HashMap<Uuid, usize> = HashMap::new();
+    for (i, job) in jobs.into_iter().enumerate() {
+        if last_rebuild.is_some_and(|r| i < r) {
+            continue;
+        }
+        match job {
+            IndexJob::Document { id, nodes } => match slots.get(&id) {
+                Some(&slot) => merged[slot] = IndexJob::Document { id, nodes },
+                None => {
+                    slots.insert(id, merged.len());
+                    merged.push(IndexJob::Document { id, nodes });
+                }
+            },
+            IndexJob::Rebuild => merged.push(IndexJob::Rebuild),
+            IndexJob::Reconcile if Some(i) == last_reconcile => merged.push(IndexJob::Reconcile),
+            IndexJob::Reconcile => {}
+        }
+    }
+    merged
+}
+
+impl SearchIndex {
+    /// Replace the index contents with every document in the data directory.
+    /// A document that fails to load or index is logged and skipped, so one
+    /// bad document doesn't leave the rest out. Returns the number of
+    /// documents indexed.
+    pub fn rebuild(&self) -> Result<usize, String> {
+        let doc_ids = list_documents()?;
+        self.clear().map_err(|e| e.to_string())?;
+        let mut indexed = 0;
+        for doc_uuid in &doc_ids {
+            let doc = match Document::load(documents_dir().join(doc_uuid.to_string())) {
+                Ok(doc) => doc,
+                Err(e) => {
+                    log::warn!("Skipping document {} in reindex: {}", doc_uuid, e);
+                    continue;
+                }
+            };
+            if doc.meta.settings.excluded {
+                continue;
+            }
+            let result = self
+                .index_document(doc_uuid, &doc.state.nodes)
+                .and_then(|_| self.update_document_links(doc_uuid, &doc.state.nodes));
+            match result {
+                Ok(()) => indexed += 1,
+                Err(e) => log::warn!("Failed to index document {} in reindex: {}", doc_uuid, e),
+            }
+        }
+        Ok(indexed)
+    }
+
+    /// Remove the entries of documents whose directory is gone. Returns
+    /// their ids.
+    pub fn remove_deleted_documents(&self) -> Result<Vec<String>, String> {
+        // An unreachable data directory (an unmounted drive) isn't every
+        // document being deleted
+        if !documents_dir().is_dir() {
+            return Err(format!("Documents directory {} not found", documents_dir().display()));
+        }
+        let on_disk: HashSet<Uuid> = list_documents()?.into_iter().collect();
+        self.remove_documents_except(&on_disk).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc_job(id: Uuid, content: &str) -> IndexJob {
+        IndexJob::Document {
+            id,
+            nodes: vec![Node::new(content.to_string())],
+        }
+    }
+
+    fn describe(jobs: &[IndexJob]) -> Vec<String> {
+        jobs.iter()
+            .map(|job| match job {
+                IndexJob::Document { nodes, .. } => nodes[0].content.clone(),
+                IndexJob::Rebuild => "rebuild".to_string(),
+                IndexJob::Reconcile => "reconcile".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_jobs() {
+        let a = Uuid::now_v7();
+        let b = Uuid::now_v7();
+
+        let jobs = vec![doc_job(a, "a1"), doc_job(b, "b1"), doc_job(a, "a2")];
+        assert_eq!(describe(&merge_jobs(jobs)), vec!["a2", "b1"]);
+
+        let jobs = vec![doc_job(a, "a1"), IndexJob::Rebuild, doc_job(b, "b1"), IndexJob::Rebuild, doc_job(a, "a2")];
+        assert_eq!(describe(&merge_jobs(jobs)), vec!["rebuild", "a2"]);
+
+        let jobs = vec![IndexJob::Reconcile, doc_job(a, "a1"), IndexJob::Reconcile, doc_job(b, "b1")];
+        assert_eq!(describe(&merge_jobs(jobs)), vec!["a1", "reconcile", "b1"]);
+        let jobs = vec![IndexJob::Reconcile, IndexJob::Rebuild];
+        assert_eq!(describe(&merge_jobs(jobs)), vec!["rebuild"]);
+    }
+}
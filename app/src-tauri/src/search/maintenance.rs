@@ -0,0 +1,220 @@
+use rusqlite::Result as SqliteResult;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use uuid::Uuid;
+
+use super::SearchIndex;
+use crate::data::{search_cache_config, SearchCacheMode};
+
+/// Outcome of a search cache maintenance run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheMaintenanceReport {
+    /// Problems reported by SQLite's integrity check (empty when sound)
+    pub integrity_errors: Vec<String>,
+    /// Whether the cache was discarded and rebuilt from the documents
+    pub rebuilt: bool,
+    /// Database size in bytes before and after vacuuming
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+/// How the search cache is stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheMode {
+    /// A plain SQLite file in the cache directory
+    Plain,
+    /// A SQLCipher database encrypted with this key
+    Encrypted(String),
+    /// Nothing on disk: the index lives in memory and is rebuilt at startup
+    Memory,
+}
+
+/// Whether this build can encrypt the search cache (the `sqlcipher` feature)
+pub fn encryption_supported() -> bool {
+    cfg!(feature = "sqlcipher")
+}
+
+impl CacheMode {
+    /// The mode configured for the current data directory. An encrypted cache
+    /// falls back to memory in builds without SQLCipher, so the plaintext is
+    /// never written to disk.
+    pub fn configured() -> Self {
+        let config = search_cache_config();
+        match (config.mode, config.key) {
+            (SearchCacheMode::Plain, _) => CacheMode::Plain,
+            (SearchCacheMode::Encrypted, Some(key)) if encryption_supported() => CacheMode::Encrypted(key),
+            (SearchCacheMode::Encrypted, _) => {
+                log::warn!("Search cache encryption unavailable, keeping the index in memory");
+                CacheMode::Memory
+            }
+            (SearchCacheMode::Disabled, _) => CacheMode::Memory,
+        }
+    }
+}
+
+/// A new random key for an encrypted cache
+pub fn new_cache_key() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Set (`key`) or change (`rekey`) a SQLCipher key
+#[cfg(feature = "sqlcipher")]
+pub(super) fn apply_key(conn: &rusqlite::Connection, pragma: &str, key: &str) -> SqliteResult<()> {
+    conn.pragma_update(None, pragma, key)
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+pub(super) fn apply_key(_conn: &rusqlite::Connection, _pragma: &str, _key: &str) -> SqliteResult<()> {
+    Err(rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+        Some("Search cache encryption needs a build with SQLCipher".to_string()),
+    ))
+}
+
+impl SearchIndex {
+    /// Open the index, starting over with an empty database if the existing
+    /// one can't be opened or fails SQLite's quick check (including when it
+    /// was written with another key, or unencrypted). Returns true when the
+    /// index was recreated and needs rebuilding from the documents.
+    pub fn open_or_recreate(mode: &CacheMode) -> SqliteResult<(Self, bool)> {
+        Self::open_or_recreate_at(&Self::db_path(), mode)
+    }
+
+    fn open_or_recreate_at(db_path: &Path, mode: &CacheMode) -> SqliteResult<(Self, bool)> {
+        let key = match mode {
+            CacheMode::Memory => return Ok((Self::recreate_at(db_path, mode)?, true)),
+            CacheMode::Plain => None,
+            CacheMode::Encrypted(key) => Some(key.as_str()),
+        };
+        match Self::open_at_with_key(db_path, key) {
+            Ok(index) => match index.quick_check() {
+                Ok(errors) if errors.is_empty() => return Ok((index, false)),
+                Ok(errors) => log::error!("Search cache is corrupted: {}", errors.join("; ")),
+                Err(e) => log::error!("Search cache check failed: {}", e),
+            },
+            Err(e) => log::error!("Failed to open search cache: {}", e),
+        }
+        Ok((Self::recreate_at(db_path, mode)?, true))
+    }
+
+    /// Delete the database and its journal files, and create an empty one
+    pub fn recreate(mode: &CacheMode) -> SqliteResult<Self> {
+        Self::recreate_at(&Self::db_path(), mode)
+    }
+
+    fn recreate_at(db_path: &Path, mode: &CacheMode) -> SqliteResult<Self> {
+        for suffix in ["", "-journal", "-wal", "-shm"] {
+            let mut path = db_path.as_os_str().to_owned();
+            path.push(suffix);
+            let _ = fs::remove_file(path);
+        }
+        match mode {
+            CacheMode::Plain => Self::open_at(db_path),
+            CacheMode::Encrypted(key) => Self::open_at_with_key(db_path, Some(key)),
+            CacheMode::Memory => Self::open_in_memory(),
+        }
+    }
+
+    /// Re-encrypt an encrypted cache with a new key, in place
+    pub fn rekey(&self, key: &str) -> SqliteResult<()> {
+        apply_key(&self.conn.lock().unwrap(), "rekey", key)
+    }
+
+    /// Run SQLite's quick check, returning any problems found
+    pub fn quick_check(&self) -> SqliteResult<Vec<String>> {
+        self.run_check("quick_check")
+    }
+
+    /// Run SQLite's full integrity check, returning any problems found
+    pub fn integrity_check(&self) -> SqliteResult<Vec<String>> {
+        self.run_check("integrity_check")
+    }
+
+    fn run_check(&self, pragma: &str) -> SqliteResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("PRAGMA {}", pragma))?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let messages = rows.collect::<SqliteResult<Vec<_>>>()?;
+        Ok(messages.into_iter().filter(|m| m != "ok").collect())
+    }
+
+    /// Merge the full-text index segments and reclaim free pages
+    pub fn optimize(&self) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute_batch("INSERT INTO nodes_fts(nodes_fts) VALUES('optimize'); VACUUM;")
+    }
+
+    /// Size of the database in bytes
+    pub fn size(&self) -> SqliteResult<u64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
+        .map(|size| size as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Node;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_open_or_recreate_replaces_corrupt_cache() {
+        let tmp = TempDir::new().unwrap();
+        let db_path = tmp.path().join("outline.db");
+        fs::write(&db_path, "definitely not a database").unwrap();
+
+        let (index, recreated) = SearchIndex::open_or_recreate_at(&db_path, &CacheMode::Plain).unwrap();
+        assert!(recreated);
+        index
+            .index_document(&Uuid::now_v7(), &[Node::new("Rebuilt".to_string())])
+            .unwrap();
+        drop(index);
+
+        let (index, recreated) = SearchIndex::open_or_recreate_at(&db_path, &CacheMode::Plain).unwrap();
+        assert!(!recreated);
+        assert_eq!(index.search("rebuilt", None, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_memory_cache_removes_file_on_disk() {
+        let tmp = TempDir::new().unwrap();
+        let db_path = tmp.path().join("outline.db");
+        let (index, _) = SearchIndex::open_or_recreate_at(&db_path, &CacheMode::Plain).unwrap();
+        index.index_document(&Uuid::now_v7(), &[Node::new("Secret".to_string())]).unwrap();
+        drop(index);
+
+        let (index, recreated) = SearchIndex::open_or_recreate_at(&db_path, &CacheMode::Memory).unwrap();
+        assert!(recreated);
+        assert!(!db_path.exists());
+        assert!(index.search("secret", None, 10).unwrap().is_empty());
+    }
+
+    #[cfg(not(feature = "sqlcipher"))]
+    #[test]
+    fn test_encryption_needs_sqlcipher() {
+        let tmp = TempDir::new().unwrap();
+        let mode = CacheMode::Encrypted("key".to_string());
+        assert!(!encryption_supported());
+        assert!(SearchIndex::open_or_recreate_at(&tmp.path().join("outline.db"), &mode).is_err());
+    }
+
+    #[test]
+    fn test_optimize() {
+        let tmp = TempDir::new().unwrap();
+        let index = SearchIndex::open_at(&tmp.path().join("outline.db")).unwrap();
+        let nodes: Vec<Node> = (0..50).map(|i| Node::new(format!("Node {}", i))).collect();
+        index.index_document(&Uuid::now_v7(), &nodes).unwrap();
+        index.clear().unwrap();
+
+        let before = index.size().unwrap();
+        index.optimize().unwrap();
+        assert!(index.size().unwrap() <= before);
+        assert!(index.integrity_check().unwrap().is_empty());
+    }
+}
@@ -1,11 +1,30 @@
-use rusqlite::{params, Connection, Result as SqliteResult};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Result as SqliteResult};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use uuid::Uuid;
 
-use crate::data::{data_dir, Node};
+use crate::data::{data_dir, markdown_to_text, Node, NodeType};
+
+/// Content is indexed as plain text
+pub use crate::data::strip_html;
+
+mod aliases;
+mod filter;
+mod indexer;
+mod maintenance;
+mod suggest;
+mod switcher;
+
+pub use aliases::*;
+pub use filter::*;
+pub use indexer::*;
+pub use maintenance::*;
+pub use suggest::*;
+pub use switcher::*;
 
 /// Search result returned to the frontend
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,11 +35,77 @@ pub struct SearchResult {
     pub note: Option<String>,
     pub snippet: String,
     pub rank: f64,
+    /// Title of the result's document (its first root node)
+    pub document_title: Option<String>,
+    /// Name of the folder the document is in (filled in by the command layer)
+    #[serde(default)]
+    pub folder_name: Option<String>,
+}
+
+/// A failed search, in a form the UI can show. `Syntax` errors come from
+/// advanced (`fts:`) queries the user can fix.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchError {
+    pub kind: SearchErrorKind,
+    pub message: String,
 }
 
-/// Manages the SQLite FTS5 search index
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchErrorKind {
+    Syntax,
+    Internal,
+}
+
+impl From<String> for SearchError {
+    fn from(message: String) -> Self {
+        Self {
+            kind: SearchErrorKind::Internal,
+            message,
+        }
+    }
+}
+
+impl From<&str> for SearchError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+/// Search results from one document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResultGroup {
+    pub document_id: String,
+    pub document_title: Option<String>,
+    pub folder_name: Option<String>,
+    pub results: Vec<SearchResult>,
+}
+
+/// Group results by document, keeping documents in the order of their best result
+pub fn group_search_results(results: Vec<SearchResult>) -> Vec<SearchResultGroup> {
+    let mut groups: Vec<SearchResultGroup> = Vec::new();
+    for result in results {
+        match groups.iter_mut().find(|g| g.document_id == result.document_id) {
+            Some(group) => group.results.push(result),
+            None => groups.push(SearchResultGroup {
+                document_id: result.document_id.clone(),
+                document_title: result.document_title.clone(),
+                folder_name: result.folder_name.clone(),
+                results: vec![result],
+            }),
+        }
+    }
+    groups
+}
+
+/// How long a statement waits for another process's lock before failing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Manages the SQLite FTS5 search index. Clones share one connection, so
+/// commands and the background indexer never contend for the database.
+#[derive(Clone)]
 pub struct SearchIndex {
-    conn: Mutex<Connection>,
+    conn: Arc<Mutex<Connection>>,
 }
 
 impl SearchIndex {
@@ -29,22 +114,55 @@ impl SearchIndex {
     /// the data directory clean for syncing via Nextcloud/Dropbox/etc.
     fn db_path() -> PathBuf {
         dirs::cache_dir()
-            .unwrap_or_else(|| data_dir())
+            .unwrap_or_else(data_dir)
             .join("outline")
             .join("outline.db")
     }
 
     /// Open or create the search index database
     pub fn open() -> SqliteResult<Self> {
-        let db_path = Self::db_path();
+        Self::open_at(&Self::db_path())
+    }
+
+    /// Open an empty index that lives only in memory (for benchmarks)
+    pub fn open_in_memory() -> SqliteResult<Self> {
+        let conn = Connection::open_in_memory()?;
+        Self::init_schema(&conn)?;
+        Ok(Self::from_connection(conn))
+    }
+
+    fn open_at(db_path: &Path) -> SqliteResult<Self> {
+        Self::open_at_with_key(db_path, None)
+    }
 
+    /// Open the database at `db_path`, encrypted with `key` if one is given
+    fn open_at_with_key(db_path: &Path, key: Option<&str>) -> SqliteResult<Self> {
         // Ensure cache directory exists
         if let Some(parent) = db_path.parent() {
             fs::create_dir_all(parent).ok();
         }
 
-        let conn = Connection::open(&db_path)?;
+        let conn = Connection::open(db_path)?;
+        // The key must be set before anything reads the file
+        if let Some(key) = key {
+            apply_key(&conn, "key", key)?;
+        }
+        // WAL lets readers carry on while the index is being written
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        Self::init_schema(&conn)?;
+
+        Ok(Self::from_connection(conn))
+    }
 
+    fn from_connection(conn: Connection) -> Self {
+        Self {
+            conn: Arc::new(Mutex::new(conn)),
+        }
+    }
+
+    /// Create tables, triggers and indexes, migrating older databases
+    fn init_schema(conn: &Connection) -> SqliteResult<()> {
         // Create tables if they don't exist
         conn.execute_batch(
             r#"
@@ -57,6 +175,13 @@ impl SearchIndex {
                 content TEXT NOT NULL,
                 note TEXT,
                 tags TEXT,
+                priority INTEGER,
+                properties TEXT,
+                date TEXT,
+                remind_at TEXT,
+                heading_level INTEGER,
+                color TEXT,
+                is_checked INTEGER NOT NULL DEFAULT 0,
                 created_at TEXT,
                 updated_at TEXT
             );
@@ -103,6 +228,12 @@ impl SearchIndex {
 
             -- Index for finding backlinks
             CREATE INDEX IF NOT EXISTS idx_links_target ON links(target_node_id);
+
+            -- Document titles, so results can be shown without loading documents
+            CREATE TABLE IF NOT EXISTS documents (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL
+            );
             "#,
         )?;
 
@@ -116,13 +247,22 @@ impl SearchIndex {
             conn.execute("ALTER TABLE nodes ADD COLUMN depth INTEGER NOT NULL DEFAULT 0", [])?;
         }
 
-        Ok(Self {
-            conn: Mutex::new(conn),
-        })
+        add_column_if_missing(conn, "priority", "INTEGER")?;
+        add_column_if_missing(conn, "properties", "TEXT")?;
+        add_column_if_missing(conn, "date", "TEXT")?;
+        add_column_if_missing(conn, "is_checked", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(conn, "remind_at", "TEXT")?;
+        add_column_if_missing(conn, "heading_level", "INTEGER")?;
+        add_column_if_missing(conn, "color", "TEXT")?;
+
+        Self::init_suggest_schema(conn)?;
+
+        Ok(())
     }
 
     /// Index a document's nodes (replaces any existing entries for that document)
     pub fn index_document(&self, document_id: &Uuid, nodes: &[Node]) -> SqliteResult<()> {
+        let _timer = crate::perf::timer("search.index_document");
         let mut conn = self.conn.lock().unwrap();
         let doc_id_str = document_id.to_string();
 
@@ -163,16 +303,34 @@ impl SearchIndex {
 
         let mut depth_cache = std::collections::HashMap::new();
 
+        let title = nodes
+            .iter()
+            .filter(|n| n.parent_id.is_none())
+            .min_by(|a, b| a.position.total_cmp(&b.position))
+            .filter(|n| !n.encrypted)
+            .map(|n| strip_html(&n.content).trim().to_string())
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| "Untitled".to_string());
+        tx.execute(
+            "INSERT OR REPLACE INTO documents (id, title) VALUES (?, ?)",
+            params![doc_id_str, title],
+        )?;
+
         // Insert new entries
         {
             let mut stmt = tx.prepare(
                 r#"
-                INSERT INTO nodes (id, document_id, parent_id, depth, content, note, tags, created_at, updated_at)
-                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                INSERT INTO nodes (id, document_id, parent_id, depth, content, note, tags, priority, properties, date, remind_at, heading_level, color, is_checked, created_at, updated_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
                 "#,
             )?;
 
             for node in nodes {
+                // Dividers have no text to find, encrypted nodes none to show
+                if node.node_type == NodeType::Divider || node.encrypted {
+                    continue;
+                }
+
                 let tags_str = if node.tags.is_empty() {
                     None
                 } else {
@@ -187,8 +345,15 @@ impl SearchIndex {
                     node.parent_id.map(|id| id.to_string()),
                     depth,
                     strip_html(&node.content),
-                    node.note,
+                    node.note.as_deref().map(markdown_to_text),
                     tags_str,
+                    node.priority,
+                    properties_json(node),
+                    node.date,
+                    node.remind_at,
+                    indexed_heading_level(node),
+                    node.color,
+                    node.is_checked,
                     node.created_at.to_rfc3339(),
                     node.updated_at.to_rfc3339(),
                 ])?;
@@ -199,97 +364,114 @@ impl SearchIndex {
         Ok(())
     }
 
-    /// Search for nodes matching a query
+    /// Search for nodes matching a query. Filter terms like `priority:1` are
+    /// pulled out of the query; a query of only filters lists matching nodes.
+    /// A query starting with `fts:` is passed to FTS5 as-is.
     pub fn search(
         &self,
         query: &str,
         document_id: Option<&Uuid>,
         limit: usize,
+    ) -> Result<Vec<SearchResult>, SearchError> {
+        let parsed = parse_search_query(query);
+        self.run_search(&parsed, document_id, limit).map_err(|e| {
+            if parsed.advanced {
+                SearchError {
+                    kind: SearchErrorKind::Syntax,
+                    message: format!("Invalid search syntax: {}", e),
+                }
+            } else {
+                format!("Search error: {}", e).into()
+            }
+        })
+    }
+
+    fn run_search(
+        &self,
+        parsed: &ParsedQuery,
+        document_id: Option<&Uuid>,
+        limit: usize,
     ) -> SqliteResult<Vec<SearchResult>> {
         let conn = self.conn.lock().unwrap();
 
         // Escape query for FTS5 (wrap words in quotes for phrase matching)
-        let escaped_query = escape_fts_query(query);
+        let escaped_query = if parsed.advanced {
+            parsed.text.trim().to_string()
+        } else {
+            escape_fts_query(&parsed.text)
+        };
+        let use_fts = !escaped_query.is_empty();
 
-        let sql = if document_id.is_some() {
-            r#"
-            SELECT
-                n.id,
-                n.document_id,
-                n.content,
-                n.note,
-                snippet(nodes_fts, 2, '<mark>', '</mark>', '...', 32) as snippet,
-                bm25(nodes_fts) as rank
-            FROM nodes_fts
-            JOIN nodes n ON nodes_fts.id = n.id
-            WHERE nodes_fts MATCH ?
-            AND n.document_id = ?
-            ORDER BY n.depth ASC, rank ASC
-            LIMIT ?
-            "#
+        let mut conditions = SqlConditions::default();
+        if use_fts {
+            conditions.push("nodes_fts MATCH ?", vec![Box::new(escaped_query)]);
+        }
+        if let Some(doc_id) = document_id {
+            conditions.push("n.document_id = ?", vec![Box::new(doc_id.to_string())]);
+        }
+        if let Some(priority) = parsed.priority {
+            conditions.priority(priority);
+        }
+        if let Some(ref color) = parsed.color {
+            conditions.color(color);
+        }
+        for (key, value) in &parsed.properties {
+            conditions.property(key, value.as_deref());
+        }
+
+        // Nothing to search for
+        if !use_fts && !parsed.has_filters() {
+            return Ok(Vec::new());
+        }
+
+        let (columns, from) = if use_fts {
+            (
+                "snippet(nodes_fts, 2, '<mark>', '</mark>', '...', 32) as snippet, bm25(nodes_fts) as rank",
+                "nodes_fts JOIN nodes n ON nodes_fts.id = n.id",
+            )
         } else {
+            ("n.content as snippet, 0.0 as rank", "nodes n")
+        };
+
+        let sql = format!(
             r#"
-            SELECT
-                n.id,
-                n.document_id,
-                n.content,
-                n.note,
-                snippet(nodes_fts, 2, '<mark>', '</mark>', '...', 32) as snippet,
-                bm25(nodes_fts) as rank
-            FROM nodes_fts
-            JOIN nodes n ON nodes_fts.id = n.id
-            WHERE nodes_fts MATCH ?
+            SELECT n.id, n.document_id, n.content, n.note, {}, d.title
+            FROM {}
+            LEFT JOIN documents d ON d.id = n.document_id
+            WHERE {}
             ORDER BY n.depth ASC, rank ASC
             LIMIT ?
-            "#
-        };
-
-        let mut results = Vec::new();
-
-        if document_id.is_some() {
-            let doc_id_str = document_id.unwrap().to_string();
-            let mut stmt = conn.prepare(sql)?;
-            let rows = stmt.query_map(params![escaped_query, doc_id_str, limit as i64], |row| {
-                Ok(SearchResult {
-                    node_id: row.get(0)?,
-                    document_id: row.get(1)?,
-                    content: row.get(2)?,
-                    note: row.get(3)?,
-                    snippet: row.get(4)?,
-                    rank: row.get(5)?,
-                })
-            })?;
-
-            for result in rows {
-                if let Ok(r) = result {
-                    results.push(r);
-                }
-            }
-        } else {
-            let mut stmt = conn.prepare(sql)?;
-            let rows = stmt.query_map(params![escaped_query, limit as i64], |row| {
-                Ok(SearchResult {
-                    node_id: row.get(0)?,
-                    document_id: row.get(1)?,
-                    content: row.get(2)?,
-                    note: row.get(3)?,
-                    snippet: row.get(4)?,
-                    rank: row.get(5)?,
-                })
-            })?;
-
-            for result in rows {
-                if let Ok(r) = result {
-                    results.push(r);
-                }
-            }
-        }
+            "#,
+            columns,
+            from,
+            conditions.where_clause()
+        );
+        conditions.values.push(Box::new(limit as i64));
+
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(conditions.values.iter()), |row| {
+            Ok(SearchResult {
+                node_id: row.get(0)?,
+                document_id: row.get(1)?,
+                content: row.get(2)?,
+                note: row.get(3)?,
+                snippet: row.get(4)?,
+                rank: row.get(5)?,
+                document_title: row.get(6)?,
+                folder_name: None,
+            })
+        })?;
 
-        Ok(results)
+        // FTS5 reports query syntax errors while stepping, not when preparing
+        rows.collect()
     }
 
-    /// Update a single node in the index
-    pub fn update_node(&self, document_id: &Uuid, node: &Node) -> SqliteResult<()> {
+    /// Update a single node in the index (`depth` is its nesting level).
+    /// Encrypted nodes are taken out instead.
+    pub fn update_node(&self, document_id: &Uuid, node: &Node, depth: usize) -> SqliteResult<()> {
+        if node.encrypted {
+            return self.delete_node(&node.id);
+        }
         let conn = self.conn.lock().unwrap();
 
         let tags_str = if node.tags.is_empty() {
@@ -300,16 +482,24 @@ impl SearchIndex {
 
         conn.execute(
             r#"
-            INSERT OR REPLACE INTO nodes (id, document_id, parent_id, content, note, tags, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT OR REPLACE INTO nodes (id, document_id, parent_id, depth, content, note, tags, priority, properties, date, remind_at, heading_level, color, is_checked, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
             params![
                 node.id.to_string(),
                 document_id.to_string(),
                 node.parent_id.map(|id| id.to_string()),
+                depth as i64,
                 strip_html(&node.content),
-                node.note,
+                node.note.as_deref().map(markdown_to_text),
                 tags_str,
+                node.priority,
+                properties_json(node),
+                node.date,
+                node.remind_at,
+                indexed_heading_level(node),
+                node.color,
+                node.is_checked,
                 node.created_at.to_rfc3339(),
                 node.updated_at.to_rfc3339(),
             ],
@@ -318,18 +508,64 @@ impl SearchIndex {
         Ok(())
     }
 
-    /// Delete a node from the index
+    /// Delete a node (and the links from it) from the index
     pub fn delete_node(&self, node_id: &Uuid) -> SqliteResult<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute("DELETE FROM nodes WHERE id = ?", params![node_id.to_string()])?;
+        conn.execute("DELETE FROM links WHERE source_node_id = ?", params![node_id.to_string()])?;
         Ok(())
     }
 
+    /// Change an indexed document's title (documents not in the index, like
+    /// private ones, stay out)
+    pub fn set_document_title(&self, document_id: &Uuid, title: &str) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE documents SET title = ? WHERE id = ?",
+            params![title, document_id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// Ids of the indexed documents with exactly this title
+    pub fn documents_titled(&self, title: &str) -> SqliteResult<Vec<Uuid>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id FROM documents WHERE title = ?")?;
+        let ids = stmt.query_map(params![title], |row| row.get::<_, String>(0))?;
+        Ok(ids.flatten().filter_map(|id| Uuid::parse_str(&id).ok()).collect())
+    }
+
+    /// Delete the entries (nodes, links from them, title) of every indexed
+    /// document not in `keep`. Returns the ids of the documents removed.
+    pub fn remove_documents_except(&self, keep: &HashSet<Uuid>) -> SqliteResult<Vec<String>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let indexed: Vec<String> = {
+            let mut stmt = tx.prepare(
+                "SELECT document_id FROM nodes UNION SELECT source_document_id FROM links UNION SELECT id FROM documents",
+            )?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            rows.collect::<SqliteResult<_>>()?
+        };
+        let stale: Vec<String> = indexed
+            .into_iter()
+            .filter(|id| Uuid::parse_str(id).map_or(true, |uuid| !keep.contains(&uuid)))
+            .collect();
+        for id in &stale {
+            tx.execute("DELETE FROM nodes WHERE document_id = ?", params![id])?;
+            tx.execute("DELETE FROM links WHERE source_document_id = ?", params![id])?;
+            tx.execute("DELETE FROM documents WHERE id = ?", params![id])?;
+        }
+        tx.commit()?;
+        Ok(stale)
+    }
+
     /// Clear all data from the index
-    #[allow(dead_code)]
     pub fn clear(&self) -> SqliteResult<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute("DELETE FROM nodes", [])?;
+        conn.execute("DELETE FROM documents", [])?;
+        conn.execute("DELETE FROM links", [])?;
         Ok(())
     }
 
@@ -394,6 +630,16 @@ impl SearchIndex {
         Ok(())
     }
 
+    /// Record a link before the source document is next indexed
+    pub fn add_link(&self, source_node_id: &Uuid, target_node_id: &Uuid, source_document_id: &Uuid) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO links (source_node_id, target_node_id, source_document_id) VALUES (?, ?, ?)",
+            params![source_node_id.to_string(), target_node_id.to_string(), source_document_id.to_string()],
+        )?;
+        Ok(())
+    }
+
     /// Get backlinks (nodes that link to the given node)
     pub fn get_backlinks(&self, target_node_id: &Uuid) -> SqliteResult<Vec<BacklinkResult>> {
         let conn = self.conn.lock().unwrap();
@@ -401,9 +647,10 @@ impl SearchIndex {
 
         let mut stmt = conn.prepare(
             r#"
-            SELECT l.source_node_id, l.source_document_id, n.content
+            SELECT l.source_node_id, l.source_document_id, n.content, d.title
             FROM links l
             LEFT JOIN nodes n ON l.source_node_id = n.id
+            LEFT JOIN documents d ON l.source_document_id = d.id
             WHERE l.target_node_id = ?
             "#,
         )?;
@@ -413,30 +660,115 @@ impl SearchIndex {
                 source_node_id: row.get(0)?,
                 source_document_id: row.get(1)?,
                 content: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                document_title: row.get(3)?,
+                breadcrumbs: Vec::new(),
             })
         })?;
 
-        let mut results = Vec::new();
-        for result in rows {
-            if let Ok(r) = result {
-                results.push(r);
-            }
-        }
+        Ok(rows.flatten().collect())
+    }
+
+    /// Nodes in other documents that link to any node of `document_id`,
+    /// grouped by source document title, in outline order within each
+    pub fn linked_references(&self, document_id: &Uuid) -> SqliteResult<Vec<LinkedReference>> {
+        let conn = self.conn.lock().unwrap();
+        let doc_id_str = document_id.to_string();
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT DISTINCT l.source_node_id, l.source_document_id, IFNULL(d.title, 'Untitled'), s.content, s.rowid
+            FROM links l
+            JOIN nodes t ON t.id = l.target_node_id
+            JOIN nodes s ON s.id = l.source_node_id
+            LEFT JOIN documents d ON d.id = l.source_document_id
+            WHERE t.document_id = ?1 AND l.source_document_id != ?1
+            ORDER BY 3, 2, s.rowid
+            "#,
+        )?;
+        let rows = stmt.query_map(params![doc_id_str], |row| {
+            Ok(LinkedReference {
+                source_node_id: row.get(0)?,
+                source_document_id: row.get(1)?,
+                source_title: row.get(2)?,
+                content: row.get(3)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Get the document a node was indexed under
+    pub fn document_for_node(&self, node_id: &Uuid) -> SqliteResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT document_id FROM nodes WHERE id = ?",
+            params![node_id.to_string()],
+            |row| row.get(0),
+        )
+        .optional()
+    }
 
-        Ok(results)
+    /// Node and overdue-task counts per document. A task is overdue when it
+    /// is unchecked and its date is before `today` (YYYY-MM-DD).
+    pub fn document_stats(&self, today: &str) -> SqliteResult<HashMap<String, DocumentStats>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT document_id,
+                   COUNT(*),
+                   SUM(CASE WHEN is_checked = 0 AND date IS NOT NULL AND date != ''
+                            AND substr(date, 1, 10) < ? THEN 1 ELSE 0 END)
+            FROM nodes
+            GROUP BY document_id
+            "#,
+        )?;
+
+        let rows = stmt.query_map(params![today], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                DocumentStats {
+                    node_count: row.get::<_, i64>(1)? as usize,
+                    overdue_count: row.get::<_, i64>(2)? as usize,
+                },
+            ))
+        })?;
+
+        Ok(rows.flatten().collect())
     }
 }
 
+/// Per-document counts from the search cache
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentStats {
+    pub node_count: usize,
+    pub overdue_count: usize,
+}
+
 /// Backlink result returned to the frontend
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BacklinkResult {
     pub source_node_id: String,
     pub source_document_id: String,
     pub content: String,
+    /// Title of the linking document
+    #[serde(default)]
+    pub document_title: Option<String>,
+    /// Ancestor texts from the top level down to the linking node's parent
+    /// (filled in from the document, not the index)
+    #[serde(default)]
+    pub breadcrumbs: Vec<String>,
+}
+
+/// A node in another document linking into an exported document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkedReference {
+    pub source_node_id: String,
+    pub source_document_id: String,
+    pub source_title: String,
+    /// Plain text of the linking node
+    pub content: String,
 }
 
 /// Extract wiki-link target IDs from HTML content
-fn extract_wiki_links(html: &str) -> Vec<String> {
+pub fn extract_wiki_links(html: &str) -> Vec<String> {
     let mut links = Vec::new();
 
     // Look for data-node-id attributes in wiki-link spans
@@ -468,48 +800,138 @@ fn extract_wiki_links(html: &str) -> Vec<String> {
     links
 }
 
-/// Strip HTML tags from content for indexing
-fn strip_html(html: &str) -> String {
-    let mut result = String::with_capacity(html.len());
-    let mut in_tag = false;
-
-    for c in html.chars() {
-        match c {
-            '<' => in_tag = true,
-            '>' => in_tag = false,
-            _ if !in_tag => result.push(c),
-            _ => {}
+/// A search query split into free text and structured filters
+#[derive(Debug, Default, PartialEq)]
+pub struct ParsedQuery {
+    /// Remaining free text for full-text matching
+    pub text: String,
+    /// `priority:N`
+    pub priority: Option<u8>,
+    /// `color:name` (lowercased)
+    pub color: Option<String>,
+    /// `prop:key=value` (or `prop:key` for any value)
+    pub properties: Vec<(String, Option<String>)>,
+    /// `fts:` prefix: the text is raw FTS5 syntax (NEAR, OR, column filters...)
+    pub advanced: bool,
+}
+
+impl ParsedQuery {
+    /// Whether any structured filter is set
+    pub fn has_filters(&self) -> bool {
+        self.priority.is_some() || self.color.is_some() || !self.properties.is_empty()
+    }
+}
+
+/// Pull filter terms (e.g. `priority:1`, `color:red`, `prop:status=waiting`) out of a search query. Unrecognized
+/// or malformed filters are left in the text. A query starting with `fts:` is advanced syntax and
+/// is kept whole.
+pub fn parse_search_query(query: &str) -> ParsedQuery {
+    if let Some(raw) = query.trim_start().strip_prefix("fts:") {
+        return ParsedQuery {
+            text: raw.trim().to_string(),
+            advanced: true,
+            ..Default::default()
+        };
+    }
+
+    let mut parsed = ParsedQuery::default();
+    let mut text_terms = Vec::new();
+
+    for term in query.split_whitespace() {
+        if let Some(value) = term.strip_prefix("priority:") {
+            if let Ok(priority) = value.parse::<u8>() {
+                parsed.priority = Some(priority);
+                continue;
+            }
+        }
+        if let Some(color) = term.strip_prefix("color:").filter(|c| !c.is_empty()) {
+            parsed.color = Some(color.to_lowercase());
+            continue;
         }
+        if let Some(prop) = term.strip_prefix("prop:").filter(|p| !p.is_empty()) {
+            let (key, value) = match prop.split_once('=') {
+                Some((key, value)) => (key, Some(value.to_string())),
+                None => (prop, None),
+            };
+            if !key.is_empty() {
+                parsed.properties.push((key.to_string(), value));
+                continue;
+            }
+        }
+        text_terms.push(term);
     }
 
-    // Decode common HTML entities
-    result
-        .replace("&nbsp;", " ")
-        .replace("&amp;", "&")
-        .replace("&lt;", "<")
-        .replace("&gt;", ">")
-        .replace("&quot;", "\"")
+    parsed.text = text_terms.join(" ");
+    parsed
+}
+
+/// Level of a heading node (1 if unset), for the quick switcher
+fn indexed_heading_level(node: &Node) -> Option<u8> {
+    (node.node_type == NodeType::Heading).then(|| node.heading_level.unwrap_or(1))
 }
 
-/// Escape a query string for FTS5 matching
+/// Add a column to the nodes table if an older database lacks it
+/// (SQLite doesn't have ALTER TABLE ADD COLUMN IF NOT EXISTS)
+fn add_column_if_missing(conn: &Connection, column: &str, definition: &str) -> SqliteResult<()> {
+    let exists = conn
+        .prepare(&format!("SELECT {} FROM nodes LIMIT 1", column))
+        .is_ok();
+    if !exists {
+        conn.execute(&format!("ALTER TABLE nodes ADD COLUMN {} {}", column, definition), [])?;
+    }
+    Ok(())
+}
+
+/// Serialize a node's properties for the index (NULL when there are none)
+fn properties_json(node: &Node) -> Option<String> {
+    if node.properties.is_empty() {
+        None
+    } else {
+        serde_json::to_string(&node.properties).ok()
+    }
+}
+
+/// Turn user text into a safe FTS5 query. Every word becomes a quoted prefix
+/// match and "quoted phrases" match exactly, so FTS5 operators and punctuation
+/// (`-`, `:`, `(`, `NEAR`, `OR`...) are searched for literally instead of being
+/// parsed. Terms without any letters or digits are dropped since the tokenizer
+/// would index nothing for them.
 fn escape_fts_query(query: &str) -> String {
-    // If query contains special FTS5 characters, wrap terms in quotes
-    // Otherwise, use prefix matching with *
-    let terms: Vec<&str> = query.split_whitespace().collect();
+    let mut terms = Vec::new();
+    let mut rest = query;
 
-    if terms.is_empty() {
-        return String::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+
+        let (term, phrase) = if let Some(quoted) = rest.strip_prefix('"') {
+            // An unterminated quote runs to the end of the query
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            let term = &quoted[..end];
+            rest = quoted.get(end + 1..).unwrap_or("");
+            (term, true)
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let term = &rest[..end];
+            rest = &rest[end..];
+            (term, false)
+        };
+
+        if !term.chars().any(char::is_alphanumeric) {
+            continue;
+        }
+        let escaped = term.replace('"', "\"\"");
+        if phrase {
+            terms.push(format!("\"{}\"", escaped));
+        } else {
+            // Prefix wildcard for partial matching
+            terms.push(format!("\"{}\"*", escaped));
+        }
     }
 
-    terms
-        .iter()
-        .map(|term| {
-            // Escape quotes and add prefix wildcard for partial matching
-            let escaped = term.replace('"', "\"\"");
-            format!("\"{}\"*", escaped)
-        })
-        .collect::<Vec<_>>()
-        .join(" ")
+    terms.join(" ")
 }
 
 #[cfg(test)]
@@ -523,57 +945,51 @@ mod tests {
         let db_path = tmp.path().join("test.db");
 
         let conn = Connection::open(&db_path).unwrap();
-        conn.execute_batch(
-            r#"
-            CREATE TABLE IF NOT EXISTS nodes (
-                id TEXT PRIMARY KEY,
-                document_id TEXT NOT NULL,
-                parent_id TEXT,
-                depth INTEGER NOT NULL DEFAULT 0,
-                content TEXT NOT NULL,
-                note TEXT,
-                tags TEXT,
-                created_at TEXT,
-                updated_at TEXT
-            );
-
-            CREATE VIRTUAL TABLE IF NOT EXISTS nodes_fts USING fts5(
-                id,
-                document_id,
-                content,
-                note,
-                tags,
-                content='nodes',
-                content_rowid='rowid'
-            );
-
-            CREATE TRIGGER IF NOT EXISTS nodes_ai AFTER INSERT ON nodes BEGIN
-                INSERT INTO nodes_fts(rowid, id, document_id, content, note, tags)
-                VALUES (new.rowid, new.id, new.document_id, new.content, new.note, new.tags);
-            END;
+        SearchIndex::init_schema(&conn).unwrap();
 
-            CREATE TRIGGER IF NOT EXISTS nodes_ad AFTER DELETE ON nodes BEGIN
-                INSERT INTO nodes_fts(nodes_fts, rowid, id, document_id, content, note, tags)
-                VALUES ('delete', old.rowid, old.id, old.document_id, old.content, old.note, old.tags);
-            END;
-
-            CREATE TRIGGER IF NOT EXISTS nodes_au AFTER UPDATE ON nodes BEGIN
-                INSERT INTO nodes_fts(nodes_fts, rowid, id, document_id, content, note, tags)
-                VALUES ('delete', old.rowid, old.id, old.document_id, old.content, old.note, old.tags);
-                INSERT INTO nodes_fts(rowid, id, document_id, content, note, tags)
-                VALUES (new.rowid, new.id, new.document_id, new.content, new.note, new.tags);
-            END;
+        let index = SearchIndex::from_connection(conn);
 
-            CREATE INDEX IF NOT EXISTS idx_nodes_document ON nodes(document_id);
-            "#,
-        )
-        .unwrap();
+        (tmp, index)
+    }
 
-        let index = SearchIndex {
-            conn: Mutex::new(conn),
-        };
+    #[test]
+    fn test_clones_share_connection_in_wal_mode() {
+        let tmp = TempDir::new().unwrap();
+        let index = SearchIndex::open_at(&tmp.path().join("outline.db")).unwrap();
+        let mode: String = index
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+
+        let doc_id = Uuid::now_v7();
+        index.clone().index_document(&doc_id, &[Node::new("Shared".to_string())]).unwrap();
+        assert_eq!(index.search("Shared", None, 10).unwrap().len(), 1);
+    }
 
-        (tmp, index)
+    #[test]
+    fn test_document_stats() {
+        let (_tmp, index) = setup_test_index();
+        let doc_id = Uuid::now_v7();
+
+        let mut late = Node::new("Late".to_string());
+        late.date = Some("2024-01-10".to_string());
+        let mut done = Node::new("Done".to_string());
+        done.date = Some("2024-01-10".to_string());
+        done.is_checked = true;
+        let mut upcoming = Node::new("Upcoming".to_string());
+        upcoming.date = Some("2024-02-01".to_string());
+        index
+            .index_document(&doc_id, &[late, done, upcoming, Node::new("Plain".to_string())])
+            .unwrap();
+
+        let stats = index.document_stats("2024-01-15").unwrap();
+        assert_eq!(
+            stats[&doc_id.to_string()],
+            DocumentStats { node_count: 4, overdue_count: 1 }
+        );
     }
 
     #[test]
@@ -589,6 +1005,34 @@ mod tests {
         assert_eq!(escape_fts_query("hello"), "\"hello\"*");
         assert_eq!(escape_fts_query("hello world"), "\"hello\"* \"world\"*");
         assert_eq!(escape_fts_query("test\"quote"), "\"test\"\"quote\"*");
+        assert_eq!(escape_fts_query("\"exact phrase\" next"), "\"exact phrase\" \"next\"*");
+        assert_eq!(escape_fts_query("a - ( ) b"), "\"a\"* \"b\"*");
+    }
+
+    #[test]
+    fn test_search_with_fts_syntax_characters() {
+        let (_tmp, index) = setup_test_index();
+        let doc_id = Uuid::new_v4();
+        index
+            .index_document(
+                &doc_id,
+                &[
+                    Node::new("Follow-up: call (NEAR) office".to_string()),
+                    Node::new("Office supplies".to_string()),
+                ],
+            )
+            .unwrap();
+
+        for query in ["follow-up:", "(near)", "NEAR office", "call OR", "-", "office AND"] {
+            assert!(index.search(query, None, 10).is_ok(), "query {:?}", query);
+        }
+        assert_eq!(index.search("follow-up", None, 10).unwrap().len(), 1);
+        assert_eq!(index.search("\"call near\"", None, 10).unwrap().len(), 1);
+
+        // Advanced syntax is passed through, and mistakes come back as syntax errors
+        assert_eq!(index.search("fts: office NOT call", None, 10).unwrap().len(), 1);
+        let err = index.search("fts: office AND (", None, 10).unwrap_err();
+        assert_eq!(err.kind, SearchErrorKind::Syntax);
     }
 
     #[test]
@@ -639,4 +1083,203 @@ mod tests {
         assert_eq!(results.len(), 1);
         assert!(results[0].content.contains("pie"));
     }
+
+    #[test]
+    fn test_remove_documents_except() {
+        let (_tmp, index) = setup_test_index();
+        let kept = Uuid::new_v4();
+        let deleted = Uuid::new_v4();
+
+        let target = Node::new("Apple pie recipe".to_string());
+        let mut linking = Node::new("Apple cider".to_string());
+        linking.content = format!("Apple cider, see <span data-node-id=\"{}\">pie</span>", target.id);
+        index.index_document(&kept, std::slice::from_ref(&target)).unwrap();
+        index.index_document(&deleted, &[linking.clone()]).unwrap();
+        index.update_document_links(&deleted, &[linking]).unwrap();
+        assert_eq!(index.get_backlinks(&target.id).unwrap().len(), 1);
+
+        let removed = index.remove_documents_except(&HashSet::from([kept])).unwrap();
+        assert_eq!(removed, vec![deleted.to_string()]);
+        let results = index.search("apple", None, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].document_id, kept.to_string());
+        assert!(index.get_backlinks(&target.id).unwrap().is_empty());
+
+        // Nothing left to remove
+        assert!(index.remove_documents_except(&HashSet::from([kept])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_linked_references() {
+        let (_tmp, index) = setup_test_index();
+        let project = Uuid::new_v4();
+        let journal = Uuid::new_v4();
+
+        let title = Node::new("Project".to_string());
+        let goal = Node::new_child(title.id, 0.0, "Goal".to_string());
+        let link = |id: Uuid| format!("<span data-node-id=\"{}\">link</span>", id);
+        let mut self_link = Node::new_child(title.id, 1.0, String::new());
+        self_link.content = format!("See {}", link(goal.id));
+        let project_nodes = [title.clone(), goal.clone(), self_link];
+
+        let day = Node::new("Monday".to_string());
+        let mut mention = Node::new_child(day.id, 0.0, String::new());
+        mention.content = format!("Worked on {} and {}", link(title.id), link(goal.id));
+        let journal_nodes = [day, mention.clone()];
+
+        for (id, nodes) in [(project, &project_nodes[..]), (journal, &journal_nodes[..])] {
+            index.index_document(&id, nodes).unwrap();
+            index.update_document_links(&id, nodes).unwrap();
+        }
+
+        // Links from the document itself don't count, and a node linking
+        // twice is listed once
+        let refs = index.linked_references(&project).unwrap();
+        assert_eq!(refs.len(), 1);
+        assert_eq!(refs[0].source_node_id, mention.id.to_string());
+        assert_eq!(refs[0].source_title, "Monday");
+        assert_eq!(refs[0].content, "Worked on link and link");
+        assert!(index.linked_references(&journal).unwrap().is_empty());
+
+        // Backlinks carry the linking document's title
+        let backlinks = index.get_backlinks(&goal.id).unwrap();
+        assert_eq!(backlinks.len(), 2);
+        let from_journal = backlinks.iter().find(|b| b.source_node_id == mention.id.to_string()).unwrap();
+        assert_eq!(from_journal.document_title.as_deref(), Some("Monday"));
+    }
+
+    #[test]
+    fn test_search_results_grouped_with_titles() {
+        let (_tmp, index) = setup_test_index();
+        let recipes = Uuid::new_v4();
+        let groceries = Uuid::new_v4();
+
+        let title = Node::new("<b>Recipes</b>".to_string());
+        let pie = Node::new_child(title.id, 0.0, "Apple pie".to_string());
+        let cake = Node::new_child(title.id, 1.0, "Apple cake".to_string());
+        index.index_document(&recipes, &[title, pie, cake]).unwrap();
+        index
+            .index_document(&groceries, &[Node::new("Apples".to_string())])
+            .unwrap();
+
+        let results = index.search("apple", None, 10).unwrap();
+        assert_eq!(results.len(), 3);
+
+        let groups = group_search_results(results);
+        assert_eq!(groups.len(), 2);
+        let recipe_group = groups
+            .iter()
+            .find(|g| g.document_id == recipes.to_string())
+            .unwrap();
+        assert_eq!(recipe_group.document_title.as_deref(), Some("Recipes"));
+        assert_eq!(recipe_group.results.len(), 2);
+
+        index.set_document_title(&recipes, "Baking").unwrap();
+        // Documents that aren't indexed don't get a row
+        index.set_document_title(&Uuid::new_v4(), "Private").unwrap();
+        let groups = group_search_results(index.search("apple", None, 10).unwrap());
+        let recipe_group = groups
+            .iter()
+            .find(|g| g.document_id == recipes.to_string())
+            .unwrap();
+        assert_eq!(recipe_group.document_title.as_deref(), Some("Baking"));
+        let count: i64 = index
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM documents", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(index.documents_titled("Baking").unwrap(), vec![recipes]);
+        assert!(index.documents_titled("Recipes").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_search_query() {
+        assert_eq!(
+            parse_search_query("call mom priority:1"),
+            ParsedQuery {
+                text: "call mom".to_string(),
+                priority: Some(1),
+                color: None,
+                properties: Vec::new(),
+                advanced: false,
+            }
+        );
+        // Malformed filters stay in the text
+        assert_eq!(parse_search_query("priority:high").text, "priority:high");
+        // Advanced queries are kept whole
+        let advanced = parse_search_query("fts: rent NEAR(pay priority:1)");
+        assert!(advanced.advanced);
+        assert_eq!(advanced.text, "rent NEAR(pay priority:1)");
+    }
+
+    #[test]
+    fn test_search_priority_filter() {
+        let (_tmp, index) = setup_test_index();
+        let doc_id = Uuid::new_v4();
+
+        let mut urgent = Node::new("Pay rent".to_string());
+        urgent.priority = Some(1);
+        let mut later = Node::new("Pay it forward".to_string());
+        later.priority = Some(3);
+        index.index_document(&doc_id, &[urgent, later, Node::new("Unrelated".to_string())]).unwrap();
+
+        let results = index.search("pay priority:1", None, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].content, "Pay rent");
+
+        // Filter-only query lists every match
+        let results = index.search("priority:3", None, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].content, "Pay it forward");
+
+        assert!(index.search("", None, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_color_filter() {
+        let (_tmp, index) = setup_test_index();
+        let doc_id = Uuid::new_v4();
+
+        let mut red = Node::new("Urgent call".to_string());
+        red.color = Some("red".to_string());
+        let mut blue = Node::new("Call later".to_string());
+        blue.color = Some("blue".to_string());
+        index.index_document(&doc_id, &[red, blue.clone(), Node::new("Call nobody".to_string())]).unwrap();
+
+        let results = index.search("call color:Red", None, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].content, "Urgent call");
+
+        // Recoloring reaches the index through node updates
+        blue.color = Some("red".to_string());
+        index.update_node(&doc_id, &blue, 0).unwrap();
+        assert_eq!(index.search("color:red", None, 10).unwrap().len(), 2);
+        assert!(index.search("color:blue", None, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_property_filter() {
+        let (_tmp, index) = setup_test_index();
+        let doc_id = Uuid::new_v4();
+
+        let mut waiting = Node::new("Order parts".to_string());
+        waiting.properties.insert("status".to_string(), "waiting".to_string());
+        let mut done = Node::new("Order lunch".to_string());
+        done.properties.insert("status".to_string(), "done".to_string());
+        index.index_document(&doc_id, &[waiting, done, Node::new("Order nothing".to_string())]).unwrap();
+
+        let results = index.search("prop:status=waiting", None, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].content, "Order parts");
+
+        let results = index.search("order prop:status", None, 10).unwrap();
+        assert_eq!(results.len(), 2);
+
+        assert_eq!(
+            parse_search_query("prop:a=b=c prop: x").properties,
+            vec![("a".to_string(), Some("b=c".to_string()))]
+        );
+    }
 }
@@ -0,0 +1,193 @@
+use chrono::Utc;
+use rusqlite::{params, Connection, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use super::SearchIndex;
+
+/// What a suggestion completes to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionKind {
+    Tag,
+    Document,
+    RecentSearch,
+}
+
+/// A completion for the search box
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub kind: SuggestionKind,
+    pub text: String,
+    /// Set for document suggestions
+    pub document_id: Option<String>,
+}
+
+/// Suggestions returned per kind
+const SUGGESTIONS_PER_KIND: usize = 5;
+/// Recent search terms kept in the cache
+const MAX_SEARCH_HISTORY: usize = 50;
+
+/// Escape LIKE wildcards so the prefix matches literally (with ESCAPE '\')
+fn like_prefix(prefix: &str) -> String {
+    let escaped = prefix
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("{}%", escaped)
+}
+
+impl SearchIndex {
+    pub(super) fn init_suggest_schema(conn: &Connection) -> SqliteResult<()> {
+        conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS search_history (
+                term TEXT PRIMARY KEY,
+                searched_at TEXT NOT NULL
+            );
+            "#,
+        )
+    }
+
+    /// Remember a search term for suggestions, keeping the most recent few
+    pub fn record_search(&self, term: &str) -> SqliteResult<()> {
+        let term = term.trim();
+        if term.is_empty() {
+            return Ok(());
+        }
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO search_history (term, searched_at) VALUES (?, ?)",
+            params![term, Utc::now().to_rfc3339()],
+        )?;
+        conn.execute(
+            r#"
+            DELETE FROM search_history WHERE term NOT IN (
+                SELECT term FROM search_history ORDER BY searched_at DESC LIMIT ?
+            )
+            "#,
+            params![MAX_SEARCH_HISTORY as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Tags, document titles and recent searches starting with `prefix`
+    /// (case-insensitive). A leading `#` only suggests tags.
+    pub fn suggest(&self, prefix: &str) -> SqliteResult<Vec<Suggestion>> {
+        let conn = self.conn.lock().unwrap();
+        let tags_only = prefix.starts_with('#');
+        let prefix = prefix.trim_start_matches('#').trim();
+        let lower = prefix.to_lowercase();
+        let mut suggestions = Vec::new();
+
+        // Tags are stored space-separated, so split them out here
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT DISTINCT tags FROM nodes
+            WHERE tags IS NOT NULL AND (' ' || lower(tags)) LIKE ? ESCAPE '\'
+            "#,
+        )?;
+        let rows = stmt.query_map(params![format!("% {}", like_prefix(&lower))], |row| {
+            row.get::<_, String>(0)
+        })?;
+        let mut tags = BTreeSet::new();
+        for tag_list in rows.flatten() {
+            for tag in tag_list.split_whitespace() {
+                if tag.to_lowercase().starts_with(&lower) {
+                    tags.insert(tag.to_string());
+                }
+            }
+        }
+        suggestions.extend(tags.into_iter().take(SUGGESTIONS_PER_KIND).map(|tag| Suggestion {
+            kind: SuggestionKind::Tag,
+            text: format!("#{}", tag),
+            document_id: None,
+        }));
+
+        if tags_only {
+            return Ok(suggestions);
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT id, title FROM documents WHERE title LIKE ? ESCAPE '\\' ORDER BY title LIMIT ?",
+        )?;
+        let rows = stmt.query_map(params![like_prefix(prefix), SUGGESTIONS_PER_KIND as i64], |row| {
+            Ok(Suggestion {
+                kind: SuggestionKind::Document,
+                text: row.get(1)?,
+                document_id: Some(row.get(0)?),
+            })
+        })?;
+        suggestions.extend(rows.flatten());
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT term FROM search_history
+            WHERE term LIKE ? ESCAPE '\'
+            ORDER BY searched_at DESC
+            LIMIT ?
+            "#,
+        )?;
+        let rows = stmt.query_map(params![like_prefix(prefix), SUGGESTIONS_PER_KIND as i64], |row| {
+            Ok(Suggestion {
+                kind: SuggestionKind::RecentSearch,
+                text: row.get(0)?,
+                document_id: None,
+            })
+        })?;
+        suggestions.extend(rows.flatten());
+
+        Ok(suggestions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Node;
+    use uuid::Uuid;
+
+    fn setup_index() -> SearchIndex {
+        let conn = Connection::open_in_memory().unwrap();
+        SearchIndex::init_schema(&conn).unwrap();
+        SearchIndex::from_connection(conn)
+    }
+
+    fn texts(suggestions: &[Suggestion], kind: SuggestionKind) -> Vec<&str> {
+        suggestions
+            .iter()
+            .filter(|s| s.kind == kind)
+            .map(|s| s.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_suggest() {
+        let index = setup_index();
+        let mut title = Node::new("Project plan".to_string());
+        title.tags = vec!["planning".to_string(), "work".to_string()];
+        let mut task = Node::new_child(title.id, 0.0, "Draft".to_string());
+        task.tags = vec!["Priority".to_string(), "personal".to_string()];
+        index.index_document(&Uuid::now_v7(), &[title, task]).unwrap();
+
+        index.record_search("pie recipe").unwrap();
+        index.record_search("  ").unwrap();
+        index.record_search("groceries").unwrap();
+
+        let suggestions = index.suggest("p").unwrap();
+        assert_eq!(
+            texts(&suggestions, SuggestionKind::Tag),
+            vec!["#Priority", "#personal", "#planning"]
+        );
+        assert_eq!(texts(&suggestions, SuggestionKind::Document), vec!["Project plan"]);
+        assert_eq!(texts(&suggestions, SuggestionKind::RecentSearch), vec!["pie recipe"]);
+
+        // A leading # only completes tags
+        let suggestions = index.suggest("#pl").unwrap();
+        assert_eq!(texts(&suggestions, SuggestionKind::Tag), vec!["#planning"]);
+        assert_eq!(suggestions.len(), 1);
+
+        // LIKE wildcards are literal
+        assert!(index.suggest("%").unwrap().is_empty());
+    }
+}
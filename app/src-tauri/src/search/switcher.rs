@@ -0,0 +1,229 @@
+//! Quick switcher (Ctrl+P): documents, headings and bookmarked nodes across
+//! the workspace, matched fuzzily against their titles from the search cache.
+
+use rusqlite::{params_from_iter, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use super::SearchIndex;
+
+/// What a quick-switcher result opens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwitchKind {
+    Document,
+    Heading,
+    /// A bookmarked node that isn't a heading
+    Node,
+}
+
+/// A quick-switcher (Ctrl+P) result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwitchCandidate {
+    pub kind: SwitchKind,
+    pub document_id: String,
+    /// Unset for documents
+    pub node_id: Option<String>,
+    pub text: String,
+    /// Title of the node's document (unset for documents)
+    pub document_title: Option<String>,
+    pub bookmarked: bool,
+    pub score: i64,
+}
+
+/// Score how well `query` (lowercase) fuzzily matches `text`: every query
+/// character must appear in order. Consecutive matches, matches at the start
+/// of words and prefix matches score higher; gaps and long texts lower.
+pub fn fuzzy_score(query: &[char], text: &str) -> Option<i64> {
+    if query.is_empty() {
+        return Some(0);
+    }
+    let chars: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut last_match: Option<usize> = None;
+    for (i, c) in chars.iter().enumerate() {
+        if next == query.len() {
+            break;
+        }
+        if *c != query[next] {
+            continue;
+        }
+        score += 16;
+        match last_match {
+            Some(last) if last + 1 == i => score += 8,
+            Some(last) => score -= (i - last - 1).min(8) as i64,
+            None => score -= i.min(8) as i64,
+        }
+        if i == 0 || !chars[i - 1].is_alphanumeric() {
+            score += 12;
+        }
+        last_match = Some(i);
+        next += 1;
+    }
+    if next < query.len() {
+        return None;
+    }
+    if chars.starts_with(query) {
+        score += 20;
+        if chars.len() == query.len() {
+            score += 30;
+        }
+    }
+    Some(score - (chars.len() / 8) as i64)
+}
+
+/// Small preference among equally good matches
+fn kind_bonus(kind: SwitchKind, bookmarked: bool) -> i64 {
+    let kind = match kind {
+        SwitchKind::Document => 6,
+        SwitchKind::Heading => 3,
+        SwitchKind::Node => 0,
+    };
+    kind + if bookmarked { 4 } else { 0 }
+}
+
+impl SearchIndex {
+    /// Documents, headings and bookmarked nodes fuzzily matching `query`,
+    /// best first. An empty query lists bookmarks and documents.
+    /// `bookmarks` are the bookmarked node ids.
+    pub fn quick_switch_candidates(
+        &self,
+        query: &str,
+        bookmarks: &[String],
+        limit: usize,
+    ) -> SqliteResult<Vec<SwitchCandidate>> {
+        let conn = self.conn.lock().unwrap();
+        let query: Vec<char> = query.trim().to_lowercase().chars().collect();
+        let bookmarked: HashSet<&str> = bookmarks.iter().map(String::as_str).collect();
+        let mut candidates = Vec::new();
+        let mut push = |kind: SwitchKind, document_id: String, node_id: Option<String>, text: String, document_title: Option<String>| {
+            let is_bookmarked = node_id.as_deref().is_some_and(|id| bookmarked.contains(id));
+            if query.is_empty() && kind == SwitchKind::Heading && !is_bookmarked {
+                return;
+            }
+            if let Some(score) = fuzzy_score(&query, &text) {
+                candidates.push(SwitchCandidate {
+                    score: score + kind_bonus(kind, is_bookmarked),
+                    kind,
+                    document_id,
+                    node_id,
+                    text,
+                    document_title,
+                    bookmarked: is_bookmarked,
+                });
+            }
+        };
+
+        // Documents with indexed nodes (private ones have none)
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT d.id, d.title FROM documents d
+            WHERE EXISTS (SELECT 1 FROM nodes n WHERE n.document_id = d.id)
+            "#,
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        for row in rows {
+            let (id, title) = row?;
+            push(SwitchKind::Document, id, None, title, None);
+        }
+
+        // Headings, and bookmarked nodes of any type
+        let placeholders = vec!["?"; bookmarked.len()].join(", ");
+        let sql = format!(
+            r#"
+            SELECT n.id, n.document_id, n.content, n.heading_level, d.title
+            FROM nodes n
+            LEFT JOIN documents d ON d.id = n.document_id
+            WHERE (n.heading_level IS NOT NULL AND n.depth > 0) OR n.id IN ({})
+            "#,
+            placeholders
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(bookmarked.iter()), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<i64>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        })?;
+        for row in rows {
+            let (id, document_id, content, heading_level, title) = row?;
+            let text = content.trim().to_string();
+            if text.is_empty() {
+                continue;
+            }
+            let kind = if heading_level.is_some() { SwitchKind::Heading } else { SwitchKind::Node };
+            push(kind, document_id, Some(id), text, title);
+        }
+
+        candidates.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.text.cmp(&b.text)));
+        candidates.truncate(limit);
+        Ok(candidates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Node, NodeType};
+    use rusqlite::Connection;
+    use uuid::Uuid;
+
+    fn setup_index() -> SearchIndex {
+        let conn = Connection::open_in_memory().unwrap();
+        SearchIndex::init_schema(&conn).unwrap();
+        SearchIndex::from_connection(conn)
+    }
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        let q = chars("mtg");
+        assert!(fuzzy_score(&q, "Meeting").is_some());
+        assert!(fuzzy_score(&q, "Team").is_none());
+        // Word starts beat scattered letters, and prefixes beat both
+        assert!(fuzzy_score(&q, "My Team Goals") > fuzzy_score(&q, "amortgage"));
+        assert!(fuzzy_score(&chars("pro"), "Projects") > fuzzy_score(&chars("pro"), "My projects"));
+    }
+
+    #[test]
+    fn test_quick_switch_candidates() {
+        let index = setup_index();
+        let doc = Uuid::now_v7();
+        let title = Node::new("Project plan".to_string());
+        let mut heading = Node::new_child(title.id, 0.0, "Prototype".to_string());
+        heading.node_type = NodeType::Heading;
+        heading.heading_level = Some(2);
+        let task = Node::new_child(title.id, 1.0, "Print posters".to_string());
+        let other = Node::new_child(title.id, 2.0, "Proofread".to_string());
+        index
+            .index_document(&doc, &[title.clone(), heading.clone(), task.clone(), other])
+            .unwrap();
+        index.index_document(&Uuid::now_v7(), &[]).unwrap();
+
+        let bookmarks = vec![task.id.to_string()];
+        let results = index.quick_switch_candidates("pr", &bookmarks, 10).unwrap();
+        let found: Vec<_> = results.iter().map(|c| (c.kind, c.text.as_str())).collect();
+        assert_eq!(
+            found,
+            vec![
+                (SwitchKind::Document, "Project plan"),
+                (SwitchKind::Node, "Print posters"),
+                (SwitchKind::Heading, "Prototype"),
+            ]
+        );
+        // Bookmarks edge out headings that match as well
+        assert!(results[1].bookmarked);
+        assert_eq!(results[2].document_title.as_deref(), Some("Project plan"));
+
+        // No query: documents and bookmarks only
+        let results = index.quick_switch_candidates("", &bookmarks, 10).unwrap();
+        assert_eq!(results.len(), 2);
+    }
+}
@@ -0,0 +1,220 @@
+//! Inbox capture from `outline://share?text=…&url=…&source=…` deep links on
+//! mobile. Each link becomes an inbox item (like captures from the web
+//! server) without opening the editor.
+//!
+//! This is only the receiving end: the Android ACTION_SEND intent filter and
+//! the iOS share extension that would open these links live in the generated
+//! mobile projects, which aren't part of this tree. Any app or web page can
+//! open the scheme, so what a link may carry is limited (see `check`) and
+//! bursts of links are refused.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::data::{append_inbox_item, InboxItem};
+
+/// Deep link prefix the share targets open
+const SHARE_LINK: &str = "outline://share";
+/// Longest text, link or subject a share may carry
+const MAX_FIELD_LEN: usize = 4096;
+/// Most shares captured per `SHARE_WINDOW`
+const MAX_SHARES: usize = 10;
+const SHARE_WINDOW: Duration = Duration::from_secs(60);
+
+/// When recent shares were captured
+static RECENT_SHARES: Mutex<VecDeque<Instant>> = Mutex::new(VecDeque::new());
+
+/// What another app shared
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SharedContent {
+    pub text: Option<String>,
+    pub url: Option<String>,
+    /// Title some apps send along with a link
+    pub subject: Option<String>,
+    /// Package name or bundle id of the sharing app
+    pub source_app: Option<String>,
+}
+
+/// Parse a share deep link; None for other links
+pub fn parse_share_link(link: &str) -> Option<SharedContent> {
+    let rest = link.strip_prefix(SHARE_LINK)?;
+    let query = match rest.strip_prefix('/').unwrap_or(rest) {
+        "" => "",
+        q => q.strip_prefix('?')?,
+    };
+
+    let mut shared = SharedContent::default();
+    for pair in query.split('&') {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = urlencoding::decode(&value.replace('+', " "))
+            .map(|v| v.chars().filter(|c| !c.is_control() || matches!(c, '\n' | '\t')).collect::<String>())
+            .map(|v| v.trim().to_string())
+            .unwrap_or_default();
+        if value.is_empty() {
+            continue;
+        }
+        let field = match key {
+            "text" => &mut shared.text,
+            "url" => &mut shared.url,
+            "subject" | "title" => &mut shared.subject,
+            "source" => &mut shared.source_app,
+            _ => continue,
+        };
+        *field = Some(value);
+    }
+    Some(shared)
+}
+
+impl SharedContent {
+    /// Refuse what the share targets never send: long fields, links that
+    /// aren't http(s), and a source that isn't a package name or bundle id
+    pub fn check(&self) -> Result<(), String> {
+        let fields = [&self.text, &self.url, &self.subject];
+        if fields.iter().any(|f| f.as_ref().is_some_and(|v| v.chars().count() > MAX_FIELD_LEN)) {
+            return Err(format!("Shared text is longer than {} characters", MAX_FIELD_LEN));
+        }
+        if let Some(ref url) = self.url {
+            let scheme = url.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase());
+            if !matches!(scheme.as_deref(), Some("http" | "https")) || url.contains(char::is_whitespace) {
+                return Err(format!("Shared link is not a web address: {}", url));
+            }
+        }
+        if let Some(ref app) = self.source_app {
+            let valid = app.len() <= 255 && app.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+            if !valid {
+                return Err("Sharing app name is not a package name or bundle id".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether another share may be captured now, recording it if so
+fn take_share_slot(recent: &mut VecDeque<Instant>, now: Instant) -> bool {
+    while recent.front().is_some_and(|t| now.duration_since(*t) >= SHARE_WINDOW) {
+        recent.pop_front();
+    }
+    if recent.len() >= MAX_SHARES {
+        return false;
+    }
+    recent.push_back(now);
+    true
+}
+
+/// The inbox item for a share: the subject, text or link becomes the content
+/// and whatever else was shared the note. None if nothing was shared.
+pub fn inbox_item_for(shared: &SharedContent) -> Option<InboxItem> {
+    let mut parts = [&shared.subject, &shared.text, &shared.url]
+        .into_iter()
+        .flatten()
+        .map(String::as_str);
+    let content = parts.next()?;
+    // Apps often put the link in the text as well
+    let mut rest: Vec<&str> = Vec::new();
+    for part in parts {
+        if !content.contains(part) && !rest.iter().any(|r| r.contains(part)) {
+            rest.push(part);
+        }
+    }
+    let note = (!rest.is_empty()).then(|| rest.join("\n"));
+    let source = match &shared.source_app {
+        Some(app) => format!("share:{}", app),
+        None => "share".to_string(),
+    };
+    Some(InboxItem::new(content.to_string(), note, Some(source)))
+}
+
+/// Add a share to the inbox, unless it fails `check` or too many came in
+/// lately
+pub fn capture_shared(shared: &SharedContent) -> Result<InboxItem, String> {
+    shared.check()?;
+    let item = inbox_item_for(shared).ok_or("Nothing was shared")?;
+    if !take_share_slot(&mut RECENT_SHARES.lock().unwrap(), Instant::now()) {
+        return Err(format!("More than {} shares in a minute, ignoring", MAX_SHARES));
+    }
+    append_inbox_item(&item)?;
+    Ok(item)
+}
+
+/// Capture shares delivered as deep links, including the one that launched
+/// the app
+#[cfg(mobile)]
+pub fn start_intake(app: &tauri::App) {
+    use tauri_plugin_deep_link::DeepLinkExt;
+
+    match app.deep_link().get_current() {
+        Ok(Some(urls)) => urls.iter().for_each(|url| capture_link(url.as_str())),
+        Ok(None) => {}
+        Err(e) => log::warn!("Failed to read launch link: {}", e),
+    }
+    app.deep_link().on_open_url(|event| {
+        for url in event.urls() {
+            capture_link(url.as_str());
+        }
+    });
+}
+
+#[cfg(mobile)]
+fn capture_link(link: &str) {
+    let Some(shared) = parse_share_link(link) else {
+        return;
+    };
+    match capture_shared(&shared) {
+        Ok(item) => log::info!("Captured shared item {} from {:?}", item.id, item.source),
+        Err(e) => log::error!("Failed to capture shared item: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_link_to_inbox_item() {
+        let shared = parse_share_link(
+            "outline://share?subject=Rust+1.80&text=Read+this%3A+https%3A%2F%2Fblog.rust-lang.org&url=https%3A%2F%2Fblog.rust-lang.org&source=com.android.chrome",
+        )
+        .unwrap();
+        assert_eq!(shared.subject.as_deref(), Some("Rust 1.80"));
+        assert_eq!(shared.source_app.as_deref(), Some("com.android.chrome"));
+
+        let item = inbox_item_for(&shared).unwrap();
+        assert_eq!(item.content, "Rust 1.80");
+        assert_eq!(item.note.as_deref(), Some("Read this: https://blog.rust-lang.org"));
+        assert_eq!(item.source.as_deref(), Some("share:com.android.chrome"));
+
+        // A bare link needs no note
+        let link = parse_share_link("outline://share/?url=https%3A%2F%2Fexample.com").unwrap();
+        let item = inbox_item_for(&link).unwrap();
+        assert_eq!((item.content.as_str(), item.note), ("https://example.com", None));
+        assert_eq!(item.source.as_deref(), Some("share"));
+
+        assert!(inbox_item_for(&parse_share_link("outline://share").unwrap()).is_none());
+        assert!(parse_share_link("outline://open?doc=1").is_none());
+        assert!(parse_share_link("outline://sharelist").is_none());
+    }
+
+    #[test]
+    fn test_share_checks() {
+        let ok = parse_share_link("outline://share?text=a%07b&url=HTTPS%3A%2F%2Fexample.com&source=com.apple.mobilesafari").unwrap();
+        assert_eq!(ok.text.as_deref(), Some("ab"));
+        assert!(ok.check().is_ok());
+
+        for link in [
+            "outline://share?url=javascript%3Aalert(1)",
+            "outline://share?url=file%3A%2F%2F%2Fetc%2Fpasswd",
+            "outline://share?text=hi&source=%3Cscript%3E",
+        ] {
+            assert!(parse_share_link(link).unwrap().check().is_err(), "{}", link);
+        }
+        let long = format!("outline://share?text={}", "a".repeat(MAX_FIELD_LEN + 1));
+        assert!(parse_share_link(&long).unwrap().check().is_err());
+
+        let mut recent = VecDeque::new();
+        let start = Instant::now();
+        assert!((0..MAX_SHARES).all(|_| take_share_slot(&mut recent, start)));
+        assert!(!take_share_slot(&mut recent, start + Duration::from_secs(1)));
+        assert!(take_share_slot(&mut recent, start + SHARE_WINDOW));
+    }
+}
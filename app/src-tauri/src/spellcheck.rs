@@ -0,0 +1,270 @@
+//! Spellchecking against Hunspell dictionaries (.dic word lists with the
+//! rules of their .aff files, checked with spellbook). Dictionaries put in the
+//! data directory's `dictionaries` folder are used, else the system's one for
+//! the current locale. Words added by the user go in `dictionary.txt` in the
+//! data directory, so they sync with the documents.
+
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::data::data_dir;
+
+/// Where system dictionaries are looked for
+const SYSTEM_DICTIONARY_DIRS: &[&str] = &[
+    "/usr/share/hunspell",
+    "/usr/share/myspell",
+    "/usr/share/myspell/dicts",
+    "/Library/Spelling",
+];
+
+/// Suggestions offered per misspelling
+const MAX_SUGGESTIONS: usize = 5;
+
+/// A misspelled word. Offsets are in UTF-16 code units, as JavaScript
+/// strings count them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Misspelling {
+    pub word: String,
+    pub start: usize,
+    pub end: usize,
+    pub suggestions: Vec<String>,
+}
+
+/// The dictionaries in use; a word is spelled right if any of them has it
+pub struct Dictionary {
+    dictionaries: Vec<spellbook::Dictionary>,
+}
+
+impl Dictionary {
+    /// Load a .dic file with the .aff file next to it (if any)
+    pub fn load(dic_path: &Path) -> Result<Self, String> {
+        let mut dictionary = Dictionary {
+            dictionaries: Vec::new(),
+        };
+        dictionary.add_dic(dic_path)?;
+        Ok(dictionary)
+    }
+
+    fn add_dic(&mut self, dic_path: &Path) -> Result<(), String> {
+        let dic = fs::read_to_string(dic_path).map_err(|e| format!("Read {}: {}", dic_path.display(), e))?;
+        let aff = fs::read_to_string(dic_path.with_extension("aff")).unwrap_or_default();
+        let dictionary =
+            spellbook::Dictionary::new(&aff, &dic).map_err(|e| format!("Parse {}: {}", dic_path.display(), e))?;
+        self.dictionaries.push(dictionary);
+        Ok(())
+    }
+
+    /// Whether a word is spelled right. Personal words also match when
+    /// capitalized.
+    pub fn contains(&self, word: &str, personal: &HashSet<String>) -> bool {
+        personal.contains(word)
+            || personal.contains(&word.to_lowercase())
+            || self.dictionaries.iter().any(|d| d.check(word))
+    }
+
+    /// Corrections the dictionaries offer for `word`
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let mut suggestions: Vec<String> = Vec::new();
+        let mut found = Vec::new();
+        for dictionary in &self.dictionaries {
+            dictionary.suggest(word, &mut found);
+            for suggestion in found.drain(..) {
+                if !suggestions.contains(&suggestion) {
+                    suggestions.push(suggestion);
+                }
+            }
+        }
+        suggestions.truncate(MAX_SUGGESTIONS);
+        suggestions
+    }
+
+    /// Find the misspelled words in a text
+    pub fn check(&self, text: &str, personal: &HashSet<String>) -> Vec<Misspelling> {
+        words(text)
+            .into_iter()
+            .filter(|(word, _, _)| !self.contains(word, personal))
+            .map(|(word, start, end)| Misspelling {
+                suggestions: self.suggest(word),
+                word: word.to_string(),
+                start,
+                end,
+            })
+            .collect()
+    }
+}
+
+/// Words in a text with their UTF-16 offsets. Words are letters with inner
+/// apostrophes; tokens containing digits and single letters are skipped.
+fn words(text: &str) -> Vec<(&str, usize, usize)> {
+    let mut words = Vec::new();
+    let mut start: Option<(usize, usize)> = None;
+    let mut offset16 = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next_is_letter = chars.peek().is_some_and(|(_, n)| n.is_alphabetic());
+        let in_word = c.is_alphanumeric() || (start.is_some() && (c == '\'' || c == '\u{2019}') && next_is_letter);
+        match (in_word, start) {
+            (true, None) => start = Some((i, offset16)),
+            (false, Some((byte_start, start16))) => {
+                words.push((&text[byte_start..i], start16, offset16));
+                start = None;
+            }
+            _ => {}
+        }
+        offset16 += c.len_utf16();
+    }
+    if let Some((byte_start, start16)) = start {
+        words.push((&text[byte_start..], start16, offset16));
+    }
+    words.retain(|(word, _, _)| word.chars().count() > 1 && !word.chars().any(|c| c.is_numeric()));
+    words
+}
+
+fn personal_dictionary_path() -> PathBuf {
+    data_dir().join("dictionary.txt")
+}
+
+/// Words the user has added
+pub fn personal_words() -> HashSet<String> {
+    fs::read_to_string(personal_dictionary_path())
+        .map(|contents| contents.lines().map(str::trim).filter(|w| !w.is_empty()).map(String::from).collect())
+        .unwrap_or_default()
+}
+
+/// Add a word to the personal dictionary
+pub fn add_to_dictionary(word: &str) -> Result<(), String> {
+    let word = word.trim();
+    if word.is_empty() || word.contains(char::is_whitespace) {
+        return Err(format!("Not a single word: {:?}", word));
+    }
+    let mut words: Vec<String> = personal_words().into_iter().collect();
+    if words.iter().any(|w| w == word) {
+        return Ok(());
+    }
+    words.push(word.to_string());
+    words.sort_by_key(|w| w.to_lowercase());
+    fs::write(personal_dictionary_path(), words.join("\n") + "\n")
+        .map_err(|e| format!("Write personal dictionary: {}", e))
+}
+
+/// .dic files to load: those in the data directory's `dictionaries` folder,
+/// else the system dictionary for $LANG (en_US if unset)
+fn dictionary_files() -> Vec<PathBuf> {
+    let user_dir = data_dir().join("dictionaries");
+    let mut files: Vec<PathBuf> = fs::read_dir(&user_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "dic"))
+                .collect()
+        })
+        .unwrap_or_default();
+    if !files.is_empty() {
+        files.sort();
+        return files;
+    }
+
+    let locale = std::env::var("LANG").unwrap_or_default();
+    let locale = locale.split('.').next().filter(|l| l.contains('_')).unwrap_or("en_US");
+    SYSTEM_DICTIONARY_DIRS
+        .iter()
+        .map(|dir| Path::new(dir).join(format!("{}.dic", locale)))
+        .find(|path| path.exists())
+        .into_iter()
+        .collect()
+}
+
+static DICTIONARY: Mutex<Option<(Vec<PathBuf>, Arc<Dictionary>)>> = Mutex::new(None);
+
+/// The dictionary for the current dictionary files, loaded once
+pub fn dictionary() -> Result<Arc<Dictionary>, String> {
+    let files = dictionary_files();
+    if files.is_empty() {
+        return Err("No spellcheck dictionary found".to_string());
+    }
+    let mut cached = DICTIONARY.lock().unwrap();
+    if let Some((loaded_files, dictionary)) = cached.as_ref() {
+        if *loaded_files == files {
+            return Ok(dictionary.clone());
+        }
+    }
+    let mut dictionary = Dictionary::load(&files[0])?;
+    for file in &files[1..] {
+        dictionary.add_dic(file)?;
+    }
+    let dictionary = Arc::new(dictionary);
+    *cached = Some((files, dictionary.clone()));
+    Ok(dictionary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFF: &str = "SET UTF-8\nTRY esianrtolcdugmphbyfvkwzESIANRTOLCDUGMPHBYFVKWZ'\n\n\
+        PFX A Y 1\nPFX A 0 re .\n\n\
+        SFX D Y 4\nSFX D 0 d e\nSFX D y ied [^aeiou]y\nSFX D 0 ed [^ey]\nSFX D 0 ed [aeiou]y\n\n\
+        SFX S Y 3\nSFX S y ies [^aeiou]y\nSFX S 0 s [aeiou]y\nSFX S 0 s [^y]\n";
+    const DIC: &str = "5\nwrite/AS\ncarry/DS\nplay/DS\nParis\nthe\n";
+
+    fn dictionary() -> Dictionary {
+        let tmp = tempfile::TempDir::new().unwrap();
+        fs::write(tmp.path().join("test.aff"), AFF).unwrap();
+        fs::write(tmp.path().join("test.dic"), DIC).unwrap();
+        Dictionary::load(&tmp.path().join("test.dic")).unwrap()
+    }
+
+    #[test]
+    fn test_affix_expansion() {
+        let dictionary = dictionary();
+        let none = HashSet::new();
+        for word in ["write", "writes", "rewrite", "rewrites", "carried", "carries", "played", "plays", "Paris"] {
+            assert!(dictionary.contains(word, &none), "{}", word);
+        }
+        // Capitalized and all-caps forms of lowercase words are fine, but
+        // proper nouns keep their capital
+        assert!(dictionary.contains("Write", &none));
+        assert!(dictionary.contains("PARIS", &none));
+        assert!(!dictionary.contains("paris", &none));
+        assert!(!dictionary.contains("carryed", &none));
+        assert!(!dictionary.contains("replayed", &none));
+    }
+
+    #[test]
+    fn test_compounds_and_flags() {
+        // Continuation classes, compounding, NEEDAFFIX and FORBIDDENWORD
+        let aff = "SET UTF-8\nNEEDAFFIX X\nFORBIDDENWORD F\nCOMPOUNDFLAG C\n\n\
+            SFX L Y 1\nSFX L 0 ful/Y .\n\nSFX Y Y 1\nSFX Y 0 ly .\n";
+        let dic = "5\nhope/L\nforget/LX\nfoot/C\nball/C\nballfoot/F\n";
+        let tmp = tempfile::TempDir::new().unwrap();
+        fs::write(tmp.path().join("test.aff"), aff).unwrap();
+        fs::write(tmp.path().join("test.dic"), dic).unwrap();
+        let dictionary = Dictionary::load(&tmp.path().join("test.dic")).unwrap();
+        let none = HashSet::new();
+
+        for word in ["hope", "hopeful", "hopefully", "forgetful", "football"] {
+            assert!(dictionary.contains(word, &none), "{}", word);
+        }
+        assert!(!dictionary.contains("forget", &none));
+        assert!(!dictionary.contains("ballfoot", &none));
+    }
+
+    #[test]
+    fn test_check_text() {
+        let dictionary = dictionary();
+        let mut personal = HashSet::new();
+        let text = "The 😀 wrtie carrys it's 3rd plays";
+        let misspelled = dictionary.check(text, &personal);
+        let found: Vec<_> = misspelled.iter().map(|m| (m.word.as_str(), m.start, m.end)).collect();
+        assert_eq!(found, vec![("wrtie", 7, 12), ("carrys", 13, 19), ("it's", 20, 24)]);
+        assert_eq!(misspelled[0].suggestions, vec!["write"]);
+        assert_eq!(misspelled[1].suggestions, vec!["carry"]);
+
+        personal.insert("it's".to_string());
+        assert_eq!(dictionary.check(text, &personal).len(), 2);
+    }
+}
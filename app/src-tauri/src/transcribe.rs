@@ -0,0 +1,302 @@
+//! Voice capture transcription. Recordings (voice captures from mobile, or
+//! audio attached to a node) are kept in the data directory's `audio`
+//! folder and transcribed one at a time on a background thread by the
+//! engine in the config: a local whisper.cpp build or an OpenAI-compatible
+//! transcription API. A voice capture's transcript becomes its inbox item's
+//! note; what happens with a node's transcript is up to the handler.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::data::{audio_dir, load_config, read_inbox, update_inbox_item, TranscriptionConfig};
+
+/// How long an API transcription may take
+const API_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Something that turns a recording into text
+pub trait Transcriber {
+    fn transcribe(&self, audio: &Path) -> Result<String, String>;
+}
+
+/// A local whisper.cpp build
+pub struct WhisperCpp {
+    pub binary: String,
+    pub model: String,
+    pub language: Option<String>,
+}
+
+impl Transcriber for WhisperCpp {
+    fn transcribe(&self, audio: &Path) -> Result<String, String> {
+        // whisper.cpp only reads 16 kHz WAV without ffmpeg support built in
+        let is_wav = audio.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+        let converted = (!is_wav).then(|| std::env::temp_dir().join(format!("outline-{}.wav", Uuid::new_v4())));
+        if let Some(ref wav) = converted {
+            let status = Command::new("ffmpeg")
+                .args(["-y", "-loglevel", "error", "-i"])
+                .arg(audio)
+                .args(["-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le"])
+                .arg(wav)
+                .status()
+                .map_err(|e| format!("Run ffmpeg to convert the recording: {}", e))?;
+            if !status.success() {
+                return Err(format!("ffmpeg could not convert {}", audio.display()));
+            }
+        }
+
+        let mut command = Command::new(&self.binary);
+        command.args(["-m", &self.model, "-nt", "-np", "-f"]).arg(converted.as_deref().unwrap_or(audio));
+        if let Some(ref language) = self.language {
+            command.args(["-l", language]);
+        }
+        let output = command.output();
+        if let Some(wav) = converted {
+            let _ = fs::remove_file(wav);
+        }
+        let output = output.map_err(|e| format!("Run {}: {}", self.binary, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "{} failed: {}",
+                self.binary,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(clean_transcript(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+/// An OpenAI-compatible `/v1/audio/transcriptions` endpoint
+pub struct TranscriptionApi {
+    pub url: String,
+    pub api_key: Option<String>,
+    pub model: String,
+    pub language: Option<String>,
+}
+
+impl Transcriber for TranscriptionApi {
+    fn transcribe(&self, audio: &Path) -> Result<String, String> {
+        let data = fs::read(audio).map_err(|e| format!("Read {}: {}", audio.display(), e))?;
+        let file_name = audio.file_name().and_then(|n| n.to_str()).unwrap_or("audio");
+        let mut fields = vec![("model", self.model.as_str())];
+        if let Some(ref language) = self.language {
+            fields.push(("language", language));
+        }
+        let boundary = format!("outline-{}", Uuid::new_v4().simple());
+        let body = multipart_body(&boundary, &fields, file_name, audio_mime_type(audio), &data);
+
+        tauri::async_runtime::block_on(async {
+            let client = reqwest::Client::builder()
+                .timeout(API_TIMEOUT)
+                .build()
+                .map_err(|e| e.to_string())?;
+            let mut request = client
+                .post(&self.url)
+                .header(reqwest::header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", boundary))
+                .body(body);
+            if let Some(ref key) = self.api_key {
+                request = request.bearer_auth(key);
+            }
+            let response = request.send().await.map_err(|e| format!("Transcription request failed: {}", e))?;
+            let status = response.status();
+            let text = response.text().await.map_err(|e| format!("Read transcription: {}", e))?;
+            if !status.is_success() {
+                return Err(format!("Transcription API error {}: {}", status, text.trim()));
+            }
+            let json: serde_json::Value =
+                serde_json::from_str(&text).map_err(|e| format!("Invalid transcription response: {}", e))?;
+            json["text"]
+                .as_str()
+                .map(clean_transcript)
+                .ok_or_else(|| "Transcription response has no text".to_string())
+        })
+    }
+}
+
+/// The engine a config describes
+pub fn transcriber(config: &TranscriptionConfig) -> Box<dyn Transcriber> {
+    match config.clone() {
+        TranscriptionConfig::WhisperCpp { binary, model, language } => Box::new(WhisperCpp { binary, model, language }),
+        TranscriptionConfig::Api { url, api_key, model, language } => {
+            Box::new(TranscriptionApi { url, api_key, model, language })
+        }
+    }
+}
+
+/// A transcript as one paragraph per line, without blank lines
+fn clean_transcript(text: &str) -> String {
+    text.lines().map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join("\n")
+}
+
+fn audio_mime_type(audio: &Path) -> &'static str {
+    let ext = audio.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    match ext.as_str() {
+        "wav" => "audio/wav",
+        "mp3" => "audio/mpeg",
+        "m4a" | "mp4" | "aac" => "audio/mp4",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "webm" => "audio/webm",
+        "flac" => "audio/flac",
+        _ => "application/octet-stream",
+    }
+}
+
+/// A multipart/form-data body with text fields and one file named "file"
+fn multipart_body(boundary: &str, fields: &[(&str, &str)], file_name: &str, mime_type: &str, data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", boundary, name, value).as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+            boundary,
+            file_name.replace('"', ""),
+            mime_type
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+/// Where a transcript goes
+#[derive(Debug, Clone, PartialEq)]
+pub enum TranscriptTarget {
+    /// The note of a voice capture in the inbox
+    Inbox(String),
+    Node { document_id: Uuid, node_id: Uuid },
+}
+
+#[derive(Debug, Clone)]
+pub struct TranscriptionJob {
+    /// File name in the audio directory
+    pub audio: String,
+    pub target: TranscriptTarget,
+}
+
+/// Called with each finished job's transcript (or why there is none), after
+/// an inbox item's note has been filled in
+pub type TranscriptHandler = Arc<dyn Fn(&TranscriptionJob, &Result<String, String>) + Send + Sync>;
+
+/// Copy a recording into the audio directory under a new name
+pub fn store_audio(data: &[u8], extension: &str) -> Result<String, String> {
+    let extension: String = extension.trim_start_matches('.').chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+    let name = match extension.as_str() {
+        "" => Uuid::new_v4().to_string(),
+        ext => format!("{}.{}", Uuid::new_v4(), ext.to_lowercase()),
+    };
+    let dir = audio_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("Create audio dir: {}", e))?;
+    fs::write(dir.join(&name), data).map_err(|e| format!("Write recording: {}", e))?;
+    Ok(name)
+}
+
+fn transcribe_job(job: &TranscriptionJob) -> Result<String, String> {
+    let config = load_config()
+        .transcription
+        .ok_or("No transcription engine is configured")?;
+    let path: PathBuf = audio_dir().join(&job.audio);
+    transcriber(&config).transcribe(&path)
+}
+
+/// The queue of recordings waiting to be transcribed
+pub struct TranscriptionQueue {
+    sender: Mutex<Option<Sender<TranscriptionJob>>>,
+}
+
+impl TranscriptionQueue {
+    pub fn new() -> Self {
+        Self {
+            sender: Mutex::new(None),
+        }
+    }
+
+    /// Start the worker, and queue voice captures left half-done by the last
+    /// run
+    pub fn start(&self, handler: TranscriptHandler) {
+        let (sender, receiver) = mpsc::channel::<TranscriptionJob>();
+        thread::spawn(move || {
+            for job in receiver {
+                let result = transcribe_job(&job);
+                if let TranscriptTarget::Inbox(ref id) = job.target {
+                    let transcript = result.as_ref().ok().filter(|t| !t.is_empty()).cloned();
+                    let updated = update_inbox_item(id, |item| {
+                        item.transcribing = false;
+                        if transcript.is_some() {
+                            item.note = transcript;
+                        }
+                    });
+                    if let Err(e) = updated {
+                        log::error!("Failed to save transcript of inbox item {}: {}", id, e);
+                    }
+                }
+                if let Err(ref e) = result {
+                    log::warn!("Failed to transcribe {}: {}", job.audio, e);
+                }
+                handler(&job, &result);
+            }
+        });
+
+        match read_inbox() {
+            Ok(items) => {
+                for item in items.into_iter().filter(|item| item.transcribing) {
+                    if let Some(audio) = item.audio {
+                        let _ = sender.send(TranscriptionJob { audio, target: TranscriptTarget::Inbox(item.id) });
+                    }
+                }
+            }
+            Err(e) => log::warn!("Failed to read inbox for pending transcriptions: {}", e),
+        }
+        *self.sender.lock().unwrap() = Some(sender);
+    }
+
+    pub fn push(&self, job: TranscriptionJob) -> Result<(), String> {
+        self.sender
+            .lock()
+            .unwrap()
+            .as_ref()
+            .ok_or("Transcription isn't running")?
+            .send(job)
+            .map_err(|_| "Transcription stopped".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multipart_body() {
+        let body = multipart_body("b", &[("model", "whisper-1")], "memo.m4a", "audio/mp4", b"DATA");
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "--b\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n\
+             --b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"memo.m4a\"\r\n\
+             Content-Type: audio/mp4\r\n\r\nDATA\r\n--b--\r\n"
+        );
+    }
+
+    #[test]
+    fn test_transcription_config() {
+        let config: TranscriptionConfig = serde_json::from_str(r#"{"engine": "whisper_cpp", "model": "/m/ggml-base.en.bin"}"#).unwrap();
+        assert_eq!(
+            config,
+            TranscriptionConfig::WhisperCpp {
+                binary: "whisper-cli".to_string(),
+                model: "/m/ggml-base.en.bin".to_string(),
+                language: None,
+            }
+        );
+        let config: TranscriptionConfig = serde_json::from_str(r#"{"engine": "api", "api_key": "k"}"#).unwrap();
+        assert!(matches!(config, TranscriptionConfig::Api { ref model, .. } if model == "whisper-1"));
+        assert_eq!(clean_transcript("\n  Buy milk. \n\n Call Sam.\n"), "Buy milk.\nCall Sam.");
+    }
+}
@@ -1,32 +1,62 @@
 //! Filesystem watcher for detecting document changes from sync.
 //!
 //! Watches the documents directory for changes and emits Tauri events
-//! when documents are added, removed, or modified.
+//! when documents are added, removed, or modified. Also watches the folder
+//! files, emitting `folders-changed` when another machine changed them (not
+//! on the app's own writes). Deleted documents are dropped from the search
+//! index.
 
 use notify_debouncer_full::{
     new_debouncer, DebounceEventResult,
     notify::event::EventKind,
     notify::RecursiveMode,
 };
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{self, Sender};
 use std::sync::Mutex;
 use std::thread;
 use std::time::Duration;
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Manager};
 
-use crate::data::documents_dir;
+use crate::commands::AppState;
+use crate::data::{data_dir, documents_dir, folders_changed_on_disk};
 
 /// Payload sent with the documents-changed event
 #[derive(Clone, serde::Serialize)]
 pub struct DocumentsChangedPayload {
     /// List of document IDs that changed (if known)
     pub document_ids: Vec<String>,
+    /// What changed in each document, one entry per document
+    pub changes: Vec<DocumentChange>,
+}
+
+/// What kind of change happened to a document's files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentChangeKind {
+    /// meta.json changed (lock state and other settings)
+    MetaModified,
+    /// A pending.*.jsonl file was written (usually ops from another machine)
+    PendingOpAdded,
+    /// state.json was rewritten (e.g. compaction)
+    StateModified,
+    /// The document directory appeared
+    Created,
+    /// The document directory is gone
+    Deleted,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct DocumentChange {
+    pub document_id: String,
+    pub kind: DocumentChangeKind,
 }
 
 /// Handle to the running watcher, used to stop it
 pub struct WatcherHandle {
     stop_tx: Sender<()>,
+    /// Directory being watched
+    dir: PathBuf,
 }
 
 impl WatcherHandle {
@@ -36,6 +66,14 @@ impl WatcherHandle {
     }
 }
 
+/// Watcher status returned to the frontend
+#[derive(Clone, serde::Serialize)]
+pub struct WatcherStatus {
+    pub running: bool,
+    /// Directory being watched, if running
+    pub watched_dir: Option<String>,
+}
+
 /// State for managing the documents watcher
 pub struct WatcherState {
     handle: Mutex<Option<WatcherHandle>>,
@@ -53,6 +91,31 @@ impl WatcherState {
         self.handle.lock().unwrap().is_some()
     }
 
+    /// Whether the watcher is running, and on which directory
+    pub fn status(&self) -> WatcherStatus {
+        let guard = self.handle.lock().unwrap();
+        WatcherStatus {
+            running: guard.is_some(),
+            watched_dir: guard.as_ref().map(|h| h.dir.to_string_lossy().to_string()),
+        }
+    }
+
+    /// Restart a running watcher so it follows the current documents
+    /// directory (e.g. after the data directory changes). Does nothing if
+    /// the watcher is stopped or already watching that directory.
+    pub fn restart_if_moved(&self, app_handle: AppHandle) -> Result<(), String> {
+        let moved = self
+            .handle
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|h| h.dir != documents_dir());
+        if moved {
+            self.set_handle(start_watcher(app_handle)?);
+        }
+        Ok(())
+    }
+
     /// Set the watcher handle
     pub fn set_handle(&self, handle: WatcherHandle) {
         let mut guard = self.handle.lock().unwrap();
@@ -114,6 +177,13 @@ pub fn start_watcher(app_handle: AppHandle) -> Result<WatcherHandle, String> {
             return;
         }
 
+        // Folder files live at the top of the data directory and can change
+        // from another machine
+        let data_dir = data_dir();
+        if let Err(e) = debouncer.watch(&data_dir, RecursiveMode::NonRecursive) {
+            log::warn!("Failed to watch data directory: {}", e);
+        }
+
         log::info!("Documents watcher started successfully");
 
         loop {
@@ -126,8 +196,9 @@ pub fn start_watcher(app_handle: AppHandle) -> Result<WatcherHandle, String> {
             // Check for events (with timeout to allow checking stop signal)
             match event_rx.recv_timeout(Duration::from_millis(100)) {
                 Ok(Ok(events)) => {
-                    // Collect changed document IDs, filtering out Access events
-                    let mut changed_ids: Vec<String> = Vec::new();
+                    // Collect document changes, filtering out Access events
+                    let mut changes: Vec<DocumentChange> = Vec::new();
+                    let mut folders_changed = false;
 
                     for debounced_event in events {
                         let event = &debounced_event.event;
@@ -143,27 +214,39 @@ pub fn start_watcher(app_handle: AppHandle) -> Result<WatcherHandle, String> {
                             continue;
                         }
 
-                        // Extract document ID from each path in the event
+                        // Classify the change to each path in the event
                         for path in &event.paths {
-                            if let Some(doc_id) = extract_document_id(path, &docs_dir_clone) {
-                                if !changed_ids.contains(&doc_id) {
-                                    log::debug!("Document change detected: {} ({:?})", doc_id, event.kind);
-                                    changed_ids.push(doc_id);
-                                }
+                            folders_changed |= is_folders_file(path, &data_dir);
+                            if let Some(change) = classify_change(path, &event.kind, &docs_dir_clone) {
+                                log::debug!("Document change detected: {:?} ({:?})", change, event.kind);
+                                merge_change(&mut changes, change);
                             }
                         }
                     }
 
+                    // Deleted documents leave the index (any others deleted
+                    // without an event go too)
+                    if changes.iter().any(|c| c.kind == DocumentChangeKind::Deleted) {
+                        app_handle.state::<AppState>().indexer.reconcile();
+                    }
+
                     // Emit event if we have changes
-                    if !changed_ids.is_empty() {
-                        log::info!("Documents changed: {:?}", changed_ids);
+                    if !changes.is_empty() {
+                        log::info!("Documents changed: {:?}", changes);
                         let payload = DocumentsChangedPayload {
-                            document_ids: changed_ids,
+                            document_ids: changes.iter().map(|c| c.document_id.clone()).collect(),
+                            changes,
                         };
                         if let Err(e) = app_handle.emit("documents-changed", payload) {
                             log::error!("Failed to emit documents-changed event: {}", e);
                         }
                     }
+                    if folders_changed && folders_changed_on_disk() {
+                        log::info!("Folders changed");
+                        if let Err(e) = app_handle.emit("folders-changed", ()) {
+                            log::error!("Failed to emit folders-changed event: {}", e);
+                        }
+                    }
                 }
                 Ok(Err(errors)) => {
                     log::warn!("Watch error: {:?}", errors);
@@ -181,12 +264,12 @@ pub fn start_watcher(app_handle: AppHandle) -> Result<WatcherHandle, String> {
         log::info!("Documents watcher stopped");
     });
 
-    Ok(WatcherHandle { stop_tx })
+    Ok(WatcherHandle { stop_tx, dir: docs_dir })
 }
 
 /// Extract document ID from a file path within the documents directory.
 /// Returns Some(uuid_string) if the path is within a document folder.
-fn extract_document_id(path: &PathBuf, docs_dir: &PathBuf) -> Option<String> {
+fn extract_document_id(path: &Path, docs_dir: &PathBuf) -> Option<String> {
     // Strip the documents directory prefix
     let relative = path.strip_prefix(docs_dir).ok()?;
 
@@ -202,6 +285,51 @@ fn extract_document_id(path: &PathBuf, docs_dir: &PathBuf) -> Option<String> {
     }
 }
 
+/// Whether a path is folders.json or a folder op log in the data directory
+fn is_folders_file(path: &Path, data_dir: &Path) -> bool {
+    if path.parent() != Some(data_dir) {
+        return false;
+    }
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    name == "folders.json" || (name.starts_with("folders.") && name.ends_with(".jsonl"))
+}
+
+/// Work out what a filesystem event on `path` means for its document.
+/// History files and other non-document paths are ignored.
+fn classify_change(path: &Path, kind: &EventKind, docs_dir: &PathBuf) -> Option<DocumentChange> {
+    let document_id = extract_document_id(path, docs_dir)?;
+    let relative = path.strip_prefix(docs_dir).ok()?;
+    let file_name = relative.components().nth(1).and_then(|c| c.as_os_str().to_str());
+
+    let kind = if !docs_dir.join(&document_id).exists() {
+        DocumentChangeKind::Deleted
+    } else {
+        match (file_name, kind) {
+            (None, EventKind::Create(_)) => DocumentChangeKind::Created,
+            (None, _) => return None,
+            (Some("state.json"), _) => DocumentChangeKind::StateModified,
+            (Some("meta.json"), _) => DocumentChangeKind::MetaModified,
+            // Pending files are removed by compaction, which rewrites state.json
+            (Some(name), EventKind::Create(_) | EventKind::Modify(_))
+                if name.starts_with("pending.") && name.ends_with(".jsonl") =>
+            {
+                DocumentChangeKind::PendingOpAdded
+            }
+            _ => return None,
+        }
+    };
+
+    Some(DocumentChange { document_id, kind })
+}
+
+/// Add a change, keeping only the most significant kind per document
+fn merge_change(changes: &mut Vec<DocumentChange>, change: DocumentChange) {
+    match changes.iter_mut().find(|c| c.document_id == change.document_id) {
+        Some(existing) => existing.kind = existing.kind.max(change.kind),
+        None => changes.push(change),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -233,4 +361,45 @@ mod tests {
         let path4 = PathBuf::from("/home/user/.outline-data/inbox.jsonl");
         assert_eq!(extract_document_id(&path4, &docs_dir), None);
     }
+
+    #[test]
+    fn test_is_folders_file() {
+        let data_dir = Path::new("/home/user/.outline-data");
+        assert!(is_folders_file(&data_dir.join("folders.json"), data_dir));
+        assert!(is_folders_file(&data_dir.join("folders.laptop.jsonl"), data_dir));
+        assert!(!is_folders_file(&data_dir.join("inbox.jsonl"), data_dir));
+        assert!(!is_folders_file(&data_dir.join("documents").join("folders.json"), data_dir));
+    }
+
+    #[test]
+    fn test_classify_change() {
+        use notify_debouncer_full::notify::event::{CreateKind, ModifyKind, RemoveKind};
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let docs_dir = tmp.path().to_path_buf();
+        let id = "550e8400-e29b-41d4-a716-446655440000";
+        std::fs::create_dir(docs_dir.join(id)).unwrap();
+        let modify = EventKind::Modify(ModifyKind::Any);
+        let kind_of = |file: &str, kind: &EventKind| {
+            let path = if file.is_empty() { docs_dir.join(id) } else { docs_dir.join(id).join(file) };
+            classify_change(&path, kind, &docs_dir).map(|c| c.kind)
+        };
+
+        assert_eq!(kind_of("", &EventKind::Create(CreateKind::Folder)), Some(DocumentChangeKind::Created));
+        assert_eq!(kind_of("state.json", &modify), Some(DocumentChangeKind::StateModified));
+        assert_eq!(kind_of("pending.laptop.jsonl", &modify), Some(DocumentChangeKind::PendingOpAdded));
+        assert_eq!(kind_of("meta.json", &modify), Some(DocumentChangeKind::MetaModified));
+        assert_eq!(kind_of("history.laptop.jsonl", &modify), None);
+        assert_eq!(kind_of("pending.laptop.jsonl", &EventKind::Remove(RemoveKind::File)), None);
+
+        std::fs::remove_dir(docs_dir.join(id)).unwrap();
+        assert_eq!(kind_of("state.json", &modify), Some(DocumentChangeKind::Deleted));
+
+        let mut changes = Vec::new();
+        for kind in [DocumentChangeKind::PendingOpAdded, DocumentChangeKind::StateModified, DocumentChangeKind::MetaModified] {
+            merge_change(&mut changes, DocumentChange { document_id: id.to_string(), kind });
+        }
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind, DocumentChangeKind::StateModified);
+    }
 }
@@ -1,8 +1,10 @@
 import React, { useState, useEffect, useCallback, useMemo, useRef, useImperativeHandle, forwardRef } from 'react';
 import {
   listDocuments,
-  updateNode,
+  renameDocument,
+  onDocumentRenamed,
   getFolders,
+  onFoldersChanged,
   createFolder,
   updateFolder,
   deleteFolder,
@@ -89,6 +91,46 @@ export const Sidebar = forwardRef<SidebarRef, SidebarProps>(function Sidebar(
     loadAll();
   }, [loadAll]);
 
+  // Follow title changes made anywhere (renames, edits, synced changes)
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let cancelled = false;
+    onDocumentRenamed(({ document_id, title }) => {
+      setDocuments((docs) => docs.map((d) => (d.id === document_id ? { ...d, title } : d)));
+    }).then((stop) => {
+      if (cancelled) {
+        stop();
+      } else {
+        unlisten = stop;
+      }
+    });
+    return () => {
+      cancelled = true;
+      unlisten?.();
+    };
+  }, []);
+
+  // Pick up folder changes synced in from other machines
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let cancelled = false;
+    onFoldersChanged(() => {
+      getFolders()
+        .then(setFolderState)
+        .catch((e) => console.error('Failed to reload folders:', e));
+    }).then((stop) => {
+      if (cancelled) {
+        stop();
+      } else {
+        unlisten = stop;
+      }
+    });
+    return () => {
+      cancelled = true;
+      unlisten?.();
+    };
+  }, []);
+
   // Close context menu on click elsewhere
   useEffect(() => {
     function handleGlobalClick() {
@@ -210,10 +252,10 @@ export const Sidebar = forwardRef<SidebarRef, SidebarProps>(function Sidebar(
   }, [contextMenuTarget, loadAll]);
 
   const handleRenameDoc = useCallback(async (newName: string) => {
-    if (!renameDoc?.title_node_id) return;
+    if (!renameDoc) return;
 
     try {
-      await updateNode(renameDoc.title_node_id, { content: newName });
+      await renameDocument(renameDoc.id, newName);
       await loadAll();
     } catch (e) {
       console.error('Failed to rename document:', e);
@@ -61,11 +61,12 @@ export function DateViewsPanel({ isOpen, onClose, onNavigate }: DateViewsPanelPr
           return false;
       }
     }).sort((a, b) => {
-      // Sort by date ascending
+      // Sort by date ascending, then priority (1 = highest; unprioritized last)
       if (a.date && b.date) {
-        return a.date.localeCompare(b.date);
+        const byDate = a.date.localeCompare(b.date);
+        if (byDate !== 0) return byDate;
       }
-      return 0;
+      return (a.priority ?? 5) - (b.priority ?? 5);
     });
   }, [nodesWithDates, activeView]);
 
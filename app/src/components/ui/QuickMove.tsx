@@ -2,6 +2,7 @@ import React, { useState, useEffect, useRef, useCallback } from 'react';
 import * as api from '../../lib/api';
 import type { SearchResult } from '../../lib/api';
 import { useOutlineStore } from '../../store/outlineStore';
+import { positionBetween } from '../../lib/utils';
 
 interface QuickMoveProps {
   isOpen: boolean;
@@ -118,15 +119,18 @@ export function QuickMove({ isOpen, onClose, bulkMode = false }: QuickMoveProps)
 
     setMoving(true);
     try {
-      // Get current children count of target
-      let targetChildren = nodes.filter(n => n.parent_id === targetNodeId);
-      let newPosition = targetChildren.length;
+      // Append after the target's current last child
+      const targetChildren = nodes.filter(n => n.parent_id === targetNodeId);
+      let lastPosition = targetChildren.reduce<number | undefined>(
+        (max, n) => (max === undefined ? n.position : Math.max(max, n.position)),
+        undefined
+      );
 
       // Move nodes in order - this maintains their relative ordering
       for (const node of nodesToMove) {
         if (!node) continue;
-        await moveNodeTo(node.id, targetNodeId, newPosition);
-        newPosition++;
+        lastPosition = positionBetween(lastPosition, undefined);
+        await moveNodeTo(node.id, targetNodeId, lastPosition);
       }
 
       // Clear selection after bulk move
@@ -1,4 +1,4 @@
-import type { DocumentState, Node, NodeChanges, NodeType, Operation } from './types';
+import type { Comment, DocumentState, Node, NodeChanges, NodeType, Operation } from './types';
 
 // Check if we're running in Tauri
 let tauriInvoke: ((cmd: string, args?: Record<string, unknown>) => Promise<unknown>) | null | undefined = undefined;
@@ -298,6 +298,40 @@ export async function updateNode(id: string, changes: NodeChanges): Promise<Docu
   return { nodes: [...mockState.nodes] };
 }
 
+// Set (or with null, clear) the color label of several nodes
+export async function bulkRecolor(nodeIds: string[], color: string | null): Promise<DocumentState> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('bulk_recolor', { nodeIds, color }) as Promise<DocumentState>;
+  }
+  const ids = new Set(nodeIds);
+  const now = new Date().toISOString();
+  mockState.nodes = mockState.nodes.map(n =>
+    ids.has(n.id) ? { ...n, color: color ?? undefined, updated_at: now } : n
+  );
+  return { nodes: [...mockState.nodes] };
+}
+
+// A node's note as HTML (rendered Markdown when the document's notes are Markdown)
+export async function renderNote(nodeId: string): Promise<string> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('render_note', { nodeId }) as Promise<string>;
+  }
+  const note = mockState.nodes.find(n => n.id === nodeId)?.note ?? '';
+  return note.replace(/&/g, '&amp;').replace(/</g, '&lt;').replace(/>/g, '&gt;').replace(/\n/g, '<br>');
+}
+
+// Clean pasted HTML down to the editor's markup (create and update commands
+// do this too before saving)
+export async function sanitizeContent(content: string): Promise<string> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('sanitize_content', { content }) as Promise<string>;
+  }
+  return content;
+}
+
 // Move a node to new parent/position
 export async function moveNode(
   id: string,
@@ -346,6 +380,89 @@ export async function deleteNode(id: string): Promise<DocumentState> {
   return { nodes: [...mockState.nodes] };
 }
 
+// Add a comment to a node
+export async function addComment(nodeId: string, text: string): Promise<DocumentState> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('add_comment', { nodeId, text }) as Promise<DocumentState>;
+  }
+  const node = mockState.nodes.find(n => n.id === nodeId);
+  if (node) {
+    const now = new Date().toISOString();
+    node.comments = [...(node.comments ?? []), { id: crypto.randomUUID(), author: 'browser', created_at: now, text }];
+  }
+  return { nodes: [...mockState.nodes] };
+}
+
+// List a node's comments (open ones only unless includeResolved)
+export async function listComments(nodeId: string, includeResolved = false): Promise<Comment[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('list_comments', { nodeId, includeResolved }) as Promise<Comment[]>;
+  }
+  const comments = mockState.nodes.find(n => n.id === nodeId)?.comments ?? [];
+  return comments.filter(c => includeResolved || !c.resolved_at);
+}
+
+// Resolve a comment (or reopen it with resolved = false)
+export async function resolveComment(commentId: string, resolved = true): Promise<DocumentState> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('resolve_comment', { commentId, resolved }) as Promise<DocumentState>;
+  }
+  for (const node of mockState.nodes) {
+    const comment = node.comments?.find(c => c.id === commentId);
+    if (comment) {
+      comment.resolved_at = resolved ? new Date().toISOString() : undefined;
+    }
+  }
+  return { nodes: [...mockState.nodes] };
+}
+
+// Per-node encryption: an encrypted node's content is ciphertext until it's
+// unlocked with its passphrase
+export interface NodeSecret {
+  content: string;
+  note?: string;
+}
+
+export async function encryptNode(nodeId: string, passphrase: string): Promise<DocumentState> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('encrypt_node', { nodeId, passphrase }) as Promise<DocumentState>;
+  }
+  throw new Error('Encrypting nodes requires the desktop app');
+}
+
+export async function unlockNode(nodeId: string, passphrase: string): Promise<NodeSecret> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('unlock_node', { nodeId, passphrase }) as Promise<NodeSecret>;
+  }
+  throw new Error('Encrypted nodes require the desktop app');
+}
+
+export async function updateEncryptedNode(
+  nodeId: string,
+  passphrase: string,
+  content: string,
+  note?: string
+): Promise<DocumentState> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('update_encrypted_node', { nodeId, passphrase, content, note }) as Promise<DocumentState>;
+  }
+  throw new Error('Encrypted nodes require the desktop app');
+}
+
+export async function decryptNode(nodeId: string, passphrase: string): Promise<DocumentState> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('decrypt_node', { nodeId, passphrase }) as Promise<DocumentState>;
+  }
+  throw new Error('Encrypted nodes require the desktop app');
+}
+
 // Compact document (merge pending into state.json)
 export async function compactDocument(): Promise<void> {
   await initTauri();
@@ -375,6 +492,36 @@ export async function reloadIfChanged(): Promise<DocumentState | null> {
   return null;
 }
 
+// A node whose fields differ between two states
+export interface ChangedNode {
+  before: Node;
+  after: Node;
+  fields: string[];
+}
+
+// Remote changes not yet applied to a loaded document
+export interface Divergence {
+  document_id: string;
+  full_reload: boolean;
+  devices: string[];
+  pending_ops: number;
+  diff: {
+    added: Node[];
+    removed: Node[];
+    changed: ChangedNode[];
+  };
+}
+
+// Preview what reloading a document would change (for a "remote changes pending" banner)
+export async function getDivergence(docId: string): Promise<Divergence | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_divergence', { docId }) as Promise<Divergence>;
+  }
+  // Browser-only mode: no external changes possible
+  return null;
+}
+
 // Search result from backend
 export interface SearchResult {
   node_id: string;
@@ -383,6 +530,22 @@ export interface SearchResult {
   note: string | null;
   snippet: string;
   rank: number;
+  document_title: string | null;
+  folder_name: string | null;
+}
+
+// Rejection value of search/searchGrouped. 'syntax' errors come from
+// advanced queries (prefixed with "fts:") and can be shown to the user.
+export interface SearchError {
+  kind: 'syntax' | 'internal';
+  message: string;
+}
+
+export interface SearchResultGroup {
+  document_id: string;
+  document_title: string | null;
+  folder_name: string | null;
+  results: SearchResult[];
 }
 
 // Document info from list_documents
@@ -391,6 +554,64 @@ export interface DocumentInfo {
   title: string;
   node_count: number;
   title_node_id?: string;  // ID of the first root node (for renaming)
+  locked?: boolean;
+}
+
+// Per-document settings, stored in the document's meta.json
+export interface DocumentSettings {
+  default_node_type?: NodeType;  // node type for new nodes
+  completed_to_bottom: boolean;  // move checked items below their siblings
+  journal_date_format?: string;  // strftime format for daily note titles
+  recurring_instances?: boolean;  // completing a recurring task adds a new instance
+  excluded?: boolean;  // private: kept out of global search and the iCal feed
+  markdown_notes?: boolean;  // notes are Markdown, rendered for display and HTML export
+  title_sync?: TitleSync;  // which of meta.json's title and the first root node follows the other
+}
+
+// Which title leads: 'first_node' (meta.json follows the first root node),
+// 'meta' (the first root node follows meta.json) or 'off'
+export type TitleSync = 'first_node' | 'meta' | 'off';
+
+// Payload of the document-renamed event
+export interface DocumentRenamed {
+  document_id: string;
+  title: string;
+}
+
+// Rename a document (its first root node and meta.json's title)
+export async function renameDocument(docId: string, title: string): Promise<void> {
+  await initTauri();
+  if (tauriInvoke) {
+    await tauriInvoke('rename_document', { docId, title });
+  }
+}
+
+// Listen for document titles changing (renames, synced edits to the first
+// root node). Returns a function that stops listening.
+export async function onDocumentRenamed(callback: (renamed: DocumentRenamed) => void): Promise<() => void> {
+  await initTauri();
+  if (!tauriInvoke) {
+    return () => {};
+  }
+  const { listen } = await import('@tauri-apps/api/event');
+  return listen<DocumentRenamed>('document-renamed', (event) => callback(event.payload));
+}
+
+// Get a document's settings
+export async function getDocumentSettings(docId: string): Promise<DocumentSettings> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_document_settings', { docId }) as Promise<DocumentSettings>;
+  }
+  return { completed_to_bottom: false };
+}
+
+// Replace a document's settings
+export async function setDocumentSettings(docId: string, settings: DocumentSettings): Promise<void> {
+  await initTauri();
+  if (tauriInvoke) {
+    await tauriInvoke('set_document_settings', { docId, settings });
+  }
 }
 
 // Search for nodes matching a query
@@ -415,12 +636,186 @@ export async function search(
         note: node.note || null,
         snippet: node.content,
         rank: 0,
+        document_title: null,
+        folder_name: null,
       });
     }
   }
   return results.slice(0, limit || 50);
 }
 
+// Search across documents, grouped per document
+export async function searchGrouped(query: string, limit?: number): Promise<SearchResultGroup[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('search_grouped', { query, limit }) as Promise<SearchResultGroup[]>;
+  }
+  const results = await search(query, undefined, limit);
+  return results.length
+    ? [{ document_id: 'mock-doc', document_title: null, folder_name: null, results }]
+    : [];
+}
+
+// Progress of the background search indexer
+export interface IndexStatus {
+  queued: number;
+  completed: number;
+  current: string | null;  // document being indexed, or "all" during a rebuild
+}
+
+export async function getIndexStatus(): Promise<IndexStatus> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_index_status') as Promise<IndexStatus>;
+  }
+  return { queued: 0, completed: 0, current: null };
+}
+
+// Result of maintain_search_cache
+export interface CacheMaintenanceReport {
+  integrity_errors: string[];
+  rebuilt: boolean;  // cache was discarded and rebuilt from the documents
+  size_before: number;
+  size_after: number;
+}
+
+// Check, optimize and vacuum the search cache
+export async function maintainSearchCache(): Promise<CacheMaintenanceReport> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('maintain_search_cache') as Promise<CacheMaintenanceReport>;
+  }
+  return { integrity_errors: [], rebuilt: false, size_before: 0, size_after: 0 };
+}
+
+export type SearchCacheMode = 'plain' | 'encrypted' | 'disabled';
+
+export interface SearchCacheInfo {
+  mode: SearchCacheMode;
+  encryption_supported: boolean;
+}
+
+export async function getSearchCacheMode(): Promise<SearchCacheInfo> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_search_cache_mode') as Promise<SearchCacheInfo>;
+  }
+  return { mode: 'disabled', encryption_supported: false };
+}
+
+export async function setSearchCacheMode(mode: SearchCacheMode): Promise<SearchCacheInfo> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('set_search_cache_mode', { mode }) as Promise<SearchCacheInfo>;
+  }
+  return { mode, encryption_supported: false };
+}
+
+export async function rekeySearchCache(): Promise<void> {
+  await initTauri();
+  if (tauriInvoke) {
+    await tauriInvoke('rekey_search_cache');
+  }
+}
+
+export interface Suggestion {
+  kind: 'tag' | 'document' | 'recent_search';
+  text: string;
+  document_id: string | null;
+}
+
+// Search box completions: tags, document titles and recent searches
+export async function suggest(prefix: string): Promise<Suggestion[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('suggest', { prefix }) as Promise<Suggestion[]>;
+  }
+  return [];
+}
+
+// Remember a committed search term for suggestions
+export async function recordSearch(term: string): Promise<void> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('record_search', { term }) as Promise<void>;
+  }
+}
+
+// Fetch one node from any document (for previews)
+export async function getNode(docId: string, nodeId: string): Promise<Node | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_node', { docId, nodeId }) as Promise<Node>;
+  }
+  return mockState.nodes.find((n) => n.id === nodeId) ?? null;
+}
+
+// Fetch a node and its descendants (optionally limited to `depth` levels)
+export async function getSubtree(docId: string, nodeId: string, depth?: number): Promise<Node[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_subtree', { docId, nodeId, depth }) as Promise<Node[]>;
+  }
+  return [];
+}
+
+export interface HoistedState {
+  root_id: string;
+  state: DocumentState;
+  breadcrumbs: { id: string; content: string; collapsed: boolean }[];
+}
+
+// Make a document current and return just one branch of it (hoist/zoom)
+export async function loadSubtreeAsRoot(docId: string, nodeId: string): Promise<HoistedState | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('load_subtree_as_root', { docId, nodeId }) as Promise<HoistedState>;
+  }
+  return null;
+}
+
+// A bookmarked node with where it lives
+export interface BookmarkInfo {
+  document_id: string;
+  node_id: string;
+  content: string;
+  breadcrumbs: { id: string; content: string; collapsed: boolean }[];
+  created_at: string;
+}
+
+// Bookmark a node (or remove its bookmark); returns whether it is now bookmarked
+export async function bookmarkNode(docId: string, nodeId: string, bookmarked: boolean): Promise<boolean> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('bookmark_node', { docId, nodeId, bookmarked }) as Promise<boolean>;
+  }
+  return bookmarked;
+}
+
+// Bookmarked nodes across all documents, oldest bookmark first
+export async function listBookmarks(): Promise<BookmarkInfo[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('list_bookmarks') as Promise<BookmarkInfo[]>;
+  }
+  return [];
+}
+
+export interface NodeLocation {
+  document_id: string;
+  ancestors: { id: string; content: string; collapsed: boolean }[];
+  collapsed_ancestor_ids: string[];
+}
+
+// Find a node's document and the ancestors to expand to reveal it
+export async function locateNode(nodeId: string): Promise<NodeLocation | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('locate_node', { nodeId }) as Promise<NodeLocation>;
+  }
+  return null;
+}
+
 // List all documents
 export async function listDocuments(): Promise<DocumentInfo[]> {
   await initTauri();
@@ -456,6 +851,9 @@ export interface BacklinkResult {
   source_node_id: string;
   source_document_id: string;
   content: string;
+  document_title?: string | null;
+  /** Ancestor texts, top level first */
+  breadcrumbs?: string[];
 }
 
 // Get backlinks for a node
@@ -468,6 +866,95 @@ export async function getBacklinks(nodeId: string): Promise<BacklinkResult[]> {
   return [];
 }
 
+// The node a [[title]] wiki link resolves to (by text or alias)
+export interface LinkTarget {
+  node_id: string;
+  document_id: string;
+  content: string;
+  alias: string | null;
+}
+
+// A node naming another node without linking to it
+export interface UnlinkedMention {
+  node_id: string;
+  document_id: string;
+  content: string;
+  matched: string;
+}
+
+// Resolve a wiki link title. Nodes list other names in their `aliases`
+// property (comma-separated).
+export async function resolveWikiLink(title: string): Promise<LinkTarget | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('resolve_wiki_link', { title }) as Promise<LinkTarget | null>;
+  }
+  return null;
+}
+
+// Get nodes that mention a node by name or alias but don't link to it
+export async function getUnlinkedMentions(nodeId: string): Promise<UnlinkedMention[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_unlinked_mentions', { nodeId }) as Promise<UnlinkedMention[]>;
+  }
+  return [];
+}
+
+// A quick switcher (Ctrl+P) result
+export interface SwitchCandidate {
+  kind: 'document' | 'heading' | 'node';
+  document_id: string;
+  node_id: string | null;
+  text: string;
+  document_title: string | null;
+  bookmarked: boolean;
+  score: number;
+}
+
+// Fuzzy-match documents, headings and bookmarks for the quick switcher
+export async function quickSwitchCandidates(query: string): Promise<SwitchCandidate[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('quick_switch_candidates', { query }) as Promise<SwitchCandidate[]>;
+  }
+  return [];
+}
+
+// A document created for a dangling wiki link
+export interface LinkedDocument {
+  doc_id: string;
+  node_id: string;
+  title: string;
+  created: boolean;
+}
+
+// Create the target document of a [[title]] link with no target (or get the
+// node the title already resolves to), recording the link from sourceNodeId
+export async function createFromLink(title: string, sourceNodeId?: string): Promise<LinkedDocument> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('create_from_link', { title, sourceNodeId: sourceNodeId ?? null }) as Promise<LinkedDocument>;
+  }
+  throw new Error('Creating documents from links requires the desktop app');
+}
+
+// Folder for documents created from wiki links (null for the root)
+export async function getLinkFolderSetting(): Promise<string | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_link_folder_setting') as Promise<string | null>;
+  }
+  return null;
+}
+
+export async function setLinkFolderSetting(folderId: string | null): Promise<void> {
+  await initTauri();
+  if (tauriInvoke) {
+    await tauriInvoke('set_link_folder_setting', { folderId });
+  }
+}
+
 // Calculate the next occurrence for a recurring task
 export async function getNextOccurrence(
   rruleStr: string,
@@ -487,7 +974,17 @@ export async function getNextOccurrence(
   return `${year}-${month}-${day}`;
 }
 
-// Generate iCalendar feed for all dated items
+// Complete a recurring task: move it to its next date, or (with the
+// document's recurring_instances setting) check it off and add the next instance
+export async function completeRecurring(nodeId: string): Promise<DocumentState | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('complete_recurring', { nodeId }) as Promise<DocumentState>;
+  }
+  return null;
+}
+
+// Generate iCalendar feed for all dated items
 export async function generateIcalFeed(): Promise<string> {
   await initTauri();
   if (tauriInvoke) {
@@ -512,6 +1009,10 @@ export interface InboxItem {
   capture_date: string;
   captured_at: string;
   source?: string;
+  /** Voice recording, a file name in the data directory's audio folder */
+  audio?: string;
+  /** Still being transcribed; not imported until the transcript is in */
+  transcribing?: boolean;
 }
 
 // Get all inbox items
@@ -524,207 +1025,776 @@ export async function getInbox(): Promise<InboxItem[]> {
   return [];
 }
 
-// Get inbox item count
-export async function getInboxCount(): Promise<number> {
+// Get inbox item count
+export async function getInboxCount(): Promise<number> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_inbox_count') as Promise<number>;
+  }
+  return 0;
+}
+
+// Clear processed inbox items
+export async function clearInboxItems(ids: string[]): Promise<void> {
+  await initTauri();
+  if (tauriInvoke) {
+    await tauriInvoke('clear_inbox_items', { ids });
+    return;
+  }
+  // Browser-only mode: no-op
+}
+
+// Import OPML content into the current document
+export async function importOpml(content: string): Promise<DocumentState> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('import_opml', { content }) as Promise<DocumentState>;
+  }
+  // Browser-only mode: parse OPML client-side (basic implementation)
+  // This is a simplified fallback - real parsing happens in Rust
+  console.warn('OPML import not fully supported in browser-only mode');
+  return mockState;
+}
+
+// Result from importing OPML as a new document
+export interface ImportOpmlResult {
+  doc_id: string;
+  title: string;
+  node_count: number;
+}
+
+// Import OPML content as a new document
+export async function importOpmlAsDocument(content: string): Promise<ImportOpmlResult> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('import_opml_as_document', { content }) as Promise<ImportOpmlResult>;
+  }
+  // Browser-only mode: create mock document
+  console.warn('OPML import as document not fully supported in browser-only mode');
+  return {
+    doc_id: 'mock-import-' + Date.now(),
+    title: 'Imported Document',
+    node_count: 0,
+  };
+}
+
+// One file of an OPML batch import
+export interface BatchImportEntry {
+  name: string;
+  result: ImportOpmlResult | null;
+  error: string | null;
+}
+
+export interface BatchImportReport {
+  folder_id: string;
+  folder_name: string;
+  entries: BatchImportEntry[];
+}
+
+// Import many OPML files (by path and/or content) into a folder named after
+// the batch, one document each
+export async function importOpmlBatch(
+  paths: string[] = [],
+  contents: { name: string; content: string }[] = [],
+  folderName?: string
+): Promise<BatchImportReport> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('import_opml_batch', { paths, contents, folderName: folderName ?? null }) as Promise<BatchImportReport>;
+  }
+  throw new Error('Batch import requires the desktop app');
+}
+
+// Import an Evernote/Apple Notes ENEX export, one document per note
+export async function importEnex(
+  path?: string,
+  content?: string,
+  folderName?: string
+): Promise<BatchImportReport> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('import_enex', { path: path ?? null, content: content ?? null, folderName: folderName ?? null }) as Promise<BatchImportReport>;
+  }
+  throw new Error('ENEX import requires the desktop app');
+}
+
+// Import a folder of plain text files, one document per file
+export async function importTextFolder(path: string, folderName?: string): Promise<BatchImportReport> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('import_text_folder', { path, folderName: folderName ?? null }) as Promise<BatchImportReport>;
+  }
+  throw new Error('Text folder import requires the desktop app');
+}
+
+// Export current document to OPML format (colors as Dynalist color labels unless colors is false)
+export async function exportOpml(title: string, colors?: boolean): Promise<string> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('export_opml', { title, colors }) as Promise<string>;
+  }
+  // Browser-only mode: generate basic OPML
+  return generateMockOpml(title);
+}
+
+// Export current document to Markdown format
+// linkedReferences appends a "Linked references" section (nodes in other
+// documents that link here)
+export async function exportMarkdown(linkedReferences = false): Promise<string> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('export_markdown', { linkedReferences }) as Promise<string>;
+  }
+  // Browser-only mode: generate basic markdown
+  return generateMockMarkdown();
+}
+
+// Export current document to JSON backup format
+export async function exportJson(): Promise<string> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('export_json') as Promise<string>;
+  }
+  // Browser-only mode: return mock state as JSON
+  return JSON.stringify({
+    version: 1,
+    exported_at: new Date().toISOString(),
+    nodes: mockState.nodes,
+  }, null, 2);
+}
+
+// Import JSON backup into the current document
+export async function importJson(content: string): Promise<DocumentState> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('import_json', { content }) as Promise<DocumentState>;
+  }
+  // Browser-only mode: parse JSON and merge into state
+  try {
+    const backup = JSON.parse(content);
+    if (backup.nodes) {
+      mockState.nodes = [...mockState.nodes, ...backup.nodes];
+    }
+    return { ...mockState };
+  } catch {
+    throw new Error('Invalid JSON backup format');
+  }
+}
+
+// Whole-workspace backup: every document plus folders, folder assignments
+// and sidebar order
+export async function exportWorkspace(): Promise<string> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('export_workspace') as Promise<string>;
+  }
+  throw new Error('Workspace backups require the desktop app');
+}
+
+export interface WorkspaceImportReport {
+  documents: ImportOpmlResult[];
+  folders_created: number;
+}
+
+// Restore a workspace backup as new documents, refiled into their folders
+export async function importWorkspace(content: string): Promise<WorkspaceImportReport> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('import_workspace', { content }) as Promise<WorkspaceImportReport>;
+  }
+  throw new Error('Workspace backups require the desktop app');
+}
+
+// Helper: Generate basic OPML for browser-only mode
+function generateMockOpml(title: string): string {
+  const lines: string[] = [
+    '<?xml version="1.0" encoding="UTF-8"?>',
+    '<opml version="2.0">',
+    '<head>',
+    `<title>${escapeXml(title)}</title>`,
+    '</head>',
+    '<body>',
+  ];
+
+  function addNode(node: Node, indent: number) {
+    const spaces = '  '.repeat(indent);
+    const text = escapeXml(stripHtml(node.content));
+    const children = mockState.nodes.filter(n => n.parent_id === node.id);
+
+    if (children.length > 0) {
+      lines.push(`${spaces}<outline text="${text}">`);
+      children.sort((a, b) => a.position - b.position);
+      for (const child of children) {
+        addNode(child, indent + 1);
+      }
+      lines.push(`${spaces}</outline>`);
+    } else {
+      lines.push(`${spaces}<outline text="${text}"/>`);
+    }
+  }
+
+  const roots = mockState.nodes.filter(n => !n.parent_id);
+  roots.sort((a, b) => a.position - b.position);
+  for (const root of roots) {
+    addNode(root, 1);
+  }
+
+  lines.push('</body>');
+  lines.push('</opml>');
+  return lines.join('\n');
+}
+
+// Helper: Generate basic Markdown for browser-only mode
+function generateMockMarkdown(): string {
+  const lines: string[] = [];
+
+  function addNode(node: Node, depth: number) {
+    const indent = '  '.repeat(depth);
+    const text = stripHtml(node.content);
+    const bullet = node.is_checked ? '- [x]' : (node.node_type === 'checkbox' ? '- [ ]' : '-');
+    lines.push(`${indent}${bullet} ${text}`);
+
+    if (node.note) {
+      const noteIndent = '  '.repeat(depth + 1);
+      lines.push(`${noteIndent}${node.note}`);
+    }
+
+    const children = mockState.nodes.filter(n => n.parent_id === node.id);
+    children.sort((a, b) => a.position - b.position);
+    for (const child of children) {
+      addNode(child, depth + 1);
+    }
+  }
+
+  const roots = mockState.nodes.filter(n => !n.parent_id);
+  roots.sort((a, b) => a.position - b.position);
+  for (const root of roots) {
+    addNode(root, 0);
+  }
+
+  return lines.join('\n');
+}
+
+// Helper: Strip HTML tags
+function stripHtml(html: string): string {
+  return html.replace(/<[^>]*>/g, '')
+    .replace(/&nbsp;/g, ' ')
+    .replace(/&amp;/g, '&')
+    .replace(/&lt;/g, '<')
+    .replace(/&gt;/g, '>')
+    .replace(/&quot;/g, '"')
+    .trim();
+}
+
+// Helper: Escape XML special characters
+function escapeXml(str: string): string {
+  return str
+    .replace(/&/g, '&amp;')
+    .replace(/</g, '&lt;')
+    .replace(/>/g, '&gt;')
+    .replace(/"/g, '&quot;')
+    .replace(/'/g, '&apos;');
+}
+
+// Data directory info from backend
+export interface DataDirectoryInfo {
+  current: string;
+  default: string;
+  is_custom: boolean;
+}
+
+// Result of health_check
+export interface HealthReport {
+  data_dir: {
+    path: string;
+    writable: boolean;
+    free_bytes: number | null;
+    low_disk_space: boolean;
+    document_count: number;
+    broken_documents: { document_id: string; error: string }[];
+  };
+  search_index: { available: boolean; errors: string[] };
+  watcher: { running: boolean; watched_dir: string | null };
+}
+
+// Check the data directory, search index and watcher
+export async function healthCheck(): Promise<HealthReport | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('health_check') as Promise<HealthReport>;
+  }
+  return null;
+}
+
+// An op line skipped (and moved to a .corrupt file) by repair_document
+export interface SkippedOp {
+  file: string;
+  line: number;
+  error: string;
+}
+
+// Load a document past malformed op lines, quarantining them
+export async function repairDocument(docId: string): Promise<SkippedOp[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('repair_document', { docId }) as Promise<SkippedOp[]>;
+  }
+  return [];
+}
+
+// Another instance in a collaboration session ("collab-peers" events carry a list of these)
+export interface CollabPeer {
+  device: string;
+  connected_at: string;
+  // Document and node the peer last changed
+  document_id: string | null;
+  node_id: string | null;
+}
+
+export interface CollabStatus {
+  role: 'host' | 'client' | null;
+  address: string | null;
+  // Token peers must present (host only)
+  token: string | null;
+  // The document the session shares
+  document_id: string | null;
+  connected: boolean;
+  peers: CollabPeer[];
+}
+
+const COLLAB_STOPPED: CollabStatus = { role: null, address: null, token: null, document_id: null, connected: false, peers: [] };
+
+// Host a live collaboration session on one document for other instances to join
+// (a token is generated if none is given)
+export async function startCollabServer(docId: string, port: number, token?: string): Promise<CollabStatus> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('start_collab_server', { docId, port, token }) as Promise<CollabStatus>;
+  }
+  throw new Error('Collaboration requires the desktop app');
+}
+
+// Join a session hosted by another instance (ws://host:port)
+export async function connectCollab(url: string, token?: string): Promise<CollabStatus> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('connect_collab', { url, token }) as Promise<CollabStatus>;
+  }
+  throw new Error('Collaboration requires the desktop app');
+}
+
+// Leave (or stop hosting) the collaboration session
+export async function stopCollab(): Promise<void> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('stop_collab') as Promise<void>;
+  }
+}
+
+export async function getCollabStatus(): Promise<CollabStatus> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_collab_status') as Promise<CollabStatus>;
+  }
+  return COLLAB_STOPPED;
+}
+
+// Where another device is in the outline ("presence-changed" events carry a list of these)
+export interface Presence {
+  device: string;
+  document_id: string | null;
+  node_id: string | null;
+  updated_at: string;
+}
+
+// Share which document is open and which node is focused
+export async function updatePresence(documentId: string | null, nodeId: string | null): Promise<void> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('update_presence', { documentId, nodeId }) as Promise<void>;
+  }
+}
+
+// Where the other devices are, optionally only those in one document
+export async function getPresence(documentId?: string): Promise<Presence[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_presence', { documentId }) as Promise<Presence[]>;
+  }
+  return [];
+}
+
+// Mount the documents as Markdown files (FUSE, desktop builds with the fuse feature)
+export async function mountDocuments(path: string): Promise<void> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('mount_documents', { path }) as Promise<void>;
+  }
+  throw new Error('Mounting documents requires the desktop app');
+}
+
+export async function unmountDocuments(): Promise<void> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('unmount_documents') as Promise<void>;
+  }
+}
+
+export async function getMountPoint(): Promise<string | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_mount_point') as Promise<string | null>;
+  }
+  return null;
+}
+
+// Markdown weekly review (completed, created and overdue tasks, most-edited
+// documents); dates are YYYY-MM-DD or RFC 3339, defaulting to the last 7 days
+export async function generateReview(start?: string, end?: string): Promise<string> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('generate_review', { range: { start, end } }) as Promise<string>;
+  }
+  return '';
+}
+
+// Tasks completed in a period across all documents, grouped by date (for
+// standups or invoicing); same range defaults as generateReview
+export async function exportCompleted(
+  start?: string,
+  end?: string,
+  format: 'markdown' | 'text' | 'csv' = 'markdown'
+): Promise<string> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('export_completed', { range: { start, end }, format }) as Promise<string>;
+  }
+  return '';
+}
+
+// The time-tracking timer running on this machine
+export interface RunningTimer {
+  document_id: string;
+  node_id: string;
+  started_at: string;
+}
+
+export interface TimeEntry {
+  node_id: string;
+  started_at: string;
+  ended_at: string;
+  device: string;
+}
+
+export interface TimeReportRow {
+  key: string;
+  label: string;
+  document_id: string | null;
+  seconds: number;
+  hours: number;
+}
+
+// Start timing a node (in the current document unless docId is given)
+export async function startTimer(nodeId: string, docId?: string): Promise<RunningTimer | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('start_timer', { nodeId, docId }) as Promise<RunningTimer>;
+  }
+  return null;
+}
+
+// Stop the running timer, returning the recorded entry
+export async function stopTimer(): Promise<TimeEntry | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('stop_timer') as Promise<TimeEntry | null>;
+  }
+  return null;
+}
+
+export async function getRunningTimer(): Promise<RunningTimer | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_running_timer') as Promise<RunningTimer | null>;
+  }
+  return null;
+}
+
+// Hours spent over a period (YYYY-MM-DD or RFC 3339 bounds), by node, tag or document
+export async function getTimeReport(
+  range: { start?: string; end?: string } = {},
+  groupBy: 'node' | 'tag' | 'document' = 'node'
+): Promise<TimeReportRow[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_time_report', { range, groupBy }) as Promise<TimeReportRow[]>;
+  }
+  return [];
+}
+
+// The running pomodoro ("pomodoro-tick" / "pomodoro-finished" events carry this)
+export interface PomodoroStatus {
+  id: string;
+  document_id: string;
+  node_id: string;
+  minutes: number;
+  started_at: string;
+  remaining_seconds: number;
+}
+
+export interface PomodoroStats {
+  total: number;
+  days: { date: string; count: number; minutes: number }[];
+  by_node: Record<string, number>;
+}
+
+// Start a pomodoro on a node (default 25 minutes)
+export async function startPomodoro(nodeId: string, minutes?: number, docId?: string): Promise<PomodoroStatus | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('start_pomodoro', { nodeId, minutes, docId }) as Promise<PomodoroStatus>;
+  }
+  return null;
+}
+
+// Cancel the running pomodoro without logging it
+export async function stopPomodoro(): Promise<boolean> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('stop_pomodoro') as Promise<boolean>;
+  }
+  return false;
+}
+
+export async function getPomodoroStatus(): Promise<PomodoroStatus | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_pomodoro_status') as Promise<PomodoroStatus | null>;
+  }
+  return null;
+}
+
+// Daily counts of completed pomodoros, optionally for one document or node
+export async function getPomodoroStats(
+  range: { start?: string; end?: string } = {},
+  docId?: string,
+  nodeId?: string
+): Promise<PomodoroStats> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_pomodoro_stats', { range, docId, nodeId }) as Promise<PomodoroStats>;
+  }
+  return { total: 0, days: [], by_node: {} };
+}
+
+export type OverdueScope =
+  | { type: 'all' }
+  | { type: 'tag'; value: string }
+  | { type: 'document'; value: string };
+
+export type OverdueAction = { type: 'roll_to_today' } | { type: 'add_tag'; tag: string };
+
+export interface OverdueRule {
+  id: string;  // empty for a new rule
+  name: string;
+  scope: OverdueScope;
+  action: OverdueAction;
+  enabled: boolean;
+}
+
+export interface OverdueChange {
+  rule_id: string;
+  rule_name: string;
+  document_id: string;
+  node_id: string;
+  content: string;
+  date: string;  // the task's date before the run
+}
+
+// Sent with the overdue-rules-applied event by the daily run
+export interface OverdueReport {
+  date: string;
+  changes: OverdueChange[];
+}
+
+export async function getOverdueRules(): Promise<OverdueRule[]> {
   await initTauri();
   if (tauriInvoke) {
-    return tauriInvoke('get_inbox_count') as Promise<number>;
+    return tauriInvoke('get_overdue_rules') as Promise<OverdueRule[]>;
   }
-  return 0;
+  return [];
 }
 
-// Clear processed inbox items
-export async function clearInboxItems(ids: string[]): Promise<void> {
+export async function setOverdueRules(rules: OverdueRule[]): Promise<OverdueRule[]> {
   await initTauri();
   if (tauriInvoke) {
-    await tauriInvoke('clear_inbox_items', { ids });
-    return;
+    return tauriInvoke('set_overdue_rules', { rules }) as Promise<OverdueRule[]>;
   }
-  // Browser-only mode: no-op
+  return rules;
 }
 
-// Import OPML content into the current document
-export async function importOpml(content: string): Promise<DocumentState> {
+// Apply the overdue rules to every document now
+export async function runOverdueRules(): Promise<OverdueReport> {
   await initTauri();
   if (tauriInvoke) {
-    return tauriInvoke('import_opml', { content }) as Promise<DocumentState>;
+    return tauriInvoke('run_overdue_rules') as Promise<OverdueReport>;
   }
-  // Browser-only mode: parse OPML client-side (basic implementation)
-  // This is a simplified fallback - real parsing happens in Rust
-  console.warn('OPML import not fully supported in browser-only mode');
-  return mockState;
+  return { date: '', changes: [] };
 }
 
-// Result from importing OPML as a new document
-export interface ImportOpmlResult {
-  doc_id: string;
+// A read-only share link: the thin server serves it at /share/<token>
+export interface Share {
+  token: string;
+  document_id: string;
+  node_id?: string;
   title: string;
-  node_count: number;
+  created_at: string;
 }
 
-// Import OPML content as a new document
-export async function importOpmlAsDocument(content: string): Promise<ImportOpmlResult> {
+// Share a document, or the subtree of one of its nodes
+export async function createShare(docId: string, nodeId?: string): Promise<Share> {
   await initTauri();
   if (tauriInvoke) {
-    return tauriInvoke('import_opml_as_document', { content }) as Promise<ImportOpmlResult>;
+    return tauriInvoke('create_share', { docId, nodeId: nodeId ?? null }) as Promise<Share>;
   }
-  // Browser-only mode: create mock document
-  console.warn('OPML import as document not fully supported in browser-only mode');
-  return {
-    doc_id: 'mock-import-' + Date.now(),
-    title: 'Imported Document',
-    node_count: 0,
-  };
+  throw new Error('Sharing requires the desktop app');
 }
 
-// Export current document to OPML format
-export async function exportOpml(title: string): Promise<string> {
+export async function revokeShare(token: string): Promise<void> {
   await initTauri();
   if (tauriInvoke) {
-    return tauriInvoke('export_opml', { title }) as Promise<string>;
+    await tauriInvoke('revoke_share', { token });
   }
-  // Browser-only mode: generate basic OPML
-  return generateMockOpml(title);
 }
 
-// Export current document to Markdown format
-export async function exportMarkdown(): Promise<string> {
+export async function listShares(docId?: string): Promise<Share[]> {
   await initTauri();
   if (tauriInvoke) {
-    return tauriInvoke('export_markdown') as Promise<string>;
+    return tauriInvoke('list_shares', { docId: docId ?? null }) as Promise<Share[]>;
   }
-  // Browser-only mode: generate basic markdown
-  return generateMockMarkdown();
+  return [];
 }
 
-// Export current document to JSON backup format
-export async function exportJson(): Promise<string> {
+// A text snippet: typing the trigger (e.g. ";today") inserts the expansion,
+// with template placeholders like {{date}} filled in
+export interface Snippet {
+  trigger: string;
+  expansion: string;
+  description?: string;
+}
+
+export async function listSnippets(): Promise<Snippet[]> {
   await initTauri();
   if (tauriInvoke) {
-    return tauriInvoke('export_json') as Promise<string>;
+    return tauriInvoke('list_snippets') as Promise<Snippet[]>;
   }
-  // Browser-only mode: return mock state as JSON
-  return JSON.stringify({
-    version: 1,
-    exported_at: new Date().toISOString(),
-    nodes: mockState.nodes,
-  }, null, 2);
+  return [];
 }
 
-// Import JSON backup into the current document
-export async function importJson(content: string): Promise<DocumentState> {
+// Replace the snippets (stored in the data directory)
+export async function setSnippets(snippets: Snippet[]): Promise<Snippet[]> {
   await initTauri();
   if (tauriInvoke) {
-    return tauriInvoke('import_json', { content }) as Promise<DocumentState>;
-  }
-  // Browser-only mode: parse JSON and merge into state
-  try {
-    const backup = JSON.parse(content);
-    if (backup.nodes) {
-      mockState.nodes = [...mockState.nodes, ...backup.nodes];
-    }
-    return { ...mockState };
-  } catch {
-    throw new Error('Invalid JSON backup format');
+    return tauriInvoke('set_snippets', { snippets }) as Promise<Snippet[]>;
   }
+  return snippets;
 }
 
-// Helper: Generate basic OPML for browser-only mode
-function generateMockOpml(title: string): string {
-  const lines: string[] = [
-    '<?xml version="1.0" encoding="UTF-8"?>',
-    '<opml version="2.0">',
-    '<head>',
-    `<title>${escapeXml(title)}</title>`,
-    '</head>',
-    '<body>',
-  ];
+// Expand a snippet trigger; null if there is no such snippet
+export async function expandSnippet(trigger: string): Promise<string | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('expand_snippet', { trigger }) as Promise<string | null>;
+  }
+  return null;
+}
 
-  function addNode(node: Node, indent: number) {
-    const spaces = '  '.repeat(indent);
-    const text = escapeXml(stripHtml(node.content));
-    const children = mockState.nodes.filter(n => n.parent_id === node.id);
+// A misspelled word; start/end index into the checked string
+export interface Misspelling {
+  word: string;
+  start: number;
+  end: number;
+  suggestions: string[];
+}
 
-    if (children.length > 0) {
-      lines.push(`${spaces}<outline text="${text}">`);
-      children.sort((a, b) => a.position - b.position);
-      for (const child of children) {
-        addNode(child, indent + 1);
-      }
-      lines.push(`${spaces}</outline>`);
-    } else {
-      lines.push(`${spaces}<outline text="${text}"/>`);
-    }
+// Spellcheck text against the Hunspell dictionary and the personal dictionary
+export async function checkText(text: string): Promise<Misspelling[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('check_text', { text }) as Promise<Misspelling[]>;
   }
+  return [];
+}
 
-  const roots = mockState.nodes.filter(n => !n.parent_id);
-  roots.sort((a, b) => a.position - b.position);
-  for (const root of roots) {
-    addNode(root, 1);
+// Add a word to the personal dictionary (synced with the data directory)
+export async function addToDictionary(word: string): Promise<void> {
+  await initTauri();
+  if (tauriInvoke) {
+    await tauriInvoke('add_to_dictionary', { word });
   }
-
-  lines.push('</body>');
-  lines.push('</opml>');
-  return lines.join('\n');
 }
 
-// Helper: Generate basic Markdown for browser-only mode
-function generateMockMarkdown(): string {
-  const lines: string[] = [];
-
-  function addNode(node: Node, depth: number) {
-    const indent = '  '.repeat(depth);
-    const text = stripHtml(node.content);
-    const bullet = node.is_checked ? '- [x]' : (node.node_type === 'checkbox' ? '- [ ]' : '-');
-    lines.push(`${indent}${bullet} ${text}`);
-
-    if (node.note) {
-      const noteIndent = '  '.repeat(depth + 1);
-      lines.push(`${noteIndent}${node.note}`);
-    }
-
-    const children = mockState.nodes.filter(n => n.parent_id === node.id);
-    children.sort((a, b) => a.position - b.position);
-    for (const child of children) {
-      addNode(child, depth + 1);
-    }
+export async function getPersonalDictionary(): Promise<string[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_personal_dictionary') as Promise<string[]>;
   }
+  return [];
+}
 
-  const roots = mockState.nodes.filter(n => !n.parent_id);
-  roots.sort((a, b) => a.position - b.position);
-  for (const root of roots) {
-    addNode(root, 0);
+// Title and favicon of a web page, for turning a pasted URL into a titled link
+export interface LinkMetadata {
+  url: string;
+  title: string | null;
+  favicon: string | null;
+  markdown: string;
+  html: string;
+}
+
+export async function fetchLinkMetadata(url: string): Promise<LinkMetadata> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('fetch_link_metadata', { url }) as Promise<LinkMetadata>;
   }
+  const escaped = url.replace(/&/g, '&amp;').replace(/</g, '&lt;').replace(/>/g, '&gt;').replace(/"/g, '&quot;');
+  return { url, title: null, favicon: null, markdown: url, html: `<a href="${escaped}">${escaped}</a>` };
+}
 
-  return lines.join('\n');
+// Recent timings of one kind of operation (e.g. "document.load")
+export interface PerfStat {
+  operation: string;
+  count: number;
+  p50_ms: number;
+  p95_ms: number;
+  max_ms: number;
+  last_ms: number;
 }
 
-// Helper: Strip HTML tags
-function stripHtml(html: string): string {
-  return html.replace(/<[^>]*>/g, '')
-    .replace(/&nbsp;/g, ' ')
-    .replace(/&amp;/g, '&')
-    .replace(/&lt;/g, '<')
-    .replace(/&gt;/g, '>')
-    .replace(/&quot;/g, '"')
-    .trim();
+// Create a synthetic document for performance testing (dev only)
+export async function generateTestDocument(nodeCount: number, depth: number): Promise<ImportOpmlResult | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('generate_test_document', { nodeCount, depth }) as Promise<ImportOpmlResult>;
+  }
+  return null;
 }
 
-// Helper: Escape XML special characters
-function escapeXml(str: string): string {
-  return str
-    .replace(/&/g, '&amp;')
-    .replace(/</g, '&lt;')
-    .replace(/>/g, '&gt;')
-    .replace(/"/g, '&quot;')
-    .replace(/'/g, '&apos;');
+// Get recent load/index/compact/export timings
+export async function getPerfStats(): Promise<PerfStat[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_perf_stats') as Promise<PerfStat[]>;
+  }
+  return [];
 }
 
-// Data directory info from backend
-export interface DataDirectoryInfo {
-  current: string;
-  default: string;
-  is_custom: boolean;
+// Get the most recent log lines, oldest first
+export async function getRecentLogs(lines?: number): Promise<string[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_recent_logs', { lines }) as Promise<string[]>;
+  }
+  return [];
 }
 
 // Get the current data directory configuration
@@ -755,6 +1825,70 @@ export async function setDataDirectory(path: string | null): Promise<DataDirecto
   };
 }
 
+// Progress payload of the data-migration-progress event
+export interface MigrationProgress {
+  copied: number;
+  total: number;
+  current: string;
+}
+
+// Copy (or move) all data to a new directory and switch to it
+export async function migrateDataDirectory(
+  newPath: string,
+  moveFiles: boolean
+): Promise<DataDirectoryInfo> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('migrate_data_directory', { newPath, moveFiles }) as Promise<DataDirectoryInfo>;
+  }
+  return setDataDirectory(newPath);
+}
+
+// When op and state files are fsynced to disk
+export type FsyncPolicy = 'always' | 'on_compact' | 'never';
+
+export async function getFsyncPolicy(): Promise<FsyncPolicy> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_fsync_policy') as Promise<FsyncPolicy>;
+  }
+  return 'always';
+}
+
+export async function setFsyncPolicy(policy: FsyncPolicy): Promise<void> {
+  await initTauri();
+  if (tauriInvoke) {
+    await tauriInvoke('set_fsync_policy', { policy });
+  }
+}
+
+// MQTT broker that task, tag and agenda events are published to
+export interface MqttConfig {
+  broker: string;
+  client_id?: string;
+  username?: string;
+  password?: string;
+  topic_prefix: string;
+  tags: string[];
+  agenda: boolean;
+}
+
+export async function getMqttConfig(): Promise<MqttConfig | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_mqtt_config') as Promise<MqttConfig | null>;
+  }
+  return null;
+}
+
+// Pass null to stop publishing
+export async function setMqttConfig(config: MqttConfig | null): Promise<void> {
+  await initTauri();
+  if (tauriInvoke) {
+    await tauriInvoke('set_mqtt_config', { config });
+  }
+}
+
 // Open a directory picker dialog and return the selected path
 export async function pickDirectory(): Promise<string | null> {
   await initTauri();
@@ -822,6 +1956,8 @@ export interface Folder {
   name: string;
   position: number;
   collapsed: boolean;
+  color?: string;
+  icon?: string;
 }
 
 // State of all folders and document assignments
@@ -829,6 +1965,13 @@ export interface FolderState {
   folders: Folder[];
   document_folders: Record<string, string>; // doc_id -> folder_id
   document_order: Record<string, string[]>; // folder_id -> [doc_id, ...], "__root__" for root level
+  favorites?: Favorite[];
+}
+
+// A pinned document, or a pinned node within a document
+export interface Favorite {
+  document_id: string;
+  node_id?: string;
 }
 
 // Get all folders and document-folder assignments
@@ -845,6 +1988,17 @@ export async function getFolders(): Promise<FolderState> {
   };
 }
 
+// Listen for folders changed on another machine and synced in. Returns a
+// function that stops listening.
+export async function onFoldersChanged(callback: () => void): Promise<() => void> {
+  await initTauri();
+  if (!tauriInvoke) {
+    return () => {};
+  }
+  const { listen } = await import('@tauri-apps/api/event');
+  return listen('folders-changed', () => callback());
+}
+
 // Create a new folder
 export async function createFolder(name: string): Promise<Folder> {
   await initTauri();
@@ -864,11 +2018,13 @@ export async function createFolder(name: string): Promise<Folder> {
 export async function updateFolder(
   id: string,
   name?: string,
-  collapsed?: boolean
+  collapsed?: boolean,
+  color?: string,
+  icon?: string
 ): Promise<Folder> {
   await initTauri();
   if (tauriInvoke) {
-    return tauriInvoke('update_folder', { id, name, collapsed }) as Promise<Folder>;
+    return tauriInvoke('update_folder', { id, name, collapsed, color, icon }) as Promise<Folder>;
   }
   // Browser-only mode: return mock folder
   return {
@@ -914,6 +2070,33 @@ export async function reorderFolders(folderIds: string[]): Promise<void> {
   // Browser-only mode: no-op
 }
 
+export interface FolderSummary {
+  folder_id: string;
+  document_count: number;
+  node_count: number;
+  overdue_count: number;
+}
+
+export async function getFolderSummaries(): Promise<FolderSummary[]> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_folder_summaries') as Promise<FolderSummary[]>;
+  }
+  return [];
+}
+
+export async function exportFolder(
+  folderId: string,
+  format: 'markdown' | 'opml',
+  linkedReferences = false
+): Promise<string | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('export_folder', { folderId, format, linkedReferences }) as Promise<string | null>;
+  }
+  return null;
+}
+
 // ============================================================================
 // Export Selection
 // ============================================================================
@@ -1099,3 +2282,50 @@ export async function importInboxItems(): Promise<number> {
   // Browser-only mode: return 0
   return 0;
 }
+
+// Voice capture: recordings are transcribed in the background by the
+// configured engine and a transcription-finished event is emitted
+export type TranscriptionConfig =
+  | { engine: 'whisper_cpp'; binary?: string; model: string; language?: string }
+  | { engine: 'api'; url?: string; api_key?: string; model?: string; language?: string };
+
+export interface TranscriptionFinished {
+  audio: string;
+  inbox_item_id: string | null;
+  document_id: string | null;
+  node_id: string | null;
+  transcript: string | null;
+  error: string | null;
+}
+
+export async function captureAudio(data: Uint8Array, extension: string): Promise<InboxItem> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('capture_audio', { data: Array.from(data), extension }) as Promise<InboxItem>;
+  }
+  throw new Error('Voice capture requires the desktop or mobile app');
+}
+
+export async function transcribeNodeAudio(documentId: string, nodeId: string, path: string): Promise<string> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('transcribe_node_audio', { documentId, nodeId, path }) as Promise<string>;
+  }
+  throw new Error('Transcription requires the desktop app');
+}
+
+export async function getTranscriptionConfig(): Promise<TranscriptionConfig | null> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('get_transcription_config') as Promise<TranscriptionConfig | null>;
+  }
+  return null;
+}
+
+export async function setTranscriptionConfig(config: TranscriptionConfig | null): Promise<void> {
+  await initTauri();
+  if (tauriInvoke) {
+    return tauriInvoke('set_transcription_config', { config }) as Promise<void>;
+  }
+  throw new Error('Transcription settings require the desktop app');
+}
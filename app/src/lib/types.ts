@@ -1,27 +1,41 @@
 // Types matching Rust data structures
 
-export type NodeType = 'bullet' | 'checkbox' | 'heading';
+export type NodeType = 'bullet' | 'checkbox' | 'heading' | 'numbered' | 'quote' | 'divider' | 'code_block';
 
 export interface Node {
   id: string;
   parent_id: string | null;
-  position: number;
+  position: number;  // Fractional sort key among siblings (may be non-integer)
   content: string;
   note?: string;
   node_type: NodeType;
   heading_level?: number;
+  language?: string;  // Syntax language for code_block nodes
   is_checked: boolean;
+  priority?: number;  // 1 (highest) to 4 (low)
   color?: string;
   tags?: string[];
+  properties?: Record<string, string>;
   date?: string;
   date_recurrence?: string;
   recurrence?: string;  // Alias for date_recurrence (used by UI)
+  remind_at?: string;  // Reminder time (RFC 3339), independent of the due date
   collapsed: boolean;
   mirror_source_id?: string;
+  comments?: Comment[];
+  encrypted?: boolean;  // content holds ciphertext; see unlockNode
   created_at: string;
   updated_at: string;
 }
 
+export interface Comment {
+  id: string;
+  author: string;  // Device (hostname) the comment was written on
+  created_at: string;
+  text: string;
+  resolved_at?: string;
+}
+
 export interface DocumentState {
   nodes: Node[];
 }
@@ -31,14 +45,19 @@ export interface NodeChanges {
   note?: string;
   node_type?: NodeType;
   heading_level?: number;
+  language?: string;  // Empty string clears
   is_checked?: boolean;
+  priority?: number;  // 0 clears
   color?: string;
   tags?: string[];
+  properties?: Record<string, string>;  // Merged; empty value removes the key
   date?: string;
   date_recurrence?: string;
   recurrence?: string;  // Alias for date_recurrence (used by UI)
+  remind_at?: string;  // Empty string clears
   collapsed?: boolean;
   mirror_source_id?: string;
+  encrypted?: boolean;
 }
 
 // Operation types matching Rust
@@ -69,6 +88,19 @@ export type Operation =
       op: 'delete';
       id: string;
       updated_at: string;
+    }
+  | {
+      op: 'add_comment';
+      node_id: string;
+      comment: Comment;
+      updated_at: string;
+    }
+  | {
+      op: 'resolve_comment';
+      node_id: string;
+      comment_id: string;
+      resolved: boolean;
+      updated_at: string;
     };
 
 // UI state for tree view
@@ -14,3 +14,19 @@ export function stripHtml(html: string): string {
     .replace(/&quot;/g, '"')
     .trim();
 }
+
+/**
+ * Get a position that sorts strictly between two sibling positions, so a
+ * node can be placed without renumbering its siblings. Mirrors the backend's
+ * `position_between`.
+ *
+ * @param before - Position of the sibling before, if any
+ * @param after - Position of the sibling after, if any
+ * @returns The new node's position
+ */
+export function positionBetween(before?: number, after?: number): number {
+  if (before !== undefined && after !== undefined) return before + (after - before) / 2;
+  if (before !== undefined) return Math.floor(before) + 1;
+  if (after !== undefined) return Math.ceil(after) - 1;
+  return 0;
+}
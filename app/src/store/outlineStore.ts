@@ -1,6 +1,7 @@
 import { create } from 'zustand';
 import type { Node, TreeNode, DocumentState, UndoEntry, UndoAction, NodeChanges } from '../lib/types';
 import * as api from '../lib/api';
+import { positionBetween } from '../lib/utils';
 
 // Constants
 const MAX_UNDO_STACK_SIZE = 100;
@@ -730,15 +731,10 @@ export const useOutlineStore = create<OutlineState>((set, get) => ({
 
     const siblings = getSiblings(nodeId);
     const idx = siblings.findIndex(n => n.id === nodeId);
-    const newPosition = idx + 1;
+    const newPosition = positionBetween(node.position, siblings[idx + 1]?.position);
 
     set(s => ({ pendingOperations: s.pendingOperations + 1 }));
     try {
-      // Shift siblings after insertion point
-      for (let i = idx + 1; i < siblings.length; i++) {
-        await api.moveNode(siblings[i].id, node.parent_id, i + 1);
-      }
-
       const result = await api.createNode(node.parent_id, newPosition, '');
       updateFromState(result.state);
       set({ focusedId: result.id });
@@ -775,23 +771,15 @@ export const useOutlineStore = create<OutlineState>((set, get) => ({
       const siblings = getSiblings(afterNodeId);
       const anchorIdx = siblings.findIndex(n => n.id === afterNodeId);
 
-      // Count top-level items to create
-      const topLevelCount = items.filter(i => i.indent === 0).length;
-
-      // Shift siblings after insertion point
-      for (let i = anchorIdx + 1; i < siblings.length; i++) {
-        await api.moveNode(siblings[i].id, anchorNode.parent_id, siblings[i].position + topLevelCount);
-      }
-
       // Track the most recent node at each indent level
       const lastNodeAtLevel = new Map<number, string>();
       lastNodeAtLevel.set(0, afterNodeId);
 
-      // Track position within each parent
-      const positionByParent = new Map<string | null, number>();
-      positionByParent.set(anchorNode.parent_id, anchorIdx + 1);
+      // Track the positions each parent's next item goes between
+      const boundsByParent = new Map<string | null, [number | undefined, number | undefined]>();
+      boundsByParent.set(anchorNode.parent_id, [anchorNode.position, siblings[anchorIdx + 1]?.position]);
       const existingChildren = childrenOf(afterNodeId);
-      positionByParent.set(afterNodeId, existingChildren.length);
+      boundsByParent.set(afterNodeId, [existingChildren[existingChildren.length - 1]?.position, undefined]);
 
       let firstCreatedId: string | null = null;
 
@@ -804,7 +792,8 @@ export const useOutlineStore = create<OutlineState>((set, get) => ({
           parentId = lastNodeAtLevel.get(item.indent - 1) ?? anchorNode.parent_id;
         }
 
-        const position = positionByParent.get(parentId) ?? 0;
+        const [before, after] = boundsByParent.get(parentId) ?? [undefined, undefined];
+        const position = positionBetween(before, after);
         const createResult = await api.createNode(parentId, position, item.content);
         let finalState = createResult.state;
 
@@ -824,7 +813,7 @@ export const useOutlineStore = create<OutlineState>((set, get) => ({
           lastNodeAtLevel.delete(level);
         }
 
-        positionByParent.set(parentId, position + 1);
+        boundsByParent.set(parentId, [position, after]);
         updateFromState(finalState);
       }
 
@@ -848,7 +837,7 @@ export const useOutlineStore = create<OutlineState>((set, get) => ({
 
     const siblings = getSiblings(nodeId);
     const idx = siblings.findIndex(n => n.id === nodeId);
-    const newPosition = idx + 1;
+    const newPosition = positionBetween(node.position, siblings[idx + 1]?.position);
 
     // Get children to move to new node
     const children = childrenOf(nodeId);
@@ -862,17 +851,12 @@ export const useOutlineStore = create<OutlineState>((set, get) => ({
       // Update current node with "before" content
       await api.updateNode(nodeId, { content: beforeContent });
 
-      // Shift siblings after insertion point
-      for (let i = idx + 1; i < siblings.length; i++) {
-        await api.moveNode(siblings[i].id, node.parent_id, i + 1);
-      }
-
       // Create new node with "after" content
       const result = await api.createNode(node.parent_id, newPosition, afterContent);
 
       // Move children from original node to new node
       for (let i = 0; i < children.length; i++) {
-        await api.moveNode(children[i].id, result.id, i);
+        await api.moveNode(children[i].id, result.id, children[i].position);
       }
 
       // Reload to get final state after all moves
@@ -1408,7 +1392,8 @@ export const useOutlineStore = create<OutlineState>((set, get) => ({
     const oldParentId = node.parent_id;
     const oldPosition = node.position;
     const newParent = siblings[idx - 1];
-    const newPosition = childrenOf(newParent.id).length;
+    const newParentChildren = childrenOf(newParent.id);
+    const newPosition = positionBetween(newParentChildren[newParentChildren.length - 1]?.position, undefined);
 
     set(s => ({ pendingOperations: s.pendingOperations + 1 }));
     try {
@@ -1454,7 +1439,7 @@ export const useOutlineStore = create<OutlineState>((set, get) => ({
       : childrenOf(parent.parent_id);
     const parentIdx = grandparentChildren.findIndex(n => n.id === parent.id);
     const newParentId = parent.parent_id;
-    const newPosition = parentIdx + 1;
+    const newPosition = positionBetween(parent.position, grandparentChildren[parentIdx + 1]?.position);
 
     set(s => ({ pendingOperations: s.pendingOperations + 1 }));
     try {
@@ -1757,24 +1742,15 @@ export const useOutlineStore = create<OutlineState>((set, get) => ({
       if (asChild) {
         // Drop as first child of target
         newParentId = targetId;
-        newPosition = 0;
-        // Shift existing children down
-        const existingChildren = childrenOf(targetId);
-        for (const child of existingChildren) {
-          await api.moveNode(child.id, newParentId, child.position + 1);
-        }
+        const existingChildren = childrenOf(targetId).filter(n => n.id !== nodeIdToDrop);
+        newPosition = positionBetween(undefined, existingChildren[0]?.position);
       } else {
         // Drop as sibling after target
         newParentId = targetNode.parent_id;
-        const siblings = newParentId === null ? rootNodes() : childrenOf(newParentId);
+        const siblings = (newParentId === null ? rootNodes() : childrenOf(newParentId))
+          .filter(n => n.id !== nodeIdToDrop);
         const targetIdx = siblings.findIndex(n => n.id === targetId);
-        newPosition = targetIdx + 1;
-        // Shift siblings after insertion point
-        for (let i = targetIdx + 1; i < siblings.length; i++) {
-          if (siblings[i].id !== nodeIdToDrop) {
-            await api.moveNode(siblings[i].id, newParentId, siblings[i].position + 1);
-          }
-        }
+        newPosition = positionBetween(targetNode.position, siblings[targetIdx + 1]?.position);
       }
 
       const state = await api.moveNode(nodeIdToDrop, newParentId, newPosition);
@@ -2299,7 +2275,8 @@ export const useOutlineStore = create<OutlineState>((set, get) => ({
         // Skip if new parent is also selected (would cause issues)
         if (selectedIds.has(newParent.id)) continue;
 
-        const newPosition = childrenOf(newParent.id).length;
+        const newParentChildren = childrenOf(newParent.id);
+        const newPosition = positionBetween(newParentChildren[newParentChildren.length - 1]?.position, undefined);
         await api.moveNode(node.id, newParent.id, newPosition);
 
         // Uncollapse new parent
@@ -2328,8 +2305,10 @@ export const useOutlineStore = create<OutlineState>((set, get) => ({
 
     set(s => ({ pendingOperations: s.pendingOperations + 1 }));
     try {
-      // Process nodes in reverse order to avoid index shifting issues
+      // Process nodes in reverse order, each placed just after its parent and
+      // before the nodes outdented from that parent already
       const reversed = [...selected].reverse();
+      const placedBefore = new Map<string, number>();
       for (const node of reversed) {
         if (!node.parent_id) continue; // Can't outdent root nodes
 
@@ -2341,7 +2320,11 @@ export const useOutlineStore = create<OutlineState>((set, get) => ({
           ? rootNodes()
           : childrenOf(parent.parent_id);
         const parentIdx = grandparentChildren.findIndex(n => n.id === parent.id);
-        const newPosition = parentIdx + 1;
+        const newPosition = positionBetween(
+          parent.position,
+          placedBefore.get(parent.id) ?? grandparentChildren[parentIdx + 1]?.position
+        );
+        placedBefore.set(parent.id, newPosition);
 
         await api.moveNode(node.id, parent.parent_id, newPosition);
       }
@@ -2382,8 +2365,11 @@ export const useOutlineStore = create<OutlineState>((set, get) => ({
         nodes.sort((a, b) => a.position - b.position);
 
         // Move each node to top, in reverse order to maintain relative order
+        const siblings = getSiblings(nodes[0].id);
+        let topPosition = siblings[0]?.position;
         for (let i = nodes.length - 1; i >= 0; i--) {
-          await api.moveNode(nodes[i].id, parentId, 0);
+          topPosition = positionBetween(undefined, topPosition);
+          await api.moveNode(nodes[i].id, parentId, topPosition);
         }
       }
 
@@ -2422,12 +2408,12 @@ export const useOutlineStore = create<OutlineState>((set, get) => ({
         // Sort nodes by their current position to maintain relative order
         nodes.sort((a, b) => a.position - b.position);
 
-        // Get total siblings count for this parent
+        // Move each node after the current last sibling
         const siblings = parentId === null ? rootNodes() : childrenOf(parentId);
-        let bottomPosition = siblings.length;
+        let bottomPosition = siblings[siblings.length - 1]?.position;
 
-        // Move each node to bottom
         for (const node of nodes) {
+          bottomPosition = positionBetween(bottomPosition, undefined);
           await api.moveNode(node.id, parentId, bottomPosition);
         }
       }
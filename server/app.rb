@@ -1,6 +1,7 @@
 # frozen_string_literal: true
 
 require 'sinatra/base'
+require 'cgi'
 require 'json'
 require 'securerandom'
 require 'time'
@@ -17,7 +18,8 @@ class OutlineServer < Sinatra::Base
     else
       set :outline_config, {
         data_dir: ENV.fetch('OUTLINE_DATA_DIR', File.expand_path('~/.outline')),
-        calendar_tokens: ENV.fetch('OUTLINE_CALENDAR_TOKENS', '').split(',').map(&:strip).reject(&:empty?)
+        calendar_tokens: ENV.fetch('OUTLINE_CALENDAR_TOKENS', '').split(',').map(&:strip).reject(&:empty?),
+        webhooks: {}
       }
     end
   end
@@ -35,6 +37,62 @@ class OutlineServer < Sinatra::Base
       calendar_tokens.include?(token)
     end
 
+    # Webhook config for a token, or nil. Each webhook maps the posted payload
+    # onto an item with {{field}} templates (dotted paths reach nested fields).
+    def webhook_for(token)
+      webhooks = settings.outline_config[:webhooks] || {}
+      webhooks[token.to_sym]
+    end
+
+    # Look up a dotted path (e.g. "sender.name" or "items.0.title") in a payload
+    def payload_value(payload, path)
+      path.split('.').reduce(payload) do |value, key|
+        case value
+        when Hash then value[key.to_sym] || value[key]
+        when Array then key.match?(/\A\d+\z/) ? value[key.to_i] : nil
+        end
+      end
+    end
+
+    # Fill {{field}} placeholders from the payload; missing fields become empty
+    def render_template(template, payload)
+      return nil if template.nil?
+
+      template.gsub(/\{\{\s*([\w.-]+)\s*\}\}/) do
+        value = payload_value(payload, Regexp.last_match(1))
+        value.is_a?(Hash) || value.is_a?(Array) ? value.to_json : value.to_s
+      end.strip
+    end
+
+    # Append a create op for a new child of parent_id (or a new top-level node)
+    # to this server's pending file, which the desktop app merges like any
+    # other device's. The node goes after the parent's existing children in
+    # the last saved state.
+    def append_node_op(doc_id, parent_id, content, note)
+      doc_dir = File.join(documents_dir, doc_id)
+      state_path = File.join(doc_dir, 'state.json')
+      json_response({ error: 'Document not found' }, status: 404) unless File.exist?(state_path)
+
+      nodes = JSON.parse(File.read(state_path), symbolize_names: true)[:nodes] || []
+      siblings = nodes.select { |node| node[:parent_id] == parent_id }
+      position = siblings.map { |node| node[:position].to_f }.max.to_f + 1.0
+      now = Time.now.utc
+      id = SecureRandom.uuid
+
+      ops = [{ op: 'create', id: id, parent_id: parent_id, position: position,
+               content: CGI.escapeHTML(content), node_type: 'bullet', updated_at: now.iso8601(3) }]
+      unless note.nil? || note.empty?
+        # Updates only apply when newer than the node, so stamp the note after
+        # the create (timestamps have millisecond precision)
+        ops << { op: 'update', id: id, changes: { note: note }, updated_at: (now + 0.001).iso8601(3) }
+      end
+
+      File.open(File.join(doc_dir, 'pending.outline-server.jsonl'), 'a') do |f|
+        ops.each { |op| f.puts(op.merge(device: 'outline-server').to_json) }
+      end
+      id
+    end
+
     def inbox_path
       File.join(data_dir, 'inbox.jsonl')
     end
@@ -100,6 +158,20 @@ class OutlineServer < Sinatra::Base
     File.read(feed_path)
   end
 
+  # Read-only share links published by the desktop app - token-based auth
+  # (no basic auth). Revoking a share deletes its bundle.
+  # URLs: /share/{token} (page) and /share/{token}/data.json
+  get %r{/share/([a-f0-9]{32})(/data\.json)?} do |token, json|
+    share_dir = File.join(data_dir, 'shares', token)
+    file = File.join(share_dir, json ? 'data.json' : 'index.html')
+    halt 404, 'Not Found' unless File.exist?(file)
+
+    content_type json ? :json : 'text/html; charset=utf-8'
+    cache_control :private, :no_cache
+    headers['X-Robots-Tag'] = 'noindex'
+    File.read(file)
+  end
+
   # Static state.json for read-only viewer (protected by nginx basic auth)
   get '/outline/data/:doc_id/state.json' do
     doc_id = params[:doc_id]
@@ -207,6 +279,51 @@ class OutlineServer < Sinatra::Base
     json_response({ success: true, id: entry[:id] }, status: 201)
   end
 
+  # Generic webhook intake for Zapier, IFTTT and the like - token-based auth
+  # (no basic auth). The token's webhook config maps the JSON (or form)
+  # payload onto an item, which goes to the inbox or straight under a node.
+  # URL: /hook/{token}
+  post '/hook/:token' do
+    webhook = webhook_for(params[:token])
+    halt 404, 'Not Found' if webhook.nil?
+
+    request.body.rewind
+    body = request.body.read
+    payload =
+      if request.media_type == 'application/json' || body.lstrip.start_with?('{')
+        begin
+          JSON.parse(body, symbolize_names: true)
+        rescue JSON::ParserError
+          json_response({ error: 'Invalid JSON' }, status: 400)
+        end
+      else
+        params.reject { |key, _| key == 'token' }.transform_keys(&:to_sym)
+      end
+    json_response({ error: 'Expected a JSON object' }, status: 400) unless payload.is_a?(Hash)
+
+    content = render_template(webhook[:content] || '{{content}}', payload)
+    if content.nil? || content.empty?
+      json_response({ error: 'Content is required' }, status: 400)
+    end
+    note = render_template(webhook[:note], payload)
+    note = nil if note&.empty?
+
+    if webhook[:document_id]
+      unless webhook[:document_id].match?(/\A[a-f0-9-]{36}\z/i)
+        json_response({ error: 'Webhook has an invalid document_id' }, status: 500)
+      end
+      id = append_node_op(webhook[:document_id], webhook[:parent_id], content, note)
+      json_response({ success: true, id: id, document_id: webhook[:document_id] }, status: 201)
+    end
+
+    entry = build_inbox_entry(content: content, note: note, source: webhook[:source] || 'webhook')
+    File.open(inbox_path, 'a') do |f|
+      f.puts(entry.to_json)
+    end
+
+    json_response({ success: true, id: entry[:id] }, status: 201)
+  end
+
   # Get inbox items (for desktop to poll, or viewer to display)
   # Returns items grouped by capture_date if ?grouped=true
   get '/outline/api/inbox' do